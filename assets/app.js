@@ -6,9 +6,23 @@ async function search() {
   list.innerHTML = "";
 
   if (box.value == "") {
+    await showRecentPages(list);
     return;
   }
 
+  const maxResults = parseInt(
+    document.getElementById("search-form").dataset.maxResults || "10",
+    10
+  );
+
+  findPages(box.value).slice(0, maxResults).forEach(function (result) {
+    list.appendChild(searchResultItem(result));
+  });
+}
+
+function findPages(query) {
+  if (searchEngine() == "simple") return findPagesSimply(query);
+
   config = {
     fields: {
       title: {
@@ -17,24 +31,141 @@ async function search() {
       body: {
         boost: 1,
       },
+      keywords: {
+        boost: 5,
+      },
     },
     bool: "OR",
     expand: true,
   };
 
-  INDEX.search(box.value, config).forEach(function (result) {
+  return INDEX.search(query, config);
+}
+
+// Sites built with `search.engine: simple` list their pages instead of an
+// elasticlunr index. A page matches when it contains every word of the
+// query, and ranks higher when the words are in its title or headings.
+function findPagesSimply(query) {
+  const words = query.toLowerCase().split(/\s+/).filter(function (word) {
+    return word != "";
+  });
+
+  return INDEX.map(function (page) {
+    const title = page.title.toLowerCase();
+    const headings = page.headings.join(" ").toLowerCase();
+    const body = page.body.toLowerCase();
+    let score = 0;
+
+    for (const word of words) {
+      if (title.startsWith(word)) score += 8;
+      else if (title.includes(word)) score += 4;
+      else if (headings.includes(word)) score += 2;
+      else if (body.includes(word)) score += 1;
+      else return null;
+    }
+
+    return { ref: page.uri, score: score, doc: page };
+  })
+    .filter(function (result) {
+      return result && result.score > 0;
+    })
+    .sort(function (a, b) {
+      return b.score - a.score;
+    });
+}
+
+function searchEngine() {
+  return document.getElementById("search-form").dataset.searchEngine;
+}
+
+function searchResultItem(result) {
+  const listItem = document.createElement("li");
+  listItem.className = "search-result-item";
+
+  const anchor = document.createElement("a");
+  anchor.href = pageHref(result.doc.uri);
+
+  const preview = document.createElement("p");
+  preview.classList.add("search-result-item-preview");
+  preview.innerHTML = searchPreview(result.doc.preview);
+  preview.innerHTML = preview.innerText;
+
+  anchor.appendChild(document.createTextNode(result.doc.title));
+  anchor.appendChild(preview);
+  listItem.appendChild(anchor);
+
+  return listItem;
+}
+
+// The search page lists every result for the `q` of its address, like
+// /search?q=webhooks
+async function initSearchPage() {
+  const list = document.getElementById("search-page-results");
+  if (!list) return;
+
+  const query = new URLSearchParams(document.location.search).get("q") || "";
+  const summary = document.getElementById("search-page-summary");
+  document.getElementById("search-box").value = query;
+
+  if (query.trim() == "") {
+    summary.innerText = "Type in the search box to find pages.";
+    return;
+  }
+
+  await loadSearchIndex();
+  const results = findPages(query);
+
+  const matches =
+    results.length == 0
+      ? "No pages match"
+      : results.length == 1
+      ? "1 page matches"
+      : results.length + " pages match";
+  summary.innerText = matches + ' "' + query + '".';
+  results.forEach(function (result) {
+    list.appendChild(searchResultItem(result));
+  });
+}
+
+// Sites built with relative links list pages relative to their root
+function pageHref(uri) {
+  return uri.startsWith("/") ? uri : BASE_PATH + uri;
+}
+
+// Data files change with every build. Asking for the ones of the build
+// this page belongs to keeps a cached copy from an older build out.
+function buildFile(name) {
+  return BASE_PATH + name + "?ts=" + DOCGEN_TIMESTAMP;
+}
+
+// Shown while the search box is empty
+var RECENT_PAGES;
+
+async function showRecentPages(list) {
+  if (!RECENT_PAGES) {
+    const response = await fetch(buildFile("recent.json"));
+    RECENT_PAGES = response.ok ? await response.json() : [];
+  }
+
+  // The box may have been cleared or typed into while loading
+  if (document.activeElement !== document.getElementById("search-box")) return;
+  if (document.getElementById("search-box").value != "") return;
+
+  list.innerHTML = "";
+
+  RECENT_PAGES.forEach(function (page) {
     const listItem = document.createElement("li");
     listItem.className = "search-result-item";
 
     const anchor = document.createElement("a");
-    anchor.href = result.doc.uri;
+    anchor.href = pageHref(page.uri);
 
     const preview = document.createElement("p");
     preview.classList.add("search-result-item-preview");
-    preview.innerHTML = searchPreview(result.doc.preview);
-    preview.innerHTML = preview.innerText;
+    preview.innerText =
+      "Recently updated on " + new Date(page.date).toLocaleDateString();
 
-    anchor.appendChild(document.createTextNode(result.doc.title));
+    anchor.appendChild(document.createTextNode(page.title));
     anchor.appendChild(preview);
     listItem.appendChild(anchor);
 
@@ -48,14 +179,15 @@ function searchPreview(body) {
       .replace(/=+/g, "")
       .replace(/#+/g, "")
       .replace(/\*+/g, "")
-      .replace(/_+/g, "") + "..."
+      .replace(/_+/g, "")
   );
 }
 
 function disableScrollifMenuOpen() {
   var checkbox = document.getElementById("menu-toggle-switch");
 
-  if (checkbox.checked) {
+  // Pages with `layout: full` have no menu
+  if (checkbox && checkbox.checked) {
     document.body.style.overflow = "hidden";
   } else {
     document.body.style.overflow = "auto";
@@ -78,6 +210,23 @@ document
   .getElementById("light-dark-mode-switch")
   .addEventListener("click", toggleColor);
 
+// Task items that readers can tick remember their state in this browser
+function initChecklists() {
+  document
+    .querySelectorAll(".checklist input[data-task-id]:not([disabled])")
+    .forEach(function (checkbox) {
+      var key = "docgen-task:" + checkbox.dataset.taskId;
+      var saved = localStorage.getItem(key);
+      if (saved !== null) checkbox.checked = saved === "true";
+
+      checkbox.addEventListener("change", function () {
+        localStorage.setItem(key, checkbox.checked);
+      });
+    });
+}
+
+initChecklists();
+
 function initMermaid() {
   const mermaidScript = document.getElementById("mermaid.min.js");
   if (!mermaidScript) return;
@@ -99,17 +248,18 @@ var INDEX;
 async function loadSearchIndex() {
   if (INDEX) return;
 
-  const response = await fetch(BASE_PATH + "search_index.json");
+  const response = await fetch(buildFile("search_index.json"));
 
   if (!response.ok) {
     throw new Error("HTTP error " + response.status);
   }
   const json = await response.json();
 
-  INDEX = elasticlunr.Index.load(json);
+  INDEX = searchEngine() == "simple" ? json : elasticlunr.Index.load(json);
 }
 
 document.getElementById("search-box").oninput = search;
+document.getElementById("search-box").onfocus = search;
 // Setup keyboard shortcuts
 document.onkeydown = function (e) {
   var searchResults = document.getElementById("search-results");
@@ -188,3 +338,4 @@ document.onclick = (ev) => {
 disableScrollifMenuOpen();
 setColor();
 initMermaid();
+initSearchPage();
@@ -0,0 +1,119 @@
+//! Builds a synthetic site, measuring how long the build takes and how much
+//! memory it holds at its peak.
+//!
+//! Run with `cargo bench --bench build`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use docgen::{BuildCommand, ColorMode, Config};
+
+const PAGES: usize = 1000;
+
+/// Keeps track of the bytes allocated at any time, and the most there were
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+fn record_alloc(size: usize) {
+    let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// A project with `pages` pages spread over ten directories, each with a
+/// README so that every page lists all of them in its navigation
+fn synthetic_project(pages: usize) -> PathBuf {
+    let root = std::env::temp_dir().join("docgen-bench");
+    if root.exists() {
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("docgen.yaml"), "---\ntitle: Benchmark\n").unwrap();
+    fs::write(root.join("docs").join("README.md"), "# Benchmark\n").unwrap();
+
+    for i in 0..pages {
+        let dir = root.join("docs").join(format!("section-{}", i % 10));
+        if !dir.exists() {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("README.md"), format!("# Section {}\n", i % 10)).unwrap();
+        }
+
+        let mut content = format!("# Page {}\n\n", i);
+        for paragraph in 0..10 {
+            content.push_str(&format!(
+                "## Part {}\n\nSome *text* with a [link](/section-{}/page-{}) and `code`.\n\n",
+                paragraph,
+                (i + 1) % 10,
+                (i + 1) % pages
+            ));
+        }
+        fs::write(dir.join(format!("page-{}.md", i)), content).unwrap();
+    }
+
+    root
+}
+
+fn build(project: &Path) {
+    let mut config = Config::load(project, false).unwrap();
+    config.set_color_mode(ColorMode::Never);
+
+    BuildCommand::run(config).unwrap();
+}
+
+fn bench_build(c: &mut Criterion) {
+    let project = synthetic_project(PAGES);
+
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+    build(&project);
+
+    println!(
+        "Building {} pages: peak of {:.1} MiB allocated, {} allocations",
+        PAGES,
+        (PEAK.load(Ordering::Relaxed) - baseline) as f64 / (1024.0 * 1024.0),
+        ALLOCATIONS.load(Ordering::Relaxed)
+    );
+
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+    group.bench_function(format!("{} pages", PAGES), |b| b.iter(|| build(&project)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_build);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use docgen::markdown::parser::{MarkdownParser, ParseOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let mut options = ParseOptions::default();
+        options.emit_ast = true;
+
+        MarkdownParser::new(Some(options)).parse(input);
+    }
+});
@@ -1,6 +1,19 @@
 use port_scanner::*;
 use std::net::SocketAddr;
 
+use crate::{Error, Result};
+
+/// How many free ports to try before giving up on finding one for the
+/// livereload server that the preview server isn't using
+const PORT_ATTEMPTS: usize = 10;
+
+/// Where the preview and livereload servers listen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServerAddrs {
+    pub preview: SocketAddr,
+    pub livereload: SocketAddr,
+}
+
 pub fn get_safe_addr(ip: &str, port: u16) -> Option<SocketAddr> {
     if local_port_available(port) {
         format!("{}:{}", ip, port).parse::<SocketAddr>().ok()
@@ -12,3 +25,125 @@ pub fn get_safe_addr(ip: &str, port: u16) -> Option<SocketAddr> {
         None
     }
 }
+
+/// Picks the addresses of the preview and livereload servers. The preview
+/// server keeps the port it was given as long as it's free. The livereload
+/// server moves to another free port when its own is taken, including when
+/// the preview server was given the same one.
+///
+/// Returns the addresses, and why a server isn't on the port it was given.
+pub fn resolve_server_addrs(
+    ip: &str,
+    preview_port: u16,
+    livereload_port: u16,
+) -> Result<(ServerAddrs, Vec<String>)> {
+    resolve_with(
+        ip,
+        preview_port,
+        livereload_port,
+        local_port_available,
+        request_open_port,
+    )
+}
+
+fn resolve_with(
+    ip: &str,
+    preview_port: u16,
+    livereload_port: u16,
+    available: impl Fn(u16) -> bool,
+    mut open_port: impl FnMut() -> Option<u16>,
+) -> Result<(ServerAddrs, Vec<String>)> {
+    let mut notes = vec![];
+
+    let preview = if available(preview_port) {
+        preview_port
+    } else {
+        let port = open_port()
+            .ok_or_else(|| Error::new("Could not find a free port for the preview server"))?;
+        notes.push(format!(
+            "Port {} is in use, serving the site on port {} instead",
+            preview_port, port
+        ));
+        port
+    };
+
+    let livereload = if livereload_port != preview && available(livereload_port) {
+        livereload_port
+    } else {
+        let port = (0..PORT_ATTEMPTS)
+            .filter_map(|_| open_port())
+            .find(|port| *port != preview)
+            .ok_or_else(|| Error::new("Could not find a free port for the livereload server"))?;
+        if livereload_port == preview {
+            notes.push(format!(
+                "Port {} is used by the preview server, live reload listens on port {} instead",
+                livereload_port, port
+            ));
+        } else {
+            notes.push(format!(
+                "Port {} is in use, live reload listens on port {} instead",
+                livereload_port, port
+            ));
+        }
+        port
+    };
+
+    let addr = |port: u16| {
+        format!("{}:{}", ip, port)
+            .parse::<SocketAddr>()
+            .map_err(|_| Error::new(format!("Invalid address {}:{}", ip, port)))
+    };
+
+    Ok((
+        ServerAddrs {
+            preview: addr(preview)?,
+            livereload: addr(livereload)?,
+        },
+        notes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_free_ports() {
+        let (addrs, notes) = resolve_with("127.0.0.1", 4001, 35729, |_| true, || None).unwrap();
+
+        assert_eq!(addrs.preview, "127.0.0.1:4001".parse().unwrap());
+        assert_eq!(addrs.livereload, "127.0.0.1:35729".parse().unwrap());
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn livereload_moves_off_the_preview_port() {
+        let mut free = vec![40000, 35729].into_iter();
+        let (addrs, notes) =
+            resolve_with("127.0.0.1", 35729, 35729, |_| true, || free.next()).unwrap();
+
+        assert_eq!(addrs.preview.port(), 35729);
+        assert_eq!(addrs.livereload.port(), 40000);
+        assert_eq!(
+            notes,
+            vec!["Port 35729 is used by the preview server, live reload listens on port 40000 instead"]
+        );
+    }
+
+    #[test]
+    fn servers_move_off_ports_in_use() {
+        let mut free = vec![40000, 40000, 40001].into_iter();
+        let (addrs, notes) =
+            resolve_with("127.0.0.1", 4001, 35729, |_| false, || free.next()).unwrap();
+
+        assert_eq!(addrs.preview.port(), 40000);
+        assert_eq!(addrs.livereload.port(), 40001);
+        assert_eq!(
+            notes,
+            vec![
+                "Port 4001 is in use, serving the site on port 40000 instead",
+                "Port 35729 is in use, live reload listens on port 40001 instead",
+            ]
+        );
+    }
+}
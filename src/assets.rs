@@ -0,0 +1,190 @@
+//! The assets built into Docgen. `build.rs` bundles them into `dist/`,
+//! together with `assets_map.json`, which maps the name of every asset to
+//! the file it was bundled as, with a hash of its contents in its name.
+
+use std::collections::HashMap;
+
+use include_dir::{include_dir, Dir};
+
+use crate::{Error, Result};
+
+pub static ASSETS: Dir = include_dir!("$CARGO_MANIFEST_DIR/dist/");
+
+static MAP_FILE: &str = "assets_map.json";
+
+lazy_static! {
+    /// The asset map, or why the bundle can't be used
+    static ref MANIFEST: std::result::Result<HashMap<String, String>, String> =
+        load(&ASSETS).map_err(|e| e.to_string());
+
+    /// Asset names mapped to the files they were bundled as. Empty if the
+    /// bundle is broken, which `check` reports.
+    pub static ref ASSETS_MAP: HashMap<String, String> =
+        MANIFEST.as_ref().cloned().unwrap_or_default();
+}
+
+/// Assets that go into every site
+fn required() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut names = vec![
+        "app.js",
+        "dark.css",
+        "elasticlunr.min.js",
+        "light.css",
+        "livereload.min.js",
+        "mermaid.min.js",
+        "normalize.css",
+        "style.css",
+    ];
+
+    #[cfg(feature = "katex")]
+    names.push("katex.min.css");
+
+    names
+}
+
+/// Checks that every asset the site needs is bundled into Docgen. Commands
+/// run this before doing any work, so a broken bundle fails with an error
+/// instead of a panic halfway through a build.
+pub fn check() -> Result<()> {
+    MANIFEST
+        .as_ref()
+        .map(|_| ())
+        .map_err(|e| Error::new(e.clone()))
+}
+
+/// The name `filename` was bundled as
+pub fn hashed_name(filename: &str) -> Result<&'static str> {
+    let map = MANIFEST.as_ref().map_err(|e| Error::new(e.clone()))?;
+
+    map.get(filename)
+        .map(String::as_str)
+        .ok_or_else(|| Error::new(format!("{} is not bundled into Docgen", filename)))
+}
+
+/// The contents of a bundled file, by the name it was bundled as
+pub fn contents(hashed_name: &str) -> Result<&'static [u8]> {
+    ASSETS
+        .get_file(hashed_name)
+        .map(|file| file.contents())
+        .ok_or_else(|| Error::new(format!("{} is not bundled into Docgen", hashed_name)))
+}
+
+/// Reads the asset map of `bundle`, and checks that every required asset
+/// is in both the map and the bundle
+fn load(bundle: &Dir) -> Result<HashMap<String, String>> {
+    let json = bundle
+        .get_file(MAP_FILE)
+        .and_then(|file| file.contents_utf8())
+        .ok_or_else(|| {
+            Error::new(
+                "Embedded asset manifest missing — Docgen was built without its dist/ bundle",
+            )
+        })?;
+
+    let map = serde_json::from_str::<HashMap<String, String>>(json).map_err(|e| {
+        Error::new(format!(
+            "Embedded asset manifest is corrupt — Docgen was built with a broken dist/ bundle: {}",
+            e
+        ))
+    })?;
+
+    let mut missing = required()
+        .into_iter()
+        .filter(|name| match map.get(*name) {
+            Some(hashed) => bundle.get_file(hashed).is_none(),
+            None => true,
+        })
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    #[cfg(feature = "katex")]
+    if bundle.get_dir("fonts").is_none() {
+        missing.push(String::from("fonts/"));
+    }
+
+    if missing.is_empty() {
+        Ok(map)
+    } else {
+        missing.sort();
+
+        Err(Error::new(format!(
+            "Docgen was built with an incomplete dist/ bundle. These assets are missing:\n\n\t{}",
+            missing.join("\n\t")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use include_dir::{DirEntry, File};
+
+    #[test]
+    fn bundled_assets_are_complete() {
+        check().unwrap();
+
+        for name in required() {
+            contents(hashed_name(name).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn missing_manifest() {
+        let bundle = Dir::new("", &[]);
+
+        let error = load(&bundle).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Embedded asset manifest missing — Docgen was built without its dist/ bundle"
+        );
+    }
+
+    #[test]
+    fn corrupt_manifest() {
+        let entries = [DirEntry::File(File::new(MAP_FILE, b"{\"app.js\": "))];
+        let bundle = Dir::new("", &entries);
+
+        let error = load(&bundle).unwrap_err();
+
+        assert!(
+            error.to_string().starts_with(
+                "Embedded asset manifest is corrupt — Docgen was built with a broken dist/ bundle"
+            ),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn lists_missing_assets() {
+        let map = required()
+            .into_iter()
+            .filter(|name| *name != "style.css")
+            .map(|name| format!("\"{}\": \"{}\"", name, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let map = format!("{{{}}}", map);
+
+        // app.js is in the map, but not in the bundle
+        let mut entries = vec![DirEntry::File(File::new(MAP_FILE, map.as_bytes()))];
+        for name in required() {
+            if name != "app.js" {
+                entries.push(DirEntry::File(File::new(name, b"")));
+            }
+        }
+        #[cfg(feature = "katex")]
+        entries.push(DirEntry::Dir(Dir::new("fonts", &[])));
+        let bundle = Dir::new("", &entries);
+
+        let error = load(&bundle).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Docgen was built with an incomplete dist/ bundle. These assets are missing:\n\n\
+             \tapp.js\n\
+             \tstyle.css"
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use crate::markdown::extensions::link_rewriter::{Link, UrlType};
-use crate::preview_server::resolve_file;
+use crate::preview_server::candidates;
 use crate::site::{Site, SiteBackend};
+use crate::warning::Warning;
 use crate::{Document, Error, Result};
 
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 pub fn check<B: SiteBackend>(root: &Vec<Document>, site: &Site<B>) -> Result<()> {
@@ -15,14 +17,25 @@ pub fn check<B: SiteBackend>(root: &Vec<Document>, site: &Site<B>) -> Result<()>
     }
 }
 
-fn find_broken_links<B: SiteBackend>(docs: &Vec<Document>, site: &Site<B>) -> Vec<(PathBuf, Link)> {
+pub fn find_broken_links<B: SiteBackend>(
+    docs: &Vec<Document>,
+    site: &Site<B>,
+) -> Vec<(PathBuf, Link)> {
+    broken_links_in(docs, &site.backend)
+}
+
+/// Like `find_broken_links`, against the files written to the backend so
+/// far, for while the site is still being built
+pub fn broken_links_in<B: SiteBackend>(docs: &[Document], backend: &B) -> Vec<(PathBuf, Link)> {
+    let targets = Targets::new(backend);
+
     let mut broken_links = vec![];
     for doc in docs {
         for link in doc.outgoing_links() {
             match &link.url {
                 UrlType::Remote(_) => {}
                 UrlType::Local(path) => {
-                    if !matches_a_target(path, site) {
+                    if targets.resolve(path) != Resolved::Found {
                         broken_links.push((doc.original_path().to_owned(), link.clone()))
                     }
                 }
@@ -32,26 +45,137 @@ fn find_broken_links<B: SiteBackend>(docs: &Vec<Document>, site: &Site<B>) -> Ve
     broken_links
 }
 
-fn matches_a_target<B: SiteBackend>(path: &Path, site: &Site<B>) -> bool {
-    resolve_file(path, site).is_some()
+/// Warnings for the broken links that would work if case didn't matter.
+/// They do on case insensitive filesystems, like the ones of macOS and
+/// Windows, but not once the site is deployed to most servers.
+pub fn case_mismatches<B: SiteBackend>(docs: &Vec<Document>, site: &Site<B>) -> Vec<Warning> {
+    let targets = Targets::new(&site.backend);
+
+    let mut warnings = vec![];
+    for doc in docs {
+        for link in doc.outgoing_links() {
+            if let UrlType::Local(path) = &link.url {
+                if let Resolved::IgnoringCase(file) = targets.resolve(path) {
+                    warnings.push(Warning::new(
+                        doc.original_path(),
+                        format!(
+                            "Links to {}, which only matches {}{} when ignoring case. \
+                             Paths are case sensitive on most servers",
+                            path.display(),
+                            site.config.base_path(),
+                            file.to_string_lossy().replace('\\', "/")
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// The broken links as warnings about the pages they are on
+pub fn as_warnings(broken_links: &[(PathBuf, Link)]) -> Vec<Warning> {
+    broken_links
+        .iter()
+        .filter_map(|(page, link)| match &link.url {
+            UrlType::Local(path) => Some(Warning::new(
+                page,
+                format!("Links to {}, which is not in the site", path.display()),
+            )),
+            UrlType::Remote(_) => None,
+        })
+        .collect()
+}
+
+/// The pages with links that are broken now, but were not in a previous run.
+/// Used to point out which pages were affected by deleting another page.
+pub fn newly_broken(previous: &[(PathBuf, Link)], current: &[(PathBuf, Link)]) -> Vec<PathBuf> {
+    let mut pages = current
+        .iter()
+        .filter(|broken| !previous.contains(broken))
+        .map(|(page, _)| page.to_owned())
+        .collect::<Vec<_>>();
+
+    pages.sort();
+    pages.dedup();
+    pages
+}
+
+#[derive(Debug, PartialEq)]
+enum Resolved {
+    Found,
+    /// Only a file whose path differs in case, at this path in the site
+    IgnoringCase(PathBuf),
+    Missing,
+}
+
+/// The files a build wrote, for links to be resolved against. These are the
+/// paths the backend recorded, rather than what is on disk, so that links
+/// resolve the same way on every filesystem.
+struct Targets<'a> {
+    base_path: &'a str,
+    files: HashSet<PathBuf>,
+    /// Every file by its path in lowercase
+    lowercase: HashMap<String, PathBuf>,
+}
+
+impl<'a> Targets<'a> {
+    fn new<B: SiteBackend>(backend: &'a B) -> Self {
+        let files = backend.output_paths();
+        let lowercase = files
+            .iter()
+            .map(|file| (lowercase_key(file), file.clone()))
+            .collect();
+
+        Targets {
+            base_path: backend.config().base_path(),
+            files: files.into_iter().collect(),
+            lowercase,
+        }
+    }
+
+    fn resolve(&self, path: &Path) -> Resolved {
+        let candidates = candidates(path, self.base_path);
+
+        if candidates.iter().any(|file| self.files.contains(file)) {
+            return Resolved::Found;
+        }
+
+        candidates
+            .iter()
+            .find_map(|file| self.lowercase.get(&lowercase_key(file)))
+            .map(|file| Resolved::IgnoringCase(file.clone()))
+            .unwrap_or(Resolved::Missing)
+    }
+}
+
+/// The path in lowercase, with the same separator on every platform
+fn lowercase_key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect::<Vec<_>>()
+        .join("/")
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::config::Config;
+    use crate::markdown::parser::ParseOptions;
+    use crate::test_helpers::{page_with_options, project};
     use crate::Document;
+    use serde_yaml::Value;
     use std::collections::BTreeMap;
     use std::time::SystemTime;
 
     fn page(path: &str, name: &str, content: &str) -> Document {
         let mut frontmatter = BTreeMap::new();
-        frontmatter.insert("title".to_string(), name.to_string());
+        frontmatter.insert("title".to_string(), Value::from(name));
 
         Document::new(
             Path::new(path),
             content.to_string(),
-            frontmatter,
+            Some(frontmatter),
             "/",
             SystemTime::now(),
         )
@@ -59,12 +183,12 @@ mod test {
 
     fn page_with_base_path(path: &str, name: &str, content: &str, base_path: &str) -> Document {
         let mut frontmatter = BTreeMap::new();
-        frontmatter.insert("title".to_string(), name.to_string());
+        frontmatter.insert("title".to_string(), Value::from(name));
 
         Document::new(
             Path::new(path),
             content.to_string(),
-            frontmatter,
+            Some(frontmatter),
             base_path,
             SystemTime::now(),
         )
@@ -157,6 +281,32 @@ mod test {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn finds_pages_named_index_next_to_readmes() {
+        let config = config(None);
+
+        let root = vec![
+            page(
+                "README.md",
+                "Getting Started",
+                "[root](/index)\n[nested](/nested/index/)\n[readme](/nested/)",
+            ),
+            page("index.md", "Index", "No links!"),
+            page("nested/README.md", "Nested", "No links!"),
+            page("nested/index.md", "Nested Index", "No links!"),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &root).unwrap();
+        let result = check(&root, &site);
+
+        println!("{:?}", result);
+
+        assert!(result.is_ok());
+        assert_eq!(root[1].uri_path, "/index/");
+        assert_eq!(root[3].uri_path, "/nested/index/");
+    }
+
     #[test]
     fn honors_a_different_base_path() {
         let config = config(Some(&indoc! {"
@@ -206,4 +356,207 @@ mod test {
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn links_to_tables_and_figures_pass() {
+        let config = config(None);
+
+        let root = vec![
+            page(
+                "README.md",
+                "Getting Started",
+                "See [the results](/results#runs)",
+            ),
+            page(
+                "results.md",
+                "Results",
+                "Table: Benchmark runs {#runs}\n| Runs |\n|------|\n| 10   |\n",
+            ),
+        ];
+        assert!(root[1]
+            .markdown
+            .html
+            .contains("<figure id=\"runs\" class=\"table\">"));
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &root).unwrap();
+
+        assert!(check(&root, &site).is_ok());
+    }
+
+    #[test]
+    fn clean_urls_resolve_with_and_without_trailing_slashes() {
+        let config = config(Some("---\ntitle: My project\nclean_urls: true\n"));
+
+        let mut options = ParseOptions::default();
+        options.clean_urls = true;
+
+        let root = vec![
+            page_with_options(
+                "README.md",
+                "[a](/other)\n[b](/other/)\n[c](/nested/)\n[d](/nested/other/#heading)",
+                &[("title", "Getting Started")],
+                options.clone(),
+            ),
+            page_with_options(
+                "other.md",
+                "No links!",
+                &[("title", "Other")],
+                options.clone(),
+            ),
+            page_with_options(
+                "nested/README.md",
+                "Content",
+                &[("title", "Nested")],
+                options.clone(),
+            ),
+            page_with_options(
+                "nested/other.md",
+                "# Heading",
+                &[("title", "Nested Child")],
+                options,
+            ),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &root).unwrap();
+        let result = check(&root, &site);
+
+        println!("{:?}", result);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn clean_urls_do_not_accept_html_extensions() {
+        let config = config(Some("---\ntitle: My project\nclean_urls: true\n"));
+
+        let mut options = ParseOptions::default();
+        options.clean_urls = true;
+
+        let root = vec![
+            page_with_options(
+                "README.md",
+                "[a](/other.html)",
+                &[("title", "Getting Started")],
+                options.clone(),
+            ),
+            page_with_options("other.md", "No links!", &[("title", "Other")], options),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &root).unwrap();
+        let result = check(&root, &site);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reports_pages_whose_links_broke_since_the_previous_run() {
+        let config = config(None);
+
+        let before = vec![
+            page(
+                "README.md",
+                "Getting Started",
+                "[Other](/other) [Gone](/gone)",
+            ),
+            page("guide.md", "Guide", "[Other](/other)"),
+            page("other.md", "Other", "No links!"),
+        ];
+        let after = vec![
+            page(
+                "README.md",
+                "Getting Started",
+                "[Other](/other) [Gone](/gone)",
+            ),
+            page("guide.md", "Guide", "[Other](/other)"),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &before).unwrap();
+        let previous = find_broken_links(&before, &site);
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &after).unwrap();
+        let current = find_broken_links(&after, &site);
+
+        assert_eq!(
+            newly_broken(&previous, &current),
+            vec![PathBuf::from("README.md"), PathBuf::from("guide.md")]
+        );
+    }
+
+    #[test]
+    fn query_strings_are_not_part_of_the_path() {
+        let root = project(
+            "query-strings",
+            &[("docs/_include/files/cli-v2.tgz", "CLI")],
+        );
+        let config = Config::from_yaml_str(&root, "---\ntitle: My project\n", false).unwrap();
+
+        let docs = vec![
+            page(
+                "README.md",
+                "Getting Started",
+                "[CLI](/files/cli-v2.tgz?version=2)\n[Other](/other?tab=linux#install)",
+            ),
+            page("other.md", "Other", "# Install"),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config, &docs).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(check(&docs, &site).is_ok());
+    }
+
+    #[test]
+    fn links_that_only_match_ignoring_case_are_broken() {
+        let root = project(
+            "case-mismatch",
+            &[("docs/_include/files/cli-v2.tgz", "CLI")],
+        );
+        let config = Config::from_yaml_str(&root, "---\ntitle: My project\n", false).unwrap();
+
+        let docs = vec![
+            page(
+                "README.md",
+                "Getting Started",
+                "[CLI](/Files/CLI-v2.tgz)\n[Other](/Other)",
+            ),
+            page("other.md", "Other", "No links!"),
+        ];
+
+        // Written to disk, which resolves either path on case insensitive
+        // filesystems
+        let mut site = Site::disk_backed(config.clone());
+        site.build(config, &docs).unwrap();
+        let broken = find_broken_links(&docs, &site);
+        let warnings = case_mismatches(&docs, &site);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            broken
+                .iter()
+                .map(|(_, link)| link.url.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                UrlType::Local(PathBuf::from("/Files/CLI-v2.tgz")),
+                UrlType::Local(PathBuf::from("/Other")),
+            ]
+        );
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| w.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Links to /Files/CLI-v2.tgz, which only matches /files/cli-v2.tgz when \
+                 ignoring case. Paths are case sensitive on most servers",
+                "Links to /Other, which only matches /other.html when ignoring case. \
+                 Paths are case sensitive on most servers",
+            ]
+        );
+    }
 }
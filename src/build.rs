@@ -1,23 +1,45 @@
+use std::sync::Arc;
 use std::time::Instant;
 
-use bunt::termcolor::{ColorChoice, StandardStream};
+use bunt::termcolor::StandardStream;
 
+use crate::build_lock::BuildLock;
 use crate::config::Config;
-use crate::site::{BuildMode, Site};
-use crate::Result;
+use crate::docs_finder::Sources;
+use crate::dry_run::DryRunReport;
+use crate::navigation::Navigation;
+use crate::post_processor::HtmlPostProcessor;
+use crate::site::{BuildMode, Site, SiteBackend};
+use crate::warning::Warning;
+use crate::{Document, Error, Result};
 
 pub struct BuildCommand {}
 
 impl BuildCommand {
     pub fn run(config: Config) -> Result<()> {
-        let mut stdout = if config.color_enabled() {
-            StandardStream::stdout(ColorChoice::Auto)
-        } else {
-            StandardStream::stdout(ColorChoice::Never)
-        };
+        Self::run_with_post_processors(config, vec![])
+    }
+
+    /// Builds the site like `run`, passing the HTML of every page through
+    /// the given processors before it is written.
+    pub fn run_with_post_processors(
+        config: Config,
+        post_processors: Vec<Arc<dyn HtmlPostProcessor>>,
+    ) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
 
-        let root = crate::docs_finder::find(&config);
         let mut site = Site::disk_backed(config.clone());
+        site.post_processors = post_processors;
+
+        // The lock is held until the build is done, so that another build of
+        // the same project doesn't write into the output directory at the
+        // same time
+        let (_lock, sources, root) = prepare(&mut stdout, &config, || {
+            crate::git_status::check(&config)?;
+            let lock = BuildLock::acquire(&config)?;
+            site.backend.create_out_dir()?;
+            Ok(lock)
+        })?;
 
         let target_dir = config.out_dir();
 
@@ -41,19 +63,179 @@ impl BuildCommand {
         let result = site.build(config.clone(), &root);
         let duration = start.elapsed();
 
-        if result.is_ok() {
+        if let Ok(build_warnings) = result {
             bunt::writeln!(stdout, "Site built in {$bold}{:?}{/$}\n", duration)?;
 
-            let dead_links_result = crate::broken_links_checker::check(&root, &site);
-            if dead_links_result.is_err() && config.allow_failed_checks() {
-                bunt::writeln!(stdout, "{$bold}{$yellow}WARNING{/$}{/$}")?;
-                bunt::writeln!(stdout, "{}", dead_links_result.unwrap_err())?;
-                Ok(())
-            } else {
-                dead_links_result
+            let mut warnings = all_warnings(&config, sources, &root, build_warnings, &site);
+            if let Err(e) = crate::cache::prune_unused(&config) {
+                let cache_dir = config.cache_dir();
+                warnings.push(Warning::new(
+                    cache_dir
+                        .strip_prefix(config.project_root())
+                        .unwrap_or(cache_dir),
+                    format!("Could not prune the cache: {}", e),
+                ));
             }
+            crate::warning::print(&mut stdout, &warnings)?;
+
+            check_strict(&config, &warnings)?;
+            check_links(&mut stdout, &config, &root, &site)
         } else {
-            result
+            result.map(|_| ())
+        }
+    }
+
+    /// Builds the site in memory, passing the HTML of every page through the
+    /// given processors like `run_with_post_processors`, and reports how it
+    /// differs from the one in the output directory, without writing
+    /// anything. With `show_diff`, also prints a diff of every modified text
+    /// file.
+    pub fn dry_run(
+        config: Config,
+        post_processors: Vec<Arc<dyn HtmlPostProcessor>>,
+        show_diff: bool,
+    ) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+
+        let ((), sources, root) = prepare(&mut stdout, &config, || Ok(()))?;
+
+        let target_dir = config.out_dir();
+
+        bunt::writeln!(stdout, "{$bold}{$blue}Docgen | Build (dry run){/$}{/$}")?;
+        bunt::writeln!(
+            stdout,
+            "Comparing with the site in {$bold}{}{/$}\n",
+            target_dir.display()
+        )?;
+
+        let mut site = Site::in_memory(config.clone());
+        site.post_processors = post_processors;
+        let build_warnings = site.build(config.clone(), &root)?;
+
+        let warnings = all_warnings(&config, sources, &root, build_warnings, &site);
+        crate::warning::print(&mut stdout, &warnings)?;
+
+        let report = DryRunReport::compare(&site.backend, &target_dir)?;
+        report.print(&mut stdout, &site.backend, target_dir, show_diff)?;
+
+        // The same checks as a real build, so that a dry run fails whenever
+        // the build would
+        check_strict(&config, &warnings)?;
+        check_links(&mut stdout, &config, &root, &site)
+    }
+}
+
+/// What a build and a dry run do before building: the checks they fail on
+/// early, and loading the pages. `before_reading` runs once the docs
+/// directory is known to exist, before any of it is read, and what it
+/// returns is handed back along with the pages.
+fn prepare<T>(
+    stdout: &mut StandardStream,
+    config: &Config,
+    before_reading: impl FnOnce() -> Result<T>,
+) -> Result<(T, Sources, Vec<Document>)> {
+    crate::assets::check()?;
+    crate::docs_finder::check_docs_dir(config)?;
+    let prepared = before_reading()?;
+
+    let sources = crate::docs_finder::load(config);
+    let root = crate::docs_finder::render(&sources, config);
+    if root.is_empty() {
+        crate::warning::print(stdout, &sources.skipped)?;
+        return Err(Error::new(crate::docs_finder::no_pages_message(config)));
+    }
+    crate::docs_finder::check_includes(&root)?;
+    Navigation::new(config).check_anchors(&root)?;
+
+    Ok((prepared, sources, root))
+}
+
+/// The warnings about the pages and the site built from them
+fn all_warnings<B: SiteBackend>(
+    config: &Config,
+    sources: Sources,
+    root: &Vec<Document>,
+    build_warnings: Vec<Warning>,
+    site: &Site<B>,
+) -> Vec<Warning> {
+    let navigation = Navigation::new(config).build_for(root);
+
+    let mut warnings = sources.skipped;
+    warnings.extend(crate::docs_finder::warnings(root, config));
+    warnings.extend(build_warnings);
+    warnings.extend(crate::orphans_checker::check(root, &navigation, site));
+    warnings.extend(crate::broken_links_checker::case_mismatches(root, site));
+    warnings
+}
+
+/// Fails the build in strict mode if there were any warnings
+fn check_strict(config: &Config, warnings: &[Warning]) -> Result<()> {
+    if config.strict() && !warnings.is_empty() {
+        return Err(Error::new(format!(
+            "Found {} warning(s) while building in strict mode",
+            warnings.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Fails the build on broken links, unless failed checks are allowed
+fn check_links<B: SiteBackend>(
+    stdout: &mut StandardStream,
+    config: &Config,
+    root: &Vec<Document>,
+    site: &Site<B>,
+) -> Result<()> {
+    match crate::broken_links_checker::check(root, site) {
+        Err(e) if config.allow_failed_checks() => {
+            bunt::writeln!(stdout, "{$bold}{$yellow}WARNING{/$}{/$}")?;
+            bunt::writeln!(stdout, "{}", e)?;
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn unwritable_out_dir_fails_before_reading_the_docs() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let project = std::env::temp_dir().join(format!("docgen-read-only-{}", std::process::id()));
+        let docs = project.join("docs");
+        let out_dir = project.join("site");
+        fs::create_dir_all(&docs).unwrap();
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::set_permissions(&out_dir, fs::Permissions::from_mode(0o555)).unwrap();
+
+        // Permissions don't stop root, so there's nothing to check then
+        if fs::write(out_dir.join("probe"), b"").is_ok() {
+            fs::remove_dir_all(&project).unwrap();
+            return;
         }
+
+        let config = Config::from_yaml_str(&project, "---\ntitle: Title", false).unwrap();
+        let result = BuildCommand::run(config);
+        fs::set_permissions(&out_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&project).unwrap();
+
+        // The docs directory is empty, so reading it would have failed with
+        // a message about finding no pages instead
+        let message = result.unwrap_err().to_string();
+        assert!(
+            message.starts_with(&format!(
+                "Could not write to the output directory {}",
+                out_dir.display()
+            )),
+            "{}",
+            message
+        );
+        assert!(message.contains("Permission denied"), "{}", message);
     }
 }
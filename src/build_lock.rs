@@ -0,0 +1,304 @@
+//! An advisory lock on the output directory of a project, so that two
+//! builds of the same checkout don't delete and write files under each
+//! other. The lock is a file in the project root with the PID of the build
+//! that holds it. Serving builds the site in memory, and doesn't take it.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::{Error, Result};
+
+pub static LOCK_FILE: &str = ".docgen-build.lock";
+
+/// How long to sleep between attempts while waiting for the lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Held for as long as a build writes to the output directory. Dropping it
+/// releases the lock.
+#[derive(Debug)]
+pub struct BuildLock {
+    path: PathBuf,
+}
+
+/// What came of trying to create the lock file
+enum Attempt {
+    Locked,
+    /// By the process with the PID in the lock file, if it could be read
+    HeldBy(Option<u32>),
+}
+
+impl BuildLock {
+    /// Takes the lock of the project. When another build holds it, this waits
+    /// for up to `config.lock_wait()` for it to be released, if at all. A lock
+    /// left behind by a build that is no longer running is taken over.
+    pub fn acquire(config: &Config) -> Result<BuildLock> {
+        Self::acquire_at(
+            &config.project_root().join(LOCK_FILE),
+            config.lock_wait(),
+            is_running,
+        )
+    }
+
+    fn acquire_at(
+        path: &Path,
+        wait: Option<Duration>,
+        is_running: impl Fn(u32) -> bool,
+    ) -> Result<BuildLock> {
+        let start = Instant::now();
+
+        loop {
+            let holder = match try_lock(path)? {
+                Attempt::Locked => {
+                    return Ok(BuildLock {
+                        path: path.to_path_buf(),
+                    })
+                }
+                Attempt::HeldBy(holder) => holder,
+            };
+
+            match holder {
+                Some(pid) if !is_running(pid) => {
+                    remove_stale(path, pid)?;
+                    continue;
+                }
+                // Released while it was being read
+                None if !path.exists() => continue,
+                _ => {}
+            }
+
+            match wait {
+                Some(timeout) if start.elapsed() < timeout => thread::sleep(RETRY_INTERVAL),
+                _ => return Err(held_error(path, holder, wait)),
+            }
+        }
+    }
+}
+
+impl Drop for BuildLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Creates the lock file with the PID of this process in it, unless there
+/// already is one. The PID is written to a file of its own first, and linked
+/// into place, so that the lock is never seen without it.
+fn try_lock(path: &Path) -> Result<Attempt> {
+    let pid = std::process::id();
+    let staged = path.with_extension(format!("lock.{}", pid));
+    fs::write(&staged, pid.to_string()).map_err(|e| {
+        Error::io(
+            e,
+            format!("Could not create the build lock {}", path.display()),
+        )
+    })?;
+
+    let linked = fs::hard_link(&staged, path);
+    let _ = fs::remove_file(&staged);
+
+    match linked {
+        Ok(()) => Ok(Attempt::Locked),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(Attempt::HeldBy(holder(path))),
+        Err(e) => Err(Error::io(
+            e,
+            format!("Could not create the build lock {}", path.display()),
+        )),
+    }
+}
+
+/// The PID in the lock file
+fn holder(path: &Path) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok())
+}
+
+/// Removes a lock whose build is no longer running, unless another build
+/// took it over in the meantime
+fn remove_stale(path: &Path, pid: u32) -> Result<()> {
+    if holder(path) != Some(pid) {
+        return Ok(());
+    }
+
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(Error::io(
+            e,
+            format!("Could not remove the stale build lock {}", path.display()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn held_error(path: &Path, holder: Option<u32>, wait: Option<Duration>) -> Error {
+    let holder = match holder {
+        Some(pid) => format!("PID {}", pid),
+        None => String::from("an unknown process"),
+    };
+
+    match wait {
+        Some(timeout) => Error::new(format!(
+            "Gave up after waiting {}s for another docgen build, whose lock {} is held by {}. \
+             Remove the lock if no build is running.",
+            timeout.as_secs(),
+            path.display(),
+            holder
+        )),
+        None => Error::new(format!(
+            "Another docgen build appears to be running, lock {} held by {}. \
+             Use --wait to wait for it to finish, or remove the stale lock.",
+            path.display(),
+            holder
+        )),
+    }
+}
+
+/// Whether a process with the PID is running. When that can't be told, it
+/// is assumed to be, so that a lock is never taken from a running build.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    if proc.join("self").exists() {
+        return proc.join(pid.to_string()).exists();
+    }
+
+    exists_for_kill(pid)
+}
+
+/// Whether `kill` finds a process with the PID. Signal 0 only checks that
+/// the process exists, but it fails with "Operation not permitted" for
+/// processes of other users, so only "No such process" means it's gone.
+#[cfg(unix)]
+fn exists_for_kill(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .env("LC_ALL", "C")
+        .stdout(Stdio::null())
+        .output()
+        .map(|output| {
+            output.status.success()
+                || !String::from_utf8_lossy(&output.stderr).contains("No such process")
+        })
+        .unwrap_or(true)
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .stderr(Stdio::null())
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::project;
+
+    #[test]
+    fn takes_a_free_lock_and_releases_it() {
+        let dir = project("lock-free", &[]);
+        let path = dir.join(LOCK_FILE);
+
+        let lock = BuildLock::acquire_at(&path, None, |_| true).unwrap();
+        let pid = holder(&path);
+        drop(lock);
+        let released = !path.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(pid, Some(std::process::id()));
+        assert!(released);
+    }
+
+    #[test]
+    fn a_held_lock_fails_the_build() {
+        let dir = project("lock-held", &[]);
+        let path = dir.join(LOCK_FILE);
+        fs::write(&path, "4242").unwrap();
+
+        let error = BuildLock::acquire_at(&path, None, |_| true).unwrap_err();
+        let kept = holder(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Another docgen build appears to be running, lock {} held by PID 4242. \
+                 Use --wait to wait for it to finish, or remove the stale lock.",
+                path.display()
+            )
+        );
+        assert_eq!(kept, Some(4242));
+    }
+
+    #[test]
+    fn waiting_for_a_held_lock_times_out() {
+        let dir = project("lock-timeout", &[]);
+        let path = dir.join(LOCK_FILE);
+        fs::write(&path, "4242").unwrap();
+
+        let error =
+            BuildLock::acquire_at(&path, Some(Duration::from_millis(300)), |_| true).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(error
+            .to_string()
+            .starts_with("Gave up after waiting 0s for another docgen build"));
+    }
+
+    #[test]
+    fn waiting_takes_the_lock_once_it_is_released() {
+        let dir = project("lock-wait", &[]);
+        let path = dir.join(LOCK_FILE);
+        fs::write(&path, "4242").unwrap();
+
+        let held = path.clone();
+        let other_build = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            fs::remove_file(held).unwrap();
+        });
+        let lock = BuildLock::acquire_at(&path, Some(Duration::from_secs(10)), |_| true);
+        other_build.join().unwrap();
+        let pid = holder(&path);
+        drop(lock);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(pid, Some(std::process::id()));
+    }
+
+    #[test]
+    fn a_stale_lock_is_taken_over() {
+        let dir = project("lock-stale", &[]);
+        let path = dir.join(LOCK_FILE);
+        fs::write(&path, "4242").unwrap();
+
+        let lock = BuildLock::acquire_at(&path, None, |pid| pid != 4242);
+        let pid = holder(&path);
+        drop(lock);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(pid, Some(std::process::id()));
+    }
+
+    #[test]
+    fn this_process_is_running() {
+        assert!(is_running(std::process::id()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_only_counts_processes_that_are_gone() {
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        assert!(exists_for_kill(std::process::id()));
+        assert!(!exists_for_kill(pid));
+    }
+}
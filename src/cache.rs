@@ -0,0 +1,479 @@
+//! A directory for results that are slow to work out and worth keeping
+//! between builds. Every kind of result has a directory of its own, a
+//! category, with a file per entry. `index.json` records the format of the
+//! cache and when each entry was last used, so that entries nobody needs
+//! anymore can be pruned at the end of a build.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::{Error, Result};
+
+static INDEX_FILE: &str = "index.json";
+
+/// Bumped whenever entries are stored differently. A cache with another
+/// version is emptied instead of being read.
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    /// When each entry was last used, in seconds since the epoch, by
+    /// `category/key`
+    entries: BTreeMap<String, u64>,
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index {
+            version: CACHE_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+pub struct Cache {
+    root: PathBuf,
+    index: Index,
+}
+
+/// How much space a category of the cache takes up
+#[derive(Debug, PartialEq)]
+pub struct CategoryInfo {
+    pub name: String,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+impl Cache {
+    /// Opens the cache at `root`, creating it if needed. A cache written in
+    /// another format is emptied first. Refuses to use a directory with
+    /// other files in it, so that a mistyped `cache_dir` doesn't end up
+    /// mixing entries with, or later deleting, someone's files.
+    pub fn open(root: &Path) -> Result<Cache> {
+        let index_path = root.join(INDEX_FILE);
+
+        if root.is_dir() && !index_path.is_file() && !is_empty_dir(root) {
+            return Err(not_a_cache(root));
+        }
+
+        let index = fs::read(&index_path)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Index>(&json).ok());
+
+        let index = match index {
+            Some(index) if index.version == CACHE_VERSION => index,
+            Some(_) | None => {
+                if index_path.is_file() {
+                    remove(root)?;
+                }
+                Index::default()
+            }
+        };
+
+        fs::create_dir_all(root).map_err(|e| {
+            Error::io(
+                e,
+                format!("Could not create the cache at {}", root.display()),
+            )
+        })?;
+
+        let cache = Cache {
+            root: root.to_path_buf(),
+            index,
+        };
+        cache.save()?;
+
+        Ok(cache)
+    }
+
+    /// The entry stored under the key, if there is one
+    pub fn get(&mut self, category: &str, key: &str) -> Option<Vec<u8>> {
+        let path = self.entry_path(category, key)?;
+        let contents = fs::read(path).ok()?;
+
+        self.index
+            .entries
+            .insert(entry_name(category, key), seconds(SystemTime::now()));
+
+        Some(contents)
+    }
+
+    /// Stores the entry under the key, replacing the one that was there.
+    /// Categories and keys are used as file names, so they can't contain
+    /// path separators.
+    pub fn put(&mut self, category: &str, key: &str, contents: &[u8]) -> Result<()> {
+        let path = self.entry_path(category, key).ok_or_else(|| {
+            Error::new(format!(
+                "Invalid cache entry {}, names can't contain path separators",
+                entry_name(category, key)
+            ))
+        })?;
+
+        fs::create_dir_all(self.root.join(category))
+            .and_then(|_| fs::write(&path, contents))
+            .map_err(|e| Error::io(e, format!("Could not write {}", path.display())))?;
+
+        self.index
+            .entries
+            .insert(entry_name(category, key), seconds(SystemTime::now()));
+
+        Ok(())
+    }
+
+    /// Writes the index, with when every entry was last used
+    pub fn save(&self) -> Result<()> {
+        let path = self.root.join(INDEX_FILE);
+        let json = serde_json::to_vec(&self.index).unwrap();
+
+        fs::write(&path, json)
+            .map_err(|e| Error::io(e, format!("Could not write {}", path.display())))
+    }
+
+    /// Removes the entries that weren't used in `max_age` before `now`,
+    /// returning how many were removed
+    pub fn prune(&mut self, max_age: Duration, now: SystemTime) -> Result<usize> {
+        let oldest = seconds(now).saturating_sub(max_age.as_secs());
+
+        let expired = self
+            .index
+            .entries
+            .iter()
+            .filter(|(_, last_used)| **last_used < oldest)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        for name in &expired {
+            self.index.entries.remove(name);
+
+            let path = name
+                .split_once('/')
+                .and_then(|(category, key)| self.entry_path(category, key));
+            if let Some(path) = path {
+                match fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(Error::io(e, format!("Could not remove {}", path.display())))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(expired.len())
+    }
+
+    /// How many entries each category has, and how much space they take up
+    pub fn info(&self) -> Result<Vec<CategoryInfo>> {
+        let read_dir = |path: &Path| {
+            fs::read_dir(path)
+                .map_err(|e| Error::io(e, format!("Could not read {}", path.display())))
+        };
+
+        let mut categories = vec![];
+        for entry in read_dir(&self.root)?.filter_map(|e| e.ok()) {
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+
+            let mut category = CategoryInfo {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                entries: 0,
+                bytes: 0,
+            };
+            for file in read_dir(&entry.path())?.filter_map(|e| e.ok()) {
+                if let Ok(metadata) = file.metadata() {
+                    category.entries += 1;
+                    category.bytes += metadata.len();
+                }
+            }
+            categories.push(category);
+        }
+        categories.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(categories)
+    }
+
+    fn entry_path(&self, category: &str, key: &str) -> Option<PathBuf> {
+        if is_plain_name(category) && is_plain_name(key) {
+            Some(self.root.join(category).join(key))
+        } else {
+            None
+        }
+    }
+}
+
+/// Removes the cache at `root`. Refuses to remove a directory that isn't a
+/// Docgen cache, or that has the project in it.
+pub fn clear(root: &Path, project_root: &Path) -> Result<()> {
+    if !root.exists() {
+        return Ok(());
+    }
+
+    if !root.join(INDEX_FILE).is_file() {
+        return Err(not_a_cache(root));
+    }
+
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let canonical_project = project_root
+        .canonicalize()
+        .unwrap_or_else(|_| project_root.to_path_buf());
+    if canonical_project.starts_with(&canonical_root) {
+        return Err(Error::new(format!(
+            "Refusing to clear the cache at {}, because the project is in it. \
+             Set cache_dir to a directory of its own.",
+            root.display()
+        )));
+    }
+
+    remove(root)
+}
+
+/// Prunes the entries of the project's cache that haven't been used for
+/// `cache_max_age` days, if the project has a cache
+pub fn prune_unused(config: &Config) -> Result<usize> {
+    let max_age = match config.cache_max_age() {
+        Some(max_age) => max_age,
+        None => return Ok(0),
+    };
+    if !config.cache_dir().join(INDEX_FILE).is_file() {
+        return Ok(0);
+    }
+
+    let mut cache = Cache::open(config.cache_dir())?;
+    let pruned = cache.prune(max_age, SystemTime::now())?;
+    cache.save()?;
+
+    Ok(pruned)
+}
+
+pub struct CacheCommand {}
+
+impl CacheCommand {
+    /// Prints where the cache is, and how much space each category takes up
+    pub fn info(config: Config) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+        let root = config.cache_dir();
+
+        bunt::writeln!(stdout, "{$bold}{$blue}Docgen | Cache{/$}{/$}")?;
+        bunt::writeln!(stdout, "Location: {$bold}{}{/$}\n", root.display())?;
+
+        if !root.join(INDEX_FILE).is_file() {
+            bunt::writeln!(stdout, "The cache is empty")?;
+            return Ok(());
+        }
+
+        let categories = Cache::open(root)?.info()?;
+        let width = categories.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for category in &categories {
+            bunt::writeln!(
+                stdout,
+                "{}  {} entries  {}",
+                format!("{:width$}", category.name, width = width),
+                format!("{:>6}", category.entries),
+                format!("{:>10}", human_size(category.bytes)),
+            )?;
+        }
+
+        let total = categories.iter().map(|c| c.bytes).sum();
+        bunt::writeln!(stdout, "\nTotal: {$bold}{}{/$}", human_size(total))?;
+
+        Ok(())
+    }
+
+    /// Removes the cache
+    pub fn clear(config: Config) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+
+        clear(config.cache_dir(), config.project_root())?;
+        bunt::writeln!(
+            stdout,
+            "Cleared the cache at {$bold}{}{/$}",
+            config.cache_dir().display()
+        )?;
+
+        Ok(())
+    }
+}
+
+fn remove(root: &Path) -> Result<()> {
+    // Doesn't follow symlinks, so nothing outside of the cache is removed
+    fs::remove_dir_all(root).map_err(|e| {
+        Error::io(
+            e,
+            format!("Could not remove the cache at {}", root.display()),
+        )
+    })
+}
+
+fn not_a_cache(root: &Path) -> Error {
+    Error::new(format!(
+        "{} is not a Docgen cache, so it was left alone. \
+         Set cache_dir to a directory of its own.",
+        root.display()
+    ))
+}
+
+fn is_empty_dir(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+/// Whether the name is a single path component, so that it can't point
+/// outside of the cache
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+fn entry_name(category: &str, key: &str) -> String {
+    format!("{}/{}", category, key)
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn human_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::project;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn prunes_entries_not_used_recently() {
+        let dir = project("cache-prune", &[]);
+        let root = dir.join(".docgen-cache");
+        let now = SystemTime::now();
+
+        let mut cache = Cache::open(&root).unwrap();
+        cache.put("images", "old", b"1").unwrap();
+        cache.put("images", "new", b"2").unwrap();
+        cache
+            .index
+            .entries
+            .insert(entry_name("images", "old"), seconds(now) - 40 * DAY);
+
+        let pruned = cache.prune(Duration::from_secs(30 * DAY), now).unwrap();
+        cache.save().unwrap();
+
+        let mut cache = Cache::open(&root).unwrap();
+        let info = cache.info().unwrap();
+        let old = cache.get("images", "old");
+        let new = cache.get("images", "new");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(pruned, 1);
+        assert_eq!(old, None);
+        assert_eq!(new, Some(b"2".to_vec()));
+        assert_eq!(
+            info,
+            vec![CategoryInfo {
+                name: String::from("images"),
+                entries: 1,
+                bytes: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn pruning_stays_inside_the_cache() {
+        let dir = project("cache-prune-outside", &[]);
+        let root = dir.join(".docgen-cache");
+        fs::write(dir.join("victim"), "keep me").unwrap();
+
+        let mut cache = Cache::open(&root).unwrap();
+        assert!(cache.put("images", "../../victim", b"").is_err());
+        cache.index.entries.insert(String::from("../victim"), 0);
+        cache
+            .index
+            .entries
+            .insert(String::from("images/../../victim"), 0);
+
+        let pruned = cache.prune(Duration::from_secs(DAY), SystemTime::now());
+        let victim = fs::read_to_string(dir.join("victim"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(pruned.unwrap(), 2);
+        assert_eq!(victim.unwrap(), "keep me");
+    }
+
+    #[test]
+    fn discards_caches_of_another_version() {
+        let dir = project("cache-version", &[]);
+        let root = dir.join(".docgen-cache");
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("images").join("logo"), "old format").unwrap();
+        fs::write(
+            root.join(INDEX_FILE),
+            r#"{"version": 0, "entries": {"images/logo": 0}}"#,
+        )
+        .unwrap();
+
+        let mut cache = Cache::open(&root).unwrap();
+        let logo = cache.get("images", "logo");
+        let entries = cache.index.entries.len();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(logo, None);
+        assert_eq!(entries, 0);
+    }
+
+    #[test]
+    fn clear_refuses_to_remove_anything_but_a_cache() {
+        let dir = project("cache-clear", &[]);
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("docs")).unwrap();
+        fs::write(project.join("docs").join("README.md"), "# Home").unwrap();
+
+        // Not a cache
+        let not_a_cache = clear(&project.join("docs"), &project);
+        // A cache with the project in it
+        fs::write(dir.join(INDEX_FILE), "{}").unwrap();
+        let around_the_project = clear(&dir, &project);
+        let project_kept = project.join("docs").join("README.md").is_file();
+
+        let root = project.join(".docgen-cache");
+        Cache::open(&root)
+            .unwrap()
+            .put("images", "logo", b"")
+            .unwrap();
+        let cache = clear(&root, &project);
+        let cache_removed = !root.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(not_a_cache.is_err());
+        assert!(around_the_project.is_err());
+        assert!(project_kept);
+        assert!(cache.is_ok());
+        assert!(cache_removed);
+    }
+
+    #[test]
+    fn refuses_to_use_a_directory_with_other_files() {
+        let dir = project("cache-open", &[]);
+        fs::write(dir.join("notes.txt"), "mine").unwrap();
+
+        let result = Cache::open(&dir);
+        let notes = fs::read_to_string(dir.join("notes.txt"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(notes.unwrap(), "mine");
+    }
+}
@@ -1,15 +1,29 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use http::Uri;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::address::get_safe_addr;
+use crate::address::resolve_server_addrs;
+use crate::frontmatter;
+use crate::markdown;
+use crate::markdown::extensions::codeblock::{
+    DEFAULT_MAX_HIGHLIGHT_LINES, DEFAULT_MAX_HIGHLIGHT_LINE_SIZE, DEFAULT_MAX_HIGHLIGHT_SIZE,
+};
+use crate::markdown::extensions::link_rewriter::resolve_root_path;
+use crate::markdown::extensions::toc::SlugStyle;
+use crate::markdown::parser::{BuiltinExtension, ParseOptions, DEFAULT_PREVIEW_LENGTH};
+use crate::markdown::sanitizer::{SanitizeConfig, SanitizeMode};
 use crate::navigation::Link;
 use crate::site::BuildMode;
-use crate::{Error, Result};
+use crate::site_generator::with_template_variables;
+use crate::terminal::ColorMode;
+use crate::{Document, Error, Result};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct DocgenYaml {
@@ -23,7 +37,52 @@ pub struct DocgenYaml {
     edit_root: Option<String>,
     base_path: Option<String>,
     docs_dir: Option<String>,
+    sources: Option<Vec<Source>>,
     base_url: Option<String>,
+    external_links_new_tab: Option<bool>,
+    sanitize_html: Option<SanitizeHtml>,
+    robots: Option<String>,
+    analytics: Option<Analytics>,
+    watch_paths: Option<Vec<PathBuf>>,
+    poll_interval: Option<u64>,
+    smart_punctuation: Option<bool>,
+    number_headings: Option<bool>,
+    number_captions: Option<bool>,
+    allow_external_includes: Option<bool>,
+    nav_title_source: Option<String>,
+    clean_urls: Option<bool>,
+    search_synonyms: Option<BTreeMap<String, String>>,
+    related_pages: Option<usize>,
+    search: Option<Search>,
+    linenos: Option<bool>,
+    relative_links: Option<bool>,
+    dedupe_navigation: Option<bool>,
+    language: Option<String>,
+    post_processors: Option<Vec<String>>,
+    extensions: Option<BTreeMap<String, bool>>,
+    interactive_checklists: Option<bool>,
+    max_page_size: Option<u64>,
+    max_highlight_lines: Option<usize>,
+    max_highlight_size: Option<usize>,
+    max_highlight_line_size: Option<usize>,
+    serve_stream_size: Option<u64>,
+    slug_style: Option<String>,
+    warn_todo_comments: Option<bool>,
+    emit_ast: Option<bool>,
+    emit_fragments: Option<bool>,
+    site_report: Option<bool>,
+    prev_next_links: Option<bool>,
+    show_page_meta: Option<bool>,
+    date_format: Option<String>,
+    build_underscore_dirs: Option<bool>,
+    require_clean_git: Option<bool>,
+    frontmatter_schema: Option<FrontmatterSchema>,
+    cache_dir: Option<PathBuf>,
+    cache_max_age: Option<u64>,
+    max_path_length: Option<usize>,
+    max_description_length: Option<usize>,
+    content_header: Option<String>,
+    content_footer: Option<String>,
 }
 
 impl DocgenYaml {
@@ -48,7 +107,10 @@ impl DocgenYaml {
 
         // Validate logo exists
         if let Some(p) = &self.logo {
-            let location = docs_dir_path.join("_include").join(p);
+            // Like links, the logo can be written from the root of the site
+            let location = docs_dir_path
+                .join("_include")
+                .join(p.strip_prefix("/").unwrap_or(p));
             if !location.exists() {
                 return Err(Error::new(format!(
                     "Could not find logo specified in docgen.yaml at {}.\n\
@@ -64,6 +126,179 @@ impl DocgenYaml {
                 .map_err(|x| Error::new(format!("Invalid edit root url. Error: {:?}", x)))?;
         }
 
+        // Validate robots
+        if let Some(robots) = &self.robots {
+            if robots != "index" && robots != "noindex" {
+                return Err(Error::new(format!(
+                    "Invalid value for robots. Found '{}', expected \"index\" or \"noindex\"",
+                    robots
+                )));
+            }
+        }
+
+        // Validate navigation title source
+        if let Some(source) = &self.nav_title_source {
+            if NavTitleSource::parse(source).is_none() {
+                return Err(Error::new(format!(
+                    "Invalid value for nav_title_source. Found '{}', \
+                     expected \"heading\", \"frontmatter\" or \"filename\"",
+                    source
+                )));
+            }
+        }
+
+        // Validate built-in post processors
+        for name in self.post_processors.iter().flatten() {
+            if BuiltinProcessor::parse(name).is_none() {
+                return Err(Error::new(format!(
+                    "Invalid value for post_processors. Found '{}', expected \"minify\"",
+                    name
+                )));
+            }
+        }
+
+        // Validate extensions that can be turned off
+        for name in self
+            .extensions
+            .iter()
+            .flat_map(|extensions| extensions.keys())
+        {
+            if BuiltinExtension::parse(name).is_none() {
+                let names = BuiltinExtension::ALL
+                    .iter()
+                    .map(|ext| format!("\"{}\"", ext.name()))
+                    .collect::<Vec<_>>();
+                return Err(Error::new(format!(
+                    "Invalid value for extensions. Found '{}', expected {} or {}",
+                    name,
+                    names[..names.len() - 1].join(", "),
+                    names[names.len() - 1]
+                )));
+            }
+        }
+
+        // Validate max page size
+        if let Some(size) = self.max_page_size {
+            if size == 0 {
+                return Err(Error::new(
+                    "Invalid value for max_page_size. Found '0', \
+                     expected a number of megabytes greater than 0",
+                ));
+            }
+        }
+
+        // Validate code block highlighting limits
+        if self.max_highlight_lines == Some(0) {
+            return Err(Error::new(
+                "Invalid value for max_highlight_lines. Found '0', \
+                 expected a number of lines greater than 0",
+            ));
+        }
+        if self.max_highlight_size == Some(0) {
+            return Err(Error::new(
+                "Invalid value for max_highlight_size. Found '0', \
+                 expected a number of kilobytes greater than 0",
+            ));
+        }
+        if self.max_highlight_line_size == Some(0) {
+            return Err(Error::new(
+                "Invalid value for max_highlight_line_size. Found '0', \
+                 expected a number of kilobytes greater than 0",
+            ));
+        }
+
+        // Validate heading slug style
+        if let Some(style) = &self.slug_style {
+            if SlugStyle::parse(style).is_none() {
+                return Err(Error::new(format!(
+                    "Invalid value for slug_style. Found '{}', \
+                     expected \"default\" or \"github\"",
+                    style
+                )));
+            }
+        }
+
+        // Validate extra watch paths exist
+        for path in self.watch_paths.iter().flatten() {
+            if !project_root.join(path).exists() {
+                return Err(Error::new(format!(
+                    "Could not find watch path specified in docgen.yaml at {}.\n\
+                     Watch paths should be relative to the project root.",
+                    project_root.join(path).display()
+                )));
+            }
+        }
+
+        // Validate sources
+        let mounts = self.mounts(project_root);
+        for (i, (source, mount)) in self.sources.iter().flatten().zip(&mounts).enumerate() {
+            source.validate(mount, &docs_dir_path)?;
+
+            for (other_source, other) in self.sources.iter().flatten().zip(&mounts).skip(i + 1) {
+                if mount.prefix.starts_with(&other.prefix)
+                    || other.prefix.starts_with(&mount.prefix)
+                {
+                    return Err(Error::new(format!(
+                        "The sources {} and {} are mounted at `{}` and `{}`, which overlap. \
+                         Mount them at paths that don't contain each other.",
+                        source.path.display(),
+                        other_source.path.display(),
+                        source.mount,
+                        other_source.mount
+                    )));
+                }
+            }
+        }
+
+        // Validate polling interval
+        if self.poll_interval == Some(0) {
+            return Err(Error::new(
+                "Invalid value for poll_interval. Found '0', \
+                 expected a number of milliseconds greater than 0",
+            ));
+        }
+
+        // Validate date format. Review dates have no time or time zone, so
+        // formatting a date is the only way to find specifiers like `%H`
+        // that can't be used with them.
+        if let Some(format) = &self.date_format {
+            use std::fmt::Write;
+
+            let sample = chrono::NaiveDate::from_ymd_opt(2021, 3, 14).unwrap();
+            let mut formatted = String::new();
+            if write!(formatted, "{}", sample.format(format)).is_err() {
+                return Err(Error::new(format!(
+                    "Invalid value for date_format. Found '{}', \
+                     expected a format like \"%B %-d, %Y\"",
+                    format
+                )));
+            }
+        }
+
+        // Validate footer
+        if let Some(footer) = &self.footer {
+            footer.validate()?;
+        }
+
+        // Validate analytics
+        if let Some(analytics) = &self.analytics {
+            analytics.validate()?;
+        }
+
+        // Validate search limits
+        if let Some(search) = &self.search {
+            search.validate()?;
+        }
+
+        if let Some(sanitize_html) = &self.sanitize_html {
+            sanitize_html.validate()?;
+        }
+
+        // Validate frontmatter types and patterns
+        if let Some(schema) = &self.frontmatter_schema {
+            schema.validate()?;
+        }
+
         // Validate navigation paths exist
         // Validate navigation wildcards recursively
         fn validate_level(
@@ -71,7 +306,11 @@ impl DocgenYaml {
             config: &DocgenYaml,
             project_root: &Path,
         ) -> Result<()> {
-            let doc_path = config.docs_dir(project_root).join(&nav.path);
+            let doc_path = source_file(
+                &config.docs_dir(project_root),
+                &config.mounts(project_root),
+                &crate::navigation::split_anchor(&nav.path).0,
+            );
             if !doc_path.exists() {
                 return Err(Error::new(format!(
                     "Could not find file specified in navigation at {}. Fix the path or run docgen nav to regenerate navigation.",
@@ -79,6 +318,16 @@ impl DocgenYaml {
                 )));
             }
 
+            if let Some(sort) = &nav.sort {
+                if NavSort::parse(sort).is_none() {
+                    return Err(Error::new(format!(
+                        "Invalid value for navigation sort. Found '{}', \
+                         expected \"index\", \"alpha\" or \"manual\"",
+                        sort
+                    )));
+                }
+            }
+
             if let Some(children) = &nav.children {
                 match children {
                     NavChildren::WildCard(pattern) => {
@@ -89,6 +338,14 @@ impl DocgenYaml {
                                 pattern
                             )));
                         }
+
+                        if nav.title.is_some() {
+                            return Err(Error::new(format!(
+                                "Navigation entry {} has a title and wildcard children. \
+                                 Titles can only be set on files and explicit lists of children.",
+                                nav.path.display()
+                            )));
+                        }
                     }
                     NavChildren::List(navs) => {
                         for nav in navs {
@@ -108,27 +365,8 @@ impl DocgenYaml {
         }
 
         // Validate base path
-        if let Some(path) = &mut self.base_path {
-            let uri: Uri = path.parse().map_err(|_| {
-                Error::new(format!(
-                    "base_path was not valid absolute URI path. Got `{}`",
-                    path
-                ))
-            })?;
-
-            if !uri.path().starts_with("/") {
-                return Err(Error::new(format!(
-                    "Base path must be an absolute path. Got `{}`.",
-                    path
-                )));
-            }
-
-            if !path.ends_with("/") {
-                return Err(Error::new(format!(
-                    "Base path must end with /. Got `{}`.",
-                    path
-                )));
-            }
+        if let Some(path) = &self.base_path {
+            validate_base_path(path)?;
         }
 
         Ok(())
@@ -143,11 +381,150 @@ impl DocgenYaml {
         let doc_root_path = project_root.join(to_join);
         doc_root_path
     }
+
+    /// The directories of the `sources`, with where their pages are mounted
+    fn mounts(&self, project_root: &Path) -> Vec<Mount> {
+        self.sources
+            .iter()
+            .flatten()
+            .map(|source| Mount {
+                prefix: PathBuf::from(source.mount.trim_matches('/')),
+                dir: project_root.join(&source.path),
+                edit_root: source.edit_root.clone(),
+            })
+            .collect()
+    }
 }
 
+/// A directory outside of the docs directory whose pages are part of the
+/// site, under the path it's mounted at
 #[derive(Debug, Clone, Deserialize)]
+pub struct Source {
+    path: PathBuf,
+    mount: String,
+    edit_root: Option<String>,
+}
+
+impl Source {
+    fn validate(&self, mount: &Mount, docs_dir: &Path) -> Result<()> {
+        let is_relative = mount
+            .prefix
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)));
+        if mount.prefix.as_os_str().is_empty() || !is_relative {
+            return Err(Error::new(format!(
+                "Invalid mount for the source {}. Found '{}', \
+                 expected a path in the site like \"handbook\"",
+                self.path.display(),
+                self.mount
+            )));
+        }
+
+        if !mount.dir.is_dir() {
+            return Err(Error::new(format!(
+                "Could not find source directory specified in docgen.yaml at {}.\n\
+                 Source paths should be relative to the project root.",
+                mount.dir.display()
+            )));
+        }
+
+        let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+        let (dir, docs) = (canonical(&mount.dir), canonical(docs_dir));
+        if dir.starts_with(&docs) || docs.starts_with(&dir) {
+            return Err(Error::new(format!(
+                "The source {} overlaps the docs directory {}. \
+                 Sources have to be directories outside of it.",
+                mount.dir.display(),
+                docs_dir.display()
+            )));
+        }
+
+        let page = docs_dir.join(&mount.prefix);
+        for taken in [page.clone(), page.with_extension("md")] {
+            if taken.exists() {
+                return Err(Error::new(format!(
+                    "The source {} is mounted at `{}`, which collides with {}. \
+                     Mount it at another path, or rename the one in the docs directory.",
+                    self.path.display(),
+                    self.mount,
+                    taken.display()
+                )));
+            }
+        }
+
+        if let Some(edit_root) = &self.edit_root {
+            Uri::try_from(edit_root).map_err(|x| {
+                Error::new(format!(
+                    "Invalid edit root url of the source {}. Error: {:?}",
+                    self.path.display(),
+                    x
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A directory of pages from `sources`, with the path in the site that its
+/// pages are under
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    /// The path of the directory in the site, like `handbook`
+    pub prefix: PathBuf,
+    /// The directory the pages are read from
+    pub dir: PathBuf,
+    /// Where the "Edit this page" links of its pages point, instead of the
+    /// `edit_root` of the site
+    pub edit_root: Option<String>,
+}
+
+/// The file on disk for a path in the docs, which is in the directory of
+/// the source it is mounted from, or else in the docs directory
+fn source_file(docs_dir: &Path, mounts: &[Mount], path: &Path) -> PathBuf {
+    let path = path.strip_prefix("/").unwrap_or(path);
+
+    mounts
+        .iter()
+        .find_map(|mount| Some(mount.dir.join(path.strip_prefix(&mount.prefix).ok()?)))
+        .unwrap_or_else(|| docs_dir.join(path))
+}
+
+/// Checks that a `base_path` is an absolute URI path that ends with a `/`,
+/// whether it comes from the docgen.yaml or `--base-path`
+fn validate_base_path(path: &str) -> Result<()> {
+    let uri: Uri = path.parse().map_err(|_| {
+        Error::new(format!(
+            "base_path was not valid absolute URI path. Got `{}`",
+            path
+        ))
+    })?;
+
+    if !uri.path().starts_with("/") {
+        return Err(Error::new(format!(
+            "Base path must be an absolute path. Got `{}`.",
+            path
+        )));
+    }
+
+    if !path.ends_with("/") {
+        return Err(Error::new(format!(
+            "Base path must end with /. Got `{}`.",
+            path
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Navigation {
     pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub children: Option<NavChildren>,
 }
 
@@ -159,13 +536,17 @@ pub struct Meta {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Footer {
     pub groups: Option<Vec<FooterGroup>>,
+    /// Inline Markdown. `{year}` is replaced with the year of the build.
     pub copyright: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct FooterGroup {
     pub title: String,
+    #[serde(default)]
     pub links: Vec<FooterLink>,
+    /// A blurb shown under the title, in inline Markdown
+    pub text: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -176,325 +557,2274 @@ pub struct FooterLink {
 }
 
 #[derive(Debug, Clone, Deserialize)]
-#[serde(untagged)]
-pub enum NavChildren {
-    WildCard(String),
-    List(Vec<Navigation>),
+pub struct Analytics {
+    pub plausible: Option<PlausibleAnalytics>,
+    pub custom: Option<CustomAnalytics>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct Themes {
-    pub light: HashMap<String, String>,
-    pub dark: HashMap<String, String>,
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlausibleAnalytics {
+    pub domain: String,
+    pub script_url: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum NavRule {
-    File(PathBuf),
-    Dir(PathBuf, Option<DirIncludeRule>),
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomAnalytics {
+    pub script_url: String,
+    pub data_attrs: Option<BTreeMap<String, String>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum DirIncludeRule {
-    WildCard,
-    Explicit(Vec<NavRule>),
+#[derive(Debug, Clone, Deserialize)]
+pub struct Search {
+    pub engine: Option<String>,
+    pub preview_length: Option<usize>,
+    pub max_results: Option<usize>,
+    pub recent_count: Option<usize>,
 }
 
-impl NavRule {
-    fn from_yaml_input(input: Vec<Navigation>) -> Vec<NavRule> {
-        let mut rules = vec![];
-        for item in input {
-            if item.children.is_some() || item.path.is_dir() {
-                let dir_rules = Self::build_directory_rules(&item);
-                rules.push(dir_rules);
-            } else {
-                rules.push(NavRule::File(item.path.clone()));
-            }
-        }
-        rules
-    }
-
-    fn build_directory_rules(dir: &Navigation) -> NavRule {
-        match &dir.children {
-            None => NavRule::Dir(dir.path.clone(), None),
-            Some(NavChildren::WildCard(_)) => {
-                NavRule::Dir(dir.path.clone(), Some(DirIncludeRule::WildCard))
-            }
-            Some(NavChildren::List(paths)) => NavRule::Dir(
-                dir.path.clone(),
-                Some(DirIncludeRule::Explicit(
-                    paths
-                        .iter()
-                        .map(|p| {
-                            if p.children.is_some() {
-                                Self::build_directory_rules(p)
-                            } else {
-                                NavRule::File(p.path.clone())
-                            }
-                        })
-                        .collect::<Vec<_>>(),
-                )),
-            ),
-        }
-    }
+/// How the HTML of the pages is sanitized. By default only the HTML that
+/// Markdown and the extensions produce is kept.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SanitizeHtml {
+    /// Turns sanitizing off, for docs written by trusted authors only
+    pub enabled: Option<bool>,
+    /// More tags to keep
+    pub tags: Option<Vec<String>>,
+    /// More attributes to keep, by the tag they are kept on. The ones of
+    /// `*` are kept on every tag.
+    pub attributes: Option<BTreeMap<String, Vec<String>>>,
+}
 
-    pub fn is_default_readme_rule(&self, root_dir: &Path, docs_dir: &Path) -> bool {
-        let my_path = match self {
-            NavRule::File(path) => path,
-            NavRule::Dir(_, _) => return false,
-        };
+/// Tags whose content the sanitizer always removes, which can't be kept
+static REMOVED_TAGS: &[&str] = &["script", "style"];
 
-        root_dir.join(my_path) == docs_dir.join("README.md")
-    }
+/// Rules the frontmatter of every page is checked against
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrontmatterSchema {
+    #[serde(default)]
+    pub keys: BTreeMap<String, FrontmatterKey>,
+    /// Warn about keys that are neither in `keys` nor used by Docgen
+    #[serde(default)]
+    pub deny_unknown_keys: bool,
 }
 
-#[derive(Debug, Clone)]
-pub struct Config {
-    color: bool,
-    allow_failed_checks: bool,
-    project_root: PathBuf,
-    meta: Option<Meta>,
-    out_dir: PathBuf,
-    docs_dir: PathBuf,
-    base_path: String,
-    base_url: Option<String>,
-    edit_root: Option<String>,
-    title: String,
-    subtitle: String,
-    logo: Option<String>,
-    navigation: Option<Vec<NavRule>>,
-    build_mode: BuildMode,
-    pub preview_addr: SocketAddr,
-    pub livereload_addr: SocketAddr,
-    footer: Option<Footer>,
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrontmatterKey {
+    #[serde(default)]
+    pub required: bool,
+    /// One of `string`, `bool`, `int`, `date` or `list`
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// A regular expression the value, or every item of a list, must match
+    pub pattern: Option<String>,
+    /// `pattern`, compiled once when the config is loaded
+    #[serde(skip)]
+    pub regex: Option<Regex>,
 }
 
-impl Config {
-    pub fn load(project_root: &Path, skip_validation: bool) -> Result<Self> {
-        let path = DocgenYaml::find(&project_root)
-            .ok_or(Error::new("Could not find docgen.yaml in project"))?;
-
-        let yaml =
-            fs::read_to_string(path).map_err(|_| Error::new("Could not read docgen.yaml file"))?;
+static PLAUSIBLE_SCRIPT_URL: &str = "https://plausible.io/js/script.js";
 
-        Config::from_yaml_str(project_root, &yaml, skip_validation)
-    }
+/// How many related pages are shown below each page by default
+static DEFAULT_RELATED_PAGES: usize = 5;
 
-    pub fn from_yaml_str(project_root: &Path, yaml: &str, skip_validation: bool) -> Result<Self> {
-        let mut docgen_yaml: DocgenYaml = serde_yaml::from_str(yaml)
-            .map_err(|e| Error::yaml(e, "Could not parse docgen.yaml"))?;
+/// Markdown files larger than this many megabytes are skipped by default
+static DEFAULT_MAX_PAGE_SIZE: u64 = 5;
 
-        if !skip_validation {
-            docgen_yaml.validate(project_root)?;
-        }
+/// Copied files larger than this many kilobytes are streamed from disk by
+/// the development server by default, instead of being kept in memory
+static DEFAULT_SERVE_STREAM_SIZE: u64 = 1024;
 
-        let preview_addr = get_safe_addr("127.0.0.1", docgen_yaml.port.unwrap_or_else(|| 4001))
-            .expect("Failed to get address for preview server.");
-        let livereload_addr = get_safe_addr("127.0.0.1", 35729)
-            .expect("Failed to get address for live reload server.");
+/// How dates are written on pages by default, like "January 31, 2024"
+static DEFAULT_DATE_FORMAT: &str = "%B %-d, %Y";
 
-        let config = Config {
-            color: true,
-            allow_failed_checks: false,
-            project_root: project_root.to_path_buf(),
-            out_dir: project_root.join("site"),
-            docs_dir: docgen_yaml.docs_dir(project_root),
-            base_path: docgen_yaml.base_path.unwrap_or(String::from("/")),
-            title: docgen_yaml.title,
-            subtitle: docgen_yaml.subtitle.unwrap_or(String::from("DOCS")),
-            edit_root: docgen_yaml.edit_root,
-            footer: docgen_yaml.footer,
-            meta: docgen_yaml.meta,
-            logo: docgen_yaml
-                .logo
-                .map(|p| Link::path_to_uri_with_extension(&p))
-                .map(|p| p.as_str().trim_start_matches("/").to_owned()),
-            navigation: docgen_yaml.navigation.map(|n| NavRule::from_yaml_input(n)),
-            preview_addr,
-            livereload_addr,
-            build_mode: BuildMode::Dev,
-            base_url: docgen_yaml.base_url,
-        };
+/// The port the preview server listens on by default
+static DEFAULT_PORT: u16 = 4001;
 
-        Ok(config)
-    }
+/// The port livereload.js connects to by default
+static LIVERELOAD_PORT: u16 = 35729;
 
-    /// The title of the project
-    pub fn base_url(&self) -> &Option<String> {
-        &self.base_url
-    }
+/// Where the build cache is kept by default, relative to the project root
+static DEFAULT_CACHE_DIR: &str = ".docgen-cache";
 
-    /// The title of the project
-    pub fn footer(&self) -> &Option<Footer> {
-        &self.footer
-    }
+/// Cache entries not used for this many days are pruned by default
+static DEFAULT_CACHE_MAX_AGE: u64 = 30;
 
-    /// The title of the project
-    pub fn meta(&self) -> &Option<Meta> {
-        &self.meta
-    }
+/// Paths of built files longer than this many characters are warned about
+/// by default. Only on Windows, where MAX_PATH keeps many tools from opening
+/// them.
+static DEFAULT_MAX_PATH_LENGTH: usize = if cfg!(windows) { 260 } else { 0 };
 
-    /// The title of the project
-    pub fn title(&self) -> &str {
-        &self.title
-    }
+/// Descriptions in frontmatter longer than this many characters are warned
+/// about by default, since search engines cut them short
+static DEFAULT_MAX_DESCRIPTION_LENGTH: usize = 160;
 
-    /// The title of the project
-    pub fn subtitle(&self) -> &str {
-        &self.subtitle
-    }
+/// How many search results are shown by default
+static DEFAULT_SEARCH_MAX_RESULTS: usize = 10;
 
-    /// The root directory of the project - the folder containing the docgen.yaml file.
-    pub fn project_root(&self) -> &Path {
-        &self.project_root
-    }
+/// The language of pages that don't say otherwise
+static DEFAULT_LANGUAGE: &str = "en";
 
-    /// The directory the HTML will get built into
-    pub fn out_dir(&self) -> &Path {
-        &self.out_dir
-    }
+/// How many recently updated pages are exported by default
+static DEFAULT_SEARCH_RECENT_COUNT: usize = 5;
 
-    /// The directory that contains all the Markdown documentation
-    pub fn docs_dir(&self) -> &Path {
+impl Search {
+    fn validate(&self) -> Result<()> {
+        if let Some(engine) = &self.engine {
+            if SearchEngine::parse(engine).is_none() {
+                return Err(Error::new(format!(
+                    "Invalid value for search.engine. Found '{}', \
+                     expected \"elasticlunr\" or \"simple\"",
+                    engine
+                )));
+            }
+        }
+
+        if let Some(length) = self.preview_length {
+            if !(20..=1000).contains(&length) {
+                return Err(Error::new(format!(
+                    "Invalid value for search.preview_length. Found '{}', \
+                     expected a number between 20 and 1000",
+                    length
+                )));
+            }
+        }
+
+        if let Some(max_results) = self.max_results {
+            if !(1..=100).contains(&max_results) {
+                return Err(Error::new(format!(
+                    "Invalid value for search.max_results. Found '{}', \
+                     expected a number between 1 and 100",
+                    max_results
+                )));
+            }
+        }
+
+        if let Some(recent_count) = self.recent_count {
+            if recent_count > 50 {
+                return Err(Error::new(format!(
+                    "Invalid value for search.recent_count. Found '{}', \
+                     expected a number between 0 and 50",
+                    recent_count
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SanitizeHtml {
+    fn validate(&self) -> Result<()> {
+        let has_allowlist = self.tags.is_some() || self.attributes.is_some();
+        if self.enabled == Some(false) && has_allowlist {
+            return Err(Error::new(
+                "Invalid value for sanitize_html. Found `tags` or `attributes` with \
+                 `enabled: false`, which keeps all HTML. Remove them, or turn sanitizing on",
+            ));
+        }
+
+        for tag in self.tags.iter().flatten() {
+            if REMOVED_TAGS.contains(&tag.to_ascii_lowercase().as_str()) {
+                return Err(Error::new(format!(
+                    "Invalid value for sanitize_html.tags. Found '{}', which is always removed \
+                     with its content. Set `enabled: false` to keep it",
+                    tag
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The sanitizing the pages get
+    fn mode(&self) -> SanitizeMode {
+        if self.enabled == Some(false) {
+            return SanitizeMode::Off;
+        }
+
+        let mut config = SanitizeConfig {
+            tags: self.tags.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        for (tag, attributes) in self.attributes.iter().flatten() {
+            if tag == "*" {
+                config.generic_attributes.extend(attributes.iter().cloned());
+            } else {
+                config
+                    .tag_attributes
+                    .insert(tag.clone(), attributes.clone());
+            }
+        }
+
+        if config.tags.is_empty()
+            && config.generic_attributes.is_empty()
+            && config.tag_attributes.is_empty()
+        {
+            SanitizeMode::Strict
+        } else {
+            SanitizeMode::Custom(config)
+        }
+    }
+}
+
+impl FrontmatterSchema {
+    fn validate(&self) -> Result<()> {
+        for (key, rules) in &self.keys {
+            if let Some(kind) = &rules.kind {
+                if frontmatter::ValueType::parse(kind).is_none() {
+                    return Err(Error::new(format!(
+                        "Invalid value for frontmatter_schema.keys.{}.type. Found '{}', \
+                         expected \"string\", \"bool\", \"int\", \"date\" or \"list\"",
+                        key, kind
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the patterns of the keys, so pages are checked against them
+    /// without compiling them again
+    pub fn compile(&mut self) -> Result<()> {
+        for (key, rules) in &mut self.keys {
+            if let Some(pattern) = &rules.pattern {
+                match Regex::new(pattern) {
+                    Ok(regex) => rules.regex = Some(regex),
+                    Err(e) => {
+                        return Err(Error::new(format!(
+                            "Invalid value for frontmatter_schema.keys.{}.pattern. Found '{}', \
+                             expected a regular expression: {}",
+                            key, pattern, e
+                        )))
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Footer {
+    fn validate(&self) -> Result<()> {
+        let mut fields = vec![];
+        if let Some(copyright) = &self.copyright {
+            fields.push(("footer.copyright", copyright));
+        }
+        for group in self.groups.iter().flatten() {
+            if let Some(text) = &group.text {
+                fields.push(("footer group text", text));
+            }
+            for link in &group.links {
+                fields.push(("footer link title", &link.title));
+            }
+        }
+
+        for (name, value) in fields {
+            if !markdown::inline::is_inline(value) {
+                return Err(Error::new(format!(
+                    "Invalid value for {}. Found '{}', expected a single line of text. \
+                     Links, code and emphasis are allowed, but not headings, lists or HTML.",
+                    name, value
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The footer with its Markdown rendered to HTML, `{year}` replaced with
+    /// the given year, `{{ docgen_version }}` with the version of Docgen, and
+    /// links from the root of the site pointed at `root`
+    pub fn rendered(&self, year: i32, root: &str) -> Footer {
+        let render = |value: &str| {
+            let value = value.replace("{year}", &year.to_string());
+            markdown::inline::render_inline(&with_template_variables(&value))
+        };
+
+        Footer {
+            groups: self.groups.as_ref().map(|groups| {
+                groups
+                    .iter()
+                    .map(|group| FooterGroup {
+                        title: group.title.clone(),
+                        links: group
+                            .links
+                            .iter()
+                            .map(|link| FooterLink {
+                                href: resolve_root_path(&link.href, root),
+                                title: render(&link.title),
+                                ..link.clone()
+                            })
+                            .collect(),
+                        text: group.text.as_deref().map(render),
+                    })
+                    .collect()
+            }),
+            copyright: self.copyright.as_deref().map(render),
+        }
+    }
+}
+
+impl Analytics {
+    fn validate(&self) -> Result<()> {
+        if let Some(plausible) = &self.plausible {
+            if plausible.domain.is_empty()
+                || plausible
+                    .domain
+                    .contains(|c: char| c.is_whitespace() || c == '/' || c == '"')
+            {
+                return Err(Error::new(format!(
+                    "Invalid analytics.plausible.domain. Expected a domain such as \
+                     `docs.example.com`, got `{}`.",
+                    plausible.domain
+                )));
+            }
+
+            if let Some(script_url) = &plausible.script_url {
+                validate_script_url("analytics.plausible.script_url", script_url)?;
+            }
+        }
+
+        if let Some(custom) = &self.custom {
+            validate_script_url("analytics.custom.script_url", &custom.script_url)?;
+
+            for key in custom.data_attrs.iter().flat_map(|attrs| attrs.keys()) {
+                if key.is_empty()
+                    || !key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    return Err(Error::new(format!(
+                        "Invalid attribute name `{}` in analytics.custom.data_attrs. \
+                         Only letters, numbers, `-` and `_` are allowed.",
+                        key
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds the script tags for the configured analytics providers. All
+    /// values are escaped.
+    pub fn script_tags(&self) -> String {
+        let mut tags = String::new();
+
+        if let Some(plausible) = &self.plausible {
+            let src = plausible
+                .script_url
+                .as_deref()
+                .unwrap_or(PLAUSIBLE_SCRIPT_URL);
+
+            tags.push_str(&script_tag(src, &[("data-domain", &plausible.domain)]));
+        }
+
+        if let Some(custom) = &self.custom {
+            let attrs = custom
+                .data_attrs
+                .iter()
+                .flat_map(|attrs| attrs.iter())
+                .map(|(k, v)| (format!("data-{}", k), v.as_str()))
+                .collect::<Vec<_>>();
+            let attrs = attrs
+                .iter()
+                .map(|(k, v)| (k.as_str(), *v))
+                .collect::<Vec<_>>();
+
+            tags.push_str(&script_tag(&custom.script_url, &attrs));
+        }
+
+        tags
+    }
+}
+
+fn validate_script_url(field: &str, script_url: &str) -> Result<()> {
+    match url::Url::parse(script_url) {
+        Ok(url) if url.scheme() == "https" || url.scheme() == "http" => Ok(()),
+        _ => Err(Error::new(format!(
+            "Invalid {}. Expected an absolute http(s) URL, got `{}`.",
+            field, script_url
+        ))),
+    }
+}
+
+fn script_tag(src: &str, attrs: &[(&str, &str)]) -> String {
+    let mut tag = String::from("<script defer");
+
+    for (name, value) in attrs {
+        tag.push_str(&format!(" {}=\"", name));
+        escape_html(&mut tag, value).unwrap();
+        tag.push('"');
+    }
+
+    tag.push_str(" src=\"");
+    escape_href(&mut tag, src).unwrap();
+    tag.push_str("\"></script>");
+
+    tag
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum NavChildren {
+    WildCard(String),
+    List(Vec<Navigation>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Themes {
+    pub light: HashMap<String, String>,
+    pub dark: HashMap<String, String>,
+}
+
+/// Whether the value looks like a BCP 47 language tag, e.g. `en`, `pt-BR`
+/// or `zh-Hant-TW`. Only the shape of the tag is checked, not whether the
+/// language exists.
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or("");
+
+    (2..=8).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Where navigation links get their titles from, unless a page sets
+/// `nav_title` in its frontmatter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NavTitleSource {
+    /// The page title: the frontmatter `title`, falling back to the first heading
+    Frontmatter,
+    /// The first heading of the page
+    Heading,
+    /// The file name, or the directory name for READMEs
+    Filename,
+}
+
+impl NavTitleSource {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "frontmatter" => Some(NavTitleSource::Frontmatter),
+            "heading" => Some(NavTitleSource::Heading),
+            "filename" => Some(NavTitleSource::Filename),
+            _ => None,
+        }
+    }
+}
+
+/// How the search box finds pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchEngine {
+    /// A full text index, searched with elasticlunr.js
+    Elasticlunr,
+    /// The title, headings and start of every page, matched as plain text
+    /// without any other scripts. Small enough for sites with a few pages.
+    Simple,
+}
+
+impl SearchEngine {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "elasticlunr" => Some(SearchEngine::Elasticlunr),
+            "simple" => Some(SearchEngine::Simple),
+            _ => None,
+        }
+    }
+}
+
+/// Post processors that come with Docgen, and can be turned on in docgen.yaml
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltinProcessor {
+    /// Drops the indentation between tags
+    Minify,
+}
+
+impl BuiltinProcessor {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "minify" => Some(BuiltinProcessor::Minify),
+            _ => None,
+        }
+    }
+}
+
+/// The order of an explicit list of children in the navigation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NavSort {
+    /// The order the pages have in the default navigation
+    Index,
+    /// Alphabetically by the title shown in the navigation
+    Alpha,
+    /// The order they are listed in docgen.yaml
+    Manual,
+}
+
+impl NavSort {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "index" => Some(NavSort::Index),
+            "alpha" => Some(NavSort::Alpha),
+            "manual" => Some(NavSort::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// A navigation entry from docgen.yaml. The optional title replaces the
+/// page title in the navigation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NavRule {
+    File(PathBuf, Option<String>),
+    Dir(PathBuf, Option<DirIncludeRule>, Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirIncludeRule {
+    WildCard,
+    Explicit(Vec<NavRule>, NavSort),
+}
+
+impl NavRule {
+    fn from_yaml_input(input: Vec<Navigation>) -> Vec<NavRule> {
+        let mut rules = vec![];
+        for item in input {
+            if item.children.is_some() || item.path.is_dir() {
+                let dir_rules = Self::build_directory_rules(&item);
+                rules.push(dir_rules);
+            } else {
+                rules.push(NavRule::File(item.path.clone(), item.title.clone()));
+            }
+        }
+        rules
+    }
+
+    fn build_directory_rules(dir: &Navigation) -> NavRule {
+        match &dir.children {
+            None => NavRule::Dir(dir.path.clone(), None, dir.title.clone()),
+            Some(NavChildren::WildCard(_)) => NavRule::Dir(
+                dir.path.clone(),
+                Some(DirIncludeRule::WildCard),
+                dir.title.clone(),
+            ),
+            Some(NavChildren::List(paths)) => NavRule::Dir(
+                dir.path.clone(),
+                Some(DirIncludeRule::Explicit(
+                    paths
+                        .iter()
+                        .map(|p| {
+                            if p.children.is_some() {
+                                Self::build_directory_rules(p)
+                            } else {
+                                NavRule::File(p.path.clone(), p.title.clone())
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                    dir.sort
+                        .as_deref()
+                        .and_then(NavSort::parse)
+                        .unwrap_or(NavSort::Manual),
+                )),
+                dir.title.clone(),
+            ),
+        }
+    }
+
+    /// The title to show instead of the page title, if any
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            NavRule::File(_, title) | NavRule::Dir(_, _, title) => title.as_deref(),
+        }
+    }
+
+    pub fn is_default_readme_rule(&self, root_dir: &Path, docs_dir: &Path) -> bool {
+        let my_path = match self {
+            NavRule::File(path, _) => path,
+            NavRule::Dir(..) => return false,
+        };
+
+        root_dir.join(crate::navigation::split_anchor(my_path).0) == docs_dir.join("README.md")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    color: ColorMode,
+    allow_failed_checks: bool,
+    strict: bool,
+    /// Whether release builds may have uncommitted changes to the docs,
+    /// from `--allow-dirty`
+    allow_dirty: bool,
+    /// How long a build waits for another one to release the output
+    /// directory, from `--wait`
+    lock_wait: Option<Duration>,
+    project_root: PathBuf,
+    config_path: PathBuf,
+    meta: Option<Meta>,
+    out_dir: PathBuf,
+    docs_dir: PathBuf,
+    mounts: Vec<Mount>,
+    base_path: String,
+    base_url: Option<String>,
+    edit_root: Option<String>,
+    title: String,
+    subtitle: String,
+    logo: Option<String>,
+    navigation: Option<Vec<NavRule>>,
+    build_mode: BuildMode,
+    pub preview_addr: SocketAddr,
+    pub livereload_addr: SocketAddr,
+    footer: Option<Footer>,
+    external_links_new_tab: bool,
+    noindex: bool,
+    analytics: Option<Analytics>,
+    watch_paths: Vec<PathBuf>,
+    poll_interval: Option<Duration>,
+    smart_punctuation: bool,
+    number_headings: bool,
+    number_captions: bool,
+    allow_external_includes: bool,
+    nav_title_source: NavTitleSource,
+    clean_urls: bool,
+    search_synonyms: BTreeMap<String, String>,
+    related_pages: usize,
+    sanitize: SanitizeMode,
+    search_engine: SearchEngine,
+    search_preview_length: usize,
+    search_max_results: usize,
+    search_recent_count: usize,
+    line_numbers: bool,
+    relative_links: bool,
+    dedupe_navigation: bool,
+    language: String,
+    post_processors: Vec<BuiltinProcessor>,
+    disabled_extensions: Vec<BuiltinExtension>,
+    interactive_checklists: bool,
+    max_page_size: u64,
+    max_highlight_lines: usize,
+    max_highlight_size: usize,
+    max_highlight_line_size: usize,
+    serve_stream_size: u64,
+    slug_style: SlugStyle,
+    warn_todo_comments: bool,
+    emit_ast: bool,
+    emit_fragments: bool,
+    site_report: bool,
+    prev_next_links: bool,
+    show_page_meta: bool,
+    date_format: String,
+    build_underscore_dirs: bool,
+    require_clean_git: bool,
+    frontmatter_schema: Option<FrontmatterSchema>,
+    cache_dir: PathBuf,
+    cache_max_age: u64,
+    max_path_length: usize,
+    max_description_length: usize,
+    content_header: Option<String>,
+    content_footer: Option<String>,
+    single_file: bool,
+    pretty_html: bool,
+}
+
+impl Config {
+    pub fn load(project_root: &Path, skip_validation: bool) -> Result<Self> {
+        let path = DocgenYaml::find(&project_root)
+            .ok_or(Error::new("Could not find docgen.yaml in project"))?;
+
+        Config::load_from(&path, skip_validation)
+    }
+
+    /// Loads the config from a file that may have any name, like
+    /// `docgen.staging.yaml`. The directory of the file is the project root,
+    /// which the paths in the config are relative to.
+    pub fn load_from(path: &Path, skip_validation: bool) -> Result<Self> {
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()
+                .map_err(|e| Error::io(e, "Could not determine the current directory"))?
+                .join(path)
+        };
+        if !path.is_file() {
+            return Err(Error::new(format!(
+                "Could not find the config file {}",
+                path.display()
+            )));
+        }
+        let project_root = path.parent().unwrap_or_else(|| Path::new("/"));
+
+        let yaml = fs::read_to_string(&path).map_err(|_| {
+            Error::new(format!("Could not read the config file {}", path.display()))
+        })?;
+
+        let mut config = Config::from_yaml_str(project_root, &yaml, skip_validation)?;
+        config.config_path = path;
+
+        Ok(config)
+    }
+
+    /// A config for previewing a single Markdown file outside of a project,
+    /// with the defaults of an empty docgen.yaml. The file's directory is
+    /// the docs directory, and the title is the file's name until it is set
+    /// from the page with `set_title`.
+    pub fn for_file(path: &Path) -> Result<Self> {
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        let mut config = Config::from_yaml_str(dir, "title: Preview", true)?;
+
+        config.docs_dir = dir.to_path_buf();
+        config.single_file = true;
+        if let Some(name) = path.file_name() {
+            config.title = name.to_string_lossy().into_owned();
+        }
+
+        Ok(config)
+    }
+
+    pub fn from_yaml_str(project_root: &Path, yaml: &str, skip_validation: bool) -> Result<Self> {
+        let mut docgen_yaml: DocgenYaml = serde_yaml::from_str(yaml)
+            .map_err(|e| Error::yaml(e, "Could not parse docgen.yaml"))?;
+
+        if !skip_validation {
+            docgen_yaml.validate(project_root)?;
+        }
+        if let Some(schema) = &mut docgen_yaml.frontmatter_schema {
+            schema.compile()?;
+        }
+
+        // Free ports are only looked for when a server is started, see
+        // `resolve_server_addrs`
+        let preview_addr =
+            SocketAddr::from(([127, 0, 0, 1], docgen_yaml.port.unwrap_or(DEFAULT_PORT)));
+        let livereload_addr = SocketAddr::from(([127, 0, 0, 1], LIVERELOAD_PORT));
+
+        let config = Config {
+            color: ColorMode::Auto,
+            allow_failed_checks: false,
+            strict: false,
+            allow_dirty: false,
+            lock_wait: None,
+            project_root: project_root.to_path_buf(),
+            config_path: project_root.join("docgen.yaml"),
+            out_dir: project_root.join("site"),
+            docs_dir: docgen_yaml.docs_dir(project_root),
+            mounts: docgen_yaml.mounts(project_root),
+            base_path: docgen_yaml.base_path.unwrap_or(String::from("/")),
+            title: docgen_yaml.title,
+            subtitle: docgen_yaml.subtitle.unwrap_or(String::from("DOCS")),
+            edit_root: docgen_yaml.edit_root,
+            footer: docgen_yaml.footer,
+            meta: docgen_yaml.meta,
+            logo: docgen_yaml
+                .logo
+                .map(|p| Link::path_to_uri_with_extension(&p))
+                .map(|p| p.as_str().trim_start_matches("/").to_owned()),
+            navigation: docgen_yaml.navigation.map(|n| NavRule::from_yaml_input(n)),
+            preview_addr,
+            livereload_addr,
+            build_mode: BuildMode::Dev,
+            base_url: docgen_yaml.base_url,
+            external_links_new_tab: docgen_yaml.external_links_new_tab.unwrap_or(false),
+            noindex: docgen_yaml.robots.as_deref() == Some("noindex"),
+            analytics: docgen_yaml.analytics,
+            watch_paths: docgen_yaml
+                .watch_paths
+                .unwrap_or_default()
+                .iter()
+                .map(|p| project_root.join(p))
+                .collect(),
+            poll_interval: docgen_yaml.poll_interval.map(Duration::from_millis),
+            smart_punctuation: docgen_yaml.smart_punctuation.unwrap_or(false),
+            number_headings: docgen_yaml.number_headings.unwrap_or(false),
+            number_captions: docgen_yaml.number_captions.unwrap_or(false),
+            allow_external_includes: docgen_yaml.allow_external_includes.unwrap_or(false),
+            nav_title_source: docgen_yaml
+                .nav_title_source
+                .as_deref()
+                .and_then(NavTitleSource::parse)
+                .unwrap_or(NavTitleSource::Frontmatter),
+            clean_urls: docgen_yaml.clean_urls.unwrap_or(false),
+            line_numbers: docgen_yaml.linenos.unwrap_or(false),
+            relative_links: docgen_yaml.relative_links.unwrap_or(false),
+            dedupe_navigation: docgen_yaml.dedupe_navigation.unwrap_or(true),
+            language: docgen_yaml
+                .language
+                .unwrap_or_else(|| String::from(DEFAULT_LANGUAGE)),
+            post_processors: docgen_yaml
+                .post_processors
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|name| BuiltinProcessor::parse(name))
+                .collect(),
+            disabled_extensions: docgen_yaml
+                .extensions
+                .unwrap_or_default()
+                .iter()
+                .filter(|(_, enabled)| !**enabled)
+                .filter_map(|(name, _)| BuiltinExtension::parse(name))
+                .collect(),
+            interactive_checklists: docgen_yaml.interactive_checklists.unwrap_or(false),
+            max_page_size: docgen_yaml
+                .max_page_size
+                .unwrap_or(DEFAULT_MAX_PAGE_SIZE)
+                .saturating_mul(1024 * 1024),
+            max_highlight_lines: docgen_yaml
+                .max_highlight_lines
+                .unwrap_or(DEFAULT_MAX_HIGHLIGHT_LINES),
+            max_highlight_size: docgen_yaml
+                .max_highlight_size
+                .unwrap_or(DEFAULT_MAX_HIGHLIGHT_SIZE)
+                .saturating_mul(1024),
+            max_highlight_line_size: docgen_yaml
+                .max_highlight_line_size
+                .unwrap_or(DEFAULT_MAX_HIGHLIGHT_LINE_SIZE)
+                .saturating_mul(1024),
+            serve_stream_size: docgen_yaml
+                .serve_stream_size
+                .unwrap_or(DEFAULT_SERVE_STREAM_SIZE)
+                .saturating_mul(1024),
+            slug_style: docgen_yaml
+                .slug_style
+                .as_deref()
+                .and_then(SlugStyle::parse)
+                .unwrap_or(SlugStyle::Default),
+            warn_todo_comments: docgen_yaml.warn_todo_comments.unwrap_or(false),
+            emit_ast: docgen_yaml.emit_ast.unwrap_or(false),
+            emit_fragments: docgen_yaml.emit_fragments.unwrap_or(false),
+            site_report: docgen_yaml.site_report.unwrap_or(false),
+            prev_next_links: docgen_yaml.prev_next_links.unwrap_or(true),
+            show_page_meta: docgen_yaml.show_page_meta.unwrap_or(false),
+            date_format: docgen_yaml
+                .date_format
+                .unwrap_or_else(|| String::from(DEFAULT_DATE_FORMAT)),
+            build_underscore_dirs: docgen_yaml.build_underscore_dirs.unwrap_or(false),
+            require_clean_git: docgen_yaml.require_clean_git.unwrap_or(false),
+            frontmatter_schema: docgen_yaml.frontmatter_schema,
+            cache_dir: project_root.join(
+                docgen_yaml
+                    .cache_dir
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR)),
+            ),
+            cache_max_age: docgen_yaml.cache_max_age.unwrap_or(DEFAULT_CACHE_MAX_AGE),
+            max_path_length: docgen_yaml
+                .max_path_length
+                .unwrap_or(DEFAULT_MAX_PATH_LENGTH),
+            max_description_length: docgen_yaml
+                .max_description_length
+                .unwrap_or(DEFAULT_MAX_DESCRIPTION_LENGTH),
+            content_header: docgen_yaml.content_header,
+            content_footer: docgen_yaml.content_footer,
+            single_file: false,
+            pretty_html: false,
+            search_synonyms: docgen_yaml.search_synonyms.unwrap_or_default(),
+            related_pages: docgen_yaml.related_pages.unwrap_or(DEFAULT_RELATED_PAGES),
+            sanitize: docgen_yaml
+                .sanitize_html
+                .as_ref()
+                .map(SanitizeHtml::mode)
+                .unwrap_or_default(),
+            search_engine: docgen_yaml
+                .search
+                .as_ref()
+                .and_then(|s| s.engine.as_deref())
+                .and_then(SearchEngine::parse)
+                .unwrap_or(SearchEngine::Elasticlunr),
+            search_preview_length: docgen_yaml
+                .search
+                .as_ref()
+                .and_then(|s| s.preview_length)
+                .unwrap_or(DEFAULT_PREVIEW_LENGTH),
+            search_max_results: docgen_yaml
+                .search
+                .as_ref()
+                .and_then(|s| s.max_results)
+                .unwrap_or(DEFAULT_SEARCH_MAX_RESULTS),
+            search_recent_count: docgen_yaml
+                .search
+                .as_ref()
+                .and_then(|s| s.recent_count)
+                .unwrap_or(DEFAULT_SEARCH_RECENT_COUNT),
+        };
+
+        Ok(config)
+    }
+
+    /// The title of the project
+    pub fn base_url(&self) -> &Option<String> {
+        &self.base_url
+    }
+
+    /// Replaces the `base_url` of the docgen.yaml, like `--base-url` does
+    pub fn set_base_url(&mut self, url: &str) -> Result<()> {
+        url::Url::parse(url)
+            .map_err(|e| Error::new(format!("Invalid base_url `{}`: {}", url, e)))?;
+        self.base_url = Some(url.to_string());
+
+        Ok(())
+    }
+
+    /// The title of the project
+    pub fn footer(&self) -> &Option<Footer> {
+        &self.footer
+    }
+
+    /// The title of the project
+    pub fn meta(&self) -> &Option<Meta> {
+        &self.meta
+    }
+
+    /// The title of the project
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The title of the project
+    pub fn subtitle(&self) -> &str {
+        &self.subtitle
+    }
+
+    /// The language pages are written in, unless they set `lang` in their
+    /// frontmatter
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The root directory of the project - the folder containing the docgen.yaml file.
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
+    /// The file the config was loaded from. Usually the docgen.yaml in the
+    /// project root, unless another file was given with `--config`.
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// The directory the HTML will get built into
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    /// The directory that contains all the Markdown documentation
+    pub fn docs_dir(&self) -> &Path {
         &self.docs_dir
     }
 
-    /// The directory that contains all the Markdown documentation
-    #[inline]
-    pub fn base_path(&self) -> &str {
-        &self.base_path
+    /// The directories from `sources` whose pages are mounted into the site
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// Whether reading the pages with both configs finds the same files,
+    /// so that a changed config doesn't need them to be read again
+    pub fn loads_same_sources(&self, other: &Config) -> bool {
+        self.docs_dir == other.docs_dir
+            && self.mounts == other.mounts
+            && self.build_underscore_dirs == other.build_underscore_dirs
+            && self.max_page_size == other.max_page_size
+    }
+
+    /// The file on disk for a path in the docs, like `handbook/intro.md`,
+    /// taking the `sources` into account
+    pub fn source_file(&self, path: &Path) -> PathBuf {
+        source_file(&self.docs_dir, &self.mounts, path)
+    }
+
+    /// The directory that contains all the Markdown documentation
+    #[inline]
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    /// Replaces the `base_path` of the docgen.yaml, like `--base-path` does.
+    /// It has to be set before the docs are loaded, since every page starts
+    /// its URI with it.
+    pub fn set_base_path(&mut self, path: &str) -> Result<()> {
+        validate_base_path(path)?;
+        self.base_path = path.to_string();
+
+        Ok(())
+    }
+
+    /// The built-in post processors to run on every page, in order
+    pub fn post_processors(&self) -> &[BuiltinProcessor] {
+        &self.post_processors
+    }
+
+    /// Whether links pointing outside of the site should open in a new tab
+    pub fn external_links_new_tab(&self) -> bool {
+        self.external_links_new_tab
+    }
+
+    /// Whether search engines should be told not to index the site
+    pub fn noindex(&self) -> bool {
+        self.noindex
+    }
+
+    /// Additional paths outside the docs directory that trigger a rebuild
+    /// when changed in `docgen serve`
+    pub fn watch_paths(&self) -> &[PathBuf] {
+        &self.watch_paths
+    }
+
+    /// How often to scan the watched paths for changes, instead of being
+    /// told about them by the file system. `None` if changes are not polled.
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval
+    }
+
+    /// Polls for changes every `interval`, like `--poll` does
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = Some(interval);
+    }
+
+    /// Analytics providers to include on every page in release builds
+    pub fn analytics(&self) -> Option<&Analytics> {
+        self.analytics.as_ref()
+    }
+
+    /// The directory of the build cache
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// How long cache entries are kept without being used. None if they
+    /// are never pruned.
+    pub fn cache_max_age(&self) -> Option<Duration> {
+        match self.cache_max_age {
+            0 => None,
+            days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+        }
+    }
+
+    /// Built files with longer paths than this, in characters, are warned
+    /// about. None if their length is not checked.
+    pub fn max_path_length(&self) -> Option<usize> {
+        match self.max_path_length {
+            0 => None,
+            length => Some(length),
+        }
+    }
+
+    /// Descriptions in frontmatter longer than this, in characters, are
+    /// warned about. None if their length is not checked.
+    pub fn max_description_length(&self) -> Option<usize> {
+        match self.max_description_length {
+            0 => None,
+            length => Some(length),
+        }
+    }
+
+    /// Markdown shown above the content of every page, unless the page sets
+    /// `content_header: false`
+    pub fn content_header(&self) -> Option<&str> {
+        self.content_header.as_deref()
+    }
+
+    /// Markdown shown below the content of every page, unless the page sets
+    /// `content_footer: false`
+    pub fn content_footer(&self) -> Option<&str> {
+        self.content_footer.as_deref()
+    }
+
+    /// Rules the frontmatter of every page is checked against
+    pub fn frontmatter_schema(&self) -> Option<&FrontmatterSchema> {
+        self.frontmatter_schema.as_ref()
+    }
+
+    /// How many related pages to show below each page. Zero turns them off.
+    pub fn related_pages(&self) -> usize {
+        self.related_pages
+    }
+
+    /// Whether every page links to the pages before and after it in the
+    /// navigation
+    pub fn prev_next_links(&self) -> bool {
+        self.prev_next_links
+    }
+
+    /// Whether pages show their authors and when they were last reviewed
+    /// under their title
+    pub fn show_page_meta(&self) -> bool {
+        self.show_page_meta
+    }
+
+    /// How dates are written on pages, in the strftime format of chrono
+    pub fn date_format(&self) -> &str {
+        &self.date_format
+    }
+
+    /// Whether Markdown files in directories starting with an underscore,
+    /// like `_include`, are built into pages
+    pub fn build_underscore_dirs(&self) -> bool {
+        self.build_underscore_dirs
+    }
+
+    /// Markdown files larger than this many bytes are skipped
+    pub fn max_page_size(&self) -> u64 {
+        self.max_page_size
+    }
+
+    /// Files copied into the site that are larger than this many bytes are
+    /// streamed from disk by the development server, instead of being kept
+    /// in memory
+    pub fn serve_stream_size(&self) -> u64 {
+        self.serve_stream_size
+    }
+
+    /// How heading anchors are derived from their titles
+    pub fn slug_style(&self) -> SlugStyle {
+        self.slug_style
+    }
+
+    /// Whether release builds warn about TODO and FIXME comments left in pages
+    pub fn warn_todo_comments(&self) -> bool {
+        self.warn_todo_comments
+    }
+
+    /// Whether the content of every page is also written as JSON
+    pub fn emit_ast(&self) -> bool {
+        self.emit_ast
+    }
+
+    /// Whether the content of every page is also written on its own, with
+    /// its title and headings as JSON, for embedding in other sites
+    pub fn emit_fragments(&self) -> bool {
+        self.emit_fragments
+    }
+
+    /// Whether the site has a page at `__report/` with an overview of the
+    /// health of the docs
+    pub fn site_report(&self) -> bool {
+        self.site_report
+    }
+
+    /// How the search box finds pages
+    pub fn search_engine(&self) -> SearchEngine {
+        self.search_engine
+    }
+
+    /// How many characters of each page are shown as a preview in search results
+    pub fn search_preview_length(&self) -> usize {
+        self.search_preview_length
+    }
+
+    /// How many results the search shows at most
+    pub fn search_max_results(&self) -> usize {
+        self.search_max_results
+    }
+
+    /// How many recently updated pages are exported for the search
+    pub fn search_recent_count(&self) -> usize {
+        self.search_recent_count
+    }
+
+    /// Extra search terms, mapped to the term they are a synonym of
+    pub fn search_synonyms(&self) -> &BTreeMap<String, String> {
+        &self.search_synonyms
+    }
+
+    /// Whether every page is built into its own directory as `index.html`
+    pub fn clean_urls(&self) -> bool {
+        self.clean_urls
+    }
+
+    /// Whether links between pages and to assets are relative to the page
+    /// they are on, instead of starting from the `base_path`
+    pub fn relative_links(&self) -> bool {
+        self.relative_links
+    }
+
+    /// Whether only the first of several navigation links to the same page
+    /// is kept
+    pub fn dedupe_navigation(&self) -> bool {
+        self.dedupe_navigation
+    }
+
+    /// Where navigation links get their titles from
+    pub fn nav_title_source(&self) -> NavTitleSource {
+        self.nav_title_source
+    }
+
+    /// Whether files in `_include` may be symlinks to files outside the project
+    pub fn allow_external_includes(&self) -> bool {
+        self.allow_external_includes
+    }
+
+    /// Whether prose should use typographic quotes, dashes and ellipses
+    pub fn smart_punctuation(&self) -> bool {
+        self.smart_punctuation
+    }
+
+    /// Whether headings are numbered, like `1.` and `1.1`
+    pub fn number_headings(&self) -> bool {
+        self.number_headings
+    }
+
+    /// Options used when parsing the Markdown of every document
+    pub fn markdown_options(&self) -> ParseOptions {
+        let mut opts = ParseOptions::default();
+        opts.url_root = self.base_path.clone();
+        opts.base_url = self.base_url.clone();
+        opts.smart_punctuation = self.smart_punctuation;
+        opts.number_headings = self.number_headings;
+        opts.number_captions = self.number_captions;
+        opts.clean_urls = self.clean_urls;
+        opts.relative_links = self.relative_links;
+        opts.preview_length = self.search_preview_length;
+        opts.line_numbers = self.line_numbers;
+        opts.max_highlight_lines = self.max_highlight_lines;
+        opts.max_highlight_size = self.max_highlight_size;
+        opts.max_highlight_line_size = self.max_highlight_line_size;
+        opts.interactive_checklists = self.interactive_checklists;
+        opts.slug_style = self.slug_style;
+        opts.emit_ast = self.emit_ast;
+        opts.external_links_new_tab = self.external_links_new_tab;
+        opts.docs_dir = Some(self.docs_dir.clone());
+        opts.sanitize = self.sanitize.clone();
+        opts.disabled_extensions = self.disabled_extensions.clone();
+        opts
+    }
+
+    /// Rules that set the site navigation structure
+    pub fn navigation(&self) -> Option<&[NavRule]> {
+        self.navigation.as_deref()
+    }
+
+    /// Port to serve the development server on
+    pub fn addr(&self) -> SocketAddr {
+        self.preview_addr
+    }
+
+    /// Port to serve the livereload server on
+    pub fn livereload_addr(&self) -> SocketAddr {
+        self.livereload_addr
+    }
+
+    /// Finds free ports for the preview and livereload servers, starting
+    /// from `port` or the configured one for the preview server. Pages built
+    /// afterwards point livereload.js at the port it really listens on.
+    ///
+    /// Returns why a server isn't on the port it was given.
+    pub fn resolve_server_addrs(&mut self, port: Option<u16>) -> Result<Vec<String>> {
+        let (addrs, notes) = resolve_server_addrs(
+            &self.preview_addr.ip().to_string(),
+            port.unwrap_or_else(|| self.preview_addr.port()),
+            self.livereload_addr.port(),
+        )?;
+
+        self.preview_addr = addrs.preview;
+        self.livereload_addr = addrs.livereload;
+
+        Ok(notes)
+    }
+
+    /// When to color what the commands print, from `--color`
+    pub fn color_mode(&self) -> ColorMode {
+        self.color
+    }
+
+    pub fn allow_failed_checks(&self) -> bool {
+        self.allow_failed_checks
+    }
+
+    /// Whether warnings should fail the build
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn set_strict(&mut self) {
+        self.strict = true
+    }
+
+    /// Whether release builds fail when the docs have uncommitted changes
+    pub fn require_clean_git(&self) -> bool {
+        self.require_clean_git
+    }
+
+    /// Whether release builds go ahead despite uncommitted changes, even
+    /// with `require_clean_git`
+    pub fn allow_dirty(&self) -> bool {
+        self.allow_dirty
+    }
+
+    pub fn set_allow_dirty(&mut self) {
+        self.allow_dirty = true
+    }
+
+    /// How long to wait for another build of the project to finish before
+    /// giving up. Without it, a build fails right away.
+    pub fn lock_wait(&self) -> Option<Duration> {
+        self.lock_wait
+    }
+
+    pub fn set_lock_wait(&mut self, timeout: Duration) {
+        self.lock_wait = Some(timeout)
+    }
+
+    /// Whether a single file is previewed, without a project
+    pub fn single_file(&self) -> bool {
+        self.single_file
+    }
+
+    pub fn set_title<S: Into<String>>(&mut self, title: S) {
+        self.title = title.into();
+    }
+
+    pub fn set_emit_ast(&mut self) {
+        self.emit_ast = true
+    }
+
+    /// Whether the HTML of every page is indented, one block element per line
+    pub fn pretty_html(&self) -> bool {
+        self.pretty_html
+    }
+
+    /// Indents the HTML of every page. Minified pages can't be indented as
+    /// well, so this fails when the `minify` post processor is turned on.
+    pub fn set_pretty_html(&mut self) -> Result<()> {
+        if self.post_processors.contains(&BuiltinProcessor::Minify) {
+            return Err(Error::new(
+                "Pages can't be pretty-printed and minified at the same time. \
+                 Remove `minify` from `post_processors` to use `--pretty-html`",
+            ));
+        }
+
+        self.pretty_html = true;
+        Ok(())
+    }
+
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.color = mode
+    }
+
+    pub fn set_allow_failed_checks(&mut self) {
+        self.allow_failed_checks = true
+    }
+
+    pub fn build_mode(&self) -> BuildMode {
+        self.build_mode
+    }
+
+    pub fn set_build_mode(&mut self, mode: BuildMode) {
+        self.build_mode = mode;
+    }
+
+    /// URI path to a logo that will show up at the top left next to the title
+    pub fn logo(&self) -> Option<&str> {
+        self.logo.as_deref()
+    }
+
+    /// Where the "Edit this page" link of a page points. Pages can point it
+    /// elsewhere with `edit_url`, or leave it out with `edit: false`.
+    pub fn build_edit_link(&self, doc: &Document) -> Option<String> {
+        if !doc.editable() {
+            return None;
+        }
+        if let Some(edit_url) = doc.edit_url() {
+            return Some(edit_url);
+        }
+        if let Some(mount) = self
+            .mounts
+            .iter()
+            .find(|mount| doc.path.starts_with(&mount.prefix))
+        {
+            let path = doc.path.strip_prefix(&mount.prefix).unwrap();
+            let edit_root = match (&mount.edit_root, &self.edit_root) {
+                (Some(edit_root), _) => Path::new(edit_root).to_path_buf(),
+                (None, Some(edit_root)) => Path::new(edit_root).join(
+                    mount
+                        .dir
+                        .strip_prefix(&self.project_root)
+                        .unwrap_or(&mount.dir),
+                ),
+                (None, None) => return None,
+            };
+            return Some(
+                edit_root
+                    .join(path)
+                    .as_os_str()
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+        if let Some(edit_root) = &self.edit_root {
+            return Some(
+                Path::new(edit_root)
+                    .join(self.docs_dir.file_name().unwrap())
+                    .join(&doc.path)
+                    .as_os_str()
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+        None
+    }
+}
+
+pub fn project_root() -> Option<PathBuf> {
+    let mut current_dir = std::env::current_dir().expect("Unable to determine current directory");
+
+    loop {
+        // If we are in the root dir, just return it
+        if current_dir.join("docgen.yaml").exists() || current_dir.join("docgen.yml").exists() {
+            return Some(current_dir);
+        }
+
+        if let Some(parent) = current_dir.parent() {
+            current_dir = parent.to_path_buf();
+        } else {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    extern crate indoc;
+
+    #[test]
+    fn validate_logo() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            logo: i-do-not-exist.png
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains("Could not find logo specified in docgen.yaml"),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn validate_base_path() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            base_path: not/absolute
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        println!("{:?}", error);
+
+        assert!(
+            format!("{}", error)
+                .contains("base_path was not valid absolute URI path. Got `not/absolute`"),
+            "Got incorrect error message: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn validate_default_base_path() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+        "};
+
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+
+        assert_eq!(config.base_path(), "/");
+    }
+
+    #[test]
+    fn base_path_and_base_url_can_be_replaced() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            base_path: /docs/
+            base_url: https://docs.example.com
+        "};
+
+        let mut config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        config.set_base_path("/preview/pr-1234/").unwrap();
+        config.set_base_url("https://preview.example.com").unwrap();
+
+        assert_eq!(config.base_path(), "/preview/pr-1234/");
+        assert_eq!(
+            config.base_url().as_deref(),
+            Some("https://preview.example.com")
+        );
+        assert_eq!(config.markdown_options().url_root, "/preview/pr-1234/");
+    }
+
+    #[test]
+    fn replaced_base_paths_are_validated_like_the_docgen_yaml() {
+        let mut config = Config::from_yaml_str(Path::new(""), "title: The Title", false).unwrap();
+
+        let error = config.set_base_path("/preview/pr-1234").unwrap_err();
+        assert!(
+            format!("{}", error).contains("Base path must end with /. Got `/preview/pr-1234`."),
+            "Error message was: {}",
+            error
+        );
+
+        let error = config.set_base_path("preview/").unwrap_err();
+        assert!(
+            format!("{}", error)
+                .contains("base_path was not valid absolute URI path. Got `preview/`"),
+            "Error message was: {}",
+            error
+        );
+
+        let error = config.set_base_url("not a url").unwrap_err();
+        assert!(
+            format!("{}", error).contains("Invalid base_url `not a url`"),
+            "Error message was: {}",
+            error
+        );
+
+        assert_eq!(config.base_path(), "/");
+        assert_eq!(config.base_url(), &None);
     }
 
-    /// Rules that set the site navigation structure
-    pub fn navigation(&self) -> Option<&[NavRule]> {
-        self.navigation.as_deref()
+    #[test]
+    fn validate_robots() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            robots: sometimes
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains(
+                "Invalid value for robots. Found 'sometimes', expected \"index\" or \"noindex\""
+            ),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn robots_noindex() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            robots: noindex
+        "};
+
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+
+        assert!(config.noindex());
+    }
+
+    #[test]
+    fn validate_nav_title_source() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            nav_title_source: path
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains("Invalid value for nav_title_source. Found 'path'"),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn nav_title_source_defaults_to_frontmatter() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.nav_title_source(), NavTitleSource::Frontmatter);
+
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            nav_title_source: filename
+        "};
+
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.nav_title_source(), NavTitleSource::Filename);
+    }
+
+    #[test]
+    fn extensions_can_be_turned_off() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            extensions:
+              tabs: false
+              emoji: false
+              math: true
+        "};
+
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        let mut disabled = config.markdown_options().disabled_extensions;
+        disabled.sort_by_key(|ext| ext.name());
+        assert_eq!(
+            disabled,
+            vec![BuiltinExtension::Emoji, BuiltinExtension::Tabs]
+        );
+
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            extensions:
+              emojis: false
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(
+            format!("{}", error).contains(
+                "Invalid value for extensions. Found 'emojis', expected \"callouts\", \
+                 \"attributions\", \"mermaid\", \"math\", \"tabs\", \"task_lists\", \"emoji\" \
+                 or \"figures\""
+            ),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn external_links_new_tab() {
+        let yaml = "---\ntitle: The Title\nexternal_links_new_tab: true\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+
+        assert!(config.markdown_options().external_links_new_tab);
+        assert!(config.post_processors().is_empty());
+    }
+
+    #[test]
+    fn post_processors() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            post_processors: [minify]
+        "};
+
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.post_processors(), &[BuiltinProcessor::Minify]);
+
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            post_processors: [compress]
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(
+            format!("{}", error).contains("Invalid value for post_processors. Found 'compress'"),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn language_defaults_to_english() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.language(), "en");
+
+        let yaml = "---\ntitle: The Title\nlanguage: es\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.language(), "es");
+    }
+
+    #[test]
+    fn language_tags() {
+        for tag in &["en", "de", "pt-BR", "zh-Hant-TW", "es-419", "sgn-ase"] {
+            assert!(is_language_tag(tag), "{}", tag);
+        }
+
+        for tag in &[
+            "",
+            "e",
+            "english please",
+            "en_US",
+            "en-",
+            "-en",
+            "12",
+            "en-toolongsubtag",
+        ] {
+            assert!(!is_language_tag(tag), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn validate_analytics_script_url() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            analytics:
+              custom:
+                script_url: not a url
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains("Invalid analytics.custom.script_url"),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn analytics_script_tags_are_escaped() {
+        let yaml = indoc! {r#"
+            ---
+            title: The Title
+            analytics:
+              plausible:
+                domain: docs.example.com
+              custom:
+                script_url: https://stats.example.com/s.js?a=1&b=2
+                data_attrs:
+                  site: 'a "quoted" <value>'
+        "#};
+
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        let tags = config.analytics().unwrap().script_tags();
+
+        assert_eq!(
+            tags,
+            "<script defer data-domain=\"docs.example.com\" \
+             src=\"https://plausible.io/js/script.js\"></script>\
+             <script defer data-site=\"a &quot;quoted&quot; &lt;value&gt;\" \
+             src=\"https://stats.example.com/s.js?a=1&amp;b=2\"></script>"
+        );
+    }
+
+    #[test]
+    fn related_pages() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.related_pages(), 5);
+
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            related_pages: 0
+        "};
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.related_pages(), 0);
+    }
+
+    #[test]
+    fn prev_next_links() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(config.prev_next_links());
+
+        let yaml = "---\ntitle: The Title\nprev_next_links: false\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(!config.prev_next_links());
+    }
+
+    #[test]
+    fn page_meta() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(!config.show_page_meta());
+        assert_eq!(config.date_format(), "%B %-d, %Y");
+
+        let yaml = "---\ntitle: The Title\nshow_page_meta: true\ndate_format: \"%d.%m.%Y\"\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(config.show_page_meta());
+        assert_eq!(config.date_format(), "%d.%m.%Y");
+
+        let yaml = "---\ntitle: The Title\ndate_format: \"%Y-%Q\"\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error).contains("Invalid value for date_format. Found '%Y-%Q'"));
+
+        // A valid specifier, but review dates have no time to format
+        let yaml = "---\ntitle: The Title\ndate_format: \"%d.%m.%Y %H:%M\"\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(
+            format!("{}", error).contains("Invalid value for date_format. Found '%d.%m.%Y %H:%M'"),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn cache() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(
+            config.cache_dir(),
+            Path::new("project").join(".docgen-cache")
+        );
+        assert_eq!(
+            config.cache_max_age(),
+            Some(Duration::from_secs(30 * 24 * 60 * 60))
+        );
+
+        let yaml = "---\ntitle: The Title\ncache_dir: /tmp/docgen\ncache_max_age: 0\n";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        assert_eq!(config.cache_dir(), Path::new("/tmp/docgen"));
+        assert_eq!(config.cache_max_age(), None);
+    }
+
+    #[test]
+    fn max_path_length() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: The Title\n", false).unwrap();
+        if cfg!(windows) {
+            assert_eq!(config.max_path_length(), Some(260));
+        } else {
+            assert_eq!(config.max_path_length(), None);
+        }
+
+        let yaml = "---\ntitle: The Title\nmax_path_length: 200\n";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        assert_eq!(config.max_path_length(), Some(200));
+
+        let yaml = "---\ntitle: The Title\nmax_path_length: 0\n";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        assert_eq!(config.max_path_length(), None);
+    }
+
+    #[test]
+    fn max_description_length() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.max_description_length(), Some(160));
+
+        let yaml = "---\ntitle: The Title\nmax_description_length: 0\n";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        assert_eq!(config.max_description_length(), None);
+    }
+
+    #[test]
+    fn frontmatter_schema() {
+        let yaml = indoc! {r#"
+            ---
+            title: The Title
+            frontmatter_schema:
+              keys:
+                owner:
+                  required: true
+                  pattern: "^@"
+        "#};
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        let schema = config.frontmatter_schema().unwrap();
+        assert!(schema.keys["owner"].required);
+        assert!(schema.keys["owner"]
+            .regex
+            .as_ref()
+            .unwrap()
+            .is_match("@docs"));
+        assert!(!schema.deny_unknown_keys);
+
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            frontmatter_schema:
+              keys:
+                owner:
+                  type: number
+        "};
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error)
+            .contains("Invalid value for frontmatter_schema.keys.owner.type. Found 'number'"));
+
+        let yaml = indoc! {r#"
+            ---
+            title: The Title
+            frontmatter_schema:
+              keys:
+                owner:
+                  pattern: "(@"
+        "#};
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error)
+            .contains("Invalid value for frontmatter_schema.keys.owner.pattern. Found '(@'"));
+        // Pages can't be checked against it either way
+        assert!(Config::from_yaml_str(Path::new(""), yaml, true).is_err());
+    }
+
+    #[test]
+    fn max_page_size() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.max_page_size(), 5 * 1024 * 1024);
+
+        let yaml = "---\ntitle: The Title\nmax_page_size: 20\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.max_page_size(), 20 * 1024 * 1024);
+
+        // Too large to count in bytes, so there is no limit
+        let yaml = format!("---\ntitle: The Title\nmax_page_size: {}\n", u64::MAX);
+        let config = Config::from_yaml_str(Path::new(""), &yaml, false).unwrap();
+        assert_eq!(config.max_page_size(), u64::MAX);
+
+        let yaml = "---\ntitle: The Title\nmax_page_size: 0\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error).contains("Invalid value for max_page_size. Found '0'"));
+    }
+
+    #[test]
+    fn max_highlight_limits() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        let options = config.markdown_options();
+        assert_eq!(options.max_highlight_lines, 2000);
+        assert_eq!(options.max_highlight_size, 100 * 1024);
+        assert_eq!(options.max_highlight_line_size, 4 * 1024);
+
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            max_highlight_lines: 500
+            max_highlight_size: 20
+            max_highlight_line_size: 16
+        "};
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        let options = config.markdown_options();
+        assert_eq!(options.max_highlight_lines, 500);
+        assert_eq!(options.max_highlight_size, 20 * 1024);
+        assert_eq!(options.max_highlight_line_size, 16 * 1024);
+
+        let yaml = "---\ntitle: The Title\nmax_highlight_lines: 0\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error).contains("Invalid value for max_highlight_lines. Found '0'"));
+    }
+
+    #[test]
+    fn serve_stream_size() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.serve_stream_size(), 1024 * 1024);
+
+        let yaml = "---\ntitle: The Title\nserve_stream_size: 64\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.serve_stream_size(), 64 * 1024);
+
+        let yaml = format!("---\ntitle: The Title\nserve_stream_size: {}\n", u64::MAX);
+        let config = Config::from_yaml_str(Path::new(""), &yaml, false).unwrap();
+        assert_eq!(config.serve_stream_size(), u64::MAX);
     }
 
-    /// Port to serve the development server on
-    pub fn addr(&self) -> SocketAddr {
-        self.preview_addr
+    #[test]
+    fn slug_style() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.slug_style(), SlugStyle::Default);
+
+        let yaml = "---\ntitle: The Title\nslug_style: github\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.slug_style(), SlugStyle::Github);
+        assert_eq!(config.markdown_options().slug_style, SlugStyle::Github);
+
+        let yaml = "---\ntitle: The Title\nslug_style: gitlab\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error).contains("Invalid value for slug_style. Found 'gitlab'"));
     }
 
-    /// Port to serve the livereload server on
-    pub fn livereload_addr(&self) -> SocketAddr {
-        self.livereload_addr
-    }
+    #[test]
+    fn warn_todo_comments() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(!config.warn_todo_comments());
 
-    pub fn color_enabled(&self) -> bool {
-        self.color
+        let yaml = "---\ntitle: The Title\nwarn_todo_comments: true\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(config.warn_todo_comments());
     }
 
-    pub fn allow_failed_checks(&self) -> bool {
-        self.allow_failed_checks
-    }
+    #[test]
+    fn emit_ast() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(!config.emit_ast());
+        assert!(!config.markdown_options().emit_ast);
 
-    pub fn disable_colors(&mut self) {
-        self.color = false
+        let yaml = "---\ntitle: The Title\nemit_ast: true\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(config.emit_ast());
+        assert!(config.markdown_options().emit_ast);
     }
 
-    pub fn set_allow_failed_checks(&mut self) {
-        self.allow_failed_checks = true
-    }
+    #[test]
+    fn emit_fragments() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(!config.emit_fragments());
 
-    pub fn build_mode(&self) -> BuildMode {
-        self.build_mode
+        let yaml = "---\ntitle: The Title\nemit_fragments: true\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(config.emit_fragments());
     }
 
-    pub fn set_build_mode(&mut self, mode: BuildMode) {
-        self.build_mode = mode;
-    }
+    #[test]
+    fn site_report() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(!config.site_report());
 
-    /// URI path to a logo that will show up at the top left next to the title
-    pub fn logo(&self) -> Option<&str> {
-        self.logo.as_deref()
+        let yaml = "---\ntitle: The Title\nsite_report: true\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(config.site_report());
     }
 
-    /// URI path to a logo that will show up at the top left next to the title
-    pub fn build_edit_link(&self, doc_path: &PathBuf) -> Option<String> {
-        if let Some(edit_root) = &self.edit_root {
-            return Some(
-                Path::new(edit_root)
-                    .join(self.docs_dir.file_name().unwrap())
-                    .join(doc_path)
-                    .as_os_str()
-                    .to_string_lossy()
-                    .to_string(),
-            );
-        }
-        None
+    #[test]
+    fn pretty_html_cannot_be_combined_with_minify() {
+        let mut config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert!(!config.pretty_html());
+        config.set_pretty_html().unwrap();
+        assert!(config.pretty_html());
+
+        let yaml = "---\ntitle: The Title\npost_processors:\n  - minify\n";
+        let mut config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(config.set_pretty_html().is_err());
+        assert!(!config.pretty_html());
     }
-}
 
-pub fn project_root() -> Option<PathBuf> {
-    let mut current_dir = std::env::current_dir().expect("Unable to determine current directory");
+    #[test]
+    fn search_limits() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            search:
+              preview_length: 300
+              max_results: 20
+              recent_count: 3
+        "};
 
-    loop {
-        // If we are in the root dir, just return it
-        if current_dir.join("docgen.yaml").exists() || current_dir.join("docgen.yml").exists() {
-            return Some(current_dir);
-        }
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
 
-        if let Some(parent) = current_dir.parent() {
-            current_dir = parent.to_path_buf();
-        } else {
-            return None;
-        }
+        assert_eq!(config.search_preview_length(), 300);
+        assert_eq!(config.search_max_results(), 20);
+        assert_eq!(config.search_recent_count(), 3);
+        assert_eq!(config.markdown_options().preview_length, 300);
+        assert_eq!(config.search_engine(), SearchEngine::Elasticlunr);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn search_engine() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            search:
+              engine: simple
+        "};
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.search_engine(), SearchEngine::Simple);
 
-    extern crate indoc;
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            search:
+              engine: lunr
+        "};
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(
+            format!("{}", error).contains(
+                "Invalid value for search.engine. Found 'lunr', \
+                 expected \"elasticlunr\" or \"simple\""
+            ),
+            "Error message was: {}",
+            error
+        );
+    }
 
     #[test]
-    fn validate_logo() {
+    fn validate_search_limits() {
         let yaml = indoc! {"
             ---
             title: The Title
-            logo: i-do-not-exist.png
+            search:
+              max_results: 0
         "};
 
         let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
 
         assert!(
-            format!("{}", error).contains("Could not find logo specified in docgen.yaml"),
+            format!("{}", error).contains(
+                "Invalid value for search.max_results. Found '0', \
+                 expected a number between 1 and 100"
+            ),
             "Error message was: {}",
             error
         );
     }
 
     #[test]
-    fn validate_base_path() {
+    fn sanitize_html() {
+        let config = Config::from_yaml_str(Path::new(""), "---\ntitle: Title", false).unwrap();
+        assert!(matches!(
+            config.markdown_options().sanitize,
+            SanitizeMode::Strict
+        ));
+
         let yaml = indoc! {"
             ---
             title: The Title
-            base_path: not/absolute
+            sanitize_html:
+              tags: [iframe]
+              attributes:
+                iframe: [src, allowfullscreen]
+                '*': [title]
         "};
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        match config.markdown_options().sanitize {
+            SanitizeMode::Custom(custom) => {
+                assert_eq!(custom.tags, vec!["iframe"]);
+                assert_eq!(
+                    custom.tag_attributes["iframe"],
+                    vec!["src", "allowfullscreen"]
+                );
+                assert_eq!(custom.generic_attributes, vec!["title"]);
+            }
+            mode => panic!("Sanitized with {:?}", mode),
+        }
 
-        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        let yaml = "---\ntitle: The Title\nsanitize_html:\n  enabled: false\n";
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert!(matches!(
+            config.markdown_options().sanitize,
+            SanitizeMode::Off
+        ));
+    }
 
-        println!("{:?}", error);
+    #[test]
+    fn validate_sanitize_html() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            sanitize_html:
+              enabled: false
+              tags: [iframe]
+        "};
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(
+            format!("{}", error).contains(
+                "Invalid value for sanitize_html. Found `tags` or `attributes` with \
+                 `enabled: false`"
+            ),
+            "Error message was: {}",
+            error
+        );
 
+        let yaml = "---\ntitle: The Title\nsanitize_html:\n  tags: [Script]\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
         assert!(
             format!("{}", error)
-                .contains("base_path was not valid absolute URI path. Got `not/absolute`"),
-            "Got incorrect error message: {}",
+                .contains("Invalid value for sanitize_html.tags. Found 'Script', which is always"),
+            "Error message was: {}",
             error
         );
     }
 
     #[test]
-    fn validate_default_base_path() {
+    fn validate_watch_paths() {
         let yaml = indoc! {"
             ---
             title: The Title
+            watch_paths:
+              - i-do-not-exist
         "};
 
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains("Could not find watch path specified in docgen.yaml"),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn validate_sources() {
+        let root = std::env::temp_dir().join(format!("docgen-sources-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs").join("guides")).unwrap();
+        fs::create_dir_all(root.join("handbook")).unwrap();
+        fs::create_dir_all(root.join("policies")).unwrap();
+        let error = |sources: &str| {
+            let yaml = format!("---\ntitle: The Title\nsources:\n{}", sources);
+            Config::from_yaml_str(&root, &yaml, false)
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default()
+        };
+
+        let valid = error("  - path: handbook\n    mount: handbook\n");
+        let overlap = error(indoc! {"
+              - path: handbook
+                mount: company
+              - path: policies
+                mount: company/policies
+        "});
+        let collision = error("  - path: handbook\n    mount: guides\n");
+        let inside = error("  - path: docs/guides\n    mount: more\n");
+        let missing = error("  - path: i-do-not-exist\n    mount: more\n");
+        let outside = error("  - path: handbook\n    mount: ../handbook\n");
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(valid, "");
+        assert!(
+            overlap.contains("are mounted at `company` and `company/policies`, which overlap"),
+            "Error message was: {}",
+            overlap
+        );
+        assert!(
+            collision.contains("is mounted at `guides`, which collides with"),
+            "Error message was: {}",
+            collision
+        );
+        assert!(
+            inside.contains("overlaps the docs directory"),
+            "Error message was: {}",
+            inside
+        );
+        assert!(
+            missing.contains("Could not find source directory specified in docgen.yaml"),
+            "Error message was: {}",
+            missing
+        );
+        assert!(
+            outside.contains("Invalid mount for the source handbook. Found '../handbook'"),
+            "Error message was: {}",
+            outside
+        );
+    }
+
+    #[test]
+    fn settings_that_change_which_pages_are_read() {
+        let config = |yaml: &str| {
+            Config::from_yaml_str(Path::new("project"), &format!("---\n{}", yaml), true).unwrap()
+        };
+        let base = config("title: The Title\n");
+
+        assert!(base.loads_same_sources(&config("title: Another Title\n")));
+        assert!(!base.loads_same_sources(&config("title: The Title\ndocs_dir: notes\n")));
+        assert!(
+            !base.loads_same_sources(&config("title: The Title\nbuild_underscore_dirs: true\n"))
+        );
+        assert!(!base.loads_same_sources(&config("title: The Title\nmax_page_size: 1\n")));
+    }
+
+    #[test]
+    fn poll_interval() {
+        let config =
+            Config::from_yaml_str(Path::new(""), "---\ntitle: The Title\n", false).unwrap();
+        assert_eq!(config.poll_interval(), None);
+
+        let yaml = "---\ntitle: The Title\npoll_interval: 500\n";
         let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        assert_eq!(config.poll_interval(), Some(Duration::from_millis(500)));
 
-        assert_eq!(config.base_path(), "/");
+        let yaml = "---\ntitle: The Title\npoll_interval: 0\n";
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+        assert!(format!("{}", error).contains("Invalid value for poll_interval. Found '0'"));
     }
 
     #[test]
@@ -523,12 +2853,14 @@ mod test {
     fn convert_navigation_input_to_rules_file() {
         let input = vec![Navigation {
             path: PathBuf::from("docs").join("README.md"),
+            title: None,
+            sort: None,
             children: None,
         }];
 
         assert_eq!(
             NavRule::from_yaml_input(input),
-            vec![NavRule::File(PathBuf::from("docs").join("README.md"))]
+            vec![NavRule::File(PathBuf::from("docs").join("README.md"), None)]
         );
     }
 
@@ -536,12 +2868,18 @@ mod test {
     fn convert_navigation_input_to_rules_directory_no_children() {
         let input = vec![Navigation {
             path: PathBuf::from("docs").join("features"), // TODO: Make not rely on our docs
+            title: None,
+            sort: None,
             children: None,
         }];
 
         assert_eq!(
             NavRule::from_yaml_input(input),
-            vec![NavRule::Dir(PathBuf::from("docs").join("features"), None)]
+            vec![NavRule::Dir(
+                PathBuf::from("docs").join("features"),
+                None,
+                None
+            )]
         );
     }
 
@@ -549,6 +2887,8 @@ mod test {
     fn convert_navigation_input_to_rules_directory_wildcard_children() {
         let input = vec![Navigation {
             path: PathBuf::from("docs").join("features"), // TODO: Make not rely on our docs
+            title: None,
+            sort: None,
             children: Some(NavChildren::WildCard(String::from("*"))),
         }];
 
@@ -556,7 +2896,8 @@ mod test {
             NavRule::from_yaml_input(input),
             vec![NavRule::Dir(
                 PathBuf::from("docs").join("features"),
-                Some(DirIncludeRule::WildCard)
+                Some(DirIncludeRule::WildCard),
+                None
             )]
         );
     }
@@ -565,8 +2906,12 @@ mod test {
     fn convert_navigation_input_to_rules_directory_explicit_children() {
         let input = vec![Navigation {
             path: PathBuf::from("docs").join("features"), // TODO: Make not rely on our docs
+            title: None,
+            sort: None,
             children: Some(NavChildren::List(vec![Navigation {
                 path: PathBuf::from("docs").join("features").join("markdown.md"),
+                title: Some(String::from("Markdown")),
+                sort: None,
                 children: None,
             }])),
         }];
@@ -575,10 +2920,186 @@ mod test {
             NavRule::from_yaml_input(input),
             vec![NavRule::Dir(
                 PathBuf::from("docs").join("features"),
-                Some(DirIncludeRule::Explicit(vec![NavRule::File(
-                    PathBuf::from("docs").join("features").join("markdown.md")
-                )]))
+                Some(DirIncludeRule::Explicit(
+                    vec![NavRule::File(
+                        PathBuf::from("docs").join("features").join("markdown.md"),
+                        Some(String::from("Markdown"))
+                    )],
+                    NavSort::Manual
+                )),
+                None
             )]
         );
     }
+
+    #[test]
+    fn validate_navigation_title_on_wildcard() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            navigation:
+              - path: features
+                title: Features
+                children: \"*\"
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error)
+                .contains("Navigation entry features has a title and wildcard children."),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn validate_navigation_sort() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            navigation:
+              - path: features
+                sort: random
+                children:
+                  - path: features/markdown.md
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains(
+                "Invalid value for navigation sort. Found 'random', \
+                 expected \"index\", \"alpha\" or \"manual\""
+            ),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    fn rendered_footer(yaml: &str) -> Footer {
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+
+        config.footer().as_ref().unwrap().rendered(2024, "/")
+    }
+
+    #[test]
+    fn footer_links_follow_the_base_path() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            base_path: /docs/
+            footer:
+              groups:
+                - title: Guides
+                  links:
+                    - href: /guides/setup
+                      title: Setup
+                    - href: https://github.com/acme
+                      title: GitHub
+        "};
+        let config = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        let footer = config.footer().as_ref().unwrap();
+
+        let hrefs = |root| {
+            footer.rendered(2024, root).groups.unwrap()[0]
+                .links
+                .iter()
+                .map(|link| link.href.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            hrefs(config.base_path()),
+            vec!["/docs/guides/setup", "https://github.com/acme"]
+        );
+        assert_eq!(
+            hrefs("../"),
+            vec!["../guides/setup", "https://github.com/acme"]
+        );
+    }
+
+    #[test]
+    fn footer_copyright_with_a_link() {
+        insta::assert_debug_snapshot!(rendered_footer(indoc! {"
+            ---
+            title: The Title
+            footer:
+              copyright: \"© Acme — [Privacy](https://acme.com/privacy)\"
+        "}));
+    }
+
+    #[test]
+    fn footer_year_placeholder() {
+        insta::assert_debug_snapshot!(rendered_footer(indoc! {"
+            ---
+            title: The Title
+            footer:
+              copyright: \"© 2019-{year} *Acme*\"
+              groups:
+                - title: Code
+                  links:
+                    - href: https://github.com/acme
+                      title: \"`acme` on GitHub\"
+        "}));
+    }
+
+    #[test]
+    fn footer_docgen_version_variable() {
+        let footer = rendered_footer(indoc! {"
+            ---
+            title: The Title
+            footer:
+              copyright: \"Built with docgen {{ docgen_version }}\"
+        "});
+
+        assert_eq!(
+            footer.copyright.unwrap(),
+            format!("Built with docgen {}", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn footer_text_only_group() {
+        insta::assert_debug_snapshot!(rendered_footer(indoc! {"
+            ---
+            title: The Title
+            footer:
+              groups:
+                - title: About
+                  text: Docs for the [Acme](https://acme.com) platform.
+        "}));
+    }
+
+    #[test]
+    fn validate_footer_markdown() {
+        let yaml = indoc! {"
+            ---
+            title: The Title
+            footer:
+              copyright: \"# Acme\"
+        "};
+
+        let error = Config::from_yaml_str(Path::new(""), yaml, false).unwrap_err();
+
+        assert!(
+            format!("{}", error).contains(
+                "Invalid value for footer.copyright. Found '# Acme', \
+                 expected a single line of text."
+            ),
+            "Error message was: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn single_files_are_previewed_from_their_directory() {
+        let config = Config::for_file(&Path::new("notes").join("draft.md")).unwrap();
+
+        assert!(config.single_file());
+        assert_eq!(config.title(), "draft.md");
+        assert_eq!(config.docs_dir(), Path::new("notes"));
+        assert_eq!(config.project_root(), Path::new("notes"));
+        assert_eq!(config.base_path(), "/");
+    }
 }
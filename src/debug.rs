@@ -0,0 +1,158 @@
+//! `docgen debug`, for finding out why a page renders the way it does
+
+use std::path::Path;
+
+use crate::config::Config;
+use crate::markdown::parser::TraceEntry;
+use crate::{docs_finder, Document, Error, Result, SourceDocument};
+
+pub struct DebugCommand {}
+
+impl DebugCommand {
+    /// Renders a single page the way a build would, and prints which
+    /// extension handled each of its Markdown events, followed by its HTML.
+    /// Nothing is built or written.
+    pub fn render(config: Config, file: &Path) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+
+        if !file.is_file() {
+            return Err(Error::new(format!(
+                "Could not find a file to render at {}",
+                file.display()
+            )));
+        }
+        let file = file
+            .canonicalize()
+            .map_err(|e| Error::io(e, format!("Could not read {}", file.display())))?;
+        let docs_dir = config
+            .docs_dir()
+            .canonicalize()
+            .map_err(|e| Error::io(e, format!("Could not read {}", config.docs_dir().display())))?;
+        // Pages of the `sources` are mounted under their path in the site
+        let mounted = config.mounts().iter().find_map(|mount| {
+            let rest = file.strip_prefix(mount.dir.canonicalize().ok()?).ok()?;
+            Some(mount.prefix.join(rest))
+        });
+        let path = match mounted {
+            Some(path) => path,
+            None => file
+                .strip_prefix(&docs_dir)
+                .map_err(|_| {
+                    Error::new(format!(
+                        "{} is not in the docs directory, {}",
+                        file.display(),
+                        docs_dir.display()
+                    ))
+                })?
+                .to_path_buf(),
+        };
+        let path = path.as_path();
+
+        // Other pages are loaded too, for the include directives of this one
+        let sources = if config.single_file() {
+            docs_finder::Sources {
+                documents: vec![SourceDocument::load(&file, path, config.max_page_size())
+                    .map_err(|warning| Error::new(warning.message))?],
+                ..Default::default()
+            }
+        } else {
+            docs_finder::load(&config)
+        };
+        let source = sources
+            .documents
+            .iter()
+            .find(|source| source.path() == path)
+            .ok_or_else(|| {
+                Error::new(format!(
+                    "{} is not a page of the site, and can't be rendered",
+                    path.display()
+                ))
+            })?;
+
+        let mut options = docs_finder::markdown_options(&sources, &config);
+        options.trace = true;
+        let doc = Document::render(source, &options);
+
+        bunt::writeln!(
+            stdout,
+            "{$bold}{$blue}Docgen | Trace of {}{/$}{/$}\n",
+            path.display()
+        )?;
+        bunt::write!(
+            stdout,
+            "{}",
+            format_trace(doc.markdown.trace.as_deref().unwrap_or_default())
+        )?;
+        bunt::writeln!(stdout, "\n{$bold}HTML{/$}\n")?;
+        bunt::writeln!(stdout, "{}", doc.html())?;
+
+        Ok(())
+    }
+}
+
+/// Every event on a line of its own, followed by the extension that handled
+/// it and what the extensions produced
+fn format_trace(trace: &[TraceEntry]) -> String {
+    let mut out = String::new();
+
+    for entry in trace {
+        match &entry.event {
+            Some(event) => {
+                out.push_str(&format!("{:>4}  {}\n", entry.index, event));
+                match entry.handled_by {
+                    Some(name) => out.push_str(&format!("      handled by {}\n", name)),
+                    None => out.push_str("      kept as it was\n"),
+                }
+            }
+            None => out.push_str(&format!("{:>4}  end of the document\n", entry.index)),
+        }
+
+        for (name, output) in &entry.outputs {
+            out.push_str(&format!("      {} → {}\n", name, output));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_every_event_with_what_happened_to_it() {
+        let trace = vec![
+            TraceEntry {
+                index: 0,
+                event: Some(String::from("Start(Paragraph)")),
+                handled_by: None,
+                outputs: vec![],
+            },
+            TraceEntry {
+                index: 1,
+                event: Some(String::from("End(Heading(1))")),
+                handled_by: Some("TableOfContents"),
+                outputs: vec![("TableOfContents", String::from("Event(Html(\"</h1>\"))"))],
+            },
+            TraceEntry {
+                index: 2,
+                event: None,
+                handled_by: None,
+                outputs: vec![("Tabs", String::from("Event(Html(\"</div>\"))"))],
+            },
+        ];
+
+        assert_eq!(
+            format_trace(&trace),
+            concat!(
+                "   0  Start(Paragraph)\n",
+                "      kept as it was\n",
+                "   1  End(Heading(1))\n",
+                "      handled by TableOfContents\n",
+                "      TableOfContents → Event(Html(\"</h1>\"))\n",
+                "   2  end of the document\n",
+                "      Tabs → Event(Html(\"</div>\"))\n",
+            )
+        );
+    }
+}
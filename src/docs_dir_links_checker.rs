@@ -0,0 +1,76 @@
+use crate::warning::Warning;
+use crate::Document;
+
+/// Finds links that start with the name of the docs directory, like
+/// `docs/images/logo.png`, and that couldn't be pointed at a file in the
+/// site. They work when browsing the repository, but not once built.
+pub fn check(root: &[Document]) -> Vec<Warning> {
+    root.iter()
+        .flat_map(|doc| {
+            doc.docs_dir_links().iter().map(move |link| {
+                Warning::new(
+                    doc.original_path(),
+                    format!(
+                        "Link to {} starts with the docs directory, which is the root of the \
+                         built site. It would be {}, but there is no such page or file in \
+                         _include to link to.",
+                        link.url, link.suggestion
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::markdown::parser::ParseOptions;
+    use crate::test_helpers::page_with_options;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    #[test]
+    fn reports_links_into_the_docs_directory() {
+        let mut options = ParseOptions::default();
+        options.docs_dir = Some(Path::new("project").join("docs"));
+        options.site_files = Some(Arc::new(HashSet::from([
+            PathBuf::from("README.md"),
+            PathBuf::from("_include/banner.png"),
+        ])));
+
+        let root = vec![page_with_options(
+            "README.md",
+            indoc! {"
+                # Welcome
+
+                ![Logo](docs/images/logo.png) and the [guide](/docs/guide.md#setup).
+
+                Nothing wrong with [this](/images/logo.png), [this](https://example.com/docs/a)
+                or [this](documents/a.md), and ![this](docs/_include/banner.png) is fixed.
+            "},
+            &[],
+            options,
+        )];
+
+        assert!(root[0].markdown.html.contains("src=\"/banner.png\""));
+        assert_eq!(
+            check(&root),
+            vec![
+                Warning::new(
+                    Path::new("README.md"),
+                    "Link to docs/images/logo.png starts with the docs directory, which is the \
+                     root of the built site. It would be /images/logo.png, but there is no such \
+                     page or file in _include to link to."
+                ),
+                Warning::new(
+                    Path::new("README.md"),
+                    "Link to /docs/guide.md#setup starts with the docs directory, which is the \
+                     root of the built site. It would be /guide.md#setup, but there is no such \
+                     page or file in _include to link to."
+                ),
+            ]
+        );
+    }
+}
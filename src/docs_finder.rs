@@ -1,53 +1,642 @@
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
+use std::sync::Arc;
 
 use crate::config::Config;
-use crate::Document;
+use crate::frontmatter;
+use crate::markdown::parser::ParseOptions;
+use crate::site_generator::INCLUDE_DIR;
+use crate::warning::Warning;
+use crate::{Document, SourceDocument};
+use crate::{Error, Result};
 use rayon::prelude::*;
 
 use walkdir::WalkDir;
 
+/// Makes sure the docs directory exists before looking for pages in it.
+/// Without this check a typo in `docs_dir` silently builds an empty site.
+pub fn check_docs_dir(config: &Config) -> Result<()> {
+    let docs_dir = config.docs_dir();
+
+    if docs_dir.is_dir() {
+        Ok(())
+    } else {
+        Err(Error::new(format!(
+            "Could not find the docs directory at {}.\n\
+             Create it, or set `docs_dir` in docgen.yaml to the directory with your Markdown files.",
+            docs_dir.display()
+        )))
+    }
+}
+
+/// Explains that the docs directory has no pages, for when `find` came back empty
+pub fn no_pages_message(config: &Config) -> String {
+    format!(
+        "Found no Markdown files in {}. Add a README.md to it, \
+         or set `docs_dir` in docgen.yaml to the directory with your Markdown files.",
+        config.docs_dir().display()
+    )
+}
+
+/// The file whose keys are the frontmatter defaults of every page in its
+/// directory and below
+pub static DEFAULTS_FILE: &str = "_defaults.yaml";
+
+/// The file listing the pages and directories of its directory in the
+/// order the navigation shows them, one name per line
+pub static ORDER_FILE: &str = ".order";
+
+/// The Markdown files found in the docs directory, as read from disk
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    pub documents: Vec<SourceDocument>,
+    /// The Markdown files of the underscore directories that aren't built
+    /// into pages, which pages can still include
+    pub includes: Vec<SourceDocument>,
+    /// Every file of the `_include` directory, by its path in the docs
+    /// directory
+    pub include_files: Vec<PathBuf>,
+    /// Files that were left out, saying why, and problems with the
+    /// defaults files
+    pub skipped: Vec<Warning>,
+}
+
 /// Loads the current state of the documentation from disk, returning the root
 /// directory which contains all files and nested directories.
 pub fn find(config: &Config) -> Vec<Document> {
-    walk_dir(config.docs_dir(), config)
+    render(&load(config), config)
 }
 
-fn walk_dir<P: AsRef<Path>>(dir: P, config: &Config) -> Vec<Document> {
-    let current_dir: &Path = dir.as_ref();
-
+/// Reads every Markdown file in the docs directory, without rendering them.
+/// Files that are too large or aren't text are skipped, and so are the
+/// directories starting with an underscore, like `_include`, unless
+/// `build_underscore_dirs` is set. Their Markdown files are only read for
+/// include directives then.
+///
+/// The frontmatter of every page starts out with the keys of the
+/// `_defaults.yaml` files in its directory and the ones above it. Nearer
+/// files win over farther ones, and the page's own frontmatter wins over all.
+///
+/// Pages in a directory with a `.order` file get their position in it, see
+/// `SourceDocument::order`.
+///
+/// The pages of every directory in `sources` are read too, with paths under
+/// the path they are mounted at, as if they were in the docs directory.
+pub fn load(config: &Config) -> Sources {
     let (sender, receiver) = channel();
+    let (include_sender, include_receiver) = channel();
+    let (defaults_sender, defaults_receiver) = channel();
+    let (order_sender, order_receiver) = channel();
+
+    // The pages of the `sources` are mounted under their path in the site
+    let roots = std::iter::once((config.docs_dir(), Path::new(""))).chain(
+        config
+            .mounts()
+            .iter()
+            .map(|m| (m.dir.as_path(), m.prefix.as_path())),
+    );
+
+    for (root, prefix) in roots {
+        WalkDir::new(root)
+            .follow_links(true)
+            .into_iter()
+            .par_bridge()
+            .for_each_with(
+                (
+                    sender.clone(),
+                    include_sender.clone(),
+                    defaults_sender.clone(),
+                    order_sender.clone(),
+                ),
+                |(sender, include_sender, defaults_sender, order_sender), entry| {
+                    if let Ok(entry) = entry {
+                        let relative = entry.path().strip_prefix(root).unwrap();
+                        let path = prefix.join(relative);
+                        let is_markdown = entry.file_type().is_file()
+                            && entry.path().extension() == Some(OsStr::new("md"));
+
+                        if !config.build_underscore_dirs() && in_underscore_dir(relative) {
+                            if is_markdown {
+                                include_sender
+                                    .send(SourceDocument::load(
+                                        entry.path(),
+                                        &path,
+                                        config.max_page_size(),
+                                    ))
+                                    .unwrap();
+                            }
+                        } else if entry.file_type().is_file() && entry.file_name() == DEFAULTS_FILE
+                        {
+                            defaults_sender
+                                .send(load_defaults(entry.path(), &path))
+                                .unwrap();
+                        } else if entry.file_type().is_file() && entry.file_name() == ORDER_FILE {
+                            order_sender.send(load_order(entry.path(), &path)).unwrap();
+                        } else if is_markdown {
+                            sender
+                                .send(SourceDocument::load(
+                                    entry.path(),
+                                    &path,
+                                    config.max_page_size(),
+                                ))
+                                .unwrap();
+                        }
+                    }
+                },
+            );
+    }
+    drop((sender, include_sender, defaults_sender, order_sender));
 
-    WalkDir::new(&current_dir)
+    let mut sources = Sources::default();
+    for source in receiver.iter() {
+        match source {
+            Ok(document) => sources.documents.push(document),
+            Err(warning) => sources.skipped.push(warning),
+        }
+    }
+    // Includes that can't be read show up as broken include directives
+    sources.includes = include_receiver
+        .iter()
+        .filter_map(|source| source.ok())
+        .collect();
+    sources.include_files = WalkDir::new(config.docs_dir().join(INCLUDE_DIR))
         .follow_links(true)
         .into_iter()
-        .par_bridge()
-        .for_each_with(sender, |sender, entry| {
-            if let Ok(entry) = entry {
-                if entry.file_type().is_file() && entry.path().extension() == Some(OsStr::new("md"))
-                {
-                    let path = entry.path().strip_prefix(config.docs_dir()).unwrap();
-
-                    sender
-                        .send(Document::load(entry.path(), path, config.base_path()))
-                        .unwrap();
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            entry
+                .path()
+                .strip_prefix(config.docs_dir())
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .collect();
+
+    let mut defaults = HashMap::new();
+    for (path, values, warnings) in defaults_receiver.iter() {
+        let directory = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        defaults.insert(directory, values);
+        sources.skipped.extend(warnings);
+    }
+    if !defaults.is_empty() {
+        for document in &mut sources.documents {
+            document.frontmatter = Arc::new(inherit_defaults(
+                &document.path,
+                &document.frontmatter,
+                &defaults,
+            ));
+        }
+    }
+
+    let mut orders = HashMap::new();
+    for (path, entries, warnings) in order_receiver.iter() {
+        sources.skipped.extend(warnings);
+        let directory = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        for entry in &entries {
+            if !is_in_directory(&config.source_file(&directory), entry) {
+                sources.skipped.push(Warning::new(
+                    &path,
+                    format!(
+                        "Lists `{}`, which is not in {}. Remove it, or fix its name",
+                        entry,
+                        display_dir(&directory)
+                    ),
+                ));
+            }
+        }
+        orders.insert(directory, (path, entries));
+    }
+    if !orders.is_empty() {
+        for document in &mut sources.documents {
+            apply_order(document, &orders);
+        }
+    }
+
+    // The navigation only has a section for directories with a README
+    for mount in config.mounts() {
+        if !mount.dir.join("README.md").is_file() {
+            sources.skipped.push(Warning::new(
+                &mount.prefix,
+                format!(
+                    "Has no README.md, so the pages mounted from {} are left out of the \
+                     navigation. Add one to give them a section.",
+                    mount.dir.display()
+                ),
+            ));
+        }
+    }
+
+    sources.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+
+    sources
+}
+
+/// Reads an order file, returning its path inside the docs directory along
+/// with the names it lists. Blank lines and lines starting with `#` are left
+/// out, and so are names listed a second time.
+fn load_order(
+    absolute_path: &Path,
+    relative_docs_path: &Path,
+) -> (PathBuf, Vec<String>, Vec<Warning>) {
+    let mut warnings = vec![];
+    let mut entries: Vec<String> = vec![];
+
+    match std::fs::read_to_string(absolute_path) {
+        Ok(content) => {
+            for line in content.lines() {
+                let entry = line.trim().trim_end_matches('/');
+                if entry.is_empty() || line.trim_start().starts_with('#') {
+                    continue;
+                }
+
+                if entries.iter().any(|e| e == entry) {
+                    warnings.push(Warning::new(
+                        relative_docs_path,
+                        format!("Lists `{}` more than once, only the first counts", entry),
+                    ));
+                } else {
+                    entries.push(entry.to_string());
                 }
             }
-        });
+        }
+        Err(e) => warnings.push(Warning::new(
+            relative_docs_path,
+            format!("Could not read the order, ignoring it: {}", e),
+        )),
+    }
 
-    let mut docs = vec![];
+    (relative_docs_path.to_path_buf(), entries, warnings)
+}
 
-    receiver.iter().for_each(|doc| {
-        docs.push(doc);
-    });
+/// Whether a page or directory listed in an order file as `entry` exists in
+/// `directory`. Pages may be listed with or without their `.md` extension.
+fn is_in_directory(directory: &Path, entry: &str) -> bool {
+    directory.join(entry).exists() || directory.join(format!("{}.md", entry)).exists()
+}
+
+/// Gives the document its position in the order file of the directory it is
+/// listed in, or `u32::MAX` when the file leaves it out. The README of a
+/// directory is listed by the name of its directory, in the order file of
+/// the directory above. An order file takes precedence over `index` values,
+/// which get a warning.
+fn apply_order(document: &mut SourceDocument, orders: &HashMap<PathBuf, (PathBuf, Vec<String>)>) {
+    let (directory, name) = if document.path.ends_with("README.md") {
+        match document.path.parent() {
+            // The root README is always first
+            Some(dir) if dir != Path::new("") => (dir.parent(), dir.file_name()),
+            _ => return,
+        }
+    } else {
+        (document.path.parent(), document.path.file_name())
+    };
+    let directory = directory.unwrap_or_else(|| Path::new(""));
+    let name = name.and_then(OsStr::to_str).unwrap_or_default();
+
+    let (order_path, entries) = match orders.get(directory) {
+        Some(order) => order,
+        None => return,
+    };
+
+    let position = entries
+        .iter()
+        .position(|entry| entry == name || Some(entry.as_str()) == name.strip_suffix(".md"));
+    document.order = Some(position.map(|p| p as u32).unwrap_or(u32::MAX));
+
+    if document.frontmatter.contains_key("index") {
+        document.warnings.push(Warning::new(
+            &document.path,
+            format!(
+                "Has an `index`, which is ignored since {} sets the order of {}. \
+                 An order file takes precedence over `index` values",
+                order_path.display(),
+                display_dir(directory)
+            ),
+        ));
+    }
+}
+
+/// A directory inside the docs directory for messages, like `guides/`
+fn display_dir(directory: &Path) -> String {
+    if directory.as_os_str().is_empty() {
+        "the docs directory".to_string()
+    } else {
+        format!("{}/", directory.display())
+    }
+}
+
+/// Reads a defaults file, returning its path inside the docs directory along
+/// with its keys. Keys Docgen knows about whose value has the wrong type are
+/// left out, and so is the whole file when it can't be read, saying why.
+fn load_defaults(
+    absolute_path: &Path,
+    relative_docs_path: &Path,
+) -> (PathBuf, frontmatter::Frontmatter, Vec<Warning>) {
+    let mut warnings = vec![];
+
+    let mut values = match std::fs::read_to_string(absolute_path) {
+        Ok(yaml) if yaml.trim().is_empty() => BTreeMap::new(),
+        Ok(yaml) => serde_yaml::from_str(&yaml).unwrap_or_else(|e| {
+            warnings.push(Warning::new(
+                relative_docs_path,
+                format!("Could not parse the defaults, ignoring them: {}", e),
+            ));
+            BTreeMap::new()
+        }),
+        Err(e) => {
+            warnings.push(Warning::new(
+                relative_docs_path,
+                format!("Could not read the defaults, ignoring them: {}", e),
+            ));
+            BTreeMap::new()
+        }
+    };
+
+    for message in frontmatter::remove_invalid(&mut values) {
+        warnings.push(Warning::new(relative_docs_path, message));
+    }
+
+    (relative_docs_path.to_path_buf(), values, warnings)
+}
+
+/// The frontmatter of the page at `path`, on top of the defaults of the
+/// directories it's in. `defaults` maps each directory inside the docs
+/// directory with a defaults file to its keys, the docs directory itself
+/// being the empty path.
+fn inherit_defaults(
+    path: &Path,
+    own: &frontmatter::Frontmatter,
+    defaults: &HashMap<PathBuf, frontmatter::Frontmatter>,
+) -> frontmatter::Frontmatter {
+    let ancestors = path.ancestors().skip(1).collect::<Vec<_>>();
+
+    let mut merged = BTreeMap::new();
+    for directory in ancestors.into_iter().rev() {
+        if let Some(values) = defaults.get(directory) {
+            merged.extend(values.clone());
+        }
+    }
+    merged.extend(own.clone());
+
+    merged
+}
+
+/// Whether the path inside the docs directory is in a directory whose name
+/// starts with an underscore. These hold files for Docgen, not pages.
+fn in_underscore_dir(path: &Path) -> bool {
+    path.parent()
+        .map(|dir| {
+            dir.components().any(|component| {
+                component
+                    .as_os_str()
+                    .to_str()
+                    .map(|name| name.starts_with('_'))
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Renders documents read with `load` using the Markdown options of the
+/// config, in document order. Doesn't read anything from disk, pages are
+/// included into each other from the sources.
+pub fn render(sources: &Sources, config: &Config) -> Vec<Document> {
+    let markdown_options = markdown_options(sources, config);
+
+    let mut docs = sources
+        .documents
+        .par_iter()
+        .map(|source| Document::render(source, &markdown_options))
+        .collect::<Vec<_>>();
 
     docs.par_sort_by(document_sort);
 
     docs
 }
 
+/// The Markdown options of the config, with the raw Markdown of every
+/// document and of the underscore directories for include directives, and
+/// the files of the site for links that start with the docs directory
+pub fn markdown_options(sources: &Sources, config: &Config) -> ParseOptions {
+    let mut markdown_options = config.markdown_options();
+    markdown_options.site_files = Some(Arc::new(
+        sources
+            .documents
+            .iter()
+            .map(|source| source.path.clone())
+            .chain(sources.include_files.iter().cloned())
+            .collect(),
+    ));
+    markdown_options.sources = Some(Arc::new(
+        sources
+            .documents
+            .iter()
+            .chain(&sources.includes)
+            .map(|source| (source.path.clone(), source.raw.clone()))
+            .collect(),
+    ));
+
+    markdown_options
+}
+
+/// Collects the warnings of all documents, in document order, along with
+/// frontmatter that doesn't follow the `frontmatter_schema` of the config,
+/// followed by documents that would be written to the same output file, or
+/// to the same file where case doesn't matter.
+pub fn warnings(docs: &[Document], config: &Config) -> Vec<Warning> {
+    let mut warnings: Vec<Warning> = vec![];
+    for doc in docs {
+        warnings.extend(doc.warnings().iter().cloned());
+
+        if let Some(schema) = config.frontmatter_schema() {
+            warnings.extend(
+                frontmatter::schema_errors(&doc.frontmatter, schema)
+                    .into_iter()
+                    .map(|message| Warning::new(doc.original_path(), message)),
+            );
+        }
+    }
+
+    let mut outputs: HashMap<&Path, &Document> = HashMap::new();
+    for doc in docs {
+        if let Some(other) = outputs.insert(&doc.html_path, doc) {
+            warnings.push(Warning::new(
+                doc.original_path(),
+                format!(
+                    "Is built to the same file as {}, one of them will be overwritten",
+                    other.original_path().display()
+                ),
+            ));
+        }
+    }
+
+    let sources = docs
+        .iter()
+        .map(|doc| doc.original_path())
+        .collect::<Vec<_>>();
+    for paths in case_collisions(&sources) {
+        warnings.push(Warning::new(
+            paths[0],
+            format!(
+                "Has the same path as {} when case is ignored. {}",
+                display_paths(&paths[1..]),
+                CASE_COLLISION_EFFECT
+            ),
+        ));
+    }
+
+    // Pages with different sources can still be built to the same file, like
+    // Setup.md and setup/README.md with `clean_urls`
+    let by_output = docs
+        .iter()
+        .map(|doc| (doc.html_path.as_path(), doc))
+        .collect::<HashMap<_, _>>();
+    let outputs = docs
+        .iter()
+        .map(|doc| doc.html_path.as_path())
+        .collect::<Vec<_>>();
+    for paths in case_collisions(&outputs) {
+        // Sources that only differ in case were reported above
+        let docs = paths.iter().map(|path| by_output[path]).collect::<Vec<_>>();
+        let source = |doc: &Document| doc.original_path().to_string_lossy().to_lowercase();
+        if docs.iter().all(|doc| source(doc) == source(docs[0])) {
+            continue;
+        }
+
+        warnings.push(Warning::new(
+            docs[0].original_path(),
+            format!(
+                "Is built to {}, the same file as {} when case is ignored. {}",
+                paths[0].display(),
+                docs[1..]
+                    .iter()
+                    .map(|doc| format!(
+                        "{} of {}",
+                        doc.html_path.display(),
+                        doc.original_path().display()
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                CASE_COLLISION_EFFECT
+            ),
+        ));
+    }
+
+    if let Some(max_length) = config.max_description_length() {
+        for doc in docs {
+            // Descriptions taken from the content are already short enough
+            let length = doc
+                .fm_str("description")
+                .and(doc.description.as_ref())
+                .map(|description| description.chars().count())
+                .unwrap_or(0);
+            if length > max_length {
+                warnings.push(Warning::new(
+                    doc.original_path(),
+                    format!(
+                        "Has a `description` of {} characters, longer than the \
+                         `max_description_length` of {}. Search engines will cut it short",
+                        length, max_length
+                    ),
+                ));
+            }
+        }
+    }
+
+    if let Some(max_length) = config.max_path_length() {
+        for doc in docs {
+            let destination = doc.destination(config.out_dir());
+            let length = destination.to_string_lossy().chars().count();
+            if length > max_length {
+                warnings.push(Warning::new(
+                    doc.original_path(),
+                    format!(
+                        "Is built to a path of {} characters, longer than the \
+                         `max_path_length` of {}: {}. Tools without long path \
+                         support won't be able to open it on Windows",
+                        length,
+                        max_length,
+                        destination.display()
+                    ),
+                ));
+            }
+        }
+    }
+
+    warnings
+}
+
+/// What happens to files whose paths only differ in case
+pub static CASE_COLLISION_EFFECT: &str = "Only one of them is kept on case insensitive file \
+                                          systems, like the ones of macOS and Windows, and \
+                                          on some hosts";
+
+/// Groups the paths that are the same when case is ignored, but not
+/// otherwise, like `API.md` and `api.md`. Each group is sorted, and so are
+/// the groups.
+pub fn case_collisions<'p>(paths: &[&'p Path]) -> Vec<Vec<&'p Path>> {
+    let mut groups: BTreeMap<String, Vec<&Path>> = BTreeMap::new();
+    for path in paths {
+        let key = path.to_string_lossy().to_lowercase();
+        let group = groups.entry(key).or_default();
+        if !group.contains(path) {
+            group.push(path);
+        }
+    }
+
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+/// The paths as a list for messages, like `a.md, b.md`
+pub fn display_paths(paths: &[&Path]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Fails with every include directive that couldn't be expanded, saying
+/// which page it is on and why.
+pub fn check_includes(docs: &[Document]) -> Result<()> {
+    let errors = include_warnings(docs);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::new(format!(
+        "Could not expand {} include(s):\n\n{}",
+        errors.len(),
+        errors
+            .iter()
+            .map(|e| format!("\t{}: {}", e.path.display(), e.message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )))
+}
+
+/// The include directives that couldn't be expanded, as warnings for when
+/// the site is served
+pub fn include_warnings(docs: &[Document]) -> Vec<Warning> {
+    docs.iter()
+        .flat_map(|doc| {
+            doc.include_errors()
+                .iter()
+                .map(move |e| Warning::new(doc.original_path(), e.clone()))
+        })
+        .collect()
+}
+
 /// This is a special sort comparator that moves all README.md files to
 /// to the top and positions all similarly nested directories together.
 pub fn document_sort(b: &Document, a: &Document) -> Ordering {
@@ -61,3 +650,437 @@ pub fn document_sort(b: &Document, a: &Document) -> Ordering {
 
     return alphanumeric_sort::compare_path(&b.path, &a.path);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::{page, page_with_options, project};
+    use std::fs;
+
+    #[test]
+    fn rendering_again_reads_no_files() {
+        let root = project(
+            "finder",
+            &[
+                ("docs/README.md", "# Home"),
+                (
+                    "docs/guides/setup.md",
+                    "---\ntitle: Setup\n---\n# Setting up",
+                ),
+            ],
+        );
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let sources = load(&config);
+
+        // Reading any of the pages again would fail from here on
+        fs::remove_dir_all(&root).unwrap();
+
+        let yaml = "---\ntitle: Title\nbase_path: /docs/\nclean_urls: true";
+        let config = Config::from_yaml_str(&root, yaml, true).unwrap();
+        let docs = render(&sources, &config);
+
+        let setup = docs
+            .iter()
+            .find(|d| d.path == Path::new("guides/setup.md"))
+            .unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(setup.uri_path, "/docs/guides/setup/");
+        assert_eq!(setup.title, "Setup");
+    }
+
+    #[test]
+    fn skips_underscore_directories() {
+        let root = project(
+            "finder-underscore",
+            &[
+                ("docs/README.md", "# Home"),
+                ("docs/_include/notes.md", "# Notes"),
+                ("docs/guides/setup.md", "# Setup"),
+                ("docs/guides/_drafts/wip.md", "# WIP"),
+            ],
+        );
+
+        let paths = |yaml: &str| {
+            let config = Config::from_yaml_str(&root, yaml, false).unwrap();
+            let mut paths = load(&config)
+                .documents
+                .iter()
+                .map(|doc| doc.path.clone())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+
+        let found = paths("---\ntitle: Title");
+        let all = paths("---\ntitle: Title\nbuild_underscore_dirs: true");
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            found,
+            vec![Path::new("README.md"), Path::new("guides/setup.md")]
+        );
+        assert_eq!(
+            all,
+            vec![
+                Path::new("README.md"),
+                Path::new("_include/notes.md"),
+                Path::new("guides/_drafts/wip.md"),
+                Path::new("guides/setup.md"),
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_markdown_from_underscore_directories() {
+        let root = project(
+            "finder-underscore-include",
+            &[
+                (
+                    "docs/README.md",
+                    "# Home\n\n{% include \"_include/snippet.md\" %}",
+                ),
+                ("docs/_include/snippet.md", "Shared *snippet*"),
+            ],
+        );
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let docs = find(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(docs.len(), 1);
+        assert!(
+            docs[0].markdown.html.contains("Shared <em>snippet</em>"),
+            "HTML was: {}",
+            docs[0].markdown.html
+        );
+        assert!(docs[0].warnings().is_empty(), "{:?}", docs[0].warnings());
+    }
+
+    #[test]
+    fn inherits_frontmatter_defaults_from_directories() {
+        let root = project(
+            "finder-defaults",
+            &[
+                (
+                    "docs/_defaults.yaml",
+                    "author: Root\nlayout: wide\nsection: Docs",
+                ),
+                (
+                    "docs/guides/_defaults.yaml",
+                    "author: Guides\nnoindex: true\nsection: Guides",
+                ),
+                (
+                    "docs/guides/deep/_defaults.yaml",
+                    "section: Deep\nnoindex: sometimes",
+                ),
+                ("docs/README.md", "# Home"),
+                ("docs/guides/setup.md", "# Setup"),
+                (
+                    "docs/guides/deep/dive.md",
+                    "---\nauthor: Diver\n---\n# Dive",
+                ),
+            ],
+        );
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let sources = load(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        let value = |path: &str, key: &str| {
+            let source = sources
+                .documents
+                .iter()
+                .find(|d| d.path == Path::new(path))
+                .unwrap();
+            source.frontmatter.get(key).cloned()
+        };
+        let string = |s: &str| Some(serde_yaml::Value::from(s));
+        let yes = Some(serde_yaml::Value::from(true));
+
+        assert_eq!(value("README.md", "author"), string("Root"));
+        assert_eq!(value("README.md", "section"), string("Docs"));
+        assert_eq!(value("README.md", "noindex"), None);
+
+        assert_eq!(value("guides/setup.md", "author"), string("Guides"));
+        assert_eq!(value("guides/setup.md", "layout"), string("wide"));
+        assert_eq!(value("guides/setup.md", "section"), string("Guides"));
+        assert_eq!(value("guides/setup.md", "noindex"), yes);
+
+        // The invalid `noindex` of the nearest file is left out with a warning,
+        // so the one of the directory above still counts
+        assert_eq!(value("guides/deep/dive.md", "author"), string("Diver"));
+        assert_eq!(value("guides/deep/dive.md", "layout"), string("wide"));
+        assert_eq!(value("guides/deep/dive.md", "section"), string("Deep"));
+        assert_eq!(value("guides/deep/dive.md", "noindex"), yes);
+
+        assert_eq!(sources.skipped.len(), 1);
+        assert_eq!(
+            sources.skipped[0].path,
+            Path::new("guides/deep").join(DEFAULTS_FILE)
+        );
+        assert!(sources.skipped[0]
+            .message
+            .contains("Invalid value for `noindex` in frontmatter"));
+    }
+
+    #[test]
+    fn order_files_give_pages_their_position() {
+        let root = project(
+            "finder-order",
+            &[
+                ("docs/README.md", "# Home"),
+                ("docs/intro.md", "---\nindex: 5\n---\n# Intro"),
+                ("docs/zebra.md", "# Zebra"),
+                ("docs/guides/README.md", "# Guides"),
+                ("docs/guides/setup.md", "# Setup"),
+                (
+                    "docs/.order",
+                    "# Shown first\nguides/\nintro\nmissing.md\nintro\n",
+                ),
+            ],
+        );
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let sources = load(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        let source = |path: &str| {
+            sources
+                .documents
+                .iter()
+                .find(|d| d.path == Path::new(path))
+                .unwrap()
+        };
+
+        assert_eq!(source("README.md").order, None);
+        assert_eq!(source("guides/README.md").order, Some(0));
+        assert_eq!(source("intro.md").order, Some(1));
+        assert_eq!(source("zebra.md").order, Some(u32::MAX));
+        assert_eq!(source("guides/setup.md").order, None);
+
+        assert_eq!(
+            source("intro.md").warnings,
+            vec![Warning::new(
+                Path::new("intro.md"),
+                "Has an `index`, which is ignored since .order sets the order of the docs \
+                 directory. An order file takes precedence over `index` values"
+            )]
+        );
+        assert_eq!(
+            sources.skipped,
+            vec![
+                Warning::new(
+                    Path::new(ORDER_FILE),
+                    "Lists `intro` more than once, only the first counts"
+                ),
+                Warning::new(
+                    Path::new(ORDER_FILE),
+                    "Lists `missing.md`, which is not in the docs directory. \
+                     Remove it, or fix its name"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_without_defaults_files_keep_their_frontmatter() {
+        let root = project(
+            "finder-no-defaults",
+            &[
+                ("docs/README.md", "# Home"),
+                (
+                    "docs/guides/setup.md",
+                    "---\ntitle: Setup\nauthor: Someone\n---\n# Setting up",
+                ),
+            ],
+        );
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let sources = load(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        for source in &sources.documents {
+            assert_eq!(
+                *source.frontmatter,
+                frontmatter::parse(&source.raw).unwrap(),
+                "{}",
+                source.path.display()
+            );
+        }
+        assert!(sources.skipped.is_empty());
+    }
+
+    #[test]
+    fn warns_about_paths_longer_than_max_path_length() {
+        let deep = format!("{}page.md", "a-long-directory-name/".repeat(12));
+        let docs = vec![page("README.md", "# Page", &[]), page(&deep, "# Page", &[])];
+
+        let yaml = "---\ntitle: Title\nmax_path_length: 260";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        let warnings = warnings(&docs, &config);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new(&deep));
+        assert!(warnings[0]
+            .message
+            .contains("longer than the `max_path_length` of 260"));
+
+        let yaml = "---\ntitle: Title\nmax_path_length: 0";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        assert!(super::warnings(&docs, &config).is_empty());
+    }
+
+    #[test]
+    fn warns_about_descriptions_longer_than_max_description_length() {
+        let long = "Far too long. ".repeat(20);
+        let docs = vec![
+            page(
+                "README.md",
+                "# Page",
+                &[("description", "Short and to the point")],
+            ),
+            page("long.md", "# Page", &[("description", &long)]),
+        ];
+
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", false).unwrap();
+        let warnings = warnings(&docs, &config);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("long.md"));
+        assert_eq!(
+            warnings[0].message,
+            "Has a `description` of 279 characters, longer than the \
+             `max_description_length` of 160. Search engines will cut it short"
+        );
+
+        let yaml = "---\ntitle: Title\nmax_description_length: 0";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, false).unwrap();
+        assert!(super::warnings(&docs, &config).is_empty());
+    }
+
+    #[test]
+    fn warns_about_paths_that_only_differ_in_case() {
+        let page = |path: &str, clean_urls: bool| {
+            let mut options = crate::markdown::parser::ParseOptions::default();
+            options.url_root = "/".to_string();
+            options.clean_urls = clean_urls;
+            page_with_options(path, "# Page", &[], options)
+        };
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", false).unwrap();
+
+        let docs = vec![
+            page("README.md", false),
+            page("API.md", false),
+            page("api.md", false),
+        ];
+        let warnings = warnings(&docs, &config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("API.md"));
+        assert!(warnings[0]
+            .message
+            .starts_with("Has the same path as api.md when case is ignored"));
+
+        let docs = vec![page("Setup.md", true), page("setup/README.md", true)];
+        let warnings = super::warnings(&docs, &config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("Setup.md"));
+        assert!(warnings[0].message.contains("setup/README.md"));
+
+        let docs = vec![
+            page("README.md", false),
+            page("api.md", false),
+            page("guides/api.md", false),
+        ];
+        assert!(super::warnings(&docs, &config).is_empty());
+    }
+
+    #[test]
+    fn rebases_relative_links_of_clean_url_pages() {
+        let page = |path: &str| {
+            let mut options = crate::markdown::parser::ParseOptions::default();
+            options.url_root = "/".to_string();
+            options.clean_urls = true;
+            let content = "[Install](install) ![Logo](../images/logo.png) \
+                           [Top](#top) [Home](/) <img src=\"diagram.svg\">";
+            page_with_options(path, content, &[], options)
+        };
+
+        let setup = page("guides/setup.md");
+        assert_eq!(setup.html_path, Path::new("guides/setup/index.html"));
+        assert!(setup.markdown.html.contains("href=\"../install\""));
+        assert!(setup
+            .markdown
+            .html
+            .contains("src=\"../../images/logo.png\""));
+        assert!(setup.markdown.html.contains("src=\"../diagram.svg\""));
+        assert!(setup.markdown.html.contains("href=\"#top\""));
+        assert!(setup.markdown.html.contains("href=\"/\""));
+
+        // READMEs are built next to their Markdown file
+        let guides = page("guides/README.md");
+        assert!(guides.markdown.html.contains("href=\"install\""));
+        assert!(guides.markdown.html.contains("src=\"../images/logo.png\""));
+    }
+
+    #[test]
+    fn mounts_the_pages_of_other_sources() {
+        let root = project(
+            "finder-sources",
+            &[
+                (
+                    "docs/README.md",
+                    "# Home\n\nRead [the handbook](/handbook/) and [hiring](/handbook/people/hiring).",
+                ),
+                ("vendor/handbook/README.md", "# Handbook\n\nBack [home](/)."),
+                ("vendor/handbook/values.md", "# Values"),
+                (
+                    "vendor/handbook/people/hiring.md",
+                    "# Hiring\n\nSee [the handbook](/handbook/).",
+                ),
+            ],
+        );
+
+        let yaml = indoc::indoc! {"
+            ---
+            title: Title
+            edit_root: https://github.com/acme/docs/edit/main
+            sources:
+              - path: vendor/handbook
+                mount: handbook
+        "};
+        let config = Config::from_yaml_str(&root, yaml, false).unwrap();
+        let sources = load(&config);
+        let docs = render(&sources, &config);
+
+        let mut site = crate::site::Site::in_memory(config.clone());
+        site.build(config.clone(), &docs).unwrap();
+        let checked = crate::broken_links_checker::check(&docs, &site);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(sources.skipped.is_empty());
+        assert!(checked.is_ok(), "{:?}", checked);
+
+        let hiring = docs
+            .iter()
+            .find(|d| d.path == Path::new("handbook/people/hiring.md"))
+            .unwrap();
+        assert_eq!(docs.len(), 4);
+        assert_eq!(hiring.uri_path, "/handbook/people/hiring");
+        assert_eq!(
+            config.build_edit_link(hiring).as_deref(),
+            Some("https://github.com/acme/docs/edit/main/vendor/handbook/people/hiring.md")
+        );
+
+        let nav = crate::navigation::Navigation::new(&config).build_for(&docs);
+        assert_eq!(
+            nav.iter()
+                .map(|link| link.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/handbook/"]
+        );
+        assert_eq!(nav[0].children[0].path, "/handbook/values");
+    }
+}
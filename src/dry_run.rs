@@ -0,0 +1,205 @@
+//! Compares a site built in memory with the one already in the output
+//! directory, for `docgen build --dry-run`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bunt::termcolor::StandardStream;
+use regex::bytes::Regex;
+use similar::TextDiff;
+use walkdir::WalkDir;
+
+use crate::site::{InMemorySite, SiteBackend};
+use crate::site_generator::MANIFEST_FILE;
+use crate::{Error, Result};
+
+/// Text files larger than this are reported as modified without a diff
+pub static MAX_DIFF_SIZE: usize = 100 * 1024;
+
+lazy_static! {
+    /// Every page embeds the timestamp of the build that wrote it
+    static ref BUILD_TIMESTAMP: Regex =
+        Regex::new(r#"DOCGEN_TIMESTAMP\s*=\s*"\d*""#).unwrap();
+}
+
+/// What a build would change in the output directory. Paths are relative
+/// to the output directory.
+#[derive(Debug, Default, PartialEq)]
+pub struct DryRunReport {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub unchanged: Vec<PathBuf>,
+}
+
+impl DryRunReport {
+    /// Compares the files of `site` with the ones in `out_dir`, without
+    /// writing anything. A missing `out_dir` counts as an empty one.
+    ///
+    /// The build manifest is left out, as it is different for every build.
+    pub fn compare(site: &InMemorySite, out_dir: &Path) -> Result<Self> {
+        let mut on_disk = existing_files(out_dir)?;
+        let mut report = DryRunReport::default();
+
+        let mut built = site.list_files();
+        built.sort();
+
+        for path in built {
+            if path == Path::new(MANIFEST_FILE) {
+                continue;
+            }
+
+            match on_disk.remove(&path) {
+                None => report.added.push(path),
+                Some(location) => {
+                    let existing = fs::read(&location).map_err(|e| {
+                        Error::io(e, format!("Could not read {}", location.display()))
+                    })?;
+                    let content = site.read_path(&path).unwrap_or_default();
+
+                    if same_output(&existing, &content) {
+                        report.unchanged.push(path);
+                    } else {
+                        report.modified.push(path);
+                    }
+                }
+            }
+        }
+
+        report.removed = on_disk
+            .into_keys()
+            .filter(|path| path != Path::new(MANIFEST_FILE))
+            .collect();
+
+        Ok(report)
+    }
+
+    /// Prints the changed files, followed by a diff of every modified text
+    /// file if `show_diff` is set.
+    pub fn print(
+        &self,
+        out: &mut StandardStream,
+        site: &InMemorySite,
+        out_dir: &Path,
+        show_diff: bool,
+    ) -> Result<()> {
+        for path in &self.added {
+            bunt::write!(out, "{$green}  added{/$}")?;
+            writeln!(out, "\t{}", path.display())?;
+        }
+        for path in &self.removed {
+            bunt::write!(out, "{$red}removed{/$}")?;
+            writeln!(out, "\t{}", path.display())?;
+        }
+        for path in &self.modified {
+            bunt::write!(out, "{$yellow}changed{/$}")?;
+            writeln!(out, "\t{}", path.display())?;
+        }
+
+        writeln!(
+            out,
+            "\n{} added, {} removed, {} modified, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len(),
+            self.unchanged.len()
+        )?;
+
+        if show_diff {
+            for path in &self.modified {
+                let existing = fs::read(out_dir.join(path)).unwrap_or_default();
+                let content = site.read_path(path).unwrap_or_default();
+
+                match unified_diff(path, &existing, &content) {
+                    Some(diff) => write!(out, "\n{}", diff)?,
+                    None => writeln!(out, "\n{}: binary or too large to diff", path.display())?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A unified diff between two versions of a file, if both are text files
+/// smaller than `MAX_DIFF_SIZE`
+pub fn unified_diff(path: &Path, old: &[u8], new: &[u8]) -> Option<String> {
+    if old.len() > MAX_DIFF_SIZE || new.len() > MAX_DIFF_SIZE {
+        return None;
+    }
+
+    let old = std::str::from_utf8(old).ok()?;
+    let new = std::str::from_utf8(new).ok()?;
+    let name = path.display().to_string();
+
+    Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .header(&format!("a/{}", name), &format!("b/{}", name))
+            .to_string(),
+    )
+}
+
+fn same_output(existing: &[u8], content: &[u8]) -> bool {
+    existing == content
+        || BUILD_TIMESTAMP.replace_all(existing, &b"DOCGEN_TIMESTAMP"[..])
+            == BUILD_TIMESTAMP.replace_all(content, &b"DOCGEN_TIMESTAMP"[..])
+}
+
+/// Every file in `out_dir`, by its path relative to it
+fn existing_files(out_dir: &Path) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    let mut files = BTreeMap::new();
+    if !out_dir.exists() {
+        return Ok(files);
+    }
+
+    for entry in WalkDir::new(out_dir) {
+        let entry = entry
+            .map_err(|e| Error::new(format!("Could not read {}: {}", out_dir.display(), e)))?;
+
+        if entry.file_type().is_file() {
+            let path = entry.path().strip_prefix(out_dir).unwrap().to_owned();
+            files.insert(path, entry.path().to_owned());
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_timestamps_are_not_changes() {
+        assert!(same_output(
+            br#"<script>var DOCGEN_TIMESTAMP = "1690000000000";</script><h1>Hi</h1>"#,
+            br#"<script>var DOCGEN_TIMESTAMP = "1700000000000";</script><h1>Hi</h1>"#,
+        ));
+        assert!(!same_output(
+            br#"<script>var DOCGEN_TIMESTAMP = "1690000000000";</script><h1>Hi</h1>"#,
+            br#"<script>var DOCGEN_TIMESTAMP = "1700000000000";</script><h1>Hello</h1>"#,
+        ));
+    }
+
+    #[test]
+    fn diffs_text_files_only() {
+        let diff = unified_diff(
+            Path::new("index.html"),
+            b"<h1>Hi</h1>\n",
+            b"<h1>Hello</h1>\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            diff,
+            "--- a/index.html\n+++ b/index.html\n@@ -1 +1 @@\n-<h1>Hi</h1>\n+<h1>Hello</h1>\n"
+        );
+        assert_eq!(
+            unified_diff(Path::new("logo.png"), &[0xff, 0xfe], &[0xff]),
+            None
+        );
+    }
+}
@@ -1,42 +1,376 @@
 use std::collections::BTreeMap;
 
-pub fn parse(input: &str) -> std::io::Result<BTreeMap<String, String>> {
-    let pos = end_pos(input);
+use chrono::NaiveDate;
+use serde_yaml::Value;
 
-    if pos > 0 {
-        serde_yaml::from_str(&input[0..pos].trim_end().trim_end_matches('-'))
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-    } else {
-        Ok(BTreeMap::new())
+use crate::config::FrontmatterSchema;
+
+const BOM: char = '\u{feff}';
+
+/// Location of the frontmatter block inside a document.
+struct Block {
+    /// Byte range of the YAML between the two fences
+    yaml: (usize, usize),
+    /// Byte offset where the content after the closing fence begins
+    end: usize,
+}
+
+/// Frontmatter values by key, with the types they were written with
+pub type Frontmatter = BTreeMap<String, Value>;
+
+pub fn parse(input: &str) -> std::io::Result<Frontmatter> {
+    match find_block(input) {
+        Some(block) => {
+            let yaml = &input[block.yaml.0..block.yaml.1];
+
+            if yaml.trim().is_empty() {
+                return Ok(BTreeMap::new());
+            }
+
+            serde_yaml::from_str(yaml)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        }
+        None => Ok(BTreeMap::new()),
     }
 }
 
-pub fn end_pos(input: &str) -> usize {
-    if input.starts_with("---\n") {
-        let after_starter_mark = &input[4..];
-        let end_mark = after_starter_mark.find("---\n");
-
-        if end_mark.is_none() {
-            0
-        } else {
-            end_mark.unwrap() + 8
+/// The type a frontmatter key known to Docgen is expected to have
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Str,
+    Bool,
+    /// A positive whole number, or `auto` to come after the siblings that
+    /// have one
+    Index,
+    StrList,
+    Date,
+}
+
+static KNOWN_KEYS: &[(&str, Kind)] = &[
+    ("title", Kind::Str),
+    ("description", Kind::Str),
+    ("nav_title", Kind::Str),
+    ("lang", Kind::Str),
+    ("layout", Kind::Str),
+    ("page_class", Kind::Str),
+    ("edit_url", Kind::Str),
+    ("index", Kind::Index),
+    ("smart_punctuation", Kind::Bool),
+    ("number_headings", Kind::Bool),
+    ("noindex", Kind::Bool),
+    ("related_pages", Kind::Bool),
+    ("edit", Kind::Bool),
+    ("content_header", Kind::Bool),
+    ("content_footer", Kind::Bool),
+    ("unlisted", Kind::Bool),
+    ("draft", Kind::Bool),
+    ("list_children", Kind::Bool),
+    ("keywords", Kind::StrList),
+    ("author", Kind::StrList),
+    ("date", Kind::Date),
+    ("reviewed", Kind::Date),
+];
+
+/// Checks the values of the keys Docgen knows about, returning a message for
+/// each one with the wrong type. Values with the wrong type are ignored.
+pub fn type_errors(values: &Frontmatter) -> Vec<String> {
+    invalid_values(values)
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect()
+}
+
+/// Removes the values that `type_errors` reports, returning its messages
+pub fn remove_invalid(values: &mut Frontmatter) -> Vec<String> {
+    invalid_values(values)
+        .into_iter()
+        .map(|(key, message)| {
+            values.remove(key);
+            message
+        })
+        .collect()
+}
+
+/// The known keys with a value of the wrong type, with a message for each
+fn invalid_values(values: &Frontmatter) -> Vec<(&'static str, String)> {
+    KNOWN_KEYS
+        .iter()
+        .filter_map(|(key, kind)| {
+            let value = values.get(*key)?;
+
+            let (valid, expected) = match kind {
+                Kind::Str => (as_string(value).is_some(), "text"),
+                Kind::Bool => (as_bool(value).is_some(), "true or false"),
+                Kind::Index => (
+                    as_u32(value).is_some() || as_string(value).as_deref() == Some("auto"),
+                    "a positive whole number or auto",
+                ),
+                Kind::StrList => (as_str_list(value).is_some(), "a list of text"),
+                Kind::Date => (as_date(value).is_some(), "a date like 2024-01-31"),
+            };
+
+            if valid {
+                None
+            } else {
+                Some((
+                    *key,
+                    format!(
+                        "Invalid value for `{}` in frontmatter. Found '{}', expected {}",
+                        key,
+                        value_to_string(value.clone()),
+                        expected
+                    ),
+                ))
+            }
+        })
+        .collect()
+}
+
+/// The type of a key in the `frontmatter_schema` of docgen.yaml
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    String,
+    Bool,
+    Int,
+    Date,
+    List,
+}
+
+impl ValueType {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(ValueType::String),
+            "bool" => Some(ValueType::Bool),
+            "int" => Some(ValueType::Int),
+            "date" => Some(ValueType::Date),
+            "list" => Some(ValueType::List),
+            _ => None,
         }
-    } else if input.starts_with("---\r\n") {
-        let after_starter_mark = &input[5..];
-        let end_mark = after_starter_mark.find("---\r\n");
-
-        if end_mark.is_none() {
-            0
-        } else {
-            end_mark.unwrap() + 10
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            ValueType::String => as_string(value).is_some(),
+            ValueType::Bool => as_bool(value).is_some(),
+            ValueType::Int => match value {
+                Value::Number(n) => n.is_i64() || n.is_u64(),
+                Value::String(s) => s.trim().parse::<i64>().is_ok(),
+                _ => false,
+            },
+            ValueType::Date => as_date(value).is_some(),
+            ValueType::List => as_str_list(value).is_some(),
         }
+    }
+
+    fn expected(&self) -> &'static str {
+        match self {
+            ValueType::String => "text",
+            ValueType::Bool => "true or false",
+            ValueType::Int => "a whole number",
+            ValueType::Date => "a date like 2024-01-31",
+            ValueType::List => "a list of text",
+        }
+    }
+}
+
+/// Checks the values against the schema of the site, returning a message for
+/// each key that is missing, has the wrong type or doesn't match its pattern.
+/// With `deny_unknown_keys`, keys that neither the schema nor Docgen know
+/// about are reported too.
+pub fn schema_errors(values: &Frontmatter, schema: &FrontmatterSchema) -> Vec<String> {
+    let mut errors = vec![];
+
+    for (key, rules) in &schema.keys {
+        let value = match values.get(key) {
+            Some(value) => value,
+            None => {
+                if rules.required {
+                    errors.push(format!("Missing required key `{}` in frontmatter", key));
+                }
+                continue;
+            }
+        };
+
+        if let Some(kind) = rules.kind.as_deref().and_then(ValueType::parse) {
+            if !kind.matches(value) {
+                errors.push(format!(
+                    "Invalid value for `{}` in frontmatter. Found '{}', expected {}",
+                    key,
+                    value_to_string(value.clone()),
+                    kind.expected()
+                ));
+                continue;
+            }
+        }
+
+        // The config compiled the pattern when it was loaded
+        if let Some(pattern) = &rules.regex {
+            let matches = match value {
+                Value::Sequence(_) => as_str_list(value)
+                    .map(|items| items.iter().all(|item| pattern.is_match(item)))
+                    .unwrap_or(false),
+                _ => as_string(value)
+                    .map(|s| pattern.is_match(&s))
+                    .unwrap_or(false),
+            };
+
+            if !matches {
+                errors.push(format!(
+                    "Invalid value for `{}` in frontmatter. Found '{}', expected it to match `{}`",
+                    key,
+                    value_to_string(value.clone()),
+                    pattern
+                ));
+            }
+        }
+    }
+
+    if schema.deny_unknown_keys {
+        for key in values.keys() {
+            let known =
+                schema.keys.contains_key(key) || KNOWN_KEYS.iter().any(|(known, _)| known == key);
+
+            if !known {
+                errors.push(format!("Unknown key `{}` in frontmatter", key));
+            }
+        }
+    }
+
+    errors
+}
+
+/// Reads a value as text. Numbers and booleans are written out, lists and
+/// maps are not text.
+pub fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
+            Some(value_to_string(value.clone()))
+        }
+        _ => None,
+    }
+}
+
+/// Reads a value as a boolean. The strings "true" and "false" count too.
+pub fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Reads a value as a positive number that fits in a u32
+pub fn as_u32(value: &Value) -> Option<u32> {
+    match value {
+        Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Reads a value as a date written like 2024-01-31
+pub fn as_date(value: &Value) -> Option<NaiveDate> {
+    as_string(value).and_then(|s| NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok())
+}
+
+/// Reads a value as a list of text. A single value is read as a comma
+/// separated list, so `keywords: a, b` is the same as `keywords: [a, b]`.
+pub fn as_str_list(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Sequence(values) => values.iter().map(as_string).collect(),
+        other => as_string(other).map(|s| list(&s).into_iter().map(String::from).collect()),
+    }
+}
+
+/// Flattens a frontmatter value into a string. Lists are joined with
+/// commas, so `keywords: [a, b]` reads the same as `keywords: a, b`.
+fn value_to_string(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s,
+        Value::Sequence(values) => values
+            .into_iter()
+            .map(value_to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => serde_yaml::to_string(&other)
+            .map(|s| s.trim_start_matches("---").trim().to_owned())
+            .unwrap_or_default(),
+    }
+}
+
+/// Splits a comma separated frontmatter value into its items.
+pub fn list(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+pub fn end_pos(input: &str) -> usize {
+    find_block(input).map(|block| block.end).unwrap_or(0)
+}
+
+pub fn without(input: &str) -> &str {
+    match end_pos(input) {
+        0 => input.strip_prefix(BOM).unwrap_or(input),
+        pos => &input[pos..],
+    }
+}
+
+/// Whether the input opens a frontmatter block that is never closed. The
+/// whole document, including the opening fence, is then treated as content.
+pub fn is_unterminated(input: &str) -> bool {
+    opening_fence_end(input).is_some() && find_block(input).is_none()
+}
+
+/// Finds the end of the opening `---` fence, skipping a leading BOM.
+fn opening_fence_end(input: &str) -> Option<usize> {
+    let start = if input.starts_with(BOM) {
+        BOM.len_utf8()
     } else {
         0
+    };
+
+    let line_end = input[start..].find('\n')? + start;
+
+    if is_fence(&input[start..line_end]) {
+        Some(line_end + 1)
+    } else {
+        None
     }
 }
 
-pub fn without(input: &str) -> &str {
-    &input[end_pos(input)..]
+fn find_block(input: &str) -> Option<Block> {
+    let yaml_start = opening_fence_end(input)?;
+    let mut line_start = yaml_start;
+
+    while line_start < input.len() {
+        let line_end = input[line_start..]
+            .find('\n')
+            .map(|i| i + line_start)
+            .unwrap_or(input.len());
+
+        if is_fence(&input[line_start..line_end]) {
+            return Some(Block {
+                yaml: (yaml_start, line_start),
+                end: (line_end + 1).min(input.len()),
+            });
+        }
+
+        line_start = line_end + 1;
+    }
+
+    None
+}
+
+/// A fence is a line of exactly three dashes, optionally followed by
+/// trailing whitespace or a carriage return.
+fn is_fence(line: &str) -> bool {
+    line.trim_end() == "---"
 }
 
 #[cfg(test)]
@@ -56,9 +390,9 @@ mod test {
         let values = parse(input).unwrap();
 
         let expected = Some("Runbooks".to_owned());
-        let actual = values.get("title");
+        let actual = values.get("title").and_then(as_string);
 
-        assert_eq!(actual, expected.as_ref());
+        assert_eq!(actual, expected);
     }
 
     #[test]
@@ -119,12 +453,484 @@ mod test {
         let values = parse(input).unwrap();
 
         let expected = Some("Runbooks".to_owned());
-        let actual = values.get("title");
+        let actual = values.get("title").and_then(as_string);
 
-        assert_eq!(actual, expected.as_ref());
+        assert_eq!(actual, expected);
 
         let without_frontmatter = without(input);
 
         assert_eq!(without_frontmatter, "\r\n# More content\r\n");
     }
+
+    fn assert_same_as_unix(input: &str) {
+        let unix = "---\ntitle: Runbooks\nindex: 2\n---\n\n# Runbooks\n";
+
+        assert_eq!(parse(input).unwrap(), parse(unix).unwrap());
+        assert_eq!(without(input).replace("\r\n", "\n"), without(unix));
+    }
+
+    #[test]
+    fn bom_and_windows_line_endings() {
+        assert_same_as_unix(
+            "\u{feff}---\r\ntitle: Runbooks\r\nindex: 2\r\n---\r\n\r\n# Runbooks\r\n",
+        );
+    }
+
+    #[test]
+    fn bom_only() {
+        assert_same_as_unix("\u{feff}---\ntitle: Runbooks\nindex: 2\n---\n\n# Runbooks\n");
+    }
+
+    #[test]
+    fn windows_line_endings_only() {
+        assert_same_as_unix("---\r\ntitle: Runbooks\r\nindex: 2\r\n---\r\n\r\n# Runbooks\r\n");
+    }
+
+    #[test]
+    fn fences_with_trailing_spaces() {
+        assert_same_as_unix("---  \ntitle: Runbooks\nindex: 2\n--- \t\n\n# Runbooks\n");
+    }
+
+    #[test]
+    fn unterminated_frontmatter() {
+        assert!(is_unterminated("---\ntitle: Runbooks\n\n# Runbooks\n"));
+        assert!(!is_unterminated("---\ntitle: Runbooks\n---\n# Runbooks\n"));
+        assert!(!is_unterminated("# Runbooks\n"));
+    }
+
+    #[test]
+    fn list_values() {
+        let input = indoc! {"
+            ---
+            keywords:
+              - k8s
+              - kube
+            aliases: [one, two]
+            index: 2
+            ---
+        "};
+
+        let values = parse(input).unwrap();
+
+        assert_eq!(
+            as_str_list(&values["keywords"]),
+            Some(vec!["k8s".to_owned(), "kube".to_owned()])
+        );
+        assert_eq!(
+            as_str_list(&values["aliases"]),
+            Some(vec!["one".to_owned(), "two".to_owned()])
+        );
+        assert_eq!(as_u32(&values["index"]), Some(2));
+        assert_eq!(list("a, b,,c "), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn typed_values() {
+        let input = indoc! {"
+            ---
+            draft: true
+            quoted: \"false\"
+            index: 3
+            year: 2024
+            tags: [a, b]
+            tagline: a, b
+            nested: [[a]]
+            ---
+        "};
+
+        let values = parse(input).unwrap();
+
+        assert_eq!(as_bool(&values["draft"]), Some(true));
+        assert_eq!(as_bool(&values["quoted"]), Some(false));
+        assert_eq!(as_bool(&values["index"]), None);
+
+        assert_eq!(as_u32(&values["index"]), Some(3));
+        assert_eq!(as_u32(&values["draft"]), None);
+        assert_eq!(as_u32(&Value::from(-1)), None);
+        assert_eq!(as_u32(&Value::from(u64::from(u32::MAX) + 1)), None);
+
+        assert_eq!(as_string(&values["year"]), Some("2024".to_owned()));
+        assert_eq!(as_string(&values["tags"]), None);
+
+        assert_eq!(
+            as_str_list(&values["tags"]),
+            as_str_list(&values["tagline"])
+        );
+        assert_eq!(as_str_list(&values["nested"]), None);
+    }
+
+    #[test]
+    fn type_errors_name_the_key() {
+        let input = indoc! {"
+            ---
+            title: [Not, a, title]
+            index: banana
+            noindex: maybe
+            keywords: {a: b}
+            unknown: [anything, goes]
+            ---
+        "};
+
+        assert_eq!(
+            type_errors(&parse(input).unwrap()),
+            vec![
+                "Invalid value for `title` in frontmatter. Found 'Not, a, title', expected text",
+                "Invalid value for `index` in frontmatter. Found 'banana', \
+                 expected a positive whole number or auto",
+                "Invalid value for `noindex` in frontmatter. Found 'maybe', expected true or false",
+                "Invalid value for `keywords` in frontmatter. Found 'a: b', expected a list of text",
+            ]
+        );
+    }
+
+    fn document(input: &str) -> crate::Document {
+        crate::Document::new(
+            std::path::Path::new("guide.md"),
+            input.to_owned(),
+            Some(parse(input).unwrap()),
+            "/",
+            std::time::SystemTime::now(),
+        )
+    }
+
+    #[test]
+    fn document_accessors() {
+        let doc = document(indoc! {"
+            ---
+            title: 2024
+            draft: true
+            index: 4
+            tags: [a, b]
+            ---
+        "});
+
+        assert_eq!(doc.title, "2024");
+        assert_eq!(doc.fm_str("title"), Some("2024".to_owned()));
+        assert_eq!(doc.fm_bool("draft"), Some(true));
+        assert_eq!(doc.fm_bool("missing"), None);
+        assert_eq!(doc.fm_u32("index"), Some(4));
+        assert_eq!(doc.index, 4);
+        assert_eq!(
+            doc.fm_str_list("tags"),
+            Some(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert!(doc.warnings().is_empty());
+    }
+
+    #[test]
+    fn index_can_be_auto() {
+        let doc = document(indoc! {"
+            ---
+            index: auto
+            ---
+        "});
+
+        assert_eq!(doc.index, u32::MAX);
+        assert!(doc.warnings().is_empty());
+    }
+
+    #[test]
+    fn documents_warn_about_invalid_known_keys() {
+        let doc = document(indoc! {"
+            ---
+            index: banana
+            ---
+        "});
+
+        assert_eq!(doc.index, u32::MAX);
+        assert_eq!(
+            doc.warnings()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "guide.md : Invalid value for `index` in frontmatter. Found 'banana', \
+                  expected a positive whole number or auto"
+            ]
+        );
+    }
+
+    #[test]
+    fn authors_and_review_dates() {
+        let doc = document(indoc! {"
+            ---
+            author: [Jane Doe, John Roe]
+            date: 2023-06-01
+            reviewed: 2024-01-31
+            ---
+        "});
+
+        assert_eq!(doc.authors(), vec!["Jane Doe", "John Roe"]);
+        assert_eq!(doc.reviewed(), NaiveDate::from_ymd_opt(2024, 1, 31));
+        assert!(doc.warnings().is_empty());
+
+        let doc = document("---\nauthor: Jane Doe\ndate: 2023-06-01\n---\n");
+        assert_eq!(doc.authors(), vec!["Jane Doe"]);
+        assert_eq!(doc.reviewed(), NaiveDate::from_ymd_opt(2023, 6, 1));
+    }
+
+    #[test]
+    fn documents_warn_about_dates_in_other_formats() {
+        let doc = document(indoc! {"
+            ---
+            author: Jane Doe
+            reviewed: 31/01/2024
+            ---
+        "});
+
+        assert_eq!(doc.reviewed(), None);
+        assert_eq!(
+            doc.warnings()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "guide.md : Invalid value for `reviewed` in frontmatter. Found '31/01/2024', \
+                  expected a date like 2024-01-31"
+            ]
+        );
+    }
+
+    #[test]
+    fn descriptions_are_collapsed_onto_one_line() {
+        let doc = document(indoc! {"
+            ---
+            description: |
+              Runbooks for the on-call
+              engineer,   one per alert.
+            ---
+
+            # Runbooks
+        "});
+
+        assert_eq!(
+            doc.meta_description("en"),
+            "Runbooks for the on-call engineer, one per alert."
+        );
+    }
+
+    #[test]
+    fn descriptions_fall_back_to_the_start_of_the_content() {
+        let doc = document("# Runbooks\n\nWhat to do\nwhen an alert fires.");
+        assert_eq!(
+            doc.meta_description("en"),
+            "What to do when an alert fires."
+        );
+
+        let doc = document("# Runbooks");
+        assert_eq!(doc.meta_description("en"), "Documentation for Runbooks");
+        assert_eq!(doc.meta_description("pt-BR"), "Runbooks");
+    }
+
+    #[test]
+    fn descriptions_from_the_content_end_on_a_word_boundary() {
+        let words = "word ".repeat(30);
+        let doc = document(&format!("# Runbooks\n\n{}1234567890", words));
+
+        // 150 characters of words, then the last word would pass 155
+        assert_eq!(doc.meta_description("en"), format!("{}…", words.trim_end()));
+        assert_eq!(doc.meta_description("en").chars().count(), 150);
+
+        let doc = document(&format!("# Runbooks\n\n{}", "word ".repeat(31).trim_end()));
+        assert_eq!(doc.meta_description("en").chars().count(), 154);
+    }
+
+    #[test]
+    fn layouts() {
+        use crate::Layout;
+
+        assert_eq!(document("# Guide").layout(), Layout::Default);
+        assert_eq!(document("---\nlayout: wide\n---\n").layout(), Layout::Wide);
+        assert_eq!(document("---\nlayout: full\n---\n").layout(), Layout::Full);
+
+        let doc = document("---\nlayout: wider\n---\n");
+        assert_eq!(doc.layout(), Layout::Default);
+        assert_eq!(
+            doc.warnings()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "guide.md : Unknown `layout` in frontmatter: 'wider'. \
+                  Expected `default`, `wide` or `full`"
+            ]
+        );
+    }
+
+    #[test]
+    fn page_classes() {
+        let doc = document("---\npage_class: landing -wide_hero\n---\n");
+        assert_eq!(doc.body_class(), "preload page-guide landing -wide_hero");
+        assert_eq!(doc.warnings(), &[]);
+
+        assert_eq!(document("# Guide").body_class(), "preload page-guide");
+
+        let doc = document("---\npage_class: landing 2col x<y\n---\n");
+        assert_eq!(doc.body_class(), "preload page-guide landing");
+        assert_eq!(
+            doc.warnings()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "guide.md : `page_class` in frontmatter is not a valid class name: '2col'. \
+                  Use letters, numbers, `-` and `_`, not starting with a number",
+                "guide.md : `page_class` in frontmatter is not a valid class name: 'x<y'. \
+                  Use letters, numbers, `-` and `_`, not starting with a number",
+            ]
+        );
+    }
+
+    #[test]
+    fn path_classes() {
+        let class = |path: &str, base_path: &str| {
+            crate::Document::new(
+                std::path::Path::new(path),
+                String::new(),
+                None,
+                base_path,
+                std::time::SystemTime::now(),
+            )
+            .path_class()
+        };
+
+        assert_eq!(class("README.md", "/"), "page-index");
+        assert_eq!(class("guides/README.md", "/docs/"), "page-guides--index");
+        assert_eq!(class("guides/setup.md", "/"), "page-guides--setup");
+        assert_eq!(class("getting-started.md", "/"), "page-getting-started");
+
+        assert_eq!(class("guides/set/up.md", "/"), "page-guides--set--up");
+        assert!(class("guides/index.md", "/").starts_with("page-guides--index---"));
+        assert!(class("Guides/Setup.md", "/").starts_with("page-guides--setup---"));
+        assert_eq!(class("Guides/Setup.md", "/"), class("Guides/Setup.md", "/"));
+
+        // Paths that would otherwise end up with the same class
+        let paths = [
+            "guides/README.md",
+            "guides/index.md",
+            "guides/setup.md",
+            "Guides/Setup.md",
+            "guides/setup_1.md",
+            "guides/setup-1.md",
+            "guides/setup-.md",
+            "guides/set--up.md",
+            "guides/set-up.md",
+            "guides/set/up.md",
+            "guides/set/-up.md",
+            "guides/Ünïcode.md",
+        ];
+        let mut classes = paths.map(|path| class(path, "/"));
+        assert!(classes.iter().all(|c| crate::is_class_name(c)));
+
+        classes.sort();
+        assert!(classes.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn edit_links() {
+        let yaml = "---\ntitle: Title\nedit_root: https://github.com/acme/docs/edit/main";
+        let config =
+            crate::config::Config::from_yaml_str(std::path::Path::new("project"), yaml, false)
+                .unwrap();
+        let edit_link = |input: &str| config.build_edit_link(&document(input));
+
+        assert_eq!(
+            edit_link("# Guide").as_deref(),
+            Some("https://github.com/acme/docs/edit/main/docs/guide.md")
+        );
+        assert_eq!(
+            edit_link("---\nedit_url: https://github.com/acme/api/edit/main/api.yaml\n---\n")
+                .as_deref(),
+            Some("https://github.com/acme/api/edit/main/api.yaml")
+        );
+        assert_eq!(edit_link("---\nedit: false\n---\n"), None);
+
+        let doc = document("---\nedit_url: api.yaml\n---\n");
+        assert_eq!(
+            config.build_edit_link(&doc).as_deref(),
+            Some("https://github.com/acme/docs/edit/main/docs/guide.md")
+        );
+        assert_eq!(
+            doc.warnings()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "guide.md : `edit_url` in frontmatter is not an absolute http(s) URL: 'api.yaml'. \
+                  The page links to its file under `edit_root` instead"
+            ]
+        );
+    }
+
+    fn schema() -> FrontmatterSchema {
+        let mut schema: FrontmatterSchema = serde_yaml::from_str(indoc! {r#"
+            keys:
+              owner:
+                required: true
+                type: string
+                pattern: "^@[a-z-]+$"
+              reviewed:
+                required: true
+                type: date
+              tags:
+                type: list
+                pattern: "^[a-z]+$"
+            deny_unknown_keys: true
+        "#})
+        .unwrap();
+        schema.compile().unwrap();
+
+        schema
+    }
+
+    #[test]
+    fn schema_accepts_valid_frontmatter() {
+        let values = parse(indoc! {"
+            ---
+            title: Runbooks
+            owner: '@platform-team'
+            reviewed: 2024-01-31
+            tags: [ops, oncall]
+            ---
+        "})
+        .unwrap();
+
+        assert_eq!(schema_errors(&values, &schema()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn schema_reports_missing_required_keys() {
+        let values = parse("---\nowner: '@platform-team'\n---\n").unwrap();
+
+        assert_eq!(
+            schema_errors(&values, &schema()),
+            vec!["Missing required key `reviewed` in frontmatter"]
+        );
+    }
+
+    #[test]
+    fn schema_reports_wrong_types_and_patterns() {
+        let values = parse(indoc! {"
+            ---
+            owner: bob
+            reviewed: last week
+            tags: [ops, On-Call]
+            reviewer: alice
+            ---
+        "})
+        .unwrap();
+
+        assert_eq!(
+            schema_errors(&values, &schema()),
+            vec![
+                "Invalid value for `owner` in frontmatter. Found 'bob', \
+                 expected it to match `^@[a-z-]+$`",
+                "Invalid value for `reviewed` in frontmatter. Found 'last week', \
+                 expected a date like 2024-01-31",
+                "Invalid value for `tags` in frontmatter. Found 'ops, On-Call', \
+                 expected it to match `^[a-z]+$`",
+                "Unknown key `reviewer` in frontmatter",
+            ]
+        );
+    }
 }
@@ -0,0 +1,257 @@
+//! Refuses release builds of docs with uncommitted changes, when the project
+//! sets `require_clean_git`, so that a published site always matches a
+//! commit. Projects that aren't in a git repository aren't checked, but
+//! git has to be installed to tell.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use crate::config::Config;
+use crate::site::BuildMode;
+use crate::{Error, Result};
+
+/// Fails a release build when the docs or the config have changes that
+/// aren't committed, listing the files, unless `--allow-dirty` was passed.
+/// Dev builds, and projects outside of a git repository, always pass. Fails
+/// when git can't be run, since the changes can't be checked without it.
+pub fn check(config: &Config) -> Result<()> {
+    if !config.require_clean_git()
+        || config.allow_dirty()
+        || config.build_mode() != BuildMode::Release
+    {
+        return Ok(());
+    }
+
+    let mut paths = vec![config.docs_dir(), config.config_path()];
+    paths.extend(config.mounts().iter().map(|mount| mount.dir.as_path()));
+
+    let dirty = match dirty_files(config.project_root(), &paths)? {
+        Some(dirty) if !dirty.is_empty() => dirty,
+        _ => return Ok(()),
+    };
+
+    Err(Error::new(format!(
+        "Found uncommitted changes to {} file(s), and `require_clean_git` is set:\n\n{}\n\n\
+         Commit them before building in release mode, or pass --allow-dirty to build anyway.",
+        dirty.len(),
+        dirty
+            .iter()
+            .map(|path| format!("\t{}", path.display()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )))
+}
+
+/// The files under the paths that are modified, staged, deleted or not
+/// tracked yet, relative to the root of the repository. `None` if the
+/// project isn't in a git repository.
+fn dirty_files(project_root: &Path, paths: &[&Path]) -> Result<Option<Vec<PathBuf>>> {
+    let output = git(project_root, &["rev-parse", "--show-toplevel"])?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let toplevel = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+
+    // Git refuses paths outside of the repository, like a source that is
+    // checked out next to it
+    let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let toplevel = canonical(&toplevel);
+    let paths = paths
+        .iter()
+        .map(|path| canonical(&project_root.join(path)))
+        .filter(|path| path.starts_with(&toplevel))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Ok(Some(vec![]));
+    }
+
+    let mut args = vec!["status", "--porcelain", "-z", "--untracked-files=all", "--"];
+    args.extend(paths.iter().filter_map(|path| path.to_str()));
+    let output = git(project_root, &args)?;
+    if !output.status.success() {
+        return Err(Error::new(format!(
+            "Could not check for uncommitted changes with git status:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(Some(parse_porcelain(&String::from_utf8_lossy(
+        &output.stdout,
+    ))))
+}
+
+/// Runs git in the directory
+fn git(dir: &Path, args: &[&str]) -> Result<Output> {
+    run("git", dir, args)
+}
+
+/// Runs a git binary in the directory, failing if it couldn't be started,
+/// like when git isn't installed
+fn run(program: &str, dir: &Path, args: &[&str]) -> Result<Output> {
+    Command::new(program)
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| {
+            Error::new(format!(
+                "Could not run `{}` to check for uncommitted changes, which \
+                 `require_clean_git` asks for: {}\n\n\
+                 Install git, or pass --allow-dirty to build anyway.",
+                program, e
+            ))
+        })
+}
+
+/// The paths in the output of `git status --porcelain -z`. Every entry is
+/// two status letters, a space and the path. Renamed and copied files are
+/// followed by the path they came from, which is left out.
+fn parse_porcelain(output: &str) -> Vec<PathBuf> {
+    let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+    let mut paths = vec![];
+
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+
+        paths.push(PathBuf::from(&entry[3..]));
+        if entry.starts_with(['R', 'C']) {
+            entries.next();
+        }
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::project;
+    use std::fs;
+
+    /// A project with a committed page, in a git repository unless `git`
+    /// is false
+    fn git_project(name: &str, git: bool) -> (PathBuf, Config) {
+        let root = project(
+            &format!("git-{}", name),
+            &[
+                ("docs/README.md", "# Home"),
+                (
+                    "docgen.yaml",
+                    "---\ntitle: Title\nrequire_clean_git: true\n",
+                ),
+            ],
+        );
+
+        if git {
+            let run = |args: &[&str]| {
+                let output = Command::new("git")
+                    .arg("-C")
+                    .arg(&root)
+                    .args([
+                        "-c",
+                        "user.name=Docgen",
+                        "-c",
+                        "user.email=docgen@example.com",
+                    ])
+                    .args(args)
+                    .output()
+                    .unwrap();
+                assert!(output.status.success(), "{:?}", output);
+            };
+            run(&["init", "--quiet"]);
+            run(&["add", "."]);
+            run(&["commit", "--quiet", "-m", "Docs"]);
+        }
+
+        let mut config = Config::load(&root, false).unwrap();
+        config.set_build_mode(BuildMode::Release);
+
+        (root, config)
+    }
+
+    #[test]
+    fn passes_a_clean_repository() {
+        let (root, config) = git_project("clean", true);
+        let result = check(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn fails_a_release_build_with_uncommitted_changes() {
+        let (root, mut config) = git_project("dirty", true);
+        fs::write(root.join("docs").join("README.md"), "# Changed").unwrap();
+        fs::write(root.join("docs").join("new.md"), "# New").unwrap();
+        fs::write(root.join("notes.txt"), "Not part of the docs").unwrap();
+
+        let error = check(&config).unwrap_err().to_string();
+        config.set_build_mode(BuildMode::Dev);
+        let dev = check(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(
+            error.contains("Found uncommitted changes to 2 file(s)"),
+            "Error message was: {}",
+            error
+        );
+        assert!(error.contains("\tdocs/README.md\n\tdocs/new.md"));
+        assert!(!error.contains("notes.txt"));
+        assert!(dev.is_ok());
+    }
+
+    #[test]
+    fn allow_dirty_builds_anyway() {
+        let (root, mut config) = git_project("allow-dirty", true);
+        fs::write(root.join("docgen.yaml"), "---\ntitle: Changed\n").unwrap();
+
+        let dirty = check(&config);
+        config.set_allow_dirty();
+        let allowed = check(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(dirty.unwrap_err().to_string().contains("\tdocgen.yaml"));
+        assert!(allowed.is_ok());
+    }
+
+    #[test]
+    fn skips_projects_outside_of_a_repository() {
+        let (root, config) = git_project("no-repo", false);
+        let result = check(&config);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn fails_when_git_cannot_be_run() {
+        let error = run("docgen-missing-git", Path::new("."), &["status"])
+            .unwrap_err()
+            .to_string();
+
+        assert!(
+            error
+                .starts_with("Could not run `docgen-missing-git` to check for uncommitted changes"),
+            "Error message was: {}",
+            error
+        );
+        assert!(error.ends_with("Install git, or pass --allow-dirty to build anyway."));
+    }
+
+    #[test]
+    fn reads_renames_from_porcelain_output() {
+        let output = " M docs/README.md\0R  docs/new.md\0docs/old.md\0?? docs/draft.md\0";
+
+        assert_eq!(
+            parse_porcelain(output),
+            vec![
+                PathBuf::from("docs/README.md"),
+                PathBuf::from("docs/new.md"),
+                PathBuf::from("docs/draft.md"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use regex::Regex;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::warning::Warning;
+
+/// Head includes bigger than this are probably pasted stylesheets or fonts,
+/// which are better served as separate files from `_include`.
+pub const MAX_HEAD_INCLUDE_SIZE: usize = 32 * 1024;
+
+/// Elements that never have a closing tag
+static VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements that only belong in the page template, never in an include
+static DOCUMENT_ELEMENTS: &[&str] = &["html", "head", "body"];
+
+lazy_static! {
+    static ref TAG_REGEX: Regex = Regex::new(r"<(/?)([a-zA-Z][a-zA-Z0-9-]*)[^>]*?(/?)>").unwrap();
+}
+
+/// Checks a snippet of HTML that gets injected into the `<head>` of every
+/// page. This is not a full HTML parser: it looks for the mistakes that
+/// break the layout of every page, like unclosed tags.
+pub fn validate(path: &Path, html: &str) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let mut open: Vec<(String, usize)> = vec![];
+    let mut pos = 0;
+
+    if html.len() > MAX_HEAD_INCLUDE_SIZE {
+        warnings.push(Warning::new(
+            path,
+            format!(
+                "Head include is {} KiB, which is included in every page. \
+                 Consider moving large styles or scripts into separate files.",
+                html.len() / 1024
+            ),
+        ));
+    }
+
+    if html.contains("document.write") {
+        warnings.push(Warning::new(
+            path,
+            format!(
+                "line {}: document.write blocks page rendering and should not be used",
+                line_of(html, html.find("document.write").unwrap())
+            ),
+        ));
+    }
+
+    let html = &without_comments(html);
+
+    while let Some(tag) = TAG_REGEX.captures(&html[pos..]) {
+        let (start, end) = {
+            let whole = tag.get(0).unwrap();
+            (pos + whole.start(), pos + whole.end())
+        };
+        let is_closing = !tag[1].is_empty();
+        let is_self_closing = !tag[3].is_empty();
+        let name = tag[2].to_ascii_lowercase();
+        let line = line_of(html, start);
+
+        pos = end;
+
+        if DOCUMENT_ELEMENTS.contains(&name.as_str()) {
+            let tag = if is_closing {
+                format!("</{}>", name)
+            } else {
+                format!("<{}>", name)
+            };
+            warnings.push(Warning::new(
+                path,
+                format!("line {}: Stray {} tag in head include", line, tag),
+            ));
+            continue;
+        }
+
+        if is_closing {
+            match open.iter().rposition(|(open_name, _)| *open_name == name) {
+                Some(index) => {
+                    for (unclosed, unclosed_line) in open.drain(index..).skip(1) {
+                        warnings.push(unclosed_warning(path, &unclosed, unclosed_line));
+                    }
+                }
+                None => warnings.push(Warning::new(
+                    path,
+                    format!(
+                        "line {}: Closing </{}> tag has no matching opening tag",
+                        line, name
+                    ),
+                )),
+            }
+        } else if is_self_closing || VOID_ELEMENTS.contains(&name.as_str()) {
+            continue;
+        } else if name == "script" || name == "style" {
+            // The contents of scripts and styles are not HTML
+            let closing = format!("</{}", name);
+            match html[pos..].to_ascii_lowercase().find(&closing) {
+                Some(i) => {
+                    pos += i;
+                    open.push((name, line));
+                }
+                None => {
+                    warnings.push(unclosed_warning(path, &name, line));
+                    pos = html.len();
+                }
+            }
+        } else {
+            open.push((name, line));
+        }
+    }
+
+    for (unclosed, line) in open {
+        warnings.push(unclosed_warning(path, &unclosed, line));
+    }
+
+    warnings
+}
+
+/// Warns about `href` and `src` attributes that point to a path in the site,
+/// like `/fonts/brand.woff2`, where the site has no file. `has_file` is given
+/// the path relative to the root of the site, after `base_path`. Links to
+/// other sites, and to paths outside of `base_path`, are not checked.
+pub fn missing_references(
+    path: &Path,
+    html: &str,
+    base_path: &str,
+    has_file: impl Fn(&Path) -> bool,
+) -> Vec<Warning> {
+    let html = &without_comments(html);
+
+    TAG_REGEX
+        .find_iter(html)
+        .flat_map(|tag| {
+            attributes(tag.as_str())
+                .into_iter()
+                .filter(|(name, _)| name == "href" || name == "src")
+                .map(move |(name, value)| (line_of(html, tag.start()), name, value))
+        })
+        .filter(|(_, _, value)| value.starts_with('/') && !value.starts_with("//"))
+        .filter_map(|(line, name, value)| {
+            let target = value.split(['?', '#']).next().unwrap_or("");
+            let target = target.strip_prefix(base_path)?;
+            if target.is_empty() || has_file(Path::new(target)) {
+                return None;
+            }
+
+            Some(Warning::new(
+                path,
+                format!(
+                    "line {}: {}=\"{}\" points to a file that is not in the site, \
+                     so every page requests it and gets a 404",
+                    line, name, value
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// The attributes of a tag like `<link rel="icon" href='/icon.svg' defer>`,
+/// with lowercase names. Attributes without a value have an empty one.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    attribute_spans(tag)
+        .into_iter()
+        .map(|attribute| {
+            let value = attribute.value.map_or("", |value| &tag[value]);
+            (attribute.name, value.to_string())
+        })
+        .collect()
+}
+
+/// An attribute of a tag, with where its value is in the tag, inside the
+/// quotes if it has any
+pub struct Attribute {
+    /// The name in lowercase
+    pub name: String,
+    /// `None` if the attribute has no value, like `defer`
+    pub value: Option<Range<usize>>,
+}
+
+/// The attributes of a tag, in the order they are written, along with
+/// where their values are, so that they can be replaced
+pub fn attribute_spans(tag: &str) -> Vec<Attribute> {
+    let inner = tag.strip_suffix('>').unwrap_or(tag);
+    // Where the first character that doesn't match is, from `from` on
+    let skip = |from: usize, matches: &dyn Fn(char) -> bool| -> usize {
+        inner[from..]
+            .find(|c: char| !matches(c))
+            .map_or(inner.len(), |i| from + i)
+    };
+
+    // Past the `<` and the name of the tag
+    let mut pos = skip(skip(0, &|c| c == '<'), &|c| !c.is_whitespace());
+    let mut attributes = vec![];
+
+    loop {
+        pos = skip(pos, &|c| c.is_whitespace() || c == '/');
+        if pos >= inner.len() {
+            break;
+        }
+
+        let name_end = skip(pos, &|c| !c.is_whitespace() && c != '=' && c != '/');
+        let name = inner[pos..name_end].to_ascii_lowercase();
+        pos = skip(name_end, &char::is_whitespace);
+
+        let value = match inner[pos..].strip_prefix('=') {
+            Some(_) => {
+                let start = skip(pos + 1, &char::is_whitespace);
+                let value = match inner[start..].chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = inner[start + 1..]
+                            .find(quote)
+                            .map_or(inner.len(), |i| start + 1 + i);
+                        pos = (end + 1).min(inner.len());
+                        start + 1..end
+                    }
+                    _ => {
+                        pos = skip(start, &|c| !c.is_whitespace());
+                        start..pos
+                    }
+                };
+                Some(value)
+            }
+            None => None,
+        };
+
+        if !name.is_empty() {
+            attributes.push(Attribute { name, value });
+        }
+    }
+
+    attributes
+}
+
+/// Where the tag at the start of `html` ends, just past its `>`. A `>` in a
+/// quoted attribute value, like `<a title="a>b">`, doesn't end it. `None` if
+/// the tag is never closed.
+pub fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut after_equals = false;
+
+    for (i, c) in html.char_indices() {
+        if let Some(open) = quote {
+            if c == open {
+                quote = None;
+            }
+        } else if c == '>' {
+            return Some(i + 1);
+        } else if after_equals && (c == '"' || c == '\'') {
+            quote = Some(c);
+        } else if c == '=' {
+            after_equals = true;
+            continue;
+        } else if c.is_whitespace() && after_equals {
+            continue;
+        }
+        after_equals = false;
+    }
+
+    None
+}
+
+fn unclosed_warning(path: &Path, name: &str, line: usize) -> Warning {
+    Warning::new(
+        path,
+        format!("line {}: <{}> tag is never closed", line, name),
+    )
+}
+
+/// Blanks out comments, keeping newlines so line numbers stay the same
+fn without_comments(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<!--") {
+        let end = rest[start..]
+            .find("-->")
+            .map(|i| start + i + 3)
+            .unwrap_or(rest.len());
+
+        result.push_str(&rest[..start]);
+        result.extend(
+            rest[start..end]
+                .chars()
+                .map(|c| if c == '\n' { '\n' } else { ' ' }),
+        );
+        rest = &rest[end..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn line_of(html: &str, offset: usize) -> usize {
+    html[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn messages(html: &str) -> Vec<String> {
+        validate(Path::new("_include/_head.html"), html)
+            .into_iter()
+            .map(|w| w.message)
+            .collect()
+    }
+
+    #[test]
+    fn clean_include() {
+        let html = indoc! {r##"
+            <!-- Fonts, loaded before <body> -->
+            <link rel="stylesheet" href="/fonts.css">
+            <meta name="theme-color" content="#ffffff" />
+            <style>
+              body > .content { color: red; }
+            </style>
+            <script>
+              if (a < b) { console.log("<div>"); }
+            </script>
+        "##};
+
+        assert_eq!(messages(html), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unclosed_tag() {
+        let html = indoc! {"
+            <link rel=\"stylesheet\" href=\"/fonts.css\">
+            <style>
+              body { color: red; }
+        "};
+
+        assert_eq!(messages(html), vec!["line 2: <style> tag is never closed"]);
+    }
+
+    #[test]
+    fn unclosed_nested_tag() {
+        assert_eq!(
+            messages("<noscript><div>Hello</noscript>"),
+            vec!["line 1: <div> tag is never closed"]
+        );
+    }
+
+    #[test]
+    fn stray_body_tag() {
+        let html = indoc! {"
+            <meta charset=\"utf-8\">
+            </head>
+            <body>
+        "};
+
+        assert_eq!(
+            messages(html),
+            vec![
+                "line 2: Stray </head> tag in head include",
+                "line 3: Stray <body> tag in head include",
+            ]
+        );
+    }
+
+    #[test]
+    fn document_write() {
+        assert_eq!(
+            messages("<script>document.write('hi')</script>"),
+            vec!["line 1: document.write blocks page rendering and should not be used"]
+        );
+    }
+
+    #[test]
+    fn large_include() {
+        let html = format!("<style>{}</style>", "a".repeat(MAX_HEAD_INCLUDE_SIZE));
+
+        let messages = messages(&html);
+
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("Head include is 32 KiB"));
+    }
+
+    #[test]
+    fn missing_references() {
+        let html = indoc! {r#"
+            <link rel="preload" href="/docs/fonts/brand.woff2?v=2" as="font">
+            <script defer src='/docs/scripts/banner.js'></script>
+            <link rel="stylesheet" href="https://fonts.example.com/brand.css">
+            <link rel="icon" href="/favicon.ico">
+        "#};
+
+        let warnings =
+            super::missing_references(Path::new("_include/_head.html"), html, "/docs/", |path| {
+                path == Path::new("fonts/brand.woff2")
+            });
+
+        assert_eq!(
+            warnings
+                .into_iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "_include/_head.html : line 2: src=\"/docs/scripts/banner.js\" points to a \
+                  file that is not in the site, so every page requests it and gets a 404"
+            ]
+        );
+    }
+
+    #[test]
+    fn tag_end() {
+        let html = r#"<a title="a>b" href='/x'>link</a>"#;
+        assert_eq!(super::tag_end(html), Some(html.find("link").unwrap()));
+        assert_eq!(super::tag_end("<a href=/x>b"), Some(11));
+        assert_eq!(super::tag_end(r#"<a title="a>b"#), None);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(
+            super::attributes(r#"<link REL=icon href='/icon.svg' data-x = "a b" defer/>"#),
+            vec![
+                ("rel".to_string(), "icon".to_string()),
+                ("href".to_string(), "/icon.svg".to_string()),
+                ("data-x".to_string(), "a b".to_string()),
+                ("defer".to_string(), "".to_string()),
+            ]
+        );
+    }
+}
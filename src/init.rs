@@ -2,8 +2,9 @@ use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use bunt::termcolor::{ColorChoice, StandardStream};
+use bunt::termcolor::StandardStream;
 
+use crate::terminal::ColorMode;
 use crate::{Error, Result};
 
 pub struct InitCommand {
@@ -12,13 +13,18 @@ pub struct InitCommand {
     custom_docs_dir: Option<String>,
 }
 
+/// A page using every supported Markdown extension. Built without warnings
+/// by the integration tests, so it always matches what this version supports.
+static KITCHEN_SINK: &str = include_str!("../templates/kitchen_sink.md");
+
 impl InitCommand {
-    pub fn run(project_root: PathBuf, colors: bool, custom_doc_root: Option<String>) -> Result<()> {
-        let stdout = if colors {
-            StandardStream::stdout(ColorChoice::Auto)
-        } else {
-            StandardStream::stdout(ColorChoice::Never)
-        };
+    pub fn run(
+        project_root: PathBuf,
+        color: ColorMode,
+        custom_doc_root: Option<String>,
+        with_kitchen_sink: bool,
+    ) -> Result<()> {
+        let stdout = crate::terminal::stdout(color);
 
         let mut cmd = InitCommand {
             stdout,
@@ -45,6 +51,10 @@ impl InitCommand {
             )?;
         }
 
+        if with_kitchen_sink {
+            cmd.create_kitchen_sink()?;
+        }
+
         bunt::writeln!(
             cmd.stdout,
             "\n{$green}Done!{/$} Run {$bold}docgen serve{/$} to view your docs site locally.",
@@ -168,6 +178,32 @@ impl InitCommand {
         Ok(())
     }
 
+    fn create_kitchen_sink(&mut self) -> Result<()> {
+        let path = self.doc_root().join("kitchen-sink.md");
+
+        if !path.exists() {
+            fs::write(&path, KITCHEN_SINK).map_err(|e| {
+                Error::io(
+                    e,
+                    format!(
+                        "Could not create kitchen-sink.md in {}",
+                        self.doc_root().display()
+                    ),
+                )
+            })?;
+
+            let doc_root_name = &self.doc_root_name();
+            let relative_path = Path::new(doc_root_name).join("kitchen-sink.md");
+            bunt::writeln!(
+                self.stdout,
+                "Created {$bold}{}{/$}...",
+                relative_path.display()
+            )?;
+        }
+
+        Ok(())
+    }
+
     fn doc_root_name(&self) -> String {
         match &self.custom_docs_dir {
             Some(doc_root) => doc_root.to_string(),
@@ -7,56 +7,75 @@ extern crate indoc;
 extern crate lazy_static;
 
 pub mod address;
+mod assets;
 mod broken_links_checker;
 mod build;
+mod build_lock;
+pub mod cache;
 pub mod config;
+mod debug;
+mod docs_dir_links_checker;
 pub mod docs_finder;
+mod dry_run;
 mod error;
 mod frontmatter;
+mod git_status;
+mod head_validator;
 mod init;
+mod link_graph;
 mod livereload_server;
+mod manifest;
 pub mod markdown;
+mod migrate;
 mod nav;
 pub mod navigation;
+mod orphans_checker;
 mod page_template;
+pub mod post_processor;
+mod preview;
 mod preview_server;
+mod routing;
 #[allow(dead_code, unused_variables)]
 mod serve;
 mod site;
 mod site_generator;
+mod site_report;
+pub mod terminal;
+#[cfg(test)]
+mod test_helpers;
+mod todo_checker;
+mod warning;
 mod watcher;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 use std::fs::{self};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 
+use chrono::NaiveDate;
+
+pub use assets::ASSETS_MAP;
 pub use build::BuildCommand;
+pub use cache::CacheCommand;
 pub use config::Config;
+pub use debug::DebugCommand;
 pub use error::Error;
 pub use init::InitCommand;
+pub use manifest::ManifestCommand;
 use markdown::extensions::toc::Heading;
-use markdown::parser::{MarkdownParser, ParseOptions, ParsedMarkdown};
+use markdown::parser::{truncate_preview, MarkdownParser, ParseOptions, ParsedMarkdown};
+pub use migrate::MigrateCommand;
 pub use nav::NavigationCommand;
+pub use preview::PreviewCommand;
 pub use serve::{ServeCommand, ServeOptions};
 pub use site::BuildMode;
+pub use terminal::ColorMode;
+pub use watcher::DEFAULT_POLL_INTERVAL;
 
-use include_dir::{include_dir, Dir};
-use navigation::Link;
-
-static ASSETS: Dir = include_dir!("$CARGO_MANIFEST_DIR/dist/");
-
-lazy_static! {
-    pub static ref ASSETS_MAP: HashMap<String, String> = {
-        let assets_map = ASSETS
-            .get_file("assets_map.json")
-            .unwrap()
-            .contents_utf8()
-            .unwrap();
-
-        serde_json::from_str::<HashMap<String, String>>(assets_map).unwrap()
-    };
-}
+use config::NavTitleSource;
+use frontmatter::Frontmatter;
+use warning::Warning;
 
 pub type Result<T> = std::result::Result<T, error::Error>;
 
@@ -67,6 +86,10 @@ static DOCUMENT_ID: AtomicU32 = AtomicU32::new(1);
 #[derive(Debug, Clone, PartialEq)]
 pub struct Document {
     pub index: u32,
+    /// The position of the page in the `.order` file of the directory it is
+    /// listed in, which takes precedence over `index`. `u32::MAX` when the
+    /// file leaves the page out, and `None` without an order file.
+    pub order: Option<u32>,
     pub id: u32,
     /// The relative path in the docs folder to the file
     path: PathBuf,
@@ -82,53 +105,253 @@ pub struct Document {
 
     raw: String,
     markdown: ParsedMarkdown,
-    frontmatter: BTreeMap<String, String>,
+    frontmatter: Frontmatter,
     base_path: String,
     title: String,
-    description: String,
+    /// Title shown in the navigation instead of the page title
+    nav_title: Option<String>,
+    /// The language of the page, if it's not the language of the site
+    lang: Option<String>,
+    /// From the frontmatter, or else the start of the content. None if the
+    /// page has neither.
+    description: Option<String>,
 
     last_modified: SystemTime,
+
+    /// Problems found while loading the document
+    warnings: Vec<Warning>,
 }
 
-impl Document {
-    /// Loads a document from disk and parses it.
+/// How much of the width of the window a page takes, from the `layout`
+/// frontmatter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// The site navigation, the content, and the "On this page" sidebar
+    Default,
+    /// No "On this page" sidebar, for big tables and diagrams
+    Wide,
+    /// Only the content, for landing pages
+    Full,
+}
+
+impl Layout {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "default" => Some(Layout::Default),
+            "wide" => Some(Layout::Wide),
+            "full" => Some(Layout::Full),
+            _ => None,
+        }
+    }
+
+    /// The classes of the container of the sidebars and the content
+    fn container_class(&self) -> &'static str {
+        match self {
+            Layout::Default => "container",
+            Layout::Wide => "container layout-wide",
+            Layout::Full => "container layout-full",
+        }
+    }
+}
+
+/// The most characters of a page's content used as its description, about
+/// as many as search engines show
+const DERIVED_DESCRIPTION_LENGTH: usize = 155;
+
+/// Turns every run of whitespace, including newlines, into a single space
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether the value is an absolute http or https URL
+fn is_http_url(value: &str) -> bool {
+    matches!(url::Url::parse(value), Ok(url) if url.scheme() == "http" || url.scheme() == "https")
+}
+
+/// Whether the value is a class name that can be used in HTML and CSS without
+/// escaping, like `landing` or `-wide_hero`
+fn is_class_name(value: &str) -> bool {
+    let name = value.strip_prefix('-').unwrap_or(value);
+    let mut chars = name.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Whether a part of a path is made only of lowercase letters and numbers,
+/// separated by single hyphens, so it can be used in a class as it is
+fn is_class_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && !segment.starts_with('-')
+        && !segment.ends_with('-')
+        && !segment.contains("--")
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// A number of bytes in the largest unit that keeps it above 1, like `5 MB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 || size.fract() == 0.0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// A Markdown file as it was read from disk, before it is rendered. Cheap to
+/// clone, so that it can be kept and rendered again, e.g. after the
+/// configuration changed, without reading the file again.
+#[derive(Debug, Clone)]
+pub struct SourceDocument {
+    /// The relative path in the docs folder to the file
+    path: PathBuf,
+    raw: Arc<str>,
+    frontmatter: Arc<Frontmatter>,
+    last_modified: SystemTime,
+    /// Problems found while reading the file
+    warnings: Vec<Warning>,
+    /// See `Document::order`
+    order: Option<u32>,
+}
+
+impl SourceDocument {
+    /// Reads a document from disk, without rendering it.
     ///
     /// Must be provided both the absolute path to the file, and the relative
-    /// path inside the docs directory to the original file.
-    fn load(absolute_path: &Path, relative_docs_path: &Path, base_path: &str) -> Self {
-        let raw = fs::read_to_string(absolute_path).unwrap();
-        let metadata = fs::metadata(absolute_path).unwrap();
-        let frontmatter =
-            frontmatter::parse(&raw).expect("TODO: Print an error when frontmatter is busted");
-
-        Document::new(
+    /// path inside the docs directory to the original file. Files larger
+    /// than `max_size` bytes, and files that aren't text, are not read. The
+    /// returned warning says why.
+    pub fn load(
+        absolute_path: &Path,
+        relative_docs_path: &Path,
+        max_size: u64,
+    ) -> std::result::Result<Self, Warning> {
+        let skip =
+            |reason: String| Warning::new(relative_docs_path, format!("Skipped, {}", reason));
+
+        let metadata = fs::metadata(absolute_path)
+            .map_err(|e| skip(format!("could not read the file: {}", e)))?;
+        if metadata.len() > max_size {
+            return Err(skip(format!(
+                "the file is {}, larger than the `max_page_size` of {}",
+                format_size(metadata.len()),
+                format_size(max_size)
+            )));
+        }
+
+        let bytes =
+            fs::read(absolute_path).map_err(|e| skip(format!("could not read the file: {}", e)))?;
+        if bytes.contains(&0) {
+            return Err(skip(String::from(
+                "the file contains NUL bytes. It looks like a binary file, not Markdown",
+            )));
+        }
+        let raw = String::from_utf8(bytes).map_err(|e| {
+            skip(format!(
+                "the file is not valid UTF-8, found an invalid byte at offset {}",
+                e.utf8_error().valid_up_to()
+            ))
+        })?;
+
+        let mut warnings = vec![];
+
+        let frontmatter = frontmatter::parse(&raw).unwrap_or_else(|e| {
+            warnings.push(Warning::new(
+                relative_docs_path,
+                format!("Could not parse frontmatter, ignoring it: {}", e),
+            ));
+            BTreeMap::new()
+        });
+
+        if frontmatter::is_unterminated(&raw) {
+            warnings.push(Warning::new(
+                relative_docs_path,
+                "Frontmatter was opened with `---` but never closed. It will be rendered as content.",
+            ));
+        }
+        warnings.append(&mut migrate::legacy_callout_warnings(
             relative_docs_path,
-            raw,
-            frontmatter,
-            base_path,
-            metadata.modified().unwrap_or_else(|_| SystemTime::now()),
-        )
+            &raw,
+        ));
+
+        Ok(SourceDocument {
+            path: relative_docs_path.to_path_buf(),
+            raw: Arc::from(raw),
+            frontmatter: Arc::new(frontmatter),
+            last_modified: metadata.modified().unwrap_or_else(|_| SystemTime::now()),
+            warnings,
+            order: None,
+        })
     }
 
-    /// Creates a new document from its raw components
+    /// The relative path in the docs folder to the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Document {
+    /// Renders a document read from disk with the given options. Nothing is
+    /// read from disk, so the same source can be rendered any number of times.
+    pub fn render(source: &SourceDocument, options: &ParseOptions) -> Self {
+        let mut doc = Document::with_options(
+            &source.path,
+            source.raw.to_string(),
+            Some((*source.frontmatter).clone()),
+            options.clone(),
+            source.last_modified,
+        );
+
+        let mut warnings = source.warnings.clone();
+        warnings.append(&mut doc.warnings);
+        doc.warnings = warnings;
+        doc.order = source.order;
+        doc
+    }
+
+    /// Creates a new document from its raw components, with `None` for a
+    /// page without frontmatter.
     pub fn new(
         path: &Path,
         raw: String,
-        frontmatter: BTreeMap<String, String>,
+        frontmatter: Option<Frontmatter>,
         base_path: &str,
         last_modified: SystemTime,
     ) -> Self {
-        let is_root = path.ends_with("README.md");
-        let html_path = if is_root {
-            path.with_file_name("index.html")
-        } else {
-            path.with_extension("html")
-        };
+        let mut options = ParseOptions::default();
+        options.url_root = base_path.to_owned();
 
-        let mut uri_path = format!("{}{}", base_path, Link::path_to_uri(&html_path));
-        if is_root && !uri_path.ends_with("/") {
-            uri_path.push_str("/");
-        }
+        Document::with_options(path, raw, frontmatter, options, last_modified)
+    }
+
+    /// Creates a new document from its raw components, parsing the Markdown
+    /// with the given options. The options' `url_root` is used as the base path.
+    pub fn with_options(
+        path: &Path,
+        raw: String,
+        frontmatter: Option<Frontmatter>,
+        mut markdown_options: ParseOptions,
+        last_modified: SystemTime,
+    ) -> Self {
+        let frontmatter = frontmatter.unwrap_or_default();
+        let warnings = frontmatter::type_errors(&frontmatter)
+            .into_iter()
+            .map(|message| Warning::new(path, message))
+            .collect();
+
+        let base_path = markdown_options.url_root.clone();
+        let (html_path, uri_path) =
+            routing::output_path(path, &base_path, markdown_options.clean_urls);
 
         let mut path_elements = uri_path
             .trim_end_matches("/")
@@ -144,56 +367,126 @@ impl Document {
             Path::new(&path_elements.join("/")).to_path_buf()
         };
 
-        let markdown_options = {
-            let mut opts = ParseOptions::default();
-            opts.url_root = base_path.to_owned();
-            opts
-        };
+        markdown_options.page_uri = Some(uri_path.clone());
+        markdown_options.page_path = Some(path.to_path_buf());
+        markdown_options.nested_page = html_path.parent() != path.parent();
+        if markdown_options.relative_links {
+            markdown_options.relative_root = Some(relative_root(&uri_path, &base_path));
+        }
+
+        // Pages can opt in or out of smart punctuation and heading numbers
+        if let Some(smart_punctuation) = frontmatter
+            .get("smart_punctuation")
+            .and_then(frontmatter::as_bool)
+        {
+            markdown_options.smart_punctuation = smart_punctuation;
+        }
+        if let Some(number_headings) = frontmatter
+            .get("number_headings")
+            .and_then(frontmatter::as_bool)
+        {
+            markdown_options.number_headings = number_headings;
+        }
 
         let mut parser = MarkdownParser::new(Some(markdown_options));
         let markdown = parser.parse(frontmatter::without(&raw));
 
-        let title = frontmatter
-            .get("title")
-            .map(|t| t.as_ref())
-            .or_else(|| {
-                if markdown.headings.len() > 0 {
-                    Some(markdown.headings[0].title.as_str())
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_else(|| path.file_stem().unwrap().to_str().unwrap())
-            .to_string();
-
-        let description = frontmatter
-            .get("description")
-            .map(|t| t.to_owned())
-            .or_else(|| Some("Documentation for ".to_owned() + &title))
-            .unwrap();
-
-        Document {
-            index: frontmatter
-                .get("index")
-                .and_then(|idx| idx.parse::<u32>().ok())
-                .unwrap_or(u32::MAX),
+        let mut doc = Document {
+            index: u32::MAX,
+            order: None,
             id: DOCUMENT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
             path: path.to_path_buf(),
-            base_path: base_path.to_owned(),
+            base_path,
             raw,
             markdown,
             frontmatter,
             html_path,
             uri_path,
-            description,
-            title,
+            description: None,
+            title: String::new(),
+            nav_title: None,
+            lang: None,
             parent,
             last_modified,
+            warnings,
+        };
+
+        doc.index = doc.fm_u32("index").unwrap_or(u32::MAX);
+        doc.title = doc
+            .fm_str("title")
+            .or_else(|| doc.headings().first().map(|h| h.title.clone()))
+            .unwrap_or_else(|| path.file_stem().unwrap().to_str().unwrap().to_string());
+        doc.description = doc
+            .fm_str("description")
+            .map(|description| collapse_whitespace(&description))
+            .or_else(|| {
+                let preview = collapse_whitespace(doc.preview());
+                Some(truncate_preview(&preview, DERIVED_DESCRIPTION_LENGTH))
+            })
+            .filter(|description| !description.is_empty());
+        doc.nav_title = doc.fm_str("nav_title");
+        doc.lang = doc.fm_str("lang");
+
+        if let Some(lang) = doc.lang.as_deref().filter(|l| !config::is_language_tag(l)) {
+            let message = format!(
+                "`lang` in frontmatter doesn't look like a language tag: '{}'. \
+                 Expected something like `en` or `pt-BR`",
+                lang
+            );
+            doc.warnings.push(Warning::new(path, message));
+        }
+
+        if let Some(layout) = doc.fm_str("layout").filter(|l| Layout::parse(l).is_none()) {
+            let message = format!(
+                "Unknown `layout` in frontmatter: '{}'. Expected `default`, `wide` or `full`",
+                layout
+            );
+            doc.warnings.push(Warning::new(path, message));
         }
+
+        if let Some(url) = doc.fm_str("edit_url").filter(|url| !is_http_url(url)) {
+            let message = format!(
+                "`edit_url` in frontmatter is not an absolute http(s) URL: '{}'. \
+                 The page links to its file under `edit_root` instead",
+                url
+            );
+            doc.warnings.push(Warning::new(path, message));
+        }
+
+        if let Some(classes) = doc.fm_str("page_class") {
+            for class in classes.split_whitespace().filter(|c| !is_class_name(c)) {
+                let message = format!(
+                    "`page_class` in frontmatter is not a valid class name: '{}'. \
+                     Use letters, numbers, `-` and `_`, not starting with a number",
+                    class
+                );
+                doc.warnings.push(Warning::new(path, message));
+            }
+        }
+
+        if let Some(error) = &doc.markdown.parse_error {
+            doc.warnings.push(Warning::new(path, error.clone()));
+        }
+
+        for warning in &doc.markdown.warnings {
+            doc.warnings.push(Warning::new(path, warning.clone()));
+        }
+
+        doc
+    }
+
+    /// The path from this page back to the root of the site, like `../../`
+    fn relative_root(&self) -> String {
+        relative_root(&self.uri_path, &self.base_path)
+    }
+
+    /// Whether this is the README of its directory
+    fn is_readme(&self) -> bool {
+        routing::is_readme(&self.path)
     }
 
     fn src(&self) -> String {
-        let is_root = self.path.ends_with("README.md");
+        let is_root = self.is_readme();
         if is_root {
             self.path.parent().unwrap().to_string_lossy().to_string()
         } else {
@@ -201,6 +494,35 @@ impl Document {
         }
     }
 
+    /// The title of the page in the navigation. The `nav_title` frontmatter
+    /// key always wins, otherwise the title comes from the given source.
+    fn nav_title(&self, source: NavTitleSource) -> String {
+        if let Some(nav_title) = &self.nav_title {
+            return nav_title.clone();
+        }
+
+        let file_name = || {
+            let name = if self.path.ends_with("README.md") {
+                self.path.parent().and_then(|p| p.file_name())
+            } else {
+                self.path.file_stem()
+            };
+
+            name.map(|n| n.to_string_lossy().to_string())
+        };
+
+        match source {
+            NavTitleSource::Frontmatter => self.title.clone(),
+            NavTitleSource::Heading => self
+                .headings()
+                .first()
+                .map(|h| h.title.clone())
+                .or_else(file_name)
+                .unwrap_or_else(|| self.title.clone()),
+            NavTitleSource::Filename => file_name().unwrap_or_else(|| self.title.clone()),
+        }
+    }
+
     fn original_path(&self) -> &Path {
         &self.path
     }
@@ -210,6 +532,181 @@ impl Document {
         out.join(&self.html_path)
     }
 
+    /// Problems found while loading the document
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// A frontmatter value as text. Numbers and booleans are written out,
+    /// lists are not text.
+    fn fm_str(&self, key: &str) -> Option<String> {
+        self.frontmatter.get(key).and_then(frontmatter::as_string)
+    }
+
+    /// A frontmatter value that is `true` or `false`
+    fn fm_bool(&self, key: &str) -> Option<bool> {
+        self.frontmatter.get(key).and_then(frontmatter::as_bool)
+    }
+
+    /// A frontmatter value that is a positive whole number
+    fn fm_u32(&self, key: &str) -> Option<u32> {
+        self.frontmatter.get(key).and_then(frontmatter::as_u32)
+    }
+
+    /// A frontmatter value that is a list, or a comma separated string
+    fn fm_str_list(&self, key: &str) -> Option<Vec<String>> {
+        self.frontmatter.get(key).and_then(frontmatter::as_str_list)
+    }
+
+    /// Whether the page asked not to be indexed by search engines
+    fn noindex(&self) -> bool {
+        self.fm_bool("noindex").unwrap_or(false)
+    }
+
+    /// Extra search terms for the page, from the `keywords` frontmatter
+    fn keywords(&self) -> Vec<String> {
+        self.fm_str_list("keywords").unwrap_or_default()
+    }
+
+    /// The authors of the page, from the `author` frontmatter, which is a
+    /// single name or a list of them
+    pub fn authors(&self) -> Vec<String> {
+        self.fm_str_list("author").unwrap_or_default()
+    }
+
+    /// When the page was last reviewed, from the `reviewed` frontmatter or
+    /// else from `date`
+    pub fn reviewed(&self) -> Option<NaiveDate> {
+        ["reviewed", "date"]
+            .iter()
+            .find_map(|key| self.frontmatter.get(*key).and_then(frontmatter::as_date))
+    }
+
+    /// The description of the page for search engines. Pages without one
+    /// get a generic description in English, or else their title.
+    fn meta_description(&self, lang: &str) -> String {
+        match &self.description {
+            Some(description) => description.clone(),
+            None if lang == "en" || lang.starts_with("en-") => {
+                format!("Documentation for {}", self.title)
+            }
+            None => self.title.clone(),
+        }
+    }
+
+    /// How much of the width of the window the page takes
+    pub fn layout(&self) -> Layout {
+        self.fm_str("layout")
+            .and_then(|layout| Layout::parse(&layout))
+            .unwrap_or(Layout::Default)
+    }
+
+    /// The classes from the `page_class` frontmatter, without the ones that
+    /// aren't valid class names
+    fn page_classes(&self) -> Vec<String> {
+        self.fm_str("page_class")
+            .map(|classes| {
+                classes
+                    .split_whitespace()
+                    .filter(|c| is_class_name(c))
+                    .map(|c| c.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// A class derived from the path of the page, like `page-guides--setup`
+    /// for `guides/setup.md` and `page-guides--index` for its README. Parts
+    /// of the path are joined with `--`, so no two pages share a class. Paths
+    /// with other characters, or pages named `index.md`, get a readable
+    /// version of the path followed by `---` and a hash of it instead.
+    fn path_class(&self) -> String {
+        let source = self.path.to_string_lossy().replace('\\', "/");
+        let mut segments = source
+            .strip_suffix(".md")
+            .unwrap_or(&source)
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+
+        let is_readme = self.is_readme();
+        let reserved = !is_readme && segments.last() == Some(&"index");
+        if is_readme {
+            segments.pop();
+            segments.push("index");
+        }
+
+        if !reserved && segments.iter().all(|s| is_class_segment(s)) {
+            return format!("page-{}", segments.join("--"));
+        }
+
+        let readable = segments
+            .iter()
+            .map(|segment| {
+                segment
+                    .to_ascii_lowercase()
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("-")
+            })
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("--");
+
+        format!(
+            "page-{}---{:08x}",
+            readable,
+            crate::site::content_hash(source.as_bytes()) as u32
+        )
+    }
+
+    /// The classes of the body element of the page: `preload`, the class
+    /// derived from its path and the ones from its `page_class` frontmatter
+    pub fn body_class(&self) -> String {
+        let mut classes = vec!["preload".to_string(), self.path_class()];
+        classes.extend(self.page_classes());
+        classes.join(" ")
+    }
+
+    /// Whether the page shows related pages below its content
+    fn shows_related_pages(&self) -> bool {
+        self.fm_bool("related_pages").unwrap_or(true)
+    }
+
+    /// Where the "Edit this page" link points, when the page is made from
+    /// another file, from the `edit_url` frontmatter
+    fn edit_url(&self) -> Option<String> {
+        self.fm_str("edit_url").filter(|url| is_http_url(url))
+    }
+
+    /// Whether the page has an "Edit this page" link at all
+    fn editable(&self) -> bool {
+        self.fm_bool("edit").unwrap_or(true)
+    }
+
+    /// Whether the page shows the `content_header` of the config
+    fn shows_content_header(&self) -> bool {
+        self.fm_bool("content_header").unwrap_or(true)
+    }
+
+    /// Whether the page shows the `content_footer` of the config
+    fn shows_content_footer(&self) -> bool {
+        self.fm_bool("content_footer").unwrap_or(true)
+    }
+
+    /// Whether the page is intentionally not linked from anywhere
+    fn unlisted(&self) -> bool {
+        self.fm_bool("unlisted").unwrap_or(false)
+    }
+
+    /// Whether the page shows up in lists of pages, like the children of a
+    /// page, the search and the recent pages. Unlisted pages and drafts are
+    /// built, but only found by their URL.
+    fn listed(&self) -> bool {
+        !self.unlisted() && !self.fm_bool("draft").unwrap_or(false)
+    }
+
     fn preview(&self) -> &String {
         &self.markdown.preview
     }
@@ -222,7 +719,49 @@ impl Document {
         &self.markdown.links
     }
 
+    /// The text of the HTML comments in the page's Markdown
+    fn comments(&self) -> &[String] {
+        &self.markdown.comments
+    }
+
+    /// Whether the page's Markdown has a `{% children %}` line
+    fn has_children_directive(&self) -> bool {
+        self.markdown.lists_children
+    }
+
+    /// Links starting with the name of the docs directory, to files that
+    /// aren't in the site
+    fn docs_dir_links(&self) -> &[markdown::extensions::link_rewriter::DocsDirLink] {
+        &self.markdown.docs_dir_links
+    }
+
+    /// Why the include directives that couldn't be expanded were left out
+    fn include_errors(&self) -> &[String] {
+        &self.markdown.include_errors
+    }
+
+    /// The content of the page as a tree, if it was parsed with `emit_ast`
+    fn ast(&self) -> Option<&[markdown::ast::Node]> {
+        self.markdown.ast.as_deref()
+    }
+
     fn html(&self) -> &String {
         &self.markdown.html
     }
 }
+
+/// The path from a page back to the root of the site, e.g. `../../` for
+/// `/guides/setup/install`. Pages in the root directory get `./`.
+fn relative_root(uri_path: &str, base_path: &str) -> String {
+    let depth = uri_path
+        .strip_prefix(base_path)
+        .unwrap_or(uri_path)
+        .matches('/')
+        .count();
+
+    if depth == 0 {
+        String::from("./")
+    } else {
+        "../".repeat(depth)
+    }
+}
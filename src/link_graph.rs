@@ -0,0 +1,233 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Serialize;
+
+use crate::markdown::extensions::link_rewriter::UrlType;
+use crate::Document;
+
+/// Score for pages that link to each other, in either direction
+const LINK_SCORE: u32 = 2;
+/// Score for pages in the same directory
+const SIBLING_SCORE: u32 = 1;
+
+/// The internal links between documents, by index into the documents the
+/// graph was built from.
+pub struct LinkGraph<'a> {
+    docs: &'a [Document],
+    /// Outgoing links of each document
+    links: Vec<BTreeSet<usize>>,
+}
+
+/// A page suggested at the bottom of another page
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelatedPage {
+    pub title: String,
+    pub uri: String,
+}
+
+#[derive(Serialize)]
+struct GraphJson<'a> {
+    nodes: Vec<NodeJson<'a>>,
+    edges: Vec<EdgeJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct NodeJson<'a> {
+    uri: &'a str,
+    title: &'a str,
+}
+
+#[derive(Serialize)]
+struct EdgeJson<'a> {
+    from: &'a str,
+    to: &'a str,
+}
+
+impl<'a> LinkGraph<'a> {
+    pub fn build(docs: &'a [Document]) -> Self {
+        let by_uri = docs
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| (normalize(&doc.uri_path), i))
+            .collect::<HashMap<_, _>>();
+
+        let links = docs
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| {
+                doc.outgoing_links()
+                    .iter()
+                    .filter_map(|link| match &link.url {
+                        UrlType::Local(path) => path.to_str(),
+                        UrlType::Remote(_) => None,
+                    })
+                    .filter(|path| path.starts_with('/'))
+                    .filter_map(|path| by_uri.get(&normalize(path)).copied())
+                    .filter(|target| *target != i)
+                    .collect()
+            })
+            .collect();
+
+        LinkGraph { docs, links }
+    }
+
+    /// Picks up to `limit` pages related to the document at `index`. Pages
+    /// linking to or from it come first, then pages in the same directory.
+    pub fn related(&self, index: usize, limit: usize) -> Vec<RelatedPage> {
+        let doc = &self.docs[index];
+
+        let mut scored = self
+            .docs
+            .iter()
+            .enumerate()
+            .filter(|(i, other)| *i != index && !other.unlisted())
+            .filter_map(|(i, other)| {
+                let mut score = 0;
+
+                if self.links[index].contains(&i) || self.links[i].contains(&index) {
+                    score += LINK_SCORE;
+                }
+                if other.parent == doc.parent {
+                    score += SIBLING_SCORE;
+                }
+
+                if score > 0 {
+                    Some((score, other))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| a.uri_path.cmp(&b.uri_path))
+        });
+
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, other)| RelatedPage {
+                title: other.title.clone(),
+                uri: other.uri_path.clone(),
+            })
+            .collect()
+    }
+
+    /// The graph as JSON, with a node per page and an edge per link.
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .docs
+            .iter()
+            .map(|doc| NodeJson {
+                uri: &doc.uri_path,
+                title: &doc.title,
+            })
+            .collect();
+
+        let edges = self
+            .links
+            .iter()
+            .enumerate()
+            .flat_map(|(from, targets)| {
+                targets.iter().map(move |to| EdgeJson {
+                    from: &self.docs[from].uri_path,
+                    to: &self.docs[*to].uri_path,
+                })
+            })
+            .collect();
+
+        serde_json::to_string(&GraphJson { nodes, edges }).unwrap()
+    }
+}
+
+/// Turns a link or URI path into a form where `/foo`, `/foo/`,
+/// `/foo.html` and `/foo#bar` all compare equal.
+fn normalize(path: &str) -> String {
+    let path = path.split(|c| c == '#' || c == '?').next().unwrap_or("");
+    let path = path.trim_end_matches('/');
+
+    path.strip_suffix(".html")
+        .map(|p| p.strip_suffix("/index").unwrap_or(p))
+        .unwrap_or(path)
+        .to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::page;
+
+    fn titles(pages: Vec<RelatedPage>) -> Vec<String> {
+        pages.into_iter().map(|p| p.title).collect()
+    }
+
+    fn fixture() -> Vec<Document> {
+        vec![
+            page("README.md", "[Setup](/guides/setup)", &[("title", "Home")]),
+            page("guides/README.md", "Guides", &[("title", "Guides")]),
+            page(
+                "guides/setup.md",
+                "[Deploy](/reference/deploy.html#top)",
+                &[("title", "Setup")],
+            ),
+            page("guides/upgrade.md", "No links", &[("title", "Upgrade")]),
+            page(
+                "reference/README.md",
+                "Reference",
+                &[("title", "Reference")],
+            ),
+            page("reference/deploy.md", "No links", &[("title", "Deploy")]),
+        ]
+    }
+
+    #[test]
+    fn links_are_related_in_both_directions() {
+        let docs = fixture();
+        let graph = LinkGraph::build(&docs);
+
+        assert!(titles(graph.related(2, 5)).contains(&"Deploy".to_owned()));
+        assert!(titles(graph.related(5, 5)).contains(&"Setup".to_owned()));
+
+        assert!(titles(graph.related(0, 5)).contains(&"Setup".to_owned()));
+        assert!(titles(graph.related(2, 5)).contains(&"Home".to_owned()));
+    }
+
+    #[test]
+    fn links_rank_above_siblings() {
+        let docs = fixture();
+        let graph = LinkGraph::build(&docs);
+
+        assert_eq!(
+            titles(graph.related(2, 5)),
+            vec!["Home", "Deploy", "Upgrade"]
+        );
+        assert_eq!(titles(graph.related(2, 2)), vec!["Home", "Deploy"]);
+    }
+
+    #[test]
+    fn pages_without_links_fall_back_to_siblings() {
+        let docs = fixture();
+        let graph = LinkGraph::build(&docs);
+
+        assert_eq!(titles(graph.related(3, 5)), vec!["Setup"]);
+    }
+
+    #[test]
+    fn exports_edges() {
+        let docs = fixture();
+        let graph = LinkGraph::build(&docs);
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 6);
+        assert_eq!(
+            json["edges"],
+            serde_json::json!([
+                { "from": "/", "to": "/guides/setup" },
+                { "from": "/guides/setup", "to": "/reference/deploy" },
+            ])
+        );
+    }
+}
@@ -10,6 +10,8 @@ use tungstenite::error::Error as TungsteniteError;
 use tungstenite::handshake::HandshakeError;
 use tungstenite::protocol::WebSocket;
 
+use crate::terminal::{self, ColorMode};
+
 /// Sets up a websocket server listening for livereload connections,
 /// and pushes updates to the browser when notified.
 ///
@@ -20,15 +22,58 @@ use tungstenite::protocol::WebSocket;
 /// other means.
 pub struct LivereloadServer {
     addr: SocketAddr,
-    channel: Receiver<()>,
-    bus: Arc<Mutex<Bus<()>>>,
+    channel: Receiver<Reload>,
+    bus: Arc<Mutex<Bus<Reload>>>,
+    color: ColorMode,
+}
+
+/// What the browser should reload
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reload {
+    /// The whole page, after a build with the given timestamp that changed
+    /// the given files of the site
+    Page {
+        timestamp: String,
+        changed: Vec<String>,
+    },
+    /// Only the stylesheet at the given path in the site, without losing
+    /// the state of the page
+    Stylesheet(String),
+    /// Nothing, but tell the reader that the site could not be rebuilt, and
+    /// why. The page stays as it was.
+    BuildFailed(String),
+}
+
+impl Reload {
+    fn message(&self) -> String {
+        match self {
+            Reload::Page { timestamp, changed } => serde_json::json!({
+                "command": "reload",
+                "path": "",
+                "liveCSS": true,
+                "timestamp": timestamp,
+                "changed": changed
+            }),
+            Reload::Stylesheet(path) => serde_json::json!({
+                "command": "reload",
+                "path": path,
+                "liveCSS": true
+            }),
+            Reload::BuildFailed(error) => serde_json::json!({
+                "command": "alert",
+                "message": format!("Build failed: {}", error)
+            }),
+        }
+        .to_string()
+    }
 }
 
 impl LivereloadServer {
-    pub fn new(addr: SocketAddr, channel: Receiver<()>) -> Self {
+    pub fn new(addr: SocketAddr, channel: Receiver<Reload>, color: ColorMode) -> Self {
         LivereloadServer {
             addr,
             channel,
+            color,
             bus: Arc::new(Mutex::new(Bus::new(128))),
         }
     }
@@ -40,16 +85,16 @@ impl LivereloadServer {
         let bus_clone = self.bus.clone();
         thread::Builder::new()
             .name("livereload-listener".into())
-            .spawn(move || run_listener(self.addr, bus_clone))
+            .spawn(move || run_listener(self.addr, bus_clone, self.color))
             .unwrap();
 
-        for _msg in self.channel {
-            self.bus.lock().unwrap().broadcast(());
+        for msg in self.channel {
+            self.bus.lock().unwrap().broadcast(msg);
         }
     }
 }
 
-fn run_listener(addr: SocketAddr, bus: Arc<Mutex<Bus<()>>>) {
+fn run_listener(addr: SocketAddr, bus: Arc<Mutex<Bus<Reload>>>, color: ColorMode) {
     let server = std::net::TcpListener::bind(addr).unwrap();
 
     for stream in server.incoming().filter_map(Result::ok) {
@@ -58,13 +103,17 @@ fn run_listener(addr: SocketAddr, bus: Arc<Mutex<Bus<()>>>) {
         thread::Builder::new()
             .name("livereload-connection".into())
             .spawn(move || {
-                handle_websocket(stream, receiver);
+                handle_websocket(stream, receiver, color);
             })
             .unwrap();
     }
 }
 
-fn handle_websocket(stream: std::net::TcpStream, mut listener: BusReader<()>) {
+fn handle_websocket(
+    stream: std::net::TcpStream,
+    mut listener: BusReader<Reload>,
+    color: ColorMode,
+) {
     let result = || -> io::Result<()> {
         let mut websocket = tungstenite::accept(stream).map_err(|err| match err {
             HandshakeError::Failure(e) => map_tungstenite_error(e),
@@ -79,18 +128,9 @@ fn handle_websocket(stream: std::net::TcpStream, mut listener: BusReader<()>) {
         }
 
         loop {
-            if let Ok(_msg) = listener.recv_timeout(Duration::from_millis(1000)) {
+            if let Ok(msg) = listener.recv_timeout(Duration::from_millis(1000)) {
                 websocket
-                    .write_message(
-                        r#"
-                        {
-                            "command": "reload",
-                            "path": "",
-                            "liveCSS": true
-                        }
-                        "#
-                        .into(),
-                    )
+                    .write_message(msg.message().into())
                     .map_err(|e| map_tungstenite_error(e))?;
             } else {
                 websocket
@@ -104,10 +144,14 @@ fn handle_websocket(stream: std::net::TcpStream, mut listener: BusReader<()>) {
         Ok(_) => {}
         Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
         // Unexpected errors that are not just disconnects.
-        Err(e) => println!(
-            "Livereload client disconnected due to an unexpected error: {}.",
-            e
-        ),
+        Err(e) => {
+            bunt::writeln!(
+                terminal::stdout(color),
+                "{$yellow}Livereload client disconnected due to an unexpected error:{/$} {}.",
+                e
+            )
+            .ok();
+        }
     };
 }
 
@@ -143,3 +187,22 @@ fn map_tungstenite_error(error: TungsteniteError) -> io::Error {
         e => io::Error::new(io::ErrorKind::Other, e),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn failed_builds_are_shown_in_the_browser() {
+        let message =
+            Reload::BuildFailed("Could not read custom head include file".to_string()).message();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&message).unwrap(),
+            serde_json::json!({
+                "command": "alert",
+                "message": "Build failed: Could not read custom head include file"
+            })
+        );
+    }
+}
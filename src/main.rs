@@ -1,5 +1,9 @@
-use bunt::termcolor::{ColorChoice, StandardStream};
-use clap::{App, Arg, ArgMatches, SubCommand};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How many seconds `build --wait` waits for another build by default
+const DEFAULT_LOCK_WAIT: u64 = 600;
 
 fn main() {
     let matches = App::new("Docgen")
@@ -11,7 +15,30 @@ fn main() {
         .arg(
             Arg::with_name("no-color")
                 .long("no-color")
-                .help("Disable terminal color output")
+                .help("Disable terminal color output, like --color never")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .takes_value(true)
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "never"])
+                .help(
+                    "When to color the output. auto leaves it out when the output is not a \
+                     terminal, or NO_COLOR is set. (Defaults to auto)",
+                )
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .help(
+                    "The config file to use instead of the docgen.yaml in this directory or its \
+                     parents. Can also be set with DOCGEN_CONFIG",
+                )
                 .global(true),
         )
         .subcommand(
@@ -19,7 +46,12 @@ fn main() {
                 .about("Initialize a new project (start here!)")
                 .arg(Arg::with_name("docs-dir").long("docs-dir").help(
                     "An optional custom root directory for your documentation. (Defaults to docs/)",
-                ).takes_value(true)),
+                ).takes_value(true))
+                .arg(
+                    Arg::with_name("with-kitchen-sink")
+                        .long("with-kitchen-sink")
+                        .help("Also create a page that uses every supported Markdown extension"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("build")
@@ -33,6 +65,103 @@ fn main() {
                     Arg::with_name("allow-failed-checks")
                         .long("allow-failed-checks")
                         .help("Don't return an error if there are failed checks"),
+                )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Return an error if there are any warnings"),
+                )
+                .arg(
+                    Arg::with_name("allow-dirty")
+                        .long("allow-dirty")
+                        .help("Build in release mode even if the docs have uncommitted changes and require_clean_git is set"),
+                )
+                .arg(
+                    Arg::with_name("wait")
+                        .long("wait")
+                        .takes_value(true)
+                        .min_values(0)
+                        .value_name("SECONDS")
+                        .help("Wait for another build of the project to finish, for up to 600 seconds or the given number"),
+                )
+                .arg(
+                    Arg::with_name("emit-ast")
+                        .long("emit-ast")
+                        .help("Also write the content of every page as JSON, next to its HTML"),
+                )
+                .arg(
+                    Arg::with_name("pretty-html")
+                        .long("pretty-html")
+                        .conflicts_with("release")
+                        .help("Indent the HTML of every page, one block element per line, for reviewing diffs"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("Report what the build would change, without writing anything"),
+                )
+                .arg(
+                    Arg::with_name("diff")
+                        .long("diff")
+                        .requires("dry-run")
+                        .help("Show a diff of every changed text file during a dry run"),
+                )
+                .arg(base_path_arg())
+                .arg(
+                    Arg::with_name("base-url")
+                        .long("base-url")
+                        .takes_value(true)
+                        .value_name("URL")
+                        .help("The base_url to build the site with, instead of the one in docgen.yaml"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .about("Manage the build cache")
+                .subcommand(
+                    SubCommand::with_name("info")
+                        .about("Show where the cache is, and how much space it takes up"),
+                )
+                .subcommand(SubCommand::with_name("clear").about("Remove the cache")),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Look into how Docgen renders your docs")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("render")
+                        .about("Print which extension handled every Markdown event of a page, and its HTML, without building the site")
+                        .arg(
+                            Arg::with_name("FILE")
+                                .required(true)
+                                .help("The Markdown file to render"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("manifest")
+                .about("Write every page and heading anchor that can be linked to as JSON, for editors")
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Where to write the manifest. (Defaults to docgen-manifest.json)"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .help("Write the manifest again whenever the docs change"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Rewrite callouts in the old `{% info %}` syntax to block quotes")
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("Show a diff of the changes instead of writing them"),
                 ),
         )
         .subcommand(
@@ -58,85 +187,277 @@ fn main() {
                             Ok(_) => Ok(()),
                             Err(e) => Err(e.to_string()),
                         }),
+                )
+                .arg(base_path_arg())
+                .arg(
+                    Arg::with_name("poll")
+                        .long("poll")
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true)
+                        .value_name("MS")
+                        .help(
+                            "Checks for changes every MS milliseconds, 1000 by default, instead \
+                             of being told about them by the file system. For network drives \
+                             and bind mounts whose changes are missed.",
+                        )
+                        .validator(|p| match p.parse::<u64>() {
+                            Ok(0) => Err(String::from("must be greater than 0")),
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("preview")
+                .about("Serves a single Markdown file, without a project, reloading it as it changes")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .help("The Markdown file to preview"),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .short("p")
+                        .takes_value(true)
+                        .value_name("PORT")
+                        .help(
+                            "Port used to serve the preview. \
+                             Must be a positive integer.",
+                        )
+                        .validator(|p| match p.parse::<u32>() {
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(e.to_string()),
+                        }),
                 ),
         )
         .get_matches();
 
+    docgen::terminal::set_panic_hook(color_mode(matches.subcommand().1.unwrap_or(&matches)));
+
     let result = match matches.subcommand() {
         ("init", Some(cmd)) => init(cmd),
         ("nav", Some(cmd)) => nav(cmd),
+        ("manifest", Some(cmd)) => manifest(cmd),
+        ("migrate", Some(cmd)) => migrate(cmd),
         ("build", Some(cmd)) => build(cmd),
         ("serve", Some(cmd)) => serve(cmd),
+        ("preview", Some(cmd)) => preview(cmd),
+        ("cache", Some(cmd)) => cache(cmd),
+        ("debug", Some(cmd)) => debug(cmd),
         _ => Ok(()),
     };
 
-    let mut out = if matches.value_of("no-color").is_some() {
-        StandardStream::stdout(ColorChoice::Never)
-    } else {
-        StandardStream::stdout(ColorChoice::Auto)
-    };
-
     if let Err(e) = result {
+        let cmd = matches.subcommand().1.unwrap_or(&matches);
+        let mut out = docgen::terminal::stdout(color_mode(cmd));
         bunt::writeln!(out, "{$red}ERROR:{/$} {}", e).unwrap();
         std::process::exit(1);
     }
 }
 
-fn init(cmd: &ArgMatches) -> docgen::Result<()> {
-    let root_dir = std::env::current_dir().expect("Unable to determine current directory");
-    let doc_root = cmd.value_of("docs-dir").map(|str| str.to_string());
-    docgen::InitCommand::run(root_dir, !cmd.is_present("no-color"), doc_root)
+/// When to color the output, from `--color` or `--no-color`
+fn color_mode(cmd: &ArgMatches) -> docgen::ColorMode {
+    if cmd.is_present("no-color") {
+        return docgen::ColorMode::Never;
+    }
+
+    cmd.value_of("color")
+        .and_then(docgen::ColorMode::parse)
+        .unwrap_or_default()
 }
 
-fn build(cmd: &ArgMatches) -> docgen::Result<()> {
+/// `--base-path`, for `build` and `serve`
+fn base_path_arg() -> Arg<'static, 'static> {
+    Arg::with_name("base-path")
+        .long("base-path")
+        .takes_value(true)
+        .value_name("PATH")
+        .help("The base_path to build the site with, instead of the one in docgen.yaml")
+}
+
+/// Loads the config given with `--config` or `DOCGEN_CONFIG`, or else the
+/// docgen.yaml in the current directory or its parents
+fn load_config(cmd: &ArgMatches, skip_validation: bool) -> docgen::Result<docgen::Config> {
+    let mut config = find_config(cmd, skip_validation)?;
+    config.set_color_mode(color_mode(cmd));
+
+    Ok(config)
+}
+
+fn find_config(cmd: &ArgMatches, skip_validation: bool) -> docgen::Result<docgen::Config> {
+    let path = cmd
+        .value_of_os("config")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("DOCGEN_CONFIG").map(PathBuf::from))
+        .filter(|path| !path.as_os_str().is_empty());
+    if let Some(path) = path {
+        return docgen::Config::load_from(&path, skip_validation);
+    }
+
     let project_dir = docgen::config::project_root().unwrap_or_else(|| {
         println!("Could not find a docgen project in this directory, or its parents.");
         std::process::exit(1);
     });
 
-    let mut config = docgen::Config::load(&project_dir, false)?;
-    if cmd.is_present("release") {
-        config.set_build_mode(docgen::BuildMode::Release);
+    docgen::Config::load(&project_dir, skip_validation)
+}
+
+fn init(cmd: &ArgMatches) -> docgen::Result<()> {
+    let root_dir = std::env::current_dir().expect("Unable to determine current directory");
+    let doc_root = cmd.value_of("docs-dir").map(|str| str.to_string());
+    docgen::InitCommand::run(
+        root_dir,
+        color_mode(cmd),
+        doc_root,
+        cmd.is_present("with-kitchen-sink"),
+    )
+}
+
+fn build(cmd: &ArgMatches) -> docgen::Result<()> {
+    let mut config = load_config(cmd, false)?;
+    if let Some(path) = cmd.value_of("base-path") {
+        config.set_base_path(path)?;
     }
 
-    if cmd.is_present("no-color") {
-        config.disable_colors();
+    if let Some(url) = cmd.value_of("base-url") {
+        config.set_base_url(url)?;
+    }
+
+    if cmd.is_present("release") {
+        config.set_build_mode(docgen::BuildMode::Release);
     }
 
     if cmd.is_present("allow-failed-checks") {
         config.set_allow_failed_checks();
     }
 
+    if cmd.is_present("strict") {
+        config.set_strict();
+    }
+
+    if cmd.is_present("allow-dirty") {
+        config.set_allow_dirty();
+    }
+
+    if cmd.is_present("wait") {
+        let seconds = match cmd.value_of("wait") {
+            Some(seconds) => seconds.parse::<u64>().map_err(|_| {
+                docgen::Error::new(format!(
+                    "Invalid value for --wait. Expected a number of seconds, got `{}`.",
+                    seconds
+                ))
+            })?,
+            None => DEFAULT_LOCK_WAIT,
+        };
+        config.set_lock_wait(Duration::from_secs(seconds));
+    }
+
+    if cmd.is_present("emit-ast") {
+        config.set_emit_ast();
+    }
+
+    if cmd.is_present("pretty-html") {
+        config.set_pretty_html()?;
+    }
+
+    if cmd.is_present("dry-run") {
+        return docgen::BuildCommand::dry_run(config, vec![], cmd.is_present("diff"));
+    }
+
     docgen::BuildCommand::run(config)
 }
 
 fn serve(cmd: &ArgMatches) -> docgen::Result<()> {
-    let project_dir = docgen::config::project_root().unwrap_or_else(|| {
-        println!("Could not find a docgen project in this directory, or its parents.");
-        std::process::exit(1);
-    });
-
     let mut options = docgen::ServeOptions::default();
-    let mut config = docgen::Config::load(&project_dir, false)?;
+    let config = load_config(cmd, false)?;
+
+    // Applied by the serve command, to every config it reloads too
+    options.base_path = cmd.value_of("base-path").map(str::to_string);
 
     if let Some(p) = cmd.value_of("port") {
         options.port = Some(p.parse::<u16>().unwrap());
     }
 
-    if cmd.is_present("no-color") {
-        config.disable_colors();
+    if cmd.is_present("poll") {
+        let interval = match cmd.value_of("poll") {
+            Some(ms) => Duration::from_millis(ms.parse().unwrap()),
+            None => docgen::DEFAULT_POLL_INTERVAL,
+        };
+        options.poll_interval = Some(interval);
     }
 
     docgen::ServeCommand::run(options, config)
 }
 
-fn nav(_cmd: &ArgMatches) -> docgen::Result<()> {
-    let project_dir = docgen::config::project_root().unwrap_or_else(|| {
-        println!("Could not find a docgen project in this directory, or its parents.");
-        std::process::exit(1);
-    });
+fn preview(cmd: &ArgMatches) -> docgen::Result<()> {
+    let mut options = docgen::ServeOptions::default();
 
-    let config = docgen::Config::load(&project_dir, true)?;
+    if let Some(p) = cmd.value_of("port") {
+        options.port = Some(p.parse::<u16>().unwrap());
+    }
+
+    docgen::PreviewCommand::run(
+        Path::new(cmd.value_of("FILE").unwrap()),
+        options,
+        color_mode(cmd),
+    )
+}
+
+fn nav(cmd: &ArgMatches) -> docgen::Result<()> {
+    let config = load_config(cmd, true)?;
 
     docgen::NavigationCommand::run(config)
 }
+
+fn manifest(cmd: &ArgMatches) -> docgen::Result<()> {
+    let config = load_config(cmd, false)?;
+
+    docgen::ManifestCommand::run(
+        config,
+        cmd.value_of("output").map(PathBuf::from),
+        cmd.is_present("watch"),
+    )
+}
+
+fn migrate(cmd: &ArgMatches) -> docgen::Result<()> {
+    let config = load_config(cmd, false)?;
+
+    docgen::MigrateCommand::run(config, cmd.is_present("dry-run"))
+}
+
+fn cache(cmd: &ArgMatches) -> docgen::Result<()> {
+    let config = load_config(cmd, true)?;
+
+    match cmd.subcommand() {
+        ("clear", Some(_)) => docgen::CacheCommand::clear(config),
+        _ => docgen::CacheCommand::info(config),
+    }
+}
+
+fn debug(cmd: &ArgMatches) -> docgen::Result<()> {
+    match cmd.subcommand() {
+        ("render", Some(cmd)) => {
+            let file = Path::new(cmd.value_of("FILE").unwrap());
+
+            // Outside of a project, the file is rendered like `docgen preview`
+            // would
+            let in_project = cmd.is_present("config")
+                || std::env::var_os("DOCGEN_CONFIG").is_some()
+                || docgen::config::project_root().is_some();
+            let config = if in_project {
+                load_config(cmd, false)?
+            } else {
+                let mut config = docgen::Config::for_file(&file.canonicalize().map_err(|e| {
+                    docgen::Error::io(e, format!("Could not read {}", file.display()))
+                })?)?;
+                config.set_color_mode(color_mode(cmd));
+                config
+            };
+
+            docgen::DebugCommand::render(config, file)
+        }
+        _ => Ok(()),
+    }
+}
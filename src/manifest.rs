@@ -0,0 +1,289 @@
+//! A JSON file listing everything pages can link to, for editor extensions
+//! that complete internal links and anchors.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crossbeam_channel::bounded;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::watcher::{Change, Watcher};
+use crate::{docs_finder, Document, Error, Result};
+
+/// Where the manifest is written when no other path is given, relative to
+/// the project root
+pub static DEFAULT_MANIFEST_FILE: &str = "docgen-manifest.json";
+
+/// Bumped whenever the shape of the manifest changes in a way that would
+/// break its readers
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Manifest {
+    pub version: u32,
+    /// The version of Docgen that wrote the manifest
+    pub docgen_version: &'static str,
+    pub pages: Vec<PageTarget>,
+    /// The URI of every file in `_include`, like `/assets/logo.png`
+    pub assets: Vec<String>,
+}
+
+/// A page, and the anchors of its headings
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PageTarget {
+    /// The path to link to the page with, without the `base_path`
+    pub uri: String,
+    /// The path of the Markdown file in the docs directory, which is also
+    /// what `{% include %}` directives take
+    pub source: String,
+    pub title: String,
+    pub anchors: Vec<AnchorTarget>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AnchorTarget {
+    pub anchor: String,
+    pub title: String,
+    pub level: u32,
+}
+
+impl Manifest {
+    /// The link targets of the pages, sorted by their URI, and of the files
+    /// in `_include`
+    pub fn new(docs: &[Document], config: &Config) -> Self {
+        let mut pages = docs
+            .iter()
+            .map(|doc| PageTarget {
+                uri: link_uri(&doc.uri_path, config.base_path()),
+                source: source_path(doc.original_path()),
+                title: doc.title.clone(),
+                anchors: doc
+                    .headings()
+                    .iter()
+                    .map(|heading| AnchorTarget {
+                        anchor: heading.anchor.clone(),
+                        title: heading.title.clone(),
+                        level: heading.level,
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        pages.sort_by(|a, b| a.uri.cmp(&b.uri));
+
+        Manifest {
+            version: MANIFEST_VERSION,
+            docgen_version: env!("CARGO_PKG_VERSION"),
+            pages,
+            assets: assets(config),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// The URI of a page as it is written in links, which leave out the `base_path`
+fn link_uri(uri_path: &str, base_path: &str) -> String {
+    let uri = uri_path.strip_prefix(base_path).unwrap_or(uri_path);
+    if uri.starts_with('/') {
+        uri.to_string()
+    } else {
+        format!("/{}", uri)
+    }
+}
+
+/// The path with forward slashes on every platform
+fn source_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn assets(config: &Config) -> Vec<String> {
+    let include_dir = config.docs_dir().join("_include");
+
+    let mut assets = WalkDir::new(&include_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(&include_dir)
+                .ok()
+                .map(|path| format!("/{}", source_path(path)))
+        })
+        .collect::<Vec<_>>();
+    assets.sort();
+
+    assets
+}
+
+pub struct ManifestCommand {}
+
+impl ManifestCommand {
+    /// Writes the manifest to `output`, or `docgen-manifest.json` in the
+    /// project root. With `watch`, writes it again whenever the docs or the
+    /// docgen.yaml change, until the process is stopped.
+    pub fn run(config: Config, output: Option<PathBuf>, watch: bool) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+
+        let output = output.unwrap_or_else(|| config.project_root().join(DEFAULT_MANIFEST_FILE));
+
+        docs_finder::check_docs_dir(&config)?;
+        Self::write(&config, &output)?;
+        bunt::writeln!(
+            stdout,
+            "Wrote the manifest to {$bold}{}{/$}",
+            output.display()
+        )?;
+
+        if !watch {
+            return Ok(());
+        }
+
+        let config_path = config.config_path().to_path_buf();
+        let config_name = config_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        // The sender is kept to restart the watcher when the config changes
+        // what it watches
+        let (watch_snd, watch_rcv) = bounded(128);
+        let mut stop_watcher = Watcher::for_config(&config, watch_snd.clone()).spawn();
+
+        println!("Watching for changes...\n");
+
+        let mut config = config;
+        for (change, msg) in watch_rcv {
+            match change.path() {
+                Some(path) => {
+                    bunt::writeln!(stdout, "    File {$bold}{}{/$} {}", path.display(), msg)?
+                }
+                None => bunt::writeln!(stdout, "    {$bold}{}{/$} {}", config_name, msg)?,
+            }
+
+            if change == Change::ConfigChanged {
+                match Config::load_from(&config_path, false) {
+                    Ok(mut new_config) => {
+                        new_config.set_color_mode(config.color_mode());
+                        if !Watcher::watches_same(&new_config, &config) {
+                            stop_watcher.store(true, Ordering::Relaxed);
+                            stop_watcher =
+                                Watcher::for_config(&new_config, watch_snd.clone()).spawn();
+                        }
+                        config = new_config;
+                    }
+                    Err(e) => {
+                        println!("    {}, keeping the previous manifest\n", e);
+                        continue;
+                    }
+                }
+            }
+
+            match Self::write(&config, &output) {
+                Ok(()) => println!("    Manifest updated\n"),
+                Err(e) => println!("    {}\n", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write(config: &Config, output: &Path) -> Result<()> {
+        let docs = docs_finder::find(config);
+        let manifest = Manifest::new(&docs, config);
+
+        fs::write(output, manifest.to_json()).map_err(|e| {
+            Error::io(
+                e,
+                format!("Could not write the manifest to {}", output.display()),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::markdown::parser::ParseOptions;
+    use crate::test_helpers::page_with_options;
+
+    #[test]
+    fn lists_pages_and_the_anchors_of_their_headings() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", false).unwrap();
+        let mut options = ParseOptions::default();
+        options.url_root = String::new();
+        let docs = vec![
+            page_with_options("README.md", "# Home\n\nWelcome.", &[], options.clone()),
+            page_with_options(
+                "guides/setup.md",
+                "# Setup\n\n## Install {#get-it}\n\n### On Linux\n\n#### With `apt`\n",
+                &[],
+                options,
+            ),
+        ];
+
+        let manifest = Manifest::new(&docs, &config);
+
+        assert_eq!(manifest.version, 1);
+        assert_eq!(manifest.docgen_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest.assets, Vec::<String>::new());
+        assert_eq!(
+            manifest.pages,
+            vec![
+                PageTarget {
+                    uri: "/".to_string(),
+                    source: "README.md".to_string(),
+                    title: "Home".to_string(),
+                    anchors: vec![AnchorTarget {
+                        anchor: "home".to_string(),
+                        title: "Home".to_string(),
+                        level: 1,
+                    }],
+                },
+                PageTarget {
+                    uri: "/guides/setup".to_string(),
+                    source: "guides/setup.md".to_string(),
+                    title: "Setup".to_string(),
+                    anchors: vec![
+                        AnchorTarget {
+                            anchor: "setup".to_string(),
+                            title: "Setup".to_string(),
+                            level: 1,
+                        },
+                        AnchorTarget {
+                            anchor: "get-it".to_string(),
+                            title: "Install".to_string(),
+                            level: 2,
+                        },
+                        AnchorTarget {
+                            anchor: "on-linux".to_string(),
+                            title: "On Linux".to_string(),
+                            level: 3,
+                        },
+                        AnchorTarget {
+                            anchor: "with-apt".to_string(),
+                            title: "With apt".to_string(),
+                            level: 4,
+                        },
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn uris_leave_out_the_base_path() {
+        assert_eq!(link_uri("/docs/guides/setup", "/docs/"), "/guides/setup");
+        assert_eq!(link_uri("/docs/", "/docs/"), "/");
+        assert_eq!(link_uri("/guides/setup", "/"), "/guides/setup");
+        assert_eq!(link_uri("/", "/"), "/");
+    }
+}
@@ -0,0 +1,485 @@
+//! A tree of the content of a page, for renderers that don't use HTML, like
+//! native apps. It is built from the same events as the HTML while parsing,
+//! and serialized to JSON next to every page with `--emit-ast`.
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, LinkType, Tag};
+use serde::Serialize;
+
+use super::extensions::callout::parse_callout;
+use super::extensions::toc::Heading;
+use super::parser::BuiltinExtension;
+
+/// Bumped whenever a change to the tree could break existing renderers
+pub const AST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Paragraph {
+        children: Vec<Node>,
+    },
+    Heading {
+        level: u32,
+        anchor: String,
+        /// The section number, like `1.` or `1.2`, when headings are numbered
+        #[serde(skip_serializing_if = "Option::is_none")]
+        number: Option<String>,
+        children: Vec<Node>,
+    },
+    BlockQuote {
+        children: Vec<Node>,
+    },
+    Callout {
+        kind: String,
+        title: String,
+        children: Vec<Node>,
+    },
+    Tabs {
+        tabs: Vec<Tab>,
+    },
+    CodeBlock {
+        /// The first word of the fence's info string, e.g. `rust`
+        language: Option<String>,
+        code: String,
+    },
+    List {
+        /// The number of the first item of an ordered list
+        start: Option<u64>,
+        children: Vec<Node>,
+    },
+    ListItem {
+        /// Whether the task of a task list item is done
+        checked: Option<bool>,
+        children: Vec<Node>,
+    },
+    Table {
+        /// One of `none`, `left`, `center` or `right` for every column
+        alignments: Vec<String>,
+        children: Vec<Node>,
+    },
+    TableHead {
+        children: Vec<Node>,
+    },
+    TableRow {
+        children: Vec<Node>,
+    },
+    TableCell {
+        children: Vec<Node>,
+    },
+    FootnoteDefinition {
+        label: String,
+        children: Vec<Node>,
+    },
+    FootnoteReference {
+        label: String,
+    },
+    Emphasis {
+        children: Vec<Node>,
+    },
+    Strong {
+        children: Vec<Node>,
+    },
+    Strikethrough {
+        children: Vec<Node>,
+    },
+    Link {
+        /// The URL as it is rendered in the HTML of the page
+        url: String,
+        title: String,
+        children: Vec<Node>,
+    },
+    Image {
+        /// The URL as it is rendered in the HTML of the page
+        url: String,
+        title: String,
+        alt: String,
+    },
+    Text {
+        text: String,
+    },
+    Code {
+        code: String,
+    },
+    Html {
+        html: String,
+    },
+    SoftBreak,
+    HardBreak,
+    Rule,
+}
+
+/// A tab of a tab group, and everything up to the next tab
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tab {
+    pub id: String,
+    pub title: String,
+    pub children: Vec<Node>,
+}
+
+/// A node that is still being built, along with its children so far
+struct Frame {
+    /// None for the root of the page
+    node: Option<Node>,
+    children: Vec<Node>,
+    /// The tab group being filled, after the first tab heading of this level
+    tabs: Option<Vec<Tab>>,
+    /// The id of the tab a heading names
+    tab_id: Option<String>,
+}
+
+impl Frame {
+    fn new(node: Option<Node>) -> Self {
+        Frame {
+            node,
+            children: vec![],
+            tabs: None,
+            tab_id: None,
+        }
+    }
+
+    fn add(&mut self, node: Node) {
+        match self.tabs.as_mut().and_then(|tabs| tabs.last_mut()) {
+            Some(tab) => tab.children.push(node),
+            None => self.children.push(node),
+        }
+    }
+
+    fn last_added(&mut self) -> Option<&mut Node> {
+        match self.tabs.as_mut().and_then(|tabs| tabs.last_mut()) {
+            Some(tab) => tab.children.last_mut(),
+            None => self.children.last_mut(),
+        }
+    }
+
+    fn close_tabs(&mut self) {
+        if let Some(tabs) = self.tabs.take() {
+            self.children.push(Node::Tabs { tabs });
+        }
+    }
+}
+
+/// Builds the tree from the events of the parser, before any extension
+/// rewrites them. Extensions fill in what only they know, like the anchors
+/// of headings and where links point to.
+pub struct AstBuilder {
+    stack: Vec<Frame>,
+    /// Whether headings that link to `#/tab/<id>` start tabs
+    tabs: bool,
+    /// Whether block quotes that start with a kind are callouts
+    callouts: bool,
+}
+
+impl Default for AstBuilder {
+    fn default() -> Self {
+        AstBuilder::new(&[])
+    }
+}
+
+impl AstBuilder {
+    /// A builder that leaves the syntax of the turned off extensions as
+    /// plain Markdown, like the HTML does
+    pub fn new(disabled: &[BuiltinExtension]) -> Self {
+        AstBuilder {
+            stack: vec![Frame::new(None)],
+            tabs: !disabled.contains(&BuiltinExtension::Tabs),
+            callouts: !disabled.contains(&BuiltinExtension::Callouts),
+        }
+    }
+
+    pub fn push(&mut self, event: &Event) {
+        match event {
+            Event::Start(tag) => self.open(tag),
+            Event::End(_) => self.close(),
+            Event::Text(text) if text.is_empty() => {}
+            Event::Text(text) => self.add(Node::Text {
+                text: text.to_string(),
+            }),
+            Event::Code(code) => self.add(Node::Code {
+                code: code.to_string(),
+            }),
+            Event::Html(html) => self.add(Node::Html {
+                html: html.to_string(),
+            }),
+            Event::FootnoteReference(label) => self.add(Node::FootnoteReference {
+                label: label.to_string(),
+            }),
+            Event::SoftBreak => self.add(Node::SoftBreak),
+            Event::HardBreak => self.add(Node::HardBreak),
+            // A rule ends a tab group, and isn't rendered then
+            Event::Rule => {
+                if self.top().tabs.is_some() {
+                    self.top().close_tabs()
+                } else {
+                    self.add(Node::Rule)
+                }
+            }
+            Event::TaskListMarker(done) => {
+                if let Some(Node::ListItem { checked, .. }) = &mut self.top().node {
+                    *checked = Some(*done);
+                }
+            }
+        }
+    }
+
+    /// Sets the anchor and number of the heading that was just closed, and
+    /// drops the `{#id}` that set it from its text
+    pub fn set_heading(&mut self, heading: &Heading) {
+        if let Some(Node::Heading {
+            anchor,
+            number,
+            children,
+            ..
+        }) = self.top().last_added()
+        {
+            *anchor = heading.anchor.clone();
+            *number = heading.number.clone();
+
+            let extra = plain_text(children)
+                .len()
+                .saturating_sub(heading.unnumbered_title().len());
+            trim_end(children, extra);
+        }
+    }
+
+    /// Sets where the link or image that was just opened points to
+    pub fn set_url(&mut self, resolved: String) {
+        if let Some(Node::Link { url, .. } | Node::Image { url, .. }) = &mut self.top().node {
+            *url = resolved;
+        }
+    }
+
+    /// The nodes of the page
+    pub fn finish(mut self) -> Vec<Node> {
+        while self.stack.len() > 1 {
+            self.close();
+        }
+
+        let mut root = self.stack.pop().unwrap();
+        root.close_tabs();
+        root.children
+    }
+
+    fn top(&mut self) -> &mut Frame {
+        self.stack.last_mut().unwrap()
+    }
+
+    fn add(&mut self, node: Node) {
+        self.top().add(node)
+    }
+
+    fn open(&mut self, tag: &Tag) {
+        let children = vec![];
+        let node = match tag {
+            Tag::Paragraph => Node::Paragraph { children },
+            Tag::Heading(level) => Node::Heading {
+                level: *level,
+                anchor: String::new(),
+                number: None,
+                children,
+            },
+            Tag::BlockQuote => Node::BlockQuote { children },
+            Tag::CodeBlock(kind) => Node::CodeBlock {
+                language: match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().map(String::from),
+                    CodeBlockKind::Indented => None,
+                },
+                code: String::new(),
+            },
+            Tag::List(start) => Node::List {
+                start: *start,
+                children,
+            },
+            Tag::Item => Node::ListItem {
+                checked: None,
+                children,
+            },
+            Tag::FootnoteDefinition(label) => Node::FootnoteDefinition {
+                label: label.to_string(),
+                children,
+            },
+            Tag::Table(alignments) => Node::Table {
+                alignments: alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None => "none",
+                        Alignment::Left => "left",
+                        Alignment::Center => "center",
+                        Alignment::Right => "right",
+                    })
+                    .map(String::from)
+                    .collect(),
+                children,
+            },
+            Tag::TableHead => Node::TableHead { children },
+            Tag::TableRow => Node::TableRow { children },
+            Tag::TableCell => Node::TableCell { children },
+            Tag::Emphasis => Node::Emphasis { children },
+            Tag::Strong => Node::Strong { children },
+            Tag::Strikethrough => Node::Strikethrough { children },
+            Tag::Link(link_type, url, title) => {
+                // A heading with a link to `#/tab/<id>` starts a tab
+                if let Some(id) = url.strip_prefix("#/tab/").filter(|_| self.tabs) {
+                    let top = self.top();
+                    if *link_type == LinkType::Inline
+                        && matches!(top.node, Some(Node::Heading { .. }))
+                    {
+                        top.tab_id = id.split('/').next().map(String::from);
+                    }
+                }
+
+                Node::Link {
+                    url: url.to_string(),
+                    title: title.to_string(),
+                    children,
+                }
+            }
+            Tag::Image(_, url, title) => Node::Image {
+                url: url.to_string(),
+                title: title.to_string(),
+                alt: String::new(),
+            },
+        };
+
+        self.stack.push(Frame::new(Some(node)));
+    }
+
+    fn close(&mut self) {
+        // An end without a start never closes the root
+        if self.stack.len() < 2 {
+            return;
+        }
+
+        let mut frame = self.stack.pop().unwrap();
+        frame.close_tabs();
+        let nodes = frame.children;
+
+        let node = match frame.node.unwrap() {
+            Node::Heading { .. } if frame.tab_id.is_some() => {
+                let tab = Tab {
+                    id: frame.tab_id.unwrap(),
+                    title: plain_text(&nodes),
+                    children: vec![],
+                };
+                self.top().tabs.get_or_insert_with(Vec::new).push(tab);
+                return;
+            }
+            Node::BlockQuote { .. } => match callout(&nodes).filter(|_| self.callouts) {
+                Some((kind, title)) => Node::Callout {
+                    kind,
+                    title,
+                    children: nodes.into_iter().skip(1).collect(),
+                },
+                None => Node::BlockQuote { children: nodes },
+            },
+            Node::CodeBlock { language, .. } => Node::CodeBlock {
+                language,
+                code: plain_text(&nodes),
+            },
+            Node::Image { url, title, .. } => Node::Image {
+                url,
+                title,
+                alt: plain_text(&nodes),
+            },
+            mut node => {
+                if let Some(children) = node.children_mut() {
+                    *children = nodes;
+                }
+                node
+            }
+        };
+
+        self.add(node);
+    }
+}
+
+/// The kind and title of the callout a block quote is, going by its first
+/// paragraph
+fn callout(nodes: &[Node]) -> Option<(String, String)> {
+    match nodes.first() {
+        Some(Node::Paragraph { children }) => {
+            parse_callout(&plain_text(children)).map(|(kind, title)| (kind.to_string(), title))
+        }
+        _ => None,
+    }
+}
+
+impl Node {
+    fn children(&self) -> Option<&Vec<Node>> {
+        match self {
+            Node::Paragraph { children }
+            | Node::Heading { children, .. }
+            | Node::BlockQuote { children }
+            | Node::Callout { children, .. }
+            | Node::List { children, .. }
+            | Node::ListItem { children, .. }
+            | Node::Table { children, .. }
+            | Node::TableHead { children }
+            | Node::TableRow { children }
+            | Node::TableCell { children }
+            | Node::FootnoteDefinition { children, .. }
+            | Node::Emphasis { children }
+            | Node::Strong { children }
+            | Node::Strikethrough { children }
+            | Node::Link { children, .. } => Some(children),
+            _ => None,
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Node::Paragraph { children }
+            | Node::Heading { children, .. }
+            | Node::BlockQuote { children }
+            | Node::Callout { children, .. }
+            | Node::List { children, .. }
+            | Node::ListItem { children, .. }
+            | Node::Table { children, .. }
+            | Node::TableHead { children }
+            | Node::TableRow { children }
+            | Node::TableCell { children }
+            | Node::FootnoteDefinition { children, .. }
+            | Node::Emphasis { children }
+            | Node::Strong { children }
+            | Node::Strikethrough { children }
+            | Node::Link { children, .. } => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// The text, inline code and image descriptions in the nodes, without any
+/// formatting
+fn plain_text(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::Text { text: t } | Node::Code { code: t } => text.push_str(t),
+            Node::Image { alt, .. } => text.push_str(alt),
+            _ => {
+                if let Some(children) = node.children() {
+                    text.push_str(&plain_text(children));
+                }
+            }
+        }
+    }
+    text
+}
+
+/// Removes `length` bytes of text from the end of the nodes, starting with
+/// the last text node. Stops at the first node that isn't text.
+fn trim_end(nodes: &mut Vec<Node>, mut length: usize) {
+    while length > 0 {
+        let text = match nodes.last_mut() {
+            Some(Node::Text { text }) => text,
+            _ => return,
+        };
+
+        if text.len() <= length {
+            length -= text.len();
+            nodes.pop();
+        } else {
+            text.truncate(text.len() - length);
+            length = 0;
+        }
+    }
+}
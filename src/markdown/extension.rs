@@ -1,17 +1,35 @@
 use pulldown_cmark::{CowStr, Event};
 
-use super::extensions::{link_rewriter::Link, toc::Heading};
+use super::extensions::{
+    link_rewriter::{DocsDirLink, Link},
+    toc::Heading,
+};
 
+#[derive(Debug)]
 pub enum Output<'a> {
     None,
     Event(Event<'a>),
     Link(Link),
     Heading(Heading),
+    /// Where the link or image that was just started points to
+    Url(String),
+    /// A link into the docs directory that isn't in the site
+    DocsDirLink(DocsDirLink),
+    /// Something about the content that authors should fix, which didn't
+    /// stop it from rendering
+    Warning(String),
 
     Block(&'a str),
 }
 
 pub trait Extension {
+    /// The name of the extension in parser traces, which is the name of its
+    /// type
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
     fn process_event<'a>(
         &mut self,
         events: &mut Vec<Event<'a>>,
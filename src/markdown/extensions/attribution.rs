@@ -0,0 +1,106 @@
+use pulldown_cmark::{CowStr, Event, Tag};
+
+use crate::markdown::extension::{Extension, Output};
+
+/// Turns the last line of a blockquote into its attribution when it starts
+/// with `-- `, like `-- RFC 2119`. Callouts are blockquotes too, but the
+/// callout extension runs first and takes them before they get here.
+pub struct Attribution;
+
+/// What an attribution line starts with. Smart punctuation turns `--` into
+/// an en dash before the extensions see it.
+const ATTRIBUTION_PREFIXES: [&str; 2] = ["-- ", "– "];
+
+impl Extension for Attribution {
+    fn process_event<'a>(
+        &mut self,
+        events: &mut Vec<Event<'a>>,
+        event: &Event<'a>,
+    ) -> (Option<Vec<Output<'a>>>, bool) {
+        if let Event::End(Tag::BlockQuote) = event {
+            if let Some(start) = quote_start(events) {
+                attribute(events, start);
+            }
+        }
+
+        (None, false)
+    }
+}
+
+/// Where the blockquote that is being closed starts, skipping over the ones
+/// nested in it
+fn quote_start(events: &[Event]) -> Option<usize> {
+    let mut depth = 0;
+    for (index, event) in events.iter().enumerate().rev() {
+        match event {
+            Event::End(Tag::BlockQuote) => depth += 1,
+            Event::Start(Tag::BlockQuote) if depth == 0 => return Some(index),
+            Event::Start(Tag::BlockQuote) => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Moves the attribution on the last line of the quote starting at `start`
+/// into a footer, if the quote has anything else in it
+fn attribute(events: &mut Vec<Event>, start: usize) {
+    if !matches!(events.last(), Some(Event::End(Tag::Paragraph))) {
+        return;
+    }
+    let paragraph = match events[start..]
+        .iter()
+        .rposition(|event| matches!(event, Event::Start(Tag::Paragraph)))
+    {
+        Some(index) => start + index,
+        None => return,
+    };
+
+    // The attribution is either a paragraph of its own, or the last line of
+    // the last paragraph
+    let line_break = events[paragraph..]
+        .iter()
+        .rposition(|event| matches!(event, Event::SoftBreak | Event::HardBreak))
+        .map(|index| paragraph + index);
+    let line_start = line_break.unwrap_or(paragraph) + 1;
+
+    if line_break.is_none() && paragraph == start + 1 {
+        return;
+    }
+
+    // Smart punctuation puts the dash in a text event of its own
+    let mut text = String::new();
+    let mut line_end = line_start;
+    while let Some(Event::Text(part)) = events.get(line_end) {
+        text.push_str(part);
+        line_end += 1;
+    }
+    let attribution = match ATTRIBUTION_PREFIXES
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+    {
+        Some(rest) => rest.trim_start().to_string(),
+        None => return,
+    };
+
+    events.splice(
+        line_start..line_end,
+        std::iter::once(Event::Text(CowStr::from(attribution))),
+    );
+    let last = events.len() - 1;
+    events[last] = html!("</cite></footer>");
+
+    match line_break {
+        Some(index) => {
+            events[index] = Event::End(Tag::Paragraph);
+            events.insert(
+                index + 1,
+                html!("<footer class=\"blockquote-attribution\"><cite>"),
+            );
+        }
+        None => {
+            events[paragraph] = html!("<footer class=\"blockquote-attribution\"><cite>");
+        }
+    }
+}
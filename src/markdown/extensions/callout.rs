@@ -22,16 +22,13 @@ impl Extension for Callout {
     ) -> (Option<Vec<Output<'a>>>, bool) {
         match event {
             Event::End(Tag::BlockQuote) => {
-                let start_index = events.len()
-                    - 1
-                    - events
-                        .iter_mut()
-                        .rev()
-                        .position(|tag| match tag {
-                            Event::Start(Tag::BlockQuote) => true,
-                            _ => false,
-                        })
-                        .unwrap();
+                let start_index = match events
+                    .iter()
+                    .rposition(|tag| matches!(tag, Event::Start(Tag::BlockQuote)))
+                {
+                    Some(index) => index,
+                    None => return (None, false),
+                };
 
                 let mut callout_title = String::new();
                 for event in &mut events[start_index + 1..] {
@@ -108,10 +105,11 @@ impl fmt::Display for CalloutKind {
     }
 }
 
-fn parse_callout(text: &str) -> Option<(CalloutKind, String)> {
+/// The kind and title of a callout, from the text of its first paragraph
+pub fn parse_callout(text: &str) -> Option<(CalloutKind, String)> {
     let callout_types = ["info", "notice", "success", "warn", "warning", "error"];
     let mut words = text.split_whitespace();
-    let first_word = words.next().unwrap();
+    let first_word = words.next()?;
     let title = words
         .map(|s| s.to_string())
         .reduce(|all, words| all + " " + &words)
@@ -0,0 +1,88 @@
+use pulldown_cmark::{CowStr, Event, Tag};
+
+/// Asks for a listing of the pages under the page, written on a line of its
+/// own
+pub static DIRECTIVE: &str = "{% children %}";
+
+/// Where the listing goes in the HTML of the page. It is kept by the
+/// sanitizer, and has no text that could end up in the preview or the
+/// search index.
+pub static PLACEHOLDER: &str = "<div class=\"children-listing\"></div>";
+
+/// Replaces the first paragraph that is only the directive with the
+/// placeholder, and drops the others, before the preview, the AST or any
+/// extension sees them. Returns whether there was one.
+pub fn replace_directives(events: &mut Vec<Event>) -> bool {
+    let mut found = false;
+    let mut i = 0;
+
+    while i < events.len() {
+        let end = match directive_paragraph(&events[i..]) {
+            Some(len) => i + len,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+
+        if found {
+            events.drain(i..end);
+        } else {
+            events.splice(i..end, [Event::Html(CowStr::from(PLACEHOLDER))]);
+            found = true;
+            i += 1;
+        }
+    }
+
+    found
+}
+
+/// The number of events of the paragraph at the start of `events`, if it
+/// is only the directive
+fn directive_paragraph(events: &[Event]) -> Option<usize> {
+    if !matches!(events.first(), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+
+    let mut text = String::new();
+    for (i, event) in events.iter().enumerate().skip(1) {
+        match event {
+            Event::Text(t) => text.push_str(t),
+            Event::End(Tag::Paragraph) if text.trim() == DIRECTIVE => return Some(i + 1),
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pulldown_cmark::Parser;
+
+    #[test]
+    fn replaces_the_first_directive_and_drops_the_others() {
+        let input = "# Guides\n\n{% children %}\n\nText about {% children %}\n\n{% children %}\n";
+        let mut events = Parser::new(input).collect::<Vec<_>>();
+
+        assert!(replace_directives(&mut events));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|ev| matches!(ev, Event::Html(html) if html.as_ref() == PLACEHOLDER))
+                .count(),
+            1
+        );
+        assert_eq!(
+            events
+                .iter()
+                .filter(|ev| matches!(ev, Event::Start(Tag::Paragraph)))
+                .count(),
+            1
+        );
+
+        let mut events = Parser::new("# Guides\n\nNo listing").collect::<Vec<_>>();
+        assert!(!replace_directives(&mut events));
+    }
+}
@@ -1,5 +1,9 @@
+use std::time::{Duration, Instant};
+
 use once_cell::sync::OnceCell;
+use pulldown_cmark::escape::escape_html;
 use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag};
+use regex::Regex;
 use syntect::util::LinesWithEndings;
 
 use crate::markdown::extension::{Extension, Output};
@@ -7,10 +11,154 @@ use syntect::html::line_tokens_to_classed_spans;
 use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
 use syntect::Error;
 
-pub struct CodeBlock;
+pub struct CodeBlock {
+    /// Show line numbers for every fence that doesn't say otherwise
+    pub line_numbers: bool,
+    /// Blocks with more lines than this are not highlighted
+    pub max_lines: usize,
+    /// Blocks larger than this many bytes are not highlighted
+    pub max_bytes: usize,
+    /// Blocks with a line longer than this many bytes are not highlighted
+    pub max_line_bytes: usize,
+    /// How long highlighting a block may take, after which it is shown
+    /// without highlighting
+    pub time_budget: Duration,
+}
+
+/// The number of lines after which code blocks are not highlighted
+pub const DEFAULT_MAX_HIGHLIGHT_LINES: usize = 2000;
+
+/// The size in KB after which code blocks are not highlighted
+pub const DEFAULT_MAX_HIGHLIGHT_SIZE: usize = 100;
+
+/// How long highlighting a single code block may take
+pub const HIGHLIGHT_TIME_BUDGET: Duration = Duration::from_secs(2);
+
+/// The size in KB after which a single line, like minified code, is not
+/// highlighted. The time budget is only checked between the steps of
+/// highlighting, and highlighting a line can't be stopped once it started.
+pub const DEFAULT_MAX_HIGHLIGHT_LINE_SIZE: usize = 4;
+
+/// Why code is shown without highlighting
+enum Unhighlighted {
+    /// Highlighting took longer than the time budget of the block
+    TooSlow,
+    /// The syntax could not parse the code
+    Failed,
+}
+
+impl From<Error> for Unhighlighted {
+    fn from(_: Error) -> Self {
+        Unhighlighted::Failed
+    }
+}
 
 static SYNTAX_SET: OnceCell<SyntaxSet> = OnceCell::new();
 
+lazy_static! {
+    static ref SPAN_TAG: Regex = Regex::new(r#"<span class="[^"]*">|</span>"#).unwrap();
+}
+
+/// The options in the info string of a fence, e.g. ```` ```rust {3-5,8} linenos ````
+#[derive(Debug, PartialEq)]
+struct Fence<'a> {
+    language: &'a str,
+    /// Highlighted lines, as inclusive ranges starting from 1
+    highlights: Vec<(usize, usize)>,
+    line_numbers: Option<bool>,
+}
+
+impl<'a> Fence<'a> {
+    fn parse(info: &'a str) -> Self {
+        let mut words = info.split_whitespace();
+        let mut fence = Fence {
+            language: words.next().unwrap_or(""),
+            highlights: vec![],
+            line_numbers: None,
+        };
+
+        for word in words {
+            if word == "linenos" || word == "linenos=true" {
+                fence.line_numbers = Some(true);
+            } else if word == "linenos=false" {
+                fence.line_numbers = Some(false);
+            } else if let Some(spec) = word.strip_prefix('{').and_then(|w| w.strip_suffix('}')) {
+                fence.highlights = spec.split(',').filter_map(parse_range).collect();
+            }
+        }
+
+        fence
+    }
+
+    /// Whether the fence has no line options, and renders as it always has
+    fn is_plain(&self) -> bool {
+        self.highlights.is_empty() && self.line_numbers.is_none()
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlights
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&line))
+    }
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once('-') {
+        Some((start, end)) => Some((start.trim().parse().ok()?, end.trim().parse().ok()?)),
+        None => {
+            let line = range.trim().parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+impl CodeBlock {
+    fn wants_lines(&self, fence: &Fence) -> bool {
+        !fence.highlights.is_empty() || fence.line_numbers.unwrap_or(self.line_numbers)
+    }
+
+    /// Why the code is too large to be highlighted, if it is
+    fn too_large(&self, code: &str, language: &str) -> Option<String> {
+        let lines = code.lines().count();
+        let (problem, fix) = if lines > self.max_lines {
+            (
+                format!(
+                    "is larger than the `max_highlight_lines` of {}",
+                    self.max_lines
+                ),
+                "Split it into smaller blocks, or raise the limit",
+            )
+        } else if code.len() > self.max_bytes {
+            (
+                format!(
+                    "is larger than the `max_highlight_size` of {}",
+                    crate::format_size(self.max_bytes as u64)
+                ),
+                "Split it into smaller blocks, or raise the limit",
+            )
+        } else if code.lines().any(|line| line.len() > self.max_line_bytes) {
+            (
+                format!(
+                    "has a line longer than the `max_highlight_line_size` of {}",
+                    crate::format_size(self.max_line_bytes as u64)
+                ),
+                "Break up the long lines, or raise the limit",
+            )
+        } else {
+            return None;
+        };
+
+        Some(format!(
+            "A `{}` code block of {} lines ({}) {}, and is shown without syntax highlighting. {}",
+            language,
+            lines,
+            crate::format_size(code.len() as u64),
+            problem,
+            fix
+        ))
+    }
+}
+
 impl Extension for CodeBlock {
     fn process_event<'a>(
         &mut self,
@@ -19,15 +167,23 @@ impl Extension for CodeBlock {
     ) -> (Option<Vec<Output<'a>>>, bool) {
         match event {
             Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(inner))) => {
-                return (
-                    Some(vec![
-                        Output::Event(html!(
-                            "<pre class=\"code\">\n<code class=\"language-{inner}\">"
-                        )),
-                        Output::None,
-                    ]),
-                    true,
-                );
+                let fence = Fence::parse(inner);
+
+                let start = if fence.is_plain() && !self.line_numbers {
+                    html!("<pre class=\"code\">\n<code class=\"language-{inner}\">")
+                } else {
+                    let class = if fence.line_numbers.unwrap_or(self.line_numbers) {
+                        "code linenos"
+                    } else {
+                        "code"
+                    };
+                    html!(
+                        "<pre class=\"{class}\">\n<code class=\"language-{}\">",
+                        fence.language
+                    )
+                };
+
+                return (Some(vec![Output::Event(start), Output::None]), true);
             }
             Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)) => {
                 return (
@@ -40,23 +196,68 @@ impl Extension for CodeBlock {
             }
             Event::End(Tag::CodeBlock(CodeBlockKind::Fenced(inner))) => {
                 let syntax_set = SYNTAX_SET.get_or_init(|| SyntaxSet::load_defaults_newlines());
+                let fence = Fence::parse(inner);
+                let wants_lines = self.wants_lines(&fence);
+                let token = if fence.is_plain() {
+                    inner
+                } else {
+                    fence.language
+                };
 
-                if let Some(syntax) = syntax_set.find_syntax_by_token(inner) {
-                    let code_event = events.last_mut().unwrap();
+                if let Some(Event::Text(code)) = events.last_mut() {
+                    let mut outputs = vec![];
 
-                    if let Event::Text(code) = code_event {
-                        let highlighted_code =
-                            highlighted_html_for_string(&code, syntax_set, syntax);
+                    let syntax = match self.too_large(code, fence.language) {
+                        Some(warning) => {
+                            outputs.push(Output::Warning(warning));
+                            None
+                        }
+                        None => syntax_set.find_syntax_by_token(token),
+                    };
 
-                        if let Ok(highlighted_code) = highlighted_code {
-                            *code_event = Event::Html(CowStr::from(highlighted_code));
+                    let deadline = Instant::now() + self.time_budget;
+                    let highlighted = match syntax {
+                        Some(syntax) if wants_lines => highlighted_lines_for_string(
+                            code, syntax_set, syntax, &fence, self, deadline,
+                        )
+                        .map(Some),
+                        Some(syntax) => {
+                            highlighted_html_for_string(code, syntax_set, syntax, deadline)
+                                .map(Some)
+                        }
+                        None => Ok(None),
+                    };
 
-                            return (
-                                Some(vec![Output::Event(event.to_owned()), Output::Block("code")]),
-                                true,
-                            );
+                    let highlighted_code = match highlighted {
+                        Ok(Some(highlighted_code)) => Some(highlighted_code),
+                        Err(Unhighlighted::TooSlow) => {
+                            outputs.push(Output::Warning(format!(
+                                "Highlighting a `{}` code block of {} lines ({}) took longer \
+                                 than {} seconds, and it is shown without syntax highlighting. \
+                                 Split it into smaller blocks",
+                                fence.language,
+                                code.lines().count(),
+                                crate::format_size(code.len() as u64),
+                                self.time_budget.as_secs_f32()
+                            )));
+                            wants_lines.then(|| plain_lines_for_string(code, &fence, self))
                         }
+                        Err(Unhighlighted::Failed) => None,
+                        Ok(None) if wants_lines => Some(plain_lines_for_string(code, &fence, self)),
+                        Ok(None) => None,
+                    };
+
+                    if let Some(highlighted_code) = highlighted_code {
+                        *events.last_mut().unwrap() = Event::Html(CowStr::from(highlighted_code));
+                        outputs.push(Output::Event(event.to_owned()));
+                        outputs.push(Output::Block("code"));
+
+                        return (Some(outputs), true);
                     }
+
+                    // Otherwise the code is escaped when the page is rendered,
+                    // like the code of languages that can't be highlighted
+                    return ((!outputs.is_empty()).then_some(outputs), false);
                 }
             }
             _ => {}
@@ -65,39 +266,56 @@ impl Extension for CodeBlock {
     }
 }
 
-fn highlighted_html_for_string(
+/// Highlights each line of the code separately, returning the HTML of each
+/// line and how many spans it leaves open.
+fn classed_lines(
     s: &str,
     ss: &SyntaxSet,
     syntax: &SyntaxReference,
-) -> Result<String, Error> {
+    deadline: Instant,
+) -> Result<Vec<(String, isize)>, Unhighlighted> {
     let mut parse_state = ParseState::new(syntax);
-    let mut html = String::new();
     let mut scope_stack = ScopeStack::new();
-    let mut open_spans = 0;
+    let mut lines = vec![];
     let mut first_line = true;
 
     for line in LinesWithEndings::from(s) {
-        let mut parsed_line = parse_state.parse_line(line, ss)?;
+        if Instant::now() >= deadline {
+            return Err(Unhighlighted::TooSlow);
+        }
+
+        let mut parsed_line = parse_state.parse_line(line, ss).map_err(Error::from)?;
+        if Instant::now() >= deadline {
+            return Err(Unhighlighted::TooSlow);
+        }
 
         // remove the wrapping <span>
         if first_line {
             parsed_line.remove(0);
+            first_line = false;
         }
 
-        let (formatted_line, delta) = line_tokens_to_classed_spans(
+        lines.push(line_tokens_to_classed_spans(
             line,
             parsed_line.as_slice(),
             syntect::html::ClassStyle::Spaced,
             &mut scope_stack,
-        )?;
+        )?);
+    }
 
-        // since we removed the wrapping span we don't want to close a
-        // non-existent span
-        if first_line {
-            // delta -= 1;
-            first_line = false;
-        }
+    Ok(lines)
+}
 
+fn highlighted_html_for_string(
+    s: &str,
+    ss: &SyntaxSet,
+    syntax: &SyntaxReference,
+    deadline: Instant,
+) -> Result<String, Unhighlighted> {
+    let mut html = String::new();
+    let mut open_spans = 0;
+
+    for (formatted_line, delta) in classed_lines(s, ss, syntax, deadline)? {
         open_spans += delta;
         html.push_str(formatted_line.as_str());
     }
@@ -108,3 +326,164 @@ fn highlighted_html_for_string(
 
     Ok(html)
 }
+
+/// Like `highlighted_html_for_string`, but wraps every line in its own span.
+/// Highlighting spans that continue over multiple lines are closed at the end
+/// of each line and opened again on the next one, so the line spans nest.
+fn highlighted_lines_for_string(
+    s: &str,
+    ss: &SyntaxSet,
+    syntax: &SyntaxReference,
+    fence: &Fence,
+    block: &CodeBlock,
+    deadline: Instant,
+) -> Result<String, Unhighlighted> {
+    let mut html = String::new();
+    let mut open: Vec<String> = vec![];
+
+    for (i, (formatted_line, _)) in classed_lines(s, ss, syntax, deadline)?
+        .into_iter()
+        .enumerate()
+    {
+        if Instant::now() >= deadline {
+            return Err(Unhighlighted::TooSlow);
+        }
+
+        let formatted_line = formatted_line.replacen("\r\n", "", 1).replacen('\n', "", 1);
+        let mut line = open.concat();
+        let mut pos = 0;
+
+        for tag in SPAN_TAG.find_iter(&formatted_line) {
+            line.push_str(&formatted_line[pos..tag.start()]);
+            pos = tag.end();
+
+            if tag.as_str() == "</span>" {
+                // Closes the wrapping span removed from the first line
+                if open.pop().is_none() {
+                    continue;
+                }
+            } else {
+                open.push(tag.as_str().to_string());
+            }
+
+            line.push_str(tag.as_str());
+        }
+
+        line.push_str(&formatted_line[pos..]);
+        line.push_str(&"</span>".repeat(open.len()));
+
+        push_line(&mut html, i + 1, &line, fence, block);
+    }
+
+    Ok(html)
+}
+
+/// Wraps every line of code in a language that can't be highlighted
+fn plain_lines_for_string(s: &str, fence: &Fence, block: &CodeBlock) -> String {
+    let mut html = String::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let mut escaped = String::new();
+        escape_html(&mut escaped, line).unwrap();
+
+        push_line(&mut html, i + 1, &escaped, fence, block);
+    }
+
+    html
+}
+
+fn push_line(html: &mut String, number: usize, line: &str, fence: &Fence, block: &CodeBlock) {
+    if fence.is_highlighted(number) {
+        html.push_str("<span class=\"line line-highlight\">");
+    } else {
+        html.push_str("<span class=\"line\">");
+    }
+
+    if fence.line_numbers.unwrap_or(block.line_numbers) {
+        html.push_str(&format!("<span class=\"line-number\">{}</span>", number));
+    }
+
+    html.push_str(line);
+    html.push_str("</span>\n");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_fence_options() {
+        assert_eq!(
+            Fence::parse("rust {3-5,8} linenos"),
+            Fence {
+                language: "rust",
+                highlights: vec![(3, 5), (8, 8)],
+                line_numbers: Some(true),
+            }
+        );
+
+        assert_eq!(
+            Fence::parse("rust linenos=false"),
+            Fence {
+                language: "rust",
+                highlights: vec![],
+                line_numbers: Some(false),
+            }
+        );
+
+        assert!(Fence::parse("rust").is_plain());
+    }
+
+    #[test]
+    fn code_that_takes_too_long_to_highlight_is_escaped_with_a_warning() {
+        let mut block = CodeBlock {
+            line_numbers: false,
+            max_lines: DEFAULT_MAX_HIGHLIGHT_LINES,
+            max_bytes: DEFAULT_MAX_HIGHLIGHT_SIZE * 1024,
+            max_line_bytes: DEFAULT_MAX_HIGHLIGHT_LINE_SIZE * 1024,
+            time_budget: Duration::ZERO,
+        };
+        let end = Event::End(Tag::CodeBlock(CodeBlockKind::Fenced("rust".into())));
+        let mut events = vec![Event::Text("fn main() {}\n".into())];
+
+        let (output, handled) = block.process_event(&mut events, &end);
+
+        assert!(!handled);
+        assert!(matches!(
+            output.as_deref(),
+            Some([Output::Warning(warning)]) if warning.starts_with(
+                "Highlighting a `rust` code block of 1 lines (13 bytes) took longer than 0 seconds"
+            )
+        ));
+        assert_eq!(events, vec![Event::Text("fn main() {}\n".into())]);
+    }
+
+    #[test]
+    fn code_with_a_very_long_line_is_escaped_with_a_warning() {
+        let mut block = CodeBlock {
+            line_numbers: false,
+            max_lines: DEFAULT_MAX_HIGHLIGHT_LINES,
+            max_bytes: DEFAULT_MAX_HIGHLIGHT_SIZE * 1024,
+            max_line_bytes: DEFAULT_MAX_HIGHLIGHT_LINE_SIZE * 1024,
+            time_budget: HIGHLIGHT_TIME_BUDGET,
+        };
+        let end = Event::End(Tag::CodeBlock(CodeBlockKind::Fenced("js".into())));
+        let code = format!(
+            "var a = [{}];\n",
+            "1,".repeat(DEFAULT_MAX_HIGHLIGHT_LINE_SIZE * 1024)
+        );
+        let mut events = vec![Event::Text(code.clone().into())];
+
+        let (output, handled) = block.process_event(&mut events, &end);
+
+        assert!(!handled);
+        assert!(matches!(
+            output.as_deref(),
+            Some([Output::Warning(warning)]) if warning.ends_with(
+                "has a line longer than the `max_highlight_line_size` of 4 KB, and is shown \
+                 without syntax highlighting. Break up the long lines, or raise the limit"
+            )
+        ));
+        assert_eq!(events, vec![Event::Text(code.into())]);
+    }
+}
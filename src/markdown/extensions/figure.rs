@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::{BrokenLink, CowStr, Event, Options, Parser, Tag};
+use regex::Regex;
+
+use crate::markdown::extension::{Extension, Output};
+use crate::markdown::include::owned_event;
+
+/// Gives tables and images an id and a caption, from the line right before
+/// them, like `{#results}` or `Table: Results {#results}` for tables and
+/// `Figure: The architecture {#architecture}` for images. They are wrapped
+/// in a `<figure>` with the id, so that links can point at them.
+pub struct Figures {
+    /// Start every caption with its number on the page, like `Table 1`
+    pub numbered: bool,
+    pub tables: usize,
+    pub images: usize,
+}
+
+lazy_static! {
+    /// The id at the end of a directive, like `{#results}`
+    static ref ID: Regex = Regex::new(r"\s*\{#([A-Za-z0-9_-]+)\}\s*$").unwrap();
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Table,
+    Image,
+}
+
+impl Kind {
+    /// What the caption of a directive for this kind starts with, and what
+    /// numbered captions start with
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Table => "Table",
+            Kind::Image => "Figure",
+        }
+    }
+}
+
+/// The id and the caption from the line before a table or an image
+struct Directive<'a> {
+    id: Option<String>,
+    caption: Vec<Event<'a>>,
+}
+
+impl Extension for Figures {
+    fn process_event<'a>(
+        &mut self,
+        events: &mut Vec<Event<'a>>,
+        event: &Event<'a>,
+    ) -> (Option<Vec<Output<'a>>>, bool) {
+        let wrapped = match event {
+            Event::End(Tag::Table(_)) => self.wrap_table(events),
+            Event::End(Tag::Paragraph) => self.wrap_image(events),
+            _ => false,
+        };
+
+        if !wrapped {
+            return (None, false);
+        }
+
+        let mut outputs = vec![];
+        if let Event::End(Tag::Table(_)) = event {
+            outputs.push(Output::Event(event.to_owned()));
+        }
+        outputs.push(Output::Event(html!("</figure>\n")));
+
+        (Some(outputs), true)
+    }
+}
+
+impl Figures {
+    /// Moves the directive before the table that is being closed into a
+    /// figure around it
+    fn wrap_table(&mut self, events: &mut Vec<Event>) -> bool {
+        let table = match events
+            .iter()
+            .rposition(|event| matches!(event, Event::Start(Tag::Table(_))))
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        let (start, directive) = match previous_paragraph(events, table)
+            .and_then(|start| Some((start, parse(&events[start + 1..table - 1], Kind::Table)?)))
+        {
+            Some(found) => found,
+            None => return false,
+        };
+
+        self.tables += 1;
+        let caption = self.caption(directive.caption, Kind::Table, self.tables, "caption");
+
+        events.splice(start..table, [figure(&directive.id, " class=\"table\"")]);
+        // The caption has to be the first child of the table
+        events.splice(start + 2..start + 2, caption);
+
+        true
+    }
+
+    /// Moves the directive before the image in the paragraph that is being
+    /// closed into a figure around it. The directive is either the first
+    /// line of the paragraph, or a paragraph of its own right before it.
+    fn wrap_image(&mut self, events: &mut Vec<Event>) -> bool {
+        let paragraph = match events
+            .iter()
+            .rposition(|event| matches!(event, Event::Start(Tag::Paragraph)))
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        let contents = &events[paragraph + 1..];
+
+        let (start, directive, image) = if is_image(contents) {
+            match previous_paragraph(events, paragraph).and_then(|start| {
+                Some((
+                    start,
+                    parse(&events[start + 1..paragraph - 1], Kind::Image)?,
+                ))
+            }) {
+                Some((start, directive)) => (start, directive, paragraph + 1),
+                None => return false,
+            }
+        } else {
+            let line_break = match contents
+                .iter()
+                .position(|event| matches!(event, Event::SoftBreak))
+            {
+                Some(index) => paragraph + 1 + index,
+                None => return false,
+            };
+            if !is_image(&events[line_break + 1..]) {
+                return false;
+            }
+            match parse(&events[paragraph + 1..line_break], Kind::Image) {
+                Some(directive) => (paragraph, directive, line_break + 1),
+                None => return false,
+            }
+        };
+
+        self.images += 1;
+        let caption = self.caption(directive.caption, Kind::Image, self.images, "figcaption");
+
+        let image = events.split_off(image);
+        events.truncate(start);
+        events.push(figure(&directive.id, ""));
+        events.extend(image);
+        events.extend(caption);
+
+        true
+    }
+
+    /// The events of the caption, numbered if asked for. Empty if there is
+    /// neither a caption nor a number.
+    fn caption<'a>(
+        &self,
+        mut caption: Vec<Event<'a>>,
+        kind: Kind,
+        number: usize,
+        tag: &str,
+    ) -> Vec<Event<'a>> {
+        if self.numbered {
+            let separator = if caption.is_empty() { "" } else { ": " };
+            caption.insert(0, html!("{} {}{}", kind.label(), number, separator));
+        }
+        if caption.is_empty() {
+            return caption;
+        }
+
+        caption.insert(0, html!("<{}>", tag));
+        caption.push(html!("</{}>", tag));
+        caption
+    }
+}
+
+/// The opening tag of the figure, with the id if there is one
+fn figure<'a>(id: &Option<String>, class: &str) -> Event<'a> {
+    match id {
+        Some(id) => {
+            let mut escaped = String::new();
+            escape_html(&mut escaped, id).unwrap();
+            html!("<figure id=\"{}\"{}>", escaped, class)
+        }
+        None => html!("<figure{}>", class),
+    }
+}
+
+/// Where the paragraph that ends right before `index` starts, if one does
+fn previous_paragraph(events: &[Event], index: usize) -> Option<usize> {
+    if index == 0 || !matches!(events[index - 1], Event::End(Tag::Paragraph)) {
+        return None;
+    }
+
+    events[..index - 1]
+        .iter()
+        .rposition(|event| matches!(event, Event::Start(Tag::Paragraph)))
+}
+
+/// Whether the events are a single image and nothing else
+fn is_image(events: &[Event]) -> bool {
+    matches!(events.first(), Some(Event::Start(Tag::Image(..))))
+        && matches!(events.last(), Some(Event::End(Tag::Image(..))))
+        && events
+            .iter()
+            .filter(|event| matches!(event, Event::Start(Tag::Image(..))))
+            .count()
+            == 1
+}
+
+/// The directive in the inline events of a line, which is either only an
+/// id, like `{#results}`, or a caption with an optional id at the end, like
+/// `Table: Results {#results}`
+fn parse<'a>(line: &[Event<'a>], kind: Kind) -> Option<Directive<'a>> {
+    // The text of a line can be split over several events
+    let mut caption: Vec<Event> = vec![];
+    for event in line {
+        match (caption.last_mut(), event) {
+            (Some(Event::Text(text)), Event::Text(more)) => {
+                *text = CowStr::from(format!("{}{}", text, more));
+            }
+            _ => caption.push(event.clone()),
+        }
+    }
+
+    if let [Event::Text(text)] = caption.as_slice() {
+        if let Some(id) = only_id(text) {
+            return Some(Directive {
+                id: Some(id),
+                caption: vec![],
+            });
+        }
+    }
+
+    match caption.first_mut() {
+        Some(Event::Text(text)) => {
+            let rest = text.strip_prefix(kind.label())?.strip_prefix(':')?;
+            *text = CowStr::from(rest.trim_start().to_string());
+        }
+        _ => return None,
+    }
+
+    let mut id = None;
+    if let Some(Event::Text(text)) = caption.last_mut() {
+        if let Some(found) = ID.captures(text) {
+            id = Some(found[1].to_string());
+            let start = found.get(0).unwrap().start();
+            *text = CowStr::from(text[..start].to_string());
+        }
+    }
+
+    caption.retain(|event| !matches!(event, Event::Text(text) if text.is_empty()));
+
+    Some(Directive { id, caption })
+}
+
+/// Whether the text is a line that gives the table or image after it an
+/// id or a caption
+pub fn is_directive(text: &str) -> bool {
+    let text = text.trim();
+
+    only_id(text).is_some() || text.starts_with("Table:") || text.starts_with("Figure:")
+}
+
+/// The id, if the text is nothing but an id like `{#results}`
+fn only_id(text: &str) -> Option<String> {
+    let text = text.trim();
+
+    ID.captures(text)
+        .filter(|found| found.get(0).unwrap().start() == 0)
+        .map(|found| found[1].to_string())
+}
+
+/// The events of the Markdown with where they are in it, like
+/// `Parser::into_offset_iter`. With `figures`, a directive right before a
+/// table is kept apart from the table. Tables can't interrupt a paragraph,
+/// so the directive and the rows are parsed as a single paragraph of text.
+/// The rows of such a paragraph are parsed again on their own, and their
+/// events are given the positions of the rows in the source.
+pub fn offset_events<'a>(
+    input: &'a str,
+    options: Options,
+    figures: bool,
+) -> Vec<(Event<'a>, Range<usize>)> {
+    let mut parser = Parser::new_ext(input, options).into_offset_iter();
+    let mut events = vec![];
+
+    while let Some((event, range)) = parser.next() {
+        let has_directive = match event {
+            Event::Start(Tag::Paragraph) if figures => starts_with_directive(&input[range.clone()]),
+            _ => false,
+        };
+        if !has_directive {
+            events.push((event, range));
+            continue;
+        }
+
+        let mut paragraph = vec![(event, range)];
+        for (event, range) in &mut parser {
+            let end = matches!(event, Event::End(Tag::Paragraph));
+            paragraph.push((event, range));
+            if end {
+                break;
+            }
+        }
+
+        match split_table(input, &paragraph, options) {
+            Some(split) => events.extend(split),
+            None => events.extend(paragraph),
+        }
+    }
+
+    events
+}
+
+/// Whether the first line of the text of a paragraph is the directive of a
+/// table
+fn starts_with_directive(paragraph: &str) -> bool {
+    let line = paragraph.lines().next().unwrap_or("").trim();
+
+    only_id(line).is_some() || line.starts_with("Table:")
+}
+
+/// The paragraph as its directive line, followed by the table its other
+/// lines are. None if they aren't a table after all.
+///
+/// The lines are parsed again without what the blocks around the paragraph
+/// put in front of them, like the `>` of a quote. The definitions of the
+/// page's reference links aren't part of the lines, so the links get the
+/// destinations they have in the paragraph.
+fn split_table<'a>(
+    input: &'a str,
+    paragraph: &[(Event<'a>, Range<usize>)],
+    options: Options,
+) -> Option<Vec<(Event<'a>, Range<usize>)>> {
+    let is_line_break = |event: &Event| matches!(event, Event::SoftBreak | Event::HardBreak);
+    let line_break = paragraph
+        .iter()
+        .position(|(event, _)| is_line_break(event))?;
+
+    // Where every line after the directive starts in the rows, and in the
+    // source
+    let mut lines: Vec<(usize, usize)> = vec![];
+    let mut rows = String::new();
+    let mut line_start = None;
+    for (event, range) in &paragraph[line_break + 1..] {
+        let line_end = match event {
+            Event::End(Tag::Paragraph) => range.end,
+            event if is_line_break(event) => range.start,
+            _ => {
+                line_start.get_or_insert(range.start);
+                continue;
+            }
+        };
+        let start = line_start.take()?;
+        let line = input[start..line_end].trim_end_matches(&['\n', '\r'][..]);
+        // A code span or some HTML over several lines
+        if line.contains('\n') {
+            return None;
+        }
+
+        lines.push((rows.len(), start));
+        rows.push_str(line);
+        rows.push('\n');
+    }
+    if !rows.starts_with('|') {
+        return None;
+    }
+
+    let source = |position: usize| {
+        let line = lines.partition_point(|(row, _)| *row <= position) - 1;
+        lines[line].1 + position - lines[line].0
+    };
+    let source_range = |range: Range<usize>| {
+        if range.is_empty() {
+            source(range.start)..source(range.start)
+        } else {
+            source(range.start)..source(range.end - 1) + 1
+        }
+    };
+
+    let links = paragraph
+        .iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Link(_, url, title) | Tag::Image(_, url, title)) => {
+                Some((range.clone(), (url.to_string(), title.to_string())))
+            }
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let mut resolve = |link: BrokenLink| {
+        links
+            .get(&source_range(link.span))
+            .map(|(url, title)| (CowStr::from(url.clone()), CowStr::from(title.clone())))
+    };
+
+    let table = Parser::new_with_broken_link_callback(&rows, options, Some(&mut resolve))
+        .into_offset_iter()
+        .map(|(event, range)| (owned_event(event), source_range(range)))
+        .collect::<Vec<_>>();
+    let is_table = matches!(table.first(), Some((Event::Start(Tag::Table(_)), _)))
+        && matches!(table.last(), Some((Event::End(Tag::Table(_)), _)));
+    if !is_table {
+        return None;
+    }
+
+    let directive = paragraph[0].1.start..paragraph[line_break].1.start;
+    let mut split = paragraph[..line_break].to_vec();
+    split.push((Event::End(Tag::Paragraph), directive));
+    split.extend(table);
+
+    Some(split)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pulldown_cmark::Alignment;
+
+    fn has_table(events: &[(Event, Range<usize>)]) -> bool {
+        events
+            .iter()
+            .any(|(event, _)| matches!(event, Event::Start(Tag::Table(_))))
+    }
+
+    #[test]
+    fn separates_directives_from_the_tables_after_them() {
+        let input = "{#results}\n| a | b |\n|---|---|\n| 1 | 2 |\n\nAfter the table\n";
+        let events = offset_events(input, Options::ENABLE_TABLES, true);
+
+        assert_eq!(
+            events[..4]
+                .iter()
+                .map(|(e, _)| e.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Event::Start(Tag::Paragraph),
+                Event::Text("{#results}".into()),
+                Event::End(Tag::Paragraph),
+                Event::Start(Tag::Table(vec![Alignment::None; 2])),
+            ]
+        );
+        // Positions are still the ones in the source
+        assert_eq!(events[3].1.start, input.find("| a").unwrap());
+        let after = events
+            .iter()
+            .find(|(event, _)| event == &Event::Text("After the table".into()))
+            .unwrap();
+        assert_eq!(after.1.start, input.find("After").unwrap());
+    }
+
+    #[test]
+    fn leaves_other_paragraphs_with_rows_alone() {
+        let input = "{#results} and more\n| a |\n|---|\n";
+        assert!(!has_table(&offset_events(
+            input,
+            Options::ENABLE_TABLES,
+            true
+        )));
+
+        let input = "Table: Results\n| a |\n|---|\n";
+        assert!(has_table(&offset_events(
+            input,
+            Options::ENABLE_TABLES,
+            true
+        )));
+        assert!(!has_table(&offset_events(
+            input,
+            Options::ENABLE_TABLES,
+            false
+        )));
+    }
+
+    #[test]
+    fn separates_directives_from_tables_in_quotes() {
+        let input = "> {#results}\n> | a |\n> |---|\n> | 1 |\n";
+        let events = offset_events(input, Options::ENABLE_TABLES, true);
+
+        let table = events
+            .iter()
+            .find(|(event, _)| matches!(event, Event::Start(Tag::Table(_))))
+            .unwrap();
+        assert_eq!(table.1, input.find("| a").unwrap()..input.len());
+        let cell = events
+            .iter()
+            .find(|(event, _)| event == &Event::Text("1".into()))
+            .unwrap();
+        assert_eq!(cell.1.start, input.find('1').unwrap());
+    }
+
+    #[test]
+    fn resolves_reference_links_in_tables() {
+        let input = "Table: Links\n| [Setup][setup] |\n|---|\n| ![Logo][logo] |\n\n\
+                     [setup]: /guides/setup \"Setup\"\n[logo]: /logo.png\n";
+        let events = offset_events(input, Options::ENABLE_TABLES, true);
+
+        assert!(has_table(&events));
+        let urls = events
+            .iter()
+            .filter_map(|(event, _)| match event {
+                Event::Start(Tag::Link(_, url, title) | Tag::Image(_, url, title)) => {
+                    Some((url.to_string(), title.to_string()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                ("/guides/setup".to_string(), "Setup".to_string()),
+                ("/logo.png".to_string(), String::new()),
+            ]
+        );
+    }
+}
@@ -1,9 +1,17 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use pulldown_cmark::escape::{escape_href, escape_html};
 use pulldown_cmark::{CowStr, Event, LinkType, Tag};
+use regex::Regex;
 use url::{ParseError, Url};
 
+use crate::head_validator::{attribute_spans, tag_end};
 use crate::markdown::extension::{Extension, Output};
+use crate::routing;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Link {
@@ -17,11 +25,42 @@ pub enum UrlType {
     Remote(Url),
 }
 
+/// A link that starts with the name of the docs directory, like
+/// `docs/images/logo.png`, which works when browsing the repository but
+/// not in the built site, and couldn't be pointed at the right file
+#[derive(Debug, PartialEq, Clone)]
+pub struct DocsDirLink {
+    pub url: String,
+    /// The URL the file would have in the site
+    pub suggestion: String,
+}
+
+/// The pages of the site and the files of its `_include` directory, by
+/// their path in the docs directory
+pub type SiteFiles = Arc<HashSet<PathBuf>>;
+
 pub struct LinkRewriter {
     pub url_root: String,
+    /// The path back to the root of the site from the current page. When
+    /// set, links starting from the root are rendered relative to the page.
+    pub relative_root: Option<String>,
+    /// The page is built one directory below its Markdown file, so
+    /// relative links are rendered with a `../` in front
+    pub nested_page: bool,
     pub link_rewrite_rules: HashMap<String, String>,
     pub url_params: Vec<(String, String)>,
     pub current_link: Option<Link>,
+    /// Render links pointing outside of the site with `target="_blank"`
+    /// and a class that themes can hook into.
+    pub external_links_new_tab: bool,
+    /// The full URL the site is hosted at. Links to the same host are
+    /// not considered external.
+    pub base_url: Option<String>,
+    pub in_external_link: bool,
+    /// The docs directory of the project. Links starting with its name are
+    /// pointed at the file they mean in the site, if it is in `site_files`.
+    pub docs_dir: Option<PathBuf>,
+    pub site_files: Option<SiteFiles>,
 }
 
 impl Extension for LinkRewriter {
@@ -32,36 +71,42 @@ impl Extension for LinkRewriter {
     ) -> (Option<Vec<Output<'a>>>, bool) {
         match event.to_owned() {
             Event::Start(Tag::Image(link_type, url, title)) => {
-                let url = self.rewrite_link(url);
-                return (
-                    Some(vec![Output::Event(Event::Start(Tag::Image(
+                let (url, misplaced) = self.fix_docs_dir_link(url);
+                let url = self.rendered_link(url);
+                let mut output = misplaced
+                    .map(Output::DocsDirLink)
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                output.extend(vec![
+                    Output::Url(url.clone()),
+                    Output::Event(Event::Start(Tag::Image(
                         link_type,
                         CowStr::from(url),
                         title,
-                    )))]),
-                    true,
-                );
+                    ))),
+                ]);
+                return (Some(output), true);
             }
             Event::Start(Tag::Link(link_type, url, title)) => {
-                let rewritten_url = self.rewrite_link(url);
-                let url = if !self.url_params.is_empty() && is_in_local_domain(&rewritten_url) {
-                    append_parameters(rewritten_url, &self.url_params)
-                } else {
-                    rewritten_url
-                };
-                let str_url = url.to_owned();
+                let (url, misplaced) = self.fix_docs_dir_link(url);
+                let mut output = misplaced
+                    .map(Output::DocsDirLink)
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                let rewritten_url = self.rewrite_link(url.clone());
+                let rendered_url = self.rendered_link(url);
+                let (url, str_url) =
+                    if !self.url_params.is_empty() && is_in_local_domain(&rewritten_url) {
+                        (
+                            append_parameters(rewritten_url, &self.url_params),
+                            append_parameters(rendered_url, &self.url_params),
+                        )
+                    } else {
+                        (rewritten_url, rendered_url)
+                    };
 
                 if link_type == LinkType::Inline {
-                    if let Ok(valid_url) = Url::parse(&url)
-                        .map(|u| UrlType::Remote(u))
-                        .or_else(|e| match e {
-                            ParseError::EmptyHost | ParseError::RelativeUrlWithoutBase => {
-                                Ok(UrlType::Local(PathBuf::from(url)))
-                            }
-                            e => Err(e),
-                        })
-                        .map_err(|l| l)
-                    {
+                    if let Some(valid_url) = url_type(url) {
                         self.current_link = Some(Link {
                             title: title.clone().to_string(),
                             url: valid_url,
@@ -69,26 +114,70 @@ impl Extension for LinkRewriter {
                     }
                 }
 
-                return (
-                    Some(vec![Output::Event(Event::Start(Tag::Link(
+                if self.external_links_new_tab && self.is_external(&str_url) {
+                    self.in_external_link = true;
+
+                    output.extend(vec![
+                        Output::Event(Event::Html(CowStr::from(external_link_tag(
+                            &str_url, &title,
+                        )))),
+                        Output::Url(str_url),
+                    ]);
+                    return (Some(output), true);
+                }
+
+                output.extend(vec![
+                    Output::Url(str_url.clone()),
+                    Output::Event(Event::Start(Tag::Link(
                         link_type,
                         CowStr::from(str_url),
                         title,
-                    )))]),
-                    true,
-                );
+                    ))),
+                ]);
+                return (Some(output), true);
             }
             Event::End(Tag::Link(link_type, url, title)) => {
                 let mut output: Vec<Output> = vec![];
 
-                if self.current_link.is_some() {
-                    output.push(Output::Link(self.current_link.take().unwrap()));
+                if let Some(link) = self.current_link.take() {
+                    output.push(Output::Link(link));
                 }
 
-                output.push(Output::Event(Event::End(Tag::Link(link_type, url, title))));
+                if self.in_external_link {
+                    self.in_external_link = false;
+                    output.push(Output::Event(Event::Html(CowStr::from("</a>"))));
+                } else {
+                    output.push(Output::Event(Event::End(Tag::Link(link_type, url, title))));
+                }
 
                 return (Some(output), true);
             }
+            Event::Html(html) => {
+                let mut links = vec![];
+                let rewritten = rewrite_html_urls(&html, |tag, url| {
+                    if tag == "a" && is_root_path(url) {
+                        links.extend(url_type(self.rewrite_link(CowStr::Borrowed(url))));
+                    }
+                    self.rendered_link(CowStr::Borrowed(url))
+                });
+                if rewritten == html.as_ref() {
+                    return (None, false);
+                }
+
+                // Only the links from the root of the site are checked, like
+                // they are in Markdown. Raw HTML has no link text to go by.
+                let mut output = links
+                    .into_iter()
+                    .map(|url| {
+                        Output::Link(Link {
+                            title: String::new(),
+                            url,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                output.push(Output::Event(Event::Html(CowStr::from(rewritten))));
+                return (Some(output), true);
+            }
             Event::Text(text) => {
                 if let Some(link) = &mut self.current_link {
                     link.title.push_str(&text);
@@ -107,14 +196,216 @@ impl LinkRewriter {
     fn rewrite_link(&self, url: CowStr) -> String {
         if let Some(matching_link) = self.link_rewrite_rules.get(&url.clone().into_string()) {
             matching_link.to_owned()
-        } else if url.starts_with("/") {
-            format!("{}{}", self.url_root.trim_end_matches('/'), url)
         } else {
-            url.to_string()
+            resolve_root_path(&url, &self.url_root)
+        }
+    }
+
+    /// The link as it is rendered in the page. Same as `rewrite_link`, except
+    /// that links starting from the root of the site are made relative to the
+    /// current page if `relative_root` is set, and relative links are rebased
+    /// onto the directory of the Markdown file for nested pages. Checks still
+    /// see the rewritten link, so they don't have to know where each page is.
+    fn rendered_link(&self, url: CowStr) -> String {
+        if self.nested_page
+            && is_relative_path(&url)
+            && !self.link_rewrite_rules.contains_key(url.as_ref())
+        {
+            return format!("../{}", url);
+        }
+
+        match &self.relative_root {
+            Some(root) if !self.link_rewrite_rules.contains_key(url.as_ref()) => {
+                resolve_root_path(&url, root)
+            }
+            _ => self.rewrite_link(url),
+        }
+    }
+
+    /// Points links written from the root of the repository, like
+    /// `docs/images/logo.png` or `/docs/guide.md`, at the same file in the
+    /// site, where the docs directory is the root. Links that really do go
+    /// to a directory with the same name in the site are left alone. If the
+    /// file isn't in the site, the link is kept as it is and returned, to be
+    /// warned about.
+    fn fix_docs_dir_link<'a>(&self, url: CowStr<'a>) -> (CowStr<'a>, Option<DocsDirLink>) {
+        let (docs_dir, files) = match (&self.docs_dir, &self.site_files) {
+            (Some(docs_dir), Some(files)) => (docs_dir, files),
+            _ => return (url, None),
+        };
+        let name = match docs_dir.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => return (url, None),
+        };
+
+        if self.link_rewrite_rules.contains_key(url.as_ref()) || Url::parse(&url).is_ok() {
+            return (url, None);
+        }
+
+        let end = url.find(['#', '?']).unwrap_or(url.len());
+        let (path, suffix) = url.split_at(end);
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let within_docs = match path
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('/'))
+        {
+            Some(rest) => rest,
+            None => return (url, None),
+        };
+
+        if in_site(files, path) {
+            return (url, None);
+        }
+
+        // Files in `_include` are copied to the root of the site
+        let within_site = within_docs
+            .strip_prefix(INCLUDE_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(within_docs);
+        let fixed = format!("/{}{}", within_site, suffix);
+
+        if in_site(files, within_site) {
+            (CowStr::from(fixed), None)
+        } else {
+            let misplaced = DocsDirLink {
+                url: url.to_string(),
+                suggestion: fixed,
+            };
+            (url, Some(misplaced))
+        }
+    }
+
+    /// Whether the URL points outside of the site
+    fn is_external(&self, url: &str) -> bool {
+        is_external_url(url, self.base_url.as_deref())
+    }
+}
+
+static INCLUDE_DIR: &str = "_include";
+
+lazy_static! {
+    static ref TAG_START_REGEX: Regex = Regex::new(r"<([a-zA-Z][a-zA-Z0-9-]*)").unwrap();
+}
+
+/// Points a URL written from the root of the site, like `/guides/setup`, at
+/// `root`, which is either the `base_path` or the way back to the root from
+/// a page, like `../`. Any other URL is returned as it is.
+pub fn resolve_root_path(url: &str, root: &str) -> String {
+    if is_root_path(url) {
+        format!("{}/{}", root.trim_end_matches('/'), &url[1..])
+    } else {
+        url.to_string()
+    }
+}
+
+/// Whether the URL is written from the root of the site. URLs starting
+/// with `//` lead to another site.
+fn is_root_path(url: &str) -> bool {
+    url.starts_with('/') && !url.starts_with("//")
+}
+
+/// Whether the URL is a path relative to the page, like `setup` or
+/// `../images/logo.png`. Links to a part of the same page are not.
+fn is_relative_path(url: &str) -> bool {
+    !url.is_empty()
+        && !url.starts_with(['/', '#', '?'])
+        && Url::parse(url) == Err(ParseError::RelativeUrlWithoutBase)
+}
+
+/// Passes the URL of every `href` and `src` attribute in a snippet of HTML
+/// through `rewrite`, along with the lowercase name of its tag. Nothing else
+/// about the HTML changes, so this is no less safe than the HTML it's given.
+pub fn rewrite_html_urls(html: &str, mut rewrite: impl FnMut(&str, &str) -> String) -> String {
+    let mut rewritten = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(start) = TAG_START_REGEX.captures(&html[pos..]) {
+        let tag_start = pos + start.get(0).unwrap().start();
+        let tag = match tag_end(&html[tag_start..]) {
+            Some(end) => &html[tag_start..tag_start + end],
+            None => break,
+        };
+        let name = start[1].to_ascii_lowercase();
+        let mut end = 0;
+
+        rewritten.push_str(&html[pos..tag_start]);
+        for attribute in attribute_spans(tag) {
+            match attribute.value {
+                Some(value) if attribute.name == "href" || attribute.name == "src" => {
+                    rewritten.push_str(&tag[end..value.start]);
+                    rewritten.push_str(&rewrite(&name, &tag[value.clone()]));
+                    end = value.end;
+                }
+                _ => {}
+            }
+        }
+        rewritten.push_str(&tag[end..]);
+        pos = tag_start + tag.len();
+    }
+    rewritten.push_str(&html[pos..]);
+
+    rewritten
+}
+
+/// Whether the URL of a link leads to another site or to a file of this one
+fn url_type(url: String) -> Option<UrlType> {
+    match Url::parse(&url) {
+        Ok(url) => Some(UrlType::Remote(url)),
+        Err(ParseError::EmptyHost | ParseError::RelativeUrlWithoutBase) => {
+            Some(UrlType::Local(PathBuf::from(url)))
         }
+        Err(_) => None,
     }
 }
 
+/// Whether a path from the root of the site leads to a page made from the
+/// docs directory, or a file copied from its `_include` directory
+fn in_site(files: &HashSet<PathBuf>, path: &str) -> bool {
+    let is_page = routing::source_paths(path)
+        .iter()
+        .any(|source| files.contains(source));
+
+    is_page || files.contains(&Path::new(INCLUDE_DIR).join(path))
+}
+
+/// Whether the URL points to a http(s) page that is not hosted on the
+/// same host as the site at `base_url`.
+fn is_external_url(url: &str, base_url: Option<&str>) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return false;
+    }
+
+    let site_host = base_url
+        .and_then(|base| Url::parse(base).ok())
+        .and_then(|base| base.host_str().map(|h| h.to_owned()));
+
+    match (url.host_str(), site_host) {
+        (Some(host), Some(site_host)) => host != site_host,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+fn external_link_tag(url: &str, title: &str) -> String {
+    let mut tag = String::from("<a href=\"");
+    escape_href(&mut tag, url).unwrap();
+    tag.push('"');
+
+    if !title.is_empty() {
+        tag.push_str(" title=\"");
+        escape_html(&mut tag, title).unwrap();
+        tag.push('"');
+    }
+
+    tag.push_str(" target=\"_blank\" rel=\"noopener noreferrer\" class=\"external-link\">");
+    tag
+}
+
 fn append_parameters<'a>(url: String, url_params: &'a Vec<(String, String)>) -> String {
     let mut appended = url;
     appended.push_str("?");
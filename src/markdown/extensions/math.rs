@@ -15,10 +15,7 @@ impl Extension for MathBlock {
                 let lang = inner.split(' ').next().unwrap();
                 if lang == "math" {
                     return (
-                        Some(vec![
-                            Output::Event(html!("<div class=\"math\">\n")),
-                            Output::Block("math"),
-                        ]),
+                        Some(vec![Output::Event(html!("<div class=\"math\">\n"))]),
                         true,
                     );
                 }
@@ -26,9 +23,9 @@ impl Extension for MathBlock {
             Event::End(Tag::CodeBlock(CodeBlockKind::Fenced(inner))) => {
                 let lang = inner.split(' ').next().unwrap();
                 if lang == "math" {
+                    // Math that doesn't render is left as it was written
                     #[cfg(feature = "katex")]
-                    {
-                        let code_event = events.last_mut().unwrap();
+                    if let Some(code_event) = events.last_mut() {
                         if let Some(code) = match code_event {
                             Event::Text(text) => Some(text.to_string()),
                             _ => None,
@@ -38,7 +35,6 @@ impl Extension for MathBlock {
                                 .output_type(katex::OutputType::HtmlAndMathml)
                                 .build()
                                 .unwrap();
-                            katex::render_with_opts(&code, &opts).unwrap();
                             if let Ok(html) = katex::render_with_opts(&code, &opts) {
                                 *code_event = Event::Html(CowStr::from(html));
                             }
@@ -46,8 +42,7 @@ impl Extension for MathBlock {
                     }
 
                     #[cfg(feature = "latex2mathml")]
-                    {
-                        let code_event = events.last_mut().unwrap();
+                    if let Some(code_event) = events.last_mut() {
                         if let Some(code) = match code_event {
                             Event::Text(text) => Some(text.to_string()),
                             _ => None,
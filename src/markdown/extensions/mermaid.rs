@@ -15,10 +15,7 @@ impl Extension for MermaidBlock {
                 let lang = inner.split(' ').next().unwrap();
                 if lang == "mermaid" {
                     return (
-                        Some(vec![
-                            Output::Event(html!("<div class=\"mermaid\">\n")),
-                            Output::Block("diagram"),
-                        ]),
+                        Some(vec![Output::Event(html!("<div class=\"mermaid\">\n"))]),
                         true,
                     );
                 }
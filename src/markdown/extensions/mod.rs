@@ -1,8 +1,11 @@
 #[macro_use]
 pub mod macros;
+pub mod attribution;
 pub mod callout;
+pub mod children;
 pub mod codeblock;
 pub mod emoji;
+pub mod figure;
 pub mod link_rewriter;
 pub mod math;
 pub mod mermaid;
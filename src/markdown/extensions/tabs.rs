@@ -135,7 +135,7 @@ fn close_tabgroup<'a>(events: &mut Vec<Event>, tabgroup: &mut TabGroup) -> Vec<O
 
     tablist.push(html!("<ul class=\"tab-list\" role=\"tablist\">"));
     tabgroup.tabs.iter().for_each(|tab| {
-        tablist.push(html!("<li class= role=\"presentation\">"));
+        tablist.push(html!("<li role=\"presentation\">"));
         tablist.push(html!(
             "<label class=\"{}\" id=\"{}\" title=\"{}\" role=\"tab\">{}</label>",
             if tab.is_active { "active" } else { "" },
@@ -147,7 +147,10 @@ fn close_tabgroup<'a>(events: &mut Vec<Event>, tabgroup: &mut TabGroup) -> Vec<O
     });
     tablist.push(html!("</ul>"));
 
-    events.splice(idx..idx + 1, tablist);
+    // Another extension may have rewritten the events the group started at
+    if idx < events.len() {
+        events.splice(idx..idx + 1, tablist);
+    }
 
     output.push(Output::Event(html!("</div>")));
 
@@ -1,10 +1,45 @@
-use std::fmt;
-
+use pulldown_cmark::escape::escape_html;
 use pulldown_cmark::{CowStr, Event, Tag};
 
 use crate::markdown::extension::{Extension, Output};
 
-pub struct Tasklist;
+/// How every checkbox rendered for a task item starts
+static CHECKBOX: &str = "<input type=\"checkbox\" data-task-id=";
+
+pub struct Tasklist {
+    /// Leave the checkboxes enabled, so readers can tick them
+    pub interactive: bool,
+    /// The URI of the page, which the ids of its task items start with
+    pub page_uri: String,
+    /// How many task items came before the current one on the page
+    pub item: usize,
+}
+
+impl Tasklist {
+    /// Gives every task item an id that stays the same between builds, as
+    /// long as the items before it on the page stay the same
+    fn checkbox(&mut self, checked: bool) -> String {
+        let mut task_id = String::new();
+        escape_html(
+            &mut task_id,
+            &format!("{}#task-{}", self.page_uri, self.item),
+        )
+        .unwrap();
+        self.item += 1;
+
+        format!(
+            "{}\"{}\"{}{}/>\n",
+            CHECKBOX,
+            task_id,
+            if checked { " checked=\"\"" } else { "" },
+            if self.interactive {
+                ""
+            } else {
+                " disabled=\"\""
+            },
+        )
+    }
+}
 
 impl Extension for Tasklist {
     fn process_event<'a>(
@@ -13,23 +48,25 @@ impl Extension for Tasklist {
         event: &Event<'a>,
     ) -> (Option<Vec<Output<'a>>>, bool) {
         match event {
+            Event::TaskListMarker(checked) => {
+                let checkbox = Event::Html(CowStr::from(self.checkbox(*checked)));
+                return (Some(vec![Output::Event(checkbox)]), true);
+            }
             Event::End(Tag::List(_)) => {
-                let is_tasklist = events.iter_mut().rev().any(|tag| match tag {
-                    Event::TaskListMarker(_) => true,
+                let start_index = match events
+                    .iter()
+                    .rposition(|tag| matches!(tag, Event::Start(Tag::List(_))))
+                {
+                    Some(index) => index,
+                    None => return (None, false),
+                };
+
+                let is_tasklist = events[start_index..].iter().any(|tag| match tag {
+                    Event::Html(html) => html.starts_with(CHECKBOX),
                     _ => false,
                 });
 
                 if is_tasklist {
-                    let start_index = events.len()
-                        - 1
-                        - events
-                            .iter_mut()
-                            .rev()
-                            .position(|tag| match tag {
-                                Event::Start(Tag::List(_)) => true,
-                                _ => false,
-                            })
-                            .unwrap();
                     events[start_index] = html!("<ul class=\"checklist\">");
                 }
             }
@@ -1,4 +1,7 @@
+use std::collections::HashSet;
+
 use pulldown_cmark::{CowStr, Event, Tag};
+use regex::Regex;
 use serde::Serialize;
 use slug::slugify;
 
@@ -6,13 +9,206 @@ use crate::markdown::extension::{Extension, Output};
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct Heading {
+    /// The text of the heading, after its number when headings are numbered
     pub title: String,
     pub anchor: String,
     pub level: u32,
+    /// The section number, like `1.` or `1.2`, when headings are numbered
+    pub number: Option<String>,
+}
+
+/// A heading with the headings of its section nested under it
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct HeadingNode {
+    pub heading: Heading,
+    pub children: Vec<HeadingNode>,
+}
+
+impl Heading {
+    /// The text of the heading, without its number
+    pub fn unnumbered_title(&self) -> &str {
+        match &self.number {
+            Some(number) => &self.title[number.len() + 1..],
+            None => &self.title,
+        }
+    }
+
+    /// Nests every heading under the nearest heading before it with a lower
+    /// level. A skipped level adds no empty nodes, so an H4 right under an
+    /// H2 is its child. Headings with nothing above them, like H3s before
+    /// the first H2, are at the top.
+    pub fn build_tree(headings: &[Heading]) -> Vec<HeadingNode> {
+        let mut nodes = vec![];
+        let mut rest = headings;
+
+        while let Some((heading, after)) = rest.split_first() {
+            let section_end = after
+                .iter()
+                .position(|next| next.level <= heading.level)
+                .unwrap_or(after.len());
+
+            nodes.push(HeadingNode {
+                heading: heading.clone(),
+                children: Heading::build_tree(&after[..section_end]),
+            });
+            rest = &after[section_end..];
+        }
+
+        nodes
+    }
+}
+
+/// How anchors are derived from heading titles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlugStyle {
+    /// ASCII-only slugs with runs of separators collapsed, e.g. `c-rust`
+    Default,
+    /// The slugs GitHub gives headings in rendered Markdown, e.g. `c--rust`
+    Github,
+}
+
+impl SlugStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "default" => Some(SlugStyle::Default),
+            "github" => Some(SlugStyle::Github),
+            _ => None,
+        }
+    }
+
+    /// The anchor for a heading, before it is made unique on the page
+    pub fn slug(&self, title: &str) -> String {
+        match self {
+            SlugStyle::Default => slugify(title),
+            SlugStyle::Github => github_slug(title),
+        }
+    }
+}
+
+/// Follows GitHub's algorithm: lowercase the title, drop everything that
+/// isn't a letter, number, underscore, hyphen or space, then turn every
+/// space into a hyphen. Runs of spaces and leading or trailing hyphens are
+/// kept as they are, so `C++ & Rust` becomes `c--rust`.
+fn github_slug(title: &str) -> String {
+    GITHUB_REMOVED
+        .replace_all(&title.to_lowercase(), "")
+        .replace(' ', "-")
 }
 
 pub struct TableOfContents {
     pub current_heading: Option<Heading>,
+    pub slug_style: SlugStyle,
+    /// Anchors given to the headings so far, to keep them unique
+    pub anchors: HashSet<String>,
+    /// Counts the headings at each level from H2 to H6, when headings are
+    /// numbered
+    pub numbers: Option<[u32; 5]>,
+}
+
+lazy_static! {
+    /// An explicit anchor at the end of a heading, e.g. `## Installation {#install}`
+    static ref EXPLICIT_ID: Regex = Regex::new(r"\s*\{#([^{}]*)\}\s*$").unwrap();
+    static ref SAFE_ID: Regex = Regex::new(r"^[A-Za-z0-9_-]+$").unwrap();
+    /// Characters GitHub leaves out of heading anchors
+    static ref GITHUB_REMOVED: Regex = Regex::new(r"[^\p{L}\p{M}\p{N}\p{Pc} -]").unwrap();
+}
+
+impl TableOfContents {
+    /// Makes the anchor unique on the page by numbering repeats, like
+    /// `setup`, `setup-1`, `setup-2`.
+    fn unique_anchor(&mut self, anchor: String) -> String {
+        let mut unique = anchor.clone();
+        let mut count = 0;
+
+        while self.anchors.contains(&unique) {
+            count += 1;
+            unique = format!("{}-{}", anchor, count);
+        }
+
+        self.anchors.insert(unique.clone());
+        unique
+    }
+
+    /// The number of the next heading at the given level, like `2.` or
+    /// `2.1`. The page title, H1, isn't numbered. A level skipped on the way
+    /// down counts as its first section, so an H4 right under the first H2
+    /// is `1.1.1`.
+    fn next_number(&mut self, level: u32) -> Option<String> {
+        let numbers = self.numbers.as_mut()?;
+        let depth = (level as usize).checked_sub(2)?;
+
+        numbers[depth] += 1;
+        for number in &mut numbers[..depth] {
+            if *number == 0 {
+                *number = 1;
+            }
+        }
+        for number in &mut numbers[depth + 1..] {
+            *number = 0;
+        }
+
+        let number = numbers[..=depth]
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+
+        Some(if depth == 0 {
+            format!("{}.", number)
+        } else {
+            number
+        })
+    }
+}
+
+/// Removes an explicit `{#id}` from the end of the heading whose events end
+/// the list, returning the id. Only plain text counts, so `{#id}` in inline
+/// code stays as it is. Ids that aren't safe to use as anchors are left in
+/// the heading text.
+fn take_explicit_id(events: &mut Vec<Event>) -> Option<String> {
+    let start = events
+        .iter()
+        .rposition(|e| matches!(e, Event::Start(Tag::Heading(_))))?;
+    let text_start = events[start + 1..]
+        .iter()
+        .rposition(|e| !matches!(e, Event::Text(_)))
+        .map(|i| start + 2 + i)
+        .unwrap_or(start + 1);
+
+    let trailing_text = events[text_start..]
+        .iter()
+        .map(|e| match e {
+            Event::Text(text) => text.as_ref(),
+            _ => "",
+        })
+        .collect::<String>();
+
+    let captures = EXPLICIT_ID.captures(&trailing_text)?;
+    let id = captures.get(1).unwrap().as_str().to_string();
+
+    if !SAFE_ID.is_match(&id) {
+        return None;
+    }
+
+    // Cut the matched suffix off the text events, starting from the last one
+    let mut to_remove = captures.get(0).unwrap().as_str().len();
+    while to_remove > 0 {
+        let text = match events.last() {
+            Some(Event::Text(text)) => text.to_string(),
+            _ => break,
+        };
+
+        if text.len() <= to_remove {
+            to_remove -= text.len();
+            events.pop();
+        } else {
+            let kept = text[..text.len() - to_remove].to_string();
+            *events.last_mut().unwrap() = Event::Text(CowStr::from(kept));
+            to_remove = 0;
+        }
+    }
+
+    Some(id)
 }
 
 impl Extension for TableOfContents {
@@ -27,17 +223,43 @@ impl Extension for TableOfContents {
                     level,
                     anchor: String::new(),
                     title: String::new(),
+                    number: None,
                 });
             }
             Event::End(Tag::Heading(_)) => {
-                let mut heading = self.current_heading.take().unwrap();
-                heading.anchor = slugify(&heading.title);
-
-                if let Some(header_start) = events.iter_mut().rev().find(|tag| match tag {
-                    Event::Start(Tag::Heading(_)) => true,
-                    _ => false,
-                }) {
-                    *header_start = html!("<h{} id=\"{}\">", heading.level, heading.anchor);
+                // Another extension may have taken the start of the heading
+                let mut heading = match self.current_heading.take() {
+                    Some(heading) => heading,
+                    None => return (None, false),
+                };
+                let anchor = match take_explicit_id(events) {
+                    Some(id) => {
+                        let title = EXPLICIT_ID.replace(&heading.title, "").into_owned();
+                        heading.title = title;
+                        id
+                    }
+                    None => self.slug_style.slug(&heading.title),
+                };
+                heading.anchor = self.unique_anchor(anchor);
+
+                // Numbers come after the anchor, which stays the same when
+                // sections are added or moved
+                heading.number = self.next_number(heading.level);
+                if let Some(number) = &heading.number {
+                    heading.title = format!("{} {}", number, heading.title);
+                }
+
+                if let Some(start) = events
+                    .iter()
+                    .rposition(|tag| matches!(tag, Event::Start(Tag::Heading(_))))
+                {
+                    events[start] = html!("<h{} id=\"{}\">", heading.level, heading.anchor);
+                    if let Some(number) = &heading.number {
+                        events.insert(
+                            start + 1,
+                            html!("<span class=\"heading-number\">{}</span> ", number),
+                        );
+                    }
                 }
 
                 return (Some(vec![Output::Heading(heading)]), false);
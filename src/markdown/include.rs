@@ -0,0 +1,567 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Tag};
+use regex::Regex;
+
+use super::{
+    extension::{Extension, Output, TextExtension},
+    extensions::{
+        emoji::EmojiConverter,
+        figure,
+        toc::{SlugStyle, TableOfContents},
+    },
+};
+use crate::frontmatter;
+
+lazy_static! {
+    /// A paragraph that includes another page, or one section of it, e.g.
+    /// `{% include "reference/errors.md#timeout-errors" %}`
+    static ref INCLUDE: Regex = Regex::new(r#"^\{%\s*include\s+"([^"]+)"\s*%\}$"#).unwrap();
+}
+
+/// The raw Markdown of every page, by its path in the docs directory
+pub type Sources = Arc<BTreeMap<PathBuf, Arc<str>>>;
+
+/// Replaces include directives with the content of the pages they point to.
+///
+/// A directive on its own paragraph includes the whole page, or only the
+/// section under the heading with the given anchor, up to the next heading
+/// of the same or a higher level. Headings are moved to fit under the last
+/// heading before the directive, and relative links are pointed at the
+/// same files from the root of the site.
+pub struct Includes {
+    /// The path of the including page in the docs directory
+    pub page: PathBuf,
+    pub sources: Sources,
+    pub options: Options,
+    pub slug_style: SlugStyle,
+    /// Whether emoji shortcodes are converted, which changes the anchors of
+    /// headings that have them
+    pub emoji: bool,
+    /// Whether tables and images get ids and captions, which keeps their
+    /// directives apart from the tables after them
+    pub figures: bool,
+}
+
+impl Includes {
+    /// The events of the page, with every include expanded, and why the
+    /// includes that couldn't be expanded were left out
+    pub fn expand<'a>(&self, input: &'a str) -> (Vec<Event<'a>>, Vec<String>) {
+        let mut errors = vec![];
+        let mut stack = vec![self.page.clone()];
+        let events = self.expand_source(input, &self.page, &mut stack, &mut errors);
+
+        (events, errors)
+    }
+
+    fn expand_source<'a>(
+        &self,
+        input: &'a str,
+        page: &Path,
+        stack: &mut Vec<PathBuf>,
+        errors: &mut Vec<String>,
+    ) -> Vec<Event<'a>> {
+        let mut events = vec![];
+        let mut context_level = None;
+        let mut parser = figure::offset_events(input, self.options, self.figures).into_iter();
+
+        while let Some((event, range)) = parser.next() {
+            if let Event::Start(Tag::Paragraph) = event {
+                if let Some(captures) = INCLUDE.captures(input[range].trim()) {
+                    for (event, _) in &mut parser {
+                        if let Event::End(Tag::Paragraph) = event {
+                            break;
+                        }
+                    }
+
+                    match self.include(&captures[1], page, stack, errors) {
+                        Ok(included) => events.extend(shift_headings(included, context_level)),
+                        Err(e) => errors.push(e),
+                    }
+                    continue;
+                }
+            }
+
+            if let Event::Start(Tag::Heading(level)) = event {
+                context_level = Some(level);
+            }
+            events.push(event);
+        }
+
+        events
+    }
+
+    fn include(
+        &self,
+        target: &str,
+        page: &Path,
+        stack: &mut Vec<PathBuf>,
+        errors: &mut Vec<String>,
+    ) -> Result<Vec<Event<'static>>, String> {
+        let (file, anchor) = match target.split_once('#') {
+            Some((file, anchor)) => (file, Some(anchor)),
+            None => (target, None),
+        };
+        let path = resolve(page, file);
+
+        let source = self
+            .sources
+            .get(&path)
+            .ok_or_else(|| format!("Could not include {}: there is no such page", target))?;
+
+        if let Some(start) = stack.iter().position(|p| p == &path) {
+            let chain = stack[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "Could not include {}: it includes itself ({})",
+                target,
+                chain.join(" → ")
+            ));
+        }
+
+        stack.push(path.clone());
+        let events = self
+            .expand_source(frontmatter::without(source), &path, stack, errors)
+            .into_iter()
+            .map(owned_event)
+            .collect::<Vec<_>>();
+        stack.pop();
+
+        let events = match anchor {
+            Some(anchor) => {
+                let headings = anchors(&events, self.slug_style, self.emoji);
+                match headings.iter().position(|(a, _)| a == anchor) {
+                    Some(i) => section(events, &headings, i),
+                    None if headings.is_empty() => {
+                        return Err(format!(
+                            "Could not include {}: {} has no headings",
+                            target,
+                            path.display()
+                        ))
+                    }
+                    None => {
+                        return Err(format!(
+                            "Could not include {}: {} has no heading with that anchor. \
+                             Its anchors are: {}",
+                            target,
+                            path.display(),
+                            headings
+                                .iter()
+                                .map(|(a, _)| a.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ))
+                    }
+                }
+            }
+            None => events,
+        };
+
+        Ok(rewrite_links(events, page, &path))
+    }
+}
+
+/// The path of an include target in the docs directory. Targets are
+/// relative to the including page, or to the docs directory if they start
+/// with a `/`.
+fn resolve(page: &Path, target: &str) -> PathBuf {
+    match target.strip_prefix('/') {
+        Some(target) => normalize(Path::new(target)),
+        None => normalize(&page.parent().unwrap_or(Path::new("")).join(target)),
+    }
+}
+
+/// Resolves `.` and `..` without touching the file system. Going above the
+/// root stays at the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(part) => normalized.push(part),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+/// The anchor and level of every heading, by the index of the event that
+/// starts it. Anchors are found the same way as when the page is rendered.
+fn anchors(
+    events: &[Event<'static>],
+    slug_style: SlugStyle,
+    emoji: bool,
+) -> Vec<(String, (usize, u32))> {
+    let mut toc = TableOfContents {
+        current_heading: None,
+        slug_style,
+        anchors: HashSet::new(),
+        numbers: None,
+    };
+    let mut heading_events = vec![];
+    let mut start = 0;
+    let mut found = vec![];
+
+    for (i, event) in events.iter().enumerate() {
+        let mut event = event.clone();
+        if let Event::Text(text) = &mut event {
+            if emoji {
+                *text = EmojiConverter.process_text(text);
+            }
+        }
+        if let Event::Start(Tag::Heading(_)) = event {
+            heading_events.clear();
+            start = i;
+        }
+
+        let (output, _) = toc.process_event(&mut heading_events, &event);
+        for output in output.into_iter().flatten() {
+            if let Output::Heading(heading) = output {
+                found.push((heading.anchor, (start, heading.level)));
+            }
+        }
+        heading_events.push(event);
+    }
+
+    found
+}
+
+/// The events from the `i`th heading up to the next heading of the same or
+/// a higher level
+fn section(
+    mut events: Vec<Event<'static>>,
+    headings: &[(String, (usize, u32))],
+    i: usize,
+) -> Vec<Event<'static>> {
+    let (start, level) = headings[i].1;
+    let end = headings[i + 1..]
+        .iter()
+        .find(|(_, (_, l))| *l <= level)
+        .map(|(_, (end, _))| *end)
+        .unwrap_or(events.len());
+
+    events.truncate(end);
+    events.split_off(start)
+}
+
+/// Moves the headings so that the first one is one level below the heading
+/// the content is included under. Without one, the levels are kept.
+fn shift_headings(events: Vec<Event<'static>>, context_level: Option<u32>) -> Vec<Event<'static>> {
+    let top = events.iter().find_map(|event| match event {
+        Event::Start(Tag::Heading(level)) => Some(*level),
+        _ => None,
+    });
+    let shift = match (context_level, top) {
+        (Some(context), Some(top)) => context as i64 + 1 - top as i64,
+        _ => return events,
+    };
+    let shifted = |level: u32| (level as i64 + shift).clamp(1, 6) as u32;
+
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::Heading(level)) => Event::Start(Tag::Heading(shifted(level))),
+            Event::End(Tag::Heading(level)) => Event::End(Tag::Heading(shifted(level))),
+            event => event,
+        })
+        .collect()
+}
+
+/// Points the relative links and images of content included from `source`
+/// at the same files from the root of the site, so they keep working on
+/// `page`. Links to anchors are left alone.
+fn rewrite_links(events: Vec<Event<'static>>, page: &Path, source: &Path) -> Vec<Event<'static>> {
+    let source_dir = source.parent().unwrap_or(Path::new(""));
+    if page.parent().unwrap_or(Path::new("")) == source_dir {
+        return events;
+    }
+
+    let rewrite = |url: CowStr<'static>| -> CowStr<'static> {
+        if url.is_empty() || url.starts_with('/') || url.starts_with('#') || url.contains(':') {
+            return url;
+        }
+        let path = normalize(&source_dir.join(url.as_ref()));
+        let parts = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        CowStr::from(format!("/{}", parts.join("/")))
+    };
+
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::Link(kind, url, title)) => {
+                Event::Start(Tag::Link(kind, rewrite(url), title))
+            }
+            Event::End(Tag::Link(kind, url, title)) => {
+                Event::End(Tag::Link(kind, rewrite(url), title))
+            }
+            Event::Start(Tag::Image(kind, url, title)) => {
+                Event::Start(Tag::Image(kind, rewrite(url), title))
+            }
+            Event::End(Tag::Image(kind, url, title)) => {
+                Event::End(Tag::Image(kind, rewrite(url), title))
+            }
+            event => event,
+        })
+        .collect()
+}
+
+fn owned_str(s: CowStr) -> CowStr<'static> {
+    CowStr::from(s.into_string())
+}
+
+fn owned_tag(tag: Tag) -> Tag<'static> {
+    match tag {
+        Tag::Paragraph => Tag::Paragraph,
+        Tag::Heading(level) => Tag::Heading(level),
+        Tag::BlockQuote => Tag::BlockQuote,
+        Tag::CodeBlock(CodeBlockKind::Indented) => Tag::CodeBlock(CodeBlockKind::Indented),
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            Tag::CodeBlock(CodeBlockKind::Fenced(owned_str(info)))
+        }
+        Tag::List(start) => Tag::List(start),
+        Tag::Item => Tag::Item,
+        Tag::FootnoteDefinition(label) => Tag::FootnoteDefinition(owned_str(label)),
+        Tag::Table(alignments) => Tag::Table(alignments),
+        Tag::TableHead => Tag::TableHead,
+        Tag::TableRow => Tag::TableRow,
+        Tag::TableCell => Tag::TableCell,
+        Tag::Emphasis => Tag::Emphasis,
+        Tag::Strong => Tag::Strong,
+        Tag::Strikethrough => Tag::Strikethrough,
+        Tag::Link(kind, url, title) => Tag::Link(kind, owned_str(url), owned_str(title)),
+        Tag::Image(kind, url, title) => Tag::Image(kind, owned_str(url), owned_str(title)),
+    }
+}
+
+/// Copies the event, so it no longer borrows from the page it was parsed from
+pub(crate) fn owned_event(event: Event) -> Event<'static> {
+    match event {
+        Event::Start(tag) => Event::Start(owned_tag(tag)),
+        Event::End(tag) => Event::End(owned_tag(tag)),
+        Event::Text(text) => Event::Text(owned_str(text)),
+        Event::Code(code) => Event::Code(owned_str(code)),
+        Event::Html(html) => Event::Html(owned_str(html)),
+        Event::FootnoteReference(label) => Event::FootnoteReference(owned_str(label)),
+        Event::SoftBreak => Event::SoftBreak,
+        Event::HardBreak => Event::HardBreak,
+        Event::Rule => Event::Rule,
+        Event::TaskListMarker(checked) => Event::TaskListMarker(checked),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn includes(page: &str, sources: &[(&str, &str)]) -> Includes {
+        Includes {
+            page: PathBuf::from(page),
+            sources: Arc::new(
+                sources
+                    .iter()
+                    .map(|(path, raw)| (PathBuf::from(path), Arc::from(*raw)))
+                    .collect(),
+            ),
+            options: Options::empty(),
+            slug_style: SlugStyle::Default,
+            emoji: true,
+            figures: true,
+        }
+    }
+
+    fn render(page: &str, input: &str, sources: &[(&str, &str)]) -> (String, Vec<String>) {
+        let (events, errors) = includes(page, sources).expand(input);
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events.into_iter());
+        (html, errors)
+    }
+
+    const ERRORS: &str = indoc! {"
+        ---
+        title: Errors
+        ---
+        # Errors
+
+        ## Overview
+
+        Everything can fail.
+
+        ## Timeout errors
+
+        Raised when a [request](../guide/requests.md) takes too long.
+
+        ### Retrying
+
+        Wait a bit first.
+
+        ## Network errors
+
+        Check the cable.
+    "};
+
+    #[test]
+    fn includes_a_section_from_the_middle_of_a_page() {
+        let (html, errors) = render(
+            "guide/README.md",
+            indoc! {r#"
+                # Guide
+
+                {% include "../reference/errors.md#timeout-errors" %}
+
+                The end.
+            "#},
+            &[("reference/errors.md", ERRORS)],
+        );
+
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(
+            html,
+            indoc! {r#"
+                <h1>Guide</h1>
+                <h2>Timeout errors</h2>
+                <p>Raised when a <a href="/guide/requests.md">request</a> takes too long.</p>
+                <h3>Retrying</h3>
+                <p>Wait a bit first.</p>
+                <p>The end.</p>
+            "#}
+        );
+    }
+
+    #[test]
+    fn includes_the_last_section_of_a_page() {
+        let (html, errors) = render(
+            "reference/README.md",
+            indoc! {r#"
+                # Reference
+
+                ## Troubleshooting
+
+                {% include "errors.md#network-errors" %}
+            "#},
+            &[("reference/errors.md", ERRORS)],
+        );
+
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(
+            html,
+            indoc! {r#"
+                <h1>Reference</h1>
+                <h2>Troubleshooting</h2>
+                <h3>Network errors</h3>
+                <p>Check the cable.</p>
+            "#}
+        );
+    }
+
+    #[test]
+    fn includes_whole_pages_without_their_frontmatter() {
+        let (html, errors) = render(
+            "README.md",
+            "{% include \"/reference/errors.md\" %}\n",
+            &[("reference/errors.md", ERRORS)],
+        );
+
+        assert_eq!(errors, Vec::<String>::new());
+        assert!(html.starts_with("<h1>Errors</h1>\n<h2>Overview</h2>"));
+        assert!(html.ends_with("<h2>Network errors</h2>\n<p>Check the cable.</p>\n"));
+    }
+
+    #[test]
+    fn missing_anchors_name_the_page_and_its_anchors() {
+        let (html, errors) = render(
+            "README.md",
+            "# Home\n\n{% include \"reference/errors.md#timeouts\" %}\n",
+            &[("reference/errors.md", ERRORS)],
+        );
+
+        assert_eq!(html, "<h1>Home</h1>\n");
+        assert_eq!(
+            errors,
+            vec![
+                "Could not include reference/errors.md#timeouts: reference/errors.md has no \
+                 heading with that anchor. Its anchors are: errors, overview, timeout-errors, \
+                 retrying, network-errors"
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_pages_are_errors() {
+        let (_, errors) = render("README.md", "{% include \"nope.md#intro\" %}\n", &[]);
+
+        assert_eq!(
+            errors,
+            vec!["Could not include nope.md#intro: there is no such page"]
+        );
+    }
+
+    #[test]
+    fn pages_including_each_other_stop_at_the_cycle() {
+        let a = indoc! {r#"
+            # A
+
+            ## Intro
+
+            Hello from A.
+
+            ## From B
+
+            {% include "b.md#intro" %}
+        "#};
+        let b = indoc! {r#"
+            # B
+
+            ## Intro
+
+            Hello from B.
+
+            {% include "a.md#intro" %}
+        "#};
+
+        let (html, errors) = render("a.md", a, &[("a.md", a), ("b.md", b)]);
+
+        assert_eq!(
+            errors,
+            vec!["Could not include a.md#intro: it includes itself (a.md → b.md → a.md)"]
+        );
+        assert_eq!(
+            html,
+            indoc! {r#"
+                <h1>A</h1>
+                <h2>Intro</h2>
+                <p>Hello from A.</p>
+                <h2>From B</h2>
+                <h3>Intro</h3>
+                <p>Hello from B.</p>
+            "#}
+        );
+    }
+
+    #[test]
+    fn directives_in_code_are_left_alone() {
+        let (html, errors) = render(
+            "README.md",
+            "Use `{% include \"a.md\" %}`\n\n```\n{% include \"a.md\" %}\n```\n",
+            &[],
+        );
+
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(
+            html,
+            "<p>Use <code>{% include &quot;a.md&quot; %}</code></p>\n\
+             <pre><code>{% include &quot;a.md&quot; %}\n</code></pre>\n"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Markdown for short, single line fields outside of pages, such as the
+//! footer in docgen.yaml.
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+use super::sanitizer::{self, SanitizeMode};
+
+fn parser(input: &str) -> Parser<'_> {
+    Parser::new_ext(input, Options::ENABLE_STRIKETHROUGH)
+}
+
+/// Whether the input is a single paragraph of text, links, code and
+/// emphasis, without any headings, lists or other blocks
+pub fn is_inline(input: &str) -> bool {
+    let mut paragraphs = 0;
+
+    parser(input).all(|event| match event {
+        Event::Start(Tag::Paragraph) => {
+            paragraphs += 1;
+            paragraphs == 1
+        }
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..)
+        ),
+        Event::Html(_) | Event::Rule | Event::TaskListMarker(_) => false,
+        _ => true,
+    })
+}
+
+/// Renders the input to HTML without wrapping it in a paragraph. The output
+/// is sanitized with the strict allowlist.
+pub fn render_inline(input: &str) -> String {
+    let events = parser(input).filter(|event| {
+        !matches!(
+            event,
+            Event::Start(Tag::Paragraph) | Event::End(Tag::Paragraph)
+        )
+    });
+
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, events);
+
+    sanitizer::sanitize(rendered.trim_end(), &SanitizeMode::Strict)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_without_a_paragraph() {
+        assert_eq!(
+            render_inline("© Acme — [Privacy](https://acme.com/privacy) & *more*"),
+            "© Acme — <a href=\"https://acme.com/privacy\">Privacy</a> &amp; <em>more</em>"
+        );
+        assert_eq!(render_inline("<script>alert(1)</script>Hi"), "Hi");
+    }
+
+    #[test]
+    fn blocks_are_not_inline() {
+        assert!(is_inline("Made with `docgen` by [Acme](https://acme.com)"));
+        assert!(is_inline(""));
+        assert!(!is_inline("# Heading"));
+        assert!(!is_inline("- a list"));
+        assert!(!is_inline("One paragraph\n\nAnother"));
+        assert!(!is_inline("<div>html</div>"));
+    }
+}
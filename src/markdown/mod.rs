@@ -1,3 +1,7 @@
+pub mod ast;
 mod extension;
 pub mod extensions;
+pub mod include;
+pub mod inline;
 pub mod parser;
+pub mod sanitizer;
@@ -1,30 +1,56 @@
 use std::{
-    borrow::BorrowMut,
     collections::{HashMap, HashSet},
+    path::PathBuf,
 };
 
-use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Tag};
 
 use super::{
+    ast::{AstBuilder, Node},
     extension::{Extension, Output, TextExtension},
     extensions::{
+        attribution::Attribution,
         callout::Callout,
-        codeblock::CodeBlock,
+        children,
+        codeblock::{
+            CodeBlock, DEFAULT_MAX_HIGHLIGHT_LINES, DEFAULT_MAX_HIGHLIGHT_LINE_SIZE,
+            DEFAULT_MAX_HIGHLIGHT_SIZE, HIGHLIGHT_TIME_BUDGET,
+        },
         emoji::EmojiConverter,
-        link_rewriter::{Link, LinkRewriter},
+        figure::{self, Figures},
+        link_rewriter::{DocsDirLink, Link, LinkRewriter, SiteFiles},
         math::MathBlock,
         mermaid::MermaidBlock,
         tabs::Tabs,
         task_list::Tasklist,
-        toc::{Heading, TableOfContents},
+        toc::{Heading, SlugStyle, TableOfContents},
     },
+    include::{Includes, Sources},
+    sanitizer::{self, SanitizeMode},
 };
+use crate::terminal;
 
 pub struct MarkdownParser {
     pub extensions: Vec<Box<dyn Extension>>,
     pub text_processors: Vec<Box<dyn TextExtension>>,
+    options: Options,
+    sanitize: SanitizeMode,
+    keep_unsanitized_html: bool,
+    preview_length: usize,
+    emit_ast: bool,
+    trace: bool,
+    disabled_extensions: Vec<BuiltinExtension>,
+    includes: Option<Includes>,
 }
 
+/// How many characters of the first paragraph are kept as the search preview
+pub const DEFAULT_PREVIEW_LENGTH: usize = 200;
+
+/// How deep quotes, lists and emphasis can nest. Anything deeper is kept,
+/// but flattened into its closest allowed parent, so that pathological input
+/// can't exhaust the stack while the page is built.
+pub const MAX_NESTING: usize = 100;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ParsedMarkdown {
     pub html: String,
@@ -32,6 +58,49 @@ pub struct ParsedMarkdown {
     pub headings: Vec<Heading>,
     pub links: Vec<Link>,
     pub blocks: HashSet<String>,
+    /// The text of the HTML comments left in the Markdown, which are not
+    /// part of the HTML or the preview
+    pub comments: Vec<String>,
+    /// Whether the Markdown has a `{% children %}` line, which is left out
+    /// of the preview and the AST, and is `children::PLACEHOLDER` in the HTML
+    pub lists_children: bool,
+    /// Links starting with the name of the docs directory, whose file isn't
+    /// in the site
+    pub docs_dir_links: Vec<DocsDirLink>,
+    /// Why the include directives that couldn't be expanded were left out
+    pub include_errors: Vec<String>,
+    /// Why the Markdown couldn't be parsed, in which case the page shows it
+    /// as it was written
+    pub parse_error: Option<String>,
+    /// The content as a tree, for renderers that don't use HTML. Only built
+    /// when asked for with `ParseOptions::emit_ast`.
+    pub ast: Option<Vec<Node>>,
+    /// The HTML before sanitization. Only kept when asked for with
+    /// `ParseOptions::keep_unsanitized_html`.
+    pub unsanitized_html: Option<String>,
+    /// What authors should fix about the content, like code blocks too
+    /// large to be highlighted
+    pub warnings: Vec<String>,
+    /// What the extensions did with every event. Only recorded when asked
+    /// for with `ParseOptions::trace`.
+    pub trace: Option<Vec<TraceEntry>>,
+}
+
+/// What the extensions did with one event of the Markdown, for finding out
+/// which of them rendered a page the way it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// The position of the event in the Markdown, or the number of events
+    /// for what the extensions added at the end of the document
+    pub index: usize,
+    /// The event as it reached the extensions. `None` at the end of the
+    /// document.
+    pub event: Option<String>,
+    /// The extension that handled the event, which the ones after it never
+    /// saw. `None` if it was kept as it was.
+    pub handled_by: Option<&'static str>,
+    /// What the extensions that saw the event produced, by their name
+    pub outputs: Vec<(&'static str, String)>,
 }
 
 impl Default for ParsedMarkdown {
@@ -42,16 +111,94 @@ impl Default for ParsedMarkdown {
             headings: vec![],
             links: vec![],
             blocks: HashSet::new(),
+            comments: vec![],
+            lists_children: false,
+            docs_dir_links: vec![],
+            include_errors: vec![],
+            parse_error: None,
+            ast: None,
+            unsanitized_html: None,
+            warnings: vec![],
+            trace: None,
         }
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct ParseOptions {
     /// Changes the root URL for any links that point to the current domain.
     pub url_root: String,
     pub link_rewrite_rules: HashMap<String, String>,
     pub url_params: Vec<(String, String)>,
     pub root_dir: Option<String>,
+    /// Open links pointing outside of the site in a new tab.
+    pub external_links_new_tab: bool,
+    /// The URL the site is hosted at, used to tell internal links apart from
+    /// external ones.
+    pub base_url: Option<String>,
+    /// Convert straight quotes, dashes and ellipses into their typographic
+    /// counterparts.
+    pub smart_punctuation: bool,
+    /// Give every page its own directory, e.g. `foo.md` becomes
+    /// `foo/index.html` instead of `foo.html`.
+    pub clean_urls: bool,
+    /// Render links to the site relative to the current page, e.g.
+    /// `../guide` instead of `/guide`.
+    pub relative_links: bool,
+    /// The path from the current page back to the root of the site, e.g.
+    /// `../../`. Set for every page when `relative_links` is on.
+    pub relative_root: Option<String>,
+    /// The page is built to an `index.html` in a directory of its own,
+    /// one below its Markdown file, like with `clean_urls`. Relative links
+    /// get a `../` to still reach the files next to the Markdown file.
+    pub nested_page: bool,
+    /// How the rendered HTML is sanitized
+    pub sanitize: SanitizeMode,
+    /// Keep a copy of the HTML as it was before sanitization
+    pub keep_unsanitized_html: bool,
+    /// The maximum number of characters kept for the search preview
+    pub preview_length: usize,
+    /// Show line numbers in code blocks, unless a fence sets `linenos=false`
+    pub line_numbers: bool,
+    /// Code blocks with more lines than this are not highlighted
+    pub max_highlight_lines: usize,
+    /// Code blocks larger than this many bytes are not highlighted
+    pub max_highlight_size: usize,
+    /// Code blocks with a line longer than this many bytes are not
+    /// highlighted
+    pub max_highlight_line_size: usize,
+    /// Leave the checkboxes of task lists enabled, so readers can tick them
+    pub interactive_checklists: bool,
+    /// The URI of the page being parsed. The ids of its task items start
+    /// with it, to keep them unique across the site.
+    pub page_uri: Option<String>,
+    /// How heading anchors are derived from their titles
+    pub slug_style: SlugStyle,
+    /// Number the headings from H2 down, like `1.`, `1.1` and `1.1.1`
+    pub number_headings: bool,
+    /// Start the captions of tables and images with their number on the
+    /// page, like `Table 1` and `Figure 1`
+    pub number_captions: bool,
+    /// Also build the content as a tree, see `ParsedMarkdown::ast`
+    pub emit_ast: bool,
+    /// The docs directory of the project, for fixing links that start with
+    /// its name, like `docs/images/logo.png`
+    pub docs_dir: Option<PathBuf>,
+    /// The files in the docs directory that links starting with its name
+    /// could be pointed at. They are left as they are without it.
+    pub site_files: Option<SiteFiles>,
+    /// The path of the page being parsed in the docs directory, which the
+    /// targets of its include directives are relative to
+    pub page_path: Option<PathBuf>,
+    /// The raw Markdown of every page, for include directives. They are
+    /// left as they are without it.
+    pub sources: Option<Sources>,
+    /// Record which extension handled every event, see
+    /// `ParsedMarkdown::trace`
+    pub trace: bool,
+    /// The extensions that are turned off, whose syntax is rendered as
+    /// plain Markdown
+    pub disabled_extensions: Vec<BuiltinExtension>,
     // pub resolve_embeds: Option<Box<dyn Fn(String) -> Option<String>>>,
 }
 
@@ -62,6 +209,76 @@ impl Default for ParseOptions {
             link_rewrite_rules: HashMap::new(),
             url_params: vec![],
             root_dir: None,
+            external_links_new_tab: false,
+            base_url: None,
+            smart_punctuation: false,
+            clean_urls: false,
+            relative_links: false,
+            relative_root: None,
+            nested_page: false,
+            sanitize: SanitizeMode::Strict,
+            keep_unsanitized_html: false,
+            preview_length: DEFAULT_PREVIEW_LENGTH,
+            line_numbers: false,
+            max_highlight_lines: DEFAULT_MAX_HIGHLIGHT_LINES,
+            max_highlight_size: DEFAULT_MAX_HIGHLIGHT_SIZE * 1024,
+            max_highlight_line_size: DEFAULT_MAX_HIGHLIGHT_LINE_SIZE * 1024,
+            interactive_checklists: false,
+            page_uri: None,
+            slug_style: SlugStyle::Default,
+            number_headings: false,
+            number_captions: false,
+            emit_ast: false,
+            docs_dir: None,
+            site_files: None,
+            page_path: None,
+            sources: None,
+            trace: false,
+            disabled_extensions: vec![],
+        }
+    }
+}
+
+/// The extensions to Markdown that a project can turn off, with the names
+/// they have in the `extensions` of docgen.yaml
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinExtension {
+    Callouts,
+    Attributions,
+    Mermaid,
+    Math,
+    Tabs,
+    TaskLists,
+    Emoji,
+    Figures,
+}
+
+impl BuiltinExtension {
+    pub const ALL: [BuiltinExtension; 8] = [
+        BuiltinExtension::Callouts,
+        BuiltinExtension::Attributions,
+        BuiltinExtension::Mermaid,
+        BuiltinExtension::Math,
+        BuiltinExtension::Tabs,
+        BuiltinExtension::TaskLists,
+        BuiltinExtension::Emoji,
+        BuiltinExtension::Figures,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|ext| ext.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinExtension::Callouts => "callouts",
+            BuiltinExtension::Attributions => "attributions",
+            BuiltinExtension::Mermaid => "mermaid",
+            BuiltinExtension::Math => "math",
+            BuiltinExtension::Tabs => "tabs",
+            BuiltinExtension::TaskLists => "task_lists",
+            BuiltinExtension::Emoji => "emoji",
+            BuiltinExtension::Figures => "figures",
         }
     }
 }
@@ -73,64 +290,243 @@ impl MarkdownParser {
         let url_root = parse_opts.url_root.to_owned();
         let link_rewrite_rules = parse_opts.link_rewrite_rules.to_owned();
         let url_params = parse_opts.url_params.to_owned();
+        let external_links_new_tab = parse_opts.external_links_new_tab;
+        let base_url = parse_opts.base_url.to_owned();
+        let relative_root = parse_opts.relative_root.to_owned();
 
-        let extensions: Vec<Box<dyn Extension>> = vec![
-            Box::new(Tasklist),
-            Box::new(Callout),
-            Box::new(MermaidBlock),
-            Box::new(MathBlock),
-            Box::new(Tabs {
+        let enabled = |ext| !parse_opts.disabled_extensions.contains(&ext);
+
+        let mut extensions: Vec<Box<dyn Extension>> = vec![];
+        if enabled(BuiltinExtension::TaskLists) {
+            extensions.push(Box::new(Tasklist {
+                interactive: parse_opts.interactive_checklists,
+                page_uri: parse_opts.page_uri.clone().unwrap_or_default(),
+                item: 0,
+            }));
+        }
+        if enabled(BuiltinExtension::Callouts) {
+            extensions.push(Box::new(Callout));
+        }
+        // After callouts, which are blockquotes that must not get an
+        // attribution
+        if enabled(BuiltinExtension::Attributions) {
+            extensions.push(Box::new(Attribution));
+        }
+        if enabled(BuiltinExtension::Mermaid) {
+            extensions.push(Box::new(MermaidBlock));
+        }
+        if enabled(BuiltinExtension::Math) {
+            extensions.push(Box::new(MathBlock));
+        }
+        if enabled(BuiltinExtension::Tabs) {
+            extensions.push(Box::new(Tabs {
                 current_tabgroup: None,
                 current_tab: None,
-            }),
-            Box::new(CodeBlock),
-            Box::new(LinkRewriter {
-                url_root,
-                link_rewrite_rules,
-                url_params,
-                current_link: None,
-            }),
-            Box::new(TableOfContents {
-                current_heading: None,
-            }),
-        ];
+            }));
+        }
+        if enabled(BuiltinExtension::Figures) {
+            extensions.push(Box::new(Figures {
+                numbered: parse_opts.number_captions,
+                tables: 0,
+                images: 0,
+            }));
+        }
+        extensions.push(Box::new(CodeBlock {
+            line_numbers: parse_opts.line_numbers,
+            max_lines: parse_opts.max_highlight_lines,
+            max_bytes: parse_opts.max_highlight_size,
+            max_line_bytes: parse_opts.max_highlight_line_size,
+            time_budget: HIGHLIGHT_TIME_BUDGET,
+        }));
+        extensions.push(Box::new(LinkRewriter {
+            url_root,
+            relative_root,
+            nested_page: parse_opts.nested_page,
+            link_rewrite_rules,
+            url_params,
+            current_link: None,
+            external_links_new_tab,
+            base_url,
+            in_external_link: false,
+            docs_dir: parse_opts.docs_dir.clone(),
+            site_files: parse_opts.site_files.clone(),
+        }));
+        extensions.push(Box::new(TableOfContents {
+            current_heading: None,
+            slug_style: parse_opts.slug_style,
+            anchors: HashSet::new(),
+            numbers: if parse_opts.number_headings {
+                Some([0; 5])
+            } else {
+                None
+            },
+        }));
 
-        let text_processors: Vec<Box<dyn TextExtension>> = vec![Box::new(EmojiConverter)];
+        let mut text_processors: Vec<Box<dyn TextExtension>> = vec![];
+        if enabled(BuiltinExtension::Emoji) {
+            text_processors.push(Box::new(EmojiConverter));
+        }
+
+        let mut options =
+            Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_STRIKETHROUGH;
+        if enabled(BuiltinExtension::TaskLists) {
+            options.insert(Options::ENABLE_TASKLISTS);
+        }
+        if parse_opts.smart_punctuation {
+            options.insert(Options::ENABLE_SMART_PUNCTUATION);
+        }
+
+        let includes = match (parse_opts.page_path, parse_opts.sources) {
+            (Some(page), Some(sources)) => Some(Includes {
+                page,
+                sources,
+                options,
+                slug_style: parse_opts.slug_style,
+                emoji: enabled(BuiltinExtension::Emoji),
+                figures: enabled(BuiltinExtension::Figures),
+            }),
+            _ => None,
+        };
 
         MarkdownParser {
             extensions,
             text_processors,
+            options,
+            sanitize: parse_opts.sanitize,
+            keep_unsanitized_html: parse_opts.keep_unsanitized_html,
+            preview_length: parse_opts.preview_length,
+            emit_ast: parse_opts.emit_ast,
+            trace: parse_opts.trace,
+            disabled_extensions: parse_opts.disabled_extensions,
+            includes,
         }
     }
 
     pub fn parse(&mut self, input: &str) -> ParsedMarkdown {
-        let mut parser = Parser::new_ext(input, Options::all()).into_iter();
+        let mut parsed = ParsedMarkdown::default();
+
+        let figures = !self
+            .disabled_extensions
+            .contains(&BuiltinExtension::Figures);
+
+        // Include directives are expanded up front, so that the included
+        // events go through the extensions like the page's own.
+        //
+        // pulldown-cmark 0.8 panics on some inline HTML in quotes and lists,
+        // which shouldn't take the whole build down with it.
+        let tokenized = terminal::catch_unwind_quietly(|| match &self.includes {
+            Some(includes) if input.contains("{%") => includes.expand(input),
+            _ => (
+                figure::offset_events(input, self.options, figures)
+                    .into_iter()
+                    .map(|(event, _)| event)
+                    .collect(),
+                vec![],
+            ),
+        });
+        let mut parser = match tokenized {
+            Ok((mut events, errors)) => {
+                parsed.include_errors = errors;
+                parsed.lists_children = children::replace_directives(&mut events);
+                events.into_iter()
+            }
+            Err(_) => {
+                parsed.parse_error = Some(String::from(
+                    "Could not be parsed as Markdown, and is shown as it was written instead",
+                ));
+                unparsed(input).into_iter()
+            }
+        };
 
         let mut events: Vec<Event> = Vec::new();
-        let mut parsed = ParsedMarkdown::default();
         let mut extract_preview = false;
+        let mut open_comment = None;
+        let mut nesting = NestingLimit::default();
+        let mut ast = if self.emit_ast {
+            Some(AstBuilder::new(&self.disabled_extensions))
+        } else {
+            None
+        };
+        let mut trace = if self.trace { Some(vec![]) } else { None };
+        let mut next_index = 0;
+
+        while let Some(ev) = &mut parser.next() {
+            let index = next_index;
+            next_index += 1;
+            if !nesting.keep(ev) {
+                continue;
+            }
+
+            // Comments are notes for authors, and never make it to readers
+            if let Event::Html(html) = ev {
+                let kept = strip_comments(html, &mut open_comment, &mut parsed.comments);
+                if kept.len() != html.len() {
+                    if kept.trim().is_empty() {
+                        continue;
+                    }
+                    *html = CowStr::from(kept);
+                }
+            }
 
-        while let Some(ev) = &mut parser.borrow_mut().next() {
             if let Event::Text(text) = ev {
                 for extension in &self.text_processors {
                     *text = extension.process_text(text)
                 }
+            }
+
+            if let Some(ast) = &mut ast {
+                if !matches!(ev, Event::Html(html) if html.as_ref() == children::PLACEHOLDER) {
+                    ast.push(ev);
+                }
+            }
 
-                if extract_preview {
-                    parsed.preview = text.to_string();
-                    extract_preview = false;
+            // The preview is the plain text of the first paragraph that has any
+            if extract_preview {
+                match ev {
+                    Event::Text(text) | Event::Code(text) => parsed.preview.push_str(text),
+                    // The caption of a table or an image isn't a preview
+                    Event::SoftBreak if figure::is_directive(&parsed.preview) => {
+                        parsed.preview.clear()
+                    }
+                    Event::SoftBreak | Event::HardBreak => parsed.preview.push(' '),
+                    Event::End(Tag::Paragraph) => {
+                        extract_preview = false;
+                        if figure::is_directive(&parsed.preview) {
+                            parsed.preview.clear();
+                        }
+                    }
+                    _ => {}
                 }
             }
 
             if let Event::Start(Tag::Paragraph) = ev {
-                extract_preview = parsed.preview.len() <= 0;
+                extract_preview = parsed.preview.is_empty();
             }
 
+            // Recorded before any extension sees the event, so that blocks
+            // nested in callouts or tabs are found no matter how the other
+            // extensions rewrite or consume the events.
+            if let Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) = ev {
+                if let Some(block) = block_for_fence(info, &self.disabled_extensions) {
+                    parsed.blocks.insert(block.to_string());
+                }
+            }
+
+            let mut entry = trace.as_ref().map(|_| TraceEntry {
+                index,
+                event: Some(format!("{:?}", ev)),
+                handled_by: None,
+                outputs: vec![],
+            });
+
             let mut handled = false;
             for extension in &mut self.extensions {
                 let (output, is_handled) = extension.process_event(&mut events, &ev);
 
-                handle_output(output, &mut events, &mut parsed);
+                if let Some(entry) = &mut entry {
+                    entry.record(extension.as_ref(), &output, is_handled);
+                }
+                handle_output(output, &mut events, &mut parsed, ast.as_mut());
 
                 if is_handled {
                     handled = true;
@@ -141,31 +537,225 @@ impl MarkdownParser {
             if !handled {
                 events.push(ev.to_owned());
             }
+
+            if let (Some(trace), Some(entry)) = (&mut trace, entry) {
+                trace.push(entry);
+            }
         }
 
+        let mut entry = trace.as_ref().map(|_| TraceEntry {
+            index: next_index,
+            event: None,
+            handled_by: None,
+            outputs: vec![],
+        });
         for extension in &mut self.extensions {
             let output = extension.end_of_doc(&mut events);
-            handle_output(output, &mut events, &mut parsed);
+            if let Some(entry) = &mut entry {
+                entry.record(extension.as_ref(), &output, false);
+            }
+            handle_output(output, &mut events, &mut parsed, ast.as_mut());
         }
+        if let (Some(trace), Some(entry)) = (&mut trace, entry) {
+            if !entry.outputs.is_empty() {
+                trace.push(entry);
+            }
+        }
+        parsed.trace = trace;
+
+        parsed.ast = ast.map(AstBuilder::finish);
+
+        parsed.preview = truncate_preview(&parsed.preview, self.preview_length);
 
         // Write to String buffer.
-        html::push_html(&mut parsed.html, events.into_iter());
+        let mut unsanitized = String::new();
+        html::push_html(&mut unsanitized, events.into_iter());
+
+        parsed.html = sanitizer::sanitize(&unsanitized, &self.sanitize);
+        let removed = sanitizer::removed(&unsanitized, &self.sanitize);
+        if !removed.is_empty() {
+            parsed.warnings.push(format!(
+                "Sanitizing the HTML of the page removed {}. Keep them with \
+                 `sanitize_html` in docgen.yaml",
+                removed.join(", ")
+            ));
+        }
+        if self.keep_unsanitized_html {
+            parsed.unsanitized_html = Some(unsanitized);
+        }
 
         parsed
     }
 }
 
+impl TraceEntry {
+    /// Records what an extension did with the event
+    fn record(&mut self, extension: &dyn Extension, output: &Option<Vec<Output>>, handled: bool) {
+        let name = extension.name();
+        if let Some(output) = output {
+            self.outputs
+                .extend(output.iter().map(|output| (name, format!("{:?}", output))));
+        }
+        if handled {
+            self.handled_by = Some(name);
+        }
+    }
+}
+
+/// The events for Markdown that couldn't be parsed, which show it as a code
+/// block
+fn unparsed(input: &str) -> Vec<Event<'_>> {
+    vec![
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)),
+        Event::Text(CowStr::from(input)),
+        Event::End(Tag::CodeBlock(CodeBlockKind::Indented)),
+    ]
+}
+
+/// Drops the start and end of the blocks and spans nested deeper than
+/// `MAX_NESTING`, keeping what's inside them.
+#[derive(Default)]
+struct NestingLimit {
+    /// Whether each open block or span was kept
+    open: Vec<bool>,
+    depth: usize,
+}
+
+impl NestingLimit {
+    fn keep(&mut self, ev: &Event) -> bool {
+        match ev {
+            Event::Start(tag) if nests(tag) => {
+                let kept = self.depth < MAX_NESTING;
+                if kept {
+                    self.depth += 1;
+                }
+                self.open.push(kept);
+                kept
+            }
+            Event::End(tag) if nests(tag) => {
+                let kept = self.open.pop().unwrap_or(true);
+                if kept {
+                    self.depth = self.depth.saturating_sub(1);
+                }
+                kept
+            }
+            _ => true,
+        }
+    }
+}
+
+/// The tags that can be nested in themselves without limit
+fn nests(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::BlockQuote
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::Emphasis
+            | Tag::Strong
+            | Tag::Strikethrough
+    )
+}
+
+/// Removes HTML comments from a piece of raw HTML, collecting their text.
+/// pulldown-cmark hands over HTML blocks one line at a time, so a comment
+/// left open at the end of one piece is carried over to the next in
+/// `open_comment`.
+fn strip_comments(
+    html: &str,
+    open_comment: &mut Option<String>,
+    comments: &mut Vec<String>,
+) -> String {
+    let mut kept = String::new();
+    let mut rest = html;
+
+    loop {
+        match open_comment.take() {
+            Some(mut comment) => match rest.find("-->") {
+                Some(end) => {
+                    comment.push_str(&rest[..end]);
+                    comments.push(comment.trim().to_string());
+                    rest = &rest[end + 3..];
+                }
+                None => {
+                    comment.push_str(rest);
+                    *open_comment = Some(comment);
+                    return kept;
+                }
+            },
+            None => match rest.find("<!--") {
+                Some(start) => {
+                    kept.push_str(&rest[..start]);
+                    *open_comment = Some(String::new());
+                    rest = &rest[start + 4..];
+                }
+                None => {
+                    kept.push_str(rest);
+                    return kept;
+                }
+            },
+        }
+    }
+}
+
+/// Shortens the preview to at most `length` characters, including the
+/// ellipsis that marks the cut. Words are never cut in half.
+pub(crate) fn truncate_preview(preview: &str, length: usize) -> String {
+    if preview.chars().count() <= length {
+        return preview.to_string();
+    }
+
+    let cut = preview
+        .char_indices()
+        .nth(length.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(preview.len());
+    let mut shortened = &preview[..cut];
+
+    // Drop the word that was cut in half, unless the cut fell between words
+    if !preview[cut..].starts_with(char::is_whitespace) {
+        if let Some(i) = shortened.rfind(char::is_whitespace) {
+            shortened = &shortened[..i];
+        }
+    }
+
+    format!("{}…", shortened.trim_end())
+}
+
+/// The kind of block a fenced code block renders as, for the blocks that
+/// need extra assets on the page.
+fn block_for_fence(info: &str, disabled: &[BuiltinExtension]) -> Option<&'static str> {
+    match info.split(' ').next() {
+        Some("mermaid") if !disabled.contains(&BuiltinExtension::Mermaid) => Some("diagram"),
+        Some("math") if !disabled.contains(&BuiltinExtension::Math) => Some("math"),
+        _ => None,
+    }
+}
+
 #[inline]
 fn handle_output<'a>(
     output: Option<Vec<Output<'a>>>,
     events: &mut Vec<Event<'a>>,
     parsed: &mut ParsedMarkdown,
+    mut ast: Option<&mut AstBuilder>,
 ) {
     if let Some(output) = output {
         output.into_iter().for_each(|result| match result {
             Output::Event(ev) => events.push(ev),
             Output::Link(link) => parsed.links.push(link),
-            Output::Heading(heading) => parsed.headings.push(heading),
+            Output::Heading(heading) => {
+                if let Some(ast) = ast.as_mut() {
+                    ast.set_heading(&heading);
+                }
+                parsed.headings.push(heading)
+            }
+            Output::Url(url) => {
+                if let Some(ast) = ast.as_mut() {
+                    ast.set_url(url);
+                }
+            }
+            Output::DocsDirLink(link) => parsed.docs_dir_links.push(link),
+            Output::Warning(warning) => parsed.warnings.push(warning),
             Output::Block(block) => {
                 parsed.blocks.insert(block.to_string());
             }
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use ammonia::Builder;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::head_validator::{attribute_spans, tag_end};
+
+/// How the rendered HTML is sanitized before being returned by the parser.
+#[derive(Debug, Clone)]
+pub enum SanitizeMode {
+    /// Only allow the elements and attributes Markdown and the docgen
+    /// extensions produce.
+    Strict,
+    /// The strict allowlist, extended with the given tags and attributes.
+    Custom(SanitizeConfig),
+    /// Don't sanitize at all. Only use this for trusted input.
+    Off,
+}
+
+impl Default for SanitizeMode {
+    fn default() -> Self {
+        SanitizeMode::Strict
+    }
+}
+
+/// Additions to the strict allowlist.
+#[derive(Debug, Clone, Default)]
+pub struct SanitizeConfig {
+    /// Extra tags to allow
+    pub tags: Vec<String>,
+    /// Extra attributes to allow, keyed by the tag they are allowed on
+    pub tag_attributes: HashMap<String, Vec<String>>,
+    /// Extra attributes to allow on every tag
+    pub generic_attributes: Vec<String>,
+}
+
+static MATHML_TAGS: &[&str] = &[
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "msup",
+    "msub",
+    "msubsup",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mover",
+    "munder",
+    "munderover",
+    "mtable",
+    "mtr",
+    "mtd",
+    "mstyle",
+    "mpadded",
+    "mphantom",
+    "menclose",
+];
+
+static MATHML_ATTRIBUTES: &[&str] = &[
+    "xmlns",
+    "display",
+    "displaystyle",
+    "mathvariant",
+    "encoding",
+    "stretchy",
+    "fence",
+    "separator",
+    "accent",
+    "accentunder",
+    "lspace",
+    "rspace",
+    "width",
+    "height",
+    "depth",
+    "columnalign",
+    "rowspacing",
+    "columnspacing",
+    "scriptlevel",
+    "notation",
+];
+
+static STRICT: Lazy<Builder<'static>> = Lazy::new(strict_builder);
+
+/// The allowlist used for every sanitization mode. Allows regular Markdown
+/// output and everything produced by the docgen extensions.
+fn strict_builder<'a>() -> Builder<'a> {
+    let mut builder = Builder::default();
+
+    builder
+        // Links to other sites get their rel from the link rewriter instead
+        .link_rel(None)
+        .add_tags(&["input", "label"])
+        // Attributions of blockquotes
+        .add_tags(&["footer", "cite"])
+        // Tables and images with an id or a caption
+        .add_tags(&["figure", "figcaption", "caption"])
+        .add_tags(MATHML_TAGS)
+        .add_generic_attributes(&["class", "id", "role", "aria-hidden"])
+        .add_generic_attribute_prefixes(&["data-"])
+        .add_tag_attributes("a", &["target", "rel"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("label", &["for"])
+        // KaTeX positions its output with inline styles
+        .add_tag_attributes("span", &["style"]);
+
+    for tag in MATHML_TAGS {
+        builder.add_tag_attributes(tag, MATHML_ATTRIBUTES);
+    }
+
+    builder
+}
+
+/// The strict allowlist, extended with the configured tags and attributes
+fn custom_builder(config: &SanitizeConfig) -> Builder<'_> {
+    let mut builder = strict_builder();
+
+    builder.add_tags(config.tags.iter().map(String::as_str));
+    builder.add_generic_attributes(config.generic_attributes.iter().map(String::as_str));
+    for (tag, attributes) in &config.tag_attributes {
+        builder.add_tag_attributes(tag.as_str(), attributes.iter().map(String::as_str));
+    }
+
+    builder
+}
+
+/// Sanitizes the rendered HTML according to the given mode.
+pub fn sanitize(html: &str, mode: &SanitizeMode) -> String {
+    match mode {
+        SanitizeMode::Strict => STRICT.clean(html).to_string(),
+        SanitizeMode::Custom(config) => custom_builder(config).clean(html).to_string(),
+        SanitizeMode::Off => html.to_owned(),
+    }
+}
+
+/// A comment, or the start of a tag along with its name
+static TAG_START_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<!--|<([a-zA-Z][a-zA-Z0-9-]*)").unwrap());
+
+/// The tags and attributes of the HTML that sanitizing it in the given mode
+/// removes, like `<iframe>` or `controls on <video>`. Each is listed once,
+/// in the order they first appear.
+pub fn removed(html: &str, mode: &SanitizeMode) -> Vec<String> {
+    match mode {
+        SanitizeMode::Strict => removed_by(&STRICT, html),
+        SanitizeMode::Custom(config) => removed_by(&custom_builder(config), html),
+        SanitizeMode::Off => vec![],
+    }
+}
+
+fn removed_by(builder: &Builder, html: &str) -> Vec<String> {
+    let tags = builder.clone_tags();
+    let clean_content_tags = builder.clone_clean_content_tags();
+    let tag_attributes = builder.clone_tag_attributes();
+    let generic_attributes = builder.clone_generic_attributes();
+    let prefixes = builder
+        .clone_generic_attribute_prefixes()
+        .unwrap_or_default();
+    let is_allowed = |tag: &str, attribute: &str| {
+        generic_attributes.contains(attribute)
+            || prefixes.iter().any(|prefix| attribute.starts_with(prefix))
+            || tag_attributes
+                .get(tag)
+                .is_some_and(|allowed| allowed.contains(attribute))
+    };
+
+    let mut removed = vec![];
+    let mut pos = 0;
+
+    while let Some(start) = TAG_START_REGEX.captures(&html[pos..]) {
+        let tag_start = pos + start.get(0).unwrap().start();
+        // Comments are never shown, so removing them loses nothing
+        let name = match start.get(1) {
+            Some(name) => name.as_str().to_ascii_lowercase(),
+            None => {
+                pos = html[tag_start..]
+                    .find("-->")
+                    .map_or(html.len(), |i| tag_start + i + 3);
+                continue;
+            }
+        };
+        let tag = match tag_end(&html[tag_start..]) {
+            Some(end) => &html[tag_start..tag_start + end],
+            None => break,
+        };
+        pos = tag_start + tag.len();
+
+        let found = if tags.contains(name.as_str()) {
+            attribute_spans(tag)
+                .into_iter()
+                .filter(|attribute| !is_allowed(&name, &attribute.name))
+                .map(|attribute| format!("{} on <{}>", attribute.name, name))
+                .collect()
+        } else {
+            vec![format!("<{}>", name)]
+        };
+        for item in found {
+            if !removed.contains(&item) {
+                removed.push(item);
+            }
+        }
+
+        // Scripts and styles are removed along with their content, which
+        // isn't HTML
+        if clean_content_tags.contains(name.as_str()) {
+            let closing = format!("</{}", name);
+            pos = html[pos..]
+                .to_ascii_lowercase()
+                .find(&closing)
+                .map_or(html.len(), |i| pos + i);
+        }
+    }
+
+    removed
+}
@@ -0,0 +1,385 @@
+//! The callout syntax of Doctave, which Docgen no longer supports, and
+//! `docgen migrate`, which rewrites it to the block quote syntax:
+//!
+//! ```text
+//! {% info Title %}       > info Title
+//! Content           =>   >
+//! {% end %}              > Content
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::config::Config;
+use crate::warning::Warning;
+use crate::{docs_finder, dry_run, Error, Result};
+
+lazy_static! {
+    static ref LEGACY_OPEN: Regex = Regex::new(
+        r"^( {0,3})\{%\s*(info|notice|success|warn|warning|error)(?:\s+(.*?))?\s*%\}\s*$"
+    )
+    .unwrap();
+    static ref LEGACY_END: Regex = Regex::new(r"^ {0,3}\{%\s*end\s*%\}\s*$").unwrap();
+    static ref FENCE: Regex = Regex::new(r"^ {0,3}(`{3,}|~{3,})").unwrap();
+}
+
+/// A callout in the old syntax
+#[derive(Debug, PartialEq)]
+struct LegacyCallout {
+    /// The index of the line that opens the callout
+    open: usize,
+    /// The index of the `{% end %}` line, if the callout is closed
+    end: Option<usize>,
+    indent: String,
+    kind: String,
+    title: String,
+}
+
+impl LegacyCallout {
+    /// The first line of the callout in the block quote syntax
+    fn replacement(&self) -> String {
+        if self.title.is_empty() {
+            format!("> {}", self.kind)
+        } else {
+            format!("> {} {}", self.kind, self.title)
+        }
+    }
+}
+
+/// One warning for every callout in the old syntax, outside of code blocks
+pub fn legacy_callout_warnings(path: &Path, raw: &str) -> Vec<Warning> {
+    let lines = raw.lines().collect::<Vec<_>>();
+
+    legacy_callouts(&lines)
+        .iter()
+        .map(|callout| {
+            let old = lines[callout.open].trim();
+            let message = match callout.end {
+                Some(_) => format!(
+                    "line {}: `{}` is the old callout syntax, and is shown as text. \
+                     Write `{}` instead, or run `docgen migrate` to rewrite it",
+                    callout.open + 1,
+                    old,
+                    callout.replacement()
+                ),
+                None => format!(
+                    "line {}: `{}` is the old callout syntax, and is shown as text. \
+                     Write `{}` instead. It is never closed with `{{% end %}}`, so \
+                     `docgen migrate` leaves it as it is",
+                    callout.open + 1,
+                    old,
+                    callout.replacement()
+                ),
+            };
+
+            Warning::new(path, message)
+        })
+        .collect()
+}
+
+/// Rewrites every closed callout in the old syntax to a block quote. Code
+/// blocks, and everything else, are left as they are.
+pub fn migrate(raw: &str) -> String {
+    let lines = raw.split_inclusive('\n').collect::<Vec<_>>();
+    let trimmed = lines
+        .iter()
+        .map(|line| line.trim_end_matches(&['\r', '\n'][..]))
+        .collect::<Vec<_>>();
+
+    let mut migrated = String::with_capacity(raw.len());
+    let mut next = 0;
+
+    for callout in legacy_callouts(&trimmed) {
+        let end = match callout.end {
+            Some(end) => end,
+            None => continue,
+        };
+
+        for line in &lines[next..callout.open] {
+            migrated.push_str(line);
+        }
+
+        let newline = &lines[callout.open][trimmed[callout.open].len()..];
+        let newline = if newline.is_empty() { "\n" } else { newline };
+        migrated.push_str(&callout.indent);
+        migrated.push_str(&callout.replacement());
+        migrated.push_str(newline);
+
+        let mut content = &trimmed[callout.open + 1..end];
+        while matches!(content.first(), Some(l) if l.trim().is_empty()) {
+            content = &content[1..];
+        }
+        while matches!(content.last(), Some(l) if l.trim().is_empty()) {
+            content = &content[..content.len() - 1];
+        }
+
+        if !content.is_empty() {
+            migrated.push_str(&callout.indent);
+            migrated.push('>');
+            migrated.push_str(newline);
+        }
+        for line in content {
+            let line = line.strip_prefix(callout.indent.as_str()).unwrap_or(line);
+            migrated.push_str(&callout.indent);
+            if line.trim().is_empty() {
+                migrated.push('>');
+            } else {
+                migrated.push_str("> ");
+                migrated.push_str(line);
+            }
+            migrated.push_str(newline);
+        }
+
+        next = end + 1;
+    }
+
+    for line in &lines[next..] {
+        migrated.push_str(line);
+    }
+
+    migrated
+}
+
+/// The callouts in the old syntax, outside of fenced code blocks
+fn legacy_callouts(lines: &[&str]) -> Vec<LegacyCallout> {
+    let mut callouts = vec![];
+    let mut open: Option<LegacyCallout> = None;
+    // The character and length of the fence of the code block the line is in
+    let mut fence: Option<(char, usize)> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(captures) = FENCE.captures(line) {
+            let marker = captures.get(1).unwrap().as_str();
+            let char = marker.chars().next().unwrap();
+
+            match fence {
+                None => fence = Some((char, marker.len())),
+                Some((c, len))
+                    if c == char
+                        && marker.len() >= len
+                        && line.trim_start()[marker.len()..].trim().is_empty() =>
+                {
+                    fence = None
+                }
+                Some(_) => {}
+            }
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+
+        match open.take() {
+            Some(mut callout) => {
+                if LEGACY_END.is_match(line) {
+                    callout.end = Some(index);
+                    callouts.push(callout);
+                } else {
+                    open = Some(callout);
+                }
+            }
+            None => {
+                if let Some(captures) = LEGACY_OPEN.captures(line) {
+                    open = Some(LegacyCallout {
+                        open: index,
+                        end: None,
+                        indent: captures[1].to_string(),
+                        kind: captures[2].to_string(),
+                        title: captures
+                            .get(3)
+                            .map(|t| t.as_str().to_string())
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+    }
+
+    callouts.extend(open);
+    callouts
+}
+
+pub struct MigrateCommand {}
+
+impl MigrateCommand {
+    /// Rewrites the callouts in the old syntax in every Markdown file of the
+    /// docs directory. With `dry_run`, prints a diff of the changes instead.
+    pub fn run(config: Config, dry_run: bool) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+
+        docs_finder::check_docs_dir(&config)?;
+        let mut sources = docs_finder::load(&config).documents;
+        sources.sort_by(|a, b| a.path().cmp(b.path()));
+
+        let mut changed = 0;
+        for source in &sources {
+            let migrated = migrate(&source.raw);
+            if migrated == *source.raw {
+                continue;
+            }
+            changed += 1;
+
+            if dry_run {
+                let diff = dry_run::unified_diff(
+                    source.path(),
+                    source.raw.as_bytes(),
+                    migrated.as_bytes(),
+                )
+                .unwrap_or_else(|| format!("{} would be rewritten\n", source.path().display()));
+                bunt::write!(stdout, "{}", diff)?;
+            } else {
+                let location = config.source_file(source.path());
+                fs::write(&location, migrated)
+                    .map_err(|e| Error::io(e, format!("Could not write {}", location.display())))?;
+                bunt::writeln!(stdout, "Migrated {$bold}{}{/$}", source.path().display())?;
+            }
+        }
+
+        match (changed, dry_run) {
+            (0, _) => bunt::writeln!(stdout, "Nothing to migrate")?,
+            (n, true) => bunt::writeln!(stdout, "\n{$bold}{}{/$} file(s) would be migrated", n)?,
+            (n, false) => bunt::writeln!(stdout, "\n{$bold}{}{/$} file(s) migrated", n)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warns_about_the_old_callout_syntax() {
+        let raw = indoc! {"
+            # Setup
+
+            {% warning Be careful %}
+            This deletes everything.
+            {% end %}
+        "};
+
+        let warnings = legacy_callout_warnings(Path::new("setup.md"), raw);
+
+        assert_eq!(
+            warnings,
+            vec![Warning::new(
+                Path::new("setup.md"),
+                "line 3: `{% warning Be careful %}` is the old callout syntax, and is shown as text. \
+                 Write `> warning Be careful` instead, or run `docgen migrate` to rewrite it"
+            )]
+        );
+    }
+
+    #[test]
+    fn warns_about_unclosed_callouts() {
+        let warnings = legacy_callout_warnings(Path::new("a.md"), "{% info %}\nNever closed\n");
+
+        assert_eq!(
+            warnings,
+            vec![Warning::new(
+                Path::new("a.md"),
+                "line 1: `{% info %}` is the old callout syntax, and is shown as text. \
+                 Write `> info` instead. It is never closed with `{% end %}`, so \
+                 `docgen migrate` leaves it as it is"
+            )]
+        );
+    }
+
+    #[test]
+    fn does_not_warn_about_code_blocks_or_includes() {
+        let raw = indoc! {"
+            {% include \"snippets/intro.md\" %}
+
+            ````markdown
+            {% info Old %}
+            ```
+            {% end %}
+            ````
+        "};
+
+        assert_eq!(legacy_callout_warnings(Path::new("a.md"), raw), vec![]);
+    }
+
+    #[test]
+    fn migrates_the_old_syntax_and_leaves_code_blocks_alone() {
+        let raw = indoc! {"
+            ---
+            title: Setup
+            ---
+
+            {% info Before you start %}
+
+            You need an account.
+
+            ```
+            docgen build
+            ```
+            {% end %}
+
+            > warning Already migrated
+            >
+            > Stays as it is.
+
+            ```markdown
+            {% error In a code block %}
+            Stays as it is.
+            {% end %}
+            ```
+
+            {% success %}
+            Done!
+            {% end %}
+        "};
+
+        let migrated = migrate(raw);
+
+        assert_eq!(
+            migrated,
+            indoc! {"
+                ---
+                title: Setup
+                ---
+
+                > info Before you start
+                >
+                > You need an account.
+                >
+                > ```
+                > docgen build
+                > ```
+
+                > warning Already migrated
+                >
+                > Stays as it is.
+
+                ```markdown
+                {% error In a code block %}
+                Stays as it is.
+                {% end %}
+                ```
+
+                > success
+                >
+                > Done!
+            "}
+        );
+        assert_eq!(migrate(&migrated), migrated);
+        assert_eq!(
+            legacy_callout_warnings(Path::new("setup.md"), &migrated),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn keeps_the_indentation_and_line_endings() {
+        let raw = "- Step\n\n  {% notice Tip %}\r\n  Indented\r\n  {% end %}\r\n";
+
+        assert_eq!(
+            migrate(raw),
+            "- Step\n\n  > notice Tip\r\n  >\r\n  > Indented\r\n"
+        );
+    }
+}
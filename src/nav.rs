@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::config::Config;
 
 use crate::{docs_finder, navigation, Result};
@@ -11,9 +13,14 @@ impl NavigationCommand {
     pub fn run(config: Config) -> Result<()> {
         let docs = docs_finder::find(&config);
         let nav = navigation::Navigation::new(&config);
-        let tree = nav.links(&docs, false);
+        let default = nav.links(&docs, false);
+        let tree = nav.build_for(&docs);
+
+        // Printed in the same shape docgen.yaml expects, so it can be pasted in
+        let mut output = BTreeMap::new();
+        output.insert("navigation", nav.to_config(&tree, &default));
 
-        println!("{}", serde_yaml::to_string(&tree).unwrap());
+        println!("{}", serde_yaml::to_string(&output).unwrap());
         Ok(())
     }
 }
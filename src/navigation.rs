@@ -1,8 +1,11 @@
-use crate::config::{Config, DirIncludeRule, NavRule};
+use crate::config::{self, Config, DirIncludeRule, NavChildren, NavRule, NavSort};
+use crate::routing;
+use crate::warning::Warning;
 use crate::Document;
+use crate::{Error, Result};
 use serde::Serialize;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
 
@@ -17,10 +20,161 @@ impl<'a> Navigation<'a> {
 
     /// Builds a navigation tree given a root directory
     pub fn build_for(&self, docs: &[Document]) -> Vec<Link> {
+        self.build_with_warnings(docs).0
+    }
+
+    /// Builds a navigation tree given a root directory, along with warnings
+    /// about pages in the same directory with the same `index`, and pages the
+    /// custom navigation links to more than once
+    pub fn build_with_warnings(&self, docs: &[Document]) -> (Vec<Link>, Vec<Warning>) {
         match &self.config.navigation() {
-            None => self.links(docs, false),
-            Some(nav) => self.customize(&nav, &self.links(docs, true)),
+            None => self.links_with_warnings(docs, false),
+            Some(nav) => {
+                let (default, mut warnings) = self.links_with_warnings(docs, true);
+                let mut links = self.customize(&nav, &default);
+                warnings.extend(self.check_duplicates(&mut links));
+
+                (links, warnings)
+            }
+        }
+    }
+
+    /// Warns about pages that are linked more than once in a customized
+    /// navigation, e.g. when a file is listed twice, or listed next to a
+    /// wildcard that already includes it. Unless `dedupe_navigation` is
+    /// turned off, only the first link to each page is kept.
+    pub fn check_duplicates(&self, links: &mut Vec<Link>) -> Vec<Warning> {
+        fn positions<'l>(
+            links: &'l [Link],
+            prefix: &str,
+            found: &mut Vec<(&'l Link, Vec<String>)>,
+        ) {
+            for (i, link) in links.iter().enumerate() {
+                let position = format!("{}[{}]", prefix, i);
+
+                match found.iter_mut().find(|(first, _)| first.path == link.path) {
+                    Some((_, positions)) => positions.push(position.clone()),
+                    None => found.push((link, vec![position.clone()])),
+                }
+
+                positions(&link.children, &format!("{}.children", position), found);
+            }
         }
+
+        fn dedupe(links: &mut Vec<Link>, seen: &mut HashSet<String>) {
+            links.retain_mut(|link| {
+                if !seen.insert(link.path.clone()) {
+                    return false;
+                }
+
+                dedupe(&mut link.children, seen);
+                true
+            });
+        }
+
+        let mut found = vec![];
+        positions(links, "navigation", &mut found);
+
+        let warnings = found
+            .into_iter()
+            .filter(|(_, positions)| positions.len() > 1)
+            .map(|(link, positions)| {
+                Warning::new(
+                    Path::new(&link.src),
+                    format!(
+                        "Listed {} times in the navigation, at {}.{}",
+                        positions.len(),
+                        positions.join(", "),
+                        if self.config.dedupe_navigation() {
+                            " Only the first one is shown."
+                        } else {
+                            ""
+                        }
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if !warnings.is_empty() && self.config.dedupe_navigation() {
+            dedupe(links, &mut HashSet::new());
+        }
+
+        warnings
+    }
+
+    /// Warns about entries of the custom navigation that link to a section
+    /// of a page, like `reference/api.md#rate-limits`, when the page has no
+    /// heading with that anchor
+    pub fn anchor_warnings(&self, docs: &[Document]) -> Vec<Warning> {
+        fn check(rules: &[NavRule], docs: &[Document], warnings: &mut Vec<Warning>) {
+            for rule in rules {
+                match rule {
+                    NavRule::File(path, _) => {
+                        let (file, anchor) = split_anchor(path);
+                        let (anchor, doc) = match (anchor, docs.iter().find(|d| d.path == file)) {
+                            (Some(anchor), Some(doc)) => (anchor, doc),
+                            _ => continue,
+                        };
+
+                        let anchors = doc
+                            .headings()
+                            .iter()
+                            .map(|heading| format!("#{}", heading.anchor))
+                            .collect::<Vec<_>>();
+                        if anchors.contains(&format!("#{}", anchor)) {
+                            continue;
+                        }
+
+                        warnings.push(Warning::new(
+                            &file,
+                            if anchors.is_empty() {
+                                format!(
+                                    "The navigation links to #{}, but the page has no headings",
+                                    anchor
+                                )
+                            } else {
+                                format!(
+                                    "The navigation links to #{}, which is not a heading of the page. \
+                                     Its anchors are {}",
+                                    anchor,
+                                    anchors.join(", ")
+                                )
+                            },
+                        ));
+                    }
+                    NavRule::Dir(_, Some(DirIncludeRule::Explicit(rules, _)), _) => {
+                        check(rules, docs, warnings)
+                    }
+                    NavRule::Dir(..) => {}
+                }
+            }
+        }
+
+        let mut warnings = vec![];
+        if let Some(rules) = self.config.navigation() {
+            check(rules, docs, &mut warnings);
+        }
+
+        warnings
+    }
+
+    /// Fails when the custom navigation links to sections that don't exist,
+    /// see `anchor_warnings`
+    pub fn check_anchors(&self, docs: &[Document]) -> Result<()> {
+        let errors = self.anchor_warnings(docs);
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        Err(Error::new(format!(
+            "Could not find {} section(s) linked from the navigation:\n\n{}",
+            errors.len(),
+            errors
+                .iter()
+                .map(|e| format!("\t{}: {}", e.path.display(), e.message))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )))
     }
 
     /// Build a nested hierarchy from a flat list of documents
@@ -28,6 +182,17 @@ impl<'a> Navigation<'a> {
     /// TODO I don't like recursive algorithms. Is there a way to represent
     /// the navigation without nesting?
     pub fn links(&self, docs: &[Document], include_root_readme: bool) -> Vec<Link> {
+        self.links_with_warnings(docs, include_root_readme).0
+    }
+
+    /// Builds the navigation like `links`, and warns about pages in the same
+    /// directory that ask for the same `index`, since nothing but their file
+    /// names decides their order then
+    fn links_with_warnings(
+        &self,
+        docs: &[Document],
+        include_root_readme: bool,
+    ) -> (Vec<Link>, Vec<Warning>) {
         let base_path = self.config.base_path();
         // This algorithm starts from bottom up and collects all the documents
         // under a specific subdirectory and stores it temporarily inside a
@@ -35,8 +200,12 @@ impl<'a> Navigation<'a> {
         // wherein, we take the collected entires and add them as children to
         // the directory link.
         let mut directories = HashMap::new();
-        let index_file_name = OsStr::new("index.html");
         directories.insert(String::from(base_path), vec![]);
+        // The `index` of every link. A directory link takes the index of its
+        // README, which orders the directory among its siblings.
+        let mut indexes = HashMap::new();
+        // The pages that set each `index` in each directory
+        let mut siblings: BTreeMap<(&Path, u32), Vec<&Path>> = BTreeMap::new();
 
         for doc in docs {
             if doc.src() == "" {
@@ -44,19 +213,35 @@ impl<'a> Navigation<'a> {
             }
 
             let uri_path = &doc.uri_path;
-            let html_path = &doc.html_path;
-            let title = &doc.title;
+            let title = doc.nav_title(self.config.nav_title_source());
             let parent_path = doc.parent.display().to_string();
 
-            let is_root_readme = html_path.file_name() == Some(index_file_name);
+            let is_root_readme = doc.is_readme();
             let is_top_most = uri_path == base_path;
 
             let mut link = Link {
-                title: title.to_string(),
+                title,
                 path: uri_path.to_string(),
                 children: vec![],
                 src: doc.src(),
             };
+            // An order file of the directory takes precedence over `index`
+            let index = doc.order.unwrap_or(doc.index);
+            indexes.insert(link.path.clone(), index);
+
+            // A README is a sibling of the pages next to its directory. The
+            // root README is always first.
+            let dir = if is_root_readme {
+                doc.path.parent().and_then(Path::parent)
+            } else {
+                doc.path.parent()
+            };
+            if index != u32::MAX && !(is_top_most && is_root_readme) {
+                siblings
+                    .entry((dir.unwrap_or_else(|| Path::new("")), index))
+                    .or_default()
+                    .push(&doc.path);
+            }
 
             if is_top_most && is_root_readme {
                 if include_root_readme {
@@ -75,7 +260,39 @@ impl<'a> Navigation<'a> {
             }
         }
 
-        directories.remove(&String::from(base_path)).unwrap()
+        let mut links = directories.remove(base_path).unwrap_or_default();
+        sort_by_index(&mut links, &indexes, base_path);
+
+        let warnings = siblings
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|((dir, index), mut paths)| {
+                paths.sort_by(|a, b| alphanumeric_sort::compare_path(a, b));
+                let dir = if dir.as_os_str().is_empty() {
+                    "the docs directory".to_string()
+                } else {
+                    format!("{}/", dir.display())
+                };
+                let others = paths[1..]
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>();
+
+                Warning::new(
+                    paths[0],
+                    format!(
+                        "Has `index: {}` like {} in {}, so they are ordered by file name. \
+                         Give them different indexes, or use `index: auto` to list a page \
+                         after the ones with an index.",
+                        index,
+                        others.join(", "),
+                        dir
+                    ),
+                )
+            })
+            .collect();
+
+        (links, warnings)
     }
 
     /// Customizes the navigation tree given some rules provided through the
@@ -94,54 +311,148 @@ impl<'a> Navigation<'a> {
     pub fn customize(&self, rules: &[NavRule], default: &[Link]) -> Vec<Link> {
         let mut links = vec![];
 
-        let root_path_rule = NavRule::File(PathBuf::from("/"));
         for rule in rules {
+            let readme_rule;
             let rule = if rule
                 .is_default_readme_rule(&self.config.project_root(), &self.config.docs_dir())
             {
                 // If we're building navigation for the default readme file, we should
                 // use a different path as the rule will contain "/README.md", while the
                 // rest of the program expects it to be "/"
-                &root_path_rule
+                let path = match rule {
+                    NavRule::File(path, _) => match split_anchor(path).1 {
+                        Some(anchor) => PathBuf::from(format!("/#{}", anchor)),
+                        None => PathBuf::from("/"),
+                    },
+                    NavRule::Dir(..) => PathBuf::from("/"),
+                };
+                readme_rule = NavRule::File(path, rule.title().map(String::from));
+                &readme_rule
             } else {
                 rule
             };
 
-            match rule {
-                NavRule::File(path) => links.push(self.find_matching_link(path, &default).expect(
-                    &format!("{}: {}", "No matching link found at", path.display()),
-                )),
-                NavRule::Dir(path, dir_rule) => {
+            let mut link = match rule {
+                NavRule::File(path, _) => {
+                    let mut link = self.find_matching_link(path, &default).expect(&format!(
+                        "{}: {}",
+                        "No matching link found at",
+                        path.display()
+                    ));
+
+                    // Links to a section of the page
+                    if let Some(anchor) = split_anchor(path).1 {
+                        link.path = format!("{}#{}", link.path, anchor);
+                        link.src = format!("{}#{}", link.src, anchor);
+                    }
+
+                    link
+                }
+                NavRule::Dir(path, dir_rule, _) => {
                     let mut index_link = self
                         .find_matching_link(path, &default)
                         .expect("No matching link found");
 
                     match dir_rule {
                         // Don't include any children
-                        None => {
-                            index_link.children.truncate(0);
-                            links.push(index_link);
-                        }
+                        None => index_link.children.truncate(0),
                         // Include all children
-                        Some(DirIncludeRule::WildCard) => links.push(index_link),
+                        Some(DirIncludeRule::WildCard) => {}
                         // Include only links that match the description
-                        Some(DirIncludeRule::Explicit(nested_rules)) => {
-                            let children = self.customize(nested_rules, &default);
+                        Some(DirIncludeRule::Explicit(nested_rules, sort)) => {
+                            let mut children = self.customize(nested_rules, &default);
+                            self.sort_links(&mut children, *sort, &default);
                             index_link.children = children;
-                            links.push(index_link);
                         }
                     }
+
+                    index_link
+                }
+            };
+
+            if let Some(title) = rule.title() {
+                link.title = title.to_owned();
+            }
+
+            links.push(link);
+        }
+
+        links
+    }
+
+    /// Orders an explicit list of children
+    fn sort_links(&self, links: &mut [Link], sort: NavSort, default: &[Link]) {
+        match sort {
+            NavSort::Manual => {}
+            NavSort::Alpha => links.sort_by(|a, b| {
+                alphanumeric_sort::compare_str(a.title.to_lowercase(), b.title.to_lowercase())
+            }),
+            NavSort::Index => {
+                fn flatten<'l>(links: &'l [Link], paths: &mut Vec<&'l str>) {
+                    for link in links {
+                        paths.push(&link.path);
+                        flatten(&link.children, paths);
+                    }
                 }
+
+                let mut order = vec![];
+                flatten(default, &mut order);
+
+                links.sort_by_key(|link| {
+                    order
+                        .iter()
+                        .position(|path| *path == link.path)
+                        .unwrap_or(usize::MAX)
+                });
             }
         }
+    }
 
+    /// Converts a navigation tree back into the format of the `navigation`
+    /// key in docgen.yaml. Directories whose children are the same as in the
+    /// default navigation get `children: "*"`, others list their children.
+    pub fn to_config(&self, links: &[Link], default: &[Link]) -> Vec<config::Navigation> {
         links
+            .iter()
+            .map(|link| {
+                let path = PathBuf::from(&link.src);
+                let is_dir = split_anchor(&path).0.extension() != Some(OsStr::new("md"));
+                let default_link = self.find_matching_link(&path, default);
+
+                let title = default_link
+                    .as_ref()
+                    .filter(|default_link| default_link.title != link.title)
+                    .map(|_| link.title.clone());
+
+                let children = if is_dir {
+                    let default_children = default_link.map(|default_link| default_link.children);
+
+                    // Wildcard entries can't have a title, so list the children instead
+                    if default_children.as_ref() == Some(&link.children) && title.is_none() {
+                        Some(NavChildren::WildCard(String::from("*")))
+                    } else {
+                        Some(NavChildren::List(self.to_config(&link.children, default)))
+                    }
+                } else {
+                    None
+                };
+
+                config::Navigation {
+                    path,
+                    title,
+                    sort: None,
+                    children,
+                }
+            })
+            .collect()
     }
 
     /// Matches a path provided in a NavRule to a Link. Recursively searches through
-    /// the link children to find a match.
+    /// the link children to find a match. The `#anchor` of a path to a section
+    /// is ignored, the link is to the whole page.
     fn find_matching_link(&self, path: &Path, links: &[Link]) -> Option<Link> {
-        let doc_path = Link::path_to_uri(path);
+        let (_, doc_path) = routing::output_path(&split_anchor(path).0, "", false);
+        let doc_path = doc_path.trim_end_matches("/");
 
         let search_result = links.iter().find(|link| {
             let link_path = link.path.strip_prefix(self.config.base_path()).unwrap();
@@ -240,32 +551,70 @@ impl Link {
     }
 }
 
+/// Splits a path from the `navigation` of docgen.yaml into the file and the
+/// anchor after its `#`, for paths that link to a section of a page
+pub fn split_anchor(path: &Path) -> (PathBuf, Option<String>) {
+    match path.to_str().and_then(|path| path.split_once('#')) {
+        Some((file, anchor)) => (PathBuf::from(file), Some(anchor.to_string())),
+        None => (path.to_path_buf(), None),
+    }
+}
+
+/// Orders every level of the navigation by the `index` of the links, or
+/// their position in the `.order` file of their directory. The sort is
+/// stable, so links with the same index keep the order of the documents,
+/// which `document_sort` decides from their paths alone. The root README
+/// always comes first.
+fn sort_by_index(links: &mut [Link], indexes: &HashMap<String, u32>, base_path: &str) {
+    let index = |link: &Link| indexes.get(&link.path).copied().unwrap_or(u32::MAX);
+
+    links.sort_by(|a, b| {
+        (a.path != base_path)
+            .cmp(&(b.path != base_path))
+            .then_with(|| index(a).cmp(&index(b)))
+    });
+
+    for link in links {
+        sort_by_index(&mut link.children, indexes, base_path);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use insta::assert_debug_snapshot;
     use rayon::slice::ParallelSliceMut;
 
     use super::*;
+    use serde_yaml::Value;
     use std::collections::BTreeMap;
     use std::path::Path;
     use std::time::SystemTime;
 
     use crate::docs_finder::document_sort;
+    use crate::test_helpers;
     use crate::Document;
 
     fn page(path: &str, name: &str, base_path: Option<&str>) -> Document {
         let mut frontmatter = BTreeMap::new();
-        frontmatter.insert("title".to_string(), name.to_string());
+        frontmatter.insert("title".to_string(), Value::from(name));
 
         Document::new(
             Path::new(path),
             "Not important".to_string(),
-            frontmatter,
+            Some(frontmatter),
             base_path.unwrap_or("/"),
             SystemTime::now(),
         )
     }
 
+    fn indexed_page(path: &str, name: &str, index: u32) -> Document {
+        test_helpers::page(
+            path,
+            "Not important",
+            &[("title", name), ("index", &index.to_string())],
+        )
+    }
+
     fn config(yaml: Option<&str>) -> Config {
         let conf = yaml.unwrap_or("---\ntitle: My project\n");
 
@@ -322,6 +671,59 @@ mod test {
         }
     }
 
+    fn title_at<'a>(links: &'a [Link], path: &str) -> &'a str {
+        &links.iter().find(|l| l.path == path).unwrap().title
+    }
+
+    #[test]
+    fn nav_title_overrides_the_title_in_navigation() {
+        let config = config(None);
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            test_helpers::page(
+                "install/README.md",
+                "# Overview",
+                &[("title", "Overview"), ("nav_title", "Installation")],
+            ),
+            test_helpers::page("usage/README.md", "# Overview", &[("title", "Overview")]),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let result = Navigation::new(&config).build_for(&docs);
+
+        assert_eq!(title_at(&result, "/install/"), "Installation");
+        assert_eq!(title_at(&result, "/usage/"), "Overview");
+
+        let install = docs.iter().find(|d| d.nav_title.is_some()).unwrap();
+        assert_eq!(install.title, "Overview");
+    }
+
+    #[test]
+    fn nav_title_source_heading_and_filename() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            test_helpers::page(
+                "install/README.md",
+                "# Installing docgen",
+                &[("title", "Overview")],
+            ),
+            test_helpers::page("usage.md", "No headings here", &[("title", "Using docgen")]),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let config_heading = config(Some("---\ntitle: My project\nnav_title_source: heading\n"));
+        let result = Navigation::new(&config_heading).build_for(&docs);
+
+        assert_eq!(title_at(&result, "/install/"), "Installing docgen");
+        assert_eq!(title_at(&result, "/usage"), "usage");
+
+        let config_filename = config(Some("---\ntitle: My project\nnav_title_source: filename\n"));
+        let result = Navigation::new(&config_filename).build_for(&docs);
+
+        assert_eq!(title_at(&result, "/install/"), "install");
+        assert_eq!(title_at(&result, "/usage"), "usage");
+    }
+
     #[test]
     fn basic_navigation() {
         let config = config(None);
@@ -393,8 +795,8 @@ mod test {
         docs.par_sort_by(document_sort);
 
         let rules = vec![
-            NavRule::File(PathBuf::from("one.md")),
-            NavRule::Dir(PathBuf::from("child"), Some(DirIncludeRule::WildCard)),
+            NavRule::File(PathBuf::from("one.md"), None),
+            NavRule::Dir(PathBuf::from("child"), Some(DirIncludeRule::WildCard), None),
         ];
 
         insta::with_settings!({
@@ -423,15 +825,24 @@ mod test {
         docs.par_sort_by(document_sort);
 
         let rules = vec![
-            NavRule::File(PathBuf::from("one.md")),
+            NavRule::File(PathBuf::from("one.md"), None),
             NavRule::Dir(
                 PathBuf::from("child"),
-                Some(DirIncludeRule::Explicit(vec![NavRule::Dir(
-                    PathBuf::from("child").join("nested"),
-                    Some(DirIncludeRule::Explicit(vec![NavRule::File(
-                        PathBuf::from("child").join("nested").join("four.md"),
-                    )])),
-                )])),
+                Some(DirIncludeRule::Explicit(
+                    vec![NavRule::Dir(
+                        PathBuf::from("child").join("nested"),
+                        Some(DirIncludeRule::Explicit(
+                            vec![NavRule::File(
+                                PathBuf::from("child").join("nested").join("four.md"),
+                                None,
+                            )],
+                            NavSort::Manual,
+                        )),
+                        None,
+                    )],
+                    NavSort::Manual,
+                )),
+                None,
             ),
         ];
 
@@ -456,7 +867,7 @@ mod test {
         ];
         docs.par_sort_by(document_sort);
 
-        let rules = vec![NavRule::File(PathBuf::from("child").join("three.md"))];
+        let rules = vec![NavRule::File(PathBuf::from("child").join("three.md"), None)];
 
         insta::with_settings!({
             description => "Manual menu file from nested directory",
@@ -481,9 +892,11 @@ mod test {
 
         let rules = vec![NavRule::Dir(
             PathBuf::from("child"),
-            Some(DirIncludeRule::Explicit(vec![NavRule::File(
-                PathBuf::from("one.md"),
-            )])),
+            Some(DirIncludeRule::Explicit(
+                vec![NavRule::File(PathBuf::from("one.md"), None)],
+                NavSort::Manual,
+            )),
+            None,
         )];
 
         insta::with_settings!({
@@ -498,6 +911,298 @@ mod test {
         });
     }
 
+    #[test]
+    fn manual_menu_titles_and_sorting() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            page("one.md", "One", None),
+            page("faq.md", "Frequently asked questions", None),
+            page("child/README.md", "Nested Root", None),
+            page("child/alpha.md", "Zebra", None),
+            page("child/beta.md", "Apple", None),
+            page("child/gamma.md", "Mango", None),
+            page("other/README.md", "Other Root", None),
+            page("other/a.md", "Yak", None),
+            page("other/b.md", "Bee", None),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let rules = vec![
+            NavRule::File(PathBuf::from("faq.md"), Some(String::from("FAQ"))),
+            NavRule::Dir(PathBuf::from("other"), Some(DirIncludeRule::WildCard), None),
+            NavRule::Dir(
+                PathBuf::from("child"),
+                Some(DirIncludeRule::Explicit(
+                    vec![
+                        NavRule::File(PathBuf::from("child").join("gamma.md"), None),
+                        NavRule::File(PathBuf::from("child").join("alpha.md"), None),
+                        NavRule::File(PathBuf::from("child").join("beta.md"), None),
+                    ],
+                    NavSort::Alpha,
+                )),
+                Some(String::from("Guides")),
+            ),
+            NavRule::Dir(
+                PathBuf::from("other"),
+                Some(DirIncludeRule::Explicit(
+                    vec![
+                        NavRule::File(PathBuf::from("other").join("b.md"), None),
+                        NavRule::File(PathBuf::from("one.md"), Some(String::from("First"))),
+                        NavRule::File(PathBuf::from("other").join("a.md"), None),
+                    ],
+                    NavSort::Index,
+                )),
+                None,
+            ),
+        ];
+
+        insta::with_settings!({
+            description => "Manual menu titles and sorting",
+            omit_expression => true // do not include the default expression
+        }, {
+            let config = config(None);
+            let navigation = Navigation::new(&config);
+            let links = navigation.build_for(&docs);
+            let result = navigation.customize(&rules, &links);
+            assert_debug_snapshot!(result);
+        });
+    }
+
+    #[test]
+    fn warns_about_and_dedupes_a_file_listed_twice() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            page("one.md", "One", None),
+            page("two.md", "Two", None),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let rules = vec![
+            NavRule::File(PathBuf::from("one.md"), None),
+            NavRule::File(PathBuf::from("two.md"), None),
+            NavRule::File(PathBuf::from("one.md"), None),
+        ];
+
+        let config = config(None);
+        let navigation = Navigation::new(&config);
+        let default = navigation.links(&docs, true);
+        let mut links = navigation.customize(&rules, &default);
+        let warnings = navigation.check_duplicates(&mut links);
+
+        assert_eq!(
+            warnings,
+            vec![Warning::new(
+                Path::new("one.md"),
+                "Listed 2 times in the navigation, at navigation[0], navigation[2]. \
+                 Only the first one is shown."
+            )]
+        );
+        assert_eq!(
+            links.iter().map(|l| l.path.as_str()).collect::<Vec<_>>(),
+            vec!["/one", "/two"]
+        );
+    }
+
+    #[test]
+    fn warns_about_a_wildcard_overlapping_an_explicit_entry() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            page("child/README.md", "Nested Root", None),
+            page("child/three.md", "Three", None),
+            page("child/four.md", "Four", None),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let rules = vec![
+            NavRule::Dir(PathBuf::from("child"), Some(DirIncludeRule::WildCard), None),
+            NavRule::File(PathBuf::from("child").join("three.md"), None),
+        ];
+
+        let config = config(None);
+        let navigation = Navigation::new(&config);
+        let default = navigation.links(&docs, true);
+        let mut links = navigation.customize(&rules, &default);
+        let warnings = navigation.check_duplicates(&mut links);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("child/three.md"));
+        assert!(warnings[0]
+            .message
+            .contains("at navigation[0].children[1], navigation[1]"));
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            links[0]
+                .children
+                .iter()
+                .map(|l| l.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/child/four", "/child/three"]
+        );
+    }
+
+    #[test]
+    fn keeps_duplicates_when_deduping_is_off() {
+        let docs = vec![
+            page("README.md", "Getting Started", None),
+            page("one.md", "One", None),
+        ];
+
+        let rules = vec![
+            NavRule::File(PathBuf::from("one.md"), None),
+            NavRule::File(PathBuf::from("one.md"), None),
+        ];
+
+        let config = config(Some("---\ntitle: My project\ndedupe_navigation: false\n"));
+        let navigation = Navigation::new(&config);
+        let default = navigation.links(&docs, true);
+        let mut links = navigation.customize(&rules, &default);
+        let warnings = navigation.check_duplicates(&mut links);
+
+        assert_eq!(warnings.len(), 1);
+        assert!(!warnings[0].message.contains("Only the first one is shown"));
+        assert_eq!(links.len(), 2);
+    }
+
+    #[test]
+    fn links_to_sections_of_pages() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", Some("/docs/")),
+            Document::new(
+                Path::new("api.md"),
+                "# API\n\n## Rate limits\n\n## Errors {#error-codes}".to_string(),
+                None,
+                "/docs/",
+                SystemTime::now(),
+            ),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let yaml = indoc! {"
+            ---
+            title: My project
+            base_path: /docs/
+            navigation:
+              - path: api.md
+              - path: api.md#rate-limits
+                title: Rate limits
+              - path: api.md#timeouts
+        "};
+        let config = Config::from_yaml_str(Path::new("project"), yaml, true).unwrap();
+        let navigation = Navigation::new(&config);
+        let default = navigation.links(&docs, true);
+        let links = navigation.customize(config.navigation().unwrap(), &default);
+
+        assert_eq!(
+            links
+                .iter()
+                .map(|l| (l.path.as_str(), l.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/docs/api", "API"),
+                ("/docs/api#rate-limits", "Rate limits"),
+                ("/docs/api#timeouts", "API"),
+            ]
+        );
+
+        let warnings = navigation.anchor_warnings(&docs);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("api.md"));
+        assert_eq!(
+            warnings[0].message,
+            "The navigation links to #timeouts, which is not a heading of the page. \
+             Its anchors are #api, #rate-limits, #error-codes"
+        );
+
+        let error = navigation.check_anchors(&docs).unwrap_err().to_string();
+        assert!(error.starts_with("Could not find 1 section(s) linked from the navigation"));
+        assert!(error.contains("api.md: The navigation links to #timeouts"));
+    }
+
+    fn round_trip(navigation: &Navigation, tree: &[Link], default: &[Link]) -> Vec<Link> {
+        let mut output = BTreeMap::new();
+        output.insert("navigation", navigation.to_config(tree, default));
+        let yaml = serde_yaml::to_string(&output).unwrap();
+
+        let yaml = format!("{}\ntitle: My project\n", yaml.trim_end());
+        let config = Config::from_yaml_str(&Path::new("project"), &yaml, true).unwrap();
+
+        navigation.customize(config.navigation().unwrap(), default)
+    }
+
+    #[test]
+    fn default_navigation_round_trips_through_config() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            page("one.md", "One", None),
+            page("child/README.md", "Nested Root", None),
+            page("child/three.md", "Three", None),
+            page("child/nested/README.md", "Nested Root", None),
+            page("child/nested/four.md", "Four", None),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let config = config(None);
+        let navigation = Navigation::new(&config);
+        let default = navigation.links(&docs, false);
+
+        let output = serde_yaml::to_string(&navigation.to_config(&default, &default)).unwrap();
+        assert!(
+            output.contains("- path: child\n  children: \"*\""),
+            "{}",
+            output
+        );
+
+        assert_eq!(round_trip(&navigation, &default, &default), default);
+    }
+
+    #[test]
+    fn custom_navigation_round_trips_through_config() {
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            page("one.md", "One", None),
+            page("two.md", "Two", None),
+            page("child/README.md", "Nested Root", None),
+            page("child/three.md", "Three", None),
+            page("child/nested/README.md", "Nested Root", None),
+            page("child/nested/four.md", "Four", None),
+            page("child/nested/five.md", "Five", None),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let rules = vec![
+            NavRule::File(PathBuf::from("two.md"), Some(String::from("Second"))),
+            NavRule::Dir(
+                PathBuf::from("child"),
+                Some(DirIncludeRule::Explicit(
+                    vec![
+                        NavRule::File(PathBuf::from("one.md"), None),
+                        NavRule::Dir(
+                            PathBuf::from("child").join("nested"),
+                            Some(DirIncludeRule::Explicit(
+                                vec![NavRule::File(
+                                    PathBuf::from("child").join("nested").join("five.md"),
+                                    None,
+                                )],
+                                NavSort::Manual,
+                            )),
+                            Some(String::from("Deeper")),
+                        ),
+                    ],
+                    NavSort::Manual,
+                )),
+                None,
+            ),
+        ];
+
+        let config = config(None);
+        let navigation = Navigation::new(&config);
+        let default = navigation.links(&docs, false);
+        let tree = navigation.customize(&rules, &default);
+
+        assert_eq!(round_trip(&navigation, &tree, &default), tree);
+    }
+
     #[test]
     fn build_with_base_path() {
         let config = config(Some(indoc! {"
@@ -524,4 +1229,215 @@ mod test {
             assert_debug_snapshot!(result);
         });
     }
+
+    #[test]
+    fn build_without_documents() {
+        let config = config(Some(indoc! {"
+        ---
+        title: Nothing here yet
+        base_path: /example/
+        "}));
+
+        let navigation = Navigation::new(&config);
+
+        assert!(navigation.links(&[], true).is_empty());
+        assert!(navigation.build_for(&[]).is_empty());
+    }
+
+    #[test]
+    fn sorting_by_index() {
+        let config = config(None);
+        let mut docs = vec![
+            page("README.md", "Getting Started", None),
+            page("alpha.md", "Alpha", None),
+            indexed_page("zulu.md", "Zulu", 1),
+            indexed_page("guides/README.md", "Guides", 1),
+            indexed_page("reference/README.md", "Reference", 2),
+            page("tutorials/README.md", "Tutorials", None),
+            page("guides/10-deploying.md", "Deploying", None),
+            page("guides/2-installing.md", "Installing", None),
+            indexed_page("guides/upgrading.md", "Upgrading", 1),
+            indexed_page("guides/configuring.md", "Configuring", 1),
+        ];
+        docs.par_sort_by(document_sort);
+
+        insta::with_settings!({
+            description => "Sort by index, with colliding indexes",
+            omit_expression => true // do not include the default expression
+        }, {
+            let navigation = Navigation::new(&config);
+            let result = navigation.build_for(&docs);
+            assert_debug_snapshot!(result);
+        });
+    }
+
+    #[test]
+    fn sorting_by_index_keeps_the_document_order_of_ties() {
+        let config = config(None);
+        let docs = vec![
+            indexed_page("b.md", "B", 3),
+            indexed_page("dir/README.md", "Dir", 3),
+            indexed_page("a.md", "A", 3),
+            page("c.md", "C", None),
+        ];
+        let mut reversed = docs.clone();
+        reversed.reverse();
+
+        let navigation = Navigation::new(&config);
+        let titles = |docs: &[Document]| {
+            let mut docs = docs.to_vec();
+            docs.par_sort_by(document_sort);
+            navigation
+                .build_for(&docs)
+                .into_iter()
+                .map(|link| link.title)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(titles(&docs), vec!["A", "B", "Dir", "C"]);
+        assert_eq!(titles(&reversed), titles(&docs));
+    }
+
+    /// Loads and renders the files written to a project like a build does,
+    /// returning the pages and the warnings about them
+    fn load_docs(name: &str, files: &[(&str, &str)]) -> (Vec<Document>, Vec<Warning>) {
+        let root = test_helpers::project(&format!("nav-{}", name), files);
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let sources = crate::docs_finder::load(&config);
+        let docs = crate::docs_finder::render(&sources, &config);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let mut warnings = sources.skipped;
+        warnings.extend(docs.iter().flat_map(|doc| doc.warnings().iter().cloned()));
+
+        (docs, warnings)
+    }
+
+    #[test]
+    fn sorting_by_a_partial_order_file() {
+        let (docs, warnings) = load_docs(
+            "partial",
+            &[
+                ("docs/README.md", "# Home"),
+                ("docs/.order", "usage\nguides\n"),
+                ("docs/alpha.md", "# Alpha"),
+                ("docs/usage.md", "# Usage"),
+                ("docs/10-later.md", "# Later"),
+                ("docs/2-sooner.md", "# Sooner"),
+                ("docs/guides/README.md", "# Guides"),
+                ("docs/guides/.order", "upgrading.md\n"),
+                ("docs/guides/configuring.md", "# Configuring"),
+                ("docs/guides/upgrading.md", "# Upgrading"),
+            ],
+        );
+        assert_eq!(warnings, vec![]);
+
+        insta::with_settings!({
+            description => "Listed entries first, then the others alphanumerically",
+            omit_expression => true
+        }, {
+            let config = config(None);
+            let navigation = Navigation::new(&config);
+            assert_debug_snapshot!(navigation.build_for(&docs));
+        });
+    }
+
+    #[test]
+    fn sorting_by_an_order_file_with_missing_entries() {
+        let (docs, warnings) = load_docs(
+            "missing",
+            &[
+                ("docs/README.md", "# Home"),
+                (
+                    "docs/.order",
+                    "removed.md\nsetup\nold-guides/\ninstall.md\n",
+                ),
+                ("docs/install.md", "# Install"),
+                ("docs/setup.md", "# Setup"),
+            ],
+        );
+        assert_eq!(
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+            vec![
+                ".order : Lists `removed.md`, which is not in the docs directory. \
+                 Remove it, or fix its name",
+                ".order : Lists `old-guides`, which is not in the docs directory. \
+                 Remove it, or fix its name",
+            ]
+        );
+
+        insta::with_settings!({
+            description => "Entries that don't exist are skipped",
+            omit_expression => true
+        }, {
+            let config = config(None);
+            let navigation = Navigation::new(&config);
+            assert_debug_snapshot!(navigation.build_for(&docs));
+        });
+    }
+
+    #[test]
+    fn order_files_take_precedence_over_indexes() {
+        let (docs, warnings) = load_docs(
+            "precedence",
+            &[
+                ("docs/README.md", "# Home"),
+                ("docs/first.md", "---\nindex: 1\n---\n# First"),
+                ("docs/unlisted.md", "---\nindex: 0\n---\n# Unlisted"),
+                ("docs/guides/README.md", "---\nindex: 2\n---\n# Guides"),
+                ("docs/guides/a.md", "---\nindex: 1\n---\n# A"),
+                ("docs/guides/b.md", "---\nindex: 2\n---\n# B"),
+                ("docs/.order", "guides\nfirst.md\n"),
+            ],
+        );
+        assert_eq!(
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+            vec![
+                "first.md : Has an `index`, which is ignored since .order sets the order of \
+                 the docs directory. An order file takes precedence over `index` values",
+                "unlisted.md : Has an `index`, which is ignored since .order sets the order of \
+                 the docs directory. An order file takes precedence over `index` values",
+                "guides/README.md : Has an `index`, which is ignored since .order sets the \
+                 order of the docs directory. An order file takes precedence over `index` values",
+            ]
+        );
+
+        insta::with_settings!({
+            description => "The order file wins, indexes still order guides/",
+            omit_expression => true
+        }, {
+            let config = config(None);
+            let navigation = Navigation::new(&config);
+            assert_debug_snapshot!(navigation.build_for(&docs));
+        });
+    }
+
+    #[test]
+    fn warns_about_siblings_with_the_same_index() {
+        let config = config(None);
+        let mut docs = vec![
+            indexed_page("README.md", "Home", 1),
+            indexed_page("guides/upgrading.md", "Upgrading", 2),
+            indexed_page("guides/configuring.md", "Configuring", 2),
+            indexed_page("guides/README.md", "Guides", 2),
+            indexed_page("install.md", "Install", 1),
+            page("usage.md", "Usage", None),
+            page("faq.md", "FAQ", None),
+        ];
+        docs.par_sort_by(document_sort);
+
+        let navigation = Navigation::new(&config);
+        let (links, warnings) = navigation.build_with_warnings(&docs);
+
+        assert_eq!(
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+            vec![
+                "guides/configuring.md : Has `index: 2` like guides/upgrading.md in guides/, so \
+                 they are ordered by file name. Give them different indexes, or use `index: \
+                 auto` to list a page after the ones with an index."
+            ]
+        );
+        assert_eq!(navigation.build_with_warnings(&docs), (links, warnings));
+    }
 }
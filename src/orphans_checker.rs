@@ -0,0 +1,158 @@
+use crate::markdown::extensions::link_rewriter::UrlType;
+use crate::navigation::Link;
+use crate::preview_server::resolve_file;
+use crate::site::{Site, SiteBackend};
+use crate::warning::Warning;
+use crate::Document;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Finds documents that are not reachable from the navigation or from any
+/// other document's links. The root README and pages marked with
+/// `unlisted: true` are never reported.
+pub fn check<B: SiteBackend>(
+    root: &[Document],
+    navigation: &[Link],
+    site: &Site<B>,
+) -> Vec<Warning> {
+    orphans(root, navigation, &site.backend)
+        .into_iter()
+        .map(|doc| {
+            Warning::new(
+                doc.original_path(),
+                "Page is not linked from the navigation or any other page",
+            )
+        })
+        .collect()
+}
+
+/// The documents `check` warns about, against the files written to the
+/// backend so far, for while the site is still being built
+pub fn orphans<'d, B: SiteBackend>(
+    root: &'d [Document],
+    navigation: &[Link],
+    backend: &B,
+) -> Vec<&'d Document> {
+    let mut referenced: HashSet<PathBuf> = HashSet::new();
+
+    collect_navigation(navigation, backend, &mut referenced);
+
+    for doc in root {
+        for link in doc.outgoing_links() {
+            if let UrlType::Local(path) = &link.url {
+                if let Some(target) = resolve_file(path, backend) {
+                    // Links to the page itself don't make it reachable
+                    if target != doc.html_path {
+                        referenced.insert(target);
+                    }
+                }
+            }
+        }
+    }
+
+    root.iter()
+        .filter(|doc| doc.uri_path != backend.config().base_path())
+        .filter(|doc| !doc.unlisted())
+        .filter(|doc| !referenced.contains(&doc.html_path))
+        .collect()
+}
+
+fn collect_navigation<B: SiteBackend>(
+    links: &[Link],
+    backend: &B,
+    referenced: &mut HashSet<PathBuf>,
+) {
+    for link in links {
+        // Links to a section of a page make the page reachable too
+        let path = link.path.split('#').next().unwrap_or_default();
+        if let Some(target) = resolve_file(Path::new(path), backend) {
+            referenced.insert(target);
+        }
+
+        collect_navigation(&link.children, backend, referenced);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::docs_finder::document_sort;
+    use crate::navigation::Navigation;
+    use crate::test_helpers::page;
+    use crate::Document;
+
+    fn config(yaml: &str) -> Config {
+        Config::from_yaml_str(&Path::new("project"), yaml, true).unwrap()
+    }
+
+    fn orphans(config: Config, root: &[Document]) -> Vec<Warning> {
+        let mut root = root.to_vec();
+        root.sort_by(document_sort);
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), &root).unwrap();
+
+        let navigation = Navigation::new(&config).build_for(&root);
+        check(&root, &navigation, &site)
+    }
+
+    // Only listed.md is in the navigation
+    static CUSTOM_NAV: &str = "---\ntitle: My project\nnavigation:\n  - path: listed.md\n";
+
+    #[test]
+    fn reports_pages_without_inbound_references() {
+        let root = vec![
+            page("README.md", "[linked](/linked)", &[]),
+            page("linked.md", "Linked", &[]),
+            page("listed.md", "Listed", &[]),
+            page("orphan.md", "Orphan", &[]),
+        ];
+
+        let warnings = orphans(config(CUSTOM_NAV), &root);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("orphan.md"));
+    }
+
+    #[test]
+    fn pages_in_the_navigation_are_not_orphans() {
+        let root = vec![
+            page("README.md", "No links", &[]),
+            page("nested/README.md", "Nested", &[]),
+            page("nested/other.md", "Other", &[]),
+        ];
+
+        let warnings = orphans(config("---\ntitle: My project\n"), &root);
+
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn links_to_self_do_not_count() {
+        let root = vec![
+            page("README.md", "No links", &[]),
+            page("listed.md", "Listed", &[]),
+            page("orphan.md", "[me](/orphan)", &[]),
+        ];
+
+        let warnings = orphans(config(CUSTOM_NAV), &root);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("orphan.md"));
+    }
+
+    #[test]
+    fn ignores_unlisted_pages() {
+        let root = vec![
+            page("README.md", "No links", &[]),
+            page("listed.md", "Listed", &[]),
+            page("hidden.md", "Hidden", &[("unlisted", "true")]),
+        ];
+
+        let warnings = orphans(config(CUSTOM_NAV), &root);
+
+        assert_eq!(warnings, vec![]);
+    }
+}
@@ -1,7 +1,13 @@
+use std::sync::Arc;
+
 use crate::config::Footer;
-use crate::markdown::extensions::toc::Heading;
+use crate::link_graph::RelatedPage;
+use crate::markdown::extensions::toc::HeadingNode;
 use crate::navigation::Link;
 use crate::site::BuildMode;
+use crate::site_generator::{ChildPage, PageMeta, GENERATOR};
+use crate::site_report::{ReportPage, SiteReport};
+use crate::Layout;
 
 static LIGHT_MODE_SVG_DATA: &str = "M10 2a1 1 0 011 1v1a1 1 0 11-2 0V3a1 1 0 011-1zm4 8a4 4 0 11-8 0 4 4 0 018 0zm-.464 4.95l.707.707a1 1 0 001.414-1.414l-.707-.707a1 1 0 00-1.414 1.414zm2.12-10.607a1 1 0 010 1.414l-.706.707a1 1 0 11-1.414-1.414l.707-.707a1 1 0 011.414 0zM17 11a1 1 0 100-2h-1a1 1 0 100 2h1zm-7 4a1 1 0 011 1v1a1 1 0 11-2 0v-1a1 1 0 011-1zM5.05 6.464A1 1 0 106.465 5.05l-.708-.707a1 1 0 00-1.414 1.414l.707.707zm1.414 8.486l-.707.707a1 1 0 01-1.414-1.414l.707-.707a1 1 0 011.414 1.414zM4 11a1 1 0 100-2H3a1 1 0 000 2h1z";
 
@@ -11,16 +17,28 @@ static DARK_MODE_SVG_DATA: &str =
 markup::define! {
     Page<'a>(
     content: &'a String,
-    headings: &'a Vec<Heading>,
-    navigation: &'a String,
+    banner: Option<&'a str>,
+    related_pages: &'a Vec<RelatedPage>,
+    prev: Option<RelatedPage>,
+    next: Option<RelatedPage>,
+    headings: &'a [HeadingNode],
+    layout: Layout,
+    body_class: &'a str,
+    navigation: Arc<str>,
     custom_head: Option<&'a str>,
+    analytics: Option<&'a str>,
     page_title: &'a str,
     page_description: &'a str,
+    lang: &'a str,
     page_subtitle: Option<String>,
+    noindex: bool,
+    generator: &'a str,
+    relative_root: Option<&'a str>,
     build_mode: BuildMode,
-    init_script: &'a String,
-    dev_script: &'a String,
-    header: &'a String,
+    init_script: Arc<str>,
+    dev_script: &'a str,
+    source_path: Option<&'a str>,
+    header: Arc<str>,
     footer: &'a Option<Footer>,
     head_links: String,
     foot_links: String,
@@ -28,7 +46,7 @@ markup::define! {
     livereload_script_path: Option<&'a str>,
     livereload_port: Option<&'a str>) {
         @markup::doctype()
-        html[lang="en"] {
+        html[lang=lang, {"data-root"}=relative_root] {
             head {
                 meta[charset="utf-8"];
 
@@ -43,55 +61,118 @@ markup::define! {
 
                 meta[name="viewport",content="width=device-width, initial-scale=1"];
 
+                meta[name="generator",content=generator];
+
+                @if *noindex {
+                    meta[name="robots",content="noindex"];
+                }
+
                 @markup::raw(head_links)
 
                 script {
                     {markup::raw(init_script)}
                 }
 
+                @if let Some(analytics) = analytics {
+                    @markup::raw(analytics)
+                }
+
                 @if let Some(custom_head) = custom_head {
                     @markup::raw(custom_head)
                 }
             }
 
-            body.preload {
-                label[for="menu-toggle-switch", class="menu-toggle-button"] {
-                    "☰"
+            body[class=body_class] {
+                @if *layout != Layout::Full {
+                    label[for="menu-toggle-switch", class="menu-toggle-button"] {
+                        "☰"
+                    }
+                    input[type="checkbox", id="menu-toggle-switch", value='0'];
                 }
-                input[type="checkbox", id="menu-toggle-switch", value='0'];
 
                 .page {
                     @markup::raw(header)
 
-                    div[class="container"] {
-                        div[class="sidebar-left"] {
-                            @markup::raw(navigation)
+                    div[class=layout.container_class()] {
+                        @if *layout != Layout::Full {
+                            div[class="sidebar-left"] {
+                                @markup::raw(navigation)
+                            }
                         }
 
                         div[class="docgen-content"] {
-                            @markup::raw(content)
-                        }
-
-                        div[class="sidebar-right"] {
-                            @if let Some(edit_link) = edit_link {
-                                a[class="edit-link", href=edit_link] {
-                                    {"Edit this page"}
+                            @if let Some(banner) = banner {
+                                p[class="preview-banner"] {
+                                    {banner}
                                 }
                             }
 
-                            div[class="page-nav", id="page-nav"] {
-                                p[class="page-nav-header"] {
-                                    {"On this page"}
+                            @markup::raw(content)
+
+                            @if !related_pages.is_empty() {
+                                div[class="related-pages"] {
+                                    p[class="related-pages-header"] {
+                                        {"Related pages"}
+                                    }
+
+                                    ul {
+                                        @for page in related_pages.iter() {
+                                            li {
+                                                a[href=&page.uri] {
+                                                    {&page.title}
+                                                }
+                                            }
+                                        }
+                                    }
                                 }
+                            }
 
-                                ul {
-                                    @for heading in headings.iter() {
-                                        li[class=format!("page-nav-level-{}", heading.level)] {
-                                            a[href=format!("#{}", heading.anchor)] {
-                                                {&heading.title}
+                            @if prev.is_some() || next.is_some() {
+                                nav[class="page-neighbours"] {
+                                    @if let Some(prev) = prev {
+                                        a[class="prev-page", href=&prev.uri] {
+                                            span[class="page-neighbours-label"] {
+                                                {"Previous"}
                                             }
+                                            {&prev.title}
                                         }
                                     }
+                                    @if let Some(next) = next {
+                                        a[class="next-page", href=&next.uri] {
+                                            span[class="page-neighbours-label"] {
+                                                {"Next"}
+                                            }
+                                            {&next.title}
+                                        }
+                                    }
+                                }
+                            }
+
+                            // Without the right sidebar, the edit link goes
+                            // below the content
+                            @if *layout != Layout::Default {
+                                @if let Some(edit_link) = edit_link {
+                                    a[class="edit-link", href=edit_link] {
+                                        {"Edit this page"}
+                                    }
+                                }
+                            }
+                        }
+
+                        @if *layout == Layout::Default {
+                            div[class="sidebar-right"] {
+                                @if let Some(edit_link) = edit_link {
+                                    a[class="edit-link", href=edit_link] {
+                                        {"Edit this page"}
+                                    }
+                                }
+
+                                div[class="page-nav", id="page-nav"] {
+                                    p[class="page-nav-header"] {
+                                        {"On this page"}
+                                    }
+
+                                    @PageNav { headings }
                                 }
                             }
                         }
@@ -108,11 +189,19 @@ markup::define! {
                                             {&group.title}
                                         }
 
-                                        ul {
-                                            @for link in &group.links {
-                                                li {
-                                                    a[href=&link.href, target=if link.external.unwrap_or(false) { "_blank" } else { "_self"} ] {
-                                                        {&link.title}
+                                        @if let Some(text) = &group.text {
+                                            p.text {
+                                                @markup::raw(text)
+                                            }
+                                        }
+
+                                        @if !group.links.is_empty() {
+                                            ul {
+                                                @for link in &group.links {
+                                                    li {
+                                                        a[href=&link.href, target=if link.external.unwrap_or(false) { "_blank" } else { "_self"} ] {
+                                                            @markup::raw(&link.title)
+                                                        }
                                                     }
                                                 }
                                             }
@@ -124,7 +213,7 @@ markup::define! {
 
                         @if let Some(copyright) = &footer.copyright {
                             div.copyright {
-                                {copyright}
+                                @markup::raw(copyright)
                             }
                         }
                     }
@@ -136,7 +225,7 @@ markup::define! {
                     script[id="livereloadjs", type="text/javascript", async="true", defer="true", src=livereload_script_path, {"data-port"}=livereload_port] {
                     }
 
-                    script {
+                    script[{"data-source"}=source_path] {
                         {markup::raw(dev_script)}
                     }
                 }
@@ -145,12 +234,12 @@ markup::define! {
     }
 
 
-    PageHeader<'a>(logo: Option<&'a str>, base_path: &'a str, project_title: &'a str, project_subtitle: &'a str) {
+    PageHeader<'a>(logo: Option<&'a str>, base_path: &'a str, search_action: &'a str, project_title: &'a str, project_subtitle: &'a str, search_max_results: usize, search_engine: &'a str) {
         .header {
             .logo {
                 @if let Some(logo) = logo {
                     a[href=base_path] {
-                        img[src=format!("{}{}", base_path, logo), alt=format!("{} logo", project_title)];
+                        img[src=logo, alt=format!("{} logo", project_title)];
                     }
                 }
 
@@ -165,8 +254,8 @@ markup::define! {
             }
 
             .search {
-                form[id="search-form"] {
-                    input[type="text", id="search-box", autocomplete="off", placeholder="Search..."];
+                form[id="search-form", action=search_action, method="get", {"data-max-results"}=search_max_results, {"data-search-engine"}=search_engine] {
+                    input[type="text", id="search-box", name="q", autocomplete="off", placeholder="Search..."];
                     span[class="search-icon"] {
                         "S"
                     }
@@ -178,7 +267,173 @@ markup::define! {
         }
     }
 
-    SideNavigation<'a>(navigation: &'a [Link]) {
+    SearchPage() {
+        h1 { "Search" }
+        p[id="search-page-summary"] {}
+        ul[id="search-page-results"] {}
+        noscript {
+            p[class="search-page-no-js"] {
+                "Searching needs JavaScript. Without it, find pages in the navigation instead."
+            }
+        }
+    }
+
+    SiteReportContent<'a>(report: &'a SiteReport) {
+        h1 { "Site report" }
+        p[class="site-report-date"] {
+            "Of the build on "
+            time[datetime=&report.date] {
+                {&report.date}
+            }
+            ", by " {GENERATOR}
+        }
+
+        table[class="site-report-summary"] {
+            tbody {
+                tr { th { "Pages" } td { {report.pages} } }
+                tr { th { "Words" } td { {report.words} } }
+                tr { th { "Stale pages" } td { {report.stale.len()} } }
+                tr { th { "Orphan pages" } td { {report.orphans.len()} } }
+                tr { th { "Broken links" } td { {report.broken_links.len()} } }
+                @if let Some(page) = &report.oldest {
+                    tr {
+                        th { "Oldest page" }
+                        td {
+                            a[href=&page.uri] {
+                                {&page.title}
+                            }
+                            " (" {&page.path} ", changed on " {&page.changed} ")"
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { "Sections" }
+        table[class="site-report-sections"] {
+            thead {
+                tr { th { "Section" } th { "Pages" } th { "Words" } th { "Stale pages" } }
+            }
+            tbody {
+                @for section in report.sections.iter() {
+                    tr {
+                        td { {&section.name} }
+                        td { {section.pages} }
+                        td { {section.words} }
+                        td { {section.stale} }
+                    }
+                }
+            }
+        }
+
+        h2 { "Stale pages" }
+        @if report.stale.is_empty() {
+            p { "Every page was changed in the last six months." }
+        } else {
+            p { "Pages that weren't changed in six months or more, the oldest first." }
+            @ReportPages { pages: &report.stale }
+        }
+
+        h2 { "Words per page" }
+        table[class="site-report-words"] {
+            thead {
+                tr { th { "Words" } th { "Pages" } }
+            }
+            tbody {
+                @for (range, pages) in report.word_ranges.iter() {
+                    tr {
+                        td { {range} }
+                        td { {pages} }
+                    }
+                }
+            }
+        }
+        p { "The shortest pages:" }
+        @ReportPages { pages: &report.shortest }
+
+        h2 { "Orphan pages" }
+        @if report.orphans.is_empty() {
+            p { "Every page is linked from the navigation or another page." }
+        } else {
+            @ReportPages { pages: &report.orphans }
+        }
+
+        h2 { "Broken links" }
+        @if report.broken_links.is_empty() {
+            p { "No page links to a page or file that is not in the site." }
+        } else {
+            table[class="site-report-broken-links"] {
+                thead {
+                    tr { th { "File" } th { "Links to" } }
+                }
+                tbody {
+                    @for (page, target) in report.broken_links.iter() {
+                        tr {
+                            td { {page} }
+                            td { code { {target} } }
+                        }
+                    }
+                }
+            }
+        }
+        p { "By day, the latest first:" }
+        table[class="site-report-history"] {
+            thead {
+                tr { th { "Day" } th { "Broken links" } }
+            }
+            tbody {
+                @for (date, count) in report.broken_link_history.iter() {
+                    tr {
+                        td { {date} }
+                        td { {count} }
+                    }
+                }
+            }
+        }
+
+        h2 { "Warnings" }
+        @if report.warnings.is_empty() {
+            p { "The build has no warnings." }
+        } else {
+            table[class="site-report-warnings"] {
+                thead {
+                    tr { th { "File" } th { "Warnings" } }
+                }
+                tbody {
+                    @for (path, count) in report.warnings.iter() {
+                        tr {
+                            td { {path} }
+                            td { {count} }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ReportPages<'a>(pages: &'a [ReportPage]) {
+        table[class="site-report-pages"] {
+            thead {
+                tr { th { "Page" } th { "File" } th { "Words" } th { "Last changed" } }
+            }
+            tbody {
+                @for page in pages.iter() {
+                    tr {
+                        td {
+                            a[href=&page.uri] {
+                                {&page.title}
+                            }
+                        }
+                        td { {&page.path} }
+                        td { {page.words} }
+                        td { {&page.changed} }
+                    }
+                }
+            }
+        }
+    }
+
+    SideNavigation<'a>(navigation: &'a [Link], footer: Option<&'a str>) {
         nav[class="site-nav"] {
             ul.tree {
                 @for link in navigation.iter() {
@@ -198,6 +453,12 @@ markup::define! {
                 path[{"fill-rule"}="evenodd", d=LIGHT_MODE_SVG_DATA, {"clip-rule"}="evenodd"]{}
             }
         }
+
+        @if let Some(footer) = footer {
+            div[class="sidebar-footer"] {
+                @markup::raw(footer)
+            }
+        }
     }
 
 
@@ -227,4 +488,117 @@ markup::define! {
                 }
             }
     }
+
+    PageNav<'a>(headings: &'a [HeadingNode]) {
+        ul {
+            @for node in headings.iter() {
+                @PageNavItem { node }
+            }
+        }
+    }
+
+    PageNavItem<'a>(node: &'a HeadingNode) {
+        li[class=format!("page-nav-level-{}", node.heading.level)] {
+            a[href=format!("#{}", node.heading.anchor)] {
+                {&node.heading.title}
+            }
+
+            @if !node.children.is_empty() {
+                @PageNav { headings: &node.children }
+            }
+        }
+    }
+
+    PageMetaLine<'a>(meta: &'a PageMeta) {
+        p[class="page-meta"] {
+            @if !meta.authors.is_empty() {
+                span[class="page-authors"] {
+                    "By "
+                    {&meta.authors}
+                }
+            }
+
+            @if let Some((datetime, date)) = &meta.reviewed {
+                span[class="page-reviewed"] {
+                    "Last reviewed "
+                    time[datetime=datetime] {
+                        {date}
+                    }
+                }
+            }
+        }
+    }
+
+    ChildrenList<'a>(pages: &'a [ChildPage]) {
+        @if !pages.is_empty() {
+            div[class="children-list"] {
+                @for page in pages.iter() {
+                    @if page.children.is_empty() {
+                        @ChildCard { page: page }
+                    } else {
+                        div[class="children-group"] {
+                            h3 {
+                                a[href=&page.uri] {
+                                    {&page.title}
+                                }
+                            }
+
+                            @if !page.description.is_empty() {
+                                p[class="child-description"] {
+                                    {&page.description}
+                                }
+                            }
+
+                            @for child in page.children.iter() {
+                                @ChildCard { page: child }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ChildCard<'a>(page: &'a ChildPage) {
+        a[class="child-card", href=&page.uri] {
+            span[class="child-title"] {
+                {&page.title}
+            }
+
+            @if !page.description.is_empty() {
+                span[class="child-description"] {
+                    {&page.description}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::markdown::extensions::toc::Heading;
+
+    fn heading(level: u32, title: &str, anchor: &str) -> Heading {
+        Heading {
+            title: title.to_string(),
+            anchor: anchor.to_string(),
+            level,
+            number: None,
+        }
+    }
+
+    #[test]
+    fn page_nav_nests_sections() {
+        let headings = vec![
+            heading(3, "Before", "before"),
+            heading(2, "Installing", "installing"),
+            heading(3, "On Linux", "on-linux"),
+            heading(2, "Configuring", "configuring"),
+            heading(4, "Skipped a level", "skipped-a-level"),
+        ];
+        let tree = Heading::build_tree(&headings);
+
+        insta::assert_snapshot!(PageNav { headings: &tree }.to_string());
+    }
 }
@@ -0,0 +1,416 @@
+//! Hooks that rewrite the HTML of every page after it has been rendered,
+//! before it is written to the site.
+//!
+//! Docgen comes with a few built-in processors that can be turned on in
+//! docgen.yaml. Programs using Docgen as a library can register their own
+//! with `BuildCommand::run_with_post_processors`.
+
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::config::{BuiltinProcessor, Config};
+use crate::Document;
+
+/// Rewrites the rendered HTML of a page. Pages are rendered in parallel, so
+/// processors are shared between threads.
+pub trait HtmlPostProcessor: Send + Sync {
+    fn process(&self, doc: &Document, html: String) -> String;
+}
+
+impl fmt::Debug for dyn HtmlPostProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HtmlPostProcessor")
+    }
+}
+
+/// The built-in processors turned on in the config, in the order they run
+pub fn builtin(config: &Config) -> Vec<Arc<dyn HtmlPostProcessor>> {
+    config
+        .post_processors()
+        .iter()
+        .map(|processor| -> Arc<dyn HtmlPostProcessor> {
+            match processor {
+                BuiltinProcessor::Minify => Arc::new(Minify),
+            }
+        })
+        .chain(
+            config
+                .pretty_html()
+                .then(|| -> Arc<dyn HtmlPostProcessor> { Arc::new(PrettyHtml) }),
+        )
+        .collect()
+}
+
+lazy_static! {
+    static ref CLASS: Regex = Regex::new(r#"\sclass="([^"]*)""#).unwrap();
+
+    /// Elements whose whitespace is part of their content
+    static ref PRESERVED: Regex = Regex::new(
+        r"(?s)<pre\b.*?</pre>|<textarea\b.*?</textarea>|<script\b.*?</script>|<style\b.*?</style>"
+    )
+    .unwrap();
+    static ref LINE_BREAK_BETWEEN_TAGS: Regex = Regex::new(r">\s*\n\s*<").unwrap();
+
+    /// A comment, doctype, or opening or closing tag. Quoted attribute
+    /// values may contain `>`.
+    static ref TAG: Regex = Regex::new(
+        r#"(?s)<!--.*?-->|<![^>]*>|</?[a-zA-Z][^"'>]*(?:(?:"[^"]*"|'[^']*')[^"'>]*)*>"#
+    )
+    .unwrap();
+    static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+/// Elements that start on a line of their own
+const BLOCK_ELEMENTS: &[&str] = &[
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "meta",
+    "nav",
+    "noscript",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Drops the indentation between tags. Line breaks between tags are kept as
+/// a single one, since they can be significant between inline elements.
+pub struct Minify;
+
+impl HtmlPostProcessor for Minify {
+    fn process(&self, _doc: &Document, html: String) -> String {
+        let mut minified = String::with_capacity(html.len());
+        let mut position = 0;
+
+        for preserved in PRESERVED.find_iter(&html) {
+            minified.push_str(
+                &LINE_BREAK_BETWEEN_TAGS.replace_all(&html[position..preserved.start()], ">\n<"),
+            );
+            minified.push_str(preserved.as_str());
+            position = preserved.end();
+        }
+        minified.push_str(&LINE_BREAK_BETWEEN_TAGS.replace_all(&html[position..], ">\n<"));
+
+        minified
+    }
+}
+
+/// Indents the HTML so that every block element is on a line of its own,
+/// which keeps diffs between two builds readable. Tags are kept as they
+/// were written, and the whitespace inside `pre`, `code`, `textarea`,
+/// `script` and `style` elements and Mermaid and math blocks is left alone.
+/// Pretty-printing a page twice gives the same result.
+pub struct PrettyHtml;
+
+impl HtmlPostProcessor for PrettyHtml {
+    fn process(&self, _doc: &Document, html: String) -> String {
+        let mut printer = Printer::default();
+        let mut position = 0;
+
+        while let Some(tag) = TAG.find_at(&html, position) {
+            printer.text(&html[position..tag.start()]);
+            position = tag.end();
+
+            let raw = tag.as_str();
+            if raw.starts_with("<!") {
+                printer.line(raw);
+                continue;
+            }
+
+            let name = tag_name(raw);
+            if raw.starts_with("</") {
+                if BLOCK_ELEMENTS.contains(&name.as_str()) {
+                    printer.close(raw);
+                } else {
+                    printer.inline(raw);
+                }
+            } else if is_preformatted(&name, raw) {
+                position = closing_tag_end(&html, &name, position);
+                let element = &html[tag.start()..position];
+                if name == "code" || name == "textarea" {
+                    printer.inline(element);
+                } else {
+                    printer.line(element);
+                }
+            } else if BLOCK_ELEMENTS.contains(&name.as_str()) {
+                if VOID_ELEMENTS.contains(&name.as_str()) || raw.ends_with("/>") {
+                    printer.line(raw);
+                } else {
+                    printer.open(raw);
+                }
+            } else {
+                printer.inline(raw);
+            }
+        }
+        printer.text(&html[position..]);
+
+        printer.finish()
+    }
+}
+
+/// Collects inline content until a block element ends the line
+#[derive(Default)]
+struct Printer {
+    output: String,
+    line: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn text(&mut self, text: &str) {
+        self.line.push_str(&WHITESPACE.replace_all(text, " "));
+    }
+
+    fn inline(&mut self, html: &str) {
+        self.line.push_str(html);
+    }
+
+    /// Puts the HTML on a line of its own, at the current depth
+    fn line(&mut self, html: &str) {
+        self.flush();
+        self.push_line(html);
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.line(tag);
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.flush();
+        self.depth = self.depth.saturating_sub(1);
+        self.push_line(tag);
+    }
+
+    fn flush(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        let line = line.trim();
+        if !line.is_empty() {
+            self.push_line(line);
+        }
+    }
+
+    fn push_line(&mut self, html: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(html);
+        self.output.push('\n');
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        self.output
+    }
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches("</")
+        .trim_start_matches('<')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Whether the whitespace inside the element is part of its content
+fn is_preformatted(name: &str, tag: &str) -> bool {
+    match name {
+        "pre" | "code" | "textarea" | "script" | "style" => true,
+        "div" => CLASS
+            .captures(tag)
+            .map(|class| {
+                class[1]
+                    .split_whitespace()
+                    .any(|class| class == "mermaid" || class == "math")
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Where the element named `name` that was opened before `position` ends,
+/// skipping over elements of the same name nested in it
+fn closing_tag_end(html: &str, name: &str, position: usize) -> usize {
+    let mut depth = 1;
+    for tag in TAG.find_iter(&html[position..]) {
+        let raw = tag.as_str();
+        if raw.starts_with("<!") || tag_name(raw) != name {
+            continue;
+        }
+
+        if raw.starts_with("</") {
+            depth -= 1;
+            if depth == 0 {
+                return position + tag.end();
+            }
+        } else if !raw.ends_with("/>") {
+            depth += 1;
+        }
+    }
+
+    html.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::page;
+
+    #[test]
+    fn minify_keeps_preformatted_whitespace() {
+        let html = Minify.process(
+            &page("README.md", "# Home", &[]),
+            String::from(
+                "<ul>\n    <li>One</li>\n    <li>Two</li>\n</ul>\n<pre>\n  <b>indented</b>\n</pre>",
+            ),
+        );
+
+        assert_eq!(
+            html,
+            "<ul>\n<li>One</li>\n<li>Two</li>\n</ul>\n<pre>\n  <b>indented</b>\n</pre>"
+        );
+    }
+
+    #[test]
+    fn pretty_html_puts_block_elements_on_their_own_lines() {
+        let html = PrettyHtml.process(
+            &page("README.md", "# Home", &[]),
+            String::from(
+                "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"utf-8\">\
+                 <title>Home</title></head><body><div id=\"main\" class=\"a b\">\
+                 <h1 id=\"home\">Home</h1><p>Some   <em>text</em>,\n  and a\
+                 <a href=\"/x\" data-x=\"a>b\">link</a>.</p><ul><li>One</li><li>Two\
+                 <br>lines</li></ul><hr/></div></body></html>",
+            ),
+        );
+
+        assert_eq!(
+            html,
+            indoc! {r#"
+                <!DOCTYPE html>
+                <html lang="en">
+                  <head>
+                    <meta charset="utf-8">
+                    <title>
+                      Home
+                    </title>
+                  </head>
+                  <body>
+                    <div id="main" class="a b">
+                      <h1 id="home">
+                        Home
+                      </h1>
+                      <p>
+                        Some <em>text</em>, and a<a href="/x" data-x="a>b">link</a>.
+                      </p>
+                      <ul>
+                        <li>
+                          One
+                        </li>
+                        <li>
+                          Two<br>lines
+                        </li>
+                      </ul>
+                      <hr/>
+                    </div>
+                  </body>
+                </html>
+            "#}
+        );
+    }
+
+    #[test]
+    fn pretty_html_keeps_preformatted_whitespace() {
+        let code = "<pre class=\"code\"><code>fn main() {\n    <span>println!</span>(\"hi\");\n}\n</code></pre>";
+        let html = PrettyHtml.process(
+            &page("README.md", "# Home", &[]),
+            format!(
+                "<div><p>Run  <code>a   b</code>:</p>{}\
+                 <div class=\"mermaid\">\ngraph TD\n  A --> B\n<div>x</div>\n</div>\
+                 <textarea>  two\n  lines</textarea></div>",
+                code
+            ),
+        );
+
+        assert_eq!(
+            html,
+            format!(
+                "<div>\n  <p>\n    Run <code>a   b</code>:\n  </p>\n  {}\n  \
+                 <div class=\"mermaid\">\ngraph TD\n  A --> B\n<div>x</div>\n</div>\n  \
+                 <textarea>  two\n  lines</textarea>\n</div>\n",
+                code
+            )
+        );
+        assert!(html.contains(code));
+    }
+
+    #[test]
+    fn pretty_html_is_idempotent() {
+        let html = String::from(
+            "<!DOCTYPE html><html><head><script>if (a < b) {\n  go();\n}</script></head>\
+             <body><!-- comment --><nav><ul><li><a href=\"/\">Home</a></li></ul></nav>\
+             <p>Text with <code> spaced </code> code and <strong>bold\n text</strong></p>\
+             <pre><code>  indented\n\n    more\n</code></pre>\
+             <div class=\"math\">\n<span>x  =  1</span>\n</div></body></html>",
+        );
+
+        let doc = page("README.md", "# Home", &[]);
+        let once = PrettyHtml.process(&doc, html);
+        let twice = PrettyHtml.process(&doc, once.clone());
+
+        assert_eq!(once, twice);
+        assert!(once.contains("<pre><code>  indented\n\n    more\n</code></pre>"));
+        assert!(once.contains("<script>if (a < b) {\n  go();\n}</script>"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::bounded;
+
+use crate::config::Config;
+use crate::livereload_server::{LivereloadServer, Reload};
+use crate::preview_server::PreviewServer;
+use crate::site::Site;
+use crate::terminal::ColorMode;
+use crate::watcher::Watcher;
+use crate::{docs_finder, warning, Document, Error, Result};
+use crate::{ServeCommand, ServeOptions, SourceDocument};
+
+pub struct PreviewCommand {}
+
+impl PreviewCommand {
+    /// Serves a single Markdown file, without a project or docgen.yaml,
+    /// rebuilding it whenever it changes. Nothing is written to disk.
+    pub fn run(file: &Path, options: ServeOptions, color: ColorMode) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(color);
+
+        if !file.is_file() {
+            return Err(Error::new(format!(
+                "Could not find a file to preview at {}",
+                file.display()
+            )));
+        }
+        let file = file
+            .canonicalize()
+            .map_err(|e| Error::io(e, format!("Could not read {}", file.display())))?;
+
+        let mut config = Config::for_file(&file)?;
+        config.set_color_mode(color);
+        let port_notes = config.resolve_server_addrs(options.port)?;
+
+        crate::assets::check()?;
+        let mut root = Self::render(&file, &mut config)?;
+        let site = Arc::new(Mutex::new(Site::in_memory(config.clone())));
+
+        bunt::writeln!(stdout, "{$bold}{$blue}Docgen | Preview{/$}{/$}")?;
+        println!("Starting development server...\n");
+        ServeCommand::print_addrs(&mut stdout, &config, &port_notes)?;
+
+        let build_warnings = site.lock().unwrap().build(config.clone(), &root)?;
+        let mut warnings = docs_finder::warnings(&root, &config);
+        warnings.extend(build_warnings);
+        warning::print(&mut stdout, &warnings)?;
+
+        // Watcher ------------------------------------
+
+        let (watch_snd, watch_rcv) = bounded(128);
+        let watcher = Watcher::for_file(&file, watch_snd, color);
+        thread::Builder::new()
+            .name("watcher".into())
+            .spawn(move || watcher.run())
+            .unwrap();
+
+        // Live Reload --------------------------------
+
+        let (reload_send, reload_rcv) = bounded(128);
+        let livereload_server =
+            LivereloadServer::new(config.livereload_addr(), reload_rcv, config.color_mode());
+        thread::Builder::new()
+            .name("livereload".into())
+            .spawn(move || livereload_server.run())
+            .unwrap();
+
+        // Preview Server -----------------------------
+
+        // The page isn't the home page of the site, so the server points
+        // straight at it
+        let http_server = PreviewServer::new(
+            config.addr(),
+            Arc::clone(&site),
+            config.color_mode(),
+            root[0].uri_path.clone(),
+        );
+        thread::Builder::new()
+            .name("http-server".into())
+            .spawn(move || http_server.run())
+            .unwrap();
+
+        for (change, msg) in watch_rcv {
+            if let Some(path) = change.path() {
+                bunt::write!(stdout, "    File {$bold}{}{/$} {}", path.display(), msg)?;
+            }
+
+            let start = Instant::now();
+            match Self::render(&file, &mut config) {
+                Ok(new_root) => root = new_root,
+                Err(e) => {
+                    println!(" → could not be rendered, still showing the previous version.");
+                    println!("    {}\n", e);
+                    continue;
+                }
+            }
+
+            let build_warnings = match Site::rebuild(&site, config.clone(), &root) {
+                Ok(warnings) => warnings,
+                Err(e) => {
+                    println!(" → could not be built, still showing the previous version.");
+                    println!("    {}\n", e);
+                    reload_send
+                        .send(Reload::BuildFailed(e.to_string()))
+                        .unwrap();
+                    continue;
+                }
+            };
+            bunt::writeln!(
+                stdout,
+                "\n    Page rebuilt in {$bold}{:?}{/$}\n",
+                start.elapsed()
+            )?;
+
+            let mut warnings = docs_finder::warnings(&root, &config);
+            warnings.extend(build_warnings);
+            warning::print(&mut stdout, &warnings)?;
+
+            match ServeCommand::page_reload(&site) {
+                Some(reload) => reload_send.send(reload).unwrap(),
+                None => println!("    No output changed, the browser is not reloaded.\n"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads and renders the file, and names the site after the page
+    fn render(file: &Path, config: &mut Config) -> Result<Vec<Document>> {
+        let name = file
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| file.to_path_buf());
+        let source = SourceDocument::load(file, &name, config.max_page_size())
+            .map_err(|warning| Error::new(warning.message))?;
+
+        let sources = docs_finder::Sources {
+            documents: vec![source],
+            ..Default::default()
+        };
+        let root = docs_finder::render(&sources, config);
+        config.set_title(root[0].title.clone());
+
+        Ok(root)
+    }
+}
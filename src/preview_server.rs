@@ -1,20 +1,18 @@
 use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
 use std::net::SocketAddr;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use bunt::termcolor::{ColorChoice, StandardStream};
 use tiny_http::{Request, Response, Server};
 
-use crate::site::{Site, SiteBackend};
+use crate::site::{BuildMode, Site, SiteBackend};
+use crate::terminal::{self, ColorMode};
 
 macro_rules! colorprint {
     ($color: expr, $format_str:literal $(, $arg:expr)* $(,)?) => {
-        let mut stdout = if $color {
-            StandardStream::stdout(ColorChoice::Auto)
-        } else {
-            StandardStream::stdout(ColorChoice::Never)
-        };
+        let mut stdout = terminal::stdout($color);
 
         bunt::writeln!(
             stdout,
@@ -25,7 +23,7 @@ macro_rules! colorprint {
 }
 
 pub struct PreviewServer<B: SiteBackend> {
-    color: bool,
+    color: ColorMode,
     base_path: String,
     addr: SocketAddr,
     site: Arc<Mutex<Site<B>>>,
@@ -35,7 +33,7 @@ impl<B: SiteBackend> PreviewServer<B> {
     pub fn new(
         addr: SocketAddr,
         site: Arc<Mutex<Site<B>>>,
-        color: bool,
+        color: ColorMode,
         base_path: String,
     ) -> Self {
         PreviewServer {
@@ -73,22 +71,20 @@ impl<B: SiteBackend> PreviewServer<B> {
 
 fn handle_request<B: SiteBackend>(request: Request, site: &Site<B>) {
     let result = {
-        let uri = request.url().parse::<http::Uri>().unwrap();
-
-        let path = PathBuf::from(uri.path());
-
-        match resolve_file(&path, &site)
-            .map(|p| (read_file(&site, &p), content_type_for(p.extension())))
-        {
-            Some((data, None)) => request.respond(Response::from_data(data).with_status_code(200)),
-            Some((data, Some(content_type))) => {
-                request.respond(Response::from_data(data).with_status_code(200).with_header(
-                    tiny_http::Header {
-                        field: "Content-Type".parse().unwrap(),
-                        value: content_type.parse().unwrap(),
-                    },
-                ))
-            }
+        match request_path(request.url()).and_then(|path| resolve_file(&path, &site.backend)) {
+            // Large files are streamed from where they were copied from,
+            // instead of being read into memory
+            Some(path) => match site.backend.streamed_path(&path).map(File::open) {
+                Some(Ok(file)) => {
+                    request.respond(with_headers(Response::from_file(file), &path, site))
+                }
+                Some(Err(_)) => request.respond(Response::new_empty(tiny_http::StatusCode(404))),
+                None => request.respond(with_headers(
+                    Response::from_data(read_file(site, &path)),
+                    &path,
+                    site,
+                )),
+            },
             None => request.respond(Response::new_empty(tiny_http::StatusCode(404))),
         }
     };
@@ -100,44 +96,80 @@ fn handle_request<B: SiteBackend>(request: Request, site: &Site<B>) {
     }
 }
 
+fn with_headers<R: Read, B: SiteBackend>(
+    response: Response<R>,
+    path: &Path,
+    site: &Site<B>,
+) -> Response<R> {
+    let mut response = response.with_status_code(200);
+
+    if let Some(content_type) = content_type_for(path.extension()) {
+        response = response.with_header(header("Content-Type", content_type));
+    }
+    if let Some(cache_control) = cache_control_for(path, site.config.build_mode()) {
+        response = response.with_header(header("Cache-Control", cache_control));
+    }
+
+    response
+}
+
+/// Turns the URL of an incoming request into a path, decoding any percent
+/// encoded characters. Returns `None` for URLs that can't be parsed or that
+/// try to escape the site root with `..` segments.
+fn request_path(url: &str) -> Option<PathBuf> {
+    let uri = url.parse::<http::Uri>().ok()?;
+    let decoded = percent_encoding::percent_decode_str(uri.path())
+        .decode_utf8()
+        .ok()?;
+
+    let path = PathBuf::from(decoded.as_ref());
+
+    if is_traversal(&path) {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+fn is_traversal(path: &Path) -> bool {
+    path.components().any(|c| c == Component::ParentDir)
+}
+
 /// Uses some basic logic for resolving a path into the correct file.
 /// This means resolving to an index.html from the root of the directory,
 /// trying with .html extensions with needed, etc.
-pub fn resolve_file<B: SiteBackend>(path: &Path, site: &Site<B>) -> Option<PathBuf> {
-    if path.to_str().map(|s| s.contains("..")).unwrap_or(false) {
-        return None;
+pub fn resolve_file<B: SiteBackend>(path: &Path, backend: &B) -> Option<PathBuf> {
+    candidates(path, backend.config().base_path())
+        .into_iter()
+        .find(|candidate| backend.has_file(candidate))
+}
+
+/// The files of the site a path could be for, in the order they are tried:
+/// the file itself, the index.html of a directory, and a page with an .html
+/// extension. Anchors and query strings are not part of the file.
+pub fn candidates(path: &Path, base_path: &str) -> Vec<PathBuf> {
+    if is_traversal(path) {
+        return vec![];
     }
 
     let mut path = path;
 
-    if path.to_str().map(|s| s.contains("#")).unwrap_or(false) {
-        let prefix = path.to_str().unwrap().split("#").next().unwrap();
-
-        path = Path::new(prefix);
+    if let Some(end) = path.to_str().and_then(|s| s.find(|c| c == '#' || c == '?')) {
+        path = Path::new(&path.to_str().unwrap()[..end]);
     }
 
-    if path.starts_with(site.config.base_path()) {
-        path = path.strip_prefix(site.config.base_path()).unwrap();
+    if path.starts_with(base_path) {
+        path = path.strip_prefix(base_path).unwrap();
     }
 
-    let mut path = path.strip_prefix("/").unwrap_or(path).to_owned();
-
-    if site.backend.has_file(&path) {
-        Some(path)
-    } else if site.backend.has_file(&path.join("index.html")) {
-        let p = path.join("index.html");
+    let path = path.strip_prefix("/").unwrap_or(path);
 
-        Some(p)
-    } else {
+    vec![
+        path.to_owned(),
+        path.join("index.html"),
         // Try with a .html extension
-        path.set_extension("html");
-
-        if site.backend.has_file(&path) {
-            Some(path)
-        } else {
-            None
-        }
-    }
+        path.with_extension("html"),
+    ]
 }
 
 fn read_file<B: SiteBackend>(site: &Site<B>, path: &Path) -> Vec<u8> {
@@ -146,6 +178,24 @@ fn read_file<B: SiteBackend>(site: &Site<B>, path: &Path) -> Vec<u8> {
         .expect("Found a file to serve but could not open it")
 }
 
+fn header(field: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header {
+        field: field.parse().unwrap(),
+        value: value.parse().unwrap(),
+    }
+}
+
+/// Data files like the search index change with every rebuild, while the
+/// pages fetching them are reloaded. They must never come from the cache
+/// during development, or they would no longer match the pages.
+fn cache_control_for(path: &Path, mode: BuildMode) -> Option<&'static str> {
+    if mode == BuildMode::Dev && path.extension() == Some(OsStr::new("json")) {
+        Some("no-store")
+    } else {
+        None
+    }
+}
+
 fn content_type_for(extension: Option<&OsStr>) -> Option<&'static str> {
     match extension {
         Some(s) => match s.to_str() {
@@ -166,3 +216,37 @@ fn content_type_for(extension: Option<&OsStr>) -> Option<&'static str> {
         None => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_request_paths() {
+        assert_eq!(
+            request_path("/getting%20started/index.html"),
+            Some(PathBuf::from("/getting started/index.html"))
+        );
+        assert_eq!(request_path("/?query=1"), Some(PathBuf::from("/")));
+    }
+
+    #[test]
+    fn rejects_traversal_in_request_paths() {
+        assert_eq!(request_path("/../docgen.yaml"), None);
+        assert_eq!(request_path("/%2e%2e/secrets.env"), None);
+        assert_eq!(request_path("/foo/%2E%2E/%2E%2E/x"), None);
+        assert_eq!(request_path("/foo/..%2Fx"), None);
+    }
+
+    #[test]
+    fn data_files_are_not_cached_in_development() {
+        let index = Path::new("search_index.json");
+
+        assert_eq!(cache_control_for(index, BuildMode::Dev), Some("no-store"));
+        assert_eq!(cache_control_for(index, BuildMode::Release), None);
+        assert_eq!(
+            cache_control_for(Path::new("index.html"), BuildMode::Dev),
+            None
+        );
+    }
+}
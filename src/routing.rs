@@ -0,0 +1,176 @@
+//! Where the page of every Markdown file of the docs is built to, and the
+//! URI it is served at. Everything that turns the path of a file into a
+//! link, or a link back into a file, goes through here, so that they agree.
+//!
+//! | File               | Built to                  | URI               |
+//! |--------------------|---------------------------|-------------------|
+//! | `README.md`        | `index.html`              | `/`               |
+//! | `guides/README.md` | `guides/index.html`       | `/guides/`        |
+//! | `guides/setup.md`  | `guides/setup.html`       | `/guides/setup`   |
+//! | `guides/index.md`  | `guides/index/index.html` | `/guides/index/`  |
+//!
+//! With `clean_urls`, every page but the READMEs is built to an `index.html`
+//! of its own, like `guides/setup/index.html` at `/guides/setup/`. A page
+//! named `index` is always built like that, since the `index.html` next to
+//! it is the page of the README of its directory.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::navigation::Link;
+
+/// Whether the file is the README of its directory, whose page is the one
+/// at the URI of the directory
+pub fn is_readme(path: &Path) -> bool {
+    path.ends_with("README.md")
+}
+
+/// The path of the page of the Markdown file at `path`, relative to the
+/// output directory, and the URI of the page, starting with `base_path`.
+/// `path` is relative to the docs directory.
+pub fn output_path(path: &Path, base_path: &str, clean_urls: bool) -> (PathBuf, String) {
+    let is_readme = is_readme(path);
+    let own_directory = clean_urls || path.file_stem() == Some(OsStr::new("index"));
+
+    let html_path = if is_readme {
+        path.with_file_name("index.html")
+    } else if own_directory {
+        path.with_extension("").join("index.html")
+    } else {
+        path.with_extension("html")
+    };
+
+    // The URI of a page built to an index.html is the one of its directory
+    let mut uri_path = format!("{}{}", base_path, Link::path_to_uri(&html_path));
+    if (is_readme || own_directory) && !uri_path.ends_with('/') {
+        uri_path.push('/');
+    }
+
+    (html_path, uri_path)
+}
+
+/// The Markdown files a link to `path` could be for, with or without
+/// `clean_urls`. `path` is relative to the root of the site, and can be the
+/// URI of a page, the file it's built to or its Markdown file.
+pub fn source_paths(path: &str) -> Vec<PathBuf> {
+    let path = path.trim_matches('/');
+    let file = Path::new(path);
+
+    match file.extension().and_then(OsStr::to_str) {
+        _ if path.is_empty() => vec![PathBuf::from("README.md")],
+        Some("md") => vec![file.to_path_buf()],
+        Some("html") if file.file_name() == Some(OsStr::new("index.html")) => {
+            let dir = file.parent().unwrap_or_else(|| Path::new(""));
+            let mut paths = vec![dir.join("README.md")];
+            if !dir.as_os_str().is_empty() {
+                paths.push(PathBuf::from(format!("{}.md", dir.display())));
+            }
+            paths
+        }
+        Some("html") => vec![file.with_extension("md")],
+        _ => vec![
+            PathBuf::from(format!("{}.md", path)),
+            file.join("README.md"),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Every kind of file, with and without `clean_urls`, and where its page
+    /// is built to and served at
+    static ROUTES: &[(&str, bool, &str, &str)] = &[
+        ("README.md", false, "index.html", "/"),
+        ("README.md", true, "index.html", "/"),
+        ("guides/README.md", false, "guides/index.html", "/guides/"),
+        ("guides/README.md", true, "guides/index.html", "/guides/"),
+        ("setup.md", false, "setup.html", "/setup"),
+        ("setup.md", true, "setup/index.html", "/setup/"),
+        (
+            "guides/setup.md",
+            false,
+            "guides/setup.html",
+            "/guides/setup",
+        ),
+        (
+            "guides/setup.md",
+            true,
+            "guides/setup/index.html",
+            "/guides/setup/",
+        ),
+        ("index.md", false, "index/index.html", "/index/"),
+        ("index.md", true, "index/index.html", "/index/"),
+        (
+            "guides/index.md",
+            false,
+            "guides/index/index.html",
+            "/guides/index/",
+        ),
+        (
+            "guides/index.md",
+            true,
+            "guides/index/index.html",
+            "/guides/index/",
+        ),
+        ("index", false, "index/index.html", "/index/"),
+        ("reindex.md", false, "reindex.html", "/reindex"),
+        ("index.en.md", false, "index.en.html", "/index.en"),
+        ("notes", false, "notes.html", "/notes"),
+        ("notes", true, "notes/index.html", "/notes/"),
+        ("v1.2.md", false, "v1.2.html", "/v1.2"),
+        ("v1.2.md", true, "v1.2/index.html", "/v1.2/"),
+    ];
+
+    #[test]
+    fn routes() {
+        for (path, clean_urls, html_path, uri_path) in ROUTES {
+            assert_eq!(
+                output_path(Path::new(path), "/", *clean_urls),
+                (PathBuf::from(html_path), uri_path.to_string()),
+                "{} with clean_urls: {}",
+                path,
+                clean_urls
+            );
+        }
+    }
+
+    #[test]
+    fn routes_start_with_the_base_path() {
+        assert_eq!(
+            output_path(Path::new("README.md"), "/docs/", false),
+            (PathBuf::from("index.html"), "/docs/".to_string())
+        );
+        assert_eq!(
+            output_path(Path::new("guides/setup.md"), "/docs/", true),
+            (
+                PathBuf::from("guides/setup/index.html"),
+                "/docs/guides/setup/".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn links_lead_back_to_their_files() {
+        // Only Markdown files are found by the docs finder
+        let markdown = ROUTES.iter().filter(|route| route.0.ends_with(".md"));
+
+        for (path, clean_urls, html_path, uri_path) in markdown {
+            for link in [html_path, uri_path] {
+                assert!(
+                    source_paths(link).contains(&PathBuf::from(path)),
+                    "{} with clean_urls: {} is not one of {:?}",
+                    path,
+                    clean_urls,
+                    source_paths(link)
+                );
+            }
+        }
+
+        assert_eq!(
+            source_paths("guides/setup.md"),
+            vec![PathBuf::from("guides/setup.md")]
+        );
+    }
+}
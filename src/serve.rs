@@ -1,73 +1,114 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use bunt::termcolor::{ColorChoice, StandardStream};
-use crossbeam_channel::bounded;
+use bunt::termcolor::StandardStream;
+use crossbeam_channel::{bounded, Receiver};
 
-use crate::config::{project_root, Config, DocgenYaml};
-use crate::livereload_server::LivereloadServer;
+use crate::config::Config;
+use crate::livereload_server::{LivereloadServer, Reload};
+use crate::markdown::extensions::link_rewriter::Link;
+use crate::navigation::Navigation;
 use crate::preview_server::PreviewServer;
-use crate::site::Site;
-use crate::watcher::Watcher;
-use crate::{broken_links_checker, docs_finder, Result};
+use crate::site::{InMemorySite, Site, SiteBackend};
+use crate::warning::Warning;
+use crate::watcher::{Change, Watcher};
+use crate::{broken_links_checker, docs_finder, site_generator, warning, Document, Error, Result};
 
 pub struct ServeCommand {}
 
 #[derive(Default)]
 pub struct ServeOptions {
     pub port: Option<u16>,
+    /// Replaces the `base_path` of docgen.yaml, like `--base-path`
+    pub base_path: Option<String>,
+    /// Polls for changes this often, like `--poll`
+    pub poll_interval: Option<Duration>,
+}
+
+impl ServeOptions {
+    /// Applies the options given on the command line to the config. Done
+    /// again for every reloaded config, so that they aren't lost.
+    fn apply(&self, config: &mut Config) -> Result<()> {
+        if let Some(path) = &self.base_path {
+            config.set_base_path(path)?;
+        }
+        if let Some(interval) = self.poll_interval {
+            config.set_poll_interval(interval);
+        }
+
+        Ok(())
+    }
 }
 
 impl ServeCommand {
-    pub fn run(options: ServeOptions, config: Config) -> Result<()> {
-        let mut stdout = if config.color_enabled() {
-            StandardStream::stdout(ColorChoice::Auto)
-        } else {
-            StandardStream::stdout(ColorChoice::Never)
-        };
-        let project_dir = project_root().unwrap_or_else(|| {
-            println!("Could not find a docgen project in this directory, or its parents.");
-            std::process::exit(1);
-        });
-        let config_path = DocgenYaml::find(&project_dir).unwrap();
+    pub fn run(options: ServeOptions, mut config: Config) -> Result<()> {
+        let mut stdout = crate::terminal::stdout(config.color_mode());
+        let project_dir = config.project_root().to_path_buf();
+        let config_path = config.config_path().to_path_buf();
+        let config_name = config_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        options.apply(&mut config)?;
+        crate::assets::check()?;
+        docs_finder::check_docs_dir(&config)?;
+        // Before the first build, so that pages connect to the port the
+        // livereload server really listens on
+        let port_notes = config.resolve_server_addrs(options.port)?;
 
-        let root = docs_finder::find(&config);
+        // Kept between rebuilds, so that changes to the config don't need
+        // every page to be read again
+        let mut sources = docs_finder::load(&config);
+        let mut root = docs_finder::render(&sources, &config);
 
         let site = Arc::new(Mutex::new(Site::in_memory(config.clone())));
         let c_site = Arc::clone(&site);
 
         bunt::writeln!(stdout, "{$bold}{$blue}Docgen | Serve{/$}{/$}")?;
         println!("Starting development server...\n");
+        Self::print_addrs(&mut stdout, &config, &port_notes)?;
 
         // Do initial build ---------------------------
 
         let start = Instant::now();
-        site.lock().unwrap().build(config.clone(), &root).unwrap();
+        let build_warnings = site.lock().unwrap().build(config.clone(), &root).unwrap();
+
+        let mut warnings = Self::page_warnings(&config, &sources, &root);
+        warnings.extend(build_warnings);
+        warnings.extend(broken_links_checker::case_mismatches(
+            &root,
+            &site.lock().unwrap(),
+        ));
+        warning::print(&mut stdout, &warnings)?;
 
-        if let Err(e) = broken_links_checker::check(&root, &site.lock().unwrap()) {
+        let mut broken_links =
+            broken_links_checker::find_broken_links(&root, &site.lock().unwrap());
+        if !broken_links.is_empty() {
             bunt::writeln!(stdout, "{$bold}{$yellow}WARNING{/$}{/$}")?;
-            println!("{}", e);
+            println!("{}", Error::broken_links(broken_links.clone()));
         }
+        Self::publish_warnings(&site, &config, &root, warnings, &broken_links)?;
 
         let duration = start.elapsed();
+        Self::print_memory_usage(&mut stdout, &site.lock().unwrap().backend)?;
 
         // Watcher ------------------------------------
 
+        // The sender is kept to restart the watcher when the config changes
+        // what it watches
         let (watch_snd, watch_rcv) = bounded(128);
-        let watcher = Watcher::new(
-            vec![config.docs_dir().to_path_buf(), config_path],
-            watch_snd,
-        );
-        thread::Builder::new()
-            .name("watcher".into())
-            .spawn(move || watcher.run())
-            .unwrap();
+        let mut stop_watcher = Watcher::for_config(&config, watch_snd.clone()).spawn();
 
         // Live Reload --------------------------------
 
         let (reload_send, reload_rcv) = bounded(128);
-        let livereload_server = LivereloadServer::new(config.livereload_addr(), reload_rcv);
+        let livereload_server =
+            LivereloadServer::new(config.livereload_addr(), reload_rcv, config.color_mode());
         thread::Builder::new()
             .name("livereload".into())
             .spawn(move || livereload_server.run())
@@ -75,13 +116,10 @@ impl ServeCommand {
 
         // Preview Server -----------------------------
 
-        let mut addr = config.addr();
-        addr.set_port(options.port.unwrap_or_else(|| config.addr().port()));
-
         let http_server = PreviewServer::new(
-            addr,
+            config.addr(),
             c_site,
-            config.color_enabled(),
+            config.color_mode(),
             config.base_path().to_owned(),
         );
         thread::Builder::new()
@@ -89,34 +127,710 @@ impl ServeCommand {
             .spawn(move || http_server.run())
             .unwrap();
 
-        // Listen for updates on from the watcher, rebuild the site,
-        // and inform the websocket listeners.
+        // Listen for updates on from the watcher, rebuild as much of the
+        // site as the change requires, and inform the websocket listeners.
+
+        // Whether the last rebuild failed, and the browser was told so
+        let mut build_failed = false;
 
-        for (path, msg) in watch_rcv {
-            let mut new_config = Config::load(&project_dir, false)?;
-            new_config.livereload_addr = config.livereload_addr;
-            new_config.preview_addr = config.preview_addr;
+        // Changes made while a rebuild runs are rebuilt together once it's
+        // done, instead of one after the other
+        Self::for_each_batch(&watch_rcv, |changes, during_rebuild| {
+            let watch_paths = Watcher::paths_for(&config);
+            let action = changes
+                .iter()
+                .map(|(change, _)| Action::for_change(change, config.docs_dir()))
+                .reduce(Action::merge)
+                .unwrap_or(Action::Ignore);
+
+            if during_rebuild && changes.len() > 1 {
+                println!(
+                    "    Coalesced {} changes made during the last rebuild:",
+                    changes.len()
+                );
+            }
+            for (i, (change, msg)) in changes.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
 
-            bunt::writeln!(stdout, "    File {$bold}{}{/$} {}.", path.display(), msg)?;
+                match change.path() {
+                    Some(path) => match Watcher::watched_root(&watch_paths, path) {
+                        Some(root) => bunt::write!(
+                            stdout,
+                            "    File {$bold}{}{/$} {} (watching {})",
+                            path.display(),
+                            msg,
+                            root.strip_prefix(&project_dir).unwrap_or(root).display()
+                        )?,
+                        None => {
+                            bunt::write!(stdout, "    File {$bold}{}{/$} {}", path.display(), msg)?
+                        }
+                    },
+                    None => bunt::write!(stdout, "    {$bold}{}{/$} {}", config_name, msg)?,
+                }
+            }
 
-            let mut site_write = site.lock().unwrap();
-            site_write.reset().unwrap();
             let start = Instant::now();
-            let root = docs_finder::find(&new_config);
-            site_write.rebuild(new_config, &root).unwrap();
-            let duration = start.elapsed();
-            drop(site_write);
 
-            bunt::writeln!(stdout, "    Site rebuilt in {$bold}{:?}{/$}\n", duration)?;
+            let reload = match action {
+                Action::Ignore => {
+                    println!(" → not part of the site, ignored.\n");
+                    return Ok(());
+                }
+                Action::RefreshIncludes { stylesheet } => {
+                    Site::refresh_includes(&site).and_then(|warnings| {
+                        let assets = site.lock().unwrap().backend.asset_counts();
+                        bunt::writeln!(
+                            stdout,
+                            " → assets refreshed in {$bold}{:?}{/$} ({} copied, {} unchanged), \
+                             no pages rebuilt\n",
+                            start.elapsed(),
+                            assets.copied,
+                            assets.skipped
+                        )?;
+                        warning::print(&mut stdout, &warnings)?;
+
+                        Ok(match stylesheet {
+                            Some(path) => Some(Reload::Stylesheet(format!(
+                                "{}/{}",
+                                config.base_path().trim_end_matches('/'),
+                                path
+                            ))),
+                            None => Self::page_reload(&site),
+                        })
+                    })
+                }
+                Action::ReloadConfig { pages_changed } => {
+                    // A config that doesn't load leaves the previous one in use
+                    Config::load_from(&config_path, false).and_then(|mut new_config| {
+                        new_config.livereload_addr = config.livereload_addr;
+                        new_config.preview_addr = config.preview_addr;
+                        new_config.set_color_mode(config.color_mode());
+                        options.apply(&mut new_config)?;
+                        if pages_changed || !new_config.loads_same_sources(&config) {
+                            sources = docs_finder::load(&new_config);
+                        }
+                        let restart_watcher = !Watcher::watches_same(&new_config, &config);
+                        config = new_config;
+
+                        println!(" → configuration reloaded");
+                        if restart_watcher {
+                            stop_watcher.store(true, Ordering::Relaxed);
+                            stop_watcher = Watcher::for_config(&config, watch_snd.clone()).spawn();
+                            println!("    Watching for changes with the new configuration");
+                        }
+                        Self::rebuild(
+                            &mut stdout,
+                            &site,
+                            &config,
+                            &sources,
+                            &mut root,
+                            &mut broken_links,
+                        )?;
+                        Ok(Self::page_reload(&site))
+                    })
+                }
+                Action::RebuildPages => {
+                    println!();
+                    sources = docs_finder::load(&config);
+                    Self::rebuild(
+                        &mut stdout,
+                        &site,
+                        &config,
+                        &sources,
+                        &mut root,
+                        &mut broken_links,
+                    )
+                    .map(|_| Self::page_reload(&site))
+                }
+            };
+
+            // A failed rebuild leaves the previous site being served, so
+            // that fixing the mistake is all it takes to carry on
+            let reload = match reload {
+                Ok(reload) if build_failed => {
+                    build_failed = false;
+                    reload.or_else(|| Self::forced_reload(&site))
+                }
+                Ok(reload) => reload,
+                Err(e) => {
+                    bunt::writeln!(
+                        stdout,
+                        "\n{$bold}{$red}ERROR{/$}{/$} The site could not be rebuilt, \
+                         still serving the last build that worked:\n"
+                    )?;
+                    println!("{}\n", e);
+
+                    build_failed = true;
+                    Some(Reload::BuildFailed(e.to_string()))
+                }
+            };
 
-            if let Err(e) = broken_links_checker::check(&root, &site.lock().unwrap()) {
-                bunt::writeln!(stdout, "{$bold}{$yellow}WARNING{/$}{/$}")?;
-                println!("{}", e);
+            match reload {
+                Some(reload) => reload_send.send(reload).unwrap(),
+                None => println!("    No output changed, the browser is not reloaded.\n"),
             }
 
-            reload_send.send(()).unwrap();
+            Ok(())
+        })
+    }
+
+    /// Hands the changes from the watcher to `handle` until the watcher goes
+    /// away. Changes that arrive while `handle` runs are handed to it
+    /// together once it returns, along with `true` to say they were made
+    /// during the last rebuild.
+    fn for_each_batch<F>(changes: &Receiver<(Change, String)>, mut handle: F) -> Result<()>
+    where
+        F: FnMut(Vec<(Change, String)>, bool) -> Result<()>,
+    {
+        let mut queued = vec![];
+        loop {
+            let during_rebuild = !queued.is_empty();
+            let mut batch = std::mem::take(&mut queued);
+            if batch.is_empty() {
+                match changes.recv() {
+                    Ok(first) => batch.push(first),
+                    Err(_) => return Ok(()),
+                }
+            }
+            batch.extend(changes.try_iter());
+            handle(batch, during_rebuild)?;
+            queued.extend(changes.try_iter());
         }
+    }
+
+    /// Says why a server isn't on the port it was given, and where live
+    /// reload listens. The preview server prints its own address once it is
+    /// listening.
+    pub(crate) fn print_addrs(
+        stdout: &mut StandardStream,
+        config: &Config,
+        notes: &[String],
+    ) -> Result<()> {
+        for note in notes {
+            bunt::writeln!(stdout, "{$yellow}{}{/$}", note)?;
+        }
+        bunt::writeln!(
+            stdout,
+            "Live reload listening on {$bold}ws://{}{/$}",
+            config.livereload_addr()
+        )?;
 
         Ok(())
     }
+
+    /// How much of the site is held in memory, and how much of it is read
+    /// from disk whenever it is requested
+    fn print_memory_usage(stdout: &mut StandardStream, site: &InMemorySite) -> Result<()> {
+        let usage = site.memory_usage();
+        bunt::write!(
+            stdout,
+            "Keeping {$bold}{}{/$} of the site in memory",
+            crate::format_size(usage.in_memory_bytes)
+        )?;
+        if usage.streamed_files > 0 {
+            bunt::write!(
+                stdout,
+                ", streaming {} large file(s) ({}) from disk",
+                usage.streamed_files,
+                crate::format_size(usage.streamed_bytes)
+            )?;
+        }
+        bunt::writeln!(stdout, "\n")?;
+
+        Ok(())
+    }
+
+    /// Warnings about the pages found. Unlike a build, serving carries on
+    /// without any pages, so the first one written shows up right away.
+    fn page_warnings(
+        config: &Config,
+        sources: &docs_finder::Sources,
+        root: &[Document],
+    ) -> Vec<Warning> {
+        let mut warnings = sources.skipped.clone();
+        warnings.extend(docs_finder::warnings(root, config));
+        warnings.extend(docs_finder::include_warnings(root));
+        warnings.extend(Navigation::new(config).anchor_warnings(root));
+
+        if root.is_empty() {
+            let docs_dir = config.docs_dir();
+            warnings.push(Warning::new(
+                docs_dir
+                    .strip_prefix(config.project_root())
+                    .unwrap_or(docs_dir),
+                docs_finder::no_pages_message(config),
+            ));
+        }
+
+        warnings
+    }
+
+    /// Reloads the page, telling it which build it is going to get and
+    /// what changed in it. Nothing is reloaded when the last rebuild made
+    /// the same files as the one before, e.g. after saving without edits.
+    pub(crate) fn page_reload(site: &Mutex<Site<InMemorySite>>) -> Option<Reload> {
+        let site = site.lock().unwrap();
+        let changed = site.backend.changed();
+        if changed.is_empty() {
+            return None;
+        }
+
+        Some(Reload::Page {
+            timestamp: site.backend.timestamp().to_owned(),
+            changed: changed
+                .iter()
+                .map(|path| path.to_string_lossy().replace('\\', "/"))
+                .collect(),
+        })
+    }
+
+    /// Reloads the page even though no output changed, e.g. to clear the
+    /// error of a build that failed before
+    fn forced_reload(site: &Mutex<Site<InMemorySite>>) -> Option<Reload> {
+        Some(Reload::Page {
+            timestamp: site.lock().unwrap().backend.timestamp().to_owned(),
+            changed: vec![],
+        })
+    }
+
+    /// Renders the documents again and rebuilds the whole site with them,
+    /// reporting warnings and any links the change broke.
+    fn rebuild(
+        stdout: &mut StandardStream,
+        site: &Mutex<Site<InMemorySite>>,
+        config: &Config,
+        sources: &docs_finder::Sources,
+        root: &mut Vec<Document>,
+        broken_links: &mut Vec<(PathBuf, Link)>,
+    ) -> Result<()> {
+        // The preview server keeps serving the previous build until the
+        // new one is complete and swapped in, and keeps serving it if the
+        // new one fails.
+        let start = Instant::now();
+        let new_root = docs_finder::render(sources, config);
+        let build_warnings = Site::rebuild(site, config.clone(), &new_root)?;
+        let previous_root = std::mem::replace(root, new_root);
+        let duration = start.elapsed();
+
+        let assets = site.lock().unwrap().backend.asset_counts();
+        bunt::writeln!(
+            stdout,
+            "    Site rebuilt in {$bold}{:?}{/$} ({} assets copied, {} unchanged)\n",
+            duration,
+            assets.copied,
+            assets.skipped
+        )?;
+
+        let mut warnings = Self::page_warnings(config, sources, root);
+        warnings.extend(build_warnings);
+        warnings.extend(broken_links_checker::case_mismatches(
+            root,
+            &site.lock().unwrap(),
+        ));
+        warning::print(stdout, &warnings)?;
+
+        let previous_links = std::mem::replace(
+            broken_links,
+            broken_links_checker::find_broken_links(root, &site.lock().unwrap()),
+        );
+        let removed_page = previous_root
+            .iter()
+            .any(|old| !root.iter().any(|doc| doc.path == old.path));
+
+        if removed_page {
+            let affected = broken_links_checker::newly_broken(&previous_links, broken_links);
+
+            if !affected.is_empty() {
+                bunt::writeln!(
+                    stdout,
+                    "    {$yellow}Removed pages are still linked from{/$} {$bold}{}{/$}\n",
+                    affected
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+            }
+        }
+
+        if !broken_links.is_empty() {
+            bunt::writeln!(stdout, "{$bold}{$yellow}WARNING{/$}{/$}")?;
+            println!("{}", Error::broken_links(broken_links.clone()));
+        }
+        Self::publish_warnings(site, config, root, warnings, broken_links)?;
+
+        Ok(())
+    }
+
+    /// Puts the warnings about each page, and its broken links, in the site
+    /// being served, for the overlay of the page to show them
+    fn publish_warnings(
+        site: &Mutex<Site<InMemorySite>>,
+        config: &Config,
+        root: &[Document],
+        mut warnings: Vec<Warning>,
+        broken_links: &[(PathBuf, Link)],
+    ) -> Result<()> {
+        warnings.extend(broken_links_checker::as_warnings(broken_links));
+        let pages = root
+            .iter()
+            .map(|doc| doc.original_path())
+            .collect::<Vec<_>>();
+        let json = serde_json::to_vec(&warning::by_page(&warnings, &pages)).unwrap();
+
+        site.lock()
+            .unwrap()
+            .backend
+            .add_file(&config.out_dir().join(warning::WARNINGS_FILE), &json)
+            .map_err(|e| Error::io(e, "Could not write the warnings of the build"))
+    }
+}
+
+/// How much of the site a change requires rebuilding
+#[derive(Debug, PartialEq)]
+enum Action {
+    /// Load docgen.yaml again and rebuild everything. The pages are only
+    /// read again if some of them changed too, or the config says to find
+    /// them elsewhere.
+    ReloadConfig {
+        pages_changed: bool,
+    },
+    /// Find the documents again and render the pages
+    RebuildPages,
+    /// Copy the includes again, without rendering any pages. When the
+    /// change was to a stylesheet, its path relative to `_include`.
+    RefreshIncludes {
+        stylesheet: Option<String>,
+    },
+    Ignore,
+}
+
+impl Action {
+    fn for_change(change: &Change, docs_dir: &Path) -> Self {
+        match change {
+            Change::ConfigChanged => Action::ReloadConfig {
+                pages_changed: false,
+            },
+            Change::MarkdownChanged(_) => Action::RebuildPages,
+            Change::IncludeChanged(path) if site_generator::include_affects_pages(path) => {
+                Action::RebuildPages
+            }
+            Change::IncludeChanged(path) => {
+                let stylesheet = path
+                    .strip_prefix(docs_dir.join("_include"))
+                    .ok()
+                    .filter(|p| p.extension() == Some(OsStr::new("css")))
+                    .map(|p| p.to_string_lossy().replace('\\', "/"));
+
+                Action::RefreshIncludes { stylesheet }
+            }
+            Change::Other(_) => Action::Ignore,
+        }
+    }
+
+    /// The action that covers both, for changes that are handled together
+    fn merge(self, other: Action) -> Self {
+        match (self, other) {
+            (Action::ReloadConfig { pages_changed }, other)
+            | (other, Action::ReloadConfig { pages_changed }) => Action::ReloadConfig {
+                pages_changed: pages_changed
+                    || matches!(
+                        other,
+                        Action::RebuildPages
+                            | Action::ReloadConfig {
+                                pages_changed: true
+                            }
+                    ),
+            },
+            (Action::RebuildPages, _) | (_, Action::RebuildPages) => Action::RebuildPages,
+            (
+                Action::RefreshIncludes { stylesheet },
+                Action::RefreshIncludes {
+                    stylesheet: other_stylesheet,
+                },
+            ) => Action::RefreshIncludes {
+                // Only a single stylesheet is swapped in without a reload
+                stylesheet: stylesheet.filter(|path| Some(path) == other_stylesheet.as_ref()),
+            },
+            (Action::Ignore, action) | (action, Action::Ignore) => action,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::site::SiteBackend;
+    use crate::test_helpers::project;
+
+    fn action(change: Change) -> Action {
+        Action::for_change(&change, Path::new("/project/docs"))
+    }
+
+    fn change(path: &str) -> (Change, String) {
+        (
+            Change::MarkdownChanged(PathBuf::from(path)),
+            "updated".into(),
+        )
+    }
+
+    #[test]
+    fn changes_during_a_rebuild_are_rebuilt_once() {
+        let (snd, rcv) = crossbeam_channel::unbounded();
+        // Saved at once, before any rebuild
+        snd.send(change("/project/docs/README.md")).unwrap();
+        snd.send(change("/project/docs/guide.md")).unwrap();
+        let mut editor = Some(snd);
+        let mut batches = vec![];
+
+        ServeCommand::for_each_batch(&rcv, |changes, during_rebuild| {
+            // Three more saves while the first rebuild is slowly running
+            if let Some(snd) = editor.take() {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                snd.send(change("/project/docs/a.md")).unwrap();
+                snd.send(change("/project/docs/b.md")).unwrap();
+                snd.send(change("/project/docs/a.md")).unwrap();
+            }
+            batches.push((changes.len(), during_rebuild));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(batches, vec![(2, false), (3, true)]);
+    }
+
+    #[test]
+    fn batched_actions_cover_every_change() {
+        let stylesheet = |path: &str| Action::RefreshIncludes {
+            stylesheet: Some(path.to_string()),
+        };
+
+        let reload_config = |pages_changed| Action::ReloadConfig { pages_changed };
+
+        // Pages saved along with the config are read again
+        assert_eq!(
+            Action::RebuildPages.merge(reload_config(false)),
+            reload_config(true)
+        );
+        assert_eq!(
+            reload_config(false).merge(Action::RebuildPages),
+            reload_config(true)
+        );
+        assert_eq!(
+            reload_config(false).merge(stylesheet("style.css")),
+            reload_config(false)
+        );
+        assert_eq!(
+            reload_config(false).merge(reload_config(true)),
+            reload_config(true)
+        );
+        assert_eq!(
+            stylesheet("style.css").merge(Action::RebuildPages),
+            Action::RebuildPages
+        );
+        assert_eq!(
+            Action::Ignore.merge(stylesheet("style.css")),
+            stylesheet("style.css")
+        );
+        assert_eq!(
+            stylesheet("style.css").merge(stylesheet("style.css")),
+            stylesheet("style.css")
+        );
+        assert_eq!(
+            stylesheet("style.css").merge(stylesheet("print.css")),
+            Action::RefreshIncludes { stylesheet: None }
+        );
+    }
+
+    #[test]
+    fn config_changes_reload_everything() {
+        assert_eq!(
+            action(Change::ConfigChanged),
+            Action::ReloadConfig {
+                pages_changed: false
+            }
+        );
+    }
+
+    #[test]
+    fn command_line_options_outlast_config_reloads() {
+        let options = ServeOptions {
+            base_path: Some("/preview/".into()),
+            poll_interval: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        // The config as it is read again after docgen.yaml changed
+        let yaml = "---\ntitle: Title\nbase_path: /docs/\n";
+        let mut reloaded = Config::from_yaml_str(Path::new(""), yaml, false).unwrap();
+        options.apply(&mut reloaded).unwrap();
+
+        assert_eq!(reloaded.base_path(), "/preview/");
+        assert_eq!(reloaded.poll_interval(), Some(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn markdown_changes_rebuild_pages() {
+        assert_eq!(
+            action(Change::MarkdownChanged(PathBuf::from(
+                "/project/docs/README.md"
+            ))),
+            Action::RebuildPages
+        );
+    }
+
+    #[test]
+    fn stylesheet_changes_only_refresh_includes() {
+        assert_eq!(
+            action(Change::IncludeChanged(PathBuf::from(
+                "/project/docs/_include/css/style.css"
+            ))),
+            Action::RefreshIncludes {
+                stylesheet: Some("css/style.css".to_string())
+            }
+        );
+        assert_eq!(
+            action(Change::IncludeChanged(PathBuf::from(
+                "/project/docs/_include/logo.png"
+            ))),
+            Action::RefreshIncludes { stylesheet: None }
+        );
+    }
+
+    #[test]
+    fn includes_rendered_into_pages_rebuild_pages() {
+        assert_eq!(
+            action(Change::IncludeChanged(PathBuf::from(
+                "/project/docs/_include/_head.html"
+            ))),
+            Action::RebuildPages
+        );
+        assert_eq!(
+            action(Change::IncludeChanged(PathBuf::from(
+                "/project/docs/_include/dark.css"
+            ))),
+            Action::RebuildPages
+        );
+        assert_eq!(
+            action(Change::IncludeChanged(PathBuf::from(
+                "/project/docs/_include/_sidebar_footer.html"
+            ))),
+            Action::RebuildPages
+        );
+    }
+
+    #[test]
+    fn other_changes_are_ignored() {
+        assert_eq!(
+            action(Change::Other(PathBuf::from("/project/docs/.DS_Store"))),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn failed_rebuilds_keep_serving_the_previous_site() {
+        let project = project("serve", &[("docs/README.md", "# Home")]);
+        let docs = project.join("docs");
+
+        let config = Config::from_yaml_str(&project, "---\ntitle: Serve\n", false).unwrap();
+        let mut stdout = crate::terminal::stdout(crate::ColorMode::Never);
+        let site = Arc::new(Mutex::new(Site::in_memory(config.clone())));
+        let sources = docs_finder::load(&config);
+        let mut root = docs_finder::render(&sources, &config);
+        let mut broken_links = vec![];
+        site.lock().unwrap().build(config.clone(), &root).unwrap();
+        let timestamp = site.lock().unwrap().backend.timestamp().to_owned();
+
+        // A head include that can't be read fails the build
+        std::fs::write(docs.join("guide.md"), "# Guide").unwrap();
+        std::fs::create_dir_all(docs.join("_include").join("_head.html")).unwrap();
+        let sources = docs_finder::load(&config);
+
+        let reader_site = Arc::clone(&site);
+        let reader = thread::spawn(move || {
+            let site = reader_site.lock().unwrap();
+            assert!(site.backend.has_file(Path::new("index.html")));
+        });
+        let result = ServeCommand::rebuild(
+            &mut stdout,
+            &site,
+            &config,
+            &sources,
+            &mut root,
+            &mut broken_links,
+        );
+        reader.join().unwrap();
+
+        assert!(result.is_err());
+        {
+            let site = site.lock().unwrap();
+            assert_eq!(site.backend.timestamp(), timestamp);
+            assert!(site.backend.has_file(Path::new("index.html")));
+            assert!(!site.backend.has_file(Path::new("guide.html")));
+        }
+        assert_eq!(root.len(), 1);
+
+        // Fixing it is enough to carry on
+        std::fs::remove_dir_all(docs.join("_include")).unwrap();
+        let result = ServeCommand::rebuild(
+            &mut stdout,
+            &site,
+            &config,
+            &sources,
+            &mut root,
+            &mut broken_links,
+        );
+        std::fs::remove_dir_all(&project).unwrap();
+
+        assert!(result.is_ok());
+        assert!(site
+            .lock()
+            .unwrap()
+            .backend
+            .has_file(Path::new("guide.html")));
+        assert_eq!(root.len(), 2);
+    }
+
+    #[test]
+    fn rebuilds_publish_the_warnings_of_each_page() {
+        let project = project(
+            "serve-warnings",
+            &[
+                ("docs/README.md", "# Home\n\n[Gone](/gone)"),
+                ("docs/guide.md", "# Guide"),
+            ],
+        );
+
+        let config = Config::from_yaml_str(&project, "---\ntitle: Serve\n", false).unwrap();
+        let mut stdout = crate::terminal::stdout(crate::ColorMode::Never);
+        let site = Arc::new(Mutex::new(Site::in_memory(config.clone())));
+        let sources = docs_finder::load(&config);
+        let mut root = vec![];
+        let mut broken_links = vec![];
+
+        let result = ServeCommand::rebuild(
+            &mut stdout,
+            &site,
+            &config,
+            &sources,
+            &mut root,
+            &mut broken_links,
+        );
+        std::fs::remove_dir_all(&project).unwrap();
+
+        assert!(result.is_ok());
+        let json = site
+            .lock()
+            .unwrap()
+            .backend
+            .read_path(Path::new(warning::WARNINGS_FILE))
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({
+                "README.md": ["Links to /gone, which is not in the site"]
+            })
+        );
+    }
 }
@@ -1,10 +1,19 @@
 use crate::config::Config;
-use crate::site_generator::SiteGenerator;
+use crate::post_processor::HtmlPostProcessor;
+use crate::site_generator::{ManifestFile, SiteGenerator, MANIFEST_FILE};
+use crate::warning::Warning;
 use crate::Document;
 use crate::{Error, Result};
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
 use std::fs;
+use std::hash::Hasher;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 /// Describes the mode we should build the site in, meaning
@@ -32,6 +41,8 @@ impl std::fmt::Display for BuildMode {
 pub struct Site<B: SiteBackend> {
     pub backend: B,
     pub config: Config,
+    /// Run on every page after the built-in post processors
+    pub post_processors: Vec<Arc<dyn HtmlPostProcessor>>,
 }
 
 impl Site<InMemorySite> {
@@ -39,8 +50,55 @@ impl Site<InMemorySite> {
         Site {
             backend: InMemorySite::new(config.clone()),
             config,
+            post_processors: vec![],
         }
     }
+
+    /// Builds a fresh site next to the shared one, and swaps it in only once
+    /// the build is complete. Readers holding the lock never see an empty or
+    /// partially built site, nor files of pages that have since been removed.
+    ///
+    /// Assets and includes that did not change are carried over from the
+    /// shared site instead of being read and copied again.
+    pub fn rebuild(
+        shared: &Mutex<Site<InMemorySite>>,
+        config: Config,
+        root: &Vec<Document>,
+    ) -> Result<Vec<Warning>> {
+        let mut site = Site::in_memory(config.clone());
+        {
+            let shared = shared.lock().unwrap();
+            site.backend.remember(&shared.backend);
+            site.post_processors = shared.post_processors.clone();
+        }
+        let warnings = site.build(config, root)?;
+
+        let mut shared = shared.lock().unwrap();
+        site.backend.changed = changed_paths(&shared.backend, &site.backend);
+        *shared = site;
+
+        Ok(warnings)
+    }
+
+    /// Copies the includes into the shared site again, keeping its pages and
+    /// assets as they are. Includes that were removed are dropped.
+    pub fn refresh_includes(shared: &Mutex<Site<InMemorySite>>) -> Result<Vec<Warning>> {
+        let mut site = shared.lock().unwrap();
+        let config = site.config.clone();
+        let root = vec![];
+        let before = InMemorySite {
+            rendered: site.backend.rendered.clone(),
+            streamed: site.backend.streamed.clone(),
+            timestamp: site.backend.timestamp.clone(),
+            ..InMemorySite::new(config.clone())
+        };
+
+        site.backend.forget_copied_files();
+        let warnings = SiteGenerator::new(config, &root).refresh_includes(&mut site.backend)?;
+        site.backend.changed = changed_paths(&before, &site.backend);
+
+        Ok(warnings)
+    }
 }
 
 impl Site<DiskBackedSite> {
@@ -48,55 +106,321 @@ impl Site<DiskBackedSite> {
         Site {
             backend: DiskBackedSite::new(config.clone()),
             config,
+            post_processors: vec![],
         }
     }
 }
 
 impl<B: SiteBackend> Site<B> {
-    pub fn reset(&mut self) -> Result<()> {
-        self.backend.reset()
-    }
-
-    pub fn build(&mut self, config: Config, root: &Vec<Document>) -> Result<()> {
-        self.backend.build(config, root)
-    }
-
-    pub fn rebuild(&mut self, config: Config, root: &Vec<Document>) -> Result<()> {
-        self.backend.reset()?;
-        self.backend.build(config, root)
+    pub fn build(&mut self, config: Config, root: &Vec<Document>) -> Result<Vec<Warning>> {
+        self.backend.build(config, root, &self.post_processors)
     }
 }
 
 pub trait SiteBackend: Send + Sync {
     fn config(&self) -> &Config;
     /// Adds the rendered content for a given path
-    fn add_file(&mut self, path: &Path, content: &Vec<u8>) -> std::io::Result<()>;
+    fn add_file(&mut self, path: &Path, content: &[u8]) -> std::io::Result<()>;
     fn copy_file(&mut self, from: &Path, to: &Path) -> std::io::Result<()>;
+    /// Keeps the file at `path` from a previous build instead of writing it
+    /// again. Returns false if there is no such file, and it has to be written.
+    fn keep_file(&mut self, path: &Path) -> bool;
+    /// Like `keep_file`, for a file copied from `from`. Only kept if `from`
+    /// has not changed since it was copied.
+    fn keep_copied_file(&mut self, from: &Path, to: &Path) -> bool;
     /// Reads the rendered output of the specified path
     fn read_path(&self, path: &Path) -> Option<Vec<u8>>;
+    /// The file on disk the content of `path` is read from, for files that
+    /// are not kept in memory, so that they can be streamed instead of read
+    /// whole with `read_path`
+    fn streamed_path(&self, path: &Path) -> Option<PathBuf>;
     /// Says if we have rendered the specified file
     fn has_file(&self, path: &Path) -> bool;
-    /// Clears the rendered output, and reloads the documentation from disk into memory
-    fn reset(&mut self) -> Result<()>;
-    /// Renders the loaded documentation into memory
-    fn build(&mut self, config: Config, root: &Vec<Document>) -> Result<()>;
+    /// Renders the loaded documentation into memory, returning any warnings
+    /// found along the way. The post processors run on every page after the
+    /// built-in ones.
+    fn build(
+        &mut self,
+        config: Config,
+        root: &Vec<Document>,
+        post_processors: &[Arc<dyn HtmlPostProcessor>],
+    ) -> Result<Vec<Warning>>;
     fn list_files(&self) -> Vec<PathBuf>;
+    /// Every file written by the current build, by its path in the site
+    fn output_files(&self) -> BTreeMap<PathBuf, OutputFile>;
+    /// The paths in the site of every file written by the current build,
+    /// like `output_files` without hashing them
+    fn output_paths(&self) -> Vec<PathBuf>;
     fn in_memory(&self) -> bool;
 }
 
+/// How many assets and includes a build wrote, and how many it kept from
+/// the previous build because they had not changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AssetCounts {
+    pub copied: usize,
+    pub skipped: usize,
+}
+
+/// How much of the content of an in-memory site is held in memory, and how
+/// much is streamed from disk whenever it is served
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MemoryUsage {
+    pub in_memory_files: usize,
+    pub in_memory_bytes: u64,
+    pub streamed_files: usize,
+    pub streamed_bytes: u64,
+}
+
+/// Copied files with these extensions are always streamed from disk by the
+/// development server, as they are large and never change during a build
+static STREAMED_EXTENSIONS: &[&str] = &[
+    "mp4", "webm", "mov", "m4v", "avi", "mkv", "mp3", "wav", "ogg", "flac", "zip", "gz", "tgz",
+    "tar", "7z", "dmg", "iso",
+];
+
+/// Files that describe a build rather than its content, which change with
+/// every build or every save of a page, even when no page looks different
+static BUILD_RECORDS: &[&str] = &[MANIFEST_FILE, "recent.json"];
+
+/// The files that were added, removed or whose content changed between two
+/// builds, sorted by path. The timestamp every page carries is left out of
+/// the comparison, and so are the files in `BUILD_RECORDS`.
+fn changed_paths(before: &InMemorySite, after: &InMemorySite) -> Vec<PathBuf> {
+    let differs = |path: &PathBuf, content: &Arc<Vec<u8>>| match before.rendered.get(path) {
+        Some(old) if Arc::ptr_eq(old, content) => false,
+        Some(old) => {
+            without_timestamp(old, &before.timestamp)
+                != without_timestamp(content, &after.timestamp)
+        }
+        None => true,
+    };
+
+    let mut changed = after
+        .rendered
+        .iter()
+        .filter(|(path, content)| differs(path, content))
+        .map(|(path, _)| path.clone())
+        .chain(
+            before
+                .rendered
+                .keys()
+                .filter(|path| !after.has_file(path))
+                .cloned(),
+        )
+        .chain(
+            after
+                .streamed
+                .iter()
+                .filter(|(path, file)| {
+                    before.streamed.get(*path).map(|old| old.output) != Some(file.output)
+                })
+                .map(|(path, _)| path.clone()),
+        )
+        .chain(
+            before
+                .streamed
+                .keys()
+                .filter(|path| !after.has_file(path))
+                .cloned(),
+        )
+        .filter(|path| !BUILD_RECORDS.iter().any(|record| path == Path::new(record)))
+        .collect::<Vec<_>>();
+
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+/// The content with every occurrence of the build's timestamp removed
+fn without_timestamp<'c>(content: &'c [u8], timestamp: &str) -> Cow<'c, [u8]> {
+    let timestamp = timestamp.as_bytes();
+    if timestamp.is_empty() || !content.windows(timestamp.len()).any(|w| w == timestamp) {
+        return Cow::Borrowed(content);
+    }
+
+    let mut stripped = Vec::with_capacity(content.len());
+    let mut rest = content;
+    while !rest.is_empty() {
+        if rest.starts_with(timestamp) {
+            rest = &rest[timestamp.len()..];
+        } else {
+            stripped.push(rest[0]);
+            rest = &rest[1..];
+        }
+    }
+
+    Cow::Owned(stripped)
+}
+
+/// Modification time and size of a copied file, to tell if it changed
+type SourceStamp = (SystemTime, u64);
+
+/// A hash of the content of a file. Seahash is specified, unlike the hasher
+/// of the standard library, so the hash of the same content stays the same
+/// from one build to the next, whichever Rust version built Docgen.
+pub fn content_hash(content: &[u8]) -> u64 {
+    seahash::hash(content)
+}
+
+/// Size and content hash of a file written to the site
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputFile {
+    pub size: u64,
+    pub hash: u64,
+}
+
+impl OutputFile {
+    pub fn of(content: &[u8]) -> Self {
+        OutputFile {
+            size: content.len() as u64,
+            hash: content_hash(content),
+        }
+    }
+
+    /// Like `of`, for the content of a file, which is hashed a chunk at a
+    /// time instead of being read whole
+    pub fn of_file(path: &Path) -> std::io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+        // Gives the same hash as `content_hash` of the whole content
+        let mut hasher = seahash::SeaHasher::new();
+        let mut buffer = vec![0; 64 * 1024];
+        let mut size = 0;
+
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.write(&buffer[..read]);
+            size += read as u64;
+        }
+
+        Ok(OutputFile {
+            size,
+            hash: hasher.finish(),
+        })
+    }
+}
+
+/// A file copied into an in-memory site that is read from where it was
+/// copied from whenever it is served, instead of being kept in memory
+#[derive(Debug, Clone)]
+struct StreamedFile {
+    source: PathBuf,
+    output: OutputFile,
+}
+
 #[derive(Debug)]
 pub struct InMemorySite {
     config: Config,
-    rendered: HashMap<PathBuf, Vec<u8>>,
+    rendered: HashMap<PathBuf, Arc<Vec<u8>>>,
+    /// Copied files that are too large to keep in memory, see
+    /// `Config::serve_stream_size`
+    streamed: HashMap<PathBuf, StreamedFile>,
+    /// Stamps of the files copied into the site, by their path in the site
+    sources: HashMap<PathBuf, SourceStamp>,
+    /// Output of the previous build, which unchanged files are taken from
+    previous: HashMap<PathBuf, Arc<Vec<u8>>>,
+    previous_streamed: HashMap<PathBuf, StreamedFile>,
+    previous_sources: HashMap<PathBuf, SourceStamp>,
+    asset_counts: AssetCounts,
+    /// DOCGEN_TIMESTAMP of the last build
+    timestamp: String,
+    /// Files whose content differs from the site this one was rebuilt from
+    changed: Vec<PathBuf>,
 }
 
 impl InMemorySite {
     pub fn new(config: Config) -> Self {
         InMemorySite {
             rendered: HashMap::new(),
+            streamed: HashMap::new(),
+            sources: HashMap::new(),
+            previous: HashMap::new(),
+            previous_streamed: HashMap::new(),
+            previous_sources: HashMap::new(),
+            asset_counts: AssetCounts::default(),
+            timestamp: String::new(),
+            changed: vec![],
             config,
         }
     }
+
+    /// Makes the output of a previous build available to `keep_file`
+    pub fn remember(&mut self, previous: &InMemorySite) {
+        self.previous = previous.rendered.clone();
+        self.previous_streamed = previous.streamed.clone();
+
+        // Copied files are copied again when the size above which they are
+        // streamed changed, as they may have to move in or out of memory
+        if previous.config.serve_stream_size() == self.config.serve_stream_size() {
+            self.previous_sources = previous.sources.clone();
+        }
+    }
+
+    /// Sets aside the files copied into the site, so that copying them again
+    /// keeps the ones that did not change and leaves out the removed ones.
+    fn forget_copied_files(&mut self) {
+        self.previous = HashMap::new();
+        self.previous_streamed = HashMap::new();
+        self.previous_sources = HashMap::new();
+        self.asset_counts = AssetCounts::default();
+
+        for (path, stamp) in self.sources.drain() {
+            if let Some(content) = self.rendered.remove(&path) {
+                self.previous.insert(path.clone(), content);
+                self.previous_sources.insert(path, stamp);
+            } else if let Some(file) = self.streamed.remove(&path) {
+                self.previous_streamed.insert(path.clone(), file);
+                self.previous_sources.insert(path, stamp);
+            }
+        }
+    }
+
+    /// The timestamp the last build gave its pages. Empty before the first build.
+    pub fn timestamp(&self) -> &str {
+        &self.timestamp
+    }
+
+    /// The files that changed in the last rebuild or refresh of includes,
+    /// see `changed_paths`
+    pub fn changed(&self) -> &[PathBuf] {
+        &self.changed
+    }
+
+    /// How many assets were copied or kept during the last build
+    pub fn asset_counts(&self) -> AssetCounts {
+        self.asset_counts
+    }
+
+    /// How much of the site is kept in memory, and how much is streamed
+    /// from disk
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            in_memory_files: self.rendered.len(),
+            in_memory_bytes: self.rendered.values().map(|c| c.len() as u64).sum(),
+            streamed_files: self.streamed.len(),
+            streamed_bytes: self.streamed.values().map(|f| f.output.size).sum(),
+        }
+    }
+
+    /// Whether a copied file of `size` bytes is streamed from disk rather
+    /// than kept in memory
+    fn streams(&self, from: &Path, size: u64) -> bool {
+        size > self.config.serve_stream_size()
+            || from
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(|ext| STREAMED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                .unwrap_or(false)
+    }
+
+    fn stamp(path: &Path) -> Option<SourceStamp> {
+        let metadata = fs::metadata(path).ok()?;
+
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
 }
 
 impl SiteBackend for InMemorySite {
@@ -108,82 +432,251 @@ impl SiteBackend for InMemorySite {
         &self.config
     }
 
-    fn add_file(&mut self, path: &Path, html: &Vec<u8>) -> std::io::Result<()> {
+    fn add_file(&mut self, path: &Path, html: &[u8]) -> std::io::Result<()> {
         // let mut content = self.content.write().unwrap();
 
         let path = path.strip_prefix(self.config.out_dir()).unwrap();
 
-        self.rendered.insert(path.to_owned(), html.to_vec());
+        self.streamed.remove(path);
+        self.rendered
+            .insert(path.to_owned(), Arc::new(html.to_vec()));
         Ok(())
     }
 
     fn copy_file(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
-        let content = fs::read(from)?;
-        self.add_file(to, &content)
+        let size = fs::metadata(from)?.len();
+        if self.streams(from, size) {
+            let path = to.strip_prefix(self.config.out_dir()).unwrap();
+            let file = StreamedFile {
+                source: from.to_path_buf(),
+                output: OutputFile::of_file(from)?,
+            };
+            self.rendered.remove(path);
+            self.streamed.insert(path.to_owned(), file);
+        } else {
+            let content = fs::read(from)?;
+            self.add_file(to, &content)?;
+        }
+
+        if let Some(stamp) = Self::stamp(from) {
+            let path = to.strip_prefix(self.config.out_dir()).unwrap();
+            self.sources.insert(path.to_owned(), stamp);
+        }
+
+        Ok(())
+    }
+
+    fn keep_file(&mut self, path: &Path) -> bool {
+        let path = path.strip_prefix(self.config.out_dir()).unwrap();
+
+        if let Some(file) = self.previous_streamed.get(path) {
+            self.streamed.insert(path.to_owned(), file.clone());
+            self.asset_counts.skipped += 1;
+            return true;
+        }
+
+        match self.previous.get(path) {
+            Some(content) => {
+                self.rendered.insert(path.to_owned(), Arc::clone(content));
+                self.asset_counts.skipped += 1;
+                true
+            }
+            None => {
+                self.asset_counts.copied += 1;
+                false
+            }
+        }
+    }
+
+    fn keep_copied_file(&mut self, from: &Path, to: &Path) -> bool {
+        let path = to.strip_prefix(self.config.out_dir()).unwrap();
+        let previous_stamp = self.previous_sources.get(path).copied();
+
+        match Self::stamp(from) {
+            Some(stamp) if previous_stamp == Some(stamp) => {
+                self.sources.insert(path.to_owned(), stamp);
+                self.keep_file(to)
+            }
+            _ => {
+                self.asset_counts.copied += 1;
+                false
+            }
+        }
     }
 
     fn read_path(&self, path: &Path) -> Option<Vec<u8>> {
-        self.rendered.get(path).map(|s| s.clone())
+        match self.streamed.get(path) {
+            Some(file) => fs::read(&file.source).ok(),
+            None => self.rendered.get(path).map(|s| s.to_vec()),
+        }
     }
 
-    fn has_file(&self, path: &Path) -> bool {
-        self.rendered.contains_key(path)
+    fn streamed_path(&self, path: &Path) -> Option<PathBuf> {
+        self.streamed.get(path).map(|file| file.source.clone())
     }
 
-    fn reset(&mut self) -> Result<()> {
-        self.rendered = HashMap::new();
-        Ok(())
+    fn has_file(&self, path: &Path) -> bool {
+        self.rendered.contains_key(path) || self.streamed.contains_key(path)
     }
 
-    fn build(&mut self, config: Config, root: &Vec<Document>) -> Result<()> {
+    fn build(
+        &mut self,
+        config: Config,
+        root: &Vec<Document>,
+        post_processors: &[Arc<dyn HtmlPostProcessor>],
+    ) -> Result<Vec<Warning>> {
         let mut generator = SiteGenerator::new(config, root);
-        generator.run(self)
+        for processor in post_processors {
+            generator.add_post_processor(Arc::clone(processor));
+        }
+        let warnings = generator.run(self)?;
+        self.timestamp = generator.timestamp();
+
+        Ok(warnings)
     }
 
     fn list_files(&self) -> Vec<PathBuf> {
         self.rendered
             .keys()
+            .chain(self.streamed.keys())
             .map(|p| p.to_owned())
             .collect::<Vec<_>>()
     }
+
+    fn output_files(&self) -> BTreeMap<PathBuf, OutputFile> {
+        self.rendered
+            .iter()
+            .map(|(path, content)| (path.to_owned(), OutputFile::of(content)))
+            .chain(
+                self.streamed
+                    .iter()
+                    .map(|(path, file)| (path.to_owned(), file.output)),
+            )
+            .collect()
+    }
+
+    fn output_paths(&self) -> Vec<PathBuf> {
+        self.rendered
+            .keys()
+            .chain(self.streamed.keys())
+            .cloned()
+            .collect()
+    }
 }
 
+/// Written to the output directory and removed again, to check that the
+/// build will be able to write there
+static WRITE_CHECK_FILE: &str = ".docgen-write-check";
+
 pub struct DiskBackedSite {
     config: Config,
+    /// Files written by the current build, hashed as they are written
+    written: BTreeMap<PathBuf, OutputFile>,
+    /// Files listed in the build manifest of the previous build
+    previous: HashMap<PathBuf, OutputFile>,
 }
 
 impl DiskBackedSite {
     pub fn new(config: Config) -> Self {
-        DiskBackedSite { config }
-    }
+        let previous = previous_build(config.out_dir());
 
-    pub fn create_dir(&self) -> Result<()> {
-        fs::create_dir(&self.config.out_dir()).map_err(|e| {
-            Error::io(
-                e,
-                format!(
-                    "Could not create site directory in {}",
-                    self.config.out_dir().display()
-                ),
-            )
-        })
+        DiskBackedSite {
+            config,
+            written: BTreeMap::new(),
+            previous,
+        }
     }
 
-    pub fn delete_dir(&self) -> Result<()> {
-        if self.config.out_dir().exists() {
-            fs::remove_dir_all(&self.config.out_dir()).map_err(|e| {
+    /// Creates the output directory, and makes sure files can be written to
+    /// it by writing one and removing it again. Without this, a build into a
+    /// read-only directory only fails once the first page is written, after
+    /// all the docs were read and rendered.
+    pub fn create_out_dir(&self) -> Result<()> {
+        let out_dir = self.config.out_dir();
+        let check_file = out_dir.join(WRITE_CHECK_FILE);
+
+        fs::create_dir_all(out_dir)
+            .and_then(|_| fs::write(&check_file, b""))
+            .and_then(|_| fs::remove_file(&check_file))
+            .map_err(|e| {
+                let resolved = std::env::current_dir()
+                    .map(|dir| dir.join(out_dir))
+                    .unwrap_or_else(|_| out_dir.to_path_buf());
+
                 Error::io(
                     e,
                     format!(
-                        "Could not clear site directory in {}",
-                        self.config.out_dir().display()
+                        "Could not write to the output directory {}",
+                        resolved.display()
                     ),
                 )
-            })?
+            })
+    }
+
+    /// Where a file of the site is on disk. See `extended_length`.
+    fn out_path(&self, path: &Path) -> PathBuf {
+        extended_length(&self.config.out_dir().join(path))
+    }
+
+    /// Writes the file, unless the previous build wrote the same content to
+    /// it. Unchanged files keep their modification time, so tools that sync
+    /// the output directory don't upload them again.
+    fn write_if_changed(&mut self, path: &Path, content: &[u8]) -> std::io::Result<()> {
+        let output = OutputFile::of(content);
+        if !self.unchanged(path, output) {
+            fs::write(self.out_path(path), content)?;
         }
 
+        self.record(path, output);
         Ok(())
     }
+
+    /// Whether the file is still on disk as the previous build wrote it. Its
+    /// size is checked, in case it was removed or edited since.
+    fn unchanged(&self, path: &Path, output: OutputFile) -> bool {
+        self.previous.get(self.site_path(path)) == Some(&output)
+            && fs::metadata(self.out_path(path))
+                .map(|metadata| metadata.len() == output.size)
+                .unwrap_or(false)
+    }
+
+    /// The path of a file relative to the output directory
+    fn site_path<'p>(&self, path: &'p Path) -> &'p Path {
+        path.strip_prefix(self.config.out_dir()).unwrap_or(path)
+    }
+
+    fn record(&mut self, path: &Path, output: OutputFile) {
+        let path = self.site_path(path).to_owned();
+        self.written.insert(path, output);
+    }
+}
+
+/// The files listed in the build manifest in the output directory, if there
+/// is one
+fn previous_build(out_dir: &Path) -> HashMap<PathBuf, OutputFile> {
+    #[derive(Deserialize)]
+    struct PreviousBuild {
+        files: Vec<ManifestFile>,
+    }
+
+    let previous = fs::read(out_dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|manifest| serde_json::from_slice::<PreviousBuild>(&manifest).ok());
+
+    previous
+        .map(|previous| previous.files)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|file| {
+            let hash = u64::from_str_radix(&file.hash, 16).ok()?;
+            let output = OutputFile {
+                size: file.size,
+                hash,
+            };
+
+            Some((PathBuf::from(file.path), output))
+        })
+        .collect()
 }
 
 impl SiteBackend for DiskBackedSite {
@@ -195,49 +688,74 @@ impl SiteBackend for DiskBackedSite {
         &self.config
     }
 
-    fn add_file(&mut self, path: &Path, content: &Vec<u8>) -> std::io::Result<()> {
-        fs::create_dir_all(
-            self.config
-                .out_dir()
-                .join(path.parent().expect("Path had no parent directory")),
-        )?;
+    fn add_file(&mut self, path: &Path, content: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(self.out_path(path.parent().expect("Path had no parent directory")))?;
 
-        fs::write(self.config.out_dir().join(path), &content)?;
+        self.write_if_changed(path, content)?;
 
         Ok(())
     }
 
     fn copy_file(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
-        fs::create_dir_all(
-            self.config
-                .out_dir()
-                .join(to.parent().expect("Path had no parent directory")),
-        )?;
+        fs::create_dir_all(self.out_path(to.parent().expect("Path had no parent directory")))?;
+
+        // Read and written instead of copied, so the file is only read once
+        // for both copying and hashing
+        let content = fs::read(extended_length(from))?;
+        self.write_if_changed(to, &content)?;
 
-        fs::copy(from, to).map(|_| ())
+        Ok(())
+    }
+
+    fn keep_file(&mut self, path: &Path) -> bool {
+        // Kept files were written by an earlier build. Their hash is taken
+        // from its manifest, and they are only read to be hashed without one.
+        let previous = self.previous.get(self.site_path(path)).copied();
+        if let Some(output) = previous.filter(|output| self.unchanged(path, *output)) {
+            self.record(path, output);
+            return true;
+        }
+
+        match OutputFile::of_file(&self.out_path(path)) {
+            Ok(output) => {
+                self.record(path, output);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn keep_copied_file(&mut self, _from: &Path, _to: &Path) -> bool {
+        false
     }
 
     fn read_path(&self, path: &Path) -> Option<Vec<u8>> {
-        if self.config.out_dir().join(path).exists() {
-            Some(fs::read(self.config.out_dir().join(path)).unwrap())
+        let path = self.out_path(path);
+        if path.exists() {
+            Some(fs::read(path).unwrap())
         } else {
             None
         }
     }
 
-    fn has_file(&self, path: &Path) -> bool {
-        self.config.out_dir().join(path).exists()
+    fn streamed_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
     }
 
-    fn reset(&mut self) -> Result<()> {
-        self.delete_dir()?;
-        self.create_dir()?;
-
-        Ok(())
+    fn has_file(&self, path: &Path) -> bool {
+        self.out_path(path).exists()
     }
 
-    fn build(&mut self, config: Config, root: &Vec<Document>) -> Result<()> {
+    fn build(
+        &mut self,
+        config: Config,
+        root: &Vec<Document>,
+        post_processors: &[Arc<dyn HtmlPostProcessor>],
+    ) -> Result<Vec<Warning>> {
         let mut generator = SiteGenerator::new(config, root);
+        for processor in post_processors {
+            generator.add_post_processor(Arc::clone(processor));
+        }
         generator.run(self)
     }
 
@@ -248,11 +766,78 @@ impl SiteBackend for DiskBackedSite {
             .map(|e| e.path().to_owned())
             .collect::<Vec<_>>()
     }
+
+    fn output_files(&self) -> BTreeMap<PathBuf, OutputFile> {
+        self.written.clone()
+    }
+
+    fn output_paths(&self) -> Vec<PathBuf> {
+        self.written.keys().cloned().collect()
+    }
+}
+
+/// Windows limits paths to MAX_PATH, 260 characters, unless they start with
+/// the `\\?\` prefix. Deeply nested docs easily go past that, so paths on
+/// disk are given the prefix. It only works for absolute paths without `.`
+/// or `..` in them, which the path is turned into first. Paths elsewhere are
+/// left as they are.
+#[cfg(windows)]
+fn extended_length(path: &Path) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let absolute = match std::env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => return path.to_path_buf(),
+    };
+
+    let mut prefix = None;
+    let mut parts: Vec<&std::ffi::OsStr> = vec![];
+    for component in absolute.components() {
+        match component {
+            Component::Prefix(p) => {
+                prefix = match p.kind() {
+                    Prefix::Disk(_) => Some(format!(r"\\?\{}", p.as_os_str().to_string_lossy())),
+                    Prefix::UNC(server, share) => Some(format!(
+                        r"\\?\UNC\{}\{}",
+                        server.to_string_lossy(),
+                        share.to_string_lossy()
+                    )),
+                    // Already extended, or a device
+                    _ => return absolute,
+                }
+            }
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part),
+        }
+    }
+
+    match prefix {
+        Some(prefix) => {
+            let mut extended = std::ffi::OsString::from(prefix);
+            for part in parts {
+                extended.push(r"\");
+                extended.push(part);
+            }
+            PathBuf::from(extended)
+        }
+        None => absolute,
+    }
+}
+
+#[cfg(not(windows))]
+fn extended_length(path: &Path) -> PathBuf {
+    path.to_path_buf()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_helpers::{page, project};
+    use std::sync::Arc;
+    use std::thread;
 
     #[test]
     fn you_can_add_a_file_and_read_it_back() {
@@ -264,11 +849,396 @@ mod test {
 
         let mut site = InMemorySite::new(config);
 
-        site.add_file(&path, &content.into()).unwrap();
+        site.add_file(&path, content.as_bytes()).unwrap();
 
         let uri = Path::new("index.html");
 
         assert_eq!(site.read_path(uri).unwrap(), content.as_bytes());
         assert!(site.has_file(uri));
     }
+
+    #[test]
+    fn rebuilds_are_never_observed_half_done() {
+        let config =
+            Config::from_yaml_str(Path::new("/workspace"), "---\ntitle: Title", false).unwrap();
+        let before = vec![
+            page("README.md", "# Home", &[]),
+            page("deleted.md", "# Deleted", &[]),
+        ];
+        let after = vec![page("README.md", "# Home", &[])];
+
+        let site = Arc::new(Mutex::new(Site::in_memory(config.clone())));
+        site.lock().unwrap().build(config.clone(), &before).unwrap();
+
+        let reader_site = Arc::clone(&site);
+        let reader = thread::spawn(move || {
+            for _ in 0..500 {
+                let site = reader_site.lock().unwrap();
+                assert!(site.backend.has_file(Path::new("index.html")));
+                assert!(site.backend.has_file(Path::new("search_index.json")));
+            }
+        });
+
+        for _ in 0..10 {
+            Site::rebuild(&site, config.clone(), &before).unwrap();
+            Site::rebuild(&site, config.clone(), &after).unwrap();
+        }
+        reader.join().unwrap();
+
+        let site = site.lock().unwrap();
+        assert!(site.backend.has_file(Path::new("index.html")));
+        assert!(!site.backend.has_file(Path::new("deleted.html")));
+    }
+
+    #[test]
+    fn rebuilds_skip_unchanged_includes_and_assets() {
+        let root = project("site", &[("docs/_include/notes.txt", "First")]);
+        let include_dir = root.join("docs").join("_include");
+        fs::write(include_dir.join("video.mp4"), vec![0; 4 * 1024 * 1024]).unwrap();
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let docs = vec![page("README.md", "# Home", &[])];
+
+        let site = Mutex::new(Site::in_memory(config.clone()));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        let first = site.lock().unwrap().backend.asset_counts();
+        assert_eq!(first.skipped, 0);
+
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        let second = site.lock().unwrap().backend.asset_counts();
+        assert_eq!(second.copied, 0);
+        assert_eq!(second.skipped, first.copied);
+
+        fs::write(include_dir.join("notes.txt"), "Second version").unwrap();
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        let third = site.lock().unwrap().backend.asset_counts();
+        assert_eq!(third.copied, 1);
+
+        let site = site.lock().unwrap();
+        assert_eq!(
+            site.backend.read_path(Path::new("notes.txt")).unwrap(),
+            b"Second version"
+        );
+        assert_eq!(
+            site.backend
+                .read_path(Path::new("video.mp4"))
+                .unwrap()
+                .len(),
+            4 * 1024 * 1024
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn large_files_are_streamed_from_disk() {
+        let root = project(
+            "stream",
+            &[
+                ("docs/_include/clip.mp4", "tiny"),
+                ("docs/_include/notes.txt", "Small"),
+            ],
+        );
+        let include_dir = root.join("docs").join("_include");
+        let large = (0..2 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        fs::write(include_dir.join("diagram.png"), &large).unwrap();
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let docs = vec![page("README.md", "# Home", &[])];
+
+        let site = Mutex::new(Site::in_memory(config.clone()));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+
+        {
+            let site = site.lock().unwrap();
+            let usage = site.backend.memory_usage();
+            assert_eq!(usage.streamed_files, 2);
+            assert_eq!(usage.streamed_bytes, large.len() as u64 + 4);
+            assert!(usage.in_memory_bytes < large.len() as u64);
+
+            let png = Path::new("diagram.png");
+            assert!(site.backend.has_file(png));
+            assert_eq!(
+                site.backend.streamed_path(png),
+                Some(include_dir.join("diagram.png"))
+            );
+            assert_eq!(site.backend.read_path(png).unwrap(), large);
+            assert_eq!(site.backend.output_files()[png], OutputFile::of(&large));
+            assert_eq!(site.backend.streamed_path(Path::new("notes.txt")), None);
+        }
+
+        // Unchanged, the file is kept as it is. Changed, it is copied again
+        // and listed as changed.
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        assert!(!site
+            .lock()
+            .unwrap()
+            .backend
+            .changed()
+            .contains(&PathBuf::from("diagram.png")));
+
+        fs::write(include_dir.join("diagram.png"), b"Now small").unwrap();
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        {
+            let site = site.lock().unwrap();
+            assert!(site
+                .backend
+                .changed()
+                .contains(&PathBuf::from("diagram.png")));
+            assert_eq!(site.backend.memory_usage().streamed_files, 1);
+            assert_eq!(
+                site.backend.read_path(Path::new("diagram.png")).unwrap(),
+                b"Now small"
+            );
+        }
+
+        fs::remove_file(include_dir.join("clip.mp4")).unwrap();
+        Site::refresh_includes(&site).unwrap();
+        let site = site.lock().unwrap();
+        assert!(!site.backend.has_file(Path::new("clip.mp4")));
+        assert!(site.backend.changed().contains(&PathBuf::from("clip.mp4")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    struct AppendComment;
+
+    impl HtmlPostProcessor for AppendComment {
+        fn process(&self, doc: &Document, html: String) -> String {
+            format!(
+                "{}<!-- processed {} -->",
+                html,
+                doc.original_path().display()
+            )
+        }
+    }
+
+    #[test]
+    fn post_processors_run_on_every_page() {
+        let config =
+            Config::from_yaml_str(Path::new("/workspace"), "---\ntitle: Title", false).unwrap();
+        let docs = vec![
+            page("README.md", "# Home", &[]),
+            page("guide/setup.md", "# Setup", &[]),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.post_processors.push(Arc::new(AppendComment));
+        site.build(config, &docs).unwrap();
+
+        for (page, source) in [
+            ("index.html", "README.md"),
+            ("guide/setup.html", "guide/setup.md"),
+        ] {
+            let html = String::from_utf8(site.backend.read_path(Path::new(page)).unwrap()).unwrap();
+            assert!(html.ends_with(&format!("<!-- processed {} -->", source)));
+        }
+
+        let search_index = site
+            .backend
+            .read_path(Path::new("search_index.json"))
+            .unwrap();
+        assert!(!String::from_utf8(search_index)
+            .unwrap()
+            .contains("processed"));
+    }
+
+    #[test]
+    fn every_rebuild_gets_a_new_timestamp() {
+        let config =
+            Config::from_yaml_str(Path::new("/workspace"), "---\ntitle: Title", false).unwrap();
+        let docs = vec![page("README.md", "# Home", &[])];
+
+        let site = Mutex::new(Site::in_memory(config.clone()));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        let first = site.lock().unwrap().backend.timestamp().to_owned();
+
+        thread::sleep(std::time::Duration::from_millis(5));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        let second = site.lock().unwrap().backend.timestamp().to_owned();
+
+        assert!(!first.is_empty());
+        assert_ne!(first, second);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn disk_paths_are_extended_to_go_past_max_path() {
+        assert_eq!(
+            extended_length(Path::new(r"C:\docs\site\.\guides\..\index.html")),
+            Path::new(r"\\?\C:\docs\site\index.html")
+        );
+        assert_eq!(
+            extended_length(Path::new(r"\\server\share\site\index.html")),
+            Path::new(r"\\?\UNC\server\share\site\index.html")
+        );
+        assert_eq!(
+            extended_length(Path::new(r"\\?\C:\site\index.html")),
+            Path::new(r"\\?\C:\site\index.html")
+        );
+    }
+
+    #[test]
+    fn unchanged_files_are_not_written_again() {
+        let root = project("unchanged-files", &[]);
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let path = config.out_dir().join("index.html");
+        DiskBackedSite::new(config.clone())
+            .add_file(&path, b"<p>Home</p>")
+            .unwrap();
+        let manifest = format!(
+            r#"{{"files":[{{"path":"index.html","size":11,"hash":"{:016x}"}}]}}"#,
+            content_hash(b"<p>Home</p>")
+        );
+        fs::write(config.out_dir().join(MANIFEST_FILE), manifest).unwrap();
+        let mut site = DiskBackedSite::new(config);
+
+        let earlier = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+        let modified = || fs::metadata(&path).unwrap().modified().unwrap();
+
+        site.add_file(&path, b"<p>Home</p>").unwrap();
+        assert_eq!(modified(), earlier);
+
+        site.add_file(&path, b"<p>Welcome</p>").unwrap();
+        assert_ne!(modified(), earlier);
+        assert_eq!(fs::read(&path).unwrap(), b"<p>Welcome</p>");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rebuilds_record_which_files_changed() {
+        let config =
+            Config::from_yaml_str(Path::new("/workspace"), "---\ntitle: Title", false).unwrap();
+        let docs = vec![
+            page("README.md", "# Home", &[]),
+            page("guide.md", "# Guide", &[]),
+        ];
+
+        let site = Mutex::new(Site::in_memory(config.clone()));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        assert!(!site.lock().unwrap().backend.changed().is_empty());
+
+        thread::sleep(std::time::Duration::from_millis(5));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        assert_eq!(site.lock().unwrap().backend.changed(), &[] as &[PathBuf]);
+
+        let edited = vec![
+            page("README.md", "# Home", &[]),
+            page("guide.md", "# Setup guide", &[]),
+        ];
+        Site::rebuild(&site, config.clone(), &edited).unwrap();
+        let changed = site.lock().unwrap().backend.changed().to_vec();
+        assert!(changed.contains(&PathBuf::from("guide.html")));
+        assert!(changed.contains(&PathBuf::from("search_index.json")));
+        assert!(!changed.contains(&PathBuf::from(MANIFEST_FILE)));
+    }
+
+    #[test]
+    fn timestamps_are_left_out_of_comparisons() {
+        assert_eq!(
+            without_timestamp(b"<script>init(\"123\")</script>123", "123"),
+            Cow::Borrowed(b"<script>init(\"\")</script>".as_slice())
+        );
+        assert_eq!(
+            without_timestamp(b"unchanged", "123"),
+            Cow::Borrowed(b"unchanged".as_slice())
+        );
+    }
+
+    #[test]
+    fn refreshing_includes_leaves_pages_alone() {
+        let root = project(
+            "refresh",
+            &[
+                ("docs/_include/style.css", "a {}"),
+                ("docs/_include/old.txt", "Old"),
+            ],
+        );
+        let include_dir = root.join("docs").join("_include");
+
+        let config = Config::from_yaml_str(&root, "---\ntitle: Title", false).unwrap();
+        let docs = vec![page("README.md", "# Home", &[])];
+
+        let site = Mutex::new(Site::in_memory(config.clone()));
+        Site::rebuild(&site, config.clone(), &docs).unwrap();
+        let page_before = site.lock().unwrap().backend.rendered[Path::new("index.html")].clone();
+
+        fs::write(include_dir.join("style.css"), "a { color: red; }").unwrap();
+        fs::remove_file(include_dir.join("old.txt")).unwrap();
+        Site::refresh_includes(&site).unwrap();
+
+        let site = site.lock().unwrap();
+        let page_after = &site.backend.rendered[Path::new("index.html")];
+        assert!(Arc::ptr_eq(&page_before, page_after));
+        assert_eq!(
+            site.backend.read_path(Path::new("style.css")).unwrap(),
+            b"a { color: red; }"
+        );
+        assert!(!site.backend.has_file(Path::new("old.txt")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn manifest_lists_every_file_with_its_hash() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let docs = vec![
+            page("README.md", "# Home", &[]),
+            page("guide/setup.md", "# Setup", &[]),
+        ];
+
+        let mut site = Site::in_memory(config.clone());
+        site.build(config, &docs).unwrap();
+
+        let manifest_path = Path::new(crate::site_generator::MANIFEST_FILE);
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&site.backend.read_path(manifest_path).unwrap()).unwrap();
+
+        assert_eq!(manifest["docgen_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest["build_mode"], "dev");
+
+        let listed = manifest["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                (
+                    PathBuf::from(file["path"].as_str().unwrap()),
+                    (
+                        file["size"].as_u64().unwrap(),
+                        file["hash"].as_str().unwrap().to_owned(),
+                    ),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let expected = site
+            .backend
+            .list_files()
+            .into_iter()
+            .filter(|path| path != manifest_path)
+            .map(|path| {
+                let content = site.backend.read_path(&path).unwrap();
+                let stamp = (
+                    content.len() as u64,
+                    format!("{:016x}", content_hash(&content)),
+                );
+
+                (path, stamp)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        assert!(listed.contains_key(Path::new("guide/setup.html")));
+        assert_eq!(listed, expected);
+    }
 }
@@ -1,29 +1,78 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
+use std::fmt::Write;
 use std::fs;
-use std::path::Path;
-use std::sync::mpsc::channel;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::sync_channel;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use elasticlunr::Index;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
-use crate::config::Config;
+use crate::broken_links_checker;
+use crate::config::{Config, SearchEngine};
+use crate::docs_finder;
+use crate::head_validator;
+use crate::link_graph::{LinkGraph, RelatedPage};
+use crate::markdown::ast::{Node, AST_VERSION};
+use crate::markdown::extensions::children;
+use crate::markdown::extensions::link_rewriter::{resolve_root_path, rewrite_html_urls};
+use crate::markdown::extensions::toc::Heading;
+use crate::markdown::parser::{truncate_preview, MarkdownParser};
+use crate::markdown::sanitizer::{sanitize, SanitizeMode};
 use crate::navigation::{Link, Navigation};
+use crate::orphans_checker;
+use crate::post_processor::{self, HtmlPostProcessor};
 use crate::site::{BuildMode, SiteBackend};
+use crate::site_report::{self, SiteReport, REPORT_PAGE};
+use crate::warning::Warning;
 use crate::Document;
 use crate::{Error, Result};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 use sitemap_rs::url::Url;
 use sitemap_rs::url_set::UrlSet;
 
-static INCLUDE_DIR: &str = "_include";
+/// The directory of the docs whose files are copied to the root of the site
+pub static INCLUDE_DIR: &str = "_include";
 static HEAD_FILE: &str = "_head.html";
+static SIDEBAR_FOOTER_FILE: &str = "_sidebar_footer.html";
 static LIGHT_SYNTAX_THEME_FILE: &str = "light.css";
 static DARK_SYNTAX_THEME_FILE: &str = "dark.css";
+pub static MANIFEST_FILE: &str = "build_manifest.json";
+/// What built the site, in the generator meta tag of every page
+pub static GENERATOR: &str = concat!("docgen ", env!("CARGO_PKG_VERSION"));
+static SINGLE_FILE_BANNER: &str =
+    "Previewing a single file. Links to other files are shown, but not checked.";
+/// The page the search form submits to, as if it was a page of the docs
+static SEARCH_PAGE: &str = "search.md";
+
+/// Whether a file in the `_include` directory is rendered into pages or
+/// assets, instead of being copied into the site as it is.
+pub fn include_affects_pages(path: &Path) -> bool {
+    [
+        HEAD_FILE,
+        SIDEBAR_FOOTER_FILE,
+        LIGHT_SYNTAX_THEME_FILE,
+        DARK_SYNTAX_THEME_FILE,
+    ]
+    .iter()
+    .any(|name| path.file_name() == Some(OsStr::new(name)))
+}
+
+/// Replaces `{{ docgen_version }}` in the partials and the footer with the
+/// version of Docgen building the site
+pub fn with_template_variables(text: &str) -> Cow<'_, str> {
+    DOCGEN_VERSION_VARIABLE.replace_all(text, env!("CARGO_PKG_VERSION"))
+}
 
 lazy_static! {
+    static ref DOCGEN_VERSION_VARIABLE: regex::Regex =
+        regex::Regex::new(r"\{\{\s*docgen_version\s*\}\}").unwrap();
     static ref DEBUG_SCRIPT: String = {
         let code = r#"document.addEventListener('load', function () {
         // Don't reset scrolling on livereload
@@ -39,7 +88,45 @@ lazy_static! {
         document.getElementById('menu-toggle-switch').addEventListener('change', function (e) {
             disableScrollifMenuOpen();
         });
-    }, false);"#
+    }, false);
+
+    // Shows the warnings of the last build about this page in a corner of
+    // it. They are published by the development server, so this is never
+    // part of a release build.
+    var source = document.currentScript.dataset.source;
+    if (source) {
+        fetch(BASE_PATH + '__docgen/warnings.json?ts=' + DOCGEN_TIMESTAMP)
+            .then(function (response) { return response.json(); })
+            .then(function (warnings) {
+                var messages = warnings[source] || [];
+                if (messages.length === 0) return;
+
+                var overlay = document.createElement('details');
+                overlay.className = 'docgen-warnings';
+                overlay.style.cssText = 'position: fixed; bottom: 16px; right: 16px; z-index: 1000; ' +
+                    'max-width: 480px; max-height: 50vh; overflow: auto; padding: 8px 12px; ' +
+                    'border-radius: 6px; background: #fff8e1; color: #5d4037; ' +
+                    'box-shadow: 0 2px 8px rgba(0, 0, 0, 0.25); font-size: 14px;';
+
+                var summary = document.createElement('summary');
+                summary.style.cssText = 'cursor: pointer; font-weight: bold;';
+                summary.textContent = messages.length + (messages.length === 1 ? ' warning' : ' warnings') +
+                    ' in ' + source;
+                overlay.appendChild(summary);
+
+                var list = document.createElement('ul');
+                list.style.cssText = 'margin: 8px 0 0; padding-left: 20px;';
+                messages.forEach(function (message) {
+                    var item = document.createElement('li');
+                    item.textContent = message;
+                    list.appendChild(item);
+                });
+                overlay.appendChild(list);
+
+                document.body.appendChild(overlay);
+            })
+            .catch(function () {});
+    }"#
             .as_bytes()
             .to_vec();
 
@@ -61,6 +148,107 @@ enum AssetScope {
     Ignore,
 }
 
+/// What is rendered into every page besides its own content
+struct Partials {
+    /// `_head.html` of the `_include` directory, as it is
+    head: Option<String>,
+    /// `_sidebar_footer.html` of the `_include` directory, sanitized
+    sidebar_footer: Option<String>,
+    /// The `content_header` of the config, rendered
+    content_header: Option<String>,
+    /// The `content_footer` of the config, rendered
+    content_footer: Option<String>,
+}
+
+/// What is written next to the content of a page with `emit_fragments`
+#[derive(Serialize)]
+struct FragmentMeta<'a> {
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    uri: &'a str,
+    headings: &'a [Heading],
+}
+
+/// The content of a page as JSON, written next to its HTML with `--emit-ast`
+#[derive(Serialize)]
+struct PageAst<'a> {
+    version: u32,
+    title: &'a str,
+    uri: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<String>,
+    /// YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reviewed: Option<String>,
+    nodes: &'a [Node],
+}
+
+/// The pages before and after a page, in the order of the navigation
+#[derive(Debug, Default, PartialEq)]
+struct Neighbours {
+    prev: Option<RelatedPage>,
+    next: Option<RelatedPage>,
+}
+
+/// A page listed on the page of its directory, along with its own children
+/// if it is a directory itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildPage {
+    pub title: String,
+    pub uri: String,
+    pub description: String,
+    pub children: Vec<ChildPage>,
+}
+
+/// The authors of a page and when it was last reviewed, shown under its
+/// title with `show_page_meta`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageMeta {
+    /// Comma separated
+    pub authors: String,
+    /// The date as YYYY-MM-DD, and in the `date_format` of the site
+    pub reviewed: Option<(String, String)>,
+}
+
+#[derive(Serialize)]
+struct BuildManifest {
+    docgen_version: &'static str,
+    build_mode: String,
+    timestamp: u64,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    /// Seahash of the contents, in hex
+    pub hash: String,
+}
+
+#[derive(Serialize)]
+struct RecentPage<'a> {
+    title: &'a str,
+    uri: &'a str,
+    date: String,
+}
+
+/// A page in the search index of the `simple` search engine
+#[derive(Serialize)]
+struct SimpleSearchPage<'a> {
+    title: &'a str,
+    uri: &'a str,
+    headings: Vec<&'a str>,
+    preview: &'a str,
+    /// The start of the text of the page, without any markup
+    body: String,
+}
+
+/// How many characters of the text of each page the `simple` search
+/// engine matches against
+const SIMPLE_SEARCH_BODY_LENGTH: usize = 2000;
+
 #[derive(Debug)]
 struct Asset {
     id: String,
@@ -71,9 +259,13 @@ struct Asset {
 pub struct SiteGenerator<'a> {
     config: Config,
     root: &'a Vec<Document>,
-    timestamp: String,
+    /// When the build started, since the Unix epoch
+    built_at: Duration,
     scripts: Vec<Asset>,
     stylesheets: Vec<Asset>,
+    warnings: Vec<Warning>,
+    /// Run on the HTML of every page, in order
+    post_processors: Vec<Arc<dyn HtmlPostProcessor>>,
 }
 
 impl<'a> SiteGenerator<'a> {
@@ -86,31 +278,125 @@ impl<'a> SiteGenerator<'a> {
 
         SiteGenerator {
             root,
-            config,
-            timestamp: format!("{}", since_the_epoch.as_secs()),
+            built_at: since_the_epoch,
             scripts: vec![],
             stylesheets: vec![],
+            warnings: vec![],
+            post_processors: post_processor::builtin(&config),
+            config,
+        }
+    }
+
+    /// The port livereload.js connects to, only in development builds. The
+    /// config must have had its server addresses resolved by then.
+    fn livereload_port(&self) -> Option<String> {
+        if let BuildMode::Dev = self.config.build_mode() {
+            Some(self.config.livereload_addr().port().to_string())
+        } else {
+            None
         }
     }
 
-    pub fn run<T: SiteBackend>(&mut self, site: &mut T) -> Result<()> {
+    /// Adds a processor to run on every page, after the built-in ones and
+    /// those added before it
+    pub fn add_post_processor(&mut self, processor: Arc<dyn HtmlPostProcessor>) {
+        self.post_processors.push(processor);
+    }
+
+    pub fn run<T: SiteBackend>(&mut self, site: &mut T) -> Result<Vec<Warning>> {
         let nav_builder = Navigation::new(&self.config);
-        let navigation = nav_builder.build_for(&self.root);
+        let (navigation, nav_warnings) = nav_builder.build_with_warnings(&self.root);
+        self.warnings.extend(nav_warnings);
+        let link_graph = LinkGraph::build(self.root);
+
+        let variables = |html: String| with_template_variables(&html).into_owned();
+        let partials = Partials {
+            head: self.read_head_include()?.map(variables),
+            sidebar_footer: self.read_sidebar_footer()?.map(variables),
+            content_header: self
+                .config
+                .content_header()
+                .map(|markdown| variables(self.render_content_block(markdown))),
+            content_footer: self
+                .config
+                .content_footer()
+                .map(|markdown| variables(self.render_content_block(markdown))),
+        };
+        let search_page = self.search_page();
+        let report_page = self.report_page();
+
+        if !crate::config::is_language_tag(self.config.language()) {
+            self.warnings.push(Warning::new(
+                Path::new("docgen.yaml"),
+                format!(
+                    "`language` doesn't look like a language tag: '{}'. \
+                     Expected something like `en` or `pt-BR`",
+                    self.config.language()
+                ),
+            ));
+        }
 
-        let head_include = self.read_head_include()?;
+        self.warnings
+            .extend(crate::docs_dir_links_checker::check(self.root));
+
+        if self.config.warn_todo_comments() && self.config.build_mode() == BuildMode::Release {
+            self.warnings.extend(crate::todo_checker::check(self.root));
+        }
 
-        self.build_sitemap(site);
+        self.build_sitemap(site)?;
+        self.build_robots(site)?;
         self.build_includes(site)?;
         self.build_assets(site)?;
-        self.build_directory(self.root, &navigation, head_include.as_deref(), site)?;
+        let extra_pages = match &search_page {
+            Some(page) => vec![(page, crate::page_template::SearchPage {}.to_string())],
+            None => vec![],
+        };
+        self.build_directory(
+            self.root,
+            &extra_pages,
+            &navigation,
+            &link_graph,
+            &partials,
+            site,
+        )?;
         self.build_search_index(&self.root, site)?;
+        self.build_recent_pages(&self.root, site)?;
+        self.build_link_graph(&link_graph, site)?;
+        if let Some(report_page) = &report_page {
+            self.build_report(report_page, &navigation, &link_graph, &partials, site)?;
+        }
+        if let Some(head_include) = &partials.head {
+            self.warnings.extend(head_validator::missing_references(
+                &Path::new(INCLUDE_DIR).join(HEAD_FILE),
+                &head_with_root(head_include, self.config.base_path()),
+                self.config.base_path(),
+                |path| site.has_file(path),
+            ));
+        }
+        // Must come last, to list every other file
+        self.build_manifest(site)?;
 
-        Ok(())
+        Ok(std::mem::take(&mut self.warnings))
+    }
+
+    /// Identifies this build in DOCGEN_TIMESTAMP, so browsers can tell
+    /// files of different builds apart. Unique for every rebuild.
+    pub fn timestamp(&self) -> String {
+        self.built_at.as_millis().to_string()
+    }
+
+    /// Copies the includes again without rendering any pages, for when only
+    /// files copied from `_include` changed.
+    pub fn refresh_includes<T: SiteBackend>(&mut self, site: &mut T) -> Result<Vec<Warning>> {
+        self.build_includes(site)?;
+        self.build_manifest(site)?;
+
+        Ok(std::mem::take(&mut self.warnings))
     }
 
-    fn build_sitemap<T: SiteBackend>(&self, site: &mut T) {
+    fn build_sitemap<T: SiteBackend>(&self, site: &mut T) -> Result<()> {
         if self.config.build_mode() == BuildMode::Dev {
-            return;
+            return Ok(());
         }
 
         if let Some(base_url) = self.config.base_url() {
@@ -118,10 +404,18 @@ impl<'a> SiteGenerator<'a> {
             let urls: Vec<Url> = self
                 .root
                 .into_iter()
+                .filter(|doc| !doc.noindex())
                 .filter_map(|doc| {
                     let location = base.join(&doc.uri_path).unwrap().to_string();
+                    // A page that says when it was reviewed was last changed
+                    // then, rather than whenever its file was last touched
+                    let last_modified = doc
+                        .reviewed()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                        .map(|date| Utc.from_utc_datetime(&date))
+                        .unwrap_or_else(|| DateTime::<Utc>::from(doc.last_modified));
                     Url::builder(location)
-                        .last_modified(DateTime::<Utc>::from(doc.last_modified).into())
+                        .last_modified(last_modified.into())
                         .build()
                         .ok()
                 })
@@ -131,34 +425,106 @@ impl<'a> SiteGenerator<'a> {
             let mut buf = Vec::<u8>::new();
             url_set.write(&mut buf).unwrap();
 
-            site.add_file(Path::new("sitemap.xml"), &buf)
-                .map_err(|e| Error::io(e, format!("Could not write sitemap.xml")))
-                .unwrap();
+            site.add_file(&self.config.out_dir().join("sitemap.xml"), &buf)
+                .map_err(|e| Error::io(e, "Could not write sitemap.xml"))?;
+        }
+
+        Ok(())
+    }
+
+    fn build_robots<T: SiteBackend>(&self, site: &mut T) -> Result<()> {
+        let mut robots = String::from("User-agent: *\n");
+
+        if self.config.noindex() {
+            robots.push_str("Disallow: /\n");
+        } else {
+            robots.push_str("Allow: /\n");
+
+            // Only reference the sitemap if build_sitemap generated one
+            if let (BuildMode::Release, Some(base_url)) =
+                (self.config.build_mode(), self.config.base_url())
+            {
+                let sitemap = url::Url::parse(base_url)
+                    .and_then(|base| base.join(&format!("{}sitemap.xml", self.config.base_path())))
+                    .map_err(|e| Error::new(format!("Invalid base_url `{}`: {}", base_url, e)))?;
+
+                robots.push_str(&format!("\nSitemap: {}\n", sitemap));
+            }
         }
+
+        site.add_file(
+            &self.config.out_dir().join("robots.txt"),
+            &robots.into_bytes(),
+        )
+        .map_err(|e| Error::io(e, "Could not write robots.txt"))
     }
 
-    fn read_head_include(&self) -> Result<Option<String>> {
+    fn read_head_include(&mut self) -> Result<Option<String>> {
         let custom_head = self.config.docs_dir().join(INCLUDE_DIR).join(HEAD_FILE);
 
         if custom_head.exists() {
             let content = fs::read_to_string(custom_head)
                 .map_err(|e| Error::io(e, "Could not read custom head include file"))?;
 
+            self.warnings.extend(head_validator::validate(
+                &Path::new(INCLUDE_DIR).join(HEAD_FILE),
+                &content,
+            ));
+
             Ok(Some(content))
         } else {
             Ok(None)
         }
     }
 
+    /// Reads the HTML to show below the navigation, if there is any. It's
+    /// part of the body of every page, so it's sanitized like the pages are.
+    fn read_sidebar_footer(&self) -> Result<Option<String>> {
+        let path = self
+            .config
+            .docs_dir()
+            .join(INCLUDE_DIR)
+            .join(SIDEBAR_FOOTER_FILE);
+
+        if path.exists() {
+            let content = fs::read_to_string(path)
+                .map_err(|e| Error::io(e, "Could not read sidebar footer include file"))?;
+
+            Ok(Some(sanitize(&content, &SanitizeMode::Strict)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Renders the `content_header` or `content_footer` of the config. It's
+    /// rendered once for all pages, and isn't part of any of them, so its
+    /// headings aren't in the page nav and its text isn't searched.
+    fn render_content_block(&self, markdown: &str) -> String {
+        let mut options = self.config.markdown_options();
+        options.emit_ast = false;
+
+        MarkdownParser::new(Some(options)).parse(markdown).html
+    }
+
     /// Copies over all custom includes from the _includes directory
+    ///
+    /// Symlinks that point outside of the project are skipped with a warning,
+    /// unless `allow_external_includes` is set.
     fn build_includes<T: SiteBackend>(&mut self, site: &mut T) -> Result<()> {
         let custom_assets_dir = self.config.docs_dir().join(INCLUDE_DIR);
+        let project_root = self
+            .config
+            .project_root()
+            .canonicalize()
+            .unwrap_or_else(|_| self.config.project_root().to_path_buf());
 
+        let mut copied = vec![];
         for asset in WalkDir::new(&custom_assets_dir)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.path().is_file())
             .filter(|e| e.path().file_name() != Some(OsStr::new(HEAD_FILE)))
+            .filter(|e| e.path().file_name() != Some(OsStr::new(SIDEBAR_FOOTER_FILE)))
             .filter(|e| e.path().file_name() != Some(OsStr::new(DARK_SYNTAX_THEME_FILE)))
             .filter(|e| e.path().file_name() != Some(OsStr::new(LIGHT_SYNTAX_THEME_FILE)))
         {
@@ -167,9 +533,41 @@ impl<'a> SiteGenerator<'a> {
                 .strip_prefix(&custom_assets_dir)
                 .expect("asset directory was not parent of found asset");
 
+            if !self.config.allow_external_includes() {
+                let is_external = asset
+                    .path()
+                    .canonicalize()
+                    .map(|target| !target.starts_with(&project_root))
+                    .unwrap_or(true);
+
+                if is_external {
+                    self.warnings.push(Warning::new(
+                        &Path::new(INCLUDE_DIR).join(stripped_path),
+                        "Include points outside of the project and was not copied. \
+                         Set allow_external_includes: true to copy it anyway.",
+                    ));
+                    continue;
+                }
+            }
+
             let destination = self.config.out_dir().join(stripped_path);
 
-            site.copy_file(asset.path(), &destination)?;
+            if !site.keep_copied_file(asset.path(), &destination) {
+                site.copy_file(asset.path(), &destination)?;
+            }
+            copied.push(Path::new(INCLUDE_DIR).join(stripped_path));
+        }
+
+        let copied = copied.iter().map(|path| path.as_path()).collect::<Vec<_>>();
+        for paths in docs_finder::case_collisions(&copied) {
+            self.warnings.push(Warning::new(
+                paths[0],
+                format!(
+                    "Has the same path as {} when case is ignored. {}",
+                    docs_finder::display_paths(&paths[1..]),
+                    docs_finder::CASE_COLLISION_EFFECT
+                ),
+            ));
         }
 
         Ok(())
@@ -177,17 +575,32 @@ impl<'a> SiteGenerator<'a> {
 
     /// Builds fixed assets required by Docgen
     fn build_assets<T: SiteBackend>(&mut self, site: &mut T) -> Result<()> {
-        self.scripts
-            .push(self.export_asset(site, "mermaid.min.js", "assets", AssetScope::Diagram));
-
-        self.scripts
-            .push(self.export_asset(site, "elasticlunr.min.js", "assets", AssetScope::App));
+        self.scripts.push(self.export_asset(
+            site,
+            "mermaid.min.js",
+            "assets",
+            AssetScope::Diagram,
+        )?);
+
+        // The simple search engine matches pages without it
+        if self.config.search_engine() == SearchEngine::Elasticlunr {
+            self.scripts.push(self.export_asset(
+                site,
+                "elasticlunr.min.js",
+                "assets",
+                AssetScope::App,
+            )?);
+        }
 
         self.scripts
-            .push(self.export_asset(site, "app.js", "assets", AssetScope::App));
+            .push(self.export_asset(site, "app.js", "assets", AssetScope::App)?);
 
-        self.stylesheets
-            .push(self.export_asset(site, "normalize.css", "assets", AssetScope::App));
+        self.stylesheets.push(self.export_asset(
+            site,
+            "normalize.css",
+            "assets",
+            AssetScope::App,
+        )?);
 
         #[cfg(feature = "katex")]
         {
@@ -196,10 +609,10 @@ impl<'a> SiteGenerator<'a> {
                 "katex.min.css",
                 "assets",
                 AssetScope::Math,
-            ));
+            )?);
 
             // Add fonts
-            for font in crate::ASSETS
+            for font in crate::assets::ASSETS
                 .get_dir("fonts")
                 .unwrap()
                 .entries()
@@ -213,7 +626,7 @@ impl<'a> SiteGenerator<'a> {
                     .join("fonts")
                     .join(font.path().file_name().unwrap());
 
-                if !asset_path.exists() {
+                if !site.keep_file(&asset_path) {
                     site.add_file(&asset_path, &Vec::from(font.contents()))
                         .map_err(|e| {
                             Error::io(e, "Could not write katex fonts to assets directory")
@@ -231,14 +644,18 @@ impl<'a> SiteGenerator<'a> {
         if custom_light_theme.exists() {
             self.stylesheets.push(self.export_file(
                 site,
-                crate::ASSETS_MAP.get("light.css").unwrap(),
+                crate::assets::hashed_name("light.css")?,
                 "assets",
                 fs::read(custom_light_theme)?.as_slice(),
                 AssetScope::Code,
-            ));
+            )?);
         } else {
-            self.stylesheets
-                .push(self.export_asset(site, "light.css", "assets", AssetScope::App));
+            self.stylesheets.push(self.export_asset(
+                site,
+                "light.css",
+                "assets",
+                AssetScope::App,
+            )?);
         }
 
         let custom_dark_theme = self
@@ -250,49 +667,88 @@ impl<'a> SiteGenerator<'a> {
         if custom_dark_theme.exists() {
             self.stylesheets.push(self.export_file(
                 site,
-                crate::ASSETS_MAP.get("dark.css").unwrap(),
+                crate::assets::hashed_name("dark.css")?,
                 "assets",
                 fs::read(custom_dark_theme)?.as_slice(),
                 AssetScope::Code,
-            ));
+            )?);
         } else {
-            self.stylesheets
-                .push(self.export_asset(site, "dark.css", "assets", AssetScope::App));
+            self.stylesheets.push(self.export_asset(
+                site,
+                "dark.css",
+                "assets",
+                AssetScope::App,
+            )?);
         }
 
         self.stylesheets
-            .push(self.export_asset(site, "style.css", "assets", AssetScope::App));
+            .push(self.export_asset(site, "style.css", "assets", AssetScope::App)?);
 
         Ok(())
     }
 
+    /// Renders the pages of the docs, and the extra pages with the HTML
+    /// they are given as their content
     fn build_directory<T: SiteBackend>(
         &self,
         docs: &Vec<Document>,
+        extra_pages: &[(&Document, String)],
         nav: &[Link],
-        head_include: Option<&str>,
+        link_graph: &LinkGraph,
+        partials: &Partials,
         site: &mut T,
     ) -> Result<()> {
-        let side_navigation = crate::page_template::SideNavigation { navigation: nav }.to_string();
-        let header = crate::page_template::PageHeader {
-            base_path: self.config.base_path(),
-            logo: self.config.logo(),
-            project_title: self.config.title(),
-            project_subtitle: self.config.subtitle(),
-        }
-        .to_string();
-        let init_script = self.init_script();
-        let livereload_script_path = if let BuildMode::Dev = self.config.build_mode() {
-            let asset = self.export_asset(site, "livereload.min.js", "assets", AssetScope::Debug);
-            Some(format!("{}{}", self.config.base_path(), asset.path))
+        // With relative links every page links to the rest of the site
+        // differently, so the navigation and header are built for each page
+        let side_navigation: Option<Arc<str>> = if self.config.relative_links() {
+            None
         } else {
+            Some(
+                crate::page_template::SideNavigation {
+                    navigation: nav,
+                    footer: partials.sidebar_footer.as_deref(),
+                }
+                .to_string()
+                .into(),
+            )
+        };
+        let header: Option<Arc<str>> = if self.config.relative_links() {
             None
+        } else {
+            Some(self.build_page_header(self.config.base_path()).into())
         };
-        let livereload_port = if let BuildMode::Dev = self.config.build_mode() {
-            Some(self.config.livereload_addr().port().to_string())
+        let init_script: Arc<str> = self.init_script().into();
+        let year = Utc::now().year();
+        // Like the header, these link to files of the site, from each page
+        // when the links are relative
+        let footer = if self.config.relative_links() {
+            None
+        } else {
+            self.config
+                .footer()
+                .as_ref()
+                .map(|footer| footer.rendered(year, self.config.base_path()))
+        };
+        let custom_head = if self.config.relative_links() {
+            None
+        } else {
+            partials
+                .head
+                .as_deref()
+                .map(|head| head_with_root(head, self.config.base_path()))
+        };
+        let livereload_asset = if let BuildMode::Dev = self.config.build_mode() {
+            Some(self.export_asset(site, "livereload.min.js", "assets", AssetScope::Debug)?)
         } else {
             None
         };
+        let livereload_port = self.livereload_port();
+
+        // Analytics are never included in development builds
+        let analytics = match self.config.build_mode() {
+            BuildMode::Release => self.config.analytics().map(|a| a.script_tags()),
+            BuildMode::Dev => None,
+        };
 
         let site_title = self.config.title().to_string();
         let meta_title = self
@@ -301,9 +757,26 @@ impl<'a> SiteGenerator<'a> {
             .as_ref()
             .map(|a| a.title.as_ref().unwrap_or_else(|| &site_title));
 
-        let (sender, receiver) = channel();
+        let docs_by_uri = docs
+            .iter()
+            .map(|doc| (doc.uri_path.as_str(), doc))
+            .collect::<HashMap<_, _>>();
 
-        docs.par_iter().for_each_with(sender, |sender, doc| {
+        let neighbours = if self.config.prev_next_links() {
+            page_neighbours(docs, nav, self.config.base_path())
+        } else {
+            HashMap::new()
+        };
+
+        // Renders a page with the given HTML as its content into the buffer,
+        // returning the files to write for it with the page first. The pages
+        // of the docs have an `index` in the link graph, extra pages like the
+        // search page have none.
+        let render = |mut buffer: String,
+                      index: Option<usize>,
+                      doc: &Document,
+                      html: &String|
+         -> Vec<(PathBuf, Vec<u8>)> {
             let page_subtitle = if doc.uri_path == "/" {
                 None
             } else if let Some(meta_title) = meta_title {
@@ -312,98 +785,651 @@ impl<'a> SiteGenerator<'a> {
                 None
             };
 
-            let data = crate::page_template::Page {
-                content: doc.html(),
-                headings: doc.headings(),
+            let link_root = self.link_root(doc);
+            let side_navigation = match &side_navigation {
+                Some(side_navigation) => Arc::clone(side_navigation),
+                None => crate::page_template::SideNavigation {
+                    navigation: &self.relative_navigation(doc, nav),
+                    footer: partials.sidebar_footer.as_deref(),
+                }
+                .to_string()
+                .into(),
+            };
+            let header = match &header {
+                Some(header) => Arc::clone(header),
+                None => self.build_page_header(&link_root).into(),
+            };
+            let (page_footer, page_head);
+            let (footer, custom_head) = if self.config.relative_links() {
+                page_footer = self
+                    .config
+                    .footer()
+                    .as_ref()
+                    .map(|footer| footer.rendered(year, &link_root));
+                page_head = partials
+                    .head
+                    .as_deref()
+                    .map(|head| head_with_root(head, &link_root));
+                (&page_footer, page_head.as_deref())
+            } else {
+                (&footer, custom_head.as_deref())
+            };
+            let livereload_script_path = livereload_asset
+                .as_ref()
+                .map(|asset| format!("{}{}", link_root, asset.path));
+
+            let mut related_pages = match index {
+                Some(index) if doc.shows_related_pages() => {
+                    link_graph.related(index, self.config.related_pages())
+                }
+                _ => vec![],
+            };
+            for page in related_pages.iter_mut() {
+                page.uri = self.href(doc, &page.uri);
+            }
+
+            let neighbours = neighbours.get(&doc.uri_path);
+            let neighbour = |page: Option<&RelatedPage>| {
+                page.map(|page| RelatedPage {
+                    title: page.title.clone(),
+                    uri: self.href(doc, &page.uri),
+                })
+            };
+            let prev = neighbour(neighbours.and_then(|n| n.prev.as_ref()));
+            let next = neighbour(neighbours.and_then(|n| n.next.as_ref()));
+
+            let lang = doc.lang.as_deref().unwrap_or(self.config.language());
+            let page_description = doc.meta_description(lang);
+
+            // The listing and the page meta are built from escaped
+            // frontmatter, and are added after the page's own HTML was
+            // sanitized
+            let mut content = Cow::Borrowed(html);
+            if lists_children(doc) {
+                let mut children = child_pages(doc, &docs_by_uri, nav, self.config.base_path());
+                self.href_children(doc, &mut children);
+                content = Cow::Owned(with_children(
+                    &content,
+                    &crate::page_template::ChildrenList { pages: &children }.to_string(),
+                ));
+            }
+            if let Some(meta) = page_meta(doc, &self.config) {
+                content = Cow::Owned(with_page_meta(
+                    &content,
+                    &crate::page_template::PageMetaLine { meta: &meta }.to_string(),
+                ));
+            }
+            // Only the pages of the docs have them, not extra pages like the
+            // search page
+            if let Some(header) = &partials.content_header {
+                if index.is_some() && doc.shows_content_header() {
+                    content = Cow::Owned(format!(
+                        "<div class=\"content-header\">{}</div>{}",
+                        header, content
+                    ));
+                }
+            }
+            if let Some(footer) = &partials.content_footer {
+                if index.is_some() && doc.shows_content_footer() {
+                    content = Cow::Owned(format!(
+                        "{}<div class=\"content-footer\">{}</div>",
+                        content, footer
+                    ));
+                }
+            }
+
+            // Previews of a single file have no other pages to check
+            // their links against
+            let banner = if self.config.single_file() && links_to_local_files(doc) {
+                Some(SINGLE_FILE_BANNER)
+            } else {
+                None
+            };
+
+            let heading_tree = Heading::build_tree(doc.headings());
+            // For the warnings overlay, which only knows the pages of the docs
+            let source_path =
+                index.map(|_| doc.original_path().to_string_lossy().replace('\\', "/"));
+            let body_class = doc.body_class();
+            let page = crate::page_template::Page {
+                content: &content,
+                banner,
+                related_pages: &related_pages,
+                prev,
+                next,
+                headings: &heading_tree,
+                layout: doc.layout(),
+                body_class: &body_class,
                 build_mode: self.config.build_mode(),
                 page_title: &doc.title,
-                page_description: &doc.description,
+                page_description: &page_description,
+                lang,
                 page_subtitle,
+                noindex: self.config.noindex() || doc.noindex(),
+                generator: GENERATOR,
+                relative_root: if self.config.relative_links() {
+                    Some(link_root.as_str())
+                } else {
+                    None
+                },
 
-                edit_link: self.config.build_edit_link(&doc.path),
+                edit_link: index.and_then(|_| self.config.build_edit_link(doc)),
 
                 head_links: self.build_header(&doc),
                 foot_links: self.build_footer(&doc),
 
-                footer: self.config.footer(),
+                footer,
 
-                custom_head: head_include,
-                header: &header,
-                navigation: &side_navigation,
-                init_script: &init_script,
+                custom_head,
+                analytics: analytics.as_deref(),
+                header,
+                navigation: side_navigation,
+                init_script: Arc::clone(&init_script),
                 dev_script: &DEBUG_SCRIPT,
+                source_path: source_path.as_deref(),
                 livereload_script_path: livereload_script_path.as_deref(),
                 livereload_port: livereload_port.as_deref(),
+            };
+
+            buffer.clear();
+            write!(buffer, "{}", page).unwrap();
+
+            let destination = doc.destination(self.config.out_dir());
+            let html = self
+                .post_processors
+                .iter()
+                .fold(buffer, |html, processor| processor.process(doc, html));
+            let mut files = vec![(destination.clone(), html.into_bytes())];
+
+            // The content as it is in the page, before the post
+            // processors rewrote the whole page
+            if self.config.emit_fragments() && index.is_some() {
+                files.push((
+                    destination.with_extension("fragment.html"),
+                    content.as_bytes().to_vec(),
+                ));
+
+                let meta = FragmentMeta {
+                    title: &doc.title,
+                    description: doc.description.as_deref(),
+                    uri: &doc.uri_path,
+                    headings: doc.headings(),
+                };
+                files.push((
+                    destination.with_extension("meta.json"),
+                    serde_json::to_vec(&meta).unwrap(),
+                ));
             }
-            .to_string();
 
-            sender
-                .send((doc.destination(self.config.out_dir()), data.into_bytes()))
-                .unwrap();
-        });
+            if let Some(nodes) = doc.ast() {
+                let ast = PageAst {
+                    version: AST_VERSION,
+                    title: &doc.title,
+                    uri: &doc.uri_path,
+                    authors: doc.authors(),
+                    reviewed: doc
+                        .reviewed()
+                        .map(|date| date.format("%Y-%m-%d").to_string()),
+                    nodes,
+                };
+                files.push((
+                    destination.with_extension("json"),
+                    serde_json::to_vec(&ast).unwrap(),
+                ));
+            }
+
+            files
+        };
+
+        // Pages are written as soon as they are rendered, by this thread
+        // alone. The channel only holds a few pages at a time, and the
+        // buffers of written pages are handed back to render the next ones.
+        let buffers = Mutex::new(Vec::new());
+        let take_buffer = || buffers.lock().unwrap().pop().unwrap_or_default();
+        let (sender, receiver) = sync_channel(rayon::current_num_threads());
+
+        // Pages that could not be written don't stop the others, so that
+        // all of them are reported at once
+        let mut failed_writes = vec![];
+        let mut write = |files: Vec<(PathBuf, Vec<u8>)>| {
+            for (i, (destination, mut content)) in files.into_iter().enumerate() {
+                if let Err(e) = site.add_file(&destination, &content) {
+                    failed_writes.push((destination, e));
+                }
+                if i == 0 {
+                    content.clear();
+                    buffers
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8(content).unwrap());
+                }
+            }
+        };
 
-        receiver.iter().for_each(|(dest, content)| {
-            site.add_file(&dest, &content).unwrap();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                docs.par_iter()
+                    .enumerate()
+                    .for_each_with(sender, |sender, (index, doc)| {
+                        let files = render(take_buffer(), Some(index), doc, doc.html());
+                        sender.send(files).unwrap();
+                    });
+            });
+
+            receiver.iter().for_each(&mut write);
         });
 
-        Ok(())
+        for (page, html) in extra_pages {
+            write(render(take_buffer(), None, page, html));
+        }
+
+        if failed_writes.is_empty() {
+            return Ok(());
+        }
+
+        Err(Error::new(format!(
+            "Could not write {} page(s):\n\n{}",
+            failed_writes.len(),
+            failed_writes
+                .iter()
+                .map(|(path, e)| format!("\t{}: {}", path.display(), e))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )))
+    }
+
+    /// Exports the links between pages, for analysis outside of Docgen
+    fn build_link_graph<T: SiteBackend>(&self, link_graph: &LinkGraph, site: &mut T) -> Result<()> {
+        site.add_file(
+            &self.config.out_dir().join("link_graph.json"),
+            &link_graph.to_json().into_bytes(),
+        )
+        .map_err(|e| Error::io(e, "Could not create link graph"))
+    }
+
+    /// Lists every file of the site with its size and hash, so that deploys
+    /// can upload only the files that changed
+    fn build_manifest<T: SiteBackend>(&self, site: &mut T) -> Result<()> {
+        let files = site
+            .output_files()
+            .into_iter()
+            .map(|(path, file)| ManifestFile {
+                path: path.to_string_lossy().replace('\\', "/"),
+                size: file.size,
+                hash: format!("{:016x}", file.hash),
+            })
+            .collect();
+
+        let manifest = BuildManifest {
+            docgen_version: env!("CARGO_PKG_VERSION"),
+            build_mode: self.config.build_mode().to_string(),
+            timestamp: self.built_at.as_secs(),
+            files,
+        };
+
+        site.add_file(
+            &self.config.out_dir().join(MANIFEST_FILE),
+            &serde_json::to_vec(&manifest).unwrap(),
+        )
+        .map_err(|e| Error::io(e, "Could not create build manifest"))
     }
 
     fn build_search_index<T: SiteBackend>(&self, root: &Vec<Document>, site: &mut T) -> Result<()> {
-        let mut index = Index::new(&["title", "uri", "body", "preview"], Some(vec!["body"]));
+        if self.config.search_engine() == SearchEngine::Simple {
+            return self.build_simple_search_index(root, site);
+        }
+
+        let mut index = Index::new(
+            &["title", "uri", "body", "preview", "keywords"],
+            Some(vec!["body", "keywords"]),
+        );
 
         self.build_search_index_for_dir(root, &mut index);
 
         {
             site.add_file(
                 &self.config.out_dir().join("search_index.json"),
-                &index.to_json().as_bytes().into(),
+                index.to_json().as_bytes(),
             )
             .map_err(|e| Error::io(e, "Could not create search index"))
         }
     }
 
     fn build_search_index_for_dir(&self, docs: &Vec<Document>, index: &mut Index) {
-        for doc in docs {
+        let synonyms = self.config.search_synonyms();
+
+        for doc in docs.iter().filter(|doc| doc.listed()) {
+            let mut body = doc.html().to_owned();
+            for synonym in synonyms_in(&format!("{} {}", doc.title, body), synonyms) {
+                body.push(' ');
+                body.push_str(synonym);
+            }
+
+            let mut keywords = doc.keywords().join(" ");
+            for synonym in synonyms_in(&keywords, synonyms) {
+                keywords.push(' ');
+                keywords.push_str(synonym);
+            }
+
             index.add_doc(
                 &doc.id.to_string(),
-                &[&doc.title, &doc.uri_path, doc.html(), doc.preview()],
+                &[
+                    &doc.title,
+                    self.search_uri(doc),
+                    &body,
+                    doc.preview(),
+                    &keywords,
+                ],
             );
         }
     }
 
+    /// Exports the title, headings and text of every page as a plain list,
+    /// which the `simple` search engine looks through in order
+    fn build_simple_search_index<T: SiteBackend>(
+        &self,
+        root: &[Document],
+        site: &mut T,
+    ) -> Result<()> {
+        let pages = root
+            .iter()
+            .filter(|doc| doc.listed())
+            .map(|doc| SimpleSearchPage {
+                title: &doc.title,
+                uri: self.search_uri(doc),
+                headings: doc
+                    .headings()
+                    .iter()
+                    .map(|heading| heading.unnumbered_title())
+                    .collect(),
+                preview: doc.preview(),
+                body: truncate_preview(&plain_text(doc.html()), SIMPLE_SEARCH_BODY_LENGTH),
+            })
+            .collect::<Vec<_>>();
+
+        site.add_file(
+            &self.config.out_dir().join("search_index.json"),
+            &serde_json::to_vec(&pages).unwrap(),
+        )
+        .map_err(|e| Error::io(e, "Could not create search index"))
+    }
+
+    /// The URI of the page in files read by the search. Relative URIs are
+    /// resolved against BASE_PATH in the browser.
+    fn search_uri<'d>(&self, doc: &'d Document) -> &'d str {
+        if self.config.relative_links() {
+            doc.uri_path
+                .strip_prefix(self.config.base_path())
+                .unwrap_or(&doc.uri_path)
+        } else {
+            &doc.uri_path
+        }
+    }
+
+    /// Exports the most recently updated pages, shown by the search before
+    /// anything is typed in
+    fn build_recent_pages<T: SiteBackend>(&self, root: &Vec<Document>, site: &mut T) -> Result<()> {
+        let mut docs = root.iter().filter(|doc| doc.listed()).collect::<Vec<_>>();
+        docs.sort_by(|a, b| {
+            b.last_modified
+                .cmp(&a.last_modified)
+                .then_with(|| a.uri_path.cmp(&b.uri_path))
+        });
+
+        let recent = docs
+            .into_iter()
+            .take(self.config.search_recent_count())
+            .map(|doc| RecentPage {
+                title: &doc.title,
+                uri: self.search_uri(doc),
+                date: DateTime::<Utc>::from(doc.last_modified)
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+            })
+            .collect::<Vec<_>>();
+
+        site.add_file(
+            &self.config.out_dir().join("recent.json"),
+            &serde_json::to_vec(&recent).unwrap(),
+        )
+        .map_err(|e| Error::io(e, "Could not create list of recent pages"))
+    }
+
     fn build_header(&self, doc: &Document) -> String {
+        let link_root = self.link_root(doc);
+
         compile_assets(&self.stylesheets, doc, &|asset: &Asset| {
             format!(
                 "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}{}\">",
-                self.config.base_path(),
-                asset.path
+                link_root, asset.path
             )
         })
     }
 
     fn build_footer(&self, doc: &Document) -> String {
+        let link_root = self.link_root(doc);
+
         compile_assets(&self.scripts, doc, &|asset: &Asset| {
             format!(
                 "<script id=\"{}\" async defer type=\"text/javascript\" src=\"{}{}\"></script>",
-                asset.id,
-                self.config.base_path(),
-                asset.path
+                asset.id, link_root, asset.path
             )
         })
     }
 
+    fn build_page_header(&self, link_root: &str) -> String {
+        let search_page = if self.config.clean_urls() {
+            "search/"
+        } else {
+            "search"
+        };
+
+        // The logo is a file of `_include`, which is copied to the root
+        let logo = self.config.logo().map(|logo| {
+            resolve_root_path(&format!("/{}", logo.trim_start_matches('/')), link_root)
+        });
+
+        crate::page_template::PageHeader {
+            base_path: link_root,
+            search_action: &format!("{}{}", link_root, search_page),
+            logo: logo.as_deref(),
+            project_title: self.config.title(),
+            project_subtitle: self.config.subtitle(),
+            search_max_results: self.config.search_max_results(),
+            search_engine: match self.config.search_engine() {
+                SearchEngine::Elasticlunr => "elasticlunr",
+                SearchEngine::Simple => "simple",
+            },
+        }
+        .to_string()
+    }
+
+    /// The page the search form of the header submits to, which shows the
+    /// results for its `q` parameter. It is built like a page of the docs,
+    /// but isn't one: it's left out of the navigation, the search index and
+    /// the sitemap. A page of the docs at the same URI takes its place.
+    fn search_page(&mut self) -> Option<Document> {
+        let mut options = self.config.markdown_options();
+        options.emit_ast = false;
+
+        let frontmatter = BTreeMap::from([
+            ("title".to_string(), serde_yaml::Value::from("Search")),
+            ("layout".to_string(), serde_yaml::Value::from("wide")),
+            ("noindex".to_string(), serde_yaml::Value::from(true)),
+            ("related_pages".to_string(), serde_yaml::Value::from(false)),
+        ]);
+        let page = Document::with_options(
+            Path::new(SEARCH_PAGE),
+            String::new(),
+            Some(frontmatter),
+            options,
+            SystemTime::now(),
+        );
+
+        match self.root.iter().find(|doc| doc.uri_path == page.uri_path) {
+            Some(doc) => {
+                self.warnings.push(Warning::new(
+                    doc.original_path(),
+                    format!(
+                        "Is at {}, where the search page would be. \
+                         Searching from the header opens this page instead",
+                        page.uri_path
+                    ),
+                ));
+                None
+            }
+            None => Some(page),
+        }
+    }
+
+    /// The page of the site report, with `site_report`. Like the search page,
+    /// it is built like a page of the docs without being one, and a page of
+    /// the docs at the same URI takes its place.
+    fn report_page(&mut self) -> Option<Document> {
+        if !self.config.site_report() {
+            return None;
+        }
+
+        let mut options = self.config.markdown_options();
+        options.emit_ast = false;
+
+        let frontmatter = BTreeMap::from([
+            ("title".to_string(), serde_yaml::Value::from("Site report")),
+            ("layout".to_string(), serde_yaml::Value::from("wide")),
+            ("noindex".to_string(), serde_yaml::Value::from(true)),
+            ("related_pages".to_string(), serde_yaml::Value::from(false)),
+        ]);
+        let page = Document::with_options(
+            Path::new(REPORT_PAGE),
+            String::new(),
+            Some(frontmatter),
+            options,
+            SystemTime::now(),
+        );
+
+        match self.root.iter().find(|doc| doc.uri_path == page.uri_path) {
+            Some(doc) => {
+                self.warnings.push(Warning::new(
+                    doc.original_path(),
+                    format!(
+                        "Is at {}, where the site report would be. \
+                         The site report is left out",
+                        page.uri_path
+                    ),
+                ));
+                None
+            }
+            None => Some(page),
+        }
+    }
+
+    /// Writes the site report. It comes after the rest of the site, so that
+    /// links are checked against every other file of it.
+    fn build_report<T: SiteBackend>(
+        &mut self,
+        page: &Document,
+        nav: &[Link],
+        link_graph: &LinkGraph,
+        partials: &Partials,
+        site: &mut T,
+    ) -> Result<()> {
+        let orphans = orphans_checker::orphans(self.root, nav, site);
+        let broken_links = broken_links_checker::broken_links_in(self.root, site);
+        let mut warnings = docs_finder::warnings(self.root, &self.config);
+        warnings.extend(self.warnings.iter().cloned());
+
+        let mut report = SiteReport::build(
+            self.root,
+            Utc::now().date_naive(),
+            &orphans,
+            &broken_links,
+            &warnings,
+            &site_report::read_history(&self.config),
+        );
+        report.map_uris(|uri| self.href(page, uri));
+
+        // Previews and dry runs only show the history, only builds add to it
+        if !site.in_memory() {
+            if let Err(e) = site_report::write_history(&self.config, &report) {
+                let cache_dir = self.config.cache_dir();
+                self.warnings.push(Warning::new(
+                    cache_dir
+                        .strip_prefix(self.config.project_root())
+                        .unwrap_or(cache_dir),
+                    format!("Could not keep the broken links of the site report: {}", e),
+                ));
+            }
+        }
+
+        let html = crate::page_template::SiteReportContent { report: &report }.to_string();
+        self.build_directory(&vec![], &[(page, html)], nav, link_graph, partials, site)
+    }
+
+    /// What links from the page to files of the site start with: the
+    /// `base_path`, or the way back to the root with relative links
+    fn link_root(&self, doc: &Document) -> String {
+        if self.config.relative_links() {
+            doc.relative_root()
+        } else {
+            self.config.base_path().to_string()
+        }
+    }
+
+    /// Links from the page to the given URI of another page. The URI is
+    /// returned as it is unless the site uses relative links.
+    fn href(&self, doc: &Document, uri: &str) -> String {
+        if !self.config.relative_links() {
+            return uri.to_string();
+        }
+
+        match uri.strip_prefix(self.config.base_path()) {
+            Some(path) => format!("{}{}", doc.relative_root(), path),
+            None => uri.to_string(),
+        }
+    }
+
+    /// Points the links of a listing of children at their pages from `doc`
+    fn href_children(&self, doc: &Document, children: &mut [ChildPage]) {
+        for child in children {
+            child.uri = self.href(doc, &child.uri);
+            self.href_children(doc, &mut child.children);
+        }
+    }
+
+    /// The navigation with links relative to the page
+    fn relative_navigation(&self, doc: &Document, nav: &[Link]) -> Vec<Link> {
+        nav.iter()
+            .map(|link| Link {
+                src: link.src.clone(),
+                children: self.relative_navigation(doc, &link.children),
+                path: self.href(doc, &link.path),
+                title: link.title.clone(),
+            })
+            .collect()
+    }
+
     fn init_script(&self) -> String {
+        // Pages with relative links don't know where the site is hosted, so
+        // the base path is worked out from their way back to the root
+        let base_path = if self.config.relative_links() {
+            String::from(
+                "new URL(document.documentElement.dataset.root, document.location.href).pathname",
+            )
+        } else {
+            format!("\"{}\"", self.config.base_path())
+        };
+
         let init_script = format!(
             r#"var DOCGEN_TIMESTAMP = "{}";
-    var BASE_PATH = "{}";
+    var DOCGEN_VERSION = "{}";
+    var BASE_PATH = {};
 
     window.onload = function() {{
         document.body.classList.remove("preload");
     }}
 
     document.addEventListener("DOMContentLoaded", function() {{
-        const link = document.querySelector(`.site-nav a[href="${{document.location.pathname}}"]`);
+        const link = Array.from(document.querySelectorAll(".site-nav a"))
+            .find((a) => a.pathname === document.location.pathname);
         if (link) {{
             const listItemElement = link.closest("li");
             if (listItemElement) listItemElement.classList.add("active");
@@ -428,8 +1454,9 @@ impl<'a> SiteGenerator<'a> {
       }}
       
       setColor();"#,
-            &self.timestamp,
-            self.config.base_path(),
+            self.timestamp(),
+            env!("CARGO_PKG_VERSION"),
+            base_path,
         )
         .as_bytes()
         .to_vec();
@@ -451,8 +1478,8 @@ impl<'a> SiteGenerator<'a> {
         filename: &str,
         dir: &str,
         scope: AssetScope,
-    ) -> Asset {
-        let dest_filename = crate::ASSETS_MAP.get(filename).unwrap();
+    ) -> Result<Asset> {
+        let dest_filename = crate::assets::hashed_name(filename)?;
         let asset = Asset {
             path: format!("{}/{}", dir, dest_filename),
             scope,
@@ -460,25 +1487,20 @@ impl<'a> SiteGenerator<'a> {
         };
         let export_path = self.config.out_dir().join(dir).join(dest_filename);
 
-        if export_path.exists() && !site.in_memory() {
-            asset
-        } else {
-            let data = crate::ASSETS
-                .get_file(dest_filename)
-                .expect("Failed to get")
-                .contents();
-
-            site.add_file(&export_path, &data.to_vec())
-                .map_err(|e| {
-                    Error::io(
-                        e,
-                        format!("Could not write {} to {} directory", filename, dir),
-                    )
-                })
-                .unwrap();
-
-            asset
+        // Built in assets have the hash of their contents in their filename,
+        // so a file with the same name never has to be written again
+        if !site.keep_file(&export_path) {
+            let data = crate::assets::contents(dest_filename)?;
+
+            site.add_file(&export_path, data).map_err(|e| {
+                Error::io(
+                    e,
+                    format!("Could not write {} to {} directory", filename, dir),
+                )
+            })?;
         }
+
+        Ok(asset)
     }
 
     fn export_file<T: SiteBackend>(
@@ -488,7 +1510,7 @@ impl<'a> SiteGenerator<'a> {
         dir: &str,
         data: &[u8],
         scope: AssetScope,
-    ) -> Asset {
+    ) -> Result<Asset> {
         let asset = Asset {
             path: format!("{}/{}", dir, filename),
             scope,
@@ -496,21 +1518,245 @@ impl<'a> SiteGenerator<'a> {
         };
         let export_path = self.config.out_dir().join(dir).join(filename);
 
-        if export_path.exists() && !site.in_memory() {
-            asset
-        } else {
-            site.add_file(&export_path, &data.to_vec())
-                .map_err(|e| {
-                    Error::io(
-                        e,
-                        format!("Could not write {} to {} directory", filename, dir),
-                    )
-                })
-                .unwrap();
+        // Written whenever the contents changed, since they can change
+        // without the name changing. Unchanged files are left alone.
+        site.add_file(&export_path, data).map_err(|e| {
+            Error::io(
+                e,
+                format!("Could not write {} to {} directory", filename, dir),
+            )
+        })?;
 
-            asset
+        Ok(asset)
+    }
+}
+
+/// Whether the page links to other files, rather than to sites elsewhere or
+/// to its own headings
+fn links_to_local_files(doc: &Document) -> bool {
+    doc.outgoing_links().iter().any(|link| match &link.url {
+        crate::markdown::extensions::link_rewriter::UrlType::Local(path) => {
+            !path.to_string_lossy().starts_with('#')
         }
+        _ => false,
+    })
+}
+
+/// Whether the page asks for a listing of its children, with a
+/// `{% children %}` line or `list_children: true` in its frontmatter
+fn lists_children(doc: &Document) -> bool {
+    doc.fm_bool("list_children").unwrap_or(false) || doc.has_children_directive()
+}
+
+/// The pages under `doc` in the navigation, in the same order as the
+/// sidebar. Pages in subdirectories are listed under the page of their
+/// directory, one level deep. Unlisted pages and drafts are left out, along
+/// with their children.
+fn child_pages(
+    doc: &Document,
+    docs: &HashMap<&str, &Document>,
+    nav: &[Link],
+    base_path: &str,
+) -> Vec<ChildPage> {
+    fn find<'n>(links: &'n [Link], uri: &str) -> Option<&'n Link> {
+        links.iter().find_map(|link| {
+            if link.path == uri {
+                Some(link)
+            } else {
+                find(&link.children, uri)
+            }
+        })
     }
+
+    fn pages(links: &[Link], docs: &HashMap<&str, &Document>, depth: usize) -> Vec<ChildPage> {
+        links
+            .iter()
+            .filter_map(|link| docs.get(link.path.as_str()).map(|doc| (link, doc)))
+            .filter(|(_, doc)| doc.listed())
+            .map(|(link, doc)| ChildPage {
+                title: doc.title.clone(),
+                uri: doc.uri_path.clone(),
+                description: doc.description.clone().unwrap_or_default(),
+                children: if depth == 0 {
+                    pages(&link.children, docs, 1)
+                } else {
+                    vec![]
+                },
+            })
+            .collect()
+    }
+
+    // The home page isn't in the navigation, everything else is under it
+    let links = if doc.uri_path == base_path {
+        nav
+    } else {
+        match find(nav, &doc.uri_path) {
+            Some(link) => &link.children,
+            None => return vec![],
+        }
+    };
+
+    pages(links, docs, 0)
+}
+
+/// Puts the listing of children in place of the `{% children %}` line, or
+/// after the content if there is none
+fn with_children(html: &str, listing: &str) -> String {
+    if html.contains(children::PLACEHOLDER) {
+        html.replacen(children::PLACEHOLDER, listing, 1)
+    } else {
+        format!("{}{}", html, listing)
+    }
+}
+
+/// The head include with its links from the root of the site, like
+/// `/favicon.ico`, pointed at `root`
+fn head_with_root(head: &str, root: &str) -> String {
+    rewrite_html_urls(head, |_, url| resolve_root_path(url, root))
+}
+
+/// The authors and review date of the page, if the site shows them and the
+/// page has either
+fn page_meta(doc: &Document, config: &Config) -> Option<PageMeta> {
+    if !config.show_page_meta() {
+        return None;
+    }
+
+    let authors = doc.authors().join(", ");
+    let reviewed = doc.reviewed().map(|date| {
+        (
+            date.format("%Y-%m-%d").to_string(),
+            date.format(config.date_format()).to_string(),
+        )
+    });
+
+    if authors.is_empty() && reviewed.is_none() {
+        None
+    } else {
+        Some(PageMeta { authors, reviewed })
+    }
+}
+
+/// Puts the page meta right after the first H1 of the page, or at the start
+/// of a page without one
+fn with_page_meta(html: &str, meta: &str) -> String {
+    match html.find("</h1>") {
+        Some(end) => {
+            let end = end + "</h1>".len();
+            format!("{}{}{}", &html[..end], meta, &html[end..])
+        }
+        None => format!("{}{}", meta, html),
+    }
+}
+
+/// The previous and next page of every page, by URI. Pages follow each
+/// other in the order of the navigation, starting from the home page, and a
+/// directory is the page of its README. Unlisted pages, and pages that
+/// aren't in the navigation, are skipped.
+fn page_neighbours(
+    docs: &[Document],
+    nav: &[Link],
+    base_path: &str,
+) -> HashMap<String, Neighbours> {
+    fn flatten<'n>(links: &'n [Link], into: &mut Vec<&'n str>) {
+        for link in links {
+            into.push(&link.path);
+            flatten(&link.children, into);
+        }
+    }
+
+    let docs = docs
+        .iter()
+        .filter(|doc| !doc.unlisted())
+        .map(|doc| (doc.uri_path.as_str(), doc))
+        .collect::<HashMap<_, _>>();
+
+    let mut uris = vec![base_path];
+    flatten(nav, &mut uris);
+
+    // A page can be linked more than once in a custom navigation
+    let mut seen = HashSet::new();
+    let pages = uris
+        .into_iter()
+        .filter_map(|uri| docs.get(uri))
+        .filter(|doc| seen.insert(doc.uri_path.as_str()))
+        .map(|doc| RelatedPage {
+            title: doc.title.clone(),
+            uri: doc.uri_path.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let neighbours = Neighbours {
+                prev: i.checked_sub(1).map(|prev| pages[prev].clone()),
+                next: pages.get(i + 1).cloned(),
+            };
+            (page.uri.clone(), neighbours)
+        })
+        .collect()
+}
+
+/// Finds the synonyms whose term appears in the text, so that they can be
+/// indexed as if they were part of it.
+fn synonyms_in<'s>(text: &str, synonyms: &'s BTreeMap<String, String>) -> Vec<&'s str> {
+    let words = format!(" {} ", normalize_terms(text));
+
+    synonyms
+        .iter()
+        .filter(|(_, term)| words.contains(&format!(" {} ", normalize_terms(term))))
+        .map(|(synonym, _)| synonym.as_str())
+        .collect()
+}
+
+fn normalize_terms(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The text of the HTML, without its tags, with every run of whitespace
+/// turned into a single space
+pub fn plain_text(html: &str) -> String {
+    // Tags that don't separate words, unlike the cells of a table
+    const INLINE_TAGS: &[&str] = &[
+        "a", "abbr", "b", "code", "del", "em", "i", "kbd", "mark", "span", "strong", "sub", "sup",
+    ];
+
+    let mut text = String::with_capacity(html.len());
+    let mut tag: Option<String> = None;
+    for c in html.chars() {
+        match (&mut tag, c) {
+            (None, '<') => tag = Some(String::new()),
+            (None, _) => text.push(c),
+            (Some(name), '>') => {
+                let name = name
+                    .trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or("")
+                    .to_ascii_lowercase();
+                if !INLINE_TAGS.contains(&name.as_str()) {
+                    text.push(' ');
+                }
+                tag = None;
+            }
+            (Some(name), _) => name.push(c),
+        }
+    }
+
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
 fn compile_assets(
@@ -540,3 +1786,710 @@ fn compile_assets(
         .collect::<Vec<String>>()
         .join("\n")
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::site::{InMemorySite, OutputFile, Site};
+    use crate::test_helpers::{page, project};
+    use std::path::PathBuf;
+
+    fn built_json(yaml: &str, root: &Vec<Document>, file: &str) -> serde_json::Value {
+        let config = Config::from_yaml_str(Path::new("project"), yaml, true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        site.build(config.clone(), root).unwrap();
+
+        let json = site.backend.read_path(Path::new(file)).unwrap();
+
+        serde_json::from_slice(&json).unwrap()
+    }
+
+    fn search_index(yaml: &str, root: &Vec<Document>) -> serde_json::Value {
+        built_json(yaml, root, "search_index.json")
+    }
+
+    /// Whether the inverted index of the field contains the term for the doc.
+    /// Terms are stored stemmed, so "k8s" is looked up as "k8".
+    fn retrieves(index: &serde_json::Value, field: &str, term: &str, doc: &Document) -> bool {
+        let mut node = &index["index"][field]["root"];
+        for c in term.chars() {
+            node = &node[c.to_string()];
+        }
+
+        node["docs"].get(doc.id.to_string()).is_some()
+    }
+
+    #[test]
+    fn pages_connect_to_the_resolved_livereload_port() {
+        let root = vec![page("README.md", "# Home", &[])];
+        let yaml = "---\ntitle: My project\nport: 35729\n";
+        let mut config = Config::from_yaml_str(Path::new("project"), yaml, true).unwrap();
+        config.resolve_server_addrs(None).unwrap();
+
+        assert_ne!(config.addr().port(), config.livereload_addr().port());
+        assert_eq!(
+            SiteGenerator::new(config.clone(), &root).livereload_port(),
+            Some(config.livereload_addr().port().to_string())
+        );
+
+        config.set_build_mode(BuildMode::Release);
+        assert_eq!(SiteGenerator::new(config, &root).livereload_port(), None);
+    }
+
+    #[test]
+    fn builds_a_search_page_outside_of_the_docs() {
+        let root = vec![page("README.md", "# Home", &[])];
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: My project\n", true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        let warnings = site.build(config, &root).unwrap();
+
+        assert!(warnings.is_empty());
+        let html =
+            String::from_utf8(site.backend.read_path(Path::new("search.html")).unwrap()).unwrap();
+        assert!(html.contains(r#"<form id="search-form" action="/search" method="get""#));
+        assert!(html.contains(r#"<ul id="search-page-results"></ul>"#));
+        assert!(html.contains("<noscript>"));
+        assert!(html.contains(r#"<meta name="robots" content="noindex">"#));
+
+        let index = search_index("---\ntitle: My project\n", &root);
+        assert_eq!(index["documentStore"]["docs"].as_object().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_page_at_the_search_uri_takes_the_place_of_the_search_page() {
+        let root = vec![
+            page("README.md", "# Home", &[]),
+            page("search.md", "# Our own search", &[]),
+        ];
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: My project\n", true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        let warnings = site.build(config, &root).unwrap();
+
+        assert_eq!(
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+            vec![
+                "search.md : Is at /search, where the search page would be. \
+                 Searching from the header opens this page instead"
+            ]
+        );
+        assert!(site.backend.has_file(Path::new("search.html")));
+    }
+
+    #[test]
+    fn builds_a_site_report_outside_of_the_docs() {
+        let root = vec![
+            page("README.md", "# Home\n\n[Setup](/guides/setup)", &[]),
+            page(
+                "guides/setup.md",
+                "# Setup\n\n[Gone](/gone)",
+                &[("reviewed", "2020-01-31")],
+            ),
+            page("faq.md", "# FAQ", &[]),
+        ];
+        let yaml = "---\ntitle: My project\nsite_report: true\n";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        site.build(config, &root).unwrap();
+
+        let html = String::from_utf8(
+            site.backend
+                .read_path(Path::new("__report/index.html"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(html.contains("<tr><th>Pages</th><td>3</td></tr>"));
+        assert!(html.contains("<tr><th>Orphan pages</th><td>0</td></tr>"));
+        assert!(html.contains("<tr><th>Broken links</th><td>1</td></tr>"));
+        assert!(html.contains(
+            r#"<th>Oldest page</th><td><a href="/guides/setup">Setup</a> (guides/setup.md, changed on 2020-01-31)</td>"#
+        ));
+        assert!(html.contains(r#"<meta name="robots" content="noindex">"#));
+
+        let index = search_index(yaml, &root);
+        assert_eq!(index["documentStore"]["docs"].as_object().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn builds_no_site_report_by_default() {
+        let root = vec![page("README.md", "# Home", &[])];
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: My project\n", true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        site.build(config, &root).unwrap();
+
+        assert!(!site.backend.has_file(Path::new("__report/index.html")));
+    }
+
+    #[test]
+    fn a_page_at_the_report_uri_takes_the_place_of_the_site_report() {
+        let root = vec![
+            page("README.md", "# Home", &[]),
+            page("__report/README.md", "# Our own report", &[]),
+        ];
+        let yaml = "---\ntitle: My project\nsite_report: true\n";
+        let config = Config::from_yaml_str(Path::new("project"), yaml, true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        let warnings = site.build(config, &root).unwrap();
+
+        assert_eq!(
+            warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+            vec![
+                "__report/README.md : Is at /__report/, where the site report would be. \
+                 The site report is left out"
+            ]
+        );
+        assert!(site.backend.has_file(Path::new("__report/index.html")));
+    }
+
+    #[test]
+    fn simple_search_lists_the_text_of_every_page() {
+        let root = vec![
+            page("README.md", "# Home\n\nWelcome *home*.", &[]),
+            page(
+                "deploy.md",
+                "# Deploying\n\n## With `helm`\n\nRun it &amp; wait.",
+                &[],
+            ),
+        ];
+        let yaml = "---\ntitle: My project\nsearch:\n  engine: simple\n";
+
+        assert_eq!(
+            search_index(yaml, &root),
+            serde_json::json!([
+                {
+                    "title": "Home",
+                    "uri": "/",
+                    "headings": ["Home"],
+                    "preview": "Welcome home.",
+                    "body": "Home Welcome home."
+                },
+                {
+                    "title": "Deploying",
+                    "uri": "/deploy",
+                    "headings": ["Deploying", "With helm"],
+                    "preview": "Run it & wait.",
+                    "body": "Deploying With helm Run it & wait."
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn unlisted_and_draft_pages_are_not_searched() {
+        let root = vec![
+            page("README.md", "# Home", &[]),
+            page("secret.md", "# Secret", &[("unlisted", "true")]),
+            page("wip.md", "# WIP", &[("draft", "true")]),
+        ];
+        let yaml = "---\ntitle: My project\nsearch:\n  engine: simple\n";
+
+        let titles = |json: serde_json::Value| {
+            json.as_array()
+                .unwrap()
+                .iter()
+                .map(|page| page["title"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(search_index(yaml, &root)), vec!["Home"]);
+        assert_eq!(titles(built_json(yaml, &root, "recent.json")), vec!["Home"]);
+    }
+
+    #[test]
+    fn content_blocks_are_not_searched() {
+        let root = vec![page("README.md", "# Home\n\nWelcome home.", &[])];
+        let yaml = "---\ntitle: My project\nsearch:\n  engine: simple\n\
+                    content_header: \"## Beta\\n\\nThis is a beta.\"\n\
+                    content_footer: \"Edited by the docs team.\"\n";
+
+        assert_eq!(
+            search_index(yaml, &root),
+            serde_json::json!([
+                {
+                    "title": "Home",
+                    "uri": "/",
+                    "headings": ["Home"],
+                    "preview": "Welcome home.",
+                    "body": "Home Welcome home."
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(
+            super::plain_text(
+                "<h1 id=\"a\">Tom &amp; Jerry</h1><p>A <em>cat</em>&lt;3</p><td>1</td><td>2</td>"
+            ),
+            "Tom & Jerry A cat<3 1 2"
+        );
+    }
+
+    #[test]
+    fn indexes_keywords() {
+        let root = vec![
+            page("README.md", "# Home", &[]),
+            page("deploy.md", "# Deploying", &[("keywords", "k8s, helm")]),
+        ];
+
+        let index = search_index("---\ntitle: My project\n", &root);
+
+        assert!(retrieves(&index, "keywords", "k8", &root[1]));
+        assert!(retrieves(&index, "keywords", "helm", &root[1]));
+        assert!(!retrieves(&index, "body", "k8", &root[1]));
+        assert!(!retrieves(&index, "keywords", "k8", &root[0]));
+    }
+
+    #[test]
+    fn indexes_synonyms() {
+        let root = vec![
+            page("README.md", "# Home", &[]),
+            page("deploy.md", "# Deploying\n\nUse Kubernetes.", &[]),
+            page("charts.md", "# Charts", &[("keywords", "kubernetes")]),
+        ];
+
+        let index = search_index(
+            "---\ntitle: My project\nsearch_synonyms:\n  k8s: kubernetes\n",
+            &root,
+        );
+
+        assert!(retrieves(&index, "body", "k8", &root[1]));
+        assert!(retrieves(&index, "keywords", "k8", &root[2]));
+        assert!(!retrieves(&index, "body", "k8", &root[0]));
+    }
+
+    #[test]
+    fn synonyms_match_whole_words() {
+        let mut synonyms = BTreeMap::new();
+        synonyms.insert("k8s".to_owned(), "kubernetes".to_owned());
+        synonyms.insert("ci".to_owned(), "continuous integration".to_owned());
+
+        assert_eq!(
+            synonyms_in("<p>Continuous Integration on Kubernetes</p>", &synonyms),
+            vec!["ci", "k8s"]
+        );
+        assert_eq!(synonyms_in("kubernetesish", &synonyms), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn exports_recently_updated_pages() {
+        let modified = |secs: u64| UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let page_modified_at = |path: &str, title: &str, secs: u64| {
+            Document::new(
+                Path::new(path),
+                format!("# {}", title),
+                None,
+                "/docs/",
+                modified(secs),
+            )
+        };
+
+        let root = vec![
+            page_modified_at("README.md", "Home", 1_000),
+            page_modified_at("old.md", "Old", 2_000),
+            page_modified_at("guides/newest.md", "Newest", 4_000),
+            page_modified_at("newer.md", "Newer", 3_000),
+        ];
+
+        let recent = built_json(
+            "---\ntitle: My project\nbase_path: /docs/\nsearch:\n  recent_count: 2\n",
+            &root,
+            "recent.json",
+        );
+
+        assert_eq!(
+            recent,
+            serde_json::json!([
+                {"title": "Newest", "uri": "/docs/guides/newest", "date": "1970-01-01T01:06:40Z"},
+                {"title": "Newer", "uri": "/docs/newer", "date": "1970-01-01T00:50:00Z"},
+            ])
+        );
+    }
+
+    #[test]
+    fn sidebar_footer_is_sanitized() {
+        let project = project("sidebar-footer", &[]);
+        let include = project.join("docs").join(INCLUDE_DIR);
+        fs::create_dir_all(&include).unwrap();
+        let config = Config::from_yaml_str(&project, "---\ntitle: Title", true).unwrap();
+        let root = vec![];
+        let generator = SiteGenerator::new(config, &root);
+
+        let without = generator.read_sidebar_footer().unwrap();
+        fs::write(
+            include.join(SIDEBAR_FOOTER_FILE),
+            "<a href=\"https://status.example.com\" onclick=\"track()\">Status</a>\
+             <script>alert('hi')</script>",
+        )
+        .unwrap();
+        let with = generator.read_sidebar_footer().unwrap();
+        fs::remove_dir_all(&project).unwrap();
+
+        assert_eq!(without, None);
+        assert_eq!(
+            with.as_deref(),
+            Some("<a href=\"https://status.example.com\">Status</a>")
+        );
+    }
+
+    #[test]
+    fn init_script_sets_the_build_timestamp() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let root = vec![];
+        let generator = SiteGenerator::new(config, &root);
+
+        assert!(generator
+            .init_script()
+            .contains(&format!("\"{}\"", generator.timestamp())));
+    }
+
+    #[test]
+    fn init_script_sets_the_docgen_version() {
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let root = vec![];
+        let generator = SiteGenerator::new(config, &root);
+
+        assert!(generator.init_script().contains(&format!(
+            "DOCGEN_VERSION = \"{}\"",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    #[test]
+    fn pages_say_which_docgen_version_built_them() {
+        let root = vec![page("README.md", "# Home", &[])];
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        site.build(config, &root).unwrap();
+
+        let html = site.backend.read_path(Path::new("index.html")).unwrap();
+        let html = String::from_utf8(html).unwrap();
+
+        assert!(html.contains(&format!(
+            "<meta name=\"generator\" content=\"docgen {}\">",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    #[test]
+    fn partials_can_show_the_docgen_version() {
+        let version = env!("CARGO_PKG_VERSION");
+
+        assert_eq!(
+            with_template_variables("<p>Built with docgen {{ docgen_version }}</p>"),
+            format!("<p>Built with docgen {}</p>", version)
+        );
+        assert_eq!(with_template_variables("{{docgen_version}}"), version);
+        assert_eq!(with_template_variables("{{ title }}"), "{{ title }}");
+    }
+
+    #[test]
+    fn every_page_is_rendered_on_its_own() {
+        let root = (0..50)
+            .map(|i| page(&format!("page-{}.md", i), &format!("# Page {}", i), &[]))
+            .collect::<Vec<_>>();
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let mut site = Site::in_memory(config.clone());
+        site.build(config, &root).unwrap();
+
+        for i in 0..50 {
+            let html = site
+                .backend
+                .read_path(Path::new(&format!("page-{}.html", i)))
+                .unwrap();
+            let html = String::from_utf8(html).unwrap();
+
+            assert_eq!(html.matches("<!DOCTYPE html>").count(), 1);
+            assert!(html.contains(&format!(">Page {}</h1>", i)));
+        }
+    }
+
+    fn neighbour_titles(
+        neighbours: &HashMap<String, Neighbours>,
+        uri: &str,
+    ) -> (Option<String>, Option<String>) {
+        let neighbours = &neighbours[uri];
+        let title = |page: &Option<RelatedPage>| page.as_ref().map(|page| page.title.clone());
+
+        (title(&neighbours.prev), title(&neighbours.next))
+    }
+
+    #[test]
+    fn pages_link_to_their_neighbours_in_the_navigation() {
+        let mut root = vec![
+            page("README.md", "# Home", &[]),
+            page("guide/README.md", "# Guide", &[("index", "1")]),
+            page("guide/install.md", "# Install", &[]),
+            page("reference/README.md", "# Reference", &[("index", "2")]),
+            page("reference/api.md", "# API", &[]),
+            page("guide/secret.md", "# Secret", &[("unlisted", "true")]),
+        ];
+        root.sort_by(docs_finder::document_sort);
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let nav = Navigation::new(&config).build_for(&root);
+
+        let neighbours = page_neighbours(&root, &nav, config.base_path());
+
+        assert_eq!(
+            neighbour_titles(&neighbours, "/"),
+            (None, Some("Guide".to_string()))
+        );
+        assert_eq!(
+            neighbour_titles(&neighbours, "/guide/install"),
+            (Some("Guide".to_string()), Some("Reference".to_string()))
+        );
+        assert_eq!(
+            neighbour_titles(&neighbours, "/reference/api"),
+            (Some("Reference".to_string()), None)
+        );
+        assert!(!neighbours.contains_key("/guide/secret"));
+    }
+
+    #[test]
+    fn children_are_listed_in_the_order_of_the_sidebar() {
+        let mut root = vec![
+            page("README.md", "# Home", &[]),
+            page("guide/README.md", "# Guide\n\n{% children %}", &[]),
+            page("guide/install.md", "# Install\n\nGetting it running.", &[]),
+            page("guide/advanced/README.md", "# Advanced", &[("index", "1")]),
+            page("guide/advanced/tuning.md", "# Tuning", &[]),
+            page(
+                "guide/faq.md",
+                "# FAQ",
+                &[("description", "Common questions")],
+            ),
+            page("guide/secret.md", "# Secret", &[("unlisted", "true")]),
+            page("guide/wip.md", "# WIP", &[("draft", "true")]),
+        ];
+        root.sort_by(docs_finder::document_sort);
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let nav = Navigation::new(&config).build_for(&root);
+        let docs = root
+            .iter()
+            .map(|doc| (doc.uri_path.as_str(), doc))
+            .collect::<HashMap<_, _>>();
+        let guide = root.iter().find(|doc| doc.title == "Guide").unwrap();
+
+        let children = child_pages(guide, &docs, &nav, config.base_path());
+
+        let sidebar = nav
+            .iter()
+            .find(|link| link.path == guide.uri_path)
+            .unwrap()
+            .children
+            .iter()
+            .map(|link| link.title.as_str())
+            .filter(|title| !["Secret", "WIP"].contains(title))
+            .collect::<Vec<_>>();
+        let titles = children
+            .iter()
+            .map(|child| child.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, sidebar);
+        assert_eq!(titles, vec!["Advanced", "FAQ", "Install"]);
+
+        assert_eq!(children[0].children.len(), 1);
+        assert_eq!(children[0].children[0].title, "Tuning");
+        assert_eq!(children[1].description, "Common questions");
+        assert_eq!(children[2].description, "Getting it running.");
+        assert!(children[2].children.is_empty());
+    }
+
+    #[test]
+    fn children_replace_the_directive_or_follow_the_content() {
+        let guide = page(
+            "guide/README.md",
+            "# Guide\n\n{% children %}\n\nMore about guides",
+            &[],
+        );
+        let html = with_children(guide.html(), "LIST");
+
+        assert!(lists_children(&guide));
+        assert_eq!(guide.description.as_deref(), Some("More about guides"));
+        assert!(!super::plain_text(guide.html()).contains("children"));
+        assert!(
+            html.ends_with("LIST\n<p>More about guides</p>\n"),
+            "{}",
+            html
+        );
+        assert_eq!(
+            with_children("<h1>Guide</h1>\n", "LIST"),
+            "<h1>Guide</h1>\nLIST"
+        );
+    }
+
+    fn meta(frontmatter: &[(&str, &str)], yaml: &str) -> Option<PageMeta> {
+        let config = Config::from_yaml_str(Path::new("project"), yaml, true).unwrap();
+        page_meta(&page("guide.md", "# Guide", frontmatter), &config)
+    }
+
+    #[test]
+    fn page_meta_shows_a_single_author_and_the_review_date() {
+        let yaml = "---\ntitle: Title\nshow_page_meta: true";
+        assert_eq!(
+            meta(&[("author", "Jane Doe"), ("reviewed", "2024-01-31")], yaml),
+            Some(PageMeta {
+                authors: "Jane Doe".to_string(),
+                reviewed: Some(("2024-01-31".to_string(), "January 31, 2024".to_string())),
+            })
+        );
+
+        let yaml = "---\ntitle: Title\nshow_page_meta: true\ndate_format: \"%d.%m.%Y\"";
+        assert_eq!(
+            meta(&[("date", "2024-01-31")], yaml),
+            Some(PageMeta {
+                authors: String::new(),
+                reviewed: Some(("2024-01-31".to_string(), "31.01.2024".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn page_meta_separates_multiple_authors_with_commas() {
+        let yaml = "---\ntitle: Title\nshow_page_meta: true";
+        assert_eq!(
+            meta(&[("author", "Jane Doe,John Roe")], yaml),
+            Some(PageMeta {
+                authors: "Jane Doe, John Roe".to_string(),
+                reviewed: None,
+            })
+        );
+    }
+
+    #[test]
+    fn page_meta_is_only_shown_when_turned_on() {
+        let frontmatter = [("author", "Jane Doe"), ("reviewed", "2024-01-31")];
+        assert_eq!(meta(&frontmatter, "---\ntitle: Title"), None);
+        assert_eq!(meta(&[], "---\ntitle: Title\nshow_page_meta: true"), None);
+
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+        let with_meta = vec![page("README.md", "# Home\n\nText", &frontmatter)];
+        let without_meta = vec![page("README.md", "# Home\n\nText", &[])];
+        let html = |root: &Vec<Document>| {
+            let mut site = Site::in_memory(config.clone());
+            site.build(config.clone(), root).unwrap();
+            let html = site.backend.read_path(Path::new("index.html")).unwrap();
+            let timestamp = site.backend.timestamp().to_owned();
+            String::from_utf8(html).unwrap().replace(&timestamp, "")
+        };
+        assert_eq!(html(&with_meta), html(&without_meta));
+    }
+
+    #[test]
+    fn page_meta_follows_the_title() {
+        assert_eq!(
+            with_page_meta("<h1 id=\"guide\">Guide</h1>\n<p>Text</p>", "META"),
+            "<h1 id=\"guide\">Guide</h1>META\n<p>Text</p>"
+        );
+        assert_eq!(with_page_meta("<p>Text</p>", "META"), "META<p>Text</p>");
+    }
+
+    /// Fails to write anything under one directory of the site, like a disk
+    /// backend would for paths past MAX_PATH on Windows
+    struct FailingSite {
+        site: InMemorySite,
+        failing: &'static str,
+    }
+
+    impl SiteBackend for FailingSite {
+        fn config(&self) -> &Config {
+            self.site.config()
+        }
+
+        fn add_file(&mut self, path: &Path, content: &[u8]) -> std::io::Result<()> {
+            let relative = path.strip_prefix(self.config().out_dir()).unwrap_or(path);
+            if relative.starts_with(self.failing) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "The system cannot find the path specified. (os error 3)",
+                ));
+            }
+            self.site.add_file(path, content)
+        }
+
+        fn copy_file(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
+            self.site.copy_file(from, to)
+        }
+
+        fn keep_file(&mut self, path: &Path) -> bool {
+            self.site.keep_file(path)
+        }
+
+        fn keep_copied_file(&mut self, from: &Path, to: &Path) -> bool {
+            self.site.keep_copied_file(from, to)
+        }
+
+        fn read_path(&self, path: &Path) -> Option<Vec<u8>> {
+            self.site.read_path(path)
+        }
+
+        fn streamed_path(&self, path: &Path) -> Option<PathBuf> {
+            self.site.streamed_path(path)
+        }
+
+        fn has_file(&self, path: &Path) -> bool {
+            self.site.has_file(path)
+        }
+
+        fn build(
+            &mut self,
+            config: Config,
+            root: &Vec<Document>,
+            _post_processors: &[Arc<dyn HtmlPostProcessor>],
+        ) -> Result<Vec<Warning>> {
+            SiteGenerator::new(config, root).run(self)
+        }
+
+        fn list_files(&self) -> Vec<PathBuf> {
+            self.site.list_files()
+        }
+
+        fn output_files(&self) -> BTreeMap<PathBuf, OutputFile> {
+            self.site.output_files()
+        }
+
+        fn output_paths(&self) -> Vec<PathBuf> {
+            self.site.output_paths()
+        }
+
+        fn in_memory(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn pages_that_could_not_be_written_are_all_reported() {
+        let root = vec![
+            page("README.md", "# Home", &[]),
+            page("deep/one.md", "# One", &[]),
+            page("deep/two.md", "# Two", &[]),
+        ];
+        let config =
+            Config::from_yaml_str(Path::new("project"), "---\ntitle: Title", true).unwrap();
+
+        let mut site = FailingSite {
+            site: InMemorySite::new(config.clone()),
+            failing: "deep",
+        };
+        let error = site.build(config, &root, &[]).unwrap_err().to_string();
+
+        assert!(error.starts_with("Could not write 2 page(s):"));
+        for page in ["one", "two"] {
+            let destination = Path::new("project/site/deep").join(format!("{}.html", page));
+            assert!(error.contains(&format!(
+                "\t{}: The system cannot find the path specified. (os error 3)",
+                destination.display()
+            )));
+        }
+        assert!(site.has_file(Path::new("index.html")));
+    }
+}
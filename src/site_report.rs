@@ -0,0 +1,437 @@
+//! The site report: an internal page with an overview of the health of the
+//! docs, like the pages nobody changed in months and the links that are
+//! broken, for whoever reviews them now and then. Like the search page, it
+//! isn't a page of the docs, so it's left out of the navigation, the search
+//! index and the sitemap.
+//!
+//! It only has dates, never times, so that the reports of two builds of the
+//! same docs on the same day are the same, and can be diffed.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::markdown::extensions::link_rewriter::{Link, UrlType};
+use crate::site_generator::plain_text;
+use crate::warning::Warning;
+use crate::{Document, Result};
+
+/// Where the report is built from, so that it ends up at `__report/`
+pub static REPORT_PAGE: &str = "__report/README.md";
+
+/// Pages that weren't changed for this many days, about six months, are
+/// listed as stale
+const STALE_DAYS: i64 = 183;
+
+/// How many of the shortest pages are listed
+const SHORTEST_PAGES: usize = 20;
+
+/// How many days of the broken link history are kept
+const HISTORY_DAYS: usize = 30;
+
+/// Where the number of broken links of every day is kept between builds
+const HISTORY_CATEGORY: &str = "site_report";
+const HISTORY_KEY: &str = "broken_links.json";
+
+/// The word counts pages are grouped by, from the first count of each range
+const WORD_RANGES: [usize; 5] = [0, 100, 500, 1000, 3000];
+
+#[derive(Debug, PartialEq)]
+pub struct SiteReport {
+    /// The day of the build, which is what makes a page stale
+    pub date: String,
+    pub pages: usize,
+    pub words: usize,
+    /// The page that was changed the longest ago
+    pub oldest: Option<ReportPage>,
+    pub sections: Vec<ReportSection>,
+    pub word_ranges: Vec<(String, usize)>,
+    pub shortest: Vec<ReportPage>,
+    pub stale: Vec<ReportPage>,
+    pub orphans: Vec<ReportPage>,
+    /// The page each broken link is on, and where it points
+    pub broken_links: Vec<(String, String)>,
+    /// The number of broken links by day, the latest first
+    pub broken_link_history: Vec<(String, usize)>,
+    /// The number of warnings by the page they are about
+    pub warnings: Vec<(String, usize)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReportPage {
+    pub title: String,
+    pub uri: String,
+    /// The path of the page's file, relative to the docs folder
+    pub path: String,
+    pub words: usize,
+    pub changed: String,
+}
+
+/// The pages of a directory of the docs root, or of the root itself
+#[derive(Debug, PartialEq)]
+pub struct ReportSection {
+    pub name: String,
+    pub pages: usize,
+    pub words: usize,
+    pub stale: usize,
+}
+
+impl SiteReport {
+    /// Works out the report of the pages on the given day, from the problems
+    /// found with the build. `history` has the number of broken links of
+    /// earlier days, by their date.
+    pub fn build(
+        docs: &[Document],
+        today: NaiveDate,
+        orphans: &[&Document],
+        broken_links: &[(PathBuf, Link)],
+        warnings: &[Warning],
+        history: &BTreeMap<String, usize>,
+    ) -> SiteReport {
+        let pages = docs
+            .iter()
+            .map(|doc| (doc, ReportPage::new(doc)))
+            .collect::<Vec<_>>();
+        let is_stale = |page: &ReportPage| {
+            NaiveDate::parse_from_str(&page.changed, "%Y-%m-%d")
+                .map(|changed| (today - changed).num_days() >= STALE_DAYS)
+                .unwrap_or(false)
+        };
+
+        let mut sections = BTreeMap::<String, ReportSection>::new();
+        for (doc, page) in &pages {
+            let name = section_name(doc.original_path());
+            let section = sections.entry(name.clone()).or_insert(ReportSection {
+                name,
+                pages: 0,
+                words: 0,
+                stale: 0,
+            });
+            section.pages += 1;
+            section.words += page.words;
+            if is_stale(page) {
+                section.stale += 1;
+            }
+        }
+        let mut sections = sections.into_values().collect::<Vec<_>>();
+        sections.sort_by(|a, b| {
+            b.stale
+                .cmp(&a.stale)
+                .then_with(|| (a.words / a.pages).cmp(&(b.words / b.pages)))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let word_ranges = WORD_RANGES
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = WORD_RANGES.get(i + 1).copied();
+                let label = match end {
+                    Some(end) => format!("{}–{}", start, end - 1),
+                    None => format!("{}+", start),
+                };
+                let count = pages
+                    .iter()
+                    .filter(|(_, page)| {
+                        page.words >= start && !matches!(end, Some(end) if page.words >= end)
+                    })
+                    .count();
+
+                (label, count)
+            })
+            .collect();
+
+        let mut by_words = pages.iter().map(|(_, page)| page).collect::<Vec<_>>();
+        by_words.sort_by(|a, b| a.words.cmp(&b.words).then_with(|| a.path.cmp(&b.path)));
+
+        let mut by_age = pages.iter().map(|(_, page)| page).collect::<Vec<_>>();
+        by_age.sort_by(|a, b| a.changed.cmp(&b.changed).then_with(|| a.path.cmp(&b.path)));
+
+        let mut orphans = orphans
+            .iter()
+            .map(|doc| ReportPage::new(doc))
+            .collect::<Vec<_>>();
+        orphans.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut broken_links = broken_links
+            .iter()
+            .filter_map(|(page, link)| match &link.url {
+                UrlType::Local(target) => Some((
+                    page.to_string_lossy().replace('\\', "/"),
+                    target.to_string_lossy().to_string(),
+                )),
+                UrlType::Remote(_) => None,
+            })
+            .collect::<Vec<_>>();
+        broken_links.sort();
+
+        let mut counts = BTreeMap::<String, usize>::new();
+        for warning in warnings {
+            *counts
+                .entry(warning.path.to_string_lossy().replace('\\', "/"))
+                .or_default() += 1;
+        }
+        let mut warnings = counts.into_iter().collect::<Vec<_>>();
+        warnings.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+        let date = today.format("%Y-%m-%d").to_string();
+        let mut broken_link_history = history.clone();
+        broken_link_history.insert(date.clone(), broken_links.len());
+        let broken_link_history = broken_link_history
+            .into_iter()
+            .rev()
+            .take(HISTORY_DAYS)
+            .collect();
+
+        SiteReport {
+            date,
+            pages: pages.len(),
+            words: pages.iter().map(|(_, page)| page.words).sum(),
+            oldest: by_age.first().map(|page| (*page).clone()),
+            sections,
+            word_ranges,
+            shortest: by_words.into_iter().take(SHORTEST_PAGES).cloned().collect(),
+            stale: by_age
+                .into_iter()
+                .filter(|page| is_stale(page))
+                .cloned()
+                .collect(),
+            orphans,
+            broken_links,
+            broken_link_history,
+            warnings,
+        }
+    }
+
+    /// Points the links to the pages of the report somewhere else, like
+    /// relative to the report
+    pub fn map_uris(&mut self, href: impl Fn(&str) -> String) {
+        let pages = self
+            .oldest
+            .iter_mut()
+            .chain(self.shortest.iter_mut())
+            .chain(self.stale.iter_mut())
+            .chain(self.orphans.iter_mut());
+
+        for page in pages {
+            page.uri = href(&page.uri);
+        }
+    }
+
+    /// The broken links of the current build by day, for the history of the
+    /// report
+    pub fn history(&self) -> BTreeMap<String, usize> {
+        self.broken_link_history.iter().cloned().collect()
+    }
+}
+
+impl ReportPage {
+    fn new(doc: &Document) -> Self {
+        // Like in the sitemap, a page that says when it was reviewed was
+        // last changed then, rather than whenever its file was last touched
+        let changed = doc
+            .reviewed()
+            .unwrap_or_else(|| DateTime::<Utc>::from(doc.last_modified).date_naive());
+
+        ReportPage {
+            title: doc.title.clone(),
+            uri: doc.uri_path.clone(),
+            path: doc.original_path().to_string_lossy().replace('\\', "/"),
+            words: plain_text(doc.html()).split_whitespace().count(),
+            changed: changed.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// The directory of the docs root the page is in, or `/` for pages of the
+/// root itself
+fn section_name(path: &Path) -> String {
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(dir), Some(_)) => format!("{}/", dir.as_os_str().to_string_lossy()),
+        _ => "/".to_string(),
+    }
+}
+
+/// The number of broken links of the previous days, kept in the cache. Only
+/// read when there is a cache already, so that previews don't create one.
+pub fn read_history(config: &Config) -> BTreeMap<String, usize> {
+    if !config.cache_dir().is_dir() {
+        return BTreeMap::new();
+    }
+
+    Cache::open(config.cache_dir())
+        .ok()
+        .and_then(|mut cache| cache.get(HISTORY_CATEGORY, HISTORY_KEY))
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Keeps the number of broken links of every day in the report for the
+/// next builds
+pub fn write_history(config: &Config, report: &SiteReport) -> Result<()> {
+    let mut cache = Cache::open(config.cache_dir())?;
+    cache.put(
+        HISTORY_CATEGORY,
+        HISTORY_KEY,
+        &serde_json::to_vec(&report.history()).unwrap(),
+    )?;
+    cache.save()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::page;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// The page, last changed on the given number of days after 2024-01-01
+    fn changed(mut page: Document, day: u64) -> Document {
+        page.last_modified = UNIX_EPOCH + Duration::from_secs(1_704_067_200 + day * 24 * 60 * 60);
+        page
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn reports_the_pages_worst_first() {
+        let docs = vec![
+            changed(page("README.md", "# Home\n\nWelcome to the docs", &[]), 300),
+            changed(
+                page("guides/setup.md", "# Setup\n\nInstall it and run it", &[]),
+                0,
+            ),
+            changed(page("guides/deploy.md", "# Deploy\n\nPush", &[]), 10),
+            changed(page("reference/cli.md", "# CLI", &[]), 250),
+        ];
+
+        let report = SiteReport::build(&docs, date("2024-12-31"), &[], &[], &[], &BTreeMap::new());
+
+        assert_eq!(report.date, "2024-12-31");
+        assert_eq!(report.pages, 4);
+        assert_eq!(report.words, 5 + 6 + 2 + 1);
+        assert_eq!(report.oldest.unwrap().path, "guides/setup.md");
+        assert_eq!(
+            report
+                .stale
+                .iter()
+                .map(|page| (page.path.as_str(), page.changed.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("guides/setup.md", "2024-01-01"),
+                ("guides/deploy.md", "2024-01-11")
+            ]
+        );
+        assert_eq!(
+            report
+                .sections
+                .iter()
+                .map(|s| (s.name.as_str(), s.pages, s.stale))
+                .collect::<Vec<_>>(),
+            vec![("guides/", 2, 2), ("reference/", 1, 0), ("/", 1, 0)]
+        );
+        assert_eq!(
+            report.shortest.first().map(|page| page.path.as_str()),
+            Some("reference/cli.md")
+        );
+        assert_eq!(
+            report.word_ranges,
+            vec![
+                ("0–99".to_string(), 4),
+                ("100–499".to_string(), 0),
+                ("500–999".to_string(), 0),
+                ("1000–2999".to_string(), 0),
+                ("3000+".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_problems_of_the_build() {
+        let docs = vec![
+            changed(page("README.md", "# Home\n\n[Gone](/gone)", &[]), 0),
+            changed(page("lost.md", "# Lost", &[]), 0),
+        ];
+        let broken_links = docs[0]
+            .outgoing_links()
+            .iter()
+            .map(|link| (PathBuf::from("README.md"), link.clone()))
+            .collect::<Vec<_>>();
+        let warnings = vec![
+            Warning::new(Path::new("lost.md"), "One"),
+            Warning::new(Path::new("README.md"), "Two"),
+            Warning::new(Path::new("lost.md"), "Three"),
+        ];
+        let history =
+            BTreeMap::from([("2024-01-01".to_string(), 3), ("2024-01-02".to_string(), 2)]);
+
+        let report = SiteReport::build(
+            &docs,
+            date("2024-01-03"),
+            &[&docs[1]],
+            &broken_links,
+            &warnings,
+            &history,
+        );
+
+        assert_eq!(
+            report
+                .orphans
+                .iter()
+                .map(|page| page.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["lost.md"]
+        );
+        assert_eq!(
+            report.broken_links,
+            vec![("README.md".to_string(), "/gone".to_string())]
+        );
+        assert_eq!(
+            report.warnings,
+            vec![("lost.md".to_string(), 2), ("README.md".to_string(), 1)]
+        );
+        assert_eq!(
+            report.broken_link_history,
+            vec![
+                ("2024-01-03".to_string(), 1),
+                ("2024-01-02".to_string(), 2),
+                ("2024-01-01".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_are_the_same_for_the_same_docs() {
+        let docs = vec![
+            changed(page("README.md", "# Home", &[]), 0),
+            changed(page("a.md", "# A", &[]), 1),
+        ];
+        let build =
+            || SiteReport::build(&docs, date("2024-06-01"), &[], &[], &[], &BTreeMap::new());
+
+        assert_eq!(build(), build());
+        assert_eq!(
+            SiteReport::build(
+                &docs.iter().rev().cloned().collect::<Vec<_>>(),
+                date("2024-06-01"),
+                &[],
+                &[],
+                &[],
+                &BTreeMap::new(),
+            ),
+            build()
+        );
+    }
+
+    #[test]
+    fn section_names() {
+        assert_eq!(section_name(Path::new("README.md")), "/");
+        assert_eq!(section_name(Path::new("guides/README.md")), "guides/");
+        assert_eq!(section_name(Path::new("guides/deep/page.md")), "guides/");
+    }
+}
@@ -0,0 +1,143 @@
+use std::cell::Cell;
+use std::io::IsTerminal;
+use std::panic::{self, AssertUnwindSafe};
+
+use bunt::termcolor::{ColorChoice, StandardStream};
+
+/// When to color what Docgen prints, from the `--color` flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Only when printing to a terminal, and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    /// Whether to print in color, given whether the `NO_COLOR` environment
+    /// variable is set and whether standard output is a terminal. Only
+    /// `auto` looks at either of them.
+    pub fn colors(self, no_color: bool, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => !no_color && is_terminal,
+        }
+    }
+}
+
+/// Standard output of this process, in color if `mode` says so. Every
+/// command prints through one of these.
+pub fn stdout(mode: ColorMode) -> StandardStream {
+    StandardStream::stdout(color_choice(mode, std::io::stdout().is_terminal()))
+}
+
+/// Standard error of this process, in color if `mode` says so
+pub fn stderr(mode: ColorMode) -> StandardStream {
+    StandardStream::stderr(color_choice(mode, std::io::stderr().is_terminal()))
+}
+
+fn color_choice(mode: ColorMode, is_terminal: bool) -> ColorChoice {
+    // An empty NO_COLOR doesn't count, see https://no-color.org
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+    if mode.colors(no_color, is_terminal) {
+        ColorChoice::Always
+    } else {
+        ColorChoice::Never
+    }
+}
+
+thread_local! {
+    /// Set while running code whose panics are caught and reported in some
+    /// other way
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Prints panics to standard error like the other errors of the CLI, in
+/// color if `mode` says so, instead of with the default hook. Panics caught
+/// with `catch_unwind_quietly` aren't printed.
+pub fn set_panic_hook(mode: ColorMode) {
+    panic::set_hook(Box::new(move |info| {
+        if CATCHING_PANICS.with(Cell::get) {
+            return;
+        }
+
+        let mut out = stderr(mode);
+        let _ = bunt::writeln!(
+            out,
+            "{$red}ERROR:{/$} Docgen crashed, which is a bug.\n\n{}",
+            info
+        );
+    }));
+}
+
+/// Runs `f`, catching a panic without printing it, for panics that are
+/// reported some other way, like a page that could not be parsed
+pub fn catch_unwind_quietly<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    let catching = CATCHING_PANICS.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.with(|c| c.set(catching));
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        // (mode, NO_COLOR, terminal, colors)
+        let cases = [
+            (ColorMode::Auto, false, true, true),
+            (ColorMode::Auto, false, false, false),
+            (ColorMode::Auto, true, true, false),
+            (ColorMode::Auto, true, false, false),
+            (ColorMode::Always, false, true, true),
+            (ColorMode::Always, false, false, true),
+            (ColorMode::Always, true, true, true),
+            (ColorMode::Always, true, false, true),
+            (ColorMode::Never, false, true, false),
+            (ColorMode::Never, false, false, false),
+            (ColorMode::Never, true, true, false),
+            (ColorMode::Never, true, false, false),
+        ];
+
+        for (mode, no_color, is_terminal, expected) in cases {
+            assert_eq!(
+                mode.colors(no_color, is_terminal),
+                expected,
+                "{:?} with NO_COLOR {} and a terminal {}",
+                mode,
+                no_color,
+                is_terminal
+            );
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(ColorMode::parse("auto"), Some(ColorMode::Auto));
+        assert_eq!(ColorMode::parse("always"), Some(ColorMode::Always));
+        assert_eq!(ColorMode::parse("never"), Some(ColorMode::Never));
+        assert_eq!(ColorMode::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn catches_panics_quietly() {
+        assert_eq!(catch_unwind_quietly(|| 1).unwrap(), 1);
+        assert!(catch_unwind_quietly(|| panic!("unparseable")).is_err());
+        assert!(!CATCHING_PANICS.with(Cell::get));
+    }
+}
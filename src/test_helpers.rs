@@ -0,0 +1,57 @@
+//! Fixtures shared by the tests of the crate.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde_yaml::Value;
+
+use crate::frontmatter::Frontmatter;
+use crate::markdown::parser::ParseOptions;
+use crate::Document;
+
+/// A page with the frontmatter, with its URLs starting at the root of the
+/// site
+pub fn page(path: &str, content: &str, frontmatter: &[(&str, &str)]) -> Document {
+    let mut options = ParseOptions::default();
+    options.url_root = "/".to_string();
+
+    page_with_options(path, content, frontmatter, options)
+}
+
+/// A page with the frontmatter, rendered with the options
+pub fn page_with_options(
+    path: &str,
+    content: &str,
+    frontmatter: &[(&str, &str)],
+    options: ParseOptions,
+) -> Document {
+    let frontmatter = frontmatter
+        .iter()
+        .map(|(k, v)| (k.to_string(), Value::from(*v)))
+        .collect::<Frontmatter>();
+
+    Document::with_options(
+        Path::new(path),
+        content.to_string(),
+        Some(frontmatter),
+        options,
+        SystemTime::now(),
+    )
+}
+
+/// A project in the temp directory with the files written into it, at paths
+/// relative to its root. The name keeps tests that run at the same time
+/// apart. Tests remove the project when they are done with it.
+pub fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("docgen-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    root
+}
@@ -0,0 +1,65 @@
+use regex::Regex;
+
+use crate::warning::Warning;
+use crate::Document;
+
+lazy_static! {
+    static ref TODO: Regex = Regex::new(r"\b(TODO|FIXME)\b").unwrap();
+}
+
+/// Finds the TODO and FIXME comments left in the Markdown of the documents,
+/// one warning per comment.
+pub fn check(root: &[Document]) -> Vec<Warning> {
+    root.iter()
+        .flat_map(|doc| {
+            doc.comments()
+                .iter()
+                .filter(|comment| TODO.is_match(comment))
+                .map(move |comment| {
+                    Warning::new(
+                        doc.original_path(),
+                        format!("Comment left in the page: <!-- {} -->", comment),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_helpers::page;
+    use std::path::Path;
+
+    #[test]
+    fn reports_todo_and_fixme_comments() {
+        let root = vec![
+            page(
+                "README.md",
+                indoc! {"
+                    <!-- TODO: rewrite this section -->
+
+                    # Welcome
+
+                    Some <!-- FIXME --> text, and <!-- a note -->.
+                "},
+                &[],
+            ),
+            page("guide.md", "# Guide\n\n<!-- Todos are fine -->\n", &[]),
+        ];
+
+        assert_eq!(
+            check(&root),
+            vec![
+                Warning::new(
+                    Path::new("README.md"),
+                    "Comment left in the page: <!-- TODO: rewrite this section -->"
+                ),
+                Warning::new(
+                    Path::new("README.md"),
+                    "Comment left in the page: <!-- FIXME -->"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bunt::termcolor::StandardStream;
+
+use crate::Result;
+
+/// Where the development server puts the warnings of the last build, for
+/// the overlay on every page to show. Release builds never have it.
+pub static WARNINGS_FILE: &str = "__docgen/warnings.json";
+
+/// A problem found while building the site that does not stop the build,
+/// but that the user should know about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// The path of the file the warning is about, relative to the docs folder
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new<S: Into<String>>(path: &Path, message: S) -> Self {
+        Warning {
+            path: path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} : {}", self.path.display(), self.message)
+    }
+}
+
+/// The messages of the warnings about each of the pages, by the path of the
+/// page relative to the docs folder, with `/` as separator. Warnings about
+/// other files, like the config, aren't about any page and are left out.
+pub fn by_page(warnings: &[Warning], pages: &[&Path]) -> BTreeMap<String, Vec<String>> {
+    let mut by_page = BTreeMap::<String, Vec<String>>::new();
+
+    for warning in warnings {
+        if pages.contains(&warning.path.as_path()) {
+            by_page
+                .entry(warning.path.to_string_lossy().replace('\\', "/"))
+                .or_default()
+                .push(warning.message.clone());
+        }
+    }
+
+    by_page
+}
+
+/// Prints the warnings under a single WARNING heading. Prints nothing if
+/// there are no warnings.
+pub fn print(out: &mut StandardStream, warnings: &[Warning]) -> Result<()> {
+    if warnings.is_empty() {
+        return Ok(());
+    }
+
+    bunt::writeln!(out, "{$bold}{$yellow}WARNING{/$}{/$}")?;
+    for warning in warnings {
+        writeln!(out, "\t{}", warning)?;
+    }
+    writeln!(out)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warnings_are_grouped_by_page() {
+        let warnings = vec![
+            Warning::new(Path::new("README.md"), "Has no title"),
+            Warning::new(Path::new("docgen.yaml"), "Unknown key `colour`"),
+            Warning::new(Path::new("guides/setup.md"), "Links to #install"),
+            Warning::new(Path::new("README.md"), "Is empty"),
+            Warning::new(Path::new("guides"), "Has no README"),
+        ];
+        let pages = [Path::new("README.md"), Path::new("guides/setup.md")];
+
+        let by_page = by_page(&warnings, &pages);
+
+        assert_eq!(
+            by_page.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    String::from("README.md"),
+                    vec![String::from("Has no title"), String::from("Is empty")]
+                ),
+                (
+                    String::from("guides/setup.md"),
+                    vec![String::from("Links to #install")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_without_warnings_are_left_out() {
+        let warnings = vec![Warning::new(Path::new("docgen.yaml"), "Unknown key")];
+
+        assert!(by_page(&warnings, &[Path::new("README.md")]).is_empty());
+    }
+}
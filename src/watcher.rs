@@ -1,32 +1,205 @@
-use std::path::PathBuf;
-use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 use crossbeam_channel::Sender;
 use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher as NotifyWatcher};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::config::Config;
+use crate::docs_finder;
+use crate::terminal::{self, ColorMode};
+
+/// What kind of file a watched change was to, which decides how much of the
+/// site has to be rebuilt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// A page, a `_defaults.yaml` or an `.order` file changed, a directory
+    /// of pages was created, moved or deleted, or content outside the docs
+    /// directory that pages pull in from `watch_paths` changed
+    MarkdownChanged(PathBuf),
+    /// A file in the `_include` directory changed
+    IncludeChanged(PathBuf),
+    /// The docgen.yaml file changed
+    ConfigChanged,
+    /// Any other file in the docs directory, which is not part of the site
+    Other(PathBuf),
+}
+
+impl Change {
+    pub fn classify(path: PathBuf, docs_dir: &Path, config_path: &Path) -> Self {
+        if path == config_path {
+            Change::ConfigChanged
+        } else if path.starts_with(docs_dir.join("_include")) {
+            Change::IncludeChanged(path)
+        } else if path.extension() == Some(OsStr::new("md"))
+            || path.file_name() == Some(OsStr::new(docs_finder::DEFAULTS_FILE))
+            || path.file_name() == Some(OsStr::new(docs_finder::ORDER_FILE))
+            || !path.starts_with(docs_dir)
+            || is_directory(&path)
+        {
+            Change::MarkdownChanged(path)
+        } else {
+            Change::Other(path)
+        }
+    }
+
+    /// The changed file, if it is not the docgen.yaml file
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Change::MarkdownChanged(path) | Change::IncludeChanged(path) | Change::Other(path) => {
+                Some(path)
+            }
+            Change::ConfigChanged => None,
+        }
+    }
+}
 
 pub struct Watcher {
     paths: Vec<PathBuf>,
-    channel: Sender<(PathBuf, String)>,
+    docs_dir: PathBuf,
+    config_path: PathBuf,
+    channel: Sender<(Change, String)>,
+    /// The only file to report changes to, see `for_file`
+    file: Option<PathBuf>,
+    /// Scan for changes this often instead of asking the file system for
+    /// them, see `Poller`
+    poll_interval: Option<Duration>,
+    color: ColorMode,
+    /// Set to stop watching, see `spawn`
+    stop: Arc<AtomicBool>,
 }
 
+/// How often changes are polled for when the file system can't be watched
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 impl Watcher {
-    pub fn new(paths: Vec<PathBuf>, channel: Sender<(PathBuf, String)>) -> Self {
-        Watcher { paths, channel }
+    pub fn new(
+        paths: Vec<PathBuf>,
+        docs_dir: &Path,
+        config_path: &Path,
+        channel: Sender<(Change, String)>,
+        poll_interval: Option<Duration>,
+        color: ColorMode,
+    ) -> Self {
+        // Notify reports canonical paths
+        let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+
+        Watcher {
+            paths,
+            docs_dir: canonical(docs_dir),
+            config_path: canonical(config_path),
+            channel,
+            file: None,
+            poll_interval,
+            color,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Watches everything the site is built from, see `paths_for`
+    pub fn for_config(config: &Config, channel: Sender<(Change, String)>) -> Self {
+        Self::new(
+            Self::paths_for(config),
+            config.docs_dir(),
+            config.config_path(),
+            channel,
+            config.poll_interval(),
+            config.color_mode(),
+        )
+    }
+
+    /// The docs directory, the config file, the directories of the sources
+    /// and the `watch_paths` of the config
+    pub fn paths_for(config: &Config) -> Vec<PathBuf> {
+        let mut paths = vec![
+            config.docs_dir().to_path_buf(),
+            config.config_path().to_path_buf(),
+        ];
+        paths.extend(config.mounts().iter().map(|mount| mount.dir.clone()));
+        paths.extend(config.watch_paths().iter().cloned());
+        paths
+    }
+
+    /// Whether a watcher for the config watches the same paths in the same
+    /// way as one for `other`, so that it doesn't need to be restarted
+    pub fn watches_same(config: &Config, other: &Config) -> bool {
+        Self::paths_for(config) == Self::paths_for(other)
+            && config.poll_interval() == other.poll_interval()
+    }
+
+    /// Watches a single file. Its directory is watched instead, without the
+    /// directories below it, since many editors save by replacing the file.
+    pub fn for_file(path: &Path, channel: Sender<(Change, String)>, color: ColorMode) -> Self {
+        let file = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let dir = file.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+        Watcher {
+            paths: vec![dir.clone()],
+            docs_dir: dir,
+            config_path: PathBuf::new(),
+            channel,
+            file: Some(file),
+            poll_interval: None,
+            color,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Runs the watcher on a thread of its own. It stops once the returned
+    /// flag is set, e.g. to watch other paths after the config changed.
+    pub fn spawn(self) -> Arc<AtomicBool> {
+        let stop = Arc::clone(&self.stop);
+        thread::Builder::new()
+            .name("watcher".into())
+            .spawn(move || self.run())
+            .unwrap();
+        stop
     }
 
     pub fn run(self) {
+        if let Some(interval) = self.poll_interval {
+            return self.poll(interval);
+        }
+
         let (tx, rx) = channel();
-        let mut watcher = watcher(tx, Duration::from_secs(1)).unwrap();
+        let mode = if self.file.is_some() {
+            RecursiveMode::NonRecursive
+        } else {
+            RecursiveMode::Recursive
+        };
 
-        for path in &self.paths {
-            if path.exists() {
-                watcher.watch(path, RecursiveMode::Recursive).unwrap();
+        // Some file systems, like network shares, can't be watched, and
+        // their changes are polled for instead
+        let watcher = watcher(tx, Duration::from_secs(1)).and_then(|mut watcher| {
+            for path in self.paths.iter().filter(|path| path.exists()) {
+                watcher.watch(path, mode)?;
             }
-        }
+            Ok(watcher)
+        });
+        let _watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                bunt::writeln!(
+                    terminal::stdout(self.color),
+                    "{$yellow}Could not watch for changes ({}), checking for them every {} \
+                     seconds instead{/$}",
+                    e,
+                    DEFAULT_POLL_INTERVAL.as_secs()
+                )
+                .ok();
+                return self.poll(DEFAULT_POLL_INTERVAL);
+            }
+        };
 
         loop {
-            let should_continue = match rx.recv() {
+            // Wakes up now and then to see whether the watcher was stopped
+            let should_continue = match rx.recv_timeout(Duration::from_secs(1)) {
                 Ok(event) => match event {
                     DebouncedEvent::NoticeWrite(_) => true,
                     DebouncedEvent::NoticeRemove(_) => true,
@@ -34,13 +207,20 @@ impl Watcher {
                     DebouncedEvent::Write(p) => self.notify(p, "updated"),
                     DebouncedEvent::Chmod(p) => self.notify(p, "updated"),
                     DebouncedEvent::Remove(p) => self.notify(p, "deleted"),
-                    DebouncedEvent::Rename(p, new) => {
-                        self.notify(p, format!("renamed to {}", new.display()))
+                    DebouncedEvent::Rename(_, new) if self.file.as_ref() == Some(&new) => {
+                        self.notify(new, "updated")
                     }
+                    DebouncedEvent::Rename(from, to) => self.notify_rename(from, to),
                     _ => true,
                 },
+                Err(RecvTimeoutError::Timeout) => !self.stop.load(Ordering::Relaxed),
                 Err(e) => {
-                    println!("watch error: {:?}", e);
+                    bunt::writeln!(
+                        terminal::stdout(self.color),
+                        "{$yellow}watch error:{/$} {:?}",
+                        e
+                    )
+                    .ok();
                     true
                 }
             };
@@ -51,12 +231,462 @@ impl Watcher {
         }
     }
 
+    /// Scans the watched paths for changes every `interval`, until the main
+    /// thread goes away or the watcher is stopped
+    fn poll(&self, interval: Duration) {
+        let mut poller = Poller::new(self.paths.clone(), self.file.is_none());
+
+        loop {
+            thread::sleep(interval);
+            if self.stop.load(Ordering::Relaxed) {
+                return;
+            }
+
+            for (path, msg) in poller.poll() {
+                if !self.notify(path, msg) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Finds which of the watched paths the changed path lives in. The
+    /// paths are compared as canonical paths, like the ones notify reports.
+    pub fn watched_root<'a>(paths: &'a [PathBuf], changed: &Path) -> Option<&'a Path> {
+        paths
+            .iter()
+            .filter_map(|root| {
+                let canonical = root.canonicalize().unwrap_or_else(|_| root.clone());
+                changed
+                    .starts_with(&canonical)
+                    .then(|| (canonical.components().count(), root.as_path()))
+            })
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, root)| root)
+    }
+
+    /// Notifies about a renamed file or directory under the path it was
+    /// renamed to, unless only the path it came from is part of the site,
+    /// like a page that is renamed to a backup
+    fn notify_rename(&self, from: PathBuf, to: PathBuf) -> bool {
+        let to_change = Change::classify(to.clone(), &self.docs_dir, &self.config_path);
+        if self.file.is_some() || matches!(to_change, Change::Other(_)) {
+            self.notify(from, format!("renamed to {}", to.display()))
+        } else {
+            self.notify(to, format!("renamed from {}", from.display()))
+        }
+    }
+
     /// Notifies the listening end (Main thread) that there the paths
     /// being monitored have updated.
     ///
     /// Returns false if the notification could not be send, meaning
-    /// the main thread has gone away.
+    /// the main thread has gone away, or if the watcher was stopped.
     fn notify<S: Into<String>>(&self, path: PathBuf, msg: S) -> bool {
-        self.channel.send((path, msg.into())).is_ok()
+        if self.stop.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        if matches!(&self.file, Some(file) if *file != path) {
+            return true;
+        }
+
+        let change = Change::classify(path, &self.docs_dir, &self.config_path);
+
+        self.channel.send((change, msg.into())).is_ok()
+    }
+}
+
+/// Finds changes to files by scanning the watched paths and comparing the
+/// modification time and size of every file with the previous scan
+pub struct Poller {
+    paths: Vec<PathBuf>,
+    recursive: bool,
+    files: HashMap<PathBuf, (Option<SystemTime>, u64)>,
+}
+
+impl Poller {
+    pub fn new(paths: Vec<PathBuf>, recursive: bool) -> Self {
+        // Changes are classified by comparing them to canonical paths, like
+        // the ones notify reports
+        let paths = paths
+            .into_iter()
+            .map(|path| path.canonicalize().unwrap_or(path))
+            .collect();
+        let mut poller = Poller {
+            paths,
+            recursive,
+            files: HashMap::new(),
+        };
+        poller.files = poller.scan();
+        poller
+    }
+
+    /// The files that were created, updated or deleted since the last poll
+    pub fn poll(&mut self) -> Vec<(PathBuf, &'static str)> {
+        let files = self.scan();
+        let mut changes = vec![];
+
+        for (path, state) in &files {
+            match self.files.get(path) {
+                None => changes.push((path.clone(), "created")),
+                Some(old) if old != state => changes.push((path.clone(), "updated")),
+                _ => {}
+            }
+        }
+        for path in self.files.keys() {
+            if !files.contains_key(path) {
+                changes.push((path.clone(), "deleted"));
+            }
+        }
+
+        self.files = files;
+        changes.sort();
+        changes
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, (Option<SystemTime>, u64)> {
+        let depth = if self.recursive { usize::MAX } else { 1 };
+
+        self.paths
+            .iter()
+            .flat_map(|path| {
+                WalkDir::new(path)
+                    .max_depth(depth)
+                    .follow_links(true)
+                    .into_iter()
+                    .filter_entry(|entry| entry.depth() == 0 || !is_hidden(entry))
+                    .filter_map(|entry| entry.ok())
+            })
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((
+                    entry.into_path(),
+                    (metadata.modified().ok(), metadata.len()),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Whether the changed path is a directory, which may have pages in it. A
+/// deleted path can't be asked, and is taken for one when it has no
+/// extension.
+fn is_directory(path: &Path) -> bool {
+    path.is_dir() || (!path.exists() && path.extension().is_none())
+}
+
+/// Whether the entry is a directory starting with a dot, like `.git`, whose
+/// changes never need a rebuild
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_type().is_dir() && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::thread;
+
+    use crossbeam_channel::unbounded;
+
+    #[test]
+    fn notifies_changes_in_extra_paths() {
+        let root = std::env::temp_dir().join(format!("docgen-watcher-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(root.join("snippets")).unwrap();
+
+        // Notify reports canonical paths
+        let docs = root.join("docs").canonicalize().unwrap();
+        let snippets = root.join("snippets").canonicalize().unwrap();
+
+        let (snd, rcv) = unbounded();
+        let config_path = root.join("docgen.yaml");
+        let watcher = Watcher::new(
+            vec![docs.clone(), snippets.clone()],
+            &docs,
+            &config_path,
+            snd,
+            None,
+            ColorMode::Never,
+        );
+        thread::spawn(move || watcher.run());
+
+        // Give the watcher time to register the paths
+        thread::sleep(Duration::from_millis(500));
+        fs::write(snippets.join("example.rs"), "fn main() {}").unwrap();
+
+        let (change, _) = rcv.recv_timeout(Duration::from_secs(10)).unwrap();
+        let paths = [docs, snippets.clone()];
+
+        assert_eq!(change, Change::MarkdownChanged(snippets.join("example.rs")));
+        assert_eq!(
+            Watcher::watched_root(&paths, change.path().unwrap()),
+            Some(snippets.as_path())
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn single_files_are_watched_on_their_own() {
+        let root = std::env::temp_dir().join(format!("docgen-watch-file-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        fs::write(root.join("draft.md"), "# Draft").unwrap();
+
+        let (snd, rcv) = unbounded();
+        let watcher = Watcher::for_file(&root.join("draft.md"), snd, ColorMode::Never);
+        thread::spawn(move || watcher.run());
+
+        // Give the watcher time to register the paths
+        thread::sleep(Duration::from_millis(500));
+        fs::write(root.join("other.md"), "# Other").unwrap();
+        fs::write(root.join("draft.md"), "# Draft, again").unwrap();
+
+        let (change, _) = rcv.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(change, Change::MarkdownChanged(root.join("draft.md")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn polls_for_changes() {
+        let root = std::env::temp_dir().join(format!("docgen-poller-{}", std::process::id()));
+        fs::create_dir_all(root.join("guides")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("README.md"), "# Home").unwrap();
+        fs::write(root.join("guides/setup.md"), "# Setup").unwrap();
+
+        let mut poller = Poller::new(vec![root.clone()], true);
+        assert_eq!(poller.poll(), vec![]);
+
+        fs::write(root.join("guides/setup.md"), "# Setup, again").unwrap();
+        fs::write(root.join("guides/.order"), "setup.md").unwrap();
+        fs::write(root.join(".git/index"), "").unwrap();
+        fs::remove_file(root.join("README.md")).unwrap();
+
+        assert_eq!(
+            poller.poll(),
+            vec![
+                (root.join("README.md"), "deleted"),
+                (root.join("guides/.order"), "created"),
+                (root.join("guides/setup.md"), "updated"),
+            ]
+        );
+        assert_eq!(poller.poll(), vec![]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn notifies_polled_changes_within_an_interval() {
+        let root = std::env::temp_dir().join(format!("docgen-poll-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let (snd, rcv) = unbounded();
+        let interval = Duration::from_millis(100);
+        let watcher = Watcher::new(
+            vec![root.clone()],
+            &root,
+            &root.join("docgen.yaml"),
+            snd,
+            Some(interval),
+            ColorMode::Never,
+        );
+        thread::spawn(move || watcher.run());
+
+        // Give the watcher time for its first scan
+        thread::sleep(interval);
+        fs::write(root.join("page.md"), "# Page").unwrap();
+
+        let (change, msg) = rcv.recv_timeout(interval * 3).unwrap();
+        assert_eq!(change, Change::MarkdownChanged(root.join("page.md")));
+        assert_eq!(msg, "created");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn polled_changes_are_classified_by_their_canonical_path() {
+        let root = std::env::temp_dir().join(format!("docgen-poll-path-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs").join("_include")).unwrap();
+        fs::create_dir_all(root.join("snippets")).unwrap();
+        let canonical = root.canonicalize().unwrap();
+
+        // Like a docs dir given as `--config ../project/docgen.yaml`
+        let docs = root.join("snippets").join("..").join("docs");
+        let (snd, rcv) = unbounded();
+        let interval = Duration::from_millis(100);
+        let watcher = Watcher::new(
+            vec![docs.clone()],
+            &docs,
+            &root.join("docgen.yaml"),
+            snd,
+            Some(interval),
+            ColorMode::Never,
+        );
+        thread::spawn(move || watcher.run());
+
+        // Give the watcher time for its first scan
+        thread::sleep(interval);
+        fs::write(docs.join("_include").join("style.css"), "body {}").unwrap();
+
+        let received = rcv.recv_timeout(interval * 3);
+        fs::remove_dir_all(root).unwrap();
+
+        let (change, _) = received.unwrap();
+        assert_eq!(
+            change,
+            Change::IncludeChanged(canonical.join("docs").join("_include").join("style.css"))
+        );
+    }
+
+    #[test]
+    fn stopped_watchers_send_no_changes() {
+        let root = std::env::temp_dir().join(format!("docgen-poll-stop-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let (snd, rcv) = unbounded();
+        let interval = Duration::from_millis(100);
+        let watcher = Watcher::new(
+            vec![root.clone()],
+            &root,
+            &root.join("docgen.yaml"),
+            snd,
+            Some(interval),
+            ColorMode::Never,
+        );
+        let stop = watcher.spawn();
+
+        // Give the watcher time for its first scan
+        thread::sleep(interval);
+        stop.store(true, Ordering::Relaxed);
+        fs::write(root.join("page.md"), "# Page").unwrap();
+
+        let received = rcv.recv_timeout(interval * 3);
+        fs::remove_dir_all(root).unwrap();
+
+        assert!(received.is_err(), "{:?}", received);
+    }
+
+    #[test]
+    fn watched_root_prefers_the_most_specific_path() {
+        let paths = [PathBuf::from("/project"), PathBuf::from("/project/docs")];
+
+        assert_eq!(
+            Watcher::watched_root(&paths, Path::new("/project/docs/README.md")),
+            Some(Path::new("/project/docs"))
+        );
+        assert_eq!(
+            Watcher::watched_root(&paths, Path::new("/project/docgen.yaml")),
+            Some(Path::new("/project"))
+        );
+        assert_eq!(Watcher::watched_root(&paths, Path::new("/elsewhere")), None);
+    }
+
+    #[test]
+    fn watched_root_compares_canonical_paths() {
+        let root = std::env::temp_dir().join(format!("docgen-watched-root-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(root.join("snippets")).unwrap();
+        let canonical = root.canonicalize().unwrap();
+
+        // Like `watch_paths: [../snippets]` in a docgen.yaml in the docs dir
+        let paths = [
+            root.join("docs"),
+            root.join("docs").join("..").join("snippets"),
+        ];
+        let changed = canonical.join("snippets").join("example.rs");
+        let watched = Watcher::watched_root(&paths, &changed).map(Path::to_path_buf);
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(watched, Some(paths[1].clone()));
+    }
+
+    #[test]
+    fn classifies_changes_by_path() {
+        let docs = Path::new("/project/docs");
+        let config = Path::new("/project/docgen.yaml");
+        let classify = |p: &str| Change::classify(PathBuf::from(p), docs, config);
+
+        assert_eq!(classify("/project/docgen.yaml"), Change::ConfigChanged);
+        assert_eq!(
+            classify("/project/docs/guides/setup.md"),
+            Change::MarkdownChanged(PathBuf::from("/project/docs/guides/setup.md"))
+        );
+        assert_eq!(
+            classify("/project/docs/_include/style.css"),
+            Change::IncludeChanged(PathBuf::from("/project/docs/_include/style.css"))
+        );
+        assert_eq!(
+            classify("/project/docs/_include/notes.md"),
+            Change::IncludeChanged(PathBuf::from("/project/docs/_include/notes.md"))
+        );
+        assert_eq!(
+            classify("/project/docs/guides/_defaults.yaml"),
+            Change::MarkdownChanged(PathBuf::from("/project/docs/guides/_defaults.yaml"))
+        );
+        assert_eq!(
+            classify("/project/docs/guides/.order"),
+            Change::MarkdownChanged(PathBuf::from("/project/docs/guides/.order"))
+        );
+        assert_eq!(
+            classify("/project/snippets/example.rs"),
+            Change::MarkdownChanged(PathBuf::from("/project/snippets/example.rs"))
+        );
+        assert_eq!(
+            classify("/project/docs/.setup.md.swp"),
+            Change::Other(PathBuf::from("/project/docs/.setup.md.swp"))
+        );
+        // A deleted directory, which had pages in it
+        assert_eq!(
+            classify("/project/docs/guides"),
+            Change::MarkdownChanged(PathBuf::from("/project/docs/guides"))
+        );
+    }
+
+    #[test]
+    fn renames_are_classified_by_their_destination() {
+        let docs = Path::new("/project/docs");
+        let (snd, rcv) = unbounded();
+        let watcher = Watcher::new(
+            vec![docs.to_path_buf()],
+            docs,
+            Path::new("/project/docgen.yaml"),
+            snd,
+            None,
+            ColorMode::Never,
+        );
+        let mut rename = |from: &str, to: &str| {
+            assert!(watcher.notify_rename(PathBuf::from(from), PathBuf::from(to)));
+            rcv.try_recv().unwrap()
+        };
+
+        assert_eq!(
+            rename("/project/docs/notes.txt", "/project/docs/notes.md"),
+            (
+                Change::MarkdownChanged(PathBuf::from("/project/docs/notes.md")),
+                "renamed from /project/docs/notes.txt".into()
+            )
+        );
+        assert_eq!(
+            rename("/project/docs/setup.md", "/project/docs/setup.md.bak"),
+            (
+                Change::MarkdownChanged(PathBuf::from("/project/docs/setup.md")),
+                "renamed to /project/docs/setup.md.bak".into()
+            )
+        );
+        assert_eq!(
+            rename("/project/docs/guides", "/project/docs/how-to"),
+            (
+                Change::MarkdownChanged(PathBuf::from("/project/docs/how-to")),
+                "renamed from /project/docs/guides".into()
+            )
+        );
     }
 }
@@ -166,6 +166,61 @@ integration_test!(mermaid_js, |area| {
     area.assert_contains(&index, "Car]\n</div>");
 });
 
+integration_test!(mermaid_js_inside_callouts_and_tabs, |area| {
+    area.mkdir("docs");
+    area.create_config();
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        indoc! {"
+        # No diagrams here
+    "}
+        .as_bytes(),
+    );
+    area.write_file(
+        Path::new("docs").join("callout.md"),
+        indoc! {"
+        # Callout
+
+        > info A diagram
+        >
+        > ```mermaid
+        > graph TD
+        >   A --> B
+        > ```
+    "}
+        .as_bytes(),
+    );
+    area.write_file(
+        Path::new("docs").join("tabs.md"),
+        indoc! {"
+        # [Tab1](#/tab/id1)
+
+        ```mermaid
+        graph TD
+          A --> B
+        ```
+
+        # [Tab2](#/tab/id2)
+
+        Bar
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.refute_contains(
+        Path::new("site").join("index.html"),
+        "id=\"mermaid.min.js\"",
+    );
+    area.assert_contains(
+        Path::new("site").join("callout.html"),
+        "id=\"mermaid.min.js\"",
+    );
+    area.assert_contains(Path::new("site").join("tabs.html"), "id=\"mermaid.min.js\"");
+});
+
 integration_test!(search_index, |area| {
     area.mkdir("docs");
     area.create_config();
@@ -184,6 +239,90 @@ integration_test!(search_index, |area| {
     area.assert_exists(Path::new("site").join("search_index.json"));
 });
 
+integration_test!(clean_urls, |area| {
+    area.mkdir(Path::new("docs").join("guides"));
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Clean
+    base_url: https://docs.example.com
+    clean_urls: true
+    "}
+        .as_bytes(),
+    );
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Home\n\n[Setup](/guides/setup)",
+    );
+    area.write_file(
+        Path::new("docs").join("guides").join("README.md"),
+        b"# Guides",
+    );
+    area.write_file(
+        Path::new("docs").join("guides").join("setup.md"),
+        b"# Setup",
+    );
+
+    let result = area.cmd(&["build", "--release"]);
+    assert_success(&result);
+
+    area.assert_exists(Path::new("site").join("index.html"));
+    area.assert_exists(Path::new("site").join("guides").join("index.html"));
+    area.assert_exists(
+        Path::new("site")
+            .join("guides")
+            .join("setup")
+            .join("index.html"),
+    );
+    area.refute_exists(Path::new("site").join("guides").join("setup.html"));
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "href=\"/guides/setup/\"");
+
+    let search_index = Path::new("site").join("search_index.json");
+    area.assert_contains(&search_index, "/guides/setup/");
+
+    let sitemap = Path::new("site").join("sitemap.xml");
+    area.assert_contains(&sitemap, "https://docs.example.com/guides/setup/");
+});
+
+integration_test!(clean_urls_warn_about_conflicting_pages, |area| {
+    area.mkdir(Path::new("docs").join("guides"));
+    area.write_file(
+        Path::new("docgen.yaml"),
+        b"---\ntitle: Clean\nclean_urls: true\n",
+    );
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    area.write_file(Path::new("docs").join("guides.md"), b"# Guides page");
+    area.write_file(
+        Path::new("docs").join("guides").join("README.md"),
+        b"# Guides",
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(&result, "Is built to the same file as");
+});
+
+integration_test!(includes_that_only_differ_in_case_are_warned_about, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("_include"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    area.write_file(Path::new("docs").join("_include").join("Logo.png"), b"");
+    area.write_file(Path::new("docs").join("_include").join("logo.png"), b"");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        "_include/Logo.png : Has the same path as _include/logo.png when case is ignored",
+    );
+
+    let result = area.cmd(&["build", "--strict"]);
+    assert_failed(&result);
+});
+
 integration_test!(frontmatter, |area| {
     area.mkdir("docs");
     area.create_config();
@@ -247,6 +386,35 @@ integration_test!(page_nav, |area| {
     area.assert_contains(&index, "<a href=\"#end\">End</a>");
 });
 
+integration_test!(page_nav_is_nested, |area| {
+    area.mkdir("docs");
+    area.create_config();
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        indoc! {"
+        # Home
+
+        ## Installing
+
+        ### On Linux
+
+        ## Configuring
+    "}
+        .as_bytes(),
+    );
+
+    let index = Path::new("site").join("index.html");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.assert_contains(
+        &index,
+        "<a href=\"#installing\">Installing</a><ul><li class=\"page-nav-level-3\">\
+         <a href=\"#on-linux\">On Linux</a></li></ul></li>",
+    );
+});
+
 // integration_test!(missing_directory_index, |area| {
 //     area.create_config();
 //     area.mkdir(Path::new("docs").join("nested"));
@@ -330,6 +498,48 @@ integration_test!(include_folder, |area| {
     area.refute_contains(&index, "<a href=\"/_assets\">_assets</a>");
 });
 
+#[cfg(unix)]
+integration_test!(include_symlinks_outside_project_are_skipped, |area| {
+    area.create_config();
+
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
+
+    let outside = std::env::temp_dir().join("docgen-external-include");
+    std::fs::write(&outside, "secret content").unwrap();
+
+    area.mkdir(Path::new("docs").join("_include"));
+    std::os::unix::fs::symlink(
+        &outside,
+        area.path.join("docs").join("_include").join("hostname"),
+    )
+    .unwrap();
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(&result, "WARNING");
+    assert_output(&result, "_include/hostname");
+    assert_output(&result, "allow_external_includes");
+
+    area.refute_exists(Path::new("site").join("hostname"));
+
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: An Project
+    allow_external_includes: true
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    refute_output(&result, "_include/hostname");
+
+    area.assert_contains(Path::new("site").join("hostname"), "secret content");
+});
+
 integration_test!(release_mode, |area| {
     area.create_config();
     area.mkdir(Path::new("docs"));
@@ -397,165 +607,909 @@ integration_test!(include_header, |area| {
     area.refute_exists(&head);
 });
 
-// integration_test!(cache_buster, |area| {
-//     area.create_config();
-//     area.mkdir("docs");
-//     area.write_file(Path::new("docs").join("README.md"), b"# Hi");
-
-//     let result = area.cmd(&["build"]);
-//     assert_success(&result);
+integration_test!(sidebar_footer_include, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("_include"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
 
-//     let index = Path::new("site").join("index.html");
+    let index = Path::new("site").join("index.html");
 
-//     // No access to the actual timestamp, but we should be fine until unix timestamps
-//     // roll over to start with the number 2.
-//     //
-//     // Famous last words ofc...
-//     area.assert_contains(&index, "docgen-style.css?v=1");
-// });
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    area.refute_contains(&index, "sidebar-footer");
 
-integration_test!(base_path, |area| {
-    area.create_config();
-    area.mkdir("docs");
-    area.write_file(Path::new("docs").join("README.md"), b"[link](/foo)");
-    area.write_file(Path::new("docs").join("foo.md"), b"[link](/)");
     area.write_file(
-        Path::new("docgen.yaml"),
-        indoc! {"
-    ---
-    title: Base Path
-    base_path: /docs/
-    "}
-        .as_bytes(),
+        Path::new("docs")
+            .join("_include")
+            .join("_sidebar_footer.html"),
+        b"<a href=\"https://status.example.com\">Status</a><script>alert(1)</script>",
     );
 
     let result = area.cmd(&["build"]);
     assert_success(&result);
-    println!("{}", std::str::from_utf8(&result.stdout).unwrap());
-
-    let index = area.path.join("site/index.html");
 
-    area.refute_contains(&index, "<a href=\"/\">");
-    area.refute_contains(&index, "<a href='/'>");
-
-    area.refute_contains(&index, "<a href=\"/foo\">");
-    area.assert_contains(&index, "<a href=\"/docs/foo\">");
+    // Below the navigation and the theme toggle, still in the sidebar
+    let html = std::fs::read_to_string(area.path.join(&index)).unwrap();
+    let sidebar_start = html.find("<div class=\"sidebar-left\">").unwrap();
+    let sidebar_end = html.find("<div class=\"docgen-content\">").unwrap();
+    let sidebar = &html[sidebar_start..sidebar_end];
+    assert!(sidebar.ends_with(
+        "</svg></span><div class=\"sidebar-footer\">\
+         <a href=\"https://status.example.com\">Status</a></div></div>"
+    ));
+    assert!(sidebar.find("</nav>") < sidebar.find("sidebar-footer"));
+
+    area.refute_contains(&index, "alert(1)");
+    area.refute_exists(Path::new("site").join("_sidebar_footer.html"));
 });
 
-integration_test!(base_path_with_custom_navigation, |area| {
-    area.create_config();
-    area.mkdir("docs");
-    area.write_file(Path::new("docs").join("README.md"), b"[link](/other)");
-    area.write_file(Path::new("docs").join("other.md"), b"[link](/)");
+integration_test!(content_header_and_footer, |area| {
     area.write_file(
         Path::new("docgen.yaml"),
-        indoc! {"
-    ---
-    title: Base Path
-    base_path: /docs/
-    navigation:
-        - path: other.md
-    "}
-        .as_bytes(),
+        b"---\ntitle: Blocks\ncontent_header: |\n  ## Beta notice\n\n  This is a **beta**.\n\
+          content_footer: Edited by the docs team.\n",
+    );
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Home\n\nWelcome home.",
+    );
+    area.write_file(
+        Path::new("docs").join("plain.md"),
+        b"---\ncontent_header: false\ncontent_footer: false\n---\n# Plain\n\nNothing else.",
     );
 
     let result = area.cmd(&["build"]);
     assert_success(&result);
-    println!("{}", std::str::from_utf8(&result.stdout).unwrap());
 
-    let index = area.path.join("site/index.html");
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(
+        &index,
+        "<div class=\"content-header\"><h2 id=\"beta-notice\">Beta notice</h2>\n<p>This is a <strong>beta</strong>.</p>\n</div>",
+    );
+    area.assert_contains(
+        &index,
+        "<div class=\"content-footer\"><p>Edited by the docs team.</p>\n</div>",
+    );
+    area.assert_contains(&index, "<a href=\"#home\">Home</a>");
+    area.refute_contains(&index, "<a href=\"#beta-notice\">");
 
-    area.refute_contains(&index, "<a href=\"/\">");
-    area.refute_contains(&index, "<a href='/'>");
+    let html = std::fs::read_to_string(area.path.join(&index)).unwrap();
+    assert!(html.find("content-header") < html.find("Welcome home."));
+    assert!(html.find("Welcome home.") < html.find("content-footer"));
 
-    area.refute_contains(&index, "<a href=\"/other\">");
-    area.assert_contains(&index, "<a href=\"/docs/other\">");
+    let plain = Path::new("site").join("plain.html");
+    area.assert_contains(&plain, "Nothing else.");
+    area.refute_contains(&plain, "content-header");
+    area.refute_contains(&plain, "content-footer");
 });
 
-integration_test!(base_path_with_logo, |area| {
-    area.create_config();
-    area.mkdir(Path::new("docs").join("_include").join("assets"));
-    area.write_file(Path::new("docs").join("README.md"), b"[link](/foo)");
-    area.write_file(Path::new("docs").join("foo.md"), b"# Foo");
-    // Create a fake logo
+integration_test!(head_include_references_are_checked, |area| {
+    area.write_file(
+        Path::new("docgen.yaml"),
+        b"---\ntitle: Fonts\nbase_path: /docs/\n",
+    );
+    area.mkdir(Path::new("docs").join("_include").join("fonts"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
     area.write_file(
         Path::new("docs")
             .join("_include")
-            .join("assets")
-            .join("fake-logo.png"),
+            .join("fonts")
+            .join("brand.woff2"),
         b"",
     );
     area.write_file(
-        Path::new("docgen.yaml"),
-        indoc! {"
-    ---
-    title: Base Path
-    base_path: /docs/
-    logo: assets/fake-logo.png
-    "}
+        Path::new("docs").join("_include").join("_head.html"),
+        indoc! {r#"
+            <link rel="preload" href="/fonts/brand.woff2" as="font">
+            <link rel="preload" href="/fonts/brand-bold.woff2" as="font">
+        "#}
         .as_bytes(),
     );
 
     let result = area.cmd(&["build"]);
     assert_success(&result);
-    println!("{}", std::str::from_utf8(&result.stdout).unwrap());
-
-    let index = area.path.join("site").join("index.html");
-    area.assert_contains(&index, "/assets/fake-logo.png");
-
-    area.refute_contains(&index, "<a href=\"/\">");
-    area.refute_contains(&index, "<a href='/'>");
+    assert_output(
+        &result,
+        "_include/_head.html : line 2: href=\"/docs/fonts/brand-bold.woff2\" points to a file",
+    );
+    refute_output(&result, "brand.woff2\" points");
 });
 
-// See (Issue 18)[https://github.com/Doctave/doctave/issues/18]
-integration_test!(issue_18, |area| {
+integration_test!(robots_noindex_site, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
     area.write_file(
         Path::new("docgen.yaml"),
         indoc! {"
     ---
-    title: Test project
-    navigation:
-        - path: another_file.md
+    title: Staging
+    robots: noindex
     "}
         .as_bytes(),
     );
-    area.mkdir("docs");
-
-    area.write_file(
-        Path::new("docs").join("README.md"),
-        indoc! {"# A test file"}.as_bytes(),
-    );
-    area.write_file(
-        Path::new("docs").join("another_file.md"),
-        indoc! {"# A second test file"}.as_bytes(),
-    );
 
     let result = area.cmd(&["build"]);
     assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "<meta name=\"robots\" content=\"noindex\">");
+
+    let robots = Path::new("site").join("robots.txt");
+    area.assert_contains(&robots, "User-agent: *\nDisallow: /\n");
 });
 
-integration_test!(broken_link_detection, |area| {
+integration_test!(robots_noindex_page, |area| {
     area.create_config();
-    area.mkdir("docs");
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
     area.write_file(
-        Path::new("docs").join("README.md"),
+        Path::new("docs").join("hidden.md"),
         indoc! {"
+        ---
+        noindex: true
+        ---
 
-        [Road to nowhere](/nope)
+        # Hidden
     "}
         .as_bytes(),
     );
 
     let result = area.cmd(&["build"]);
-    assert_failed(&result);
+    assert_success(&result);
 
-    let stdout = std::str::from_utf8(&result.stdout).unwrap();
+    let hidden = Path::new("site").join("hidden.html");
+    area.assert_contains(&hidden, "<meta name=\"robots\" content=\"noindex\">");
 
-    println!("{}", stdout);
+    let index = Path::new("site").join("index.html");
+    area.refute_contains(&index, "<meta name=\"robots\"");
 
-    assert!(stdout.contains("Detected broken internal links"));
-    assert!(stdout.contains("/nope"));
-    assert!(stdout.contains("Road to nowhere"));
+    let robots = Path::new("site").join("robots.txt");
+    area.assert_contains(&robots, "Allow: /");
+});
+
+integration_test!(robots_references_sitemap, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Production
+    base_url: https://docs.example.com
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build", "--release"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.refute_contains(&index, "<meta name=\"robots\"");
+
+    let robots = Path::new("site").join("robots.txt");
+    area.assert_contains(&robots, "User-agent: *\nAllow: /\n");
+    area.assert_contains(&robots, "Sitemap: https://docs.example.com/sitemap.xml");
+});
+
+integration_test!(sitemap_leaves_out_noindex_pages, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
+    area.write_file(Path::new("docs").join("setup.md"), b"# Setup");
+    area.write_file(
+        Path::new("docs").join("hidden.md"),
+        b"---\nnoindex: true\n---\n# Hidden",
+    );
+    area.write_file(
+        Path::new("docgen.yaml"),
+        b"---\ntitle: Production\nbase_url: https://docs.example.com\n",
+    );
+
+    let result = area.cmd(&["build", "--release"]);
+    assert_success(&result);
+
+    let sitemap = Path::new("site").join("sitemap.xml");
+    area.assert_contains(&sitemap, "https://docs.example.com/setup");
+    area.refute_contains(&sitemap, "https://docs.example.com/hidden");
+});
+
+integration_test!(
+    base_path_and_base_url_can_be_given_on_the_command_line,
+    |area| {
+        area.mkdir(Path::new("docs").join("guides"));
+        area.write_file(
+            Path::new("docgen.yaml"),
+            indoc! {"
+    ---
+    title: Production
+    base_path: /docs/
+    base_url: https://docs.example.com
+    "}
+            .as_bytes(),
+        );
+        area.write_file(
+            Path::new("docs").join("README.md"),
+            b"# Hi\n\n[Setup](/guides/setup)",
+        );
+        area.write_file(
+            Path::new("docs").join("guides").join("setup.md"),
+            b"# Setup",
+        );
+
+        let result = area.cmd(&[
+            "build",
+            "--release",
+            "--base-path",
+            "/preview/pr-1234/",
+            "--base-url",
+            "https://preview.example.com",
+        ]);
+        assert_success(&result);
+
+        let index = Path::new("site").join("index.html");
+        area.assert_contains(&index, "href=\"/preview/pr-1234/guides/setup\"");
+        area.refute_contains(&index, "href=\"/docs/");
+
+        let sitemap = Path::new("site").join("sitemap.xml");
+        area.assert_contains(
+            &sitemap,
+            "https://preview.example.com/preview/pr-1234/guides/setup",
+        );
+        area.refute_contains(&sitemap, "docs.example.com");
+
+        let robots = Path::new("site").join("robots.txt");
+        area.assert_contains(
+            &robots,
+            "Sitemap: https://preview.example.com/preview/pr-1234/sitemap.xml",
+        );
+    }
+);
+
+integration_test!(
+    base_path_on_the_command_line_must_end_with_a_slash,
+    |area| {
+        area.create_config();
+        area.mkdir("docs");
+        area.write_file(Path::new("docs").join("README.md"), b"# Hi");
+
+        let result = area.cmd(&["build", "--base-path", "/preview/pr-1234"]);
+        assert_failed(&result);
+        assert_output(
+            &result,
+            "Base path must end with /. Got `/preview/pr-1234`.",
+        );
+    }
+);
+
+integration_test!(analytics_only_in_release, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Hi");
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Analytics
+    analytics:
+      plausible:
+        domain: docs.example.com
+    "}
+        .as_bytes(),
+    );
+
+    let snippet = "<script defer data-domain=\"docs.example.com\" \
+                   src=\"https://plausible.io/js/script.js\"></script>";
+    let index = Path::new("site").join("index.html");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    area.refute_contains(&index, "plausible.io");
+
+    let result = area.cmd(&["build", "--release"]);
+    assert_success(&result);
+    area.assert_contains(&index, snippet);
+});
+
+integration_test!(smart_punctuation_frontmatter_override, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Smart
+    smart_punctuation: true
+    "}
+        .as_bytes(),
+    );
+    area.write_file(Path::new("docs").join("README.md"), b"It's \"smart\"");
+    area.write_file(
+        Path::new("docs").join("straight.md"),
+        indoc! {"
+        ---
+        smart_punctuation: false
+        ---
+
+        It's \"straight\"
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "It’s “smart”");
+
+    let straight = Path::new("site").join("straight.html");
+    area.assert_contains(&straight, "It's \"straight\"");
+});
+
+integration_test!(number_headings_frontmatter_override, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"## Overview");
+    area.write_file(
+        Path::new("docs").join("spec.md"),
+        indoc! {"
+        ---
+        number_headings: true
+        ---
+
+        # Spec
+
+        ## Overview
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.refute_contains(&index, "heading-number");
+
+    let spec = Path::new("site").join("spec.html");
+    area.assert_contains(
+        &spec,
+        "<h2 id=\"overview\"><span class=\"heading-number\">1.</span> Overview</h2>",
+    );
+    area.assert_contains(&spec, "1. Overview</a>");
+});
+
+integration_test!(edit_links_can_be_overridden_or_hidden, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Edit links
+    edit_root: https://github.com/acme/docs/edit/main
+    "}
+        .as_bytes(),
+    );
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    area.write_file(
+        Path::new("docs").join("api.md"),
+        b"---\nedit_url: https://github.com/acme/api/edit/main/openapi.yaml\n---\n\n# API",
+    );
+    area.write_file(
+        Path::new("docs").join("changelog.md"),
+        b"---\nedit: false\n---\n\n# Changelog",
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let home = Path::new("site").join("index.html");
+    area.assert_contains(
+        &home,
+        "href=\"https://github.com/acme/docs/edit/main/docs/README.md\"",
+    );
+
+    let api = Path::new("site").join("api.html");
+    area.assert_contains(
+        &api,
+        "href=\"https://github.com/acme/api/edit/main/openapi.yaml\"",
+    );
+    area.refute_contains(&api, "acme/docs/edit");
+
+    let changelog = Path::new("site").join("changelog.html");
+    area.refute_contains(&changelog, "Edit this page");
+});
+
+integration_test!(page_layouts, |area| {
+    area.mkdir(Path::new("docs"));
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Layouts
+    edit_root: https://github.com/docgen/docgen/edit/main
+    "}
+        .as_bytes(),
+    );
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    for layout in &["wide", "full", "wider"] {
+        area.write_file(
+            Path::new("docs").join(format!("{}.md", layout)),
+            format!("---\nlayout: {}\n---\n\n# A {} page", layout, layout).as_bytes(),
+        );
+    }
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        "Unknown `layout` in frontmatter: 'wider'. Expected `default`, `wide` or `full`",
+    );
+
+    for page in &["index.html", "wider.html"] {
+        let page = Path::new("site").join(page);
+        area.assert_contains(&page, "<div class=\"container\">");
+        area.assert_contains(&page, "<div class=\"sidebar-left\">");
+        area.assert_contains(&page, "<div class=\"sidebar-right\">");
+        area.assert_contains(&page, "On this page");
+    }
+
+    let wide = Path::new("site").join("wide.html");
+    area.assert_contains(&wide, "<div class=\"container layout-wide\">");
+    area.assert_contains(&wide, "<div class=\"sidebar-left\">");
+    area.refute_contains(&wide, "sidebar-right");
+    area.refute_contains(&wide, "On this page");
+    area.assert_contains(&wide, "Edit this page");
+
+    let full = Path::new("site").join("full.html");
+    area.assert_contains(&full, "<div class=\"container layout-full\">");
+    area.refute_contains(&full, "sidebar-left");
+    area.refute_contains(&full, "menu-toggle-switch");
+    area.refute_contains(&full, "sidebar-right");
+    area.refute_contains(&full, "On this page");
+    area.assert_contains(&full, "Edit this page");
+});
+
+integration_test!(related_pages, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs"));
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Home\n\n[Setup](/setup.html)",
+    );
+    area.write_file(Path::new("docs").join("setup.md"), b"# Setup");
+    area.write_file(
+        Path::new("docs").join("hidden.md"),
+        indoc! {"
+        ---
+        related_pages: false
+        ---
+
+        # Hidden
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let setup = Path::new("site").join("setup.html");
+    area.assert_contains(&setup, "class=\"related-pages\"");
+    area.assert_contains(&setup, "href=\"/\"");
+
+    let hidden = Path::new("site").join("hidden.html");
+    area.refute_contains(&hidden, "class=\"related-pages\"");
+
+    let link_graph = Path::new("site").join("link_graph.json");
+    area.assert_contains(&link_graph, "{\"from\":\"/\",\"to\":\"/setup\"}");
+});
+
+// integration_test!(cache_buster, |area| {
+//     area.create_config();
+//     area.mkdir("docs");
+//     area.write_file(Path::new("docs").join("README.md"), b"# Hi");
+
+//     let result = area.cmd(&["build"]);
+//     assert_success(&result);
+
+//     let index = Path::new("site").join("index.html");
+
+//     // No access to the actual timestamp, but we should be fine until unix timestamps
+//     // roll over to start with the number 2.
+//     //
+//     // Famous last words ofc...
+//     area.assert_contains(&index, "docgen-style.css?v=1");
+// });
+
+integration_test!(base_path, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"[link](/foo)");
+    area.write_file(Path::new("docs").join("foo.md"), b"[link](/)");
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Base Path
+    base_path: /docs/
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    println!("{}", std::str::from_utf8(&result.stdout).unwrap());
+
+    let index = area.path.join("site/index.html");
+
+    area.refute_contains(&index, "<a href=\"/\">");
+    area.refute_contains(&index, "<a href='/'>");
+
+    area.refute_contains(&index, "<a href=\"/foo\">");
+    area.assert_contains(&index, "<a href=\"/docs/foo\">");
+});
+
+integration_test!(base_path_with_custom_navigation, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"[link](/other)");
+    area.write_file(Path::new("docs").join("other.md"), b"[link](/)");
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Base Path
+    base_path: /docs/
+    navigation:
+        - path: other.md
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    println!("{}", std::str::from_utf8(&result.stdout).unwrap());
+
+    let index = area.path.join("site/index.html");
+
+    area.refute_contains(&index, "<a href=\"/\">");
+    area.refute_contains(&index, "<a href='/'>");
+
+    area.refute_contains(&index, "<a href=\"/other\">");
+    area.assert_contains(&index, "<a href=\"/docs/other\">");
+});
+
+integration_test!(base_path_with_logo, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("_include").join("assets"));
+    area.write_file(Path::new("docs").join("README.md"), b"[link](/foo)");
+    area.write_file(Path::new("docs").join("foo.md"), b"# Foo");
+    // Create a fake logo
+    area.write_file(
+        Path::new("docs")
+            .join("_include")
+            .join("assets")
+            .join("fake-logo.png"),
+        b"",
+    );
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Base Path
+    base_path: /docs/
+    logo: assets/fake-logo.png
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    println!("{}", std::str::from_utf8(&result.stdout).unwrap());
+
+    let index = area.path.join("site").join("index.html");
+    area.assert_contains(&index, "/assets/fake-logo.png");
+
+    area.refute_contains(&index, "<a href=\"/\">");
+    area.refute_contains(&index, "<a href='/'>");
+});
+
+integration_test!(base_path_prefixes_links_everywhere, |area| {
+    area.mkdir(Path::new("docs").join("guides"));
+    area.mkdir(Path::new("docs").join("_include").join("assets"));
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Home\n\n[Setup](/guides/setup) or <a href=\"/guides/setup\">the same page</a>\n",
+    );
+    area.write_file(
+        Path::new("docs").join("guides").join("setup.md"),
+        b"# Setup",
+    );
+    area.write_file(
+        Path::new("docs")
+            .join("_include")
+            .join("assets")
+            .join("logo.png"),
+        b"",
+    );
+    area.write_file(
+        Path::new("docs").join("_include").join("_head.html"),
+        b"<link rel=\"icon\" href=\"/assets/logo.png\">",
+    );
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Base Path
+    base_path: /docs/
+    logo: /assets/logo.png
+    footer:
+      groups:
+        - title: Guides
+          links:
+            - href: /guides/setup
+              title: Setup
+    "}
+        .as_bytes(),
+    );
+
+    // Fails on broken links, and on warnings about the head include
+    let result = area.cmd(&["build", "--strict"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "<a href=\"/docs/guides/setup\">Setup</a>");
+    area.assert_contains(&index, "<a href=\"/docs/guides/setup\">the same page</a>");
+    area.assert_contains(&index, "<link rel=\"icon\" href=\"/docs/assets/logo.png\">");
+    area.assert_contains(&index, "<img src=\"/docs/assets/logo.png\"");
+    area.assert_contains(&index, "href=\"/docs/guides/setup\" target=\"_self\"");
+    area.refute_contains(&index, "\"/guides/setup\"");
+    area.refute_contains(&index, "/docs/docs/");
+});
+
+integration_test!(release_builds_have_no_warnings_overlay, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs"));
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Home\n\n[Gone](/gone)",
+    );
+
+    let result = area.cmd(&["build", "--release", "--allow-failed-checks"]);
+    assert_success(&result);
+
+    area.refute_exists(Path::new("site").join("__docgen"));
+    let index = Path::new("site").join("index.html");
+    area.refute_contains(&index, "warnings.json");
+    area.refute_contains(&index, "data-source");
+});
+
+integration_test!(builds_wait_for_the_lock_of_another_build, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    // Held by this test, which is running
+    area.write_file(
+        Path::new(".docgen-build.lock"),
+        std::process::id().to_string().as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+    assert_output(&result, "Another docgen build appears to be running");
+    area.refute_exists(Path::new("site"));
+
+    let result = area.cmd(&["build", "--wait", "1"]);
+    assert_failed(&result);
+    assert_output(&result, "Gave up after waiting 1s for another docgen build");
+    area.assert_exists(Path::new(".docgen-build.lock"));
+});
+
+integration_test!(builds_take_over_stale_locks, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    // No process is ever given the largest PID
+    area.write_file(Path::new(".docgen-build.lock"), b"4294967295");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.assert_exists(Path::new("site").join("index.html"));
+    area.refute_exists(Path::new(".docgen-build.lock"));
+});
+
+integration_test!(relative_links, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("a").join("b").join("c"));
+    area.write_file(Path::new("docs").join("README.md"), b"[Deep](/a/b/c/deep)");
+    area.write_file(Path::new("docs").join("setup.md"), b"# Setup");
+    area.write_file(
+        Path::new("docs")
+            .join("a")
+            .join("b")
+            .join("c")
+            .join("deep.md"),
+        b"# Deep\n\n[Home](/) and [setup](/setup)\n\n![Cat](/assets/cat.jpg)",
+    );
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Relative links
+    base_path: /docs/
+    relative_links: true
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let deep = Path::new("site")
+        .join("a")
+        .join("b")
+        .join("c")
+        .join("deep.html");
+
+    area.assert_contains(&deep, "<a href=\"../../../\">Home</a>");
+    area.assert_contains(&deep, "<a href=\"../../../setup\">setup</a>");
+    area.assert_contains(&deep, "src=\"../../../assets/cat.jpg\"");
+    area.assert_contains(
+        &deep,
+        &format!(
+            "href=\"../../../assets/{}\"",
+            ASSETS_MAP.get("style.css").unwrap()
+        ),
+    );
+    area.assert_contains(&deep, "data-root=\"../../../\"");
+    area.refute_contains(&deep, "href=\"/");
+    area.refute_contains(&deep, "src=\"/");
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "<a href=\"./a/b/c/deep\">Deep</a>");
+    area.assert_contains(&index, "data-root=\"./\"");
+
+    let search_index = Path::new("site").join("search_index.json");
+    area.assert_contains(&search_index, "\"a/b/c/deep\"");
+});
+
+integration_test!(page_language, |area| {
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"# Inicio");
+    area.write_file(
+        Path::new("docs").join("german.md"),
+        indoc! {"
+        ---
+        lang: de
+        ---
+
+        # Deutsch
+    "}
+        .as_bytes(),
+    );
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Documentación
+    language: es
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let german = Path::new("site").join("german.html");
+    area.assert_contains(&german, "<html lang=\"de\"");
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "<html lang=\"es\"");
+    area.assert_contains(&index, "<meta name=\"description\" content=\"Inicio\">");
+});
+
+// See (Issue 18)[https://github.com/Doctave/doctave/issues/18]
+integration_test!(issue_18, |area| {
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Test project
+    navigation:
+        - path: another_file.md
+    "}
+        .as_bytes(),
+    );
+    area.mkdir("docs");
+
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        indoc! {"# A test file"}.as_bytes(),
+    );
+    area.write_file(
+        Path::new("docs").join("another_file.md"),
+        indoc! {"# A second test file"}.as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+});
+
+integration_test!(navigation_links_to_sections, |area| {
+    area.mkdir(Path::new("docs").join("reference"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+    area.write_file(
+        Path::new("docs").join("reference").join("README.md"),
+        b"# Reference",
+    );
+    area.write_file(
+        Path::new("docs").join("reference").join("api.md"),
+        indoc! {"
+        # API
+
+        ## Rate limits
+
+        ## Errors
+    "}
+        .as_bytes(),
+    );
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Sections
+    navigation:
+        - path: reference/api.md#rate-limits
+          title: Rate limits
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    area.assert_contains(
+        Path::new("site").join("index.html"),
+        "<a href=\"/reference/api#rate-limits\">Rate limits</a>",
+    );
+
+    area.write_file(
+        Path::new("docgen.yaml"),
+        indoc! {"
+    ---
+    title: Sections
+    navigation:
+        - path: reference/api.md#rate-limit
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+    assert_output(
+        &result,
+        "reference/api.md: The navigation links to #rate-limit, which is not a heading of the page. \
+         Its anchors are #api, #rate-limits, #errors",
+    );
+});
+
+integration_test!(broken_link_detection, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        indoc! {"
+
+        [Road to nowhere](/nope)
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+
+    let stdout = std::str::from_utf8(&result.stdout).unwrap();
+
+    println!("{}", stdout);
+
+    assert!(stdout.contains("Detected broken internal links"));
+    assert!(stdout.contains("/nope"));
+    assert!(stdout.contains("Road to nowhere"));
 });
 
 integration_test!(broken_link_detection_can_be_skipped_with_flag, |area| {
@@ -565,44 +1519,640 @@ integration_test!(broken_link_detection_can_be_skipped_with_flag, |area| {
         Path::new("docs").join("README.md"),
         indoc! {"
 
-        [Road to nowhere](/nope)
-    "}
+        [Road to nowhere](/nope)
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build", "--allow-failed-checks"]);
+    assert_success(&result);
+
+    let stdout = std::str::from_utf8(&result.stdout).unwrap();
+
+    println!("{}", stdout);
+
+    assert!(stdout.contains("Detected broken internal links"));
+    assert!(stdout.contains("/nope"));
+    assert!(stdout.contains("Road to nowhere"));
+});
+
+#[cfg(feature = "katex")]
+integration_test!(includes_katex_bundles, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        indoc! {"
+        # New phone, who dis?
+    "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.assert_exists(area.path.join("site").join("assets").join("fonts"));
+
+    area.assert_exists(
+        area.path
+            .join("site")
+            .join("assets")
+            .join(ASSETS_MAP.get("katex.min.css").unwrap()),
+    );
+});
+
+integration_test!(missing_docs_dir, |area| {
+    area.create_config();
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+    assert_output(&result, "Could not find the docs directory at");
+    assert_output(&result, "`docs_dir`");
+    refute_output(&result, "panicked");
+});
+
+integration_test!(empty_docs_dir, |area| {
+    area.create_config();
+    area.mkdir("docs");
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+    assert_output(&result, "Found no Markdown files in");
+    refute_output(&result, "panicked");
+});
+
+integration_test!(docs_dir_without_markdown, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.mkdir("docs/_include");
+    area.write_file("docs/notes.txt", b"Not a page");
+    area.write_file("docs/_include/logo.png", b"");
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+    assert_output(&result, "Found no Markdown files in");
+    refute_output(&result, "panicked");
+});
+
+integration_test!(dry_run_reports_changes_without_writing, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file("docs/README.md", b"# Home\n\nSome *content*");
+    area.write_file("docs/other.md", b"# Other\n\nMore content");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let site_files = || {
+        walkdir::WalkDir::new(area.path.join("site"))
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                (
+                    entry.path().to_owned(),
+                    entry.metadata().unwrap().modified().unwrap(),
+                    std::fs::read(entry.path()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let before = site_files();
+
+    area.write_file("docs/README.md", b"# Home\n\nSome **content**");
+
+    let result = area.cmd(&["build", "--dry-run", "--diff"]);
+    assert_success(&result);
+    assert_output(&result, "0 added, 0 removed,");
+    assert_output(&result, "changed\tindex.html");
+    refute_output(&result, "changed\tother.html");
+    assert_output(&result, "+<p>Some <strong>content</strong></p>");
+
+    assert_eq!(site_files(), before);
+});
+
+integration_test!(dry_run_fails_on_warnings_in_strict_mode, |area| {
+    area.write_file(
+        "docgen.yaml",
+        indoc! {r#"
+        ---
+        title: Test Project
+        frontmatter_schema:
+          keys:
+            owner:
+              required: true
+    "#}
+        .as_bytes(),
+    );
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"# Home\n");
+
+    let result = area.cmd(&["build", "--dry-run"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        "README.md : Missing required key `owner` in frontmatter",
+    );
+
+    let result = area.cmd(&["build", "--dry-run", "--strict"]);
+    assert_failed(&result);
+    assert_output(&result, " added, 0 removed, 0 modified");
+});
+
+integration_test!(oversized_pages_are_skipped, |area| {
+    area.write_file(
+        "docgen.yaml",
+        b"---\ntitle: Test Project\nmax_page_size: 1\n",
+    );
+    area.mkdir("docs");
+    area.write_file("docs/README.md", b"# Home");
+    area.write_file(
+        "docs/generated.md",
+        "| a | b |\n".repeat(200_000).as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        "generated.md : Skipped, the file is 1.9 MB, larger than the `max_page_size` of 1 MB",
+    );
+
+    area.assert_exists(Path::new("site").join("index.html"));
+    area.refute_exists(Path::new("site").join("generated.html"));
+});
+
+integration_test!(binary_pages_are_skipped, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file("docs/README.md", b"# Home");
+    area.write_file("docs/logo.md", b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR");
+    area.write_file("docs/latin1.md", b"# Caf\xe9");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(&result, "logo.md : Skipped, the file contains NUL bytes");
+    assert_output(
+        &result,
+        "latin1.md : Skipped, the file is not valid UTF-8, found an invalid byte at offset 5",
+    );
+
+    area.assert_exists(Path::new("site").join("index.html"));
+    area.refute_exists(Path::new("site").join("logo.html"));
+});
+
+integration_test!(emit_ast_writes_json_next_to_pages, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Welcome {#start}\n\nRead the [guide](/guide).\n",
+    );
+    area.write_file(Path::new("docs").join("guide.md"), b"# Guide\n");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    area.refute_exists(Path::new("site").join("index.json"));
+
+    let result = area.cmd(&["build", "--emit-ast"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.json");
+    area.assert_contains(&index, r#""version":1"#);
+    area.assert_contains(&index, r#""title":"Welcome""#);
+    area.assert_contains(
+        &index,
+        r#"{"type":"heading","level":1,"anchor":"start","children":[{"type":"text","text":"Welcome"}]}"#,
+    );
+    area.assert_contains(&index, r#"{"type":"link","url":"/guide","title":"""#);
+    area.assert_exists(Path::new("site").join("guide.json"));
+});
+
+integration_test!(emit_fragments_writes_page_content_on_its_own, |area| {
+    area.write_file(
+        "docgen.yaml",
+        b"---\ntitle: Test Project\nbase_path: /docs/\nemit_fragments: true\n",
+    );
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        indoc! {"
+            ---
+            description: Where it starts
+            ---
+
+            # Welcome
+
+            Read the [guide](/guide).
+
+            ## Next steps {#next}
+        "}
         .as_bytes(),
     );
+    area.write_file(Path::new("docs").join("guide.md"), b"# Guide\n");
 
-    let result = area.cmd(&["build", "--allow-failed-checks"]);
+    let result = area.cmd(&["build"]);
     assert_success(&result);
 
-    let stdout = std::str::from_utf8(&result.stdout).unwrap();
+    let fragment =
+        std::fs::read_to_string(area.path.join("site").join("index.fragment.html")).unwrap();
+    assert!(!fragment.contains("<html"));
+    assert!(!fragment.contains("<head"));
+    assert!(fragment.contains(r#"<a href="/docs/guide">guide</a>"#));
+
+    let page = std::fs::read_to_string(area.path.join("site").join("index.html")).unwrap();
+    assert!(page.contains(&format!(r#"<div class="docgen-content">{}"#, fragment)));
+
+    let meta: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(area.path.join("site").join("index.meta.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(meta["title"], "Welcome");
+    assert_eq!(meta["description"], "Where it starts");
+    assert_eq!(meta["uri"], "/docs/");
+    assert_eq!(meta["headings"][1]["anchor"], "next");
+    assert_eq!(meta["headings"][1]["level"], 2);
+});
 
-    println!("{}", stdout);
+integration_test!(config_can_be_given_with_a_path, |area| {
+    area.mkdir(Path::new("config").join("pages").join("_include"));
+    area.write_file(
+        Path::new("config").join("docgen.staging.yaml"),
+        b"---\ntitle: Staging\ndocs_dir: pages\nlogo: logo.png\n",
+    );
+    area.write_file(
+        Path::new("config").join("docgen.prod.yaml"),
+        b"---\ntitle: Production\ndocs_dir: pages\nlogo: missing.png\n",
+    );
+    area.write_file(
+        Path::new("config")
+            .join("pages")
+            .join("_include")
+            .join("logo.png"),
+        b"",
+    );
+    area.write_file(
+        Path::new("config").join("pages").join("README.md"),
+        b"# Staging docs\n",
+    );
 
-    assert!(stdout.contains("Detected broken internal links"));
-    assert!(stdout.contains("/nope"));
-    assert!(stdout.contains("Road to nowhere"));
+    let result = area.cmd(&["build", "--config", "config/docgen.staging.yaml"]);
+    assert_success(&result);
+
+    let index = Path::new("config").join("site").join("index.html");
+    area.assert_contains(&index, ">Staging docs</h1>");
+    area.assert_contains(&index, "<title>Staging</title>");
+
+    // The logo is looked for next to the given config, not in the current directory
+    let result = area.cmd(&["build", "--config", "config/docgen.prod.yaml"]);
+    assert_failed(&result);
+
+    let result = area.cmd(&["build", "--config", "config/docgen.missing.yaml"]);
+    assert_failed(&result);
 });
 
-#[cfg(feature = "katex")]
-integration_test!(includes_katex_bundles, |area| {
+integration_test!(config_can_be_given_with_an_env_var, |area| {
+    area.mkdir(Path::new("config").join("docs"));
+    area.write_file(
+        Path::new("config").join("docgen.staging.yaml"),
+        b"---\ntitle: Staging\n",
+    );
+    area.write_file(
+        Path::new("config").join("docs").join("README.md"),
+        b"# Staging docs\n",
+    );
+
+    let result = std::process::Command::new(area.binary())
+        .args(&["build", "--no-color"])
+        .env("DOCGEN_CONFIG", "config/docgen.staging.yaml")
+        .current_dir(&area.path)
+        .output()
+        .unwrap();
+    assert_success(&result);
+
+    area.assert_contains(
+        Path::new("config").join("site").join("index.html"),
+        ">Staging docs</h1>",
+    );
+});
+
+integration_test!(search_page, |area| {
+    area.write_file(
+        "docgen.yaml",
+        b"---\ntitle: Test Project\nbase_path: /docs/\n",
+    );
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Welcome\n\nSee the [search](/search?q=webhooks).\n",
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let search = Path::new("site").join("search.html");
+    area.assert_contains(&search, r#"<title>Search"#);
+    area.assert_contains(&search, r#"<ul id="search-page-results"></ul>"#);
+    area.assert_contains(&search, "Searching needs JavaScript");
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, r#"action="/docs/search" method="get""#);
+    area.assert_contains(&index, r#"name="q""#);
+    area.refute_contains(&index, r#"href="/docs/search">Search</a>"#);
+
+    area.refute_contains(Path::new("site").join("search_index.json"), "/docs/search");
+});
+
+integration_test!(simple_search_engine, |area| {
+    area.write_file(
+        "docgen.yaml",
+        b"---\ntitle: Test Project\nsearch:\n  engine: simple\n",
+    );
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Welcome\n\n## Webhooks\n\nSent on every deploy.\n",
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, r#"data-search-engine="simple""#);
+    area.refute_contains(&index, "elasticlunr");
+
+    let search_index: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(area.path.join("site").join("search_index.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(search_index[0]["title"], "Welcome");
+    assert_eq!(
+        search_index[0]["headings"],
+        serde_json::json!(["Welcome", "Webhooks"])
+    );
+    assert_eq!(
+        search_index[0]["body"],
+        "Welcome Webhooks Sent on every deploy."
+    );
+});
+
+integration_test!(pretty_html_indents_pages, |area| {
     area.create_config();
     area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Welcome\n\nSome *text*.\n\n```\nkeep   this\n  indented\n```\n",
+    );
+
+    let result = area.cmd(&["build", "--pretty-html"]);
+    assert_success(&result);
+
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "\n<html");
+    area.assert_contains(&index, "\n  <head>\n");
+    area.assert_contains(&index, "Some <em>text</em>.\n");
+    area.assert_contains(&index, "keep   this\n  indented\n</code></pre>");
+
+    let result = area.cmd(&["build", "--pretty-html", "--release"]);
+    assert_failed(&result);
+});
+
+integration_test!(links_starting_with_the_docs_dir_are_fixed, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("_include").join("images"));
+    area.write_file(
+        Path::new("docs")
+            .join("_include")
+            .join("images")
+            .join("logo.png"),
+        b"",
+    );
+    area.write_file(Path::new("docs").join("guide.md"), b"# Guide\n");
     area.write_file(
         Path::new("docs").join("README.md"),
         indoc! {"
-        # New phone, who dis?
+        # Welcome
+
+        ![Logo](docs/images/logo.png)
+
+        Read the [guide](/docs/guide.md#setup), or the one on
+        [GitHub](https://github.com/example/project/blob/main/docs/guide.md).
     "}
         .as_bytes(),
     );
 
     let result = area.cmd(&["build"]);
     assert_success(&result);
+    refute_output(&result, "starts with the docs directory");
 
-    area.assert_exists(area.path.join("site").join("assets").join("fonts"));
+    let index = Path::new("site").join("index.html");
+    area.assert_contains(&index, "src=\"/images/logo.png\"");
+    area.assert_contains(&index, "href=\"/guide.md#setup\"");
+    area.assert_contains(
+        &index,
+        "href=\"https://github.com/example/project/blob/main/docs/guide.md\"",
+    );
+});
 
-    area.assert_exists(
-        area.path
-            .join("site")
-            .join("assets")
-            .join(ASSETS_MAP.get("katex.min.css").unwrap()),
+integration_test!(
+    links_starting_with_the_docs_dir_without_a_target_warn,
+    |area| {
+        area.create_config();
+        area.mkdir(Path::new("docs").join("images"));
+        area.write_file(Path::new("docs").join("images").join("chart.png"), b"");
+        area.write_file(
+            Path::new("docs").join("README.md"),
+            b"# Welcome\n\n![Chart](docs/images/chart.png)\n",
+        );
+
+        let result = area.cmd(&["build"]);
+        assert_success(&result);
+        assert_output(
+            &result,
+            "Link to docs/images/chart.png starts with the docs directory",
+        );
+        assert_output(&result, "It would be /images/chart.png");
+
+        let index = Path::new("site").join("index.html");
+        area.assert_contains(&index, "src=\"docs/images/chart.png\"");
+    }
+);
+
+integration_test!(
+    links_to_a_directory_named_like_the_docs_dir_are_kept,
+    |area| {
+        area.create_config();
+        area.mkdir(Path::new("docs").join("_include").join("docs"));
+        area.write_file(
+            Path::new("docs")
+                .join("_include")
+                .join("docs")
+                .join("spec.pdf"),
+            b"",
+        );
+        area.write_file(
+            Path::new("docs").join("README.md"),
+            b"# Welcome\n\nRead the [spec](/docs/spec.pdf).\n",
+        );
+
+        let result = area.cmd(&["build"]);
+        assert_success(&result);
+        refute_output(&result, "starts with the docs directory");
+
+        let index = Path::new("site").join("index.html");
+        area.assert_contains(&index, "href=\"/docs/spec.pdf\"");
+    }
+);
+
+integration_test!(markdown_in_underscore_dirs_is_not_built, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("_include"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Welcome\n");
+    area.write_file(
+        Path::new("docs").join("_include").join("notes.md"),
+        b"# Notes\n\nFiles in here are copied into the site.\n",
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.refute_exists(Path::new("site").join("_include").join("notes.html"));
+    area.assert_contains(
+        Path::new("site").join("notes.md"),
+        "Files in here are copied into the site.",
+    );
+    area.refute_contains(Path::new("site").join("index.html"), "_include");
+    area.refute_contains(Path::new("site").join("search_index.json"), "copied");
+});
+
+integration_test!(frontmatter_schema_warnings_fail_strict_builds, |area| {
+    area.write_file(
+        "docgen.yaml",
+        indoc! {r#"
+        ---
+        title: Test Project
+        frontmatter_schema:
+          keys:
+            owner:
+              required: true
+              pattern: "^@"
+    "#}
+        .as_bytes(),
+    );
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"---\nowner: '@docs-team'\n---\n# Welcome\n",
+    );
+    area.write_file(Path::new("docs").join("guide.md"), b"# Guide\n");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        "guide.md : Missing required key `owner` in frontmatter",
+    );
+    refute_output(&result, "README.md :");
+
+    let result = area.cmd(&["build", "--strict"]);
+    assert_failed(&result);
+});
+
+integration_test!(pages_include_sections_of_other_pages, |area| {
+    area.create_config();
+    area.mkdir(Path::new("docs").join("reference"));
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Welcome\n\n{% include \"reference/errors.md#timeout-errors\" %}\n",
+    );
+    area.write_file(
+        Path::new("docs").join("reference").join("errors.md"),
+        indoc! {"
+            # Errors
+
+            ## Timeout errors
+
+            Raised when a request takes too long.
+
+            ## Network errors
+
+            Check the cable.
+        "}
+        .as_bytes(),
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.assert_contains(
+        Path::new("site").join("index.html"),
+        "Raised when a request takes too long.",
+    );
+    area.refute_contains(Path::new("site").join("index.html"), "Check the cable.");
+});
+
+integration_test!(includes_of_missing_anchors_fail_the_build, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Welcome\n\n{% include \"errors.md#timeouts\" %}\n",
+    );
+    area.write_file(
+        Path::new("docs").join("errors.md"),
+        b"# Errors\n\n## Timeout errors\n",
+    );
+
+    let result = area.cmd(&["build"]);
+    assert_failed(&result);
+    assert_output(
+        &result,
+        "README.md: Could not include errors.md#timeouts: errors.md has no heading with \
+         that anchor. Its anchors are: errors, timeout-errors",
+    );
+});
+
+integration_test!(site_report_gives_an_overview_of_the_docs, |area| {
+    area.mkdir(Path::new("docs").join("guides"));
+    area.write_file(
+        Path::new("docgen.yaml"),
+        b"---\ntitle: Report\nbase_url: https://docs.example.com\nsite_report: true\n",
+    );
+    area.write_file(
+        Path::new("docs").join("README.md"),
+        b"# Home\n\n[Setup](/guides/setup)",
+    );
+    area.write_file(
+        Path::new("docs").join("guides").join("setup.md"),
+        b"---\nreviewed: 2020-01-31\n---\n\n# Setup\n\nInstall it first.",
+    );
+
+    let result = area.cmd(&["build", "--release"]);
+    assert_success(&result);
+
+    let report = Path::new("site").join("__report").join("index.html");
+    area.assert_contains(&report, "<tr><th>Pages</th><td>2</td></tr>");
+    area.assert_contains(
+        &report,
+        "<a href=\"/guides/setup\">Setup</a> (guides/setup.md, changed on 2020-01-31)",
     );
+
+    let index = Path::new("site").join("index.html");
+    area.refute_contains(&index, "__report");
+    let search_index = Path::new("site").join("search_index.json");
+    area.refute_contains(&search_index, "__report");
+    let sitemap = Path::new("site").join("sitemap.xml");
+    area.refute_contains(&sitemap, "__report");
+});
+
+integration_test!(site_report_is_off_by_default, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"# Home");
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+
+    area.assert_exists(Path::new("site").join("index.html"));
+    area.refute_exists(Path::new("site").join("__report"));
 });
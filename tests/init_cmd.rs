@@ -128,3 +128,24 @@ integration_test!(custom_docsdir_skips_generating_if_path_exists, |area| {
     area.refute_exists(Path::new("custom_docs_dir").join("examples.md"));
     area.assert_exists(Path::new("docgen.yaml"));
 });
+
+integration_test!(kitchen_sink_builds_without_warnings, |area| {
+    let result = area.cmd(&["init", "--with-kitchen-sink"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        &format!(
+            "Created docs{}kitchen-sink.md...",
+            std::path::MAIN_SEPARATOR
+        ),
+    );
+
+    let result = area.cmd(&["build", "--strict"]);
+    assert_success(&result);
+    refute_output(&result, "WARNING");
+
+    let page = Path::new("site").join("kitchen-sink.html");
+    area.assert_contains(&page, "class=\"callout");
+    area.assert_contains(&page, "class=\"mermaid\"");
+    area.assert_contains(&page, "🚀");
+});
@@ -0,0 +1,98 @@
+#[allow(dead_code)]
+mod support;
+
+#[macro_use]
+extern crate indoc;
+
+use std::fs;
+use std::path::Path;
+use support::*;
+
+use serde_json::json;
+
+integration_test!(manifest_lists_every_link_target, |area| {
+    area.write_file(
+        "docgen.yaml",
+        b"---\ntitle: Test Project\nbase_path: /docs/\n",
+    );
+    area.mkdir(Path::new("docs").join("guides"));
+    area.mkdir(Path::new("docs").join("_include").join("images"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Welcome\n");
+    area.write_file(
+        Path::new("docs").join("guides").join("setup.md"),
+        indoc! {"
+            ---
+            title: Getting set up
+            ---
+
+            # Setup
+
+            ## Install {#get-it}
+
+            ### On Linux
+
+            #### With `apt`
+
+            ## Configure
+        "}
+        .as_bytes(),
+    );
+    area.write_file(
+        Path::new("docs")
+            .join("_include")
+            .join("images")
+            .join("logo.png"),
+        b"",
+    );
+
+    let result = area.cmd(&["manifest"]);
+    assert_success(&result);
+    assert_output(&result, "Wrote the manifest to");
+    area.refute_exists("site");
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(area.path.join("docgen-manifest.json")).unwrap())
+            .unwrap();
+
+    assert_eq!(
+        manifest,
+        json!({
+            "version": 1,
+            "pages": [
+                {
+                    "uri": "/",
+                    "source": "README.md",
+                    "title": "Welcome",
+                    "anchors": [
+                        { "anchor": "welcome", "title": "Welcome", "level": 1 }
+                    ]
+                },
+                {
+                    "uri": "/guides/setup",
+                    "source": "guides/setup.md",
+                    "title": "Getting set up",
+                    "anchors": [
+                        { "anchor": "setup", "title": "Setup", "level": 1 },
+                        { "anchor": "get-it", "title": "Install", "level": 2 },
+                        { "anchor": "on-linux", "title": "On Linux", "level": 3 },
+                        { "anchor": "with-apt", "title": "With apt", "level": 4 },
+                        { "anchor": "configure", "title": "Configure", "level": 2 }
+                    ]
+                }
+            ],
+            "assets": ["/images/logo.png"]
+        })
+    );
+});
+
+integration_test!(manifest_can_be_written_elsewhere, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"# Welcome\n");
+
+    let result = area.cmd(&["manifest", "--output", "editor.json"]);
+    assert_success(&result);
+
+    area.assert_contains("editor.json", r#""uri": "/""#);
+    area.refute_exists("docgen-manifest.json");
+});
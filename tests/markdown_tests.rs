@@ -1,4 +1,9 @@
-use docgen::markdown::parser::{MarkdownParser, ParseOptions};
+use docgen::markdown::extensions::link_rewriter::UrlType;
+use docgen::markdown::extensions::toc::{Heading, HeadingNode, SlugStyle};
+use docgen::markdown::parser::{
+    BuiltinExtension, MarkdownParser, ParseOptions, ParsedMarkdown, MAX_NESTING,
+};
+use docgen::markdown::sanitizer::{SanitizeConfig, SanitizeMode};
 use insta::*;
 
 #[macro_use]
@@ -41,6 +46,78 @@ mod test {
 
     snapshot_test!(supports_links, "\n[an link](/foo/bar)\n", |_| {});
 
+    snapshot_test!(
+        truncates_long_previews_at_a_word_boundary,
+        "# Getting started
+
+    Docgen turns a folder of *Markdown* files into a fast documentation site,
+    with search, navigation and `live reload` built in.
+
+    A second paragraph that is never part of the preview.",
+        |options: &mut ParseOptions| {
+            options.preview_length = 60;
+        }
+    );
+
+    snapshot_test!(
+        numbers_headings,
+        "# Engineering spec
+
+    ## Overview
+
+    ### Goals
+
+    ### Non-goals
+
+    ## Design {#design}
+
+    #### Storage
+
+    ### API
+
+    #### Endpoints
+
+    ## Rollout",
+        |options: &mut ParseOptions| {
+            options.number_headings = true;
+        }
+    );
+
+    #[test]
+    fn numbered_heading_anchors_ignore_the_numbers() {
+        let mut options = ParseOptions::default();
+        options.number_headings = true;
+
+        let before = MarkdownParser::new(Some(options.clone()))
+            .parse("## Overview\n\n## Design\n\n### API\n");
+        let after = MarkdownParser::new(Some(options))
+            .parse("## Background\n\n## Overview\n\n## Design\n\n### API\n");
+
+        let numbered = |parsed: &docgen::markdown::parser::ParsedMarkdown| {
+            parsed
+                .headings
+                .iter()
+                .map(|h| (h.title.clone(), h.anchor.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            numbered(&before),
+            vec![
+                ("1. Overview".to_string(), "overview".to_string()),
+                ("2. Design".to_string(), "design".to_string()),
+                ("2.1 API".to_string(), "api".to_string()),
+            ]
+        );
+        assert_eq!(
+            numbered(&after)[1..],
+            vec![
+                ("2. Overview".to_string(), "overview".to_string()),
+                ("3. Design".to_string(), "design".to_string()),
+                ("3.1 API".to_string(), "api".to_string()),
+            ]
+        );
+    }
+
     snapshot_test!(
         rewrite_link_root_path,
         "\n[an link](/foo/bar)\n",
@@ -49,6 +126,242 @@ mod test {
         }
     );
 
+    snapshot_test!(
+        renders_root_paths_relative_to_the_page,
+        "\n[an link](/foo/bar) and ![an image](/assets/cat.jpg)\n",
+        |options: &mut ParseOptions| {
+            options.url_root = "/other/root/".to_owned();
+            options.relative_root = Some("../../".to_owned());
+        }
+    );
+
+    #[test]
+    fn traces_which_extension_handled_every_event() {
+        let input = indoc! {"
+            # Diagrams
+
+            ```mermaid
+            graph TD;
+                A-->B;
+            ```
+        "};
+
+        let parsed = MarkdownParser::new(None).parse(input);
+        assert_eq!(parsed.trace, None);
+
+        let mut options = ParseOptions::default();
+        options.trace = true;
+        let parsed = MarkdownParser::new(Some(options)).parse(input);
+        let trace = parsed.trace.unwrap();
+
+        let handled_by = |start: &str| {
+            trace
+                .iter()
+                .find(|entry| entry.event.as_deref().unwrap_or("").starts_with(start))
+                .and_then(|entry| entry.handled_by)
+        };
+        assert_eq!(handled_by("Start(CodeBlock(Fenced("), Some("MermaidBlock"));
+
+        // Headings are kept as they are, and rewritten when they end
+        assert_eq!(handled_by("Start(Heading(1))"), None);
+        let heading = trace
+            .iter()
+            .find(|entry| entry.event.as_deref() == Some("End(Heading(1))"))
+            .unwrap();
+        assert!(heading
+            .outputs
+            .iter()
+            .any(|(name, output)| *name == "TableOfContents" && output.starts_with("Heading(")));
+
+        assert!(trace.windows(2).all(|w| w[0].index < w[1].index));
+        assert_eq!(trace[0].index, 0);
+    }
+
+    fn without(extension: BuiltinExtension, input: &str) -> ParsedMarkdown {
+        let mut options = ParseOptions::default();
+        options.disabled_extensions = vec![extension];
+
+        MarkdownParser::new(Some(options)).parse(input)
+    }
+
+    #[test]
+    fn tab_headings_are_linked_headings_without_tabs() {
+        let input = "# [Tab1](#/tab/id1)\n\nFoo\n\n# [Tab2](#/tab/id2)\n\nBar\n\n---\n";
+        assert!(MarkdownParser::new(None)
+            .parse(input)
+            .html
+            .contains("tabgroup"));
+
+        assert_eq!(
+            without(BuiltinExtension::Tabs, input).html,
+            "<h1 id=\"tab1\"><a href=\"#/tab/id1\">Tab1</a></h1>\n<p>Foo</p>\n\
+             <h1 id=\"tab2\"><a href=\"#/tab/id2\">Tab2</a></h1>\n<p>Bar</p>\n<hr>\n"
+        );
+    }
+
+    #[test]
+    fn emoji_shortcodes_stay_as_they_are_without_emoji() {
+        let input = "# I am :grinning:\n\nI am :grinning:.";
+        assert!(MarkdownParser::new(None)
+            .parse(input)
+            .html
+            .contains("<p>I am 😀.</p>"));
+
+        let parsed = without(BuiltinExtension::Emoji, input);
+        assert!(parsed.html.contains("<p>I am :grinning:.</p>"));
+        assert_eq!(parsed.headings[0].title, "I am :grinning:");
+    }
+
+    #[test]
+    fn callouts_are_block_quotes_without_callouts() {
+        assert_eq!(
+            without(BuiltinExtension::Callouts, "> info Heads up\n>\n> Text\n").html,
+            "<blockquote>\n<p>info Heads up</p>\n<p>Text</p>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn attributions_are_paragraphs_without_attributions() {
+        assert_eq!(
+            without(BuiltinExtension::Attributions, "> Quote\n>\n> -- Someone\n").html,
+            "<blockquote>\n<p>Quote</p>\n<p>-- Someone</p>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn diagrams_and_math_are_code_blocks_without_their_extensions() {
+        let parsed = without(BuiltinExtension::Mermaid, "```mermaid\ngraph TD;\n```\n");
+        assert_eq!(
+            parsed.html,
+            "<pre class=\"code\"><code class=\"language-mermaid\">graph TD;\n</code></pre>\n"
+        );
+        assert!(!parsed.blocks.contains("diagram"));
+
+        let parsed = without(BuiltinExtension::Math, "```math\nx^2\n```\n");
+        assert_eq!(
+            parsed.html,
+            "<pre class=\"code\"><code class=\"language-math\">x^2\n</code></pre>\n"
+        );
+        assert!(!parsed.blocks.contains("math"));
+    }
+
+    #[test]
+    fn task_items_are_list_items_without_task_lists() {
+        assert_eq!(
+            without(BuiltinExtension::TaskLists, "- [ ] Todo\n- [x] Done\n").html,
+            "<ul>\n<li>[ ] Todo</li>\n<li>[x] Done</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn code_blocks_too_large_to_highlight_are_escaped_with_a_warning() {
+        let code = (0..50)
+            .map(|i| format!("{{\"key\": \"<{}>\"}}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let input = format!("```json\n{}\n```\n", code);
+
+        let mut options = ParseOptions::default();
+        options.max_highlight_lines = 20;
+        let parsed = MarkdownParser::new(Some(options)).parse(&input);
+
+        assert!(parsed.html.starts_with(
+            "<pre class=\"code\"><code class=\"language-json\">{\"key\": \"&lt;0&gt;\"}\n"
+        ));
+        assert!(!parsed.html.contains("<span"));
+        assert_eq!(
+            parsed.warnings,
+            vec![String::from(
+                "A `json` code block of 50 lines (790 bytes) is larger than the \
+                 `max_highlight_lines` of 20, and is shown without syntax highlighting. \
+                 Split it into smaller blocks, or raise the limit"
+            )]
+        );
+
+        let mut options = ParseOptions::default();
+        options.max_highlight_size = 100;
+        let parsed = MarkdownParser::new(Some(options)).parse(&input);
+        assert!(!parsed.html.contains("<span"));
+        assert!(parsed.warnings[0].contains("larger than the `max_highlight_size` of 100 bytes"));
+    }
+
+    #[test]
+    fn code_blocks_within_the_limits_are_highlighted() {
+        let parsed = MarkdownParser::new(None).parse("```json\n{\"key\": 1}\n```\n");
+
+        assert!(parsed.html.contains("<span class=\""));
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn figure_directives_are_paragraphs_without_figures() {
+        let input = "{#logo}\n![Logo](/logo.png)\n";
+
+        assert_eq!(
+            without(BuiltinExtension::Figures, input).html,
+            "<p>{#logo}\n<img src=\"/logo.png\" alt=\"Logo\"></p>\n"
+        );
+    }
+
+    #[test]
+    fn rewrites_root_paths_in_html() {
+        let input = indoc! {r#"
+            Read <a href="/guides/setup">the setup</a> first. <img src='/assets/cat.jpg'>
+
+            <p><a href="https://example.com/">Elsewhere</a> <a href=/faq>FAQ</a> <a href="//cdn.example.com/x.js">CDN</a></p>
+        "#};
+
+        let mut options = ParseOptions::default();
+        options.url_root = String::from("/docs/");
+        let parsed = MarkdownParser::new(Some(options.clone())).parse(input);
+
+        assert!(parsed
+            .html
+            .contains("<a href=\"/docs/guides/setup\">the setup</a>"));
+        assert!(parsed.html.contains("<img src=\"/docs/assets/cat.jpg\">"));
+        assert!(parsed
+            .html
+            .contains("<a href=\"https://example.com/\">Elsewhere</a>"));
+        assert!(parsed.html.contains("<a href=\"/docs/faq\">FAQ</a>"));
+        assert!(parsed
+            .html
+            .contains("<a href=\"//cdn.example.com/x.js\">CDN</a>"));
+        assert_eq!(
+            parsed
+                .links
+                .iter()
+                .map(|link| link.url.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                UrlType::Local("/docs/guides/setup".into()),
+                UrlType::Local("/docs/faq".into()),
+            ]
+        );
+
+        options.relative_root = Some(String::from("../"));
+        let parsed = MarkdownParser::new(Some(options)).parse(input);
+
+        assert!(parsed
+            .html
+            .contains("<a href=\"../guides/setup\">the setup</a>"));
+        assert!(parsed.html.contains("<img src=\"../assets/cat.jpg\">"));
+    }
+
+    #[test]
+    fn rewrites_root_paths_after_quoted_angle_brackets() {
+        let input = r#"<a title="a>b" href="/guides/setup">Setup</a>"#;
+
+        let mut options = ParseOptions::default();
+        options.url_root = String::from("/docs/");
+        let parsed = MarkdownParser::new(Some(options)).parse(input);
+
+        assert!(
+            parsed.html.contains("href=\"/docs/guides/setup\""),
+            "{}",
+            parsed.html
+        );
+    }
+
     snapshot_test!(
         does_not_rewrite_non_absolute_urls,
         "\n[an link](https://www.google.com)\n",
@@ -136,6 +449,39 @@ mod test {
         }
     );
 
+    snapshot_test!(
+        external_links_open_in_a_new_tab,
+        "\n[an link](https://www.example.com/page)\n",
+        |options: &mut ParseOptions| {
+            options.external_links_new_tab = true;
+        }
+    );
+
+    snapshot_test!(
+        internal_links_are_not_treated_as_external,
+        "\n[an link](/foo/bar)\n",
+        |options: &mut ParseOptions| {
+            options.external_links_new_tab = true;
+        }
+    );
+
+    snapshot_test!(
+        same_host_absolute_links_are_not_treated_as_external,
+        "\n[an link](https://docs.example.com/foo)\n",
+        |options: &mut ParseOptions| {
+            options.external_links_new_tab = true;
+            options.base_url = Some("https://docs.example.com".to_owned());
+        }
+    );
+
+    snapshot_test!(
+        mailto_links_are_not_treated_as_external,
+        "\n[an email](mailto:hello@example.com)\n",
+        |options: &mut ParseOptions| {
+            options.external_links_new_tab = true;
+        }
+    );
+
     snapshot_test!(
         sanitizes_input,
         "<script>
@@ -144,6 +490,81 @@ mod test {
         |_| {}
     );
 
+    snapshot_test!(
+        smart_punctuation_converts_prose,
+        "\"Quoted\" -- it's here... --- done
+
+        Inline `\"code\" -- stays...` straight
+
+        ```
+        let s = \"code\" -- stays...;
+        ```",
+        |options: &mut ParseOptions| {
+            options.smart_punctuation = true;
+        }
+    );
+
+    snapshot_test!(
+        smart_punctuation_is_off_by_default,
+        "\"Quoted\" -- it's here...",
+        |_| {}
+    );
+
+    snapshot_test!(
+        sanitization_can_be_turned_off,
+        "<figure><img src=\"/cat.jpg\"><figcaption>A cat</figcaption></figure>
+
+        <script>alert('trusted');</script>",
+        |options: &mut ParseOptions| {
+            options.sanitize = SanitizeMode::Off;
+        }
+    );
+
+    snapshot_test!(
+        custom_sanitization_extends_the_strict_allowlist,
+        "<video src=\"/intro.mp4\" onclick=\"alert(1)\"></video>
+
+        <script>alert('I break you');</script>",
+        |options: &mut ParseOptions| {
+            let mut config = SanitizeConfig::default();
+            config.tags.push("video".to_owned());
+            config
+                .tag_attributes
+                .insert("video".to_owned(), vec!["src".to_owned()]);
+
+            options.sanitize = SanitizeMode::Custom(config);
+        }
+    );
+
+    #[test]
+    fn warns_once_about_what_sanitizing_removes() {
+        let input = indoc! {r#"
+            <iframe src="/a"></iframe> <iframe src="/b"></iframe>
+
+            <details open><summary title="a>b">More</summary></details>
+
+            <!-- <svg></svg> -->
+        "#};
+
+        let parsed = MarkdownParser::new(None).parse(input);
+
+        assert_eq!(
+            parsed.warnings,
+            vec![
+                "Sanitizing the HTML of the page removed <iframe>, open on <details>. \
+                 Keep them with `sanitize_html` in docgen.yaml"
+            ]
+        );
+    }
+
+    snapshot_test!(
+        keeps_unsanitized_html_when_asked,
+        "<script>alert('I break you');</script>",
+        |options: &mut ParseOptions| {
+            options.keep_unsanitized_html = true;
+        }
+    );
+
     snapshot_test!(
         allows_mermaid_blocks,
         "```mermaid
@@ -154,6 +575,41 @@ mod test {
         |_| {}
     );
 
+    snapshot_test!(
+        finds_mermaid_blocks_inside_callouts,
+        "> info A diagram
+        >
+        > ```mermaid
+        > graph TD;
+        >     A-->B;
+        > ```",
+        |_| {}
+    );
+
+    snapshot_test!(
+        finds_mermaid_blocks_inside_tabs,
+        "# [Tab1](#/tab/id1)
+
+        ```mermaid
+        graph TD;
+            A-->B;
+        ```
+
+        # [Tab2](#/tab/id2)
+
+        Bar
+
+        ---",
+        |_| {}
+    );
+
+    snapshot_test!(
+        blockquotes_can_start_with_an_empty_code_block,
+        "> ```mermaid
+        > ```",
+        |_| {}
+    );
+
     snapshot_test!(
         allows_code_blocks,
         "```ruby
@@ -165,6 +621,46 @@ mod test {
         |_| {}
     );
 
+    snapshot_test!(
+        highlights_lines_in_code_blocks,
+        "```rust {2,4-5}
+        /* A comment
+           over two lines */
+        fn main() {
+            println!(\"Hello\");
+        }
+        ```",
+        |_| {}
+    );
+
+    snapshot_test!(
+        shows_line_numbers_in_code_blocks,
+        "```ruby linenos
+        1 + 1
+        something < else
+        ```",
+        |_| {}
+    );
+
+    snapshot_test!(
+        highlights_lines_and_shows_line_numbers,
+        "```unknown-language {1} linenos
+        plain <text>
+        more text
+        ```",
+        |_| {}
+    );
+
+    snapshot_test!(
+        line_numbers_can_be_turned_off_per_fence,
+        "```ruby linenos=false
+        1 + 1
+        ```",
+        |options: &mut ParseOptions| {
+            options.line_numbers = true;
+        }
+    );
+
     snapshot_test!(
         gathers_a_list_of_links_on_the_page,
         "[foo](/bar)
@@ -314,6 +810,49 @@ mod test {
         |_| {}
     );
 
+    snapshot_test!(
+        blockquotes_without_an_attribution_are_left_alone,
+        "> Dashes -- like these -- stay in the quote
+        >
+        > --so do these, without a space after them
+
+        > -- A quote of nothing but an attribution",
+        |_| {}
+    );
+
+    snapshot_test!(
+        blockquotes_can_end_with_an_attribution,
+        "> The key words MUST and MUST NOT are to be interpreted as described here.
+        > -- RFC 2119",
+        |_| {}
+    );
+
+    snapshot_test!(
+        attributions_can_contain_links,
+        "> Be conservative in what you send, be liberal in what you accept.
+        >
+        > -- Jon Postel, [RFC 761](https://www.rfc-editor.org/rfc/rfc761)",
+        |_| {}
+    );
+
+    snapshot_test!(
+        attributions_work_with_smart_punctuation,
+        "> Quoted
+        > -- Someone",
+        |options: &mut ParseOptions| {
+            options.smart_punctuation = true;
+        }
+    );
+
+    snapshot_test!(
+        callouts_do_not_get_an_attribution,
+        "> info Quoting
+        >
+        > The content
+        > -- Not an attribution",
+        |_| {}
+    );
+
     snapshot_test!(
         supports_github_style_markdown_checkboxes,
         "
@@ -354,6 +893,191 @@ mod test {
         |_| {}
     );
 
+    snapshot_test!(
+        headings_can_set_their_own_anchor,
+        "## Installation {#install}
+
+        Some text
+
+        ## Installation
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        unsafe_heading_anchors_are_left_in_the_heading,
+        "## Installation {#how to install}
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        heading_anchors_after_inline_code,
+        "## The `docgen.yaml` file {#config_file}
+
+        ## Placeholders like `{#id}`
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        repeated_heading_anchors_are_numbered,
+        "## Setup
+
+        ## Setup
+
+        ## Other {#setup}
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        html_comments_are_left_out_of_the_preview_and_html,
+        "<!-- TODO: rewrite this section -->
+
+        Docgen turns Markdown into a site.
+
+        <!--
+        FIXME: a longer note,
+
+        over a few lines
+        -->
+
+        Some <!-- inline note --> text
+        ",
+        |options: &mut ParseOptions| {
+            options.sanitize = SanitizeMode::Off;
+        }
+    );
+
+    snapshot_test!(
+        task_lists_are_static_by_default,
+        "- [x] Install docgen
+        - [ ] Write the first page
+
+        Afterwards:
+
+        - Not a task
+        ",
+        |options: &mut ParseOptions| {
+            options.page_uri = Some(String::from("/onboarding"));
+        }
+    );
+
+    snapshot_test!(
+        interactive_task_lists,
+        "- [x] Install docgen
+        - [ ] Write the first page
+        ",
+        |options: &mut ParseOptions| {
+            options.page_uri = Some(String::from("/onboarding"));
+            options.interactive_checklists = true;
+        }
+    );
+
+    snapshot_test!(
+        nested_task_lists,
+        "- [ ] Set up the project
+          - [x] Install docgen
+          - [ ] Run `docgen init`
+        - [ ] Publish the site
+        ",
+        |options: &mut ParseOptions| {
+            options.page_uri = Some(String::from("/onboarding"));
+            options.interactive_checklists = true;
+        }
+    );
+
+    snapshot_test!(
+        tables_can_have_a_caption_and_an_id,
+        "Table: Results of the *benchmark* {#results}
+        | Runs | Time |
+        |------|------|
+        | 10   | 2s   |
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        images_can_have_a_caption_and_an_id,
+        "Figure: The architecture {#architecture}
+
+        ![Boxes and arrows](/images/architecture.png)
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        tables_and_images_can_have_only_an_id,
+        "{#results}
+        | Runs | Time |
+        |------|------|
+        | 10   | 2s   |
+
+        {#logo}
+        ![Logo](/images/logo.png)
+        ",
+        |_| {}
+    );
+
+    snapshot_test!(
+        captions_can_be_numbered,
+        "Table: Results {#results}
+        | Runs | Time |
+        |------|------|
+        | 10   | 2s   |
+
+        {#more-results}
+        | Runs | Time |
+        |------|------|
+        | 20   | 4s   |
+
+        Figure: The logo
+        ![Logo](/images/logo.png)
+        ",
+        |options: &mut ParseOptions| {
+            options.number_captions = true;
+        }
+    );
+
+    #[test]
+    fn tables_in_quotes_can_have_a_caption_with_reference_links() {
+        let input = indoc! {"
+            > Table: See [the setup][setup] {#steps}
+            > | Step |
+            > |------|
+            > | [Install][setup] |
+
+            [setup]: /guides/setup
+        "};
+
+        let html = MarkdownParser::new(None).parse(input).html;
+
+        assert!(
+            html.starts_with(
+                "<blockquote>\n<figure id=\"steps\" class=\"table\"><table>\
+                 <caption>See <a href=\"/guides/setup\">the setup</a></caption>"
+            ),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains("<td><a href=\"/guides/setup\">Install</a></td>"),
+            "{}",
+            html
+        );
+    }
+
+    #[test]
+    fn figure_directives_stay_text_before_other_blocks() {
+        let input = "{#results}\n\nJust a paragraph\n\nTable: not a caption\n";
+
+        assert_eq!(
+            MarkdownParser::new(None).parse(input).html,
+            "<p>{#results}</p>\n<p>Just a paragraph</p>\n<p>Table: not a caption</p>\n"
+        );
+    }
+
     // snapshot_test!(
     //     supports_markdown_source_embeds,
     // "I was working but I couldn't.
@@ -416,4 +1140,183 @@ mod test {
             assert_debug_snapshot!(parser.parse(&input));
         });
     }
+
+    #[test]
+    fn github_slug_style_matches_github_anchors() {
+        // Anchors GitHub gives these headings in a rendered README
+        let cases = [
+            ("Hello World", "hello-world"),
+            ("snake_case_heading", "snake_case_heading"),
+            ("What's new?", "whats-new"),
+            ("C++ & Rust", "c--rust"),
+            ("Double  space", "double--space"),
+            ("1. Introduction", "1-introduction"),
+            ("2024 roadmap", "2024-roadmap"),
+            ("🚀 Launch", "-launch"),
+            ("Ends with a dash -", "ends-with-a-dash--"),
+            ("The `docgen.yaml` file", "the-docgenyaml-file"),
+            ("API (v2)", "api-v2"),
+            ("Über uns", "über-uns"),
+            ("foo.bar/baz", "foobarbaz"),
+            ("Setup", "setup"),
+            ("Setup", "setup-1"),
+            ("Setup", "setup-2"),
+        ];
+
+        let input = cases
+            .iter()
+            .map(|(title, _)| format!("## {}\n", title))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut options = ParseOptions::default();
+        options.slug_style = SlugStyle::Github;
+        let parsed = MarkdownParser::new(Some(options)).parse(&input);
+
+        let anchors = parsed
+            .headings
+            .iter()
+            .map(|h| h.anchor.as_str())
+            .collect::<Vec<_>>();
+        let expected = cases.iter().map(|(_, anchor)| *anchor).collect::<Vec<_>>();
+
+        assert_eq!(anchors, expected);
+    }
+
+    /// One line per heading, indented by how deep it is nested
+    fn outline(nodes: &[HeadingNode], depth: usize, lines: &mut Vec<String>) {
+        for node in nodes {
+            lines.push(format!("{}{}", "  ".repeat(depth), node.heading.title));
+            outline(&node.children, depth + 1, lines);
+        }
+    }
+
+    #[test]
+    fn headings_are_nested_into_a_tree() {
+        let input = indoc! {"
+            ### Before the first section
+
+            # Title
+
+            ## Installing
+
+            ### On Linux
+
+            #### From source
+
+            ### On macOS
+
+            ## Configuring
+
+            #### Skipped a level
+
+            ### Back one level
+
+            ## Troubleshooting
+        "};
+
+        let parsed = MarkdownParser::new(None).parse(input);
+        let mut lines = vec![];
+        outline(&Heading::build_tree(&parsed.headings), 0, &mut lines);
+
+        assert_eq!(
+            lines,
+            vec![
+                "Before the first section",
+                "Title",
+                "  Installing",
+                "    On Linux",
+                "      From source",
+                "    On macOS",
+                "  Configuring",
+                "    Skipped a level",
+                "    Back one level",
+                "  Troubleshooting",
+            ]
+        );
+    }
+
+    #[test]
+    fn headings_of_the_same_level_stay_flat() {
+        let headings = MarkdownParser::new(None)
+            .parse("## One\n\n## Two\n\n## Three\n")
+            .headings;
+
+        let tree = Heading::build_tree(&headings);
+
+        assert_eq!(tree.len(), 3);
+        assert!(tree.iter().all(|node| node.children.is_empty()));
+        assert_eq!(
+            tree.into_iter()
+                .map(|node| node.heading)
+                .collect::<Vec<_>>(),
+            headings
+        );
+        assert!(Heading::build_tree(&[]).is_empty());
+    }
+
+    #[test]
+    fn kitchen_sink_ast() {
+        let input = include_str!("../templates/kitchen_sink.md");
+        let content = input.splitn(3, "---\n").nth(2).unwrap();
+
+        let mut options = ParseOptions::default();
+        options.url_root = String::from("/docs/");
+        options.emit_ast = true;
+        let parsed = MarkdownParser::new(Some(options)).parse(content);
+
+        insta::with_settings!({
+            description => "The content of the kitchen sink page as JSON",
+            omit_expression => true
+        }, {
+            assert_snapshot!(serde_json::to_string_pretty(&parsed.ast.unwrap()).unwrap());
+        });
+    }
+
+    #[test]
+    fn pathological_inputs_parse_without_panicking() {
+        let fixtures = std::path::Path::new("tests/fixtures/pathological");
+        let mut paths = std::fs::read_dir(fixtures)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let input = std::fs::read_to_string(&path).unwrap();
+
+            let mut options = ParseOptions::default();
+            options.emit_ast = true;
+            let parsed = MarkdownParser::new(Some(options)).parse(&input);
+
+            serde_json::to_string(&parsed.ast.unwrap())
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+
+    #[test]
+    fn markdown_that_cannot_be_parsed_is_shown_as_written() {
+        let input = "> <x<y\n";
+
+        let parsed = MarkdownParser::new(None).parse(input);
+
+        assert!(parsed.parse_error.is_some());
+        assert_eq!(
+            parsed.html,
+            "<pre class=\"code\"><code>&gt; &lt;x&lt;y\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_flattened() {
+        let depth = MAX_NESTING * 3;
+        let input = format!("{} innermost", ">".repeat(depth));
+
+        let parsed = MarkdownParser::new(None).parse(&input);
+
+        assert_eq!(parsed.html.matches("<blockquote>").count(), MAX_NESTING);
+        assert_eq!(parsed.html.matches("</blockquote>").count(), MAX_NESTING);
+        assert!(parsed.html.contains("innermost"));
+    }
 }
@@ -0,0 +1,83 @@
+#[allow(dead_code)]
+mod support;
+
+#[macro_use]
+extern crate indoc;
+
+use std::fs;
+use std::path::Path;
+use support::*;
+
+/// A page with a callout in the old syntax, and one in a code block
+fn page() -> &'static str {
+    indoc! {"
+        # Setup
+
+        {% warning Be careful %}
+        This deletes everything.
+        {% end %}
+
+        ```
+        {% info Stays as it is %}
+        {% end %}
+        ```
+    "}
+}
+
+integration_test!(migrate_rewrites_old_callouts, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), page().as_bytes());
+    area.write_file(Path::new("docs").join("other.md"), b"# Other\n");
+
+    let result = area.cmd(&["migrate"]);
+    assert_success(&result);
+    assert_output(&result, "Migrated README.md");
+    refute_output(&result, "other.md");
+
+    assert_eq!(
+        fs::read_to_string(area.path.join("docs").join("README.md")).unwrap(),
+        indoc! {"
+            # Setup
+
+            > warning Be careful
+            >
+            > This deletes everything.
+
+            ```
+            {% info Stays as it is %}
+            {% end %}
+            ```
+        "}
+    );
+});
+
+integration_test!(migrate_dry_run_shows_a_diff, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), page().as_bytes());
+
+    let result = area.cmd(&["migrate", "--dry-run"]);
+    assert_success(&result);
+    assert_output(&result, "-{% warning Be careful %}");
+    assert_output(&result, "+> warning Be careful");
+    assert_output(&result, "1 file(s) would be migrated");
+
+    assert_eq!(
+        fs::read_to_string(area.path.join("docs").join("README.md")).unwrap(),
+        page()
+    );
+});
+
+integration_test!(build_warns_about_old_callouts, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), page().as_bytes());
+
+    let result = area.cmd(&["build"]);
+    assert_success(&result);
+    assert_output(
+        &result,
+        "line 3: `{% warning Be careful %}` is the old callout syntax",
+    );
+});
@@ -62,3 +62,128 @@ integration_test!(serve_smoke_test, |area| {
 
     assert!(buf.contains("Some content"));
 });
+
+integration_test!(serve_rejects_encoded_path_traversal, |area| {
+    area.create_config();
+    area.mkdir("docs");
+    area.write_file(Path::new("docs").join("README.md"), b"# Some content");
+    let binary = area.binary();
+    let path = area.path.to_path_buf();
+    let safe_addr = get_safe_addr("127.0.0.1", 4101).expect("Failed to get new available address.");
+
+    let (sender1, receiver1) = channel::<()>();
+    let (sender2, receiver2) = channel::<()>();
+
+    std::thread::spawn(move || {
+        let mut handle = Command::new(binary)
+            .args(&["serve", "--port", safe_addr.port().to_string().as_str()])
+            .current_dir(path)
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .expect("Unable to spawn command");
+
+        sender2.send(()).unwrap();
+        receiver1.recv().unwrap();
+        handle.kill().unwrap();
+        sender2.send(()).unwrap();
+    });
+
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    receiver2.recv().unwrap();
+
+    let mut responses = vec![];
+
+    for target in [
+        "/../docgen.yaml",
+        "/%2e%2e/docgen.yaml",
+        "/%2E%2E%2Fdocgen.yaml",
+    ] {
+        let mut stream = TcpStream::connect(safe_addr).unwrap();
+
+        let request_data = format!(
+            "GET {} HTTP/1.0\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            target
+        );
+
+        stream.write_all(request_data.as_bytes()).unwrap();
+
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).unwrap();
+        responses.push(buf);
+    }
+
+    sender1.send(()).unwrap();
+    receiver2.recv().unwrap();
+
+    for response in responses {
+        assert!(
+            response.starts_with("HTTP/1.0 404") || response.starts_with("HTTP/1.1 404"),
+            "Expected a 404, got:\n{}",
+            response
+        );
+    }
+});
+
+integration_test!(serve_streams_large_files_from_disk, |area| {
+    area.write_file(
+        "docgen.yaml",
+        b"---\ntitle: Test Project\nserve_stream_size: 64\n",
+    );
+    area.mkdir(Path::new("docs").join("_include"));
+    area.write_file(Path::new("docs").join("README.md"), b"# Some content");
+    let large = (0..512 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    area.write_file(
+        Path::new("docs").join("_include").join("diagram.png"),
+        &large,
+    );
+    let binary = area.binary();
+    let path = area.path.to_path_buf();
+    let safe_addr = get_safe_addr("127.0.0.1", 4201).expect("Failed to get new available address.");
+
+    let (sender1, receiver1) = channel::<()>();
+    let (sender2, receiver2) = channel::<()>();
+
+    std::thread::spawn(move || {
+        let mut handle = Command::new(binary)
+            .args(&["serve", "--port", safe_addr.port().to_string().as_str()])
+            .current_dir(path)
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .expect("Unable to spawn command");
+
+        sender2.send(()).unwrap();
+        receiver1.recv().unwrap();
+        handle.kill().unwrap();
+        sender2.send(()).unwrap();
+    });
+
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    receiver2.recv().unwrap();
+
+    let mut stream = TcpStream::connect(safe_addr).unwrap();
+    stream
+        .write_all(b"GET /diagram.png HTTP/1.0\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    let mut buf = vec![];
+    stream.read_to_end(&mut buf).unwrap();
+
+    sender1.send(()).unwrap();
+    receiver2.recv().unwrap();
+
+    let body_start = buf.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8_lossy(&buf[..body_start]);
+    assert!(head.contains(" 200 "), "Expected a 200, got:\n{}", head);
+    assert!(head.contains("image/png"));
+    assert_eq!(&buf[body_start..], &large[..]);
+});